@@ -1,10 +1,13 @@
 mod tty_terminal;
 
+use risc_v::config::EmulatorBuilder;
 use risc_v::cpu::Xlen;
-use risc_v::Emulator;
+use risc_v::mmu::{AddressingMode, DRAM_BASE};
+use risc_v::StopReason;
 use tty_terminal::TTYTerminal;
 
 use std::fs;
+use std::io;
 
 use clap::{Parser, ValueEnum};
 
@@ -19,14 +22,66 @@ struct Cli {
     #[clap(short, long)]
     fs: Option<String>,
 
-    /// Device tree file
+    /// Device tree file. Default is generated from the machine configuration
     #[clap(short, long)]
     dtb: Option<String>,
 
+    /// Address in the main memory to place the device tree at boot.
+    /// Default is the end of the main memory
+    #[clap(long, value_parser = parse_address)]
+    dtb_address: Option<u64>,
+
     /// Enable experimental page cache optimization
     #[clap(short, long)]
     page_cache: bool,
 
+    /// Main memory size in MiB
+    #[clap(short, long, default_value_t = 128)]
+    memory: u64,
+
+    /// Main memory base address (e.g. 0x80000000)
+    #[clap(long, value_parser = parse_address)]
+    memory_base: Option<u64>,
+
+    /// Number of harts
+    #[clap(long, default_value_t = 1)]
+    harts: usize,
+
+    /// Vector register length in bits
+    #[clap(long, default_value_t = 128)]
+    vlen: usize,
+
+    /// The widest virtual memory addressing mode. Default is Sv32 for
+    /// 32-bit and Sv39 for 64-bit
+    #[clap(value_enum, long)]
+    mmu_type: Option<MmuTypeArg>,
+
+    /// Disable the Virtio block disk
+    #[clap(long)]
+    no_disk: bool,
+
+    /// Additional ELF file to load symbols from (e.g. vmlinux). Can be repeated
+    #[clap(long)]
+    symbols: Vec<String>,
+
+    /// Linux kernel Image to boot with the firmware specified as the ELF file
+    #[clap(short, long)]
+    kernel: Option<String>,
+
+    /// Initial ramdisk file for Linux
+    #[clap(short, long)]
+    initrd: Option<String>,
+
+    /// Address to place the initrd. Default is far enough from the kernel,
+    /// the middle of the main memory or 128 MiB from the base
+    /// whichever is lower
+    #[clap(long, value_parser = parse_address)]
+    initrd_address: Option<u64>,
+
+    /// Kernel command line
+    #[clap(short, long)]
+    append: Option<String>,
+
     /// The ELF file to run
     elf: String,
 }
@@ -41,15 +96,62 @@ enum XLenArg {
     Bit64,
 }
 
-fn main() -> std::io::Result<()> {
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum MmuTypeArg {
+    /// No virtual memory
+    Bare,
+    Sv32,
+    Sv39,
+    Sv48,
+    Sv57,
+}
+
+fn parse_address(value: &str) -> Result<u64, std::num::ParseIntError> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+}
+
+fn main() -> io::Result<()> {
     let cli = Cli::parse();
 
     let fs_contents = cli.fs.map(fs::read).transpose()?.unwrap_or_default();
     let dtb_contents = cli.dtb.map(fs::read).transpose()?;
     let elf_contents = fs::read(cli.elf)?;
-
-    let mut emulator = Emulator::new(Box::new(TTYTerminal::new()));
-    emulator.setup_program(elf_contents);
+    let kernel_contents = cli.kernel.map(fs::read).transpose()?;
+    let initrd_contents = cli.initrd.map(fs::read).transpose()?;
+    let symbol_contents = cli
+        .symbols
+        .iter()
+        .map(fs::read)
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let memory_size = cli.memory.saturating_mul(1024 * 1024);
+    let mut builder = EmulatorBuilder::new()
+        .memory_size(memory_size)
+        .hart_count(cli.harts)
+        .vlen(cli.vlen)
+        .mmu_type(cli.mmu_type.map(|mode| match mode {
+            MmuTypeArg::Bare => AddressingMode::None,
+            MmuTypeArg::Sv32 => AddressingMode::SV32,
+            MmuTypeArg::Sv39 => AddressingMode::SV39,
+            MmuTypeArg::Sv48 => AddressingMode::SV48,
+            MmuTypeArg::Sv57 => AddressingMode::SV57,
+        }))
+        .dtb_address(cli.dtb_address);
+    if let Some(base) = cli.memory_base {
+        builder = builder.memory_base(base);
+    }
+    if cli.no_disk {
+        builder = builder.virtio_block_disk_base(None);
+    }
+    let mut emulator = builder
+        .build(Box::new(TTYTerminal::new()))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    emulator
+        .setup_program(elf_contents)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
     if let Some(x) = cli.xlen {
         match x {
@@ -58,15 +160,69 @@ fn main() -> std::io::Result<()> {
         }
     };
 
-    emulator.setup_filesystem(fs_contents);
+    if !cli.no_disk {
+        emulator
+            .setup_filesystem(fs_contents)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
 
     if let Some(dtb) = dtb_contents {
-        emulator.setup_dtb(dtb);
+        emulator
+            .setup_dtb(dtb)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+
+    if let Some(kernel) = kernel_contents {
+        emulator
+            .setup_kernel_image(kernel)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+
+    if let Some(initrd) = initrd_contents {
+        // Leaves room for the kernel to be decompressed as QEMU does
+        let offset = (memory_size / 2).min(128 * 1024 * 1024) & !0xfff;
+        let address = cli
+            .initrd_address
+            .unwrap_or(cli.memory_base.unwrap_or(DRAM_BASE) + offset);
+        emulator
+            .setup_initrd(initrd, address)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+
+    if let Some(bootargs) = cli.append {
+        emulator
+            .setup_bootargs(&bootargs)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+
+    for content in symbol_contents {
+        emulator
+            .load_symbols(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
     }
 
     if cli.page_cache {
         emulator.enable_page_cache(true);
     }
-    emulator.run();
-    Ok(())
+    match emulator.run() {
+        StopReason::PowerOff { exit_code } => {
+            // Dropping the terminal restores the console mode
+            drop(emulator);
+            std::process::exit(exit_code as i32);
+        }
+        StopReason::FatalTrap {
+            trap_type,
+            value,
+            address,
+        } => {
+            let location = match emulator.symbolize(address) {
+                Some((name, offset)) => format!("{address:#x} ({name}+{offset:#x})"),
+                None => format!("{address:#x}"),
+            };
+            Err(io::Error::other(format!(
+                "Unhandled trap {trap_type:?} (value: {value:#x}) at {location}"
+            )))
+        }
+        _ => Ok(()),
+    }
 }
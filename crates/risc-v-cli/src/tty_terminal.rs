@@ -38,6 +38,7 @@ fn spawn_stdin_channel() -> Receiver<u8> {
     let (tx, rx) = mpsc::channel::<u8>();
     thread::spawn(move || loop {
         let ch = io::stdin()
+            .lock()
             .bytes()
             .next()
             .and_then(|result| result.ok())
@@ -0,0 +1,692 @@
+use crate::config::{
+    MachineConfig, BOOT_ROM_SIZE, CLINT_SIZE, PLIC_SIZE, RESET_VECTOR, TEST_FINISHER_SIZE,
+    UART_SIZE, VIRTIO_BLOCK_DISK_SIZE,
+};
+use crate::cpu::Xlen;
+use crate::device::boot_rom::BootRom;
+use crate::device::clint::Clint;
+use crate::device::plic::Plic;
+use crate::device::test_finisher::TestFinisher;
+use crate::device::uart::Uart;
+use crate::device::virtio_block_disk::VirtioBlockDisk;
+use crate::error::EmulatorError;
+use crate::memory::Memory;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
+use crate::terminal::Terminal;
+
+/// System bus shared by all the harts. It holds the Main memory and
+/// peripheral devices, maps physical address to them, and accesses them
+/// depending on address. [`Mmu`](crate::mmu::Mmu) of each hart accesses it
+/// with physical address after address translation.
+pub struct Bus {
+    clock: u64,
+    hart_count: usize,
+    memory: MemoryWrapper,
+    dtb: Vec<u8>,
+    /// Physical address where `dtb` is placed at boot. `None` means
+    /// the end of the main memory.
+    dtb_address: Option<u64>,
+    /// The maximum size of `dtb`, up to the end of the main memory
+    dtb_capacity: u64,
+    boot_rom: BootRom,
+    disk: VirtioBlockDisk,
+    plic: Plic,
+    clint: Clint,
+    uart: Uart,
+    test_finisher: TestFinisher,
+
+    /// Device base addresses. `None` means the device is disabled.
+    clint_base: Option<u64>,
+    plic_base: Option<u64>,
+    uart_base: Option<u64>,
+    virtio_block_disk_base: Option<u64>,
+    test_finisher_base: Option<u64>,
+}
+
+/// Devices mapped to physical address space other than main memory
+enum MmioDevice {
+    BootRom,
+    Clint,
+    Plic,
+    Uart,
+    VirtioBlockDisk,
+    TestFinisher,
+}
+
+impl Bus {
+    /// Creates a new `Bus`. Main memory and devices are mapped as `config`
+    /// describes. `config` is expected to be validated beforehand.
+    ///
+    /// # Arguments
+    /// * `terminal`
+    /// * `config`
+    pub fn new(terminal: Box<dyn Terminal>, config: &MachineConfig) -> Self {
+        // Device tree describing the configuration
+        let dtb = config
+            .generate_device_tree()
+            .map(|tree| tree.to_bytes())
+            .unwrap_or_default();
+
+        Self {
+            clock: 0,
+            hart_count: config.hart_count,
+            memory: MemoryWrapper::new(config.memory_base, config.hart_count),
+            dtb,
+            dtb_address: config.dtb_address,
+            dtb_capacity: config.get_dtb_capacity(),
+            boot_rom: BootRom::new(),
+            disk: VirtioBlockDisk::new(),
+            plic: Plic::new(config.hart_count),
+            clint: Clint::new(config.hart_count),
+            uart: Uart::new(terminal),
+            test_finisher: TestFinisher::new(),
+            clint_base: config.clint_base,
+            plic_base: config.plic_base,
+            uart_base: config.uart_base,
+            virtio_block_disk_base: config.virtio_block_disk_base,
+            test_finisher_base: config.test_finisher_base,
+        }
+    }
+
+    /// Returns the number of harts sharing the bus.
+    pub fn get_hart_count(&self) -> usize {
+        self.hart_count
+    }
+
+    /// Initializes Main memory. This method is expected to be called only once.
+    pub fn init_memory(&mut self, capacity: u64) {
+        self.memory.init(capacity);
+    }
+
+    /// Initializes Virtio block disk. This method is expected to be called only once.
+    /// Returns `Err` if the content is larger than the disk capacity.
+    ///
+    /// # Arguments
+    /// * `data` Filesystem binary content
+    pub fn init_disk(&mut self, data: Vec<u8>) -> Result<(), EmulatorError> {
+        let capacity = self.disk.capacity();
+        if data.len() as u64 > capacity {
+            return Err(EmulatorError::DiskImageTooLarge {
+                size: data.len() as u64,
+                capacity,
+            });
+        }
+        self.disk.init(data);
+        Ok(())
+    }
+
+    /// Overrides the device tree generated from the machine configuration.
+    /// Returns `Err` if the content doesn't fit into the main memory
+    /// from the device tree address.
+    ///
+    /// # Arguments
+    /// * `data` DTB binary content
+    pub fn init_dtb(&mut self, data: Vec<u8>) -> Result<(), EmulatorError> {
+        if data.len() as u64 > self.dtb_capacity {
+            return Err(EmulatorError::DtbTooLarge {
+                size: data.len() as u64,
+                capacity: self.dtb_capacity,
+            });
+        }
+        self.dtb = data;
+        Ok(())
+    }
+
+    /// Returns the device tree binary placed in the main memory at boot.
+    pub fn get_dtb(&self) -> &[u8] {
+        &self.dtb
+    }
+
+    /// Returns the physical address where the device tree is placed at boot.
+    /// By default it's the end of the main memory aligned down to the page.
+    pub fn get_dtb_address(&self) -> u64 {
+        match self.dtb_address {
+            Some(address) => address,
+            None => {
+                let room = self.memory.capacity() - self.dtb.len() as u64;
+                self.memory.base + (room & !0xfff)
+            }
+        }
+    }
+
+    /// Places the device tree in the main memory and sets up the boot ROM
+    /// jumping to `entry` with the device tree address. Called once before
+    /// harts start from the reset vector.
+    ///
+    /// # Arguments
+    /// * `xlen`
+    /// * `entry` Program entry point
+    pub fn init_boot_rom(&mut self, xlen: &Xlen, entry: u64) {
+        let dtb_address = self.get_dtb_address();
+        for (i, byte) in self.dtb.iter().enumerate() {
+            self.memory.write_byte(dtb_address + i as u64, *byte);
+        }
+        self.boot_rom.init(xlen, entry, dtb_address);
+    }
+
+    /// Returns the main memory capacity in bytes.
+    pub fn memory_capacity(&self) -> u64 {
+        self.memory.capacity()
+    }
+
+    /// Returns the main memory base address.
+    pub fn memory_base(&self) -> u64 {
+        self.memory.base
+    }
+
+    /// Runs one cycle of peripheral devices. Called once for all the harts.
+    pub fn tick(&mut self) {
+        if self.clint_base.is_some() {
+            self.clint.tick();
+        }
+        if self.virtio_block_disk_base.is_some() {
+            self.disk.tick(&mut self.memory);
+        }
+        if self.uart_base.is_some() {
+            // Bus clock is the same as CPU clock
+            self.uart.tick(self.clock);
+        }
+        if self.plic_base.is_some() {
+            let virtio_ip = self.virtio_block_disk_base.is_some() && self.disk.is_interrupting();
+            let uart_ip = self.uart_base.is_some() && self.uart.is_interrupting();
+            self.plic.tick(virtio_ip, uart_ip);
+        }
+        self.clock = self.clock.wrapping_add(1);
+    }
+
+    /// Raises the interrupts of a hart from the interrupt controllers.
+    ///
+    /// # Arguments
+    /// * `hart_id`
+    /// * `mip` CPU `mip` register of the hart
+    pub fn raise_interrupts(&mut self, hart_id: usize, mip: &mut u64) {
+        if self.clint_base.is_some() {
+            self.clint.raise_interrupts(hart_id, mip);
+        }
+        if self.plic_base.is_some() {
+            self.plic.raise_interrupts(hart_id, mip);
+        }
+    }
+
+    /// Loads a byte from main memory or peripheral devices depending on
+    /// physical address.
+    ///
+    /// # Arguments
+    /// * `p_address` Effective physical address
+    pub fn load(&mut self, p_address: u64) -> u8 {
+        if self.memory.contains(p_address, 1) {
+            return self.memory.read_byte(p_address);
+        }
+        match self.map_device(p_address) {
+            Some((MmioDevice::BootRom, offset)) => self.boot_rom.load(offset),
+            Some((MmioDevice::Clint, offset)) => self.clint.load(offset),
+            Some((MmioDevice::Plic, offset)) => self.plic.load(offset),
+            Some((MmioDevice::Uart, offset)) => self.uart.load(offset),
+            Some((MmioDevice::VirtioBlockDisk, offset)) => self.disk.load(offset),
+            Some((MmioDevice::TestFinisher, offset)) => self.test_finisher.load(offset),
+            // Accesses are checked with `is_accessible()` beforehand. Page
+            // table walks reading unmapped address see invalid entries.
+            None => 0,
+        }
+    }
+
+    /// Loads two bytes from main memory or peripheral devices depending on
+    /// physical address.
+    ///
+    /// # Arguments
+    /// * `p_address` Effective physical address
+    pub fn load_halfword(&mut self, p_address: u64) -> u16 {
+        match self.memory.contains(p_address, 2) {
+            // Fast path. Directly load main memory at a time.
+            true => self.memory.read_halfword(p_address),
+            false => {
+                let mut data = 0_u16;
+                for i in 0..2 {
+                    data |= (self.load(p_address.wrapping_add(i)) as u16) << (i * 8)
+                }
+                data
+            }
+        }
+    }
+
+    /// Loads four bytes from main memory or peripheral devices depending on
+    /// physical address.
+    ///
+    /// # Arguments
+    /// * `p_address` Effective physical address
+    pub fn load_word(&mut self, p_address: u64) -> u32 {
+        match self.memory.contains(p_address, 4) {
+            // Fast path. Directly load main memory at a time.
+            true => self.memory.read_word(p_address),
+            false => {
+                let mut data = 0_u32;
+                for i in 0..4 {
+                    data |= (self.load(p_address.wrapping_add(i)) as u32) << (i * 8)
+                }
+                data
+            }
+        }
+    }
+
+    /// Loads eight bytes from main memory or peripheral devices depending on
+    /// physical address.
+    ///
+    /// # Arguments
+    /// * `p_address` Effective physical address
+    pub fn load_doubleword(&mut self, p_address: u64) -> u64 {
+        match self.memory.contains(p_address, 8) {
+            // Fast path. Directly load main memory at a time.
+            true => self.memory.read_doubleword(p_address),
+            false => {
+                let mut data = 0_u64;
+                for i in 0..8 {
+                    data |= (self.load(p_address.wrapping_add(i)) as u64) << (i * 8)
+                }
+                data
+            }
+        }
+    }
+
+    /// Stores a byte to main memory or peripheral devices depending on
+    /// physical address.
+    ///
+    /// # Arguments
+    /// * `p_address` Effective physical address
+    /// * `value` data written
+    pub fn store(&mut self, p_address: u64, value: u8) {
+        if self.memory.contains(p_address, 1) {
+            return self.memory.write_byte(p_address, value);
+        }
+        match self.map_device(p_address) {
+            Some((MmioDevice::Clint, offset)) => self.clint.store(offset, value),
+            Some((MmioDevice::Plic, offset)) => self.plic.store(offset, value),
+            Some((MmioDevice::Uart, offset)) => self.uart.store(offset, value),
+            Some((MmioDevice::VirtioBlockDisk, offset)) => self.disk.store(offset, value),
+            Some((MmioDevice::TestFinisher, offset)) => self.test_finisher.store(offset, value),
+            // Boot ROM is read only. Accesses are checked with
+            // `is_accessible()` beforehand.
+            Some((MmioDevice::BootRom, _)) | None => {}
+        };
+    }
+
+    /// Stores two bytes to main memory or peripheral devices depending on
+    /// physical address.
+    ///
+    /// # Arguments
+    /// * `p_address` Effective physical address
+    /// * `value` data written
+    pub fn store_halfword(&mut self, p_address: u64, value: u16) {
+        match self.memory.contains(p_address, 2) {
+            // Fast path. Directly store to main memory at a time.
+            true => self.memory.write_halfword(p_address, value),
+            false => {
+                for i in 0..2 {
+                    self.store(p_address.wrapping_add(i), ((value >> (i * 8)) & 0xff) as u8);
+                }
+            }
+        }
+    }
+
+    /// Stores four bytes to main memory or peripheral devices depending on
+    /// physical address.
+    ///
+    /// # Arguments
+    /// * `p_address` Effective physical address
+    /// * `value` data written
+    pub fn store_word(&mut self, p_address: u64, value: u32) {
+        match self.memory.contains(p_address, 4) {
+            // Fast path. Directly store to main memory at a time.
+            true => self.memory.write_word(p_address, value),
+            false => {
+                for i in 0..4 {
+                    self.store(p_address.wrapping_add(i), ((value >> (i * 8)) & 0xff) as u8);
+                }
+            }
+        }
+    }
+
+    /// Stores eight bytes to main memory or peripheral devices depending on
+    /// physical address.
+    ///
+    /// # Arguments
+    /// * `p_address` Effective physical address
+    /// * `value` data written
+    pub fn store_doubleword(&mut self, p_address: u64, value: u64) {
+        match self.memory.contains(p_address, 8) {
+            // Fast path. Directly store to main memory at a time.
+            true => self.memory.write_doubleword(p_address, value),
+            false => {
+                for i in 0..8 {
+                    self.store(p_address.wrapping_add(i), ((value >> (i * 8)) & 0xff) as u8);
+                }
+            }
+        }
+    }
+
+    /// Indicates whether `width` bytes from physical address are mapped
+    /// and the device accepts the access.
+    ///
+    /// # Arguments
+    /// * `p_address` Effective physical address
+    /// * `width`
+    /// * `is_write`
+    pub fn is_accessible(&self, p_address: u64, width: u64, is_write: bool) -> bool {
+        let is_accessible = |address| match self.map_device(address) {
+            Some((MmioDevice::BootRom, _)) => !is_write,
+            Some(_) => true,
+            None => false,
+        };
+        self.memory.contains(p_address, width)
+            || (is_accessible(p_address) && is_accessible(p_address.wrapping_add(width - 1)))
+    }
+
+    /// Indicates whether `width` bytes from physical address are in
+    /// the main memory.
+    ///
+    /// # Arguments
+    /// * `p_address` Effective physical address
+    /// * `width`
+    pub fn is_main_memory(&self, p_address: u64, width: u64) -> bool {
+        self.memory.contains(p_address, width)
+    }
+
+    /// Indicates whether the main memory or a device is mapped at
+    /// physical address.
+    ///
+    /// # Arguments
+    /// * `p_address` Effective physical address
+    pub fn is_mapped(&self, p_address: u64) -> bool {
+        self.memory.contains(p_address, 1) || self.map_device(p_address).is_some()
+    }
+
+    /// Returns the device mapped at physical address and the offset
+    /// from its base address, or `None` if no device is mapped.
+    ///
+    /// # Arguments
+    /// * `p_address` Effective physical address
+    fn map_device(&self, p_address: u64) -> Option<(MmioDevice, u64)> {
+        let devices = [
+            (MmioDevice::BootRom, Some(RESET_VECTOR), BOOT_ROM_SIZE),
+            (MmioDevice::Clint, self.clint_base, CLINT_SIZE),
+            (MmioDevice::Plic, self.plic_base, PLIC_SIZE),
+            (MmioDevice::Uart, self.uart_base, UART_SIZE),
+            (
+                MmioDevice::VirtioBlockDisk,
+                self.virtio_block_disk_base,
+                VIRTIO_BLOCK_DISK_SIZE,
+            ),
+            (
+                MmioDevice::TestFinisher,
+                self.test_finisher_base,
+                TEST_FINISHER_SIZE,
+            ),
+        ];
+        for (device, base, size) in devices {
+            if let Some(base) = base {
+                if p_address >= base && p_address - base < size {
+                    return Some((device, p_address - base));
+                }
+            }
+        }
+        None
+    }
+
+    /// Registers the reservation set of a hart containing the address.
+    /// The former one of the hart is discarded.
+    ///
+    /// # Arguments
+    /// * `hart_id`
+    /// * `p_address` Effective physical address
+    pub fn reserve(&mut self, hart_id: usize, p_address: u64) {
+        self.memory.reserve(hart_id, p_address);
+    }
+
+    /// Clears the reservation set of a hart and returns whether it
+    /// contained the address.
+    ///
+    /// # Arguments
+    /// * `hart_id`
+    /// * `p_address` Effective physical address
+    pub fn take_reservation(&mut self, hart_id: usize, p_address: u64) -> bool {
+        self.memory.take_reservation(hart_id, p_address)
+    }
+
+    /// Reads `mtime` register content of CLINT
+    pub fn read_mtime(&self) -> u64 {
+        self.clint.read_mtime()
+    }
+
+    /// Returns the exit code if the guest has requested power-off
+    /// through the test finisher.
+    pub fn get_exit_code(&self) -> Option<u32> {
+        self.test_finisher.get_exit_code()
+    }
+
+    /// Returns mutable reference to `Uart`.
+    pub fn get_mut_uart(&mut self) -> &mut Uart {
+        &mut self.uart
+    }
+
+    /// Saves the memory map, main memory, device tree and device states
+    /// to a snapshot.
+    pub(crate) fn save_state(&self, writer: &mut SnapshotWriter) {
+        for value in self.get_memory_map() {
+            writer.write_option_u64(value);
+        }
+        writer.write_u64(self.clock);
+        self.memory.memory.save_state(writer);
+        for reservation in &self.memory.reservations {
+            writer.write_option_u64(*reservation);
+        }
+        writer.write_bytes(&self.dtb);
+        self.disk.save_state(writer);
+        self.plic.save_state(writer);
+        self.clint.save_state(writer);
+        self.uart.save_state(writer);
+        self.test_finisher.save_state(writer);
+        self.boot_rom.save_state(writer);
+    }
+
+    /// Restores the state saved with `save_state()`. Returns `Err` without
+    /// updating anything if the memory map differs from the saved one.
+    pub(crate) fn restore_state(
+        &mut self,
+        reader: &mut SnapshotReader,
+    ) -> Result<(), EmulatorError> {
+        for value in self.get_memory_map() {
+            if reader.read_option_u64()? != value {
+                return Err(EmulatorError::SnapshotMismatch);
+            }
+        }
+        self.clock = reader.read_u64()?;
+        self.memory.memory.restore_state(reader)?;
+        for reservation in self.memory.reservations.iter_mut() {
+            *reservation = reader.read_option_u64()?;
+        }
+        let dtb = reader.read_bytes()?;
+        if dtb.len() as u64 > self.dtb_capacity {
+            return Err(EmulatorError::InvalidSnapshot);
+        }
+        self.dtb = dtb.to_vec();
+        self.disk.restore_state(reader)?;
+        self.plic.restore_state(reader)?;
+        self.clint.restore_state(reader)?;
+        self.uart.restore_state(reader)?;
+        self.test_finisher.restore_state(reader)?;
+        self.boot_rom.restore_state(reader)?;
+        Ok(())
+    }
+
+    /// Returns the hart count, main memory base and capacity, and device
+    /// base addresses which a snapshot depends on.
+    fn get_memory_map(&self) -> [Option<u64>; 9] {
+        [
+            Some(self.hart_count as u64),
+            Some(self.memory.base),
+            Some(self.memory.capacity()),
+            self.clint_base,
+            self.plic_base,
+            self.uart_base,
+            self.virtio_block_disk_base,
+            self.test_finisher_base,
+            self.dtb_address,
+        ]
+    }
+}
+
+/// Size and alignment of a reservation set of `LR`/`SC` instructions
+const RESERVATION_SET_SIZE: u64 = 8;
+
+/// [`Memory`] wrapper. Converts physical address to the one in memory
+/// using the main memory base address and accesses [`Memory`].
+/// It also holds the reservation set of `LR`/`SC` instructions of each hart
+/// so that any write to the reserved address, from any hart or device,
+/// invalidates it.
+pub struct MemoryWrapper {
+    memory: Memory,
+    base: u64,
+    /// Physical address of the reservation set of each hart
+    reservations: Vec<Option<u64>>,
+}
+
+impl MemoryWrapper {
+    fn new(base: u64, hart_count: usize) -> Self {
+        MemoryWrapper {
+            memory: Memory::new(),
+            base,
+            reservations: vec![None; hart_count],
+        }
+    }
+
+    /// Registers the reservation set of a hart containing the address.
+    /// The former one of the hart is discarded.
+    ///
+    /// # Arguments
+    /// * `hart_id`
+    /// * `p_address` Physical address
+    pub fn reserve(&mut self, hart_id: usize, p_address: u64) {
+        self.reservations[hart_id] = Some(p_address & !(RESERVATION_SET_SIZE - 1));
+    }
+
+    /// Clears the reservation set of a hart and returns whether it
+    /// contained the address.
+    ///
+    /// # Arguments
+    /// * `hart_id`
+    /// * `p_address` Physical address
+    pub fn take_reservation(&mut self, hart_id: usize, p_address: u64) -> bool {
+        self.reservations[hart_id].take() == Some(p_address & !(RESERVATION_SET_SIZE - 1))
+    }
+
+    /// Clears the reservation sets overlapping the written bytes.
+    fn invalidate_reservations(&mut self, p_address: u64, width: u64) {
+        for reservation in self.reservations.iter_mut() {
+            if let Some(address) = *reservation {
+                if p_address < address + RESERVATION_SET_SIZE
+                    && address < p_address.wrapping_add(width)
+                {
+                    *reservation = None;
+                }
+            }
+        }
+    }
+
+    fn init(&mut self, capacity: u64) {
+        self.memory.init(capacity);
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.memory.capacity()
+    }
+
+    /// Indicates whether `width` bytes from physical address are
+    /// all in the main memory.
+    ///
+    /// # Arguments
+    /// * `p_address` Physical address
+    /// * `width`
+    pub fn contains(&self, p_address: u64, width: u64) -> bool {
+        let capacity = self.capacity();
+        p_address >= self.base && width <= capacity && p_address - self.base <= capacity - width
+    }
+
+    pub fn read_byte(&mut self, p_address: u64) -> u8 {
+        debug_assert!(
+            p_address >= self.base,
+            "Memory address must equals to or bigger than the memory base. {:X}",
+            p_address
+        );
+        self.memory.read_byte(p_address - self.base)
+    }
+
+    pub fn read_halfword(&mut self, p_address: u64) -> u16 {
+        debug_assert!(
+            p_address >= self.base && p_address.wrapping_add(1) >= self.base,
+            "Memory address must equals to or bigger than the memory base. {:X}",
+            p_address
+        );
+        self.memory.read_halfword(p_address - self.base)
+    }
+
+    pub fn read_word(&mut self, p_address: u64) -> u32 {
+        debug_assert!(
+            p_address >= self.base && p_address.wrapping_add(3) >= self.base,
+            "Memory address must equals to or bigger than the memory base. {:X}",
+            p_address
+        );
+        self.memory.read_word(p_address - self.base)
+    }
+
+    pub fn read_doubleword(&mut self, p_address: u64) -> u64 {
+        debug_assert!(
+            p_address >= self.base && p_address.wrapping_add(7) >= self.base,
+            "Memory address must equals to or bigger than the memory base. {:X}",
+            p_address
+        );
+        self.memory.read_doubleword(p_address - self.base)
+    }
+
+    pub fn write_byte(&mut self, p_address: u64, value: u8) {
+        self.invalidate_reservations(p_address, 1);
+        debug_assert!(
+            p_address >= self.base,
+            "Memory address must equals to or bigger than the memory base. {:X}",
+            p_address
+        );
+        self.memory.write_byte(p_address - self.base, value)
+    }
+
+    pub fn write_halfword(&mut self, p_address: u64, value: u16) {
+        self.invalidate_reservations(p_address, 2);
+        debug_assert!(
+            p_address >= self.base && p_address.wrapping_add(1) >= self.base,
+            "Memory address must equals to or bigger than the memory base. {:X}",
+            p_address
+        );
+        self.memory.write_halfword(p_address - self.base, value)
+    }
+
+    pub fn write_word(&mut self, p_address: u64, value: u32) {
+        self.invalidate_reservations(p_address, 4);
+        debug_assert!(
+            p_address >= self.base && p_address.wrapping_add(3) >= self.base,
+            "Memory address must equals to or bigger than the memory base. {:X}",
+            p_address
+        );
+        self.memory.write_word(p_address - self.base, value)
+    }
+
+    pub fn write_doubleword(&mut self, p_address: u64, value: u64) {
+        self.invalidate_reservations(p_address, 8);
+        debug_assert!(
+            p_address >= self.base && p_address.wrapping_add(7) >= self.base,
+            "Memory address must equals to or bigger than the memory base. {:X}",
+            p_address
+        );
+        self.memory.write_doubleword(p_address - self.base, value)
+    }
+
+    pub fn validate_address(&self, address: u64) -> bool {
+        self.memory.validate_address(address - self.base)
+    }
+}
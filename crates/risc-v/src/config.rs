@@ -0,0 +1,504 @@
+use crate::cpu::Xlen;
+use crate::device::plic::{PLIC_SOURCE_COUNT, UART_IRQ, VIRTIO_IRQ};
+use crate::error::EmulatorError;
+use crate::fdt::DeviceTree;
+use crate::mmu::{AddressingMode, DRAM_BASE};
+use crate::terminal::Terminal;
+use crate::Emulator;
+
+/// Default main memory capacity, big enough to run Linux and xv6
+pub const DEFAULT_MEMORY_SIZE: u64 = 1024 * 1024 * 128;
+
+/// Address where harts start from, the boot ROM base address
+pub const RESET_VECTOR: u64 = 0x1000;
+
+/// The maximum number of harts, limited by `mtimecmp` registers fitting
+/// in the CLINT register region
+pub const MAX_HART_COUNT: usize = 4095;
+
+/// The range of the vector register length in bits. The minimum is the one
+/// of the V extension for application processors, the maximum is the one
+/// the spec allows.
+pub const MIN_VLEN: usize = 128;
+pub const MAX_VLEN: usize = 65536;
+
+/// Sizes of MMIO register regions of the devices
+pub(crate) const BOOT_ROM_SIZE: u64 = 0x1000;
+pub(crate) const CLINT_SIZE: u64 = 0x10000;
+pub(crate) const PLIC_SIZE: u64 = 0x4000000;
+pub(crate) const UART_SIZE: u64 = 0x100;
+pub(crate) const VIRTIO_BLOCK_DISK_SIZE: u64 = 0x1000;
+pub(crate) const TEST_FINISHER_SIZE: u64 = 0x1000;
+
+/// Kernel command line in the generated device tree
+const DEFAULT_BOOTARGS: &str = "root=/dev/vda rw ttyS0";
+
+/// Frequencies in Hz advertised in the generated device tree
+const TIMEBASE_FREQUENCY: u32 = 10_000_000;
+const UART_CLOCK_FREQUENCY: u32 = 3_686_400;
+
+/// Machine configuration: main memory, peripheral devices and CPU.
+/// [`Default`] is the configuration the emulator has always run with.
+/// The device tree passed to the guest is generated from the configuration
+/// with `generate_device_tree()`, `Emulator::setup_dtb()` overrides it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MachineConfig {
+    /// Main memory base address
+    pub memory_base: u64,
+    /// Main memory capacity in bytes
+    pub memory_size: u64,
+    /// ISA string like `rv64imafdcsu`. It decides the initial XLEN and `misa`.
+    /// XLEN is updated to the one of the program in `Emulator::setup_program()`.
+    /// Instructions of M, A, F, D, C, V, Zba, Zbb, Zbc and Zbs extensions
+    /// not in the string raise illegal instruction exceptions. Supervisor
+    /// and User modes are available regardless of `s` and `u`.
+    pub isa: String,
+    /// The number of harts, from 1 to [`MAX_HART_COUNT`]. The harts share
+    /// the main memory and devices, and run one instruction each per cycle
+    /// in hart ID order.
+    pub hart_count: usize,
+    /// Vector register length in bits, VLEN, a power of two from
+    /// [`MIN_VLEN`] to [`MAX_VLEN`]
+    pub vlen: usize,
+    /// The widest virtual memory addressing mode the harts support,
+    /// advertised as `mmu-type` in the device tree. `satp` accepts the
+    /// narrower modes too. `None` is Sv32 for RV32 and Sv39 for RV64.
+    pub mmu_type: Option<AddressingMode>,
+    /// CLINT base address. `None` disables the device.
+    pub clint_base: Option<u64>,
+    /// PLIC base address. `None` disables the device.
+    pub plic_base: Option<u64>,
+    /// UART base address. `None` disables the device.
+    pub uart_base: Option<u64>,
+    /// Virtio block disk base address. `None` disables the device.
+    pub virtio_block_disk_base: Option<u64>,
+    /// Test finisher (power-off device) base address. `None` disables the device.
+    pub test_finisher_base: Option<u64>,
+    /// Physical address in the main memory where the device tree is placed
+    /// at boot. `None` places it at the end of the main memory.
+    pub dtb_address: Option<u64>,
+}
+
+impl Default for MachineConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MachineConfig {
+    /// Creates a new `MachineConfig` with the default configuration.
+    pub fn new() -> Self {
+        MachineConfig {
+            memory_base: DRAM_BASE,
+            memory_size: DEFAULT_MEMORY_SIZE,
+            isa: "rv64imafdcvsu_zba_zbb_zbc_zbs_sstc_svadu".to_string(),
+            hart_count: 1,
+            vlen: 128,
+            mmu_type: None,
+            clint_base: Some(0x02000000),
+            plic_base: Some(0x0c000000),
+            uart_base: Some(0x10000000),
+            virtio_block_disk_base: Some(0x10001000),
+            test_finisher_base: Some(0x100000),
+            dtb_address: None,
+        }
+    }
+
+    /// Validates the configuration. Returns `Err` if the ISA string is
+    /// malformed, the hart count, VLEN or the MMU type isn't supported,
+    /// the main memory and devices overlap each other, or the generated
+    /// device tree doesn't fit at the device tree address.
+    pub fn validate(&self) -> Result<(), EmulatorError> {
+        let (xlen, _) = parse_isa(&self.isa)?;
+        if let Some(mode) = self.mmu_type {
+            if !mode.is_valid_for(&xlen) {
+                return Err(EmulatorError::UnsupportedMmuType(mode));
+            }
+        }
+        if self.hart_count == 0 || self.hart_count > MAX_HART_COUNT {
+            return Err(EmulatorError::UnsupportedHartCount(self.hart_count));
+        }
+        if !self.vlen.is_power_of_two() || !(MIN_VLEN..=MAX_VLEN).contains(&self.vlen) {
+            return Err(EmulatorError::UnsupportedVlen(self.vlen));
+        }
+        if self.memory_size == 0 || self.memory_base.checked_add(self.memory_size).is_none() {
+            return Err(EmulatorError::InvalidMemoryRange {
+                base: self.memory_base,
+                size: self.memory_size,
+            });
+        }
+        let dtb_size = self.generate_device_tree()?.to_bytes().len() as u64;
+        if let Some(address) = self.dtb_address {
+            if address % 8 != 0
+                || address < self.memory_base
+                || address - self.memory_base >= self.memory_size
+            {
+                return Err(EmulatorError::InvalidDtbAddress(address));
+            }
+        }
+        let capacity = self.get_dtb_capacity();
+        if dtb_size > capacity {
+            return Err(EmulatorError::DtbTooLarge {
+                size: dtb_size,
+                capacity,
+            });
+        }
+        let mut regions = self.get_device_regions();
+        regions.push((self.memory_base, self.memory_size));
+        regions.sort();
+        for pair in regions.windows(2) {
+            let (base, size) = pair[0];
+            match base.checked_add(size) {
+                Some(end) if end <= pair[1].0 => {}
+                _ => return Err(EmulatorError::OverlappingMemoryMap { address: pair[1].0 }),
+            };
+        }
+        Ok(())
+    }
+
+    /// Returns XLEN and `misa` extension bits represented by the ISA string.
+    pub fn get_isa(&self) -> Result<(Xlen, u64), EmulatorError> {
+        parse_isa(&self.isa)
+    }
+
+    /// Returns true if the ISA string has the multi-letter extension.
+    /// `Zba`, `Zbb` and `Zbs` are also implied by `b`.
+    ///
+    /// # Arguments
+    /// * `name` Lowercase extension name like `svadu`
+    pub fn has_extension(&self, name: &str) -> bool {
+        let lower = self.isa.to_ascii_lowercase();
+        let mut names = lower.split('_');
+        let single_letters = names.next().unwrap_or("").get(4..).unwrap_or("");
+        names.any(|extension| extension == name)
+            || (matches!(name, "zba" | "zbb" | "zbs") && single_letters.contains('b'))
+    }
+
+    /// Returns the maximum size of device tree binary, the room from
+    /// the device tree address to the end of the main memory.
+    pub(crate) fn get_dtb_capacity(&self) -> u64 {
+        match self.dtb_address {
+            Some(address) => (self.memory_base + self.memory_size).saturating_sub(address),
+            None => self.memory_size,
+        }
+    }
+
+    /// Generates the device tree describing this configuration; the main
+    /// memory, the harts with the ISA string, and the enabled devices.
+    /// Returns `Err` if the ISA string is malformed.
+    pub fn generate_device_tree(&self) -> Result<DeviceTree, EmulatorError> {
+        let mmu_type = self
+            .mmu_type
+            .unwrap_or(AddressingMode::get_default(&self.get_isa()?.0))
+            .get_mmu_type();
+        let hart_count = self.hart_count as u32;
+        // Phandles. Each hart has two, for the CPU and its interrupt controller.
+        let cpu_phandle = |hart: u32| hart * 2 + 1;
+        let intc_phandle = |hart: u32| hart * 2 + 2;
+        let plic_phandle = hart_count * 2 + 1;
+        let test_phandle = hart_count * 2 + 2;
+        // Interrupts delivered to every hart's interrupt controller
+        let interrupts_extended = |causes: &[u32]| -> Vec<u32> {
+            (0..hart_count)
+                .flat_map(|hart| causes.iter().flat_map(move |c| [intc_phandle(hart), *c]))
+                .collect()
+        };
+
+        let mut tree = DeviceTree::new();
+        let root = &mut tree.root;
+        root.set_property_cells("#address-cells", &[2]);
+        root.set_property_cells("#size-cells", &[2]);
+        root.set_property_string("compatible", "riscv-virtio");
+        root.set_property_string("model", "riscv-virtio,qemu");
+
+        let chosen = root.get_or_add_child("chosen");
+        chosen.set_property_string("bootargs", DEFAULT_BOOTARGS);
+        if let Some(base) = self.uart_base {
+            chosen.set_property_string("stdout-path", &format!("/soc/uart@{:x}", base));
+        }
+
+        let cpus = root.get_or_add_child("cpus");
+        cpus.set_property_cells("#address-cells", &[1]);
+        cpus.set_property_cells("#size-cells", &[0]);
+        cpus.set_property_cells("timebase-frequency", &[TIMEBASE_FREQUENCY]);
+        for hart in 0..hart_count {
+            let cpu = cpus.get_or_add_child(&format!("cpu@{:x}", hart));
+            cpu.set_property_cells("phandle", &[cpu_phandle(hart)]);
+            cpu.set_property_string("device_type", "cpu");
+            cpu.set_property_cells("reg", &[hart]);
+            cpu.set_property_string("status", "okay");
+            cpu.set_property_string("compatible", "riscv");
+            cpu.set_property_string("riscv,isa", &self.isa.to_ascii_lowercase());
+            cpu.set_property_string("mmu-type", mmu_type);
+            let intc = cpu.get_or_add_child("interrupt-controller");
+            intc.set_property_cells("#interrupt-cells", &[1]);
+            intc.set_property("interrupt-controller", vec![]);
+            intc.set_property_string("compatible", "riscv,cpu-intc");
+            intc.set_property_cells("phandle", &[intc_phandle(hart)]);
+        }
+        let cluster = cpus
+            .get_or_add_child("cpu-map")
+            .get_or_add_child("cluster0");
+        for hart in 0..hart_count {
+            let core = cluster.get_or_add_child(&format!("core{}", hart));
+            core.set_property_cells("cpu", &[cpu_phandle(hart)]);
+        }
+
+        let memory = root.get_or_add_child(&format!("memory@{:x}", self.memory_base));
+        memory.set_property_string("device_type", "memory");
+        memory.set_property_cells("reg", &reg_cells(self.memory_base, self.memory_size));
+
+        let soc = root.get_or_add_child("soc");
+        soc.set_property_cells("#address-cells", &[2]);
+        soc.set_property_cells("#size-cells", &[2]);
+        soc.set_property_string("compatible", "simple-bus");
+        soc.set_property("ranges", vec![]);
+        if let Some(base) = self.clint_base {
+            let clint = soc.get_or_add_child(&format!("clint@{:x}", base));
+            // Machine software and timer interrupts
+            clint.set_property_cells("interrupts-extended", &interrupts_extended(&[3, 7]));
+            clint.set_property_cells("reg", &reg_cells(base, CLINT_SIZE));
+            clint.set_property_string("compatible", "riscv,clint0");
+        }
+        if let Some(base) = self.plic_base {
+            let plic = soc.get_or_add_child(&format!("interrupt-controller@{:x}", base));
+            plic.set_property_cells("phandle", &[plic_phandle]);
+            plic.set_property_cells("riscv,ndev", &[PLIC_SOURCE_COUNT]);
+            plic.set_property_cells("reg", &reg_cells(base, PLIC_SIZE));
+            // Machine and supervisor external interrupts
+            plic.set_property_cells("interrupts-extended", &interrupts_extended(&[11, 9]));
+            plic.set_property("interrupt-controller", vec![]);
+            plic.set_property_string("compatible", "riscv,plic0");
+            plic.set_property_cells("#interrupt-cells", &[1]);
+            plic.set_property_cells("#address-cells", &[0]);
+        }
+        if let Some(base) = self.uart_base {
+            let uart = soc.get_or_add_child(&format!("uart@{:x}", base));
+            if self.plic_base.is_some() {
+                uart.set_property_cells("interrupts", &[UART_IRQ]);
+                uart.set_property_cells("interrupt-parent", &[plic_phandle]);
+            }
+            uart.set_property_cells("clock-frequency", &[UART_CLOCK_FREQUENCY]);
+            uart.set_property_cells("reg", &reg_cells(base, UART_SIZE));
+            uart.set_property_string("compatible", "ns16550a");
+        }
+        if let Some(base) = self.virtio_block_disk_base {
+            let disk = soc.get_or_add_child(&format!("virtio_mmio@{:x}", base));
+            if self.plic_base.is_some() {
+                disk.set_property_cells("interrupts", &[VIRTIO_IRQ]);
+                disk.set_property_cells("interrupt-parent", &[plic_phandle]);
+            }
+            disk.set_property_cells("reg", &reg_cells(base, VIRTIO_BLOCK_DISK_SIZE));
+            disk.set_property_string("compatible", "virtio,mmio");
+        }
+        if let Some(base) = self.test_finisher_base {
+            let test = soc.get_or_add_child(&format!("test@{:x}", base));
+            test.set_property_cells("phandle", &[test_phandle]);
+            test.set_property_cells("reg", &reg_cells(base, TEST_FINISHER_SIZE));
+            test.set_property(
+                "compatible",
+                b"sifive,test1\0sifive,test0\0syscon\0".to_vec(),
+            );
+            // Writing 0x5555 to the test finisher powers off the machine
+            let poweroff = soc.get_or_add_child("poweroff");
+            poweroff.set_property_cells("value", &[0x5555]);
+            poweroff.set_property_cells("offset", &[0]);
+            poweroff.set_property_cells("regmap", &[test_phandle]);
+            poweroff.set_property_string("compatible", "syscon-poweroff");
+        }
+        Ok(tree)
+    }
+
+    /// Returns base address and size of the enabled device regions.
+    fn get_device_regions(&self) -> Vec<(u64, u64)> {
+        let mut regions = vec![(RESET_VECTOR, BOOT_ROM_SIZE)];
+        let devices = [
+            (self.clint_base, CLINT_SIZE),
+            (self.plic_base, PLIC_SIZE),
+            (self.uart_base, UART_SIZE),
+            (self.virtio_block_disk_base, VIRTIO_BLOCK_DISK_SIZE),
+            (self.test_finisher_base, TEST_FINISHER_SIZE),
+        ];
+        for (base, size) in devices {
+            if let Some(base) = base {
+                regions.push((base, size));
+            }
+        }
+        regions
+    }
+}
+
+/// Returns `reg` property cells of a region, two cells for each
+/// of the address and the size.
+///
+/// # Arguments
+/// * `base`
+/// * `size`
+fn reg_cells(base: u64, size: u64) -> [u32; 4] {
+    [
+        (base >> 32) as u32,
+        base as u32,
+        (size >> 32) as u32,
+        size as u32,
+    ]
+}
+
+/// Parses ISA string like `rv64imafdcsu` or `rv32gc_zicsr`.
+/// `g` is expanded to `imafd`. Multi-letter extensions following `_`
+/// are accepted but not reflected to `misa`, except that `misa.B` is set
+/// if `Zba`, `Zbb` and `Zbs` are all present or `b` is.
+///
+/// # Arguments
+/// * `isa`
+fn parse_isa(isa: &str) -> Result<(Xlen, u64), EmulatorError> {
+    let invalid = || EmulatorError::InvalidIsa(isa.to_string());
+    let lower = isa.to_ascii_lowercase();
+    let (xlen, rest) = match lower.get(..4) {
+        Some("rv32") => (Xlen::Bit32, &lower[4..]),
+        Some("rv64") => (Xlen::Bit64, &lower[4..]),
+        _ => return Err(invalid()),
+    };
+    let mut names = rest.split('_');
+    let single_letters = names.next().unwrap_or("");
+    let mut extensions = 0;
+    for (i, letter) in single_letters.bytes().enumerate() {
+        let letters: &[u8] = match letter {
+            b'g' => b"imafd",
+            b'i' | b'e' if i != 0 => return Err(invalid()),
+            b'i' | b'e' | b'm' | b'a' | b'f' | b'd' | b'c' | b'b' | b'v' | b's' | b'u' => &[letter],
+            _ => return Err(invalid()),
+        };
+        for letter in letters {
+            extensions |= 1 << (letter - b'a');
+        }
+    }
+    // B is the combination of Zba, Zbb and Zbs
+    let multi_letters = names.collect::<Vec<_>>();
+    if ["zba", "zbb", "zbs"]
+        .iter()
+        .all(|name| multi_letters.contains(name))
+    {
+        extensions |= 1 << (b'b' - b'a');
+    }
+    // The base integer ISA must come first
+    if extensions & ((1 << (b'i' - b'a')) | (1 << (b'e' - b'a'))) == 0 {
+        return Err(invalid());
+    }
+    Ok((xlen, extensions))
+}
+
+/// Builds [`Emulator`] from [`MachineConfig`].
+///
+/// Sample code to build an emulator with 16MiB memory and without disk.
+/// ```ignore
+/// let mut emulator = EmulatorBuilder::new()
+///     .memory_size(16 * 1024 * 1024)
+///     .virtio_block_disk_base(None)
+///     .build(Box::new(DefaultTerminal::new()))?;
+/// ```
+pub struct EmulatorBuilder {
+    config: MachineConfig,
+}
+
+impl Default for EmulatorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EmulatorBuilder {
+    /// Creates a new `EmulatorBuilder` with the default configuration.
+    pub fn new() -> Self {
+        EmulatorBuilder {
+            config: MachineConfig::new(),
+        }
+    }
+
+    /// Replaces the whole configuration.
+    pub fn config(mut self, config: MachineConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets main memory base address.
+    pub fn memory_base(mut self, base: u64) -> Self {
+        self.config.memory_base = base;
+        self
+    }
+
+    /// Sets main memory capacity in bytes.
+    pub fn memory_size(mut self, size: u64) -> Self {
+        self.config.memory_size = size;
+        self
+    }
+
+    /// Sets ISA string.
+    pub fn isa(mut self, isa: &str) -> Self {
+        self.config.isa = isa.to_string();
+        self
+    }
+
+    /// Sets the number of harts.
+    pub fn hart_count(mut self, count: usize) -> Self {
+        self.config.hart_count = count;
+        self
+    }
+
+    /// Sets the vector register length in bits.
+    pub fn vlen(mut self, vlen: usize) -> Self {
+        self.config.vlen = vlen;
+        self
+    }
+
+    /// Sets the widest virtual memory addressing mode.
+    /// `None` is the default of XLEN.
+    pub fn mmu_type(mut self, mode: Option<AddressingMode>) -> Self {
+        self.config.mmu_type = mode;
+        self
+    }
+
+    /// Sets CLINT base address. `None` disables the device.
+    pub fn clint_base(mut self, base: Option<u64>) -> Self {
+        self.config.clint_base = base;
+        self
+    }
+
+    /// Sets PLIC base address. `None` disables the device.
+    pub fn plic_base(mut self, base: Option<u64>) -> Self {
+        self.config.plic_base = base;
+        self
+    }
+
+    /// Sets UART base address. `None` disables the device.
+    pub fn uart_base(mut self, base: Option<u64>) -> Self {
+        self.config.uart_base = base;
+        self
+    }
+
+    /// Sets Virtio block disk base address. `None` disables the device.
+    pub fn virtio_block_disk_base(mut self, base: Option<u64>) -> Self {
+        self.config.virtio_block_disk_base = base;
+        self
+    }
+
+    /// Sets test finisher (power-off device) base address. `None` disables the device.
+    pub fn test_finisher_base(mut self, base: Option<u64>) -> Self {
+        self.config.test_finisher_base = base;
+        self
+    }
+
+    /// Sets physical address where the device tree is placed at boot.
+    /// `None` places it at the end of the main memory.
+    pub fn dtb_address(mut self, address: Option<u64>) -> Self {
+        self.config.dtb_address = address;
+        self
+    }
+
+    /// Builds `Emulator`. Returns `Err` if the configuration is invalid.
+    ///
+    /// # Arguments
+    /// * `terminal` Used for transferring input/output data to/from `Emulator`
+    pub fn build(self, terminal: Box<dyn Terminal>) -> Result<Emulator, EmulatorError> {
+        Emulator::new_with_config(terminal, self.config)
+    }
+}
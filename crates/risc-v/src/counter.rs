@@ -0,0 +1,60 @@
+//! Hardware performance monitor events. `mhpmevent3`-`mhpmevent31` select
+//! the event each of `mhpmcounter3`-`mhpmcounter31` counts. The event
+//! encodings are platform specific, these are the ones the emulator
+//! observes.
+
+/// Events `mhpmevent` can select. Zero selects no event.
+#[derive(Clone, Copy)]
+pub(crate) enum HpmEvent {
+    /// Exceptions and interrupts taken
+    Trap = 1,
+    /// Page table walks translating virtual addresses
+    PageWalk = 2,
+    /// Instruction decodes missing the decode cache
+    DecodeCacheMiss = 3,
+    /// Loads completed, including the ones of atomic instructions
+    Load = 4,
+    /// Stores completed, including the ones of atomic instructions
+    Store = 5,
+}
+
+/// The number of event encodings, no event included
+const HPM_EVENT_NUM: usize = 6;
+
+/// Indicates whether `mhpmevent` can hold the event. `mhpmevent` is WARL
+/// and unsupported events are written as no event.
+///
+/// # Arguments
+/// * `event`
+pub(crate) fn is_supported_event(event: u64) -> bool {
+    event < HPM_EVENT_NUM as u64
+}
+
+/// Occurrences of the events since they were taken last time
+#[derive(Default)]
+pub(crate) struct HpmEventCounts {
+    counts: [u64; HPM_EVENT_NUM],
+}
+
+impl HpmEventCounts {
+    /// Counts an occurrence of the event.
+    ///
+    /// # Arguments
+    /// * `event`
+    pub(crate) fn count(&mut self, event: HpmEvent) {
+        self.counts[event as usize] += 1;
+    }
+
+    /// Returns the occurrences counted so far and resets them.
+    pub(crate) fn take(&mut self) -> Self {
+        std::mem::take(self)
+    }
+
+    /// Returns the occurrences of the event selected with `mhpmevent`.
+    ///
+    /// # Arguments
+    /// * `event` Event encoding, must be supported
+    pub(crate) fn get(&self, event: u64) -> u64 {
+        self.counts[event as usize]
+    }
+}
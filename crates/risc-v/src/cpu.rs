@@ -1,58 +1,40 @@
+use std::cell::RefMut;
+use std::rc::Rc;
+
+use crate::config::MachineConfig;
+use crate::counter::{self, HpmEvent, HpmEventCounts};
+use crate::csr::*;
+use crate::decoder::Decoder;
+use crate::error::EmulatorError;
+use crate::fpu::{self, RoundingMode};
 use crate::mmu::{AddressingMode, Mmu};
+use crate::pmp::PMP_ENTRY_NUM;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
 use crate::terminal::Terminal;
+use crate::vector::{
+    self, Addressing, BinaryOperation, CarryOperation, CarryOutOperation, CompareOperation,
+    Operand, TernaryOperation, UnaryOperation, VectorContext, VectorType, VectorUnit, Widths,
+};
 use fnv::FnvHashMap;
+use std::sync::OnceLock;
 
 const CSR_CAPACITY: usize = 4096;
 
-const CSR_USTATUS_ADDRESS: u16 = 0x000;
-const CSR_FFLAGS_ADDRESS: u16 = 0x001;
-const CSR_FRM_ADDRESS: u16 = 0x002;
-const CSR_FCSR_ADDRESS: u16 = 0x003;
-const CSR_UIE_ADDRESS: u16 = 0x004;
-const CSR_UTVEC_ADDRESS: u16 = 0x005;
-const _CSR_USCRATCH_ADDRESS: u16 = 0x040;
-const CSR_UEPC_ADDRESS: u16 = 0x041;
-const CSR_UCAUSE_ADDRESS: u16 = 0x042;
-const CSR_UTVAL_ADDRESS: u16 = 0x043;
-const _CSR_UIP_ADDRESS: u16 = 0x044;
-const CSR_SSTATUS_ADDRESS: u16 = 0x100;
-const CSR_SEDELEG_ADDRESS: u16 = 0x102;
-const CSR_SIDELEG_ADDRESS: u16 = 0x103;
-const CSR_SIE_ADDRESS: u16 = 0x104;
-const CSR_STVEC_ADDRESS: u16 = 0x105;
-const _CSR_SSCRATCH_ADDRESS: u16 = 0x140;
-const CSR_SEPC_ADDRESS: u16 = 0x141;
-const CSR_SCAUSE_ADDRESS: u16 = 0x142;
-const CSR_STVAL_ADDRESS: u16 = 0x143;
-const CSR_SIP_ADDRESS: u16 = 0x144;
-const CSR_SATP_ADDRESS: u16 = 0x180;
-const CSR_MSTATUS_ADDRESS: u16 = 0x300;
-const CSR_MISA_ADDRESS: u16 = 0x301;
-const CSR_MEDELEG_ADDRESS: u16 = 0x302;
-const CSR_MIDELEG_ADDRESS: u16 = 0x303;
-const CSR_MIE_ADDRESS: u16 = 0x304;
-
-const CSR_MTVEC_ADDRESS: u16 = 0x305;
-const _CSR_MSCRATCH_ADDRESS: u16 = 0x340;
-const CSR_MEPC_ADDRESS: u16 = 0x341;
-const CSR_MCAUSE_ADDRESS: u16 = 0x342;
-const CSR_MTVAL_ADDRESS: u16 = 0x343;
-const CSR_MIP_ADDRESS: u16 = 0x344;
-const _CSR_PMPCFG0_ADDRESS: u16 = 0x3a0;
-const _CSR_PMPADDR0_ADDRESS: u16 = 0x3b0;
-const _CSR_MCYCLE_ADDRESS: u16 = 0xb00;
-const CSR_CYCLE_ADDRESS: u16 = 0xc00;
-const CSR_TIME_ADDRESS: u16 = 0xc01;
-const _CSR_INSERT_ADDRESS: u16 = 0xc02;
-const _CSR_MHARTID_ADDRESS: u16 = 0xf14;
-
-const MIP_MEIP: u64 = 0x800;
+pub const MIP_MEIP: u64 = 0x800;
 pub const MIP_MTIP: u64 = 0x080;
 pub const MIP_MSIP: u64 = 0x008;
 pub const MIP_SEIP: u64 = 0x200;
 const MIP_STIP: u64 = 0x020;
 const MIP_SSIP: u64 = 0x002;
 
+/// `misa.C`, the compressed extension
+const MISA_C: u64 = 1 << 2;
+/// `misa.V`, the vector extension
+const MISA_V: u64 = 1 << 21;
+
+/// Upper 32 bits of a floating-point register holding a single-precision value
+const F32_NAN_BOX: u64 = 0xffffffff00000000;
+
 /// Emulates a RISC-V CPU core
 pub struct Cpu {
     clock: u64,
@@ -62,15 +44,34 @@ pub struct Cpu {
     // using only lower 32bits of x, pc, and csr registers
     // for 32-bit mode
     x: [i64; 32],
-    f: [f64; 32],
+    // raw bits. single-precision values are NaN-boxed
+    f: [u64; 32],
+    vector: VectorUnit,
     pub pc: u64,
     csr: [u64; CSR_CAPACITY],
     mmu: Mmu,
-    reservation: u64, // @TODO: Should support multiple address reservations
-    is_reservation_set: bool,
     _dump_flag: bool,
     decode_cache: DecodeCache,
+    block_cache: BlockCache,
     unsigned_data_mask: u64,
+    /// Whether the Svadu extension is implemented, making hardware A/D bit
+    /// updates configurable with `menvcfg.ADUE`
+    svadu: bool,
+    /// Whether the Sstc extension is implemented, providing `stimecmp`
+    /// enabled with `menvcfg.STCE`
+    sstc: bool,
+    /// Whether each instruction in [`INSTRUCTIONS`](static.INSTRUCTIONS.html)
+    /// is in the base ISA or an extension in the ISA string. The others
+    /// raise illegal instruction exceptions.
+    enabled_instructions: Vec<bool>,
+    /// Trap and decode events for the hardware performance counters
+    hpm_events: HpmEventCounts,
+    /// Bits of `mhpmcounter3`-`mhpmcounter31` counting an event and not
+    /// inhibited, updated when `mhpmevent` or `mcountinhibit` is written
+    active_hpm_counters: u32,
+    /// Exception taken while no trap handler is set (trap vector is zero)
+    /// and the address of the instruction raising it
+    unhandled_exception: Option<(Trap, u64)>,
 }
 
 #[derive(Clone)]
@@ -88,12 +89,14 @@ pub enum PrivilegeMode {
     Machine,
 }
 
+#[derive(Clone, Debug)]
 pub struct Trap {
     pub trap_type: TrapType,
     pub value: u64, // Trap type specific value
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TrapType {
     InstructionAddressMisaligned,
     InstructionAccessFault,
@@ -130,7 +133,7 @@ fn _get_privilege_mode_name(mode: &PrivilegeMode) -> &'static str {
 }
 
 // bigger number is higher privilege level
-fn get_privilege_encoding(mode: &PrivilegeMode) -> u8 {
+pub fn get_privilege_encoding(mode: &PrivilegeMode) -> u8 {
     match mode {
         PrivilegeMode::User => 0,
         PrivilegeMode::Supervisor => 1,
@@ -149,6 +152,33 @@ pub fn get_privilege_mode(encoding: u64) -> PrivilegeMode {
     }
 }
 
+/// Reads privilege mode saved in a snapshot as `get_privilege_encoding()` returns
+pub(crate) fn read_privilege_mode(
+    reader: &mut SnapshotReader,
+) -> Result<PrivilegeMode, EmulatorError> {
+    match reader.read_u8()? {
+        encoding @ (0 | 1 | 3) => Ok(get_privilege_mode(encoding as u64)),
+        _ => Err(EmulatorError::InvalidSnapshot),
+    }
+}
+
+/// Writes XLEN to a snapshot
+pub(crate) fn write_xlen(writer: &mut SnapshotWriter, xlen: &Xlen) {
+    writer.write_u8(match xlen {
+        Xlen::Bit32 => 32,
+        Xlen::Bit64 => 64,
+    });
+}
+
+/// Reads XLEN written with `write_xlen()`
+pub(crate) fn read_xlen(reader: &mut SnapshotReader) -> Result<Xlen, EmulatorError> {
+    match reader.read_u8()? {
+        32 => Ok(Xlen::Bit32),
+        64 => Ok(Xlen::Bit64),
+        _ => Err(EmulatorError::InvalidSnapshot),
+    }
+}
+
 fn _get_trap_type_name(trap_type: &TrapType) -> &'static str {
     match trap_type {
         TrapType::InstructionAddressMisaligned => "InstructionAddressMisaligned",
@@ -209,39 +239,155 @@ fn get_trap_cause(trap: &Trap, xlen: &Xlen) -> u64 {
     }
 }
 
+/// Returns `TrapType` from the cause `get_trap_cause()` returns in 64-bit mode
+fn get_trap_type(cause: u64) -> Option<TrapType> {
+    let interrupt_bit = 0x8000000000000000_u64;
+    let trap_type = match cause {
+        0 => TrapType::InstructionAddressMisaligned,
+        1 => TrapType::InstructionAccessFault,
+        2 => TrapType::IllegalInstruction,
+        3 => TrapType::Breakpoint,
+        4 => TrapType::LoadAddressMisaligned,
+        5 => TrapType::LoadAccessFault,
+        6 => TrapType::StoreAddressMisaligned,
+        7 => TrapType::StoreAccessFault,
+        8 => TrapType::EnvironmentCallFromUMode,
+        9 => TrapType::EnvironmentCallFromSMode,
+        11 => TrapType::EnvironmentCallFromMMode,
+        12 => TrapType::InstructionPageFault,
+        13 => TrapType::LoadPageFault,
+        15 => TrapType::StorePageFault,
+        _ => match cause.wrapping_sub(interrupt_bit) {
+            0 => TrapType::UserSoftwareInterrupt,
+            1 => TrapType::SupervisorSoftwareInterrupt,
+            3 => TrapType::MachineSoftwareInterrupt,
+            4 => TrapType::UserTimerInterrupt,
+            5 => TrapType::SupervisorTimerInterrupt,
+            7 => TrapType::MachineTimerInterrupt,
+            8 => TrapType::UserExternalInterrupt,
+            9 => TrapType::SupervisorExternalInterrupt,
+            11 => TrapType::MachineExternalInterrupt,
+            _ => return None,
+        },
+    };
+    Some(trap_type)
+}
+
 impl Cpu {
     /// Creates a new `Cpu`.
     ///
     /// # Arguments
     /// * `Terminal`
     pub fn new(terminal: Box<dyn Terminal>) -> Self {
+        Self::new_with_config(terminal, &MachineConfig::default())
+    }
+
+    /// Creates a new `Cpu` with machine configuration. `config` is expected
+    /// to be validated with `MachineConfig::validate()` beforehand.
+    ///
+    /// # Arguments
+    /// * `Terminal`
+    /// * `config`
+    pub fn new_with_config(terminal: Box<dyn Terminal>, config: &MachineConfig) -> Self {
+        let (xlen, extensions) = config
+            .get_isa()
+            .expect("ISA string must be validated beforehand");
         let mut cpu = Cpu {
             clock: 0,
             xlen: Xlen::Bit64,
             privilege_mode: PrivilegeMode::Machine,
             wfi: false,
             x: [0; 32],
-            f: [0.0; 32],
+            f: [0; 32],
+            vector: VectorUnit::new(config.vlen),
             pc: 0,
             csr: [0; CSR_CAPACITY],
-            mmu: Mmu::new(Xlen::Bit64, terminal),
-            reservation: 0,
-            is_reservation_set: false,
+            mmu: Mmu::new(Xlen::Bit64, terminal, config),
             _dump_flag: false,
             decode_cache: DecodeCache::new(),
+            block_cache: BlockCache::new(),
             unsigned_data_mask: 0xffffffffffffffff,
+            svadu: config.has_extension("svadu"),
+            sstc: config.has_extension("sstc"),
+            enabled_instructions: INSTRUCTIONS
+                .iter()
+                .map(|inst| match get_extension(inst) {
+                    Some(Extension::Base) => true,
+                    Some(Extension::Named(name)) if name.len() == 1 => {
+                        (extensions & (1 << (name.as_bytes()[0] - b'a'))) != 0
+                    }
+                    Some(Extension::Named(name)) => config.has_extension(name),
+                    None => false,
+                })
+                .collect(),
+            hpm_events: HpmEventCounts::default(),
+            active_hpm_counters: 0,
+            unhandled_exception: None,
+        };
+        cpu.write_csr_raw(CSR_MISA_ADDRESS, extensions);
+        cpu.reset_envcfg();
+        cpu.update_xlen(xlen);
+        cpu.mmu.init_memory(config.memory_size);
+        cpu
+    }
+
+    /// Creates a new `Cpu` of another hart sharing the bus, main memory and
+    /// devices with this hart. The new hart has the same ISA, XLEN and VLEN.
+    ///
+    /// # Arguments
+    /// * `hart_id`
+    pub(crate) fn create_secondary_hart(&self, hart_id: usize) -> Self {
+        let mut cpu = Cpu {
+            clock: 0,
+            xlen: self.xlen.clone(),
+            privilege_mode: PrivilegeMode::Machine,
+            wfi: false,
+            x: [0; 32],
+            f: [0; 32],
+            vector: VectorUnit::new(self.vector.get_vlen()),
+            pc: 0,
+            csr: [0; CSR_CAPACITY],
+            mmu: self.mmu.create_secondary_hart(hart_id),
+            _dump_flag: false,
+            decode_cache: DecodeCache::new(),
+            block_cache: BlockCache::new(),
+            unsigned_data_mask: self.unsigned_data_mask,
+            svadu: self.svadu,
+            sstc: self.sstc,
+            enabled_instructions: self.enabled_instructions.clone(),
+            hpm_events: HpmEventCounts::default(),
+            active_hpm_counters: 0,
+            unhandled_exception: None,
         };
-        cpu.x[0xb] = 0x1020; // TODO: I don't know why but Linux boot seems to require this initialization
-        cpu.write_csr_raw(CSR_MISA_ADDRESS, 0x800000008014312f);
+        cpu.write_csr_raw(CSR_MISA_ADDRESS, self.csr[CSR_MISA_ADDRESS as usize]);
+        cpu.reset_envcfg();
+        cpu.write_csr_raw(CSR_MHARTID_ADDRESS, hart_id as u64);
+        cpu.update_xlen(self.xlen.clone());
         cpu
     }
 
+    /// Sets the reset value of `menvcfg`. Hardware A/D bit updates are
+    /// enabled with Svadu as the harts have always done.
+    fn reset_envcfg(&mut self) {
+        let menvcfg = match self.svadu {
+            true => MENVCFG_ADUE,
+            false => 0,
+        };
+        self.write_csr_raw(CSR_MENVCFG_ADDRESS, menvcfg);
+    }
+
     /// Updates Program Counter content
     ///
     /// # Arguments
     /// * `value`
     pub fn update_pc(&mut self, value: u64) {
         self.pc = value;
+        self.block_cache.leave();
+    }
+
+    /// Returns XLEN, 32-bit or 64-bit
+    pub fn get_xlen(&self) -> &Xlen {
+        &self.xlen
     }
 
     /// Updates XLEN, 32-bit or 64-bit
@@ -255,6 +401,18 @@ impl Cpu {
             Xlen::Bit64 => 0xffffffffffffffff,
         };
         self.mmu.update_xlen(xlen.clone());
+        // Compressed instructions are decoded depending on XLEN
+        self.invalidate_blocks();
+
+        // misa.MXL, mstatus.UXL, and mstatus.SXL follow XLEN
+        let (mxl, uxl_sxl) = match xlen {
+            Xlen::Bit32 => (1 << 30, 0),
+            Xlen::Bit64 => (2 << 62, MSTATUS_UXL_SXL_64),
+        };
+        let extensions = self.csr[CSR_MISA_ADDRESS as usize] & 0x3ffffff;
+        self.csr[CSR_MISA_ADDRESS as usize] = mxl | extensions;
+        let status = self.csr[CSR_MSTATUS_ADDRESS as usize] & !MSTATUS_UXL_SXL_64;
+        self.csr[CSR_MSTATUS_ADDRESS as usize] = status | uxl_sxl;
     }
 
     /// Reads integer register content
@@ -277,18 +435,97 @@ impl Cpu {
     /// Runs program one cycle. Fetch, decode, and execution are completed in a cycle so far.
     pub fn tick(&mut self) {
         let instruction_address = self.pc;
-        match self.tick_operate() {
-            Ok(()) => {}
-            Err(e) => self.handle_exception(e, instruction_address),
-        }
+        let is_waiting = self.wfi;
+        let counters = (
+            self.csr[CSR_MCYCLE_ADDRESS as usize],
+            self.csr[CSR_MINSTRET_ADDRESS as usize],
+        );
+        let is_retired = match self.tick_operate() {
+            Ok(()) => !is_waiting,
+            Err(e) => {
+                self.handle_exception(e, instruction_address);
+                false
+            }
+        };
         self.mmu.tick(&mut self.csr[CSR_MIP_ADDRESS as usize]);
+        self.update_supervisor_timer_interrupt();
         self.handle_interrupt(self.pc);
         self.clock = self.clock.wrapping_add(1);
+        self.update_counters(is_retired, counters);
+    }
+
+    /// Increments the counters not inhibited with `mcountinhibit`, `mcycle`
+    /// every cycle, `minstret` if an instruction retires, and `mhpmcounter`s
+    /// by the occurrences of the events `mhpmevent`s select. `mcycle` and
+    /// `minstret` written in the cycle keep the written values.
+    ///
+    /// # Arguments
+    /// * `is_retired` Whether an instruction retires in the cycle
+    /// * `counters` `mcycle` and `minstret` values at the beginning of the cycle
+    fn update_counters(&mut self, is_retired: bool, counters: (u64, u64)) {
+        let inhibit = self.csr[CSR_MCOUNTINHIBIT_ADDRESS as usize];
+        let mcycle = &mut self.csr[CSR_MCYCLE_ADDRESS as usize];
+        if (inhibit & 0x1) == 0 && *mcycle == counters.0 {
+            *mcycle = mcycle.wrapping_add(1);
+        }
+        let minstret = &mut self.csr[CSR_MINSTRET_ADDRESS as usize];
+        if is_retired && (inhibit & 0x4) == 0 && *minstret == counters.1 {
+            *minstret = minstret.wrapping_add(1);
+        }
+
+        let events = self.hpm_events.take();
+        let mmu_events = self.mmu.take_hpm_events();
+        let mut active_counters = self.active_hpm_counters;
+        while active_counters != 0 {
+            let index = active_counters.trailing_zeros() as u16;
+            active_counters &= active_counters - 1;
+            let event = self.csr[(CSR_MCOUNTINHIBIT_ADDRESS + index) as usize];
+            let counter = &mut self.csr[(CSR_MCYCLE_ADDRESS + index) as usize];
+            *counter = counter
+                .wrapping_add(events.get(event))
+                .wrapping_add(mmu_events.get(event));
+        }
+    }
+
+    /// Updates the bits of `mhpmcounter`s counting an event and not
+    /// inhibited. `mhpmevent3` is at the offset 3 from `mcountinhibit`
+    /// as `mhpmcounter3` is from `mcycle`.
+    fn update_active_hpm_counters(&mut self) {
+        let inhibit = self.csr[CSR_MCOUNTINHIBIT_ADDRESS as usize];
+        self.active_hpm_counters = (3..32)
+            .filter(|index| {
+                self.csr[(CSR_MCOUNTINHIBIT_ADDRESS + index) as usize] != 0
+                    && ((inhibit >> index) & 1) == 0
+            })
+            .fold(0, |counters, index| counters | (1 << index));
+    }
+
+    /// Reflects `stimecmp` in `mip.STIP` if it's enabled with `menvcfg.STCE`.
+    /// Supervisor timer interrupt is pending while `time` is greater than
+    /// or equal to `stimecmp`.
+    fn update_supervisor_timer_interrupt(&mut self) {
+        if (self.csr[CSR_MENVCFG_ADDRESS as usize] & MENVCFG_STCE) == 0 {
+            return;
+        }
+        let is_pending = self.mmu.read_mtime() >= self.csr[CSR_STIMECMP_ADDRESS as usize];
+        let mip = &mut self.csr[CSR_MIP_ADDRESS as usize];
+        match is_pending {
+            true => *mip |= MIP_STIP,
+            false => *mip &= !MIP_STIP,
+        };
+    }
+
+    /// Returns the exception taken while no trap handler is set up
+    /// (trap vector is zero) and the address of the instruction raising it.
+    /// Execution can't continue meaningfully after such an exception.
+    pub fn get_unhandled_exception(&self) -> Option<&(Trap, u64)> {
+        self.unhandled_exception.as_ref()
+    }
 
-        // cpu core clock : mtime clock in clint = 8 : 1 is
-        // just an arbitrary ratio.
-        // @TODO: Implement more properly
-        self.write_csr_raw(CSR_CYCLE_ADDRESS, self.clock * 8);
+    /// Indicates whether the CPU waits for interrupt (WFI) which never comes
+    /// because all the interrupts are disabled in `mie`.
+    pub fn is_halted(&self) -> bool {
+        self.wfi && self.read_csr_raw(CSR_MIE_ADDRESS) == 0
     }
 
     // @TODO: Rename?
@@ -300,32 +537,139 @@ impl Cpu {
             return Ok(());
         }
 
-        let original_word = self.fetch()?;
+        // Stores may have overwritten cached instructions
+        if self.mmu.has_written_code_pages() {
+            for page in self.mmu.take_written_code_pages() {
+                self.block_cache.invalidate_page(page);
+            }
+        }
+
         let instruction_address = self.pc;
-        let word = match (original_word & 0x3) == 0x3 {
-            true => {
-                self.pc = self.pc.wrapping_add(4); // 32-bit length non-compressed instruction
-                original_word
+        let instruction = match self.block_cache.next(self.pc) {
+            Some(instruction) => Some(instruction),
+            None => self.enter_block(),
+        };
+        let (result, instruction_bits) = match instruction {
+            Some(instruction) => {
+                self.pc = self.pc.wrapping_add(instruction.size);
+                let result = match instruction.operation {
+                    BlockOperation::Decoded(operation, operands) => {
+                        operation(self, &operands, instruction_address)
+                    }
+                    BlockOperation::Word(operation, word) => {
+                        operation(self, word, instruction_address)
+                    }
+                };
+                self.x[0] = 0; // hardwired zero
+                (result, instruction.instruction_bits)
             }
-            false => {
-                self.pc = self.pc.wrapping_add(2); // 16-bit length compressed instruction
-                self.uncompress(original_word & 0xffff)
+            // Instructions not cacheable are fetched and decoded one by one
+            None => {
+                let original_word = self.fetch()?;
+                let (word, instruction_bits) = match (original_word & 0x3) == 0x3 {
+                    true => {
+                        self.pc = self.pc.wrapping_add(4); // 32-bit length non-compressed instruction
+                        (original_word, original_word)
+                    }
+                    false => {
+                        self.pc = self.pc.wrapping_add(2); // 16-bit length compressed instruction
+                        let halfword = original_word & 0xffff;
+                        (self.uncompress(halfword), halfword)
+                    }
+                };
+
+                let result = match self.decode(word) {
+                    Ok(inst) => {
+                        let result = (inst.operation)(self, word, instruction_address);
+                        self.x[0] = 0; // hardwired zero
+                        result
+                    }
+                    Err(()) => Err(Trap {
+                        trap_type: TrapType::IllegalInstruction,
+                        value: 0,
+                    }),
+                };
+                (result, instruction_bits)
             }
         };
+        // Illegal instruction exception reports the faulting instruction
+        // bits, not uncompressed ones, in xtval
+        result.map_err(|trap| match trap.trap_type {
+            TrapType::IllegalInstruction => Trap {
+                trap_type: TrapType::IllegalInstruction,
+                value: instruction_bits as u64,
+            },
+            _ => trap,
+        })
+    }
 
-        match self.decode(word) {
-            Ok(inst) => {
-                let result = (inst.operation)(self, word, instruction_address);
-                self.x[0] = 0; // hardwired zero
-                result
+    /// Starts executing the block of [`BlockCache`](struct.BlockCache.html)
+    /// at `pc`, decoding and caching it unless cached, and returns its
+    /// first instruction. Returns `None` if the instruction at `pc` can't
+    /// be cached, for example it isn't in the main memory or fetching it
+    /// faults.
+    fn enter_block(&mut self) -> Option<BlockInstruction> {
+        let p_address = self.mmu.translate_fetch_address(self.pc).ok()?;
+        let size = match self.block_cache.get_block_size(p_address) {
+            Some(size) => size,
+            None => {
+                let block = self.decode_block(p_address);
+                let size = block.iter().map(|instruction| instruction.size).sum();
+                if block.is_empty() {
+                    return None;
+                }
+                self.block_cache.insert(p_address, block);
+                size
             }
-            Err(()) => {
-                panic!(
-                    "Unknown instruction PC:{:x} WORD:{:x}",
-                    instruction_address, original_word
-                );
+        };
+        // Permissions differ between the privilege modes
+        match self.mmu.is_fetchable(p_address, size) {
+            true => self.block_cache.enter(p_address, self.pc),
+            false => None,
+        }
+    }
+
+    /// Decodes the instructions from the physical address up to a control
+    /// transfer or a system instruction, the end of the page, or an
+    /// instruction failing to decode.
+    ///
+    /// # Arguments
+    /// * `p_address` Physical address of the first instruction
+    fn decode_block(&mut self, p_address: u64) -> Vec<BlockInstruction> {
+        let mut block = vec![];
+        let mut address = p_address;
+        while block.len() < BLOCK_INSTRUCTION_NUM {
+            let (word, instruction_bits, size) = match self.mmu.read_code(address) {
+                Some(bits) if (bits & 0x3) == 0x3 => (bits, bits, 4),
+                Some(bits) => (self.uncompress(bits), bits, 2),
+                None => break,
+            };
+            let operation = match self.decode(word) {
+                Ok(instruction) => match decode_operands(word) {
+                    Some((operation, operands)) => BlockOperation::Decoded(operation, operands),
+                    None => BlockOperation::Word(instruction.operation, word),
+                },
+                Err(()) => break,
+            };
+            block.push(BlockInstruction {
+                operation,
+                instruction_bits,
+                size,
+            });
+            address = address.wrapping_add(size);
+            // Branch, JALR, JAL, SYSTEM, and MISC-MEM (FENCE and FENCE.I)
+            if matches!(word & 0x7f, 0x63 | 0x67 | 0x6f | 0x73 | 0x0f) {
+                break;
             }
         }
+        block
+    }
+
+    /// Invalidates all the cached blocks, with `FENCE.I` and `SFENCE.VMA`
+    /// for example.
+    fn invalidate_blocks(&mut self) {
+        self.block_cache.clear();
+        self.mmu.clear_code_pages();
     }
 
     /// Decodes a word instruction data and returns a reference to
@@ -337,6 +681,7 @@ impl Cpu {
             Some(index) => Ok(&INSTRUCTIONS[index]),
             None => match self.decode_and_get_instruction_index(word) {
                 Ok(index) => {
+                    self.hpm_events.count(HpmEvent::DecodeCacheMiss);
                     self.decode_cache.insert(word, index);
                     Ok(&INSTRUCTIONS[index])
                 }
@@ -357,17 +702,15 @@ impl Cpu {
     }
 
     /// Decodes a word instruction data and returns an index of
-    /// [`INSTRUCTIONS`](constant.INSTRUCTIONS.html)
+    /// [`INSTRUCTIONS`](static.INSTRUCTIONS.html)
     ///
     /// # Arguments
     /// * `word` word instruction data decoded
     fn decode_and_get_instruction_index(&self, word: u32) -> Result<usize, ()> {
-        for (i, inst) in INSTRUCTIONS.iter().enumerate().take(INSTRUCTION_NUM) {
-            if (word & inst.mask) == inst.data {
-                return Ok(i);
-            }
+        match decoder().decode(word) {
+            Some(index) if self.enabled_instructions[index] => Ok(index),
+            _ => Err(()),
         }
-        Err(())
     }
 
     fn handle_interrupt(&mut self, instruction_address: u64) {
@@ -492,18 +835,15 @@ impl Cpu {
             true => self.read_csr_raw(CSR_MIDELEG_ADDRESS),
             false => self.read_csr_raw(CSR_MEDELEG_ADDRESS),
         };
-        let sdeleg = match is_interrupt {
-            true => self.read_csr_raw(CSR_SIDELEG_ADDRESS),
-            false => self.read_csr_raw(CSR_SEDELEG_ADDRESS),
-        };
         let pos = cause & 0xffff;
 
-        let new_privilege_mode = match ((mdeleg >> pos) & 1) == 0 {
+        // Traps never move to a lower privilege mode. User mode traps
+        // (N extension) aren't supported, so sedeleg and sideleg are ignored.
+        let new_privilege_mode = match ((mdeleg >> pos) & 1) == 0
+            || matches!(self.privilege_mode, PrivilegeMode::Machine)
+        {
             true => PrivilegeMode::Machine,
-            false => match ((sdeleg >> pos) & 1) == 0 {
-                true => PrivilegeMode::Supervisor,
-                false => PrivilegeMode::User,
-            },
+            false => PrivilegeMode::Supervisor,
         };
         let new_privilege_encoding = get_privilege_encoding(&new_privilege_mode) as u64;
 
@@ -574,57 +914,23 @@ impl Cpu {
             // where x is a new privilege mode.
 
             match trap.trap_type {
-                TrapType::UserSoftwareInterrupt => {
-                    if usie == 0 {
-                        return false;
-                    }
-                }
-                TrapType::SupervisorSoftwareInterrupt => {
-                    if ssie == 0 {
-                        return false;
-                    }
-                }
-                TrapType::MachineSoftwareInterrupt => {
-                    if msie == 0 {
-                        return false;
-                    }
-                }
-                TrapType::UserTimerInterrupt => {
-                    if utie == 0 {
-                        return false;
-                    }
-                }
-                TrapType::SupervisorTimerInterrupt => {
-                    if stie == 0 {
-                        return false;
-                    }
-                }
-                TrapType::MachineTimerInterrupt => {
-                    if mtie == 0 {
-                        return false;
-                    }
-                }
-                TrapType::UserExternalInterrupt => {
-                    if ueie == 0 {
-                        return false;
-                    }
-                }
-                TrapType::SupervisorExternalInterrupt => {
-                    if seie == 0 {
-                        return false;
-                    }
-                }
-                TrapType::MachineExternalInterrupt => {
-                    if meie == 0 {
-                        return false;
-                    }
-                }
+                TrapType::UserSoftwareInterrupt if usie == 0 => return false,
+                TrapType::SupervisorSoftwareInterrupt if ssie == 0 => return false,
+                TrapType::MachineSoftwareInterrupt if msie == 0 => return false,
+                TrapType::UserTimerInterrupt if utie == 0 => return false,
+                TrapType::SupervisorTimerInterrupt if stie == 0 => return false,
+                TrapType::MachineTimerInterrupt if mtie == 0 => return false,
+                TrapType::UserExternalInterrupt if ueie == 0 => return false,
+                TrapType::SupervisorExternalInterrupt if seie == 0 => return false,
+                TrapType::MachineExternalInterrupt if meie == 0 => return false,
                 _ => {}
             };
         }
 
         // So, this trap should be taken
 
+        self.hpm_events.count(HpmEvent::Trap);
+        self.block_cache.leave();
         self.privilege_mode = new_privilege_mode;
         self.mmu.update_privilege_mode(self.privilege_mode.clone());
         let csr_epc_address = match self.privilege_mode {
@@ -657,6 +963,11 @@ impl Cpu {
         self.write_csr_raw(csr_tval_address, trap.value);
         self.pc = self.read_csr_raw(csr_tvec_address);
 
+        // Zero trap vector means no trap handler has been set up
+        if !is_interrupt && (self.pc & !0x3) == 0 {
+            self.unhandled_exception = Some((trap, instruction_address));
+        }
+
         // Add 4 * cause if tvec has vector type address
         if (self.pc & 0x3) != 0 {
             self.pc = (self.pc & !0x3) + 4 * (cause & 0xffff);
@@ -671,7 +982,8 @@ impl Cpu {
                     (status & !0x1888) | (mie << 7) | (current_privilege_encoding << 11);
                 self.write_csr_raw(CSR_MSTATUS_ADDRESS, new_status);
             }
-            PrivilegeMode::Supervisor => {
+            // Traps are taken only into Machine or Supervisor mode
+            _ => {
                 let status = self.read_csr_raw(CSR_SSTATUS_ADDRESS);
                 let sie = (status >> 1) & 1;
                 // clear SIE[1], override SPIE[5] with SIE[1], override SPP[8] with current privilege encoding
@@ -679,10 +991,6 @@ impl Cpu {
                     (status & !0x122) | (sie << 5) | ((current_privilege_encoding & 1) << 8);
                 self.write_csr_raw(CSR_SSTATUS_ADDRESS, new_status);
             }
-            PrivilegeMode::User => {
-                panic!("Not implemented yet");
-            }
-            PrivilegeMode::Reserved => panic!(), // shouldn't happen
         };
         //println!("Trap! {:x} Clock:{:x}", cause, self.clock);
         true
@@ -704,53 +1012,181 @@ impl Cpu {
         privilege as u8 <= get_privilege_encoding(&self.privilege_mode)
     }
 
-    fn read_csr(&mut self, address: u16) -> Result<u64, Trap> {
-        match self.has_csr_access_privilege(address) {
-            true => Ok(self.read_csr_raw(address)),
-            false => Err(Trap {
-                trap_type: TrapType::IllegalInstruction,
-                value: self.pc.wrapping_sub(4), // @TODO: Is this always correct?
-            }),
+    /// Checks if the CSR can be accessed with a CSR instruction and returns
+    /// the bits software can write. Accessing a nonexistent CSR, a CSR of
+    /// higher privilege level, or writing a read-only CSR raises
+    /// IllegalInstruction. `tick_operate()` sets the instruction bits to
+    /// the trap value.
+    ///
+    /// # Arguments
+    /// * `address`
+    /// * `is_write`
+    fn check_csr_access(&self, address: u16, is_write: bool) -> Result<u64, Trap> {
+        let illegal_instruction = Trap {
+            trap_type: TrapType::IllegalInstruction,
+            value: 0,
+        };
+        let write_mask = match get_write_mask(address, &self.xlen) {
+            Some(write_mask) => write_mask,
+            None => return Err(illegal_instruction),
+        };
+        if !self.has_csr_access_privilege(address) || (is_write && is_read_only(address)) {
+            return Err(illegal_instruction);
+        }
+        // The vector CSRs exist only with the V extension and are
+        // inaccessible while the vector state is Off
+        if is_vector(address)
+            && ((self.csr[CSR_MISA_ADDRESS as usize] & MISA_V) == 0
+                || (self.csr[CSR_MSTATUS_ADDRESS as usize] & MSTATUS_VS) == 0)
+        {
+            return Err(illegal_instruction);
+        }
+        // mstatus.TVM traps satp accesses in Supervisor mode
+        if address == CSR_SATP_ADDRESS
+            && matches!(self.privilege_mode, PrivilegeMode::Supervisor)
+            && (self.read_csr_raw(CSR_MSTATUS_ADDRESS) & MSTATUS_TVM) != 0
+        {
+            return Err(illegal_instruction);
+        }
+        // Machine mode permits Supervisor mode to read the counters with
+        // mcounteren, and Supervisor mode permits User mode with scounteren
+        if matches!(
+            address,
+            CSR_CYCLE_ADDRESS..=CSR_HPMCOUNTER31_ADDRESS
+                | CSR_CYCLEH_ADDRESS..=CSR_HPMCOUNTER31H_ADDRESS
+        ) {
+            let counter = 1 << (address & 0x1f);
+            let is_enabled = |counteren: u16| (self.csr[counteren as usize] & counter) != 0;
+            let is_permitted = match self.privilege_mode {
+                PrivilegeMode::Machine => true,
+                PrivilegeMode::Supervisor => is_enabled(CSR_MCOUNTEREN_ADDRESS),
+                _ => is_enabled(CSR_MCOUNTEREN_ADDRESS) && is_enabled(CSR_SCOUNTEREN_ADDRESS),
+            };
+            if !is_permitted {
+                return Err(illegal_instruction);
+            }
         }
+        // stimecmp exists only with Sstc. Machine mode permits the lower
+        // privilege modes to access it with menvcfg.STCE and mcounteren.TM.
+        if matches!(address, CSR_STIMECMP_ADDRESS | CSR_STIMECMPH_ADDRESS)
+            && (!self.sstc
+                || (!matches!(self.privilege_mode, PrivilegeMode::Machine)
+                    && ((self.csr[CSR_MENVCFG_ADDRESS as usize] & MENVCFG_STCE) == 0
+                        || (self.csr[CSR_MCOUNTEREN_ADDRESS as usize] & COUNTEREN_TM) == 0)))
+        {
+            return Err(illegal_instruction);
+        }
+        // The fields of the extensions in the ISA string. menvcfg holds
+        // only the lower half of them in 32-bit mode.
+        let menvcfg_fields = match self.svadu {
+            true => MENVCFG_ADUE,
+            false => 0,
+        } | match self.sstc {
+            true => MENVCFG_STCE,
+            false => 0,
+        };
+        let write_mask = match address {
+            CSR_MENVCFG_ADDRESS if matches!(self.xlen, Xlen::Bit64) => write_mask | menvcfg_fields,
+            CSR_MENVCFGH_ADDRESS => write_mask | (menvcfg_fields >> 32),
+            // mip.STIP reflects stimecmp while it's enabled
+            CSR_MIP_ADDRESS if (self.csr[CSR_MENVCFG_ADDRESS as usize] & MENVCFG_STCE) != 0 => {
+                write_mask & !MIP_STIP
+            }
+            _ => write_mask,
+        };
+        Ok(write_mask)
+    }
+
+    fn read_csr(&mut self, address: u16) -> Result<u64, Trap> {
+        self.check_csr_access(address, false)?;
+        Ok(self.read_csr_raw(address))
     }
 
     fn write_csr(&mut self, address: u16, value: u64) -> Result<(), Trap> {
-        match self.has_csr_access_privilege(address) {
-            true => {
-                /*
-                // Checking writability fails some tests so disabling so far
-                let read_only = ((address >> 10) & 0x3) == 0x3;
-                if read_only {
-                    return Err(Exception::IllegalInstruction);
-                }
-                */
-                self.write_csr_raw(address, value);
-                if address == CSR_SATP_ADDRESS {
-                    self.update_addressing_mode(value);
-                }
-                Ok(())
+        let write_mask = self.check_csr_access(address, true)?;
+        let value = (self.read_csr_raw(address) & !write_mask) | (value & write_mask);
+        match address {
+            CSR_SATP_ADDRESS => {
+                // Writing an unsupported mode has no effect
+                match self.get_addressing_mode(value) {
+                    Some(addressing_mode) => self.update_addressing_mode(addressing_mode, value),
+                    None => return Ok(()),
+                };
             }
-            false => Err(Trap {
-                trap_type: TrapType::IllegalInstruction,
-                value: self.pc.wrapping_sub(4), // @TODO: Is this always correct?
-            }),
-        }
+            CSR_FFLAGS_ADDRESS | CSR_FRM_ADDRESS | CSR_FCSR_ADDRESS => {
+                self.csr[CSR_MSTATUS_ADDRESS as usize] |= MSTATUS_FS_DIRTY;
+            }
+            _ if is_vector(address) => {
+                self.csr[CSR_MSTATUS_ADDRESS as usize] |= MSTATUS_VS_DIRTY;
+            }
+            _ => {}
+        };
+        self.write_csr_raw(address, value);
+        Ok(())
     }
 
     // SSTATUS, SIE, and SIP are subsets of MSTATUS, MIE, and MIP
     fn read_csr_raw(&self, address: u16) -> u64 {
         match address {
-            // @TODO: Mask should consider of 32-bit mode
             CSR_FFLAGS_ADDRESS => self.csr[CSR_FCSR_ADDRESS as usize] & 0x1f,
             CSR_FRM_ADDRESS => (self.csr[CSR_FCSR_ADDRESS as usize] >> 5) & 0x7,
-            CSR_SSTATUS_ADDRESS => self.csr[CSR_MSTATUS_ADDRESS as usize] & 0x80000003000de162,
-            CSR_SIE_ADDRESS => self.csr[CSR_MIE_ADDRESS as usize] & 0x222,
-            CSR_SIP_ADDRESS => self.csr[CSR_MIP_ADDRESS as usize] & 0x222,
-            CSR_TIME_ADDRESS => self.mmu.get_clint().read_mtime(),
+            CSR_VXSAT_ADDRESS => self.csr[CSR_VCSR_ADDRESS as usize] & 0x1,
+            CSR_VXRM_ADDRESS => (self.csr[CSR_VCSR_ADDRESS as usize] >> 1) & 0x3,
+            CSR_VLENB_ADDRESS => self.vector.get_vlenb() as u64,
+            CSR_SSTATUS_ADDRESS => self.read_csr_raw(CSR_MSTATUS_ADDRESS) & self.get_sstatus_mask(),
+            CSR_SIE_ADDRESS => self.csr[CSR_MIE_ADDRESS as usize] & SUPERVISOR_INTERRUPTS,
+            CSR_SIP_ADDRESS => self.csr[CSR_MIP_ADDRESS as usize] & SUPERVISOR_INTERRUPTS,
+            CSR_MSTATUS_ADDRESS => {
+                // SD summarizes the dirty floating-point and vector state
+                let status = self.csr[address as usize];
+                let sd = match self.xlen {
+                    Xlen::Bit32 => 1 << 31,
+                    Xlen::Bit64 => 1 << 63,
+                };
+                match (status & MSTATUS_FS_DIRTY) == MSTATUS_FS_DIRTY
+                    || (status & MSTATUS_VS_DIRTY) == MSTATUS_VS_DIRTY
+                {
+                    true => status | sd,
+                    false => status & !sd,
+                }
+            }
+            CSR_TIME_ADDRESS => self.mmu.read_mtime(),
+            CSR_MENVCFGH_ADDRESS => self.csr[CSR_MENVCFG_ADDRESS as usize] >> 32,
+            CSR_STIMECMPH_ADDRESS => self.csr[CSR_STIMECMP_ADDRESS as usize] >> 32,
+            CSR_PMPCFG0_ADDRESS..=CSR_PMPCFG15_ADDRESS => {
+                let pmp = self.mmu.get_pmp();
+                self.get_pmp_config_entries(address)
+                    .rev()
+                    .fold(0, |value, index| {
+                        (value << 8) | pmp.read_config(index) as u64
+                    })
+            }
+            CSR_PMPADDR0_ADDRESS..=CSR_PMPADDR63_ADDRESS => self
+                .mmu
+                .get_pmp()
+                .read_address((address - CSR_PMPADDR0_ADDRESS) as usize),
+            // User mode counters are the read-only shadows of Machine
+            // mode ones, except for time
+            CSR_CYCLE_ADDRESS..=CSR_HPMCOUNTER31_ADDRESS => {
+                self.csr[(address - CSR_CYCLE_ADDRESS + CSR_MCYCLE_ADDRESS) as usize]
+            }
+            CSR_MCYCLEH_ADDRESS..=CSR_MHPMCOUNTER31H_ADDRESS
+            | CSR_CYCLEH_ADDRESS..=CSR_HPMCOUNTER31H_ADDRESS => {
+                self.read_csr_raw(address - CSR_UPPER_HALF_OFFSET) >> 32
+            }
             _ => self.csr[address as usize],
         }
     }
 
+    /// Returns `mstatus` bits visible in `sstatus`. SD is at the top and
+    /// UXL doesn't exist in 32-bit mode.
+    fn get_sstatus_mask(&self) -> u64 {
+        match self.xlen {
+            Xlen::Bit32 => (SSTATUS_MASK & 0xffffffff) | (1 << 31),
+            Xlen::Bit64 => SSTATUS_MASK,
+        }
+    }
+
     fn write_csr_raw(&mut self, address: u16, value: u64) {
         match address {
             CSR_FFLAGS_ADDRESS => {
@@ -761,30 +1197,76 @@ impl Cpu {
                 self.csr[CSR_FCSR_ADDRESS as usize] &= !0xe0;
                 self.csr[CSR_FCSR_ADDRESS as usize] |= (value << 5) & 0xe0;
             }
+            CSR_VXSAT_ADDRESS => {
+                self.csr[CSR_VCSR_ADDRESS as usize] &= !0x1;
+                self.csr[CSR_VCSR_ADDRESS as usize] |= value & 0x1;
+            }
+            CSR_VXRM_ADDRESS => {
+                self.csr[CSR_VCSR_ADDRESS as usize] &= !0x6;
+                self.csr[CSR_VCSR_ADDRESS as usize] |= (value << 1) & 0x6;
+            }
             CSR_SSTATUS_ADDRESS => {
-                self.csr[CSR_MSTATUS_ADDRESS as usize] &= !0x80000003000de162;
-                self.csr[CSR_MSTATUS_ADDRESS as usize] |= value & 0x80000003000de162;
+                let mask = self.get_sstatus_mask();
+                self.csr[CSR_MSTATUS_ADDRESS as usize] &= !mask;
+                self.csr[CSR_MSTATUS_ADDRESS as usize] |= value & mask;
                 self.mmu
                     .update_mstatus(self.read_csr_raw(CSR_MSTATUS_ADDRESS));
             }
             CSR_SIE_ADDRESS => {
-                self.csr[CSR_MIE_ADDRESS as usize] &= !0x222;
-                self.csr[CSR_MIE_ADDRESS as usize] |= value & 0x222;
+                self.csr[CSR_MIE_ADDRESS as usize] &= !SUPERVISOR_INTERRUPTS;
+                self.csr[CSR_MIE_ADDRESS as usize] |= value & SUPERVISOR_INTERRUPTS;
             }
             CSR_SIP_ADDRESS => {
-                self.csr[CSR_MIP_ADDRESS as usize] &= !0x222;
-                self.csr[CSR_MIP_ADDRESS as usize] |= value & 0x222;
-            }
-            CSR_MIDELEG_ADDRESS => {
-                self.csr[address as usize] = value & 0x666; // from qemu
+                self.csr[CSR_MIP_ADDRESS as usize] &= !SUPERVISOR_INTERRUPTS;
+                self.csr[CSR_MIP_ADDRESS as usize] |= value & SUPERVISOR_INTERRUPTS;
             }
             CSR_MSTATUS_ADDRESS => {
+                // MPP is WARL. The reserved encoding keeps the former value.
+                let value = match (value >> 11) & 0x3 {
+                    2 => (value & !0x1800) | (self.csr[address as usize] & 0x1800),
+                    _ => value,
+                };
                 self.csr[address as usize] = value;
                 self.mmu
                     .update_mstatus(self.read_csr_raw(CSR_MSTATUS_ADDRESS));
             }
-            CSR_TIME_ADDRESS => {
-                self.mmu.get_mut_clint().write_mtime(value);
+            CSR_MENVCFG_ADDRESS => {
+                self.csr[address as usize] = value;
+                self.mmu.update_menvcfg(value);
+            }
+            CSR_MENVCFGH_ADDRESS => {
+                let menvcfg = (self.csr[CSR_MENVCFG_ADDRESS as usize] & 0xffffffff) | (value << 32);
+                self.write_csr_raw(CSR_MENVCFG_ADDRESS, menvcfg);
+            }
+            CSR_STIMECMPH_ADDRESS => {
+                let stimecmp =
+                    (self.csr[CSR_STIMECMP_ADDRESS as usize] & 0xffffffff) | (value << 32);
+                self.csr[CSR_STIMECMP_ADDRESS as usize] = stimecmp;
+            }
+            CSR_PMPCFG0_ADDRESS..=CSR_PMPCFG15_ADDRESS => {
+                for (i, index) in self.get_pmp_config_entries(address).enumerate() {
+                    self.mmu.write_pmp_config(index, (value >> (i * 8)) as u8);
+                }
+            }
+            CSR_PMPADDR0_ADDRESS..=CSR_PMPADDR63_ADDRESS => {
+                let index = (address - CSR_PMPADDR0_ADDRESS) as usize;
+                self.mmu.write_pmp_address(index, value);
+            }
+            CSR_MCOUNTINHIBIT_ADDRESS => {
+                self.csr[address as usize] = value;
+                self.update_active_hpm_counters();
+            }
+            CSR_MHPMEVENT3_ADDRESS..=CSR_MHPMEVENT31_ADDRESS => {
+                // WARL, unsupported events select no event
+                self.csr[address as usize] = match counter::is_supported_event(value) {
+                    true => value,
+                    false => 0,
+                };
+                self.update_active_hpm_counters();
+            }
+            CSR_MCYCLEH_ADDRESS..=CSR_MHPMCOUNTER31H_ADDRESS => {
+                let lower = (address - CSR_UPPER_HALF_OFFSET) as usize;
+                self.csr[lower] = (self.csr[lower] & 0xffffffff) | (value << 32);
             }
             _ => {
                 self.csr[address as usize] = value;
@@ -792,78 +1274,902 @@ impl Cpu {
         };
     }
 
-    fn _set_fcsr_nv(&mut self) {
-        self.csr[CSR_FCSR_ADDRESS as usize] |= 0x10;
+    /// Returns the indices of the PMP entries a `pmpcfg` CSR holds,
+    /// four in 32-bit mode and eight in 64-bit mode.
+    ///
+    /// # Arguments
+    /// * `address` `pmpcfg` CSR address
+    fn get_pmp_config_entries(&self, address: u16) -> std::ops::Range<usize> {
+        let first = (address - CSR_PMPCFG0_ADDRESS) as usize * 4;
+        let count = match self.xlen {
+            Xlen::Bit32 => 4,
+            Xlen::Bit64 => 8,
+        };
+        first..(first + count).min(PMP_ENTRY_NUM)
     }
 
-    fn set_fcsr_dz(&mut self) {
-        self.csr[CSR_FCSR_ADDRESS as usize] |= 0x8;
+    /// Performs an atomic memory operation, `AMO*` instruction. Loads
+    /// the value at the address in `rs1`, stores the result of `operation`
+    /// on it and `rs2`, and writes the loaded value to `rd`. The operands of
+    /// word operations are sign-extended. The load and the store happen
+    /// in a single step, so the `aq` and `rl` ordering bits need nothing.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `width` 4 or 8
+    /// * `operation` Takes the loaded value and `rs2`
+    fn atomic_memory_operation(
+        &mut self,
+        word: u32,
+        width: u64,
+        operation: fn(i64, i64) -> i64,
+    ) -> Result<(), Trap> {
+        let f = parse_format_r(word);
+        let address = self.x[f.rs1] as u64;
+        if !address.is_multiple_of(width) {
+            return Err(Trap {
+                trap_type: TrapType::StoreAddressMisaligned,
+                value: address,
+            });
+        }
+        // AMOs raise store page and access faults even if the load fails
+        let loaded = match width {
+            4 => self.mmu.load_word(address).map(|data| data as i32 as i64),
+            _ => self.mmu.load_doubleword(address).map(|data| data as i64),
+        }
+        .map_err(|trap| match trap.trap_type {
+            TrapType::LoadPageFault => Trap {
+                trap_type: TrapType::StorePageFault,
+                value: trap.value,
+            },
+            TrapType::LoadAccessFault => Trap {
+                trap_type: TrapType::StoreAccessFault,
+                value: trap.value,
+            },
+            _ => trap,
+        })?;
+        let operand = match width {
+            4 => self.x[f.rs2] as i32 as i64,
+            _ => self.x[f.rs2],
+        };
+        let result = operation(loaded, operand);
+        match width {
+            4 => self.mmu.store_word(address, result as u32)?,
+            _ => self.mmu.store_doubleword(address, result as u64)?,
+        };
+        self.x[f.rd] = loaded;
+        Ok(())
     }
 
-    fn _set_fcsr_of(&mut self) {
-        self.csr[CSR_FCSR_ADDRESS as usize] |= 0x4;
+    /// Performs `LR` instruction, loads the value at the address in `rs1`
+    /// to `rd` and reserves the address.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `width` 4 or 8
+    fn load_reserved(&mut self, word: u32, width: u64) -> Result<(), Trap> {
+        let f = parse_format_r(word);
+        let address = self.x[f.rs1] as u64;
+        if !address.is_multiple_of(width) {
+            return Err(Trap {
+                trap_type: TrapType::LoadAddressMisaligned,
+                value: address,
+            });
+        }
+        let data = self.mmu.load_reserved(address, width)?;
+        self.x[f.rd] = match width {
+            4 => data as i32 as i64,
+            _ => data as i64,
+        };
+        Ok(())
     }
 
-    fn _set_fcsr_uf(&mut self) {
-        self.csr[CSR_FCSR_ADDRESS as usize] |= 0x2;
+    /// Performs `SC` instruction, stores `rs2` to the address in `rs1` if
+    /// the reservation by `LR` is still valid. Writes zero to `rd` on
+    /// success, one on failure.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `width` 4 or 8
+    fn store_conditional(&mut self, word: u32, width: u64) -> Result<(), Trap> {
+        let f = parse_format_r(word);
+        let address = self.x[f.rs1] as u64;
+        if !address.is_multiple_of(width) {
+            return Err(Trap {
+                trap_type: TrapType::StoreAddressMisaligned,
+                value: address,
+            });
+        }
+        let stored = self
+            .mmu
+            .store_conditional(address, self.x[f.rs2] as u64, width)?;
+        self.x[f.rd] = match stored {
+            true => 0,
+            false => 1,
+        };
+        Ok(())
     }
 
-    fn _set_fcsr_nx(&mut self) {
-        self.csr[CSR_FCSR_ADDRESS as usize] |= 0x1;
+    /// Accrues floating-point exception flags to `fflags`.
+    ///
+    /// # Arguments
+    /// * `flags` `fpu::FLAG_*` bits
+    fn accrue_fflags(&mut self, flags: u8) {
+        if flags != 0 {
+            self.csr[CSR_FCSR_ADDRESS as usize] |= flags as u64;
+            self.csr[CSR_MSTATUS_ADDRESS as usize] |= MSTATUS_FS_DIRTY;
+        }
     }
 
-    fn update_addressing_mode(&mut self, value: u64) {
-        let addressing_mode = match self.xlen {
-            Xlen::Bit32 => match value & 0x80000000 {
-                0 => AddressingMode::None,
-                _ => AddressingMode::SV32,
-            },
-            Xlen::Bit64 => match value >> 60 {
-                0 => AddressingMode::None,
-                8 => AddressingMode::SV39,
-                9 => AddressingMode::SV48,
-                _ => {
-                    println!("Unknown addressing_mode {:x}", value >> 60);
-                    panic!();
-                }
-            },
-        };
-        let ppn = match self.xlen {
-            Xlen::Bit32 => value & 0x3fffff,
-            Xlen::Bit64 => value & 0xfffffffffff,
+    /// Returns the rounding mode of a floating-point instruction.
+    /// The dynamic rounding mode (`rm` = 7) refers to `frm`.
+    /// Reserved rounding modes raise an illegal instruction exception.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    fn get_rounding_mode(&self, word: u32) -> Result<RoundingMode, Trap> {
+        let rm = match (word >> 12) & 0x7 {
+            7 => (self.csr[CSR_FCSR_ADDRESS as usize] >> 5) & 0x7,
+            rm => rm as u64,
         };
-        self.mmu.update_addressing_mode(addressing_mode);
-        self.mmu.update_ppn(ppn);
+        RoundingMode::from_encoding(rm).ok_or(Trap {
+            trap_type: TrapType::IllegalInstruction,
+            value: word as u64,
+        })
     }
 
-    // @TODO: Rename to better name?
-    fn sign_extend(&self, value: i64) -> i64 {
-        match self.xlen {
-            Xlen::Bit32 => value as i32 as i64,
-            Xlen::Bit64 => value,
+    /// Writes raw bits to a floating-point register and marks
+    /// the floating-point state dirty in `mstatus`.
+    fn write_f(&mut self, reg: usize, bits: u64) {
+        self.f[reg] = bits;
+        self.csr[CSR_MSTATUS_ADDRESS as usize] |= MSTATUS_FS_DIRTY;
+    }
+
+    /// Reads the bits of a single-precision value. A register not holding
+    /// a properly NaN-boxed value reads as the canonical NaN.
+    fn read_f32_bits(&self, reg: usize) -> u32 {
+        match self.f[reg] & F32_NAN_BOX == F32_NAN_BOX {
+            true => self.f[reg] as u32,
+            false => fpu::CANONICAL_NAN_F32,
         }
     }
 
-    // @TODO: Rename to better name?
-    fn unsigned_data(&self, value: i64) -> u64 {
-        (value as u64) & self.unsigned_data_mask
+    fn read_f32(&self, reg: usize) -> f32 {
+        f32::from_bits(self.read_f32_bits(reg))
     }
 
-    // @TODO: Rename to better name?
-    fn most_negative(&self) -> i64 {
-        match self.xlen {
-            Xlen::Bit32 => std::i32::MIN as i64,
-            Xlen::Bit64 => std::i64::MIN,
-        }
+    /// Writes a single-precision value NaN-boxed, the upper 32 bits set.
+    fn write_f32_bits(&mut self, reg: usize, bits: u32) {
+        self.write_f(reg, F32_NAN_BOX | bits as u64);
     }
 
-    // @TODO: Optimize
-    fn uncompress(&self, halfword: u32) -> u32 {
-        let op = halfword & 0x3; // [1:0]
-        let funct3 = (halfword >> 13) & 0x7; // [15:13]
+    fn write_f32(&mut self, reg: usize, value: f32) {
+        self.write_f32_bits(reg, value.to_bits());
+    }
 
-        match op {
-            0 => match funct3 {
+    fn read_f64(&self, reg: usize) -> f64 {
+        f64::from_bits(self.f[reg])
+    }
+
+    fn write_f64(&mut self, reg: usize, value: f64) {
+        self.write_f(reg, value.to_bits());
+    }
+
+    /// Returns the vector state for a vector instruction from `vtype`, `vl`,
+    /// `vstart`, `vxrm` and `frm`. Raises an illegal instruction exception
+    /// if the vector state is Off in `mstatus` or `vtype.vill` is set.
+    ///
+    /// # Arguments
+    /// * `float_width` Base 2 logarithm of the floating-point element width
+    ///   relative to SEW for the floating-point instructions
+    fn get_vector_context(&self, float_width: Option<i32>) -> Result<VectorContext, Trap> {
+        let vtype = VectorType::decode(self.csr[CSR_VTYPE_ADDRESS as usize]).ok_or(Trap {
+            trap_type: TrapType::IllegalInstruction,
+            value: 0,
+        })?;
+        let context = self.create_vector_context(vtype)?;
+        if let Some(width) = float_width {
+            context.check_float(width)?;
+        }
+        Ok(context)
+    }
+
+    /// Returns the vector state for a vector instruction with the element
+    /// width and the register group multiplier. Raises an illegal
+    /// instruction exception if the vector state is Off in `mstatus`.
+    ///
+    /// # Arguments
+    /// * `vtype`
+    fn create_vector_context(&self, vtype: VectorType) -> Result<VectorContext, Trap> {
+        if (self.csr[CSR_MSTATUS_ADDRESS as usize] & MSTATUS_VS) == 0 {
+            return Err(Trap {
+                trap_type: TrapType::IllegalInstruction,
+                value: 0,
+            });
+        }
+        Ok(VectorContext {
+            sew: vtype.sew,
+            lmul: vtype.lmul,
+            vl: self.csr[CSR_VL_ADDRESS as usize] as usize,
+            vstart: self.csr[CSR_VSTART_ADDRESS as usize] as usize,
+            vxrm: self.read_csr_raw(CSR_VXRM_ADDRESS),
+            rm: RoundingMode::from_encoding(self.read_csr_raw(CSR_FRM_ADDRESS)),
+            saturated: false,
+            flags: 0,
+        })
+    }
+
+    /// Finishes a vector instruction. Resets `vstart`, or sets it to
+    /// the element raising the exception, writes `vl` trimmed by
+    /// fault-only-first loads, accrues `vxsat` and `fflags`, and marks
+    /// the vector state dirty in `mstatus`.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `result` The result of the instruction
+    fn finish_vector(
+        &mut self,
+        context: &VectorContext,
+        result: Result<(), Trap>,
+    ) -> Result<(), Trap> {
+        if let Err(trap) = &result {
+            if trap.trap_type == TrapType::IllegalInstruction {
+                return result;
+            }
+        }
+        self.csr[CSR_VSTART_ADDRESS as usize] = match result {
+            Ok(()) => 0,
+            Err(_) => context.vstart as u64,
+        };
+        self.csr[CSR_VL_ADDRESS as usize] = context.vl as u64;
+        if context.saturated {
+            self.csr[CSR_VCSR_ADDRESS as usize] |= 0x1;
+        }
+        self.accrue_fflags(context.flags);
+        self.csr[CSR_MSTATUS_ADDRESS as usize] |= MSTATUS_VS_DIRTY;
+        result
+    }
+
+    /// Reads the second source operand of a vector arithmetic instruction.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `operand` Where the operand is
+    /// * `sew` Element width in bits
+    fn read_vector_operand(&self, word: u32, operand: VectorOperand, sew: usize) -> Operand {
+        let rs1 = ((word >> 15) & 0x1f) as usize;
+        match operand {
+            VectorOperand::Vector => Operand::Vector(rs1),
+            VectorOperand::Integer => Operand::Scalar(self.x[rs1] as u64),
+            VectorOperand::Float => Operand::Scalar(match sew {
+                32 => self.read_f32_bits(rs1) as u64,
+                _ => self.f[rs1],
+            }),
+            VectorOperand::Immediate => Operand::Scalar((((word as i32) << 12) >> 27) as u64),
+            VectorOperand::UnsignedImmediate => Operand::Scalar(rs1 as u64),
+        }
+    }
+
+    /// Performs a vector arithmetic instruction, `vd[i] = op(vs2[i], rhs[i])`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `operand` Where the second source operand is
+    /// * `widths` Element widths of the operands
+    /// * `operation`
+    fn vector_binary(
+        &mut self,
+        word: u32,
+        operand: VectorOperand,
+        widths: Widths,
+        operation: BinaryOperation,
+    ) -> Result<(), Trap> {
+        let mut context = self.get_vector_context(get_vector_float_width(word))?;
+        let f = parse_format_v(word);
+        let rhs = self.read_vector_operand(word, operand, context.sew);
+        let result = self
+            .vector
+            .binary(&mut context, f.vd, f.vs2, rhs, f.vm, widths, operation);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs a vector multiply-add instruction,
+    /// `vd[i] = op(vs2[i], rhs[i], vd[i])`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `operand` Where the second source operand is
+    /// * `widths` Element widths of the operands
+    /// * `operation`
+    fn vector_ternary(
+        &mut self,
+        word: u32,
+        operand: VectorOperand,
+        widths: Widths,
+        operation: TernaryOperation,
+    ) -> Result<(), Trap> {
+        let mut context = self.get_vector_context(get_vector_float_width(word))?;
+        let f = parse_format_v(word);
+        let rhs = self.read_vector_operand(word, operand, context.sew);
+        let result = self
+            .vector
+            .ternary(&mut context, f.vd, f.vs2, rhs, f.vm, widths, operation);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs a vector compare instruction writing a mask register.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `operand` Where the second source operand is
+    /// * `operation`
+    fn vector_compare(
+        &mut self,
+        word: u32,
+        operand: VectorOperand,
+        operation: CompareOperation,
+    ) -> Result<(), Trap> {
+        let mut context = self.get_vector_context(get_vector_float_width(word))?;
+        let f = parse_format_v(word);
+        let rhs = self.read_vector_operand(word, operand, context.sew);
+        let result = self
+            .vector
+            .compare(&mut context, f.vd, f.vs2, rhs, f.vm, operation);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs a vector instruction with a single source operand `vs2`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `widths` Element widths of the operands
+    /// * `operation`
+    fn vector_unary(
+        &mut self,
+        word: u32,
+        widths: Widths,
+        operation: UnaryOperation,
+    ) -> Result<(), Trap> {
+        let mut context = self.get_vector_context(get_vector_float_width(word))?;
+        let f = parse_format_v(word);
+        let result = self
+            .vector
+            .unary(&mut context, f.vd, f.vs2, f.vm, widths, operation);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs a vector conversion instruction from or to floating-point.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `widths` Element widths of the operands
+    /// * `float_width` Base 2 logarithm of the floating-point element width
+    ///   relative to SEW
+    /// * `operation`
+    fn vector_convert(
+        &mut self,
+        word: u32,
+        widths: Widths,
+        float_width: i32,
+        operation: UnaryOperation,
+    ) -> Result<(), Trap> {
+        let mut context = self.get_vector_context(Some(float_width))?;
+        let f = parse_format_v(word);
+        let result = self
+            .vector
+            .unary(&mut context, f.vd, f.vs2, f.vm, widths, operation);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs `VADC` and `VSBC`, taking the carry or borrow in `v0`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `operand` Where the second source operand is
+    /// * `operation`
+    fn vector_carry(
+        &mut self,
+        word: u32,
+        operand: VectorOperand,
+        operation: CarryOperation,
+    ) -> Result<(), Trap> {
+        let mut context = self.get_vector_context(None)?;
+        let f = parse_format_v(word);
+        let rhs = self.read_vector_operand(word, operand, context.sew);
+        let result = self.vector.carry(&mut context, f.vd, f.vs2, rhs, operation);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs `VMADC` and `VMSBC`, writing the carry or borrow out to
+    /// a mask register.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `operand` Where the second source operand is
+    /// * `operation`
+    fn vector_carry_out(
+        &mut self,
+        word: u32,
+        operand: VectorOperand,
+        operation: CarryOutOperation,
+    ) -> Result<(), Trap> {
+        let mut context = self.get_vector_context(None)?;
+        let f = parse_format_v(word);
+        let rhs = self.read_vector_operand(word, operand, context.sew);
+        let result = self
+            .vector
+            .carry_out(&mut context, f.vd, f.vs2, rhs, f.vm, operation);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs `VMERGE`, `VFMERGE`, `VMV.V` and `VFMV.V.F`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `operand` Where the source operand is
+    fn vector_merge(&mut self, word: u32, operand: VectorOperand) -> Result<(), Trap> {
+        let mut context = self.get_vector_context(get_vector_float_width(word))?;
+        let f = parse_format_v(word);
+        let rhs = self.read_vector_operand(word, operand, context.sew);
+        let result = self.vector.merge(&mut context, f.vd, f.vs2, rhs, f.vm);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs a vector reduction instruction, `vd[0]` is the reduction
+    /// of `vs1[0]` and the active elements of `vs2`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `widening` 2*SEW wide result if true
+    /// * `operation` Takes the accumulator and an element
+    fn vector_reduce(
+        &mut self,
+        word: u32,
+        widening: bool,
+        operation: BinaryOperation,
+    ) -> Result<(), Trap> {
+        let mut context = self.get_vector_context(get_vector_float_width(word))?;
+        let f = parse_format_v(word);
+        let result =
+            self.vector
+                .reduce(&mut context, f.vd, f.vs2, f.vs1, f.vm, widening, operation);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs `VMV.X.S` and `VFMV.F.S`, copies the first element of `vs2`
+    /// to `rd`, sign-extended or NaN-boxed.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `is_float` `VFMV.F.S` if true
+    fn vector_move_to_scalar(&mut self, word: u32, is_float: bool) -> Result<(), Trap> {
+        let context = self.get_vector_context(get_vector_float_width(word))?;
+        let f = parse_format_v(word);
+        let value = self.vector.read_element(f.vs2, 0, context.sew);
+        match (is_float, context.sew) {
+            (true, 32) => self.write_f32_bits(f.vd, value as u32),
+            (true, _) => self.write_f(f.vd, value),
+            (false, _) => self.x[f.vd] = self.sign_extend(vector::sign_extend(value, context.sew)),
+        };
+        self.finish_vector(&context, Ok(()))
+    }
+
+    /// Performs `VMV.S.X` and `VFMV.S.F`, copies the scalar to the first
+    /// element of `vd`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `operand` Where the source operand is
+    fn vector_move_from_scalar(&mut self, word: u32, operand: VectorOperand) -> Result<(), Trap> {
+        let context = self.get_vector_context(get_vector_float_width(word))?;
+        let f = parse_format_v(word);
+        if let Operand::Scalar(value) = self.read_vector_operand(word, operand, context.sew) {
+            self.vector.move_from_scalar(&context, f.vd, value);
+        }
+        self.finish_vector(&context, Ok(()))
+    }
+
+    /// Performs `VSLIDEUP` and `VSLIDEDOWN`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `operand` Where the offset is
+    /// * `up` `VSLIDEUP` if true
+    fn vector_slide(&mut self, word: u32, operand: VectorOperand, up: bool) -> Result<(), Trap> {
+        let context = self.get_vector_context(None)?;
+        let f = parse_format_v(word);
+        let offset = match self.read_vector_operand(word, operand, context.sew) {
+            Operand::Scalar(offset) => self.unsigned_data(offset as i64),
+            Operand::Vector(_) => 0,
+        };
+        let result = match up {
+            true => self.vector.slide_up(&context, f.vd, f.vs2, offset, f.vm),
+            false => self.vector.slide_down(&context, f.vd, f.vs2, offset, f.vm),
+        };
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs `VSLIDE1UP`, `VSLIDE1DOWN`, `VFSLIDE1UP` and `VFSLIDE1DOWN`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `operand` Where the scalar is
+    /// * `up` Slides up if true
+    fn vector_slide1(&mut self, word: u32, operand: VectorOperand, up: bool) -> Result<(), Trap> {
+        let context = self.get_vector_context(get_vector_float_width(word))?;
+        let f = parse_format_v(word);
+        let value = match self.read_vector_operand(word, operand, context.sew) {
+            Operand::Scalar(value) => value,
+            Operand::Vector(_) => 0,
+        };
+        let result = match up {
+            true => self.vector.slide1_up(&context, f.vd, f.vs2, value, f.vm),
+            false => self.vector.slide1_down(&context, f.vd, f.vs2, value, f.vm),
+        };
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs `VRGATHER` and `VRGATHEREI16`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `operand` Where the indices are
+    /// * `ei16` `VRGATHEREI16` if true, the indices are 16 bits wide
+    fn vector_gather(&mut self, word: u32, operand: VectorOperand, ei16: bool) -> Result<(), Trap> {
+        let context = self.get_vector_context(None)?;
+        let f = parse_format_v(word);
+        let index = match self.read_vector_operand(word, operand, context.sew) {
+            Operand::Scalar(index) => Operand::Scalar(self.unsigned_data(index as i64)),
+            index => index,
+        };
+        let index_eew = match ei16 {
+            true => 16,
+            false => context.sew,
+        };
+        let result = self
+            .vector
+            .gather(&context, f.vd, f.vs2, index, index_eew, f.vm);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs a mask-register logical instruction like `VMAND.MM`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `operation` Takes the bits of `vs2` and `vs1`
+    fn vector_mask_logical(
+        &mut self,
+        word: u32,
+        operation: fn(bool, bool) -> bool,
+    ) -> Result<(), Trap> {
+        let mut context = self.get_vector_context(None)?;
+        let f = parse_format_v(word);
+        let result = self
+            .vector
+            .mask_logical(&mut context, f.vd, f.vs2, f.vs1, operation);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs `VCPOP.M` and `VFIRST.M`, writes the population count or
+    /// the index of the first set element of the mask register to `rd`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `find_first` `VFIRST.M` if true
+    fn vector_mask_to_scalar(&mut self, word: u32, find_first: bool) -> Result<(), Trap> {
+        let context = self.get_vector_context(None)?;
+        let f = parse_format_v(word);
+        self.x[f.vd] = match find_first {
+            true => self.vector.find_first(&context, f.vs2, f.vm)?,
+            false => self.vector.count_population(&context, f.vs2, f.vm)? as i64,
+        };
+        self.finish_vector(&context, Ok(()))
+    }
+
+    /// Performs `VMSBF.M`, `VMSIF.M` and `VMSOF.M`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `before` Sets the bits before the first set element if true
+    /// * `including` Sets the bit of the first set element if true
+    fn vector_set_first(&mut self, word: u32, before: bool, including: bool) -> Result<(), Trap> {
+        let context = self.get_vector_context(None)?;
+        let f = parse_format_v(word);
+        let result = self
+            .vector
+            .set_first(&context, f.vd, f.vs2, f.vm, before, including);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs `VIOTA.M` and `VID.V`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `is_index` `VID.V` if true
+    fn vector_iota(&mut self, word: u32, is_index: bool) -> Result<(), Trap> {
+        let context = self.get_vector_context(None)?;
+        let f = parse_format_v(word);
+        let result = match is_index {
+            true => self.vector.element_index(&context, f.vd, f.vm),
+            false => self.vector.iota(&context, f.vd, f.vs2, f.vm),
+        };
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs `VCOMPRESS.VM`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    fn vector_compress(&mut self, word: u32) -> Result<(), Trap> {
+        let context = self.get_vector_context(None)?;
+        let f = parse_format_v(word);
+        let result = self.vector.compress(&context, f.vd, f.vs2, f.vs1);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs `VMV<nr>R.V`. The elements are SEW wide, or 8 bits wide if
+    /// `vtype.vill` is set, for `vstart`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    fn vector_move_whole_register(&mut self, word: u32) -> Result<(), Trap> {
+        let vtype = VectorType::decode(self.csr[CSR_VTYPE_ADDRESS as usize])
+            .unwrap_or(VectorType { sew: 8, lmul: 0 });
+        let context = self.create_vector_context(vtype)?;
+        let f = parse_format_v(word);
+        let result = self
+            .vector
+            .move_whole_register(&context, f.vd, f.vs2, f.vs1 + 1);
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs a vector load other than the whole register loads and
+    /// `VLM.V`. The addressing mode, the element width and the number of
+    /// segment fields are encoded in the instruction.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `fault_only_first`
+    fn vector_load(&mut self, word: u32, fault_only_first: bool) -> Result<(), Trap> {
+        let mut context = self.get_vector_context(None)?;
+        let f = parse_format_v(word);
+        let addressing = self.get_vector_addressing(word);
+        let nf = (word >> 29) as usize + 1;
+        let base = self.x[f.vs1] as u64;
+        let result = self.vector.load(
+            &mut context,
+            &mut self.mmu,
+            f.vd,
+            base,
+            addressing,
+            nf,
+            f.vm,
+            fault_only_first,
+        );
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs a vector store other than the whole register stores and
+    /// `VSM.V`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    fn vector_store(&mut self, word: u32) -> Result<(), Trap> {
+        let mut context = self.get_vector_context(None)?;
+        let f = parse_format_v(word);
+        let addressing = self.get_vector_addressing(word);
+        let nf = (word >> 29) as usize + 1;
+        let base = self.x[f.vs1] as u64;
+        let result = self.vector.store(
+            &mut context,
+            &mut self.mmu,
+            f.vd,
+            base,
+            addressing,
+            nf,
+            f.vm,
+        );
+        self.finish_vector(&context, result)
+    }
+
+    /// Returns the addressing mode of a vector load or store from `mop`
+    /// and the width fields of the instruction.
+    fn get_vector_addressing(&self, word: u32) -> Addressing {
+        let eew = get_vector_memory_eew(word);
+        match (word >> 26) & 0x3 {
+            0 => Addressing::UnitStride { eew },
+            2 => Addressing::Strided {
+                eew,
+                stride: self.x[((word >> 20) & 0x1f) as usize] as u64,
+            },
+            _ => Addressing::Indexed {
+                vs2: ((word >> 20) & 0x1f) as usize,
+                eew,
+            },
+        }
+    }
+
+    /// Performs the whole register loads and stores, `VL<nr>RE<eew>.V` and
+    /// `VS<nr>R.V`, which don't depend on `vtype`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `is_store`
+    fn vector_whole_register_memory(&mut self, word: u32, is_store: bool) -> Result<(), Trap> {
+        let eew = get_vector_memory_eew(word);
+        let mut context = self.create_vector_context(VectorType { sew: eew, lmul: 0 })?;
+        let f = parse_format_v(word);
+        let count = (word >> 29) as usize + 1;
+        let base = self.x[f.vs1] as u64;
+        let result = match is_store {
+            true => {
+                self.vector
+                    .store_whole_register(&mut context, &mut self.mmu, f.vd, base, count)
+            }
+            false => {
+                self.vector
+                    .load_whole_register(&mut context, &mut self.mmu, f.vd, base, eew, count)
+            }
+        };
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs `VLM.V` and `VSM.V`, the mask register loads and stores.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `is_store`
+    fn vector_mask_memory(&mut self, word: u32, is_store: bool) -> Result<(), Trap> {
+        let mut context = self.get_vector_context(None)?;
+        let f = parse_format_v(word);
+        let base = self.x[f.vs1] as u64;
+        let result = match is_store {
+            true => self
+                .vector
+                .store_mask(&mut context, &mut self.mmu, f.vd, base),
+            false => self
+                .vector
+                .load_mask(&mut context, &mut self.mmu, f.vd, base),
+        };
+        self.finish_vector(&context, result)
+    }
+
+    /// Performs `VSETVLI`, `VSETIVLI` and `VSETVL`. Sets `vtype`, and `vl`
+    /// to the application vector length capped to VLMAX, and writes `vl`
+    /// to `rd`. An unsupported `vtype` sets `vtype.vill` and clears `vl`.
+    ///
+    /// # Arguments
+    /// * `word` Instruction word
+    /// * `vtype` New `vtype` value
+    /// * `avl` Application vector length, `None` to keep `vl`
+    fn set_vector_configuration(
+        &mut self,
+        word: u32,
+        vtype: u64,
+        avl: Option<u64>,
+    ) -> Result<(), Trap> {
+        if (self.csr[CSR_MSTATUS_ADDRESS as usize] & MSTATUS_VS) == 0 {
+            return Err(Trap {
+                trap_type: TrapType::IllegalInstruction,
+                value: 0,
+            });
+        }
+        let f = parse_format_v(word);
+        let (vtype, vl) = match VectorType::decode(vtype) {
+            Some(decoded) => {
+                let vlmax = decoded.get_vlmax(self.vector.get_vlen()) as u64;
+                let vl = avl.unwrap_or(self.csr[CSR_VL_ADDRESS as usize]).min(vlmax);
+                (vtype, vl)
+            }
+            None => (1 << (self.get_xlen_bits() - 1), 0),
+        };
+        self.csr[CSR_VTYPE_ADDRESS as usize] = vtype;
+        self.csr[CSR_VL_ADDRESS as usize] = vl;
+        self.csr[CSR_VSTART_ADDRESS as usize] = 0;
+        self.csr[CSR_MSTATUS_ADDRESS as usize] |= MSTATUS_VS_DIRTY;
+        self.x[f.vd] = vl as i64;
+        Ok(())
+    }
+
+    /// Returns the application vector length of `VSETVLI` and `VSETVL`.
+    /// `rs1` other than `x0` holds it. `rs1` = `x0` requests VLMAX unless
+    /// `rd` is `x0` too, which keeps `vl`.
+    fn get_application_vector_length(&self, word: u32) -> Option<u64> {
+        let f = parse_format_v(word);
+        match (f.vs1, f.vd) {
+            (0, 0) => None,
+            (0, _) => Some(u64::MAX),
+            (rs1, _) => Some(self.unsigned_data(self.x[rs1])),
+        }
+    }
+
+    /// Returns the addressing mode `satp` value specifies,
+    /// `None` if it's unsupported.
+    fn get_addressing_mode(&self, value: u64) -> Option<AddressingMode> {
+        let mode = match self.xlen {
+            Xlen::Bit32 => match value & 0x80000000 {
+                0 => AddressingMode::None,
+                _ => AddressingMode::SV32,
+            },
+            Xlen::Bit64 => match value >> 60 {
+                0 => AddressingMode::None,
+                8 => AddressingMode::SV39,
+                9 => AddressingMode::SV48,
+                10 => AddressingMode::SV57,
+                _ => return None,
+            },
+        };
+        match self.mmu.is_addressing_mode_supported(&mode) {
+            true => Some(mode),
+            false => None,
+        }
+    }
+
+    fn update_addressing_mode(&mut self, addressing_mode: AddressingMode, value: u64) {
+        let ppn = match self.xlen {
+            Xlen::Bit32 => value & 0x3fffff,
+            Xlen::Bit64 => value & 0xfffffffffff,
+        };
+        self.mmu.update_addressing_mode(addressing_mode);
+        self.mmu.update_ppn(ppn);
+    }
+
+    // @TODO: Rename to better name?
+    fn sign_extend(&self, value: i64) -> i64 {
+        match self.xlen {
+            Xlen::Bit32 => value as i32 as i64,
+            Xlen::Bit64 => value,
+        }
+    }
+
+    // @TODO: Rename to better name?
+    fn unsigned_data(&self, value: i64) -> u64 {
+        (value as u64) & self.unsigned_data_mask
+    }
+
+    /// Returns XLEN in bits
+    fn get_xlen_bits(&self) -> u32 {
+        match self.xlen {
+            Xlen::Bit32 => 32,
+            Xlen::Bit64 => 64,
+        }
+    }
+
+    /// Raises an illegal instruction exception unless XLEN is the one
+    /// the instruction is defined for. `tick_operate()` sets the instruction
+    /// bits to the trap value.
+    ///
+    /// # Arguments
+    /// * `bits` XLEN in bits, 32 or 64
+    fn check_xlen(&self, bits: u32) -> Result<(), Trap> {
+        match self.get_xlen_bits() == bits {
+            true => Ok(()),
+            false => Err(Trap {
+                trap_type: TrapType::IllegalInstruction,
+                value: 0,
+            }),
+        }
+    }
+
+    // @TODO: Rename to better name?
+    fn most_negative(&self) -> i64 {
+        match self.xlen {
+            Xlen::Bit32 => i32::MIN as i64,
+            Xlen::Bit64 => i64::MIN,
+        }
+    }
+
+    // @TODO: Optimize
+    fn uncompress(&self, halfword: u32) -> u32 {
+        // Compressed instructions are illegal without the C extension
+        if (self.csr[CSR_MISA_ADDRESS as usize] & MISA_C) == 0 {
+            return 0xffffffff; // Return invalid value
+        }
+        let op = halfword & 0x3; // [1:0]
+        let funct3 = (halfword >> 13) & 0x7; // [15:13]
+
+        match op {
+            0 => match funct3 {
                 0 => {
                     // C.ADDI4SPN
                     // addi rd+8, x2, nzuimm
@@ -1421,14 +2727,87 @@ impl Cpu {
         s
     }
 
+    /// Returns immutable `Mmu`
+    pub fn get_mmu(&self) -> &Mmu {
+        &self.mmu
+    }
+
     /// Returns mutable `Mmu`
     pub fn get_mut_mmu(&mut self) -> &mut Mmu {
         &mut self.mmu
     }
 
     /// Returns mutable `Terminal`
-    pub fn get_mut_terminal(&mut self) -> &mut Box<dyn Terminal> {
-        &mut self.mmu.get_mut_uart().terminal
+    pub fn get_mut_terminal(&mut self) -> RefMut<'_, Box<dyn Terminal>> {
+        self.mmu.get_mut_terminal()
+    }
+
+    /// Saves the hart state, the address translation state of `Mmu` included,
+    /// to a snapshot. The bus shared by the harts is saved separately.
+    pub(crate) fn save_state(&self, writer: &mut SnapshotWriter) {
+        self.mmu.save_state(writer);
+        writer.write_u64(self.clock);
+        write_xlen(writer, &self.xlen);
+        writer.write_u8(get_privilege_encoding(&self.privilege_mode));
+        writer.write_bool(self.wfi);
+        for x in &self.x {
+            writer.write_u64(*x as u64);
+        }
+        for f in &self.f {
+            writer.write_u64(*f);
+        }
+        writer.write_u64(self.pc);
+        writer.write_sparse_words(&self.csr);
+        self.vector.save_state(writer);
+        match &self.unhandled_exception {
+            Some((trap, address)) => {
+                writer.write_bool(true);
+                writer.write_u64(get_trap_cause(trap, &Xlen::Bit64));
+                writer.write_u64(trap.value);
+                writer.write_u64(*address);
+            }
+            None => writer.write_bool(false),
+        };
+    }
+
+    /// Restores the state saved with `save_state()`.
+    pub(crate) fn restore_state(
+        &mut self,
+        reader: &mut SnapshotReader,
+    ) -> Result<(), EmulatorError> {
+        self.mmu.restore_state(reader)?;
+        self.clock = reader.read_u64()?;
+        let xlen = read_xlen(reader)?;
+        self.update_xlen(xlen);
+        self.privilege_mode = read_privilege_mode(reader)?;
+        self.wfi = reader.read_bool()?;
+        for x in self.x.iter_mut() {
+            *x = reader.read_u64()? as i64;
+        }
+        for f in self.f.iter_mut() {
+            *f = reader.read_u64()?;
+        }
+        self.pc = reader.read_u64()?;
+        let mut csr = vec![];
+        reader.read_sparse_words(&mut csr, CSR_CAPACITY)?;
+        if csr.len() != CSR_CAPACITY {
+            return Err(EmulatorError::InvalidSnapshot);
+        }
+        self.csr.copy_from_slice(&csr);
+        self.update_active_hpm_counters();
+        self.invalidate_blocks();
+        self.vector.restore_state(reader)?;
+        self.unhandled_exception = match reader.read_bool()? {
+            true => {
+                let trap_type =
+                    get_trap_type(reader.read_u64()?).ok_or(EmulatorError::InvalidSnapshot)?;
+                let value = reader.read_u64()?;
+                let address = reader.read_u64()?;
+                Some((Trap { trap_type, value }, address))
+            }
+            false => None,
+        };
+        Ok(())
     }
 }
 
@@ -1626,25 +3005,54 @@ fn dump_format_r(cpu: &mut Cpu, word: u32, _address: u64, evaluate: bool) -> Str
     s
 }
 
-// has rs3
-struct FormatR2 {
-    rd: usize,
-    rs1: usize,
-    rs2: usize,
-    rs3: usize,
-}
-
-fn parse_format_r2(word: u32) -> FormatR2 {
-    FormatR2 {
-        rd: ((word >> 7) & 0x1f) as usize,   // [11:7]
-        rs1: ((word >> 15) & 0x1f) as usize, // [19:15]
-        rs2: ((word >> 20) & 0x1f) as usize, // [24:20]
-        rs3: ((word >> 27) & 0x1f) as usize, // [31:27]
-    }
-}
-
-fn dump_format_r2(cpu: &mut Cpu, word: u32, _address: u64, evaluate: bool) -> String {
-    let f = parse_format_r2(word);
+fn dump_format_r_unary(cpu: &mut Cpu, word: u32, _address: u64, evaluate: bool) -> String {
+    let f = parse_format_r(word);
+    let mut s = String::new();
+    s += get_register_name(f.rd);
+    if evaluate {
+        s += &format!(":{:x}", cpu.x[f.rd]);
+    }
+    s += &format!(",{}", get_register_name(f.rs1));
+    if evaluate {
+        s += &format!(":{:x}", cpu.x[f.rs1]);
+    }
+    s
+}
+
+fn dump_format_r_shamt(cpu: &mut Cpu, word: u32, _address: u64, evaluate: bool) -> String {
+    let f = parse_format_r(word);
+    let mut s = String::new();
+    s += get_register_name(f.rd);
+    if evaluate {
+        s += &format!(":{:x}", cpu.x[f.rd]);
+    }
+    s += &format!(",{}", get_register_name(f.rs1));
+    if evaluate {
+        s += &format!(":{:x}", cpu.x[f.rs1]);
+    }
+    s += &format!(",{:x}", (word >> 20) & 0x3f);
+    s
+}
+
+// has rs3
+struct FormatR2 {
+    rd: usize,
+    rs1: usize,
+    rs2: usize,
+    rs3: usize,
+}
+
+fn parse_format_r2(word: u32) -> FormatR2 {
+    FormatR2 {
+        rd: ((word >> 7) & 0x1f) as usize,   // [11:7]
+        rs1: ((word >> 15) & 0x1f) as usize, // [19:15]
+        rs2: ((word >> 20) & 0x1f) as usize, // [24:20]
+        rs3: ((word >> 27) & 0x1f) as usize, // [31:27]
+    }
+}
+
+fn dump_format_r2(cpu: &mut Cpu, word: u32, _address: u64, evaluate: bool) -> String {
+    let f = parse_format_r2(word);
     let mut s = String::new();
     s += get_register_name(f.rd);
     if evaluate {
@@ -1732,6 +3140,196 @@ fn dump_format_u(cpu: &mut Cpu, word: u32, _address: u64, evaluate: bool) -> Str
     s
 }
 
+/// Where the second source operand of a vector arithmetic instruction is,
+/// decided by the `funct3` field and the instruction
+#[derive(Clone, Copy)]
+enum VectorOperand {
+    /// Vector register `vs1`
+    Vector,
+    /// Integer register `rs1`
+    Integer,
+    /// Floating-point register `rs1`
+    Float,
+    /// `simm5` sign-extended
+    Immediate,
+    /// `uimm5` zero-extended
+    UnsignedImmediate,
+}
+
+struct FormatV {
+    vd: usize,
+    vs1: usize,
+    vs2: usize,
+    vm: bool,
+}
+
+fn parse_format_v(word: u32) -> FormatV {
+    FormatV {
+        vd: ((word >> 7) & 0x1f) as usize,   // [11:7], also vs3 and rd
+        vs1: ((word >> 15) & 0x1f) as usize, // [19:15], also rs1 and imm
+        vs2: ((word >> 20) & 0x1f) as usize, // [24:20], also rs2
+        vm: ((word >> 25) & 0x1) == 1,       // [25]
+    }
+}
+
+/// Returns the floating-point element width of OPFVV and OPFVF vector
+/// instructions, SEW, for `Cpu::get_vector_context()`.
+fn get_vector_float_width(word: u32) -> Option<i32> {
+    match (word & 0x7f, (word >> 12) & 0x7) {
+        (0x57, 1 | 5) => Some(0),
+        _ => None,
+    }
+}
+
+/// Returns the element width in bits of a vector load or store encoded
+/// in the width field.
+fn get_vector_memory_eew(word: u32) -> usize {
+    match (word >> 12) & 0x7 {
+        0 => 8,
+        5 => 16,
+        6 => 32,
+        _ => 64,
+    }
+}
+
+fn dump_vector_mask(word: u32) -> &'static str {
+    match parse_format_v(word).vm {
+        true => "",
+        false => ",v0.t",
+    }
+}
+
+/// Returns the second source operand of a vector arithmetic instruction
+/// depending on `funct3`.
+fn dump_vector_operand(cpu: &mut Cpu, word: u32, evaluate: bool) -> String {
+    let f = parse_format_v(word);
+    match (word >> 12) & 0x7 {
+        // OPIVI
+        3 => format!("{}", ((word as i32) << 12) >> 27),
+        // OPIVX and OPMVX
+        4 | 6 => {
+            let mut s = get_register_name(f.vs1).to_string();
+            if evaluate {
+                s += &format!(":{:x}", cpu.x[f.vs1]);
+            }
+            s
+        }
+        // OPFVF
+        5 => format!("f{}", f.vs1),
+        _ => format!("v{}", f.vs1),
+    }
+}
+
+fn dump_format_v(cpu: &mut Cpu, word: u32, _address: u64, evaluate: bool) -> String {
+    let f = parse_format_v(word);
+    let operand = dump_vector_operand(cpu, word, evaluate);
+    format!("v{},v{},{}{}", f.vd, f.vs2, operand, dump_vector_mask(word))
+}
+
+fn dump_format_v_uimm(_cpu: &mut Cpu, word: u32, _address: u64, _evaluate: bool) -> String {
+    let f = parse_format_v(word);
+    format!("v{},v{},{}{}", f.vd, f.vs2, f.vs1, dump_vector_mask(word))
+}
+
+fn dump_format_v_multiply_add(cpu: &mut Cpu, word: u32, _address: u64, evaluate: bool) -> String {
+    let f = parse_format_v(word);
+    let operand = dump_vector_operand(cpu, word, evaluate);
+    format!("v{},{},v{}{}", f.vd, operand, f.vs2, dump_vector_mask(word))
+}
+
+fn dump_format_v_merge(cpu: &mut Cpu, word: u32, _address: u64, evaluate: bool) -> String {
+    let f = parse_format_v(word);
+    let operand = dump_vector_operand(cpu, word, evaluate);
+    format!("v{},v{},{},v0", f.vd, f.vs2, operand)
+}
+
+fn dump_format_v_move(cpu: &mut Cpu, word: u32, _address: u64, evaluate: bool) -> String {
+    let f = parse_format_v(word);
+    let operand = dump_vector_operand(cpu, word, evaluate);
+    format!("v{},{}", f.vd, operand)
+}
+
+fn dump_format_v_unary(_cpu: &mut Cpu, word: u32, _address: u64, _evaluate: bool) -> String {
+    let f = parse_format_v(word);
+    format!("v{},v{}{}", f.vd, f.vs2, dump_vector_mask(word))
+}
+
+fn dump_format_v_index(_cpu: &mut Cpu, word: u32, _address: u64, _evaluate: bool) -> String {
+    let f = parse_format_v(word);
+    format!("v{}{}", f.vd, dump_vector_mask(word))
+}
+
+fn dump_format_v_to_scalar(cpu: &mut Cpu, word: u32, _address: u64, evaluate: bool) -> String {
+    let f = parse_format_v(word);
+    let mut s = match (word >> 12) & 0x7 {
+        // OPFVV
+        1 => format!("f{}", f.vd),
+        _ => get_register_name(f.vd).to_string(),
+    };
+    if evaluate && (word >> 12) & 0x7 != 1 {
+        s += &format!(":{:x}", cpu.x[f.vd]);
+    }
+    s += &format!(",v{}{}", f.vs2, dump_vector_mask(word));
+    s
+}
+
+fn dump_format_v_config(cpu: &mut Cpu, word: u32, _address: u64, evaluate: bool) -> String {
+    let f = parse_format_v(word);
+    let mut s = String::new();
+    s += get_register_name(f.vd);
+    if evaluate {
+        s += &format!(":{:x}", cpu.x[f.vd]);
+    }
+    match word >> 30 {
+        // VSETIVLI
+        3 => s += &format!(",{},{:x}", f.vs1, (word >> 20) & 0x3ff),
+        // VSETVL
+        2 => {
+            s += &format!(",{}", get_register_name(f.vs1));
+            if evaluate {
+                s += &format!(":{:x}", cpu.x[f.vs1]);
+            }
+            s += &format!(",{}", get_register_name(f.vs2));
+            if evaluate {
+                s += &format!(":{:x}", cpu.x[f.vs2]);
+            }
+        }
+        // VSETVLI
+        _ => {
+            s += &format!(",{}", get_register_name(f.vs1));
+            if evaluate {
+                s += &format!(":{:x}", cpu.x[f.vs1]);
+            }
+            s += &format!(",{:x}", (word >> 20) & 0x7ff);
+        }
+    };
+    s
+}
+
+fn dump_format_v_memory(cpu: &mut Cpu, word: u32, _address: u64, evaluate: bool) -> String {
+    let f = parse_format_v(word);
+    let mut s = format!("v{},({}", f.vd, get_register_name(f.vs1));
+    if evaluate {
+        s += &format!(":{:x}", cpu.x[f.vs1]);
+    }
+    s += ")";
+    match (word >> 26) & 0x3 {
+        // Unit-stride
+        0 => {}
+        // Strided
+        2 => {
+            s += &format!(",{}", get_register_name(f.vs2));
+            if evaluate {
+                s += &format!(":{:x}", cpu.x[f.vs2]);
+            }
+        }
+        // Indexed
+        _ => s += &format!(",v{}", f.vs2),
+    };
+    s += dump_vector_mask(word);
+    s
+}
+
 fn dump_empty(_cpu: &mut Cpu, _word: u32, _address: u64, _evaluate: bool) -> String {
     String::new()
 }
@@ -1774,17 +3372,121 @@ fn get_register_name(num: usize) -> &'static str {
     }
 }
 
-const INSTRUCTION_NUM: usize = 116;
+/// Extension an instruction belongs to, see [`get_extension`](fn.get_extension.html)
+enum Extension {
+    /// Base integer ISA with Zicsr and Zifencei, always enabled
+    Base,
+
+    /// Extension name as written in ISA string
+    Named(&'static str),
+}
+
+/// Returns the extension an instruction belongs to, classified by the
+/// opcode and the funct fields of its encoding, or `None` if the encoding
+/// isn't classified.
+///
+/// # Arguments
+/// * `inst`
+fn get_extension(inst: &Instruction) -> Option<Extension> {
+    let word = inst.data;
+    let funct3 = (word >> 12) & 0x7;
+    let funct6 = word >> 26;
+    let funct7 = word >> 25;
+    let name = match word & 0x7f {
+        // LOAD, MISC-MEM, AUIPC, STORE, LUI, BRANCH, JALR, JAL, and SYSTEM
+        0x03 | 0x0f | 0x17 | 0x23 | 0x37 | 0x63 | 0x67 | 0x6f | 0x73 => {
+            return Some(Extension::Base)
+        }
+        // OP-IMM
+        0x13 => match (funct3, funct6) {
+            (1, 0x00) | (5, 0x00 | 0x10) => return Some(Extension::Base),
+            (1, 0x18) | (5, 0x0a | 0x18 | 0x1a) => "zbb",
+            (1, 0x0a | 0x12 | 0x1a) | (5, 0x12) => "zbs",
+            (1 | 5, _) => return None,
+            _ => return Some(Extension::Base),
+        },
+        // OP-IMM-32
+        0x1b => match (funct3, funct7) {
+            (0, _) | (1, 0x00) | (5, 0x00 | 0x20) => return Some(Extension::Base),
+            (1 | 5, 0x30) => "zbb",
+            (1, 0x04 | 0x05) => "zba", // SLLI.UW
+            _ => return None,
+        },
+        // OP
+        0x33 => match (funct7, funct3) {
+            (0x00, _) | (0x20, 0 | 5) => return Some(Extension::Base),
+            (0x01, _) => "m",
+            (0x20, 4 | 6 | 7) | (0x05, 4..=7) | (0x30, 1 | 5) | (0x04, 4) => "zbb",
+            (0x05, 1..=3) => "zbc",
+            (0x10, 2 | 4 | 6) => "zba",
+            (0x14 | 0x24 | 0x34, 1) | (0x24, 5) => "zbs",
+            _ => return None,
+        },
+        // OP-32
+        0x3b => match (funct7, funct3) {
+            (0x00, 0 | 1 | 5) | (0x20, 0 | 5) => return Some(Extension::Base),
+            (0x01, _) => "m",
+            (0x04, 0) | (0x10, 2 | 4 | 6) => "zba",
+            (0x04, 4) | (0x30, 1 | 5) => "zbb",
+            _ => return None,
+        },
+        0x2f => "a",
+        0x57 => "v",
+        // Vector loads and stores share the opcodes with FLW, FLD, FSW and FSD
+        0x07 | 0x27 => match funct3 {
+            2 => "f",
+            3 => "d",
+            _ => "v",
+        },
+        // fmt field tells the precision except for FCVT.S.D
+        0x43 | 0x47 | 0x4b | 0x4f | 0x53 => match funct7 & 0x3 {
+            0 if word >> 20 == 0x401 => "d",
+            0 => "f",
+            _ => "d",
+        },
+        _ => return None,
+    };
+    Some(Extension::Named(name))
+}
+
+/// Returns the carry-less product of two values for `CLMUL`, `CLMULH` and
+/// `CLMULR` instructions.
+///
+/// # Arguments
+/// * `a`
+/// * `b`
+fn carry_less_multiply(a: u64, b: u64) -> u128 {
+    (0..64)
+        .filter(|i| (b >> i) & 1 == 1)
+        .fold(0, |product, i| product ^ ((a as u128) << i))
+}
+
+/// Returns the decoder of [`INSTRUCTIONS`](static.INSTRUCTIONS.html) shared
+/// by all the harts and the disassembler. Generated at the first decode.
+fn decoder() -> &'static Decoder {
+    static DECODER: OnceLock<Decoder> = OnceLock::new();
+    DECODER.get_or_init(|| Decoder::new(INSTRUCTIONS.iter().map(|inst| (inst.mask, inst.data))))
+}
+
+const INSTRUCTION_NUM: usize = 581;
 
 // @TODO: Reorder in often used order as
-const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
+static INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
     Instruction {
         mask: 0xfe00707f,
         data: 0x00000033,
         name: "ADD",
+        operation: |cpu, word, address| add(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x0800003b,
+        name: "ADD.UW",
         operation: |cpu, word, _address| {
+            cpu.check_xlen(64)?;
             let f = parse_format_r(word);
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_add(cpu.x[f.rs2]));
+            cpu.x[f.rd] = cpu.x[f.rs2].wrapping_add(cpu.x[f.rs1] as u32 as i64);
             Ok(())
         },
         disassemble: dump_format_r,
@@ -1793,33 +3495,21 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         mask: 0x0000707f,
         data: 0x00000013,
         name: "ADDI",
-        operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_add(f.imm));
-            Ok(())
-        },
+        operation: |cpu, word, address| addi(cpu, &Operands::format_i(word), address),
         disassemble: dump_format_i,
     },
     Instruction {
         mask: 0x0000707f,
         data: 0x0000001b,
         name: "ADDIW",
-        operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = cpu.x[f.rs1].wrapping_add(f.imm) as i32 as i64;
-            Ok(())
-        },
+        operation: |cpu, word, address| addiw(cpu, &Operands::format_i(word), address),
         disassemble: dump_format_i,
     },
     Instruction {
         mask: 0xfe00707f,
         data: 0x0000003b,
         name: "ADDW",
-        operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            cpu.x[f.rd] = cpu.x[f.rs1].wrapping_add(cpu.x[f.rs2]) as i32 as i64;
-            Ok(())
-        },
+        operation: |cpu, word, address| addw(cpu, &Operands::format_r(word), address),
         disassemble: dump_format_r,
     },
     Instruction {
@@ -1827,20 +3517,7 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         data: 0x0000302f,
         name: "AMOADD.D",
         operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            let tmp = match cpu.mmu.load_doubleword(cpu.x[f.rs1] as u64) {
-                Ok(data) => data as i64,
-                Err(e) => return Err(e),
-            };
-            match cpu
-                .mmu
-                .store_doubleword(cpu.x[f.rs1] as u64, cpu.x[f.rs2].wrapping_add(tmp) as u64)
-            {
-                Ok(()) => {}
-                Err(e) => return Err(e),
-            };
-            cpu.x[f.rd] = tmp;
-            Ok(())
+            cpu.atomic_memory_operation(word, 8, |value, operand| value.wrapping_add(operand))
         },
         disassemble: dump_format_r,
     },
@@ -1849,20 +3526,7 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         data: 0x0000202f,
         name: "AMOADD.W",
         operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            let tmp = match cpu.mmu.load_word(cpu.x[f.rs1] as u64) {
-                Ok(data) => data as i32 as i64,
-                Err(e) => return Err(e),
-            };
-            match cpu
-                .mmu
-                .store_word(cpu.x[f.rs1] as u64, cpu.x[f.rs2].wrapping_add(tmp) as u32)
-            {
-                Ok(()) => {}
-                Err(e) => return Err(e),
-            };
-            cpu.x[f.rd] = tmp;
-            Ok(())
+            cpu.atomic_memory_operation(word, 4, |value, operand| value.wrapping_add(operand))
         },
         disassemble: dump_format_r,
     },
@@ -1871,20 +3535,7 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         data: 0x6000302f,
         name: "AMOAND.D",
         operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            let tmp = match cpu.mmu.load_doubleword(cpu.x[f.rs1] as u64) {
-                Ok(data) => data as i64,
-                Err(e) => return Err(e),
-            };
-            match cpu
-                .mmu
-                .store_doubleword(cpu.x[f.rs1] as u64, (cpu.x[f.rs2] & tmp) as u64)
-            {
-                Ok(()) => {}
-                Err(e) => return Err(e),
-            };
-            cpu.x[f.rd] = tmp;
-            Ok(())
+            cpu.atomic_memory_operation(word, 8, |value, operand| value & operand)
         },
         disassemble: dump_format_r,
     },
@@ -1893,264 +3544,410 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         data: 0x6000202f,
         name: "AMOAND.W",
         operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            let tmp = match cpu.mmu.load_word(cpu.x[f.rs1] as u64) {
-                Ok(data) => data as i32 as i64,
-                Err(e) => return Err(e),
-            };
-            match cpu
-                .mmu
-                .store_word(cpu.x[f.rs1] as u64, (cpu.x[f.rs2] & tmp) as u32)
-            {
-                Ok(()) => {}
-                Err(e) => return Err(e),
-            };
-            cpu.x[f.rd] = tmp;
-            Ok(())
+            cpu.atomic_memory_operation(word, 4, |value, operand| value & operand)
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xf800707f,
-        data: 0xe000302f,
-        name: "AMOMAXU.D",
+        data: 0xa000302f,
+        name: "AMOMAX.D",
         operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            let tmp = match cpu.mmu.load_doubleword(cpu.x[f.rs1] as u64) {
-                Ok(data) => data,
-                Err(e) => return Err(e),
-            };
-            let max = match cpu.x[f.rs2] as u64 >= tmp {
-                true => cpu.x[f.rs2] as u64,
-                false => tmp,
-            };
-            match cpu.mmu.store_doubleword(cpu.x[f.rs1] as u64, max) {
-                Ok(()) => {}
-                Err(e) => return Err(e),
-            };
-            cpu.x[f.rd] = tmp as i64;
-            Ok(())
+            cpu.atomic_memory_operation(word, 8, |value, operand| value.max(operand))
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xf800707f,
-        data: 0xe000202f,
-        name: "AMOMAXU.W",
+        data: 0xa000202f,
+        name: "AMOMAX.W",
         operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            let tmp = match cpu.mmu.load_word(cpu.x[f.rs1] as u64) {
-                Ok(data) => data,
-                Err(e) => return Err(e),
-            };
-            let max = match cpu.x[f.rs2] as u32 >= tmp {
-                true => cpu.x[f.rs2] as u32,
-                false => tmp,
-            };
-            match cpu.mmu.store_word(cpu.x[f.rs1] as u64, max) {
-                Ok(()) => {}
-                Err(e) => return Err(e),
-            };
-            cpu.x[f.rd] = tmp as i32 as i64;
-            Ok(())
+            cpu.atomic_memory_operation(word, 4, |value, operand| value.max(operand))
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xf800707f,
-        data: 0x4000302f,
-        name: "AMOOR.D",
+        data: 0xe000302f,
+        name: "AMOMAXU.D",
         operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            let tmp = match cpu.mmu.load_doubleword(cpu.x[f.rs1] as u64) {
-                Ok(data) => data as i64,
-                Err(e) => return Err(e),
-            };
-            match cpu
-                .mmu
-                .store_doubleword(cpu.x[f.rs1] as u64, (cpu.x[f.rs2] | tmp) as u64)
-            {
-                Ok(()) => {}
-                Err(e) => return Err(e),
-            };
-            cpu.x[f.rd] = tmp;
-            Ok(())
+            cpu.atomic_memory_operation(word, 8, |value, operand| {
+                (value as u64).max(operand as u64) as i64
+            })
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xf800707f,
-        data: 0x4000202f,
-        name: "AMOOR.W",
+        data: 0xe000202f,
+        name: "AMOMAXU.W",
         operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            let tmp = match cpu.mmu.load_word(cpu.x[f.rs1] as u64) {
-                Ok(data) => data as i32 as i64,
-                Err(e) => return Err(e),
-            };
-            match cpu
-                .mmu
-                .store_word(cpu.x[f.rs1] as u64, (cpu.x[f.rs2] | tmp) as u32)
-            {
-                Ok(()) => {}
-                Err(e) => return Err(e),
-            };
-            cpu.x[f.rd] = tmp;
-            Ok(())
+            cpu.atomic_memory_operation(word, 4, |value, operand| {
+                (value as u64).max(operand as u64) as i64
+            })
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xf800707f,
-        data: 0x0800302f,
-        name: "AMOSWAP.D",
+        data: 0x8000302f,
+        name: "AMOMIN.D",
         operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            let tmp = match cpu.mmu.load_doubleword(cpu.x[f.rs1] as u64) {
-                Ok(data) => data as i64,
-                Err(e) => return Err(e),
-            };
-            match cpu
-                .mmu
-                .store_doubleword(cpu.x[f.rs1] as u64, cpu.x[f.rs2] as u64)
-            {
-                Ok(()) => {}
-                Err(e) => return Err(e),
-            };
-            cpu.x[f.rd] = tmp;
-            Ok(())
+            cpu.atomic_memory_operation(word, 8, |value, operand| value.min(operand))
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xf800707f,
-        data: 0x0800202f,
-        name: "AMOSWAP.W",
+        data: 0x8000202f,
+        name: "AMOMIN.W",
         operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            let tmp = match cpu.mmu.load_word(cpu.x[f.rs1] as u64) {
-                Ok(data) => data as i32 as i64,
-                Err(e) => return Err(e),
-            };
-            match cpu.mmu.store_word(cpu.x[f.rs1] as u64, cpu.x[f.rs2] as u32) {
-                Ok(()) => {}
-                Err(e) => return Err(e),
-            };
-            cpu.x[f.rd] = tmp;
-            Ok(())
+            cpu.atomic_memory_operation(word, 4, |value, operand| value.min(operand))
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfe00707f,
-        data: 0x00007033,
-        name: "AND",
+        mask: 0xf800707f,
+        data: 0xc000302f,
+        name: "AMOMINU.D",
         operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] & cpu.x[f.rs2]);
-            Ok(())
+            cpu.atomic_memory_operation(word, 8, |value, operand| {
+                (value as u64).min(operand as u64) as i64
+            })
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0x0000707f,
-        data: 0x00007013,
-        name: "ANDI",
+        mask: 0xf800707f,
+        data: 0xc000202f,
+        name: "AMOMINU.W",
         operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] & f.imm);
-            Ok(())
+            cpu.atomic_memory_operation(word, 4, |value, operand| {
+                (value as u64).min(operand as u64) as i64
+            })
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xf800707f,
+        data: 0x4000302f,
+        name: "AMOOR.D",
+        operation: |cpu, word, _address| {
+            cpu.atomic_memory_operation(word, 8, |value, operand| value | operand)
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xf800707f,
+        data: 0x4000202f,
+        name: "AMOOR.W",
+        operation: |cpu, word, _address| {
+            cpu.atomic_memory_operation(word, 4, |value, operand| value | operand)
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xf800707f,
+        data: 0x0800302f,
+        name: "AMOSWAP.D",
+        operation: |cpu, word, _address| {
+            cpu.atomic_memory_operation(word, 8, |_value, operand| operand)
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xf800707f,
+        data: 0x0800202f,
+        name: "AMOSWAP.W",
+        operation: |cpu, word, _address| {
+            cpu.atomic_memory_operation(word, 4, |_value, operand| operand)
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xf800707f,
+        data: 0x2000302f,
+        name: "AMOXOR.D",
+        operation: |cpu, word, _address| {
+            cpu.atomic_memory_operation(word, 8, |value, operand| value ^ operand)
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xf800707f,
+        data: 0x2000202f,
+        name: "AMOXOR.W",
+        operation: |cpu, word, _address| {
+            cpu.atomic_memory_operation(word, 4, |value, operand| value ^ operand)
         },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x00007033,
+        name: "AND",
+        operation: |cpu, word, address| and(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0x0000707f,
+        data: 0x00007013,
+        name: "ANDI",
+        operation: |cpu, word, address| andi(cpu, &Operands::format_i(word), address),
         disassemble: dump_format_i,
     },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x40007033,
+        name: "ANDN",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.x[f.rs1] & !cpu.x[f.rs2];
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
     Instruction {
         mask: 0x0000007f,
         data: 0x00000017,
         name: "AUIPC",
-        operation: |cpu, word, address| {
-            let f = parse_format_u(word);
-            cpu.x[f.rd] = cpu.sign_extend(address.wrapping_add(f.imm) as i64);
+        operation: |cpu, word, address| auipc(cpu, &Operands::format_u(word), address),
+        disassemble: dump_format_u,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x48001033,
+        name: "BCLR",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.sign_extend(
+                cpu.x[f.rs1] & !(1 << (cpu.x[f.rs2] as u32 & (cpu.get_xlen_bits() - 1))),
+            );
             Ok(())
         },
-        disassemble: dump_format_u,
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x48001013,
+        name: "BCLRI",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let shamt = (word >> 20) & (cpu.get_xlen_bits() - 1);
+            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] & !(1 << shamt));
+            Ok(())
+        },
+        disassemble: dump_format_r_shamt,
     },
     Instruction {
         mask: 0x0000707f,
         data: 0x00000063,
         name: "BEQ",
-        operation: |cpu, word, address| {
-            let f = parse_format_b(word);
-            if cpu.sign_extend(cpu.x[f.rs1]) == cpu.sign_extend(cpu.x[f.rs2]) {
-                cpu.pc = address.wrapping_add(f.imm);
-            }
+        operation: |cpu, word, address| beq(cpu, &Operands::format_b(word), address),
+        disassemble: dump_format_b,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x48005033,
+        name: "BEXT",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = ((cpu.x[f.rs1] as u64
+                >> (cpu.x[f.rs2] as u32 & (cpu.get_xlen_bits() - 1)))
+                & 1) as i64;
             Ok(())
         },
-        disassemble: dump_format_b,
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x48005013,
+        name: "BEXTI",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let shamt = (word >> 20) & (cpu.get_xlen_bits() - 1);
+            cpu.x[f.rd] = ((cpu.x[f.rs1] as u64 >> shamt) & 1) as i64;
+            Ok(())
+        },
+        disassemble: dump_format_r_shamt,
     },
     Instruction {
         mask: 0x0000707f,
         data: 0x00005063,
         name: "BGE",
-        operation: |cpu, word, address| {
-            let f = parse_format_b(word);
-            if cpu.sign_extend(cpu.x[f.rs1]) >= cpu.sign_extend(cpu.x[f.rs2]) {
-                cpu.pc = address.wrapping_add(f.imm);
-            }
-            Ok(())
-        },
+        operation: |cpu, word, address| bge(cpu, &Operands::format_b(word), address),
         disassemble: dump_format_b,
     },
     Instruction {
         mask: 0x0000707f,
         data: 0x00007063,
         name: "BGEU",
-        operation: |cpu, word, address| {
-            let f = parse_format_b(word);
-            if cpu.unsigned_data(cpu.x[f.rs1]) >= cpu.unsigned_data(cpu.x[f.rs2]) {
-                cpu.pc = address.wrapping_add(f.imm);
-            }
+        operation: |cpu, word, address| bgeu(cpu, &Operands::format_b(word), address),
+        disassemble: dump_format_b,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x68001033,
+        name: "BINV",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.sign_extend(
+                cpu.x[f.rs1] ^ (1 << (cpu.x[f.rs2] as u32 & (cpu.get_xlen_bits() - 1))),
+            );
             Ok(())
         },
-        disassemble: dump_format_b,
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x68001013,
+        name: "BINVI",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let shamt = (word >> 20) & (cpu.get_xlen_bits() - 1);
+            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] ^ (1 << shamt));
+            Ok(())
+        },
+        disassemble: dump_format_r_shamt,
     },
     Instruction {
         mask: 0x0000707f,
         data: 0x00004063,
         name: "BLT",
-        operation: |cpu, word, address| {
-            let f = parse_format_b(word);
-            if cpu.sign_extend(cpu.x[f.rs1]) < cpu.sign_extend(cpu.x[f.rs2]) {
-                cpu.pc = address.wrapping_add(f.imm);
-            }
-            Ok(())
-        },
+        operation: |cpu, word, address| blt(cpu, &Operands::format_b(word), address),
         disassemble: dump_format_b,
     },
     Instruction {
         mask: 0x0000707f,
         data: 0x00006063,
         name: "BLTU",
-        operation: |cpu, word, address| {
-            let f = parse_format_b(word);
-            if cpu.unsigned_data(cpu.x[f.rs1]) < cpu.unsigned_data(cpu.x[f.rs2]) {
-                cpu.pc = address.wrapping_add(f.imm);
-            }
-            Ok(())
-        },
+        operation: |cpu, word, address| bltu(cpu, &Operands::format_b(word), address),
         disassemble: dump_format_b,
     },
     Instruction {
         mask: 0x0000707f,
         data: 0x00001063,
         name: "BNE",
-        operation: |cpu, word, address| {
-            let f = parse_format_b(word);
-            if cpu.sign_extend(cpu.x[f.rs1]) != cpu.sign_extend(cpu.x[f.rs2]) {
-                cpu.pc = address.wrapping_add(f.imm);
-            }
+        operation: |cpu, word, address| bne(cpu, &Operands::format_b(word), address),
+        disassemble: dump_format_b,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x28001033,
+        name: "BSET",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.sign_extend(
+                cpu.x[f.rs1] | (1 << (cpu.x[f.rs2] as u32 & (cpu.get_xlen_bits() - 1))),
+            );
             Ok(())
         },
-        disassemble: dump_format_b,
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x28001013,
+        name: "BSETI",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let shamt = (word >> 20) & (cpu.get_xlen_bits() - 1);
+            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] | (1 << shamt));
+            Ok(())
+        },
+        disassemble: dump_format_r_shamt,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x0a001033,
+        name: "CLMUL",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.sign_extend(carry_less_multiply(
+                cpu.unsigned_data(cpu.x[f.rs1]),
+                cpu.unsigned_data(cpu.x[f.rs2]),
+            ) as i64);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x0a003033,
+        name: "CLMULH",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let product = carry_less_multiply(
+                cpu.unsigned_data(cpu.x[f.rs1]),
+                cpu.unsigned_data(cpu.x[f.rs2]),
+            );
+            cpu.x[f.rd] = cpu.sign_extend((product >> cpu.get_xlen_bits()) as i64);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x0a002033,
+        name: "CLMULR",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let product = carry_less_multiply(
+                cpu.unsigned_data(cpu.x[f.rs1]),
+                cpu.unsigned_data(cpu.x[f.rs2]),
+            );
+            cpu.x[f.rd] = cpu.sign_extend((product >> (cpu.get_xlen_bits() - 1)) as i64);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x60001013,
+        name: "CLZ",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = match cpu.xlen {
+                Xlen::Bit32 => (cpu.x[f.rs1] as u32).leading_zeros(),
+                Xlen::Bit64 => (cpu.x[f.rs1] as u64).leading_zeros(),
+            } as i64;
+            Ok(())
+        },
+        disassemble: dump_format_r_unary,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x6000101b,
+        name: "CLZW",
+        operation: |cpu, word, _address| {
+            cpu.check_xlen(64)?;
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = (cpu.x[f.rs1] as u32).leading_zeros() as i64;
+            Ok(())
+        },
+        disassemble: dump_format_r_unary,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x60201013,
+        name: "CPOP",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.unsigned_data(cpu.x[f.rs1]).count_ones() as i64;
+            Ok(())
+        },
+        disassemble: dump_format_r_unary,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x6020101b,
+        name: "CPOPW",
+        operation: |cpu, word, _address| {
+            cpu.check_xlen(64)?;
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = (cpu.x[f.rs1] as u32).count_ones() as i64;
+            Ok(())
+        },
+        disassemble: dump_format_r_unary,
     },
     Instruction {
         mask: 0x0000707f,
@@ -2159,15 +3956,17 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         operation: |cpu, word, _address| {
             let f = parse_format_csr(word);
             let data = match cpu.read_csr(f.csr) {
-                Ok(data) => data as i64,
-                Err(e) => return Err(e),
-            };
-            let tmp = cpu.x[f.rs];
-            cpu.x[f.rd] = cpu.sign_extend(data);
-            match cpu.write_csr(f.csr, (cpu.x[f.rd] & !tmp) as u64) {
-                Ok(()) => {}
+                Ok(data) => cpu.sign_extend(data as i64),
                 Err(e) => return Err(e),
             };
+            // x0 in rs1 means no write, which doesn't trap on read-only CSRs
+            if f.rs != 0 {
+                match cpu.write_csr(f.csr, (data & !cpu.x[f.rs]) as u64) {
+                    Ok(()) => {}
+                    Err(e) => return Err(e),
+                };
+            }
+            cpu.x[f.rd] = data;
             Ok(())
         },
         disassemble: dump_format_csr,
@@ -2179,14 +3978,17 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         operation: |cpu, word, _address| {
             let f = parse_format_csr(word);
             let data = match cpu.read_csr(f.csr) {
-                Ok(data) => data as i64,
-                Err(e) => return Err(e),
-            };
-            cpu.x[f.rd] = cpu.sign_extend(data);
-            match cpu.write_csr(f.csr, (cpu.x[f.rd] & !(f.rs as i64)) as u64) {
-                Ok(()) => {}
+                Ok(data) => cpu.sign_extend(data as i64),
                 Err(e) => return Err(e),
             };
+            // Zero uimm means no write, which doesn't trap on read-only CSRs
+            if f.rs != 0 {
+                match cpu.write_csr(f.csr, (data & !(f.rs as i64)) as u64) {
+                    Ok(()) => {}
+                    Err(e) => return Err(e),
+                };
+            }
+            cpu.x[f.rd] = data;
             Ok(())
         },
         disassemble: dump_format_csr,
@@ -2198,15 +4000,17 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         operation: |cpu, word, _address| {
             let f = parse_format_csr(word);
             let data = match cpu.read_csr(f.csr) {
-                Ok(data) => data as i64,
-                Err(e) => return Err(e),
-            };
-            let tmp = cpu.x[f.rs];
-            cpu.x[f.rd] = cpu.sign_extend(data);
-            match cpu.write_csr(f.csr, cpu.unsigned_data(cpu.x[f.rd] | tmp)) {
-                Ok(()) => {}
+                Ok(data) => cpu.sign_extend(data as i64),
                 Err(e) => return Err(e),
             };
+            // x0 in rs1 means no write, which doesn't trap on read-only CSRs
+            if f.rs != 0 {
+                match cpu.write_csr(f.csr, cpu.unsigned_data(data | cpu.x[f.rs])) {
+                    Ok(()) => {}
+                    Err(e) => return Err(e),
+                };
+            }
+            cpu.x[f.rd] = data;
             Ok(())
         },
         disassemble: dump_format_csr,
@@ -2218,14 +4022,17 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         operation: |cpu, word, _address| {
             let f = parse_format_csr(word);
             let data = match cpu.read_csr(f.csr) {
-                Ok(data) => data as i64,
-                Err(e) => return Err(e),
-            };
-            cpu.x[f.rd] = cpu.sign_extend(data);
-            match cpu.write_csr(f.csr, cpu.unsigned_data(cpu.x[f.rd] | (f.rs as i64))) {
-                Ok(()) => {}
+                Ok(data) => cpu.sign_extend(data as i64),
                 Err(e) => return Err(e),
             };
+            // Zero uimm means no write, which doesn't trap on read-only CSRs
+            if f.rs != 0 {
+                match cpu.write_csr(f.csr, cpu.unsigned_data(data | (f.rs as i64))) {
+                    Ok(()) => {}
+                    Err(e) => return Err(e),
+                };
+            }
+            cpu.x[f.rd] = data;
             Ok(())
         },
         disassemble: dump_format_csr,
@@ -2237,15 +4044,14 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         operation: |cpu, word, _address| {
             let f = parse_format_csr(word);
             let data = match cpu.read_csr(f.csr) {
-                Ok(data) => data as i64,
+                Ok(data) => cpu.sign_extend(data as i64),
                 Err(e) => return Err(e),
             };
-            let tmp = cpu.x[f.rs];
-            cpu.x[f.rd] = cpu.sign_extend(data);
-            match cpu.write_csr(f.csr, cpu.unsigned_data(tmp)) {
+            match cpu.write_csr(f.csr, cpu.unsigned_data(cpu.x[f.rs])) {
                 Ok(()) => {}
                 Err(e) => return Err(e),
             };
+            cpu.x[f.rd] = data;
             Ok(())
         },
         disassemble: dump_format_csr,
@@ -2257,18 +4063,44 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         operation: |cpu, word, _address| {
             let f = parse_format_csr(word);
             let data = match cpu.read_csr(f.csr) {
-                Ok(data) => data as i64,
+                Ok(data) => cpu.sign_extend(data as i64),
                 Err(e) => return Err(e),
             };
-            cpu.x[f.rd] = cpu.sign_extend(data);
             match cpu.write_csr(f.csr, f.rs as u64) {
                 Ok(()) => {}
                 Err(e) => return Err(e),
             };
+            cpu.x[f.rd] = data;
             Ok(())
         },
         disassemble: dump_format_csr,
     },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x60101013,
+        name: "CTZ",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = match cpu.xlen {
+                Xlen::Bit32 => (cpu.x[f.rs1] as u32).trailing_zeros(),
+                Xlen::Bit64 => (cpu.x[f.rs1] as u64).trailing_zeros(),
+            } as i64;
+            Ok(())
+        },
+        disassemble: dump_format_r_unary,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x6010101b,
+        name: "CTZW",
+        operation: |cpu, word, _address| {
+            cpu.check_xlen(64)?;
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = (cpu.x[f.rs1] as u32).trailing_zeros() as i64;
+            Ok(())
+        },
+        disassemble: dump_format_r_unary,
+    },
     Instruction {
         mask: 0xfe00707f,
         data: 0x02004033,
@@ -2332,10 +4164,10 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
             let divisor = cpu.x[f.rs2] as i32;
             if divisor == 0 {
                 cpu.x[f.rd] = -1;
-            } else if dividend == std::i32::MIN && divisor == -1 {
-                cpu.x[f.rd] = dividend as i32 as i64;
+            } else if dividend == i32::MIN && divisor == -1 {
+                cpu.x[f.rd] = dividend as i64;
             } else {
-                cpu.x[f.rd] = dividend.wrapping_div(divisor) as i32 as i64
+                cpu.x[f.rd] = dividend.wrapping_div(divisor) as i64
             }
             Ok(())
         },
@@ -2375,52 +4207,202 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         name: "FADD.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.f[f.rd] = cpu.f[f.rs1] + cpu.f[f.rs2];
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::add_f64(cpu.read_f64(f.rs1), cpu.read_f64(f.rs2), rm, &mut flags);
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfff0007f,
-        data: 0xd2200053,
-        name: "FCVT.D.L",
+        mask: 0xfe00007f,
+        data: 0x00000053,
+        name: "FADD.S",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.f[f.rd] = cpu.x[f.rs1] as f64;
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::add_f32(cpu.read_f32(f.rs1), cpu.read_f32(f.rs2), rm, &mut flags);
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfff0007f,
-        data: 0x42000053,
-        name: "FCVT.D.S",
+        mask: 0xfff0707f,
+        data: 0xe2001053,
+        name: "FCLASS.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            // Is this implementation correct?
-            cpu.f[f.rd] = f32::from_bits(cpu.f[f.rs1].to_bits() as u32) as f64;
+            cpu.x[f.rd] = fpu::classify_f64(cpu.read_f64(f.rs1)) as i64;
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfff0007f,
-        data: 0xd2000053,
-        name: "FCVT.D.W",
+        mask: 0xfff0707f,
+        data: 0xe0001053,
+        name: "FCLASS.S",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.f[f.rd] = cpu.x[f.rs1] as i32 as f64;
+            cpu.x[f.rd] = fpu::classify_f32(cpu.read_f32(f.rs1)) as i64;
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xfff0007f,
-        data: 0xd2100053,
-        name: "FCVT.D.WU",
-        operation: |cpu, word, _address| {
+        data: 0xd2200053,
+        name: "FCVT.D.L",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::from_integer_f64(cpu.x[f.rs1] as i128, rm, &mut flags);
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xd2300053,
+        name: "FCVT.D.LU",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::from_integer_f64(cpu.x[f.rs1] as u64 as i128, rm, &mut flags);
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0x42000053,
+        name: "FCVT.D.S",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            // The conversion is exact, but reserved rounding modes are still illegal
+            cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::to_f64(cpu.read_f32(f.rs1), &mut flags);
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xd2000053,
+        name: "FCVT.D.W",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::from_integer_f64(cpu.x[f.rs1] as i32 as i128, rm, &mut flags);
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xd2100053,
+        name: "FCVT.D.WU",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::from_integer_f64(cpu.x[f.rs1] as u32 as i128, rm, &mut flags);
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xc2200053,
+        name: "FCVT.L.D",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::to_integer(
+                cpu.read_f64(f.rs1),
+                i64::MIN as i128,
+                i64::MAX as i128,
+                rm,
+                &mut flags,
+            );
+            cpu.x[f.rd] = value as i64;
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xc0200053,
+        name: "FCVT.L.S",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::to_integer(
+                cpu.read_f32(f.rs1) as f64,
+                i64::MIN as i128,
+                i64::MAX as i128,
+                rm,
+                &mut flags,
+            );
+            cpu.x[f.rd] = value as i64;
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xc2300053,
+        name: "FCVT.LU.D",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::to_integer(cpu.read_f64(f.rs1), 0, u64::MAX as i128, rm, &mut flags);
+            cpu.x[f.rd] = value as u64 as i64;
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xc0300053,
+        name: "FCVT.LU.S",
+        operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.f[f.rd] = cpu.x[f.rs1] as u32 as f64;
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::to_integer(
+                cpu.read_f32(f.rs1) as f64,
+                0,
+                u64::MAX as i128,
+                rm,
+                &mut flags,
+            );
+            cpu.x[f.rd] = value as u64 as i64;
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
@@ -2431,8 +4413,71 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         name: "FCVT.S.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            // Is this implementation correct?
-            cpu.f[f.rd] = cpu.f[f.rs1] as f32 as f64;
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::to_f32(cpu.read_f64(f.rs1), rm, &mut flags);
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xd0200053,
+        name: "FCVT.S.L",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::from_integer_f32(cpu.x[f.rs1] as i128, rm, &mut flags);
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xd0300053,
+        name: "FCVT.S.LU",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::from_integer_f32(cpu.x[f.rs1] as u64 as i128, rm, &mut flags);
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xd0000053,
+        name: "FCVT.S.W",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::from_integer_f32(cpu.x[f.rs1] as i32 as i128, rm, &mut flags);
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xd0100053,
+        name: "FCVT.S.WU",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::from_integer_f32(cpu.x[f.rs1] as u32 as i128, rm, &mut flags);
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
@@ -2443,8 +4488,74 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         name: "FCVT.W.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            // Is this implementation correct?
-            cpu.x[f.rd] = cpu.f[f.rs1] as u32 as i32 as i64;
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::to_integer(
+                cpu.read_f64(f.rs1),
+                i32::MIN as i128,
+                i32::MAX as i128,
+                rm,
+                &mut flags,
+            );
+            cpu.x[f.rd] = value as i32 as i64;
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xc0000053,
+        name: "FCVT.W.S",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::to_integer(
+                cpu.read_f32(f.rs1) as f64,
+                i32::MIN as i128,
+                i32::MAX as i128,
+                rm,
+                &mut flags,
+            );
+            cpu.x[f.rd] = value as i32 as i64;
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xc2100053,
+        name: "FCVT.WU.D",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::to_integer(cpu.read_f64(f.rs1), 0, u32::MAX as i128, rm, &mut flags);
+            cpu.x[f.rd] = value as u32 as i32 as i64;
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0007f,
+        data: 0xc0100053,
+        name: "FCVT.WU.S",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::to_integer(
+                cpu.read_f32(f.rs1) as f64,
+                0,
+                u32::MAX as i128,
+                rm,
+                &mut flags,
+            );
+            cpu.x[f.rd] = value as u32 as i32 as i64;
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
@@ -2455,18 +4566,26 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         name: "FDIV.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            let dividend = cpu.f[f.rs1];
-            let divisor = cpu.f[f.rs2];
-            // Is this implementation correct?
-            if divisor == 0.0 {
-                cpu.f[f.rd] = std::f64::INFINITY;
-                cpu.set_fcsr_dz();
-            } else if divisor == -0.0 {
-                cpu.f[f.rd] = std::f64::NEG_INFINITY;
-                cpu.set_fcsr_dz();
-            } else {
-                cpu.f[f.rd] = dividend / divisor;
-            }
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::div_f64(cpu.read_f64(f.rs1), cpu.read_f64(f.rs2), rm, &mut flags);
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00007f,
+        data: 0x18000053,
+        name: "FDIV.S",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::div_f32(cpu.read_f32(f.rs1), cpu.read_f32(f.rs2), rm, &mut flags);
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
@@ -2485,8 +4604,8 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         mask: 0x0000707f,
         data: 0x0000100f,
         name: "FENCE.I",
-        operation: |_cpu, _word, _address| {
-            // Do nothing?
+        operation: |cpu, _word, _address| {
+            cpu.invalidate_blocks();
             Ok(())
         },
         disassemble: dump_empty,
@@ -2497,13 +4616,25 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         name: "FEQ.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = match cpu.f[f.rs1] == cpu.f[f.rs2] {
-                true => 1,
-                false => 0,
-            };
+            let mut flags = 0;
+            cpu.x[f.rd] = fpu::eq_f64(cpu.read_f64(f.rs1), cpu.read_f64(f.rs2), &mut flags) as i64;
+            cpu.accrue_fflags(flags);
             Ok(())
         },
-        disassemble: dump_empty,
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0xa0002053,
+        name: "FEQ.S",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let mut flags = 0;
+            cpu.x[f.rd] = fpu::eq_f32(cpu.read_f32(f.rs1), cpu.read_f32(f.rs2), &mut flags) as i64;
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
     },
     Instruction {
         mask: 0x0000707f,
@@ -2511,13 +4642,10 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         name: "FLD",
         operation: |cpu, word, _address| {
             let f = parse_format_i(word);
-            cpu.f[f.rd] = match cpu
+            let data = cpu
                 .mmu
-                .load_doubleword(cpu.x[f.rs1].wrapping_add(f.imm) as u64)
-            {
-                Ok(data) => f64::from_bits(data),
-                Err(e) => return Err(e),
-            };
+                .load_doubleword(cpu.x[f.rs1].wrapping_add(f.imm) as u64)?;
+            cpu.write_f(f.rd, data);
             Ok(())
         },
         disassemble: dump_format_i,
@@ -2528,10 +4656,22 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         name: "FLE.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = match cpu.f[f.rs1] <= cpu.f[f.rs2] {
-                true => 1,
-                false => 0,
-            };
+            let mut flags = 0;
+            cpu.x[f.rd] = fpu::le_f64(cpu.read_f64(f.rs1), cpu.read_f64(f.rs2), &mut flags) as i64;
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0xa0000053,
+        name: "FLE.S",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let mut flags = 0;
+            cpu.x[f.rd] = fpu::le_f32(cpu.read_f32(f.rs1), cpu.read_f32(f.rs2), &mut flags) as i64;
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
@@ -2542,10 +4682,22 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         name: "FLT.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = match cpu.f[f.rs1] < cpu.f[f.rs2] {
-                true => 1,
-                false => 0,
-            };
+            let mut flags = 0;
+            cpu.x[f.rd] = fpu::lt_f64(cpu.read_f64(f.rs1), cpu.read_f64(f.rs2), &mut flags) as i64;
+            cpu.accrue_fflags(flags);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0xa0001053,
+        name: "FLT.S",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let mut flags = 0;
+            cpu.x[f.rd] = fpu::lt_f32(cpu.read_f32(f.rs1), cpu.read_f32(f.rs2), &mut flags) as i64;
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
@@ -2556,10 +4708,8 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         name: "FLW",
         operation: |cpu, word, _address| {
             let f = parse_format_i(word);
-            cpu.f[f.rd] = match cpu.mmu.load_word(cpu.x[f.rs1].wrapping_add(f.imm) as u64) {
-                Ok(data) => f64::from_bits(data as i32 as i64 as u64),
-                Err(e) => return Err(e),
-            };
+            let data = cpu.mmu.load_word(cpu.x[f.rs1].wrapping_add(f.imm) as u64)?;
+            cpu.write_f32_bits(f.rd, data);
             Ok(())
         },
         disassemble: dump_format_i_mem,
@@ -2569,726 +4719,648 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
         data: 0x02000043,
         name: "FMADD.D",
         operation: |cpu, word, _address| {
-            // @TODO: Update fcsr if needed?
             let f = parse_format_r2(word);
-            cpu.f[f.rd] = cpu.f[f.rs1] * cpu.f[f.rs2] + cpu.f[f.rs3];
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::fused_mul_add_f64(
+                cpu.read_f64(f.rs1),
+                cpu.read_f64(f.rs2),
+                cpu.read_f64(f.rs3),
+                false,
+                false,
+                rm,
+                &mut flags,
+            );
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r2,
     },
     Instruction {
-        mask: 0xfe00007f,
-        data: 0x12000053,
-        name: "FMUL.D",
+        mask: 0x0600007f,
+        data: 0x00000043,
+        name: "FMADD.S",
         operation: |cpu, word, _address| {
-            // @TODO: Update fcsr if needed?
-            let f = parse_format_r(word);
-            cpu.f[f.rd] = cpu.f[f.rs1] * cpu.f[f.rs2];
+            let f = parse_format_r2(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::fused_mul_add_f32(
+                cpu.read_f32(f.rs1),
+                cpu.read_f32(f.rs2),
+                cpu.read_f32(f.rs3),
+                false,
+                false,
+                rm,
+                &mut flags,
+            );
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
-        disassemble: dump_format_r,
+        disassemble: dump_format_r2,
     },
     Instruction {
-        mask: 0xfff0707f,
-        data: 0xf2000053,
-        name: "FMV.D.X",
+        mask: 0xfe00707f,
+        data: 0x2a001053,
+        name: "FMAX.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.f[f.rd] = f64::from_bits(cpu.x[f.rs1] as u64);
+            let mut flags = 0;
+            let value = fpu::max_f64(cpu.read_f64(f.rs1), cpu.read_f64(f.rs2), &mut flags);
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfff0707f,
-        data: 0xe2000053,
-        name: "FMV.X.D",
+        mask: 0xfe00707f,
+        data: 0x28001053,
+        name: "FMAX.S",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = cpu.f[f.rs1].to_bits() as i64;
+            let mut flags = 0;
+            let value = fpu::max_f32(cpu.read_f32(f.rs1), cpu.read_f32(f.rs2), &mut flags);
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfff0707f,
-        data: 0xe0000053,
-        name: "FMV.X.W",
+        mask: 0xfe00707f,
+        data: 0x2a000053,
+        name: "FMIN.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = cpu.f[f.rs1].to_bits() as i32 as i64;
+            let mut flags = 0;
+            let value = fpu::min_f64(cpu.read_f64(f.rs1), cpu.read_f64(f.rs2), &mut flags);
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfff0707f,
-        data: 0xf0000053,
-        name: "FMV.W.X",
+        mask: 0xfe00707f,
+        data: 0x28000053,
+        name: "FMIN.S",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.f[f.rd] = f64::from_bits(cpu.x[f.rs1] as u32 as u64);
+            let mut flags = 0;
+            let value = fpu::min_f32(cpu.read_f32(f.rs1), cpu.read_f32(f.rs2), &mut flags);
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0x0600007f,
-        data: 0x0200004b,
-        name: "FNMSUB.D",
+        data: 0x02000047,
+        name: "FMSUB.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r2(word);
-            cpu.f[f.rd] = -(cpu.f[f.rs1] * cpu.f[f.rs2]) + cpu.f[f.rs3];
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::fused_mul_add_f64(
+                cpu.read_f64(f.rs1),
+                cpu.read_f64(f.rs2),
+                cpu.read_f64(f.rs3),
+                false,
+                true,
+                rm,
+                &mut flags,
+            );
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r2,
     },
     Instruction {
-        mask: 0x0000707f,
-        data: 0x00003027,
-        name: "FSD",
+        mask: 0x0600007f,
+        data: 0x00000047,
+        name: "FMSUB.S",
         operation: |cpu, word, _address| {
-            let f = parse_format_s(word);
-            cpu.mmu.store_doubleword(
-                cpu.x[f.rs1].wrapping_add(f.imm) as u64,
-                cpu.f[f.rs2].to_bits(),
-            )
+            let f = parse_format_r2(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::fused_mul_add_f32(
+                cpu.read_f32(f.rs1),
+                cpu.read_f32(f.rs2),
+                cpu.read_f32(f.rs3),
+                false,
+                true,
+                rm,
+                &mut flags,
+            );
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
+            Ok(())
         },
-        disassemble: dump_format_s,
+        disassemble: dump_format_r2,
     },
     Instruction {
-        mask: 0xfe00707f,
-        data: 0x22000053,
-        name: "FSGNJ.D",
+        mask: 0xfe00007f,
+        data: 0x12000053,
+        name: "FMUL.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            let rs1_bits = cpu.f[f.rs1].to_bits();
-            let rs2_bits = cpu.f[f.rs2].to_bits();
-            let sign_bit = rs2_bits & 0x8000000000000000;
-            cpu.f[f.rd] = f64::from_bits(sign_bit | (rs1_bits & 0x7fffffffffffffff));
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::mul_f64(cpu.read_f64(f.rs1), cpu.read_f64(f.rs2), rm, &mut flags);
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfe00707f,
-        data: 0x22002053,
-        name: "FSGNJX.D",
+        mask: 0xfe00007f,
+        data: 0x10000053,
+        name: "FMUL.S",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            let rs1_bits = cpu.f[f.rs1].to_bits();
-            let rs2_bits = cpu.f[f.rs2].to_bits();
-            let sign_bit = (rs1_bits ^ rs2_bits) & 0x8000000000000000;
-            cpu.f[f.rd] = f64::from_bits(sign_bit | (rs1_bits & 0x7fffffffffffffff));
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::mul_f32(cpu.read_f32(f.rs1), cpu.read_f32(f.rs2), rm, &mut flags);
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfe00007f,
-        data: 0x0a000053,
-        name: "FSUB.D",
+        mask: 0xfff0707f,
+        data: 0xf2000053,
+        name: "FMV.D.X",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            // @TODO: Update fcsr if needed?
-            cpu.f[f.rd] = cpu.f[f.rs1] - cpu.f[f.rs2];
+            cpu.write_f(f.rd, cpu.x[f.rs1] as u64);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0x0000707f,
-        data: 0x00002027,
-        name: "FSW",
+        mask: 0xfff0707f,
+        data: 0xe2000053,
+        name: "FMV.X.D",
         operation: |cpu, word, _address| {
-            let f = parse_format_s(word);
-            cpu.mmu.store_word(
-                cpu.x[f.rs1].wrapping_add(f.imm) as u64,
-                cpu.f[f.rs2].to_bits() as u32,
-            )
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.f[f.rs1] as i64;
+            Ok(())
         },
-        disassemble: dump_format_s,
+        disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0x0000007f,
-        data: 0x0000006f,
-        name: "JAL",
-        operation: |cpu, word, address| {
-            let f = parse_format_j(word);
-            cpu.x[f.rd] = cpu.sign_extend(cpu.pc as i64);
-            cpu.pc = address.wrapping_add(f.imm);
+        mask: 0xfff0707f,
+        data: 0xe0000053,
+        name: "FMV.X.W",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.f[f.rs1] as i32 as i64;
             Ok(())
         },
-        disassemble: dump_format_j,
+        disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0x0000707f,
-        data: 0x00000067,
-        name: "JALR",
+        mask: 0xfff0707f,
+        data: 0xf0000053,
+        name: "FMV.W.X",
         operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            let tmp = cpu.sign_extend(cpu.pc as i64);
-            cpu.pc = (cpu.x[f.rs1] as u64).wrapping_add(f.imm as u64);
-            cpu.x[f.rd] = tmp;
+            let f = parse_format_r(word);
+            cpu.write_f32_bits(f.rd, cpu.x[f.rs1] as u32);
             Ok(())
         },
-        disassemble: |cpu, word, _address, evaluate| {
-            let f = parse_format_i(word);
-            let mut s = String::new();
-            s += get_register_name(f.rd);
-            if evaluate {
-                s += &format!(":{:x}", cpu.x[f.rd]);
-            }
-            s += &format!(",{:x}({}", f.imm, get_register_name(f.rs1));
-            if evaluate {
-                s += &format!(":{:x}", cpu.x[f.rs1]);
-            }
-            s += ")";
-            s
-        },
+        disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0x0000707f,
-        data: 0x00000003,
-        name: "LB",
+        mask: 0x0600007f,
+        data: 0x0200004f,
+        name: "FNMADD.D",
         operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = match cpu.mmu.load(cpu.x[f.rs1].wrapping_add(f.imm) as u64) {
-                Ok(data) => data as i8 as i64,
-                Err(e) => return Err(e),
-            };
+            let f = parse_format_r2(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::fused_mul_add_f64(
+                cpu.read_f64(f.rs1),
+                cpu.read_f64(f.rs2),
+                cpu.read_f64(f.rs3),
+                true,
+                true,
+                rm,
+                &mut flags,
+            );
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
-        disassemble: dump_format_i_mem,
+        disassemble: dump_format_r2,
     },
     Instruction {
-        mask: 0x0000707f,
-        data: 0x00004003,
-        name: "LBU",
+        mask: 0x0600007f,
+        data: 0x0000004f,
+        name: "FNMADD.S",
         operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = match cpu.mmu.load(cpu.x[f.rs1].wrapping_add(f.imm) as u64) {
-                Ok(data) => data as i64,
-                Err(e) => return Err(e),
-            };
+            let f = parse_format_r2(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::fused_mul_add_f32(
+                cpu.read_f32(f.rs1),
+                cpu.read_f32(f.rs2),
+                cpu.read_f32(f.rs3),
+                true,
+                true,
+                rm,
+                &mut flags,
+            );
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
-        disassemble: dump_format_i_mem,
+        disassemble: dump_format_r2,
     },
     Instruction {
-        mask: 0x0000707f,
-        data: 0x00003003,
-        name: "LD",
+        mask: 0x0600007f,
+        data: 0x0200004b,
+        name: "FNMSUB.D",
         operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = match cpu
-                .mmu
-                .load_doubleword(cpu.x[f.rs1].wrapping_add(f.imm) as u64)
-            {
-                Ok(data) => data as i64,
-                Err(e) => return Err(e),
-            };
+            let f = parse_format_r2(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::fused_mul_add_f64(
+                cpu.read_f64(f.rs1),
+                cpu.read_f64(f.rs2),
+                cpu.read_f64(f.rs3),
+                true,
+                false,
+                rm,
+                &mut flags,
+            );
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
-        disassemble: dump_format_i_mem,
+        disassemble: dump_format_r2,
     },
     Instruction {
-        mask: 0x0000707f,
-        data: 0x00001003,
-        name: "LH",
+        mask: 0x0600007f,
+        data: 0x0000004b,
+        name: "FNMSUB.S",
         operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = match cpu
-                .mmu
-                .load_halfword(cpu.x[f.rs1].wrapping_add(f.imm) as u64)
-            {
-                Ok(data) => data as i16 as i64,
-                Err(e) => return Err(e),
-            };
+            let f = parse_format_r2(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::fused_mul_add_f32(
+                cpu.read_f32(f.rs1),
+                cpu.read_f32(f.rs2),
+                cpu.read_f32(f.rs3),
+                true,
+                false,
+                rm,
+                &mut flags,
+            );
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
-        disassemble: dump_format_i_mem,
+        disassemble: dump_format_r2,
     },
     Instruction {
         mask: 0x0000707f,
-        data: 0x00005003,
-        name: "LHU",
+        data: 0x00003027,
+        name: "FSD",
         operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = match cpu
-                .mmu
-                .load_halfword(cpu.x[f.rs1].wrapping_add(f.imm) as u64)
-            {
-                Ok(data) => data as i64,
-                Err(e) => return Err(e),
-            };
-            Ok(())
+            let f = parse_format_s(word);
+            cpu.mmu
+                .store_doubleword(cpu.x[f.rs1].wrapping_add(f.imm) as u64, cpu.f[f.rs2])
         },
-        disassemble: dump_format_i_mem,
+        disassemble: dump_format_s,
     },
     Instruction {
-        mask: 0xf9f0707f,
-        data: 0x1000302f,
-        name: "LR.D",
+        mask: 0xfe00707f,
+        data: 0x22000053,
+        name: "FSGNJ.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            // @TODO: Implement properly
-            cpu.x[f.rd] = match cpu.mmu.load_doubleword(cpu.x[f.rs1] as u64) {
-                Ok(data) => {
-                    cpu.is_reservation_set = true;
-                    cpu.reservation = cpu.x[f.rs1] as u64; // Is virtual address ok?
-                    data as i64
-                }
-                Err(e) => return Err(e),
-            };
+            let rs1_bits = cpu.f[f.rs1];
+            let rs2_bits = cpu.f[f.rs2];
+            let sign_bit = rs2_bits & 0x8000000000000000;
+            cpu.write_f(f.rd, sign_bit | (rs1_bits & 0x7fffffffffffffff));
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xf9f0707f,
-        data: 0x1000202f,
-        name: "LR.W",
+        mask: 0xfe00707f,
+        data: 0x20000053,
+        name: "FSGNJ.S",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            // @TODO: Implement properly
-            cpu.x[f.rd] = match cpu.mmu.load_word(cpu.x[f.rs1] as u64) {
-                Ok(data) => {
-                    cpu.is_reservation_set = true;
-                    cpu.reservation = cpu.x[f.rs1] as u64; // Is virtual address ok?
-                    data as i32 as i64
-                }
-                Err(e) => return Err(e),
-            };
+            let rs1_bits = cpu.read_f32_bits(f.rs1);
+            let rs2_bits = cpu.read_f32_bits(f.rs2);
+            let sign_bit = rs2_bits & 0x80000000;
+            cpu.write_f32_bits(f.rd, sign_bit | (rs1_bits & 0x7fffffff));
             Ok(())
         },
         disassemble: dump_format_r,
     },
-    Instruction {
-        mask: 0x0000007f,
-        data: 0x00000037,
-        name: "LUI",
-        operation: |cpu, word, _address| {
-            let f = parse_format_u(word);
-            cpu.x[f.rd] = f.imm as i64;
-            Ok(())
-        },
-        disassemble: dump_format_u,
-    },
-    Instruction {
-        mask: 0x0000707f,
-        data: 0x00002003,
-        name: "LW",
-        operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = match cpu.mmu.load_word(cpu.x[f.rs1].wrapping_add(f.imm) as u64) {
-                Ok(data) => data as i32 as i64,
-                Err(e) => return Err(e),
-            };
-            Ok(())
-        },
-        disassemble: dump_format_i_mem,
-    },
-    Instruction {
-        mask: 0x0000707f,
-        data: 0x00006003,
-        name: "LWU",
-        operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = match cpu.mmu.load_word(cpu.x[f.rs1].wrapping_add(f.imm) as u64) {
-                Ok(data) => data as i64,
-                Err(e) => return Err(e),
-            };
-            Ok(())
-        },
-        disassemble: dump_format_i_mem,
-    },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x02000033,
-        name: "MUL",
+        data: 0x22001053,
+        name: "FSGNJN.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_mul(cpu.x[f.rs2]));
+            let rs1_bits = cpu.f[f.rs1];
+            let rs2_bits = cpu.f[f.rs2];
+            let sign_bit = !rs2_bits & 0x8000000000000000;
+            cpu.write_f(f.rd, sign_bit | (rs1_bits & 0x7fffffffffffffff));
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x02001033,
-        name: "MULH",
+        data: 0x20001053,
+        name: "FSGNJN.S",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = match cpu.xlen {
-                Xlen::Bit32 => cpu.sign_extend((cpu.x[f.rs1] * cpu.x[f.rs2]) >> 32),
-                Xlen::Bit64 => (((cpu.x[f.rs1] as i128) * (cpu.x[f.rs2] as i128)) >> 64) as i64,
-            };
+            let rs1_bits = cpu.read_f32_bits(f.rs1);
+            let rs2_bits = cpu.read_f32_bits(f.rs2);
+            let sign_bit = !rs2_bits & 0x80000000;
+            cpu.write_f32_bits(f.rd, sign_bit | (rs1_bits & 0x7fffffff));
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x02003033,
-        name: "MULHU",
+        data: 0x22002053,
+        name: "FSGNJX.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = match cpu.xlen {
-                Xlen::Bit32 => cpu.sign_extend(
-                    (((cpu.x[f.rs1] as u32 as u64) * (cpu.x[f.rs2] as u32 as u64)) >> 32) as i64,
-                ),
-                Xlen::Bit64 => {
-                    ((cpu.x[f.rs1] as u64 as u128).wrapping_mul(cpu.x[f.rs2] as u64 as u128) >> 64)
-                        as i64
-                }
-            };
+            let rs1_bits = cpu.f[f.rs1];
+            let rs2_bits = cpu.f[f.rs2];
+            let sign_bit = (rs1_bits ^ rs2_bits) & 0x8000000000000000;
+            cpu.write_f(f.rd, sign_bit | (rs1_bits & 0x7fffffffffffffff));
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x02002033,
-        name: "MULHSU",
+        data: 0x20002053,
+        name: "FSGNJX.S",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = match cpu.xlen {
-                Xlen::Bit32 => cpu.sign_extend(
-                    ((cpu.x[f.rs1] as i64).wrapping_mul(cpu.x[f.rs2] as u32 as i64) >> 32) as i64,
-                ),
-                Xlen::Bit64 => {
-                    ((cpu.x[f.rs1] as u128).wrapping_mul(cpu.x[f.rs2] as u64 as u128) >> 64) as i64
-                }
-            };
+            let rs1_bits = cpu.read_f32_bits(f.rs1);
+            let rs2_bits = cpu.read_f32_bits(f.rs2);
+            let sign_bit = (rs1_bits ^ rs2_bits) & 0x80000000;
+            cpu.write_f32_bits(f.rd, sign_bit | (rs1_bits & 0x7fffffff));
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfe00707f,
-        data: 0x0200003b,
-        name: "MULW",
+        mask: 0xfff0007f,
+        data: 0x5a000053,
+        name: "FSQRT.D",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] =
-                cpu.sign_extend((cpu.x[f.rs1] as i32).wrapping_mul(cpu.x[f.rs2] as i32) as i64);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::sqrt_f64(cpu.read_f64(f.rs1), rm, &mut flags);
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xffffffff,
-        data: 0x30200073,
-        name: "MRET",
-        operation: |cpu, _word, _address| {
-            cpu.pc = match cpu.read_csr(CSR_MEPC_ADDRESS) {
-                Ok(data) => data,
-                Err(e) => return Err(e),
-            };
-            let status = cpu.read_csr_raw(CSR_MSTATUS_ADDRESS);
-            let mpie = (status >> 7) & 1;
-            let mpp = (status >> 11) & 0x3;
-            let mprv = match get_privilege_mode(mpp) {
-                PrivilegeMode::Machine => (status >> 17) & 1,
-                _ => 0,
-            };
-            // Override MIE[3] with MPIE[7], set MPIE[7] to 1, set MPP[12:11] to 0
-            // and override MPRV[17]
-            let new_status = (status & !0x21888) | (mprv << 17) | (mpie << 3) | (1 << 7);
-            cpu.write_csr_raw(CSR_MSTATUS_ADDRESS, new_status);
-            cpu.privilege_mode = match mpp {
-                0 => PrivilegeMode::User,
-                1 => PrivilegeMode::Supervisor,
-                3 => PrivilegeMode::Machine,
-                _ => panic!(), // Shouldn't happen
-            };
-            cpu.mmu.update_privilege_mode(cpu.privilege_mode.clone());
-            Ok(())
-        },
-        disassemble: dump_empty,
-    },
-    Instruction {
-        mask: 0xfe00707f,
-        data: 0x00006033,
-        name: "OR",
+        mask: 0xfff0007f,
+        data: 0x58000053,
+        name: "FSQRT.S",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] | cpu.x[f.rs2]);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::sqrt_f32(cpu.read_f32(f.rs1), rm, &mut flags);
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0x0000707f,
-        data: 0x00006013,
-        name: "ORI",
+        mask: 0xfe00007f,
+        data: 0x0a000053,
+        name: "FSUB.D",
         operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] | f.imm);
+            let f = parse_format_r(word);
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::sub_f64(cpu.read_f64(f.rs1), cpu.read_f64(f.rs2), rm, &mut flags);
+            cpu.write_f64(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
-        disassemble: dump_format_i,
+        disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfe00707f,
-        data: 0x02006033,
-        name: "REM",
+        mask: 0xfe00007f,
+        data: 0x08000053,
+        name: "FSUB.S",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            let dividend = cpu.x[f.rs1];
-            let divisor = cpu.x[f.rs2];
-            if divisor == 0 {
-                cpu.x[f.rd] = dividend;
-            } else if dividend == cpu.most_negative() && divisor == -1 {
-                cpu.x[f.rd] = 0;
-            } else {
-                cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_rem(cpu.x[f.rs2]));
-            }
+            let rm = cpu.get_rounding_mode(word)?;
+            let mut flags = 0;
+            let value = fpu::sub_f32(cpu.read_f32(f.rs1), cpu.read_f32(f.rs2), rm, &mut flags);
+            cpu.write_f32(f.rd, value);
+            cpu.accrue_fflags(flags);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfe00707f,
-        data: 0x02007033,
-        name: "REMU",
+        mask: 0x0000707f,
+        data: 0x00002027,
+        name: "FSW",
         operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            let dividend = cpu.unsigned_data(cpu.x[f.rs1]);
-            let divisor = cpu.unsigned_data(cpu.x[f.rs2]);
-            cpu.x[f.rd] = match divisor {
-                0 => cpu.sign_extend(dividend as i64),
-                _ => cpu.sign_extend(dividend.wrapping_rem(divisor) as i64),
-            };
-            Ok(())
+            let f = parse_format_s(word);
+            cpu.mmu
+                .store_word(cpu.x[f.rs1].wrapping_add(f.imm) as u64, cpu.f[f.rs2] as u32)
         },
-        disassemble: dump_format_r,
+        disassemble: dump_format_s,
     },
     Instruction {
-        mask: 0xfe00707f,
-        data: 0x0200703b,
-        name: "REMUW",
-        operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            let dividend = cpu.x[f.rs1] as u32;
-            let divisor = cpu.x[f.rs2] as u32;
-            cpu.x[f.rd] = match divisor {
-                0 => dividend as i32 as i64,
-                _ => dividend.wrapping_rem(divisor) as i32 as i64,
-            };
-            Ok(())
-        },
-        disassemble: dump_format_r,
+        mask: 0x0000007f,
+        data: 0x0000006f,
+        name: "JAL",
+        operation: |cpu, word, address| jal(cpu, &Operands::format_j(word), address),
+        disassemble: dump_format_j,
     },
     Instruction {
-        mask: 0xfe00707f,
-        data: 0x0200603b,
-        name: "REMW",
-        operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            let dividend = cpu.x[f.rs1] as i32;
-            let divisor = cpu.x[f.rs2] as i32;
-            if divisor == 0 {
-                cpu.x[f.rd] = dividend as i64;
-            } else if dividend == std::i32::MIN && divisor == -1 {
-                cpu.x[f.rd] = 0;
-            } else {
-                cpu.x[f.rd] = dividend.wrapping_rem(divisor) as i64;
+        mask: 0x0000707f,
+        data: 0x00000067,
+        name: "JALR",
+        operation: |cpu, word, address| jalr(cpu, &Operands::format_i(word), address),
+        disassemble: |cpu, word, _address, evaluate| {
+            let f = parse_format_i(word);
+            let mut s = String::new();
+            s += get_register_name(f.rd);
+            if evaluate {
+                s += &format!(":{:x}", cpu.x[f.rd]);
             }
-            Ok(())
+            s += &format!(",{:x}({}", f.imm, get_register_name(f.rs1));
+            if evaluate {
+                s += &format!(":{:x}", cpu.x[f.rs1]);
+            }
+            s += ")";
+            s
         },
-        disassemble: dump_format_r,
     },
     Instruction {
         mask: 0x0000707f,
-        data: 0x00000023,
-        name: "SB",
-        operation: |cpu, word, _address| {
-            let f = parse_format_s(word);
-            cpu.mmu
-                .store(cpu.x[f.rs1].wrapping_add(f.imm) as u64, cpu.x[f.rs2] as u8)
-        },
-        disassemble: dump_format_s,
+        data: 0x00000003,
+        name: "LB",
+        operation: |cpu, word, address| lb(cpu, &Operands::format_i(word), address),
+        disassemble: dump_format_i_mem,
     },
     Instruction {
-        mask: 0xf800707f,
-        data: 0x1800302f,
-        name: "SC.D",
-        operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            // @TODO: Implement properly
-            cpu.x[f.rd] = match cpu.is_reservation_set && cpu.reservation == (cpu.x[f.rs1] as u64) {
-                true => match cpu
-                    .mmu
-                    .store_doubleword(cpu.x[f.rs1] as u64, cpu.x[f.rs2] as u64)
-                {
-                    Ok(()) => {
-                        cpu.is_reservation_set = false;
-                        0
-                    }
-                    Err(e) => return Err(e),
-                },
-                false => 1,
-            };
-            Ok(())
-        },
+        mask: 0x0000707f,
+        data: 0x00004003,
+        name: "LBU",
+        operation: |cpu, word, address| lbu(cpu, &Operands::format_i(word), address),
+        disassemble: dump_format_i_mem,
+    },
+    Instruction {
+        mask: 0x0000707f,
+        data: 0x00003003,
+        name: "LD",
+        operation: |cpu, word, address| ld(cpu, &Operands::format_i(word), address),
+        disassemble: dump_format_i_mem,
+    },
+    Instruction {
+        mask: 0x0000707f,
+        data: 0x00001003,
+        name: "LH",
+        operation: |cpu, word, address| lh(cpu, &Operands::format_i(word), address),
+        disassemble: dump_format_i_mem,
+    },
+    Instruction {
+        mask: 0x0000707f,
+        data: 0x00005003,
+        name: "LHU",
+        operation: |cpu, word, address| lhu(cpu, &Operands::format_i(word), address),
+        disassemble: dump_format_i_mem,
+    },
+    Instruction {
+        mask: 0xf9f0707f,
+        data: 0x1000302f,
+        name: "LR.D",
+        operation: |cpu, word, _address| cpu.load_reserved(word, 8),
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xf800707f,
-        data: 0x1800202f,
-        name: "SC.W",
-        operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            // @TODO: Implement properly
-            cpu.x[f.rd] = match cpu.is_reservation_set && cpu.reservation == (cpu.x[f.rs1] as u64) {
-                true => match cpu.mmu.store_word(cpu.x[f.rs1] as u64, cpu.x[f.rs2] as u32) {
-                    Ok(()) => {
-                        cpu.is_reservation_set = false;
-                        0
-                    }
-                    Err(e) => return Err(e),
-                },
-                false => 1,
-            };
-            Ok(())
-        },
+        mask: 0xf9f0707f,
+        data: 0x1000202f,
+        name: "LR.W",
+        operation: |cpu, word, _address| cpu.load_reserved(word, 4),
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0x0000707f,
-        data: 0x00003023,
-        name: "SD",
-        operation: |cpu, word, _address| {
-            let f = parse_format_s(word);
-            cpu.mmu
-                .store_doubleword(cpu.x[f.rs1].wrapping_add(f.imm) as u64, cpu.x[f.rs2] as u64)
-        },
-        disassemble: dump_format_s,
+        mask: 0x0000007f,
+        data: 0x00000037,
+        name: "LUI",
+        operation: |cpu, word, address| lui(cpu, &Operands::format_u(word), address),
+        disassemble: dump_format_u,
     },
     Instruction {
-        mask: 0xfe007fff,
-        data: 0x12000073,
-        name: "SFENCE.VMA",
-        operation: |_cpu, _word, _address| {
-            // Do nothing?
-            Ok(())
-        },
-        disassemble: dump_empty,
+        mask: 0x0000707f,
+        data: 0x00002003,
+        name: "LW",
+        operation: |cpu, word, address| lw(cpu, &Operands::format_i(word), address),
+        disassemble: dump_format_i_mem,
     },
     Instruction {
         mask: 0x0000707f,
-        data: 0x00001023,
-        name: "SH",
-        operation: |cpu, word, _address| {
-            let f = parse_format_s(word);
-            cpu.mmu
-                .store_halfword(cpu.x[f.rs1].wrapping_add(f.imm) as u64, cpu.x[f.rs2] as u16)
-        },
-        disassemble: dump_format_s,
+        data: 0x00006003,
+        name: "LWU",
+        operation: |cpu, word, address| lwu(cpu, &Operands::format_i(word), address),
+        disassemble: dump_format_i_mem,
     },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x00001033,
-        name: "SLL",
+        data: 0x0a006033,
+        name: "MAX",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_shl(cpu.x[f.rs2] as u32));
+            cpu.x[f.rd] = cpu.x[f.rs1].max(cpu.x[f.rs2]);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfc00707f,
-        data: 0x00001013,
-        name: "SLLI",
+        mask: 0xfe00707f,
+        data: 0x0a007033,
+        name: "MAXU",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            let mask = match cpu.xlen {
-                Xlen::Bit32 => 0x1f,
-                Xlen::Bit64 => 0x3f,
+            cpu.x[f.rd] = match cpu.unsigned_data(cpu.x[f.rs1]) >= cpu.unsigned_data(cpu.x[f.rs2]) {
+                true => cpu.x[f.rs1],
+                false => cpu.x[f.rs2],
             };
-            let shamt = (word >> 20) & mask;
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] << shamt);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x0000101b,
-        name: "SLLIW",
+        data: 0x0a004033,
+        name: "MIN",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            let shamt = f.rs2 as u32;
-            cpu.x[f.rd] = (cpu.x[f.rs1] << shamt) as i32 as i64;
+            cpu.x[f.rd] = cpu.x[f.rs1].min(cpu.x[f.rs2]);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x0000103b,
-        name: "SLLW",
+        data: 0x0a005033,
+        name: "MINU",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = (cpu.x[f.rs1] as u32).wrapping_shl(cpu.x[f.rs2] as u32) as i32 as i64;
+            cpu.x[f.rd] = match cpu.unsigned_data(cpu.x[f.rs1]) <= cpu.unsigned_data(cpu.x[f.rs2]) {
+                true => cpu.x[f.rs1],
+                false => cpu.x[f.rs2],
+            };
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x00002033,
-        name: "SLT",
+        data: 0x02000033,
+        name: "MUL",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = match cpu.x[f.rs1] < cpu.x[f.rs2] {
-                true => 1,
-                false => 0,
-            };
+            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_mul(cpu.x[f.rs2]));
             Ok(())
         },
         disassemble: dump_format_r,
     },
-    Instruction {
-        mask: 0x0000707f,
-        data: 0x00002013,
-        name: "SLTI",
-        operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = match cpu.x[f.rs1] < f.imm {
-                true => 1,
-                false => 0,
-            };
-            Ok(())
-        },
-        disassemble: dump_format_i,
-    },
-    Instruction {
-        mask: 0x0000707f,
-        data: 0x00003013,
-        name: "SLTIU",
-        operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = match cpu.unsigned_data(cpu.x[f.rs1]) < cpu.unsigned_data(f.imm) {
-                true => 1,
-                false => 0,
-            };
-            Ok(())
-        },
-        disassemble: dump_format_i,
-    },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x00003033,
-        name: "SLTU",
+        data: 0x02001033,
+        name: "MULH",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = match cpu.unsigned_data(cpu.x[f.rs1]) < cpu.unsigned_data(cpu.x[f.rs2]) {
-                true => 1,
-                false => 0,
+            cpu.x[f.rd] = match cpu.xlen {
+                Xlen::Bit32 => cpu.sign_extend((cpu.x[f.rs1] * cpu.x[f.rs2]) >> 32),
+                Xlen::Bit64 => (((cpu.x[f.rs1] as i128) * (cpu.x[f.rs2] as i128)) >> 64) as i64,
             };
             Ok(())
         },
@@ -3296,80 +5368,79 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
     },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x40005033,
-        name: "SRA",
-        operation: |cpu, word, _address| {
-            let f = parse_format_r(word);
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_shr(cpu.x[f.rs2] as u32));
-            Ok(())
-        },
-        disassemble: dump_format_r,
-    },
-    Instruction {
-        mask: 0xfc00707f,
-        data: 0x40005013,
-        name: "SRAI",
+        data: 0x02003033,
+        name: "MULHU",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            let mask = match cpu.xlen {
-                Xlen::Bit32 => 0x1f,
-                Xlen::Bit64 => 0x3f,
+            cpu.x[f.rd] = match cpu.xlen {
+                Xlen::Bit32 => cpu.sign_extend(
+                    (((cpu.x[f.rs1] as u32 as u64) * (cpu.x[f.rs2] as u32 as u64)) >> 32) as i64,
+                ),
+                Xlen::Bit64 => {
+                    ((cpu.x[f.rs1] as u64 as u128).wrapping_mul(cpu.x[f.rs2] as u64 as u128) >> 64)
+                        as i64
+                }
             };
-            let shamt = (word >> 20) & mask;
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] >> shamt);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfc00707f,
-        data: 0x4000501b,
-        name: "SRAIW",
+        mask: 0xfe00707f,
+        data: 0x02002033,
+        name: "MULHSU",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            let shamt = (word >> 20) & 0x1f;
-            cpu.x[f.rd] = ((cpu.x[f.rs1] as i32) >> shamt) as i64;
+            cpu.x[f.rd] = match cpu.xlen {
+                Xlen::Bit32 => {
+                    cpu.sign_extend(cpu.x[f.rs1].wrapping_mul(cpu.x[f.rs2] as u32 as i64) >> 32)
+                }
+                Xlen::Bit64 => {
+                    ((cpu.x[f.rs1] as u128).wrapping_mul(cpu.x[f.rs2] as u64 as u128) >> 64) as i64
+                }
+            };
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x4000503b,
-        name: "SRAW",
+        data: 0x0200003b,
+        name: "MULW",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = (cpu.x[f.rs1] as i32).wrapping_shr(cpu.x[f.rs2] as u32) as i64;
+            cpu.x[f.rd] =
+                cpu.sign_extend((cpu.x[f.rs1] as i32).wrapping_mul(cpu.x[f.rs2] as i32) as i64);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xffffffff,
-        data: 0x10200073,
-        name: "SRET",
+        data: 0x30200073,
+        name: "MRET",
         operation: |cpu, _word, _address| {
-            // @TODO: Throw error if higher privilege return instruction is executed
-            cpu.pc = match cpu.read_csr(CSR_SEPC_ADDRESS) {
+            cpu.pc = match cpu.read_csr(CSR_MEPC_ADDRESS) {
                 Ok(data) => data,
                 Err(e) => return Err(e),
             };
-            let status = cpu.read_csr_raw(CSR_SSTATUS_ADDRESS);
-            let spie = (status >> 5) & 1;
-            let spp = (status >> 8) & 1;
-            let mprv = match get_privilege_mode(spp) {
+            let status = cpu.read_csr_raw(CSR_MSTATUS_ADDRESS);
+            let mpie = (status >> 7) & 1;
+            // MPP is WARL and never holds the reserved encoding
+            let privilege_mode = match (status >> 11) & 0x3 {
+                0 => PrivilegeMode::User,
+                1 => PrivilegeMode::Supervisor,
+                _ => PrivilegeMode::Machine,
+            };
+            let mprv = match privilege_mode {
                 PrivilegeMode::Machine => (status >> 17) & 1,
                 _ => 0,
             };
-            // Override SIE[1] with SPIE[5], set SPIE[5] to 1, set SPP[8] to 0,
+            // Override MIE[3] with MPIE[7], set MPIE[7] to 1, set MPP[12:11] to 0
             // and override MPRV[17]
-            let new_status = (status & !0x20122) | (mprv << 17) | (spie << 1) | (1 << 5);
-            cpu.write_csr_raw(CSR_SSTATUS_ADDRESS, new_status);
-            cpu.privilege_mode = match spp {
-                0 => PrivilegeMode::User,
-                1 => PrivilegeMode::Supervisor,
-                _ => panic!(), // Shouldn't happen
-            };
+            let new_status = (status & !0x21888) | (mprv << 17) | (mpie << 3) | (1 << 7);
+            cpu.write_csr_raw(CSR_MSTATUS_ADDRESS, new_status);
+            cpu.privilege_mode = privilege_mode;
             cpu.mmu.update_privilege_mode(cpu.privilege_mode.clone());
             Ok(())
         },
@@ -3377,662 +5448,8165 @@ const INSTRUCTIONS: [Instruction; INSTRUCTION_NUM] = [
     },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x00005033,
-        name: "SRL",
-        operation: |cpu, word, _address| {
+        data: 0x00006033,
+        name: "OR",
+        operation: |cpu, word, address| or(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x28705013,
+        name: "ORC.B",
+        operation: |cpu, word, _address| {
             let f = parse_format_r(word);
             cpu.x[f.rd] = cpu.sign_extend(
-                cpu.unsigned_data(cpu.x[f.rs1])
-                    .wrapping_shr(cpu.x[f.rs2] as u32) as i64,
+                cpu.x[f.rs1]
+                    .to_le_bytes()
+                    .iter()
+                    .rev()
+                    .fold(0, |value, byte| {
+                        (value << 8) | if *byte != 0 { 0xff } else { 0 }
+                    }),
             );
             Ok(())
         },
+        disassemble: dump_format_r_unary,
+    },
+    Instruction {
+        mask: 0x0000707f,
+        data: 0x00006013,
+        name: "ORI",
+        operation: |cpu, word, address| ori(cpu, &Operands::format_i(word), address),
+        disassemble: dump_format_i,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x40006033,
+        name: "ORN",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.x[f.rs1] | !cpu.x[f.rs2];
+            Ok(())
+        },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfc00707f,
-        data: 0x00005013,
-        name: "SRLI",
+        mask: 0xfe00707f,
+        data: 0x02006033,
+        name: "REM",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let dividend = cpu.x[f.rs1];
+            let divisor = cpu.x[f.rs2];
+            if divisor == 0 {
+                cpu.x[f.rd] = dividend;
+            } else if dividend == cpu.most_negative() && divisor == -1 {
+                cpu.x[f.rd] = 0;
+            } else {
+                cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_rem(cpu.x[f.rs2]));
+            }
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x02007033,
+        name: "REMU",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            let mask = match cpu.xlen {
-                Xlen::Bit32 => 0x1f,
-                Xlen::Bit64 => 0x3f,
+            let dividend = cpu.unsigned_data(cpu.x[f.rs1]);
+            let divisor = cpu.unsigned_data(cpu.x[f.rs2]);
+            cpu.x[f.rd] = match divisor {
+                0 => cpu.sign_extend(dividend as i64),
+                _ => cpu.sign_extend(dividend.wrapping_rem(divisor) as i64),
             };
-            let shamt = (word >> 20) & mask;
-            cpu.x[f.rd] = cpu.sign_extend((cpu.unsigned_data(cpu.x[f.rs1]) >> shamt) as i64);
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0xfc00707f,
-        data: 0x0000501b,
-        name: "SRLIW",
+        mask: 0xfe00707f,
+        data: 0x0200703b,
+        name: "REMUW",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            let mask = match cpu.xlen {
-                Xlen::Bit32 => 0x1f,
-                Xlen::Bit64 => 0x3f,
+            let dividend = cpu.x[f.rs1] as u32;
+            let divisor = cpu.x[f.rs2] as u32;
+            cpu.x[f.rd] = match divisor {
+                0 => dividend as i32 as i64,
+                _ => dividend.wrapping_rem(divisor) as i32 as i64,
             };
-            let shamt = (word >> 20) & mask;
-            cpu.x[f.rd] = ((cpu.x[f.rs1] as u32) >> shamt) as i32 as i64;
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x0000503b,
-        name: "SRLW",
+        data: 0x0200603b,
+        name: "REMW",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = (cpu.x[f.rs1] as u32).wrapping_shr(cpu.x[f.rs2] as u32) as i32 as i64;
+            let dividend = cpu.x[f.rs1] as i32;
+            let divisor = cpu.x[f.rs2] as i32;
+            if divisor == 0 {
+                cpu.x[f.rd] = dividend as i64;
+            } else if dividend == i32::MIN && divisor == -1 {
+                cpu.x[f.rd] = 0;
+            } else {
+                cpu.x[f.rd] = dividend.wrapping_rem(divisor) as i64;
+            }
             Ok(())
         },
         disassemble: dump_format_r,
     },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x69805013,
+        name: "REV8",
+        operation: |cpu, word, _address| {
+            cpu.check_xlen(32)?;
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = (cpu.x[f.rs1] as u32).swap_bytes() as i32 as i64;
+            Ok(())
+        },
+        disassemble: dump_format_r_unary,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x6b805013,
+        name: "REV8",
+        operation: |cpu, word, _address| {
+            cpu.check_xlen(64)?;
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.x[f.rs1].swap_bytes();
+            Ok(())
+        },
+        disassemble: dump_format_r_unary,
+    },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x40000033,
-        name: "SUB",
+        data: 0x60001033,
+        name: "ROL",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_sub(cpu.x[f.rs2]));
+            cpu.x[f.rd] = match cpu.xlen {
+                Xlen::Bit32 => {
+                    (cpu.x[f.rs1] as u32).rotate_left(cpu.x[f.rs2] as u32 & 0x1f) as i32 as i64
+                }
+                Xlen::Bit64 => cpu.x[f.rs1].rotate_left(cpu.x[f.rs2] as u32 & 0x3f),
+            };
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x4000003b,
-        name: "SUBW",
+        data: 0x6000103b,
+        name: "ROLW",
         operation: |cpu, word, _address| {
+            cpu.check_xlen(64)?;
             let f = parse_format_r(word);
-            cpu.x[f.rd] = cpu.x[f.rs1].wrapping_sub(cpu.x[f.rs2]) as i32 as i64;
+            cpu.x[f.rd] =
+                (cpu.x[f.rs1] as u32).rotate_left(cpu.x[f.rs2] as u32 & 0x1f) as i32 as i64;
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0x0000707f,
-        data: 0x00002023,
-        name: "SW",
+        mask: 0xfe00707f,
+        data: 0x60005033,
+        name: "ROR",
         operation: |cpu, word, _address| {
-            let f = parse_format_s(word);
-            cpu.mmu
-                .store_word(cpu.x[f.rs1].wrapping_add(f.imm) as u64, cpu.x[f.rs2] as u32)
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = match cpu.xlen {
+                Xlen::Bit32 => {
+                    (cpu.x[f.rs1] as u32).rotate_right(cpu.x[f.rs2] as u32 & 0x1f) as i32 as i64
+                }
+                Xlen::Bit64 => cpu.x[f.rs1].rotate_right(cpu.x[f.rs2] as u32 & 0x3f),
+            };
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x60005013,
+        name: "RORI",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            let shamt = (word >> 20) & (cpu.get_xlen_bits() - 1);
+            cpu.x[f.rd] = match cpu.xlen {
+                Xlen::Bit32 => (cpu.x[f.rs1] as u32).rotate_right(shamt) as i32 as i64,
+                Xlen::Bit64 => cpu.x[f.rs1].rotate_right(shamt),
+            };
+            Ok(())
+        },
+        disassemble: dump_format_r_shamt,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x6000501b,
+        name: "RORIW",
+        operation: |cpu, word, _address| {
+            cpu.check_xlen(64)?;
+            let f = parse_format_r(word);
+            let shamt = (word >> 20) & 0x1f;
+            cpu.x[f.rd] = (cpu.x[f.rs1] as u32).rotate_right(shamt) as i32 as i64;
+            Ok(())
+        },
+        disassemble: dump_format_r_shamt,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x6000503b,
+        name: "RORW",
+        operation: |cpu, word, _address| {
+            cpu.check_xlen(64)?;
+            let f = parse_format_r(word);
+            cpu.x[f.rd] =
+                (cpu.x[f.rs1] as u32).rotate_right(cpu.x[f.rs2] as u32 & 0x1f) as i32 as i64;
+            Ok(())
         },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0x0000707f,
+        data: 0x00000023,
+        name: "SB",
+        operation: |cpu, word, address| sb(cpu, &Operands::format_s(word), address),
         disassemble: dump_format_s,
     },
     Instruction {
-        mask: 0xffffffff,
-        data: 0x00200073,
-        name: "URET",
-        operation: |_cpu, _word, _address| {
-            // @TODO: Implement
-            panic!("URET instruction is not implemented yet.");
+        mask: 0xf800707f,
+        data: 0x1800302f,
+        name: "SC.D",
+        operation: |cpu, word, _address| cpu.store_conditional(word, 8),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xf800707f,
+        data: 0x1800202f,
+        name: "SC.W",
+        operation: |cpu, word, _address| cpu.store_conditional(word, 4),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0x0000707f,
+        data: 0x00003023,
+        name: "SD",
+        operation: |cpu, word, address| sd(cpu, &Operands::format_s(word), address),
+        disassemble: dump_format_s,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x60401013,
+        name: "SEXT.B",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.x[f.rs1] as i8 as i64;
+            Ok(())
         },
-        disassemble: dump_empty,
+        disassemble: dump_format_r_unary,
     },
     Instruction {
-        mask: 0xffffffff,
-        data: 0x10500073,
-        name: "WFI",
+        mask: 0xfff0707f,
+        data: 0x60501013,
+        name: "SEXT.H",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.x[f.rs1] as i16 as i64;
+            Ok(())
+        },
+        disassemble: dump_format_r_unary,
+    },
+    Instruction {
+        mask: 0xfe007fff,
+        data: 0x12000073,
+        name: "SFENCE.VMA",
         operation: |cpu, _word, _address| {
-            cpu.wfi = true;
+            // @TODO: Flush only the address and the address space specified
+            cpu.mmu.clear_page_cache();
+            cpu.invalidate_blocks();
             Ok(())
         },
         disassemble: dump_empty,
     },
+    Instruction {
+        mask: 0x0000707f,
+        data: 0x00001023,
+        name: "SH",
+        operation: |cpu, word, address| sh(cpu, &Operands::format_s(word), address),
+        disassemble: dump_format_s,
+    },
     Instruction {
         mask: 0xfe00707f,
-        data: 0x00004033,
-        name: "XOR",
+        data: 0x20002033,
+        name: "SH1ADD",
         operation: |cpu, word, _address| {
             let f = parse_format_r(word);
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] ^ cpu.x[f.rs2]);
+            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs2].wrapping_add(cpu.x[f.rs1] << 1));
             Ok(())
         },
         disassemble: dump_format_r,
     },
     Instruction {
-        mask: 0x0000707f,
-        data: 0x00004013,
-        name: "XORI",
+        mask: 0xfe00707f,
+        data: 0x2000203b,
+        name: "SH1ADD.UW",
         operation: |cpu, word, _address| {
-            let f = parse_format_i(word);
-            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] ^ f.imm);
+            cpu.check_xlen(64)?;
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.x[f.rs2].wrapping_add((cpu.x[f.rs1] as u32 as i64) << 1);
             Ok(())
         },
-        disassemble: dump_format_i,
+        disassemble: dump_format_r,
     },
-];
-
-/// The number of results [`DecodeCache`](struct.DecodeCache.html) holds.
-/// You need to carefully choose the number. Too small number causes
-/// bad cache hit ratio. Too large number causes memory consumption
-/// and host hardware CPU cache memory miss.
-const DECODE_CACHE_ENTRY_NUM: usize = 0x1000;
-
-const INVALID_CACHE_ENTRY: usize = INSTRUCTION_NUM;
-const NULL_ENTRY: usize = DECODE_CACHE_ENTRY_NUM;
-
-/// `DecodeCache` provides a cache system for instruction decoding.
-/// It holds the recent [`DECODE_CACHE_ENTRY_NUM`](constant.DECODE_CACHE_ENTRY_NUM.html)
-/// instruction decode results. If it has a cache (called "hit") for passed
-/// word data, it returns decoding result very quickly. Decoding is one of the
-/// slowest parts in CPU. This cache system improves the CPU processing speed
-/// by skipping decoding. Especially it should work well for loop. It is said
-/// that some loops in a program consume the majority of time then this cache
-/// system is expected to reduce the decoding time very well.
-///
-/// This cache system is based on LRU algorithm, and consists of a hash map and
-/// a linked list. Linked list is for LRU, front means recently used and back
-/// means least recently used. A content in hash map points to an entry in the
-/// linked list. This is the key to achieve computing in O(1).
-///
-// @TODO: Write performance benchmark test to confirm this cache actually
-//        improves the speed.
-struct DecodeCache {
-    /// Holds mappings from word instruction data to an index of `entries`
-    /// pointing to the entry having the decoding result. Containing the word
-    /// means cache hit.
-    hash_map: FnvHashMap<u32, usize>,
-
-    /// Holds the entries [`DecodeCacheEntry`](struct.DecodeCacheEntry.html)
-    /// forming linked list.
-    entries: Vec<DecodeCacheEntry>,
-
-    /// An index of `entries` pointing to the head entry in the linked list
-    front_index: usize,
-
-    /// An index of `entries` pointing to the tail entry in the linked list
-    back_index: usize,
-
-    /// Cache hit count for debugging purpose
-    hit_count: u64,
-
-    /// Cache miss count for debugging purpose
-    miss_count: u64,
-}
-
-impl DecodeCache {
-    /// Creates a new `DecodeCache`.
-    fn new() -> Self {
-        // Initialize linked list
-        let mut entries = Vec::new();
-        for i in 0..DECODE_CACHE_ENTRY_NUM {
-            let next_index = match i == DECODE_CACHE_ENTRY_NUM - 1 {
-                true => NULL_ENTRY,
-                false => i + 1,
-            };
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x20004033,
+        name: "SH2ADD",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs2].wrapping_add(cpu.x[f.rs1] << 2));
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x2000403b,
+        name: "SH2ADD.UW",
+        operation: |cpu, word, _address| {
+            cpu.check_xlen(64)?;
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.x[f.rs2].wrapping_add((cpu.x[f.rs1] as u32 as i64) << 2);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x20006033,
+        name: "SH3ADD",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs2].wrapping_add(cpu.x[f.rs1] << 3));
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x2000603b,
+        name: "SH3ADD.UW",
+        operation: |cpu, word, _address| {
+            cpu.check_xlen(64)?;
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.x[f.rs2].wrapping_add((cpu.x[f.rs1] as u32 as i64) << 3);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x00001033,
+        name: "SLL",
+        operation: |cpu, word, address| sll(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x00001013,
+        name: "SLLI",
+        operation: |cpu, word, address| slli(cpu, &Operands::format_shift(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x0800101b,
+        name: "SLLI.UW",
+        operation: |cpu, word, _address| {
+            cpu.check_xlen(64)?;
+            let f = parse_format_r(word);
+            let shamt = (word >> 20) & 0x3f;
+            cpu.x[f.rd] = ((cpu.x[f.rs1] as u32 as u64) << shamt) as i64;
+            Ok(())
+        },
+        disassemble: dump_format_r_shamt,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x0000101b,
+        name: "SLLIW",
+        operation: |cpu, word, address| slliw(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x0000103b,
+        name: "SLLW",
+        operation: |cpu, word, address| sllw(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x00002033,
+        name: "SLT",
+        operation: |cpu, word, address| slt(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0x0000707f,
+        data: 0x00002013,
+        name: "SLTI",
+        operation: |cpu, word, address| slti(cpu, &Operands::format_i(word), address),
+        disassemble: dump_format_i,
+    },
+    Instruction {
+        mask: 0x0000707f,
+        data: 0x00003013,
+        name: "SLTIU",
+        operation: |cpu, word, address| sltiu(cpu, &Operands::format_i(word), address),
+        disassemble: dump_format_i,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x00003033,
+        name: "SLTU",
+        operation: |cpu, word, address| sltu(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x40005033,
+        name: "SRA",
+        operation: |cpu, word, address| sra(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x40005013,
+        name: "SRAI",
+        operation: |cpu, word, address| srai(cpu, &Operands::format_shift(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x4000501b,
+        name: "SRAIW",
+        operation: |cpu, word, address| sraiw(cpu, &Operands::format_shift(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x4000503b,
+        name: "SRAW",
+        operation: |cpu, word, address| sraw(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xffffffff,
+        data: 0x10200073,
+        name: "SRET",
+        operation: |cpu, _word, _address| {
+            // @TODO: Throw error if higher privilege return instruction is executed
+            cpu.pc = match cpu.read_csr(CSR_SEPC_ADDRESS) {
+                Ok(data) => data,
+                Err(e) => return Err(e),
+            };
+            let status = cpu.read_csr_raw(CSR_SSTATUS_ADDRESS);
+            let spie = (status >> 5) & 1;
+            // SRET never returns to Machine mode, so it always clears MPRV
+            let privilege_mode = match (status >> 8) & 1 {
+                0 => PrivilegeMode::User,
+                _ => PrivilegeMode::Supervisor,
+            };
+            // Override SIE[1] with SPIE[5], set SPIE[5] to 1, set SPP[8] to 0,
+            // and clear MPRV[17]
+            let new_status = (status & !0x20122) | (spie << 1) | (1 << 5);
+            cpu.write_csr_raw(CSR_SSTATUS_ADDRESS, new_status);
+            cpu.privilege_mode = privilege_mode;
+            cpu.mmu.update_privilege_mode(cpu.privilege_mode.clone());
+            Ok(())
+        },
+        disassemble: dump_empty,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x00005033,
+        name: "SRL",
+        operation: |cpu, word, address| srl(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x00005013,
+        name: "SRLI",
+        operation: |cpu, word, address| srli(cpu, &Operands::format_shift(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x0000501b,
+        name: "SRLIW",
+        operation: |cpu, word, address| srliw(cpu, &Operands::format_shift(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x0000503b,
+        name: "SRLW",
+        operation: |cpu, word, address| srlw(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x40000033,
+        name: "SUB",
+        operation: |cpu, word, address| sub(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x4000003b,
+        name: "SUBW",
+        operation: |cpu, word, address| subw(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0x0000707f,
+        data: 0x00002023,
+        name: "SW",
+        operation: |cpu, word, address| sw(cpu, &Operands::format_s(word), address),
+        disassemble: dump_format_s,
+    },
+    Instruction {
+        mask: 0xffffffff,
+        data: 0x00200073,
+        name: "URET",
+        operation: |_cpu, word, _address| {
+            // User mode traps (N extension) aren't supported
+            Err(Trap {
+                trap_type: TrapType::IllegalInstruction,
+                value: word as u64,
+            })
+        },
+        disassemble: dump_empty,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x24002057,
+        name: "VAADD.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::averaging_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x24006057,
+        name: "VAADD.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::averaging_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x20002057,
+        name: "VAADDU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::averaging_add_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x20006057,
+        name: "VAADDU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::averaging_add_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x40003057,
+        name: "VADC.VIM",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry(word, VectorOperand::Immediate, vector::add_with_carry)
+        },
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x40000057,
+        name: "VADC.VVM",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry(word, VectorOperand::Vector, vector::add_with_carry)
+        },
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x40004057,
+        name: "VADC.VXM",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry(word, VectorOperand::Integer, vector::add_with_carry)
+        },
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x00003057,
+        name: "VADD.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Immediate,
+                vector::SINGLE_WIDTH,
+                vector::add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x00000057,
+        name: "VADD.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x00004057,
+        name: "VADD.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x24003057,
+        name: "VAND.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Immediate,
+                vector::SINGLE_WIDTH,
+                vector::and,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x24000057,
+        name: "VAND.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::and,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x24004057,
+        name: "VAND.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::and,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x2c002057,
+        name: "VASUB.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::averaging_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x2c006057,
+        name: "VASUB.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::averaging_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x28002057,
+        name: "VASUBU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::averaging_sub_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x28006057,
+        name: "VASUBU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::averaging_sub_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x5e002057,
+        name: "VCOMPRESS.VM",
+        operation: |cpu, word, _address| cpu.vector_compress(word),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x40082057,
+        name: "VCPOP.M",
+        operation: |cpu, word, _address| cpu.vector_mask_to_scalar(word, false),
+        disassemble: dump_format_v_to_scalar,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x84002057,
+        name: "VDIV.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::divide,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x84006057,
+        name: "VDIV.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::divide,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x80002057,
+        name: "VDIVU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::divide_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x80006057,
+        name: "VDIVU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::divide_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x00005057,
+        name: "VFADD.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x00001057,
+        name: "VFADD.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x4c081057,
+        name: "VFCLASS.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_unary(word, vector::SINGLE_WIDTH, vector::float_classify)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48019057,
+        name: "VFCVT.F.X.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(word, vector::SINGLE_WIDTH, 0, vector::float_from_signed)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48011057,
+        name: "VFCVT.F.XU.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(word, vector::SINGLE_WIDTH, 0, vector::float_from_unsigned)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48039057,
+        name: "VFCVT.RTZ.X.F.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(word, vector::SINGLE_WIDTH, 0, vector::signed_from_float_rtz)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48031057,
+        name: "VFCVT.RTZ.XU.F.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::SINGLE_WIDTH,
+                0,
+                vector::unsigned_from_float_rtz,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48009057,
+        name: "VFCVT.X.F.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(word, vector::SINGLE_WIDTH, 0, vector::signed_from_float)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48001057,
+        name: "VFCVT.XU.F.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(word, vector::SINGLE_WIDTH, 0, vector::unsigned_from_float)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x80005057,
+        name: "VFDIV.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_divide,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x80001057,
+        name: "VFDIV.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_divide,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x4008a057,
+        name: "VFIRST.M",
+        operation: |cpu, word, _address| cpu.vector_mask_to_scalar(word, true),
+        disassemble: dump_format_v_to_scalar,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb0005057,
+        name: "VFMACC.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_multiply_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb0001057,
+        name: "VFMACC.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_multiply_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa0005057,
+        name: "VFMADD.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_multiply_add,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa0001057,
+        name: "VFMADD.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_multiply_add,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x18005057,
+        name: "VFMAX.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_max,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x18001057,
+        name: "VFMAX.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_max,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x5c005057,
+        name: "VFMERGE.VFM",
+        operation: |cpu, word, _address| cpu.vector_merge(word, VectorOperand::Float),
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x10005057,
+        name: "VFMIN.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_min,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x10001057,
+        name: "VFMIN.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_min,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb8005057,
+        name: "VFMSAC.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_multiply_subtract_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb8001057,
+        name: "VFMSAC.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_multiply_subtract_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa8005057,
+        name: "VFMSUB.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_multiply_subtract,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa8001057,
+        name: "VFMSUB.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_multiply_subtract,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x90005057,
+        name: "VFMUL.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_multiply,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x90001057,
+        name: "VFMUL.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_multiply,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe0ff07f,
+        data: 0x42001057,
+        name: "VFMV.F.S",
+        operation: |cpu, word, _address| cpu.vector_move_to_scalar(word, true),
+        disassemble: dump_format_v_to_scalar,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x42005057,
+        name: "VFMV.S.F",
+        operation: |cpu, word, _address| cpu.vector_move_from_scalar(word, VectorOperand::Float),
+        disassemble: dump_format_v_move,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x5e005057,
+        name: "VFMV.V.F",
+        operation: |cpu, word, _address| cpu.vector_merge(word, VectorOperand::Float),
+        disassemble: dump_format_v_move,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x480a1057,
+        name: "VFNCVT.F.F.W",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::NARROWING,
+                0,
+                vector::narrowing_float_from_float,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48099057,
+        name: "VFNCVT.F.X.W",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::NARROWING,
+                0,
+                vector::narrowing_float_from_signed,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48091057,
+        name: "VFNCVT.F.XU.W",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::NARROWING,
+                0,
+                vector::narrowing_float_from_unsigned,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x480a9057,
+        name: "VFNCVT.ROD.F.F.W",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::NARROWING,
+                0,
+                vector::narrowing_float_from_float_rod,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x480b9057,
+        name: "VFNCVT.RTZ.X.F.W",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::NARROWING,
+                1,
+                vector::narrowing_signed_from_float_rtz,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x480b1057,
+        name: "VFNCVT.RTZ.XU.F.W",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::NARROWING,
+                1,
+                vector::narrowing_unsigned_from_float_rtz,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48089057,
+        name: "VFNCVT.X.F.W",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::NARROWING,
+                1,
+                vector::narrowing_signed_from_float,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48081057,
+        name: "VFNCVT.XU.F.W",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::NARROWING,
+                1,
+                vector::narrowing_unsigned_from_float,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb4005057,
+        name: "VFNMACC.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_negative_multiply_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb4001057,
+        name: "VFNMACC.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_negative_multiply_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa4005057,
+        name: "VFNMADD.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_negative_multiply_add,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa4001057,
+        name: "VFNMADD.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_negative_multiply_add,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xbc005057,
+        name: "VFNMSAC.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_negative_multiply_subtract_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xbc001057,
+        name: "VFNMSAC.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_negative_multiply_subtract_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xac005057,
+        name: "VFNMSUB.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_negative_multiply_subtract,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xac001057,
+        name: "VFNMSUB.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_negative_multiply_subtract,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x84005057,
+        name: "VFRDIV.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_reverse_divide,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x4c029057,
+        name: "VFREC7.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_unary(
+                word,
+                vector::SINGLE_WIDTH,
+                vector::float_reciprocal_estimate,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x1c001057,
+        name: "VFREDMAX.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, false, vector::float_max),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x14001057,
+        name: "VFREDMIN.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, false, vector::float_min),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x0c001057,
+        name: "VFREDOSUM.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, false, vector::float_add),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x04001057,
+        name: "VFREDUSUM.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, false, vector::float_add),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x4c021057,
+        name: "VFRSQRT7.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_unary(
+                word,
+                vector::SINGLE_WIDTH,
+                vector::float_reciprocal_sqrt_estimate,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x9c005057,
+        name: "VFRSUB.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_reverse_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x20005057,
+        name: "VFSGNJ.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::sign_inject,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x20001057,
+        name: "VFSGNJ.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::sign_inject,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x24005057,
+        name: "VFSGNJN.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::sign_inject_negate,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x24001057,
+        name: "VFSGNJN.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::sign_inject_negate,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x28005057,
+        name: "VFSGNJX.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::sign_inject_xor,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x28001057,
+        name: "VFSGNJX.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::sign_inject_xor,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x3c005057,
+        name: "VFSLIDE1DOWN.VF",
+        operation: |cpu, word, _address| cpu.vector_slide1(word, VectorOperand::Float, false),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x38005057,
+        name: "VFSLIDE1UP.VF",
+        operation: |cpu, word, _address| cpu.vector_slide1(word, VectorOperand::Float, true),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x4c001057,
+        name: "VFSQRT.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_unary(word, vector::SINGLE_WIDTH, vector::float_sqrt)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x08005057,
+        name: "VFSUB.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::SINGLE_WIDTH,
+                vector::float_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x08001057,
+        name: "VFSUB.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::float_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xc0005057,
+        name: "VFWADD.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::WIDENING,
+                vector::float_widening_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xc0001057,
+        name: "VFWADD.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::float_widening_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xd0005057,
+        name: "VFWADD.WF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::WIDENING_WIDE,
+                vector::float_wide_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xd0001057,
+        name: "VFWADD.WV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING_WIDE,
+                vector::float_wide_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48061057,
+        name: "VFWCVT.F.F.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(word, vector::WIDENING, 0, vector::widening_float_from_float)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48059057,
+        name: "VFWCVT.F.X.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::WIDENING,
+                1,
+                vector::widening_float_from_signed,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48051057,
+        name: "VFWCVT.F.XU.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::WIDENING,
+                1,
+                vector::widening_float_from_unsigned,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48079057,
+        name: "VFWCVT.RTZ.X.F.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::WIDENING,
+                0,
+                vector::widening_signed_from_float_rtz,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48071057,
+        name: "VFWCVT.RTZ.XU.F.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::WIDENING,
+                0,
+                vector::widening_unsigned_from_float_rtz,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48049057,
+        name: "VFWCVT.X.F.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::WIDENING,
+                0,
+                vector::widening_signed_from_float,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48041057,
+        name: "VFWCVT.XU.F.V",
+        operation: |cpu, word, _address| {
+            cpu.vector_convert(
+                word,
+                vector::WIDENING,
+                0,
+                vector::widening_unsigned_from_float,
+            )
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xf0005057,
+        name: "VFWMACC.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Float,
+                vector::WIDENING,
+                vector::float_widening_multiply_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xf0001057,
+        name: "VFWMACC.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::float_widening_multiply_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xf8005057,
+        name: "VFWMSAC.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Float,
+                vector::WIDENING,
+                vector::float_widening_multiply_subtract_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xf8001057,
+        name: "VFWMSAC.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::float_widening_multiply_subtract_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xe0005057,
+        name: "VFWMUL.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::WIDENING,
+                vector::float_widening_multiply,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xe0001057,
+        name: "VFWMUL.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::float_widening_multiply,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xf4005057,
+        name: "VFWNMACC.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Float,
+                vector::WIDENING,
+                vector::float_widening_negative_multiply_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xf4001057,
+        name: "VFWNMACC.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::float_widening_negative_multiply_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xfc005057,
+        name: "VFWNMSAC.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Float,
+                vector::WIDENING,
+                vector::float_widening_negative_multiply_subtract_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xfc001057,
+        name: "VFWNMSAC.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::float_widening_negative_multiply_subtract_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xcc001057,
+        name: "VFWREDOSUM.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, true, vector::float_wide_add),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xc4001057,
+        name: "VFWREDUSUM.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, true, vector::float_wide_add),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xc8005057,
+        name: "VFWSUB.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::WIDENING,
+                vector::float_widening_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xc8001057,
+        name: "VFWSUB.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::float_widening_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xd8005057,
+        name: "VFWSUB.WF",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Float,
+                vector::WIDENING_WIDE,
+                vector::float_wide_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xd8001057,
+        name: "VFWSUB.WV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING_WIDE,
+                vector::float_wide_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfdfff07f,
+        data: 0x5008a057,
+        name: "VID.V",
+        operation: |cpu, word, _address| cpu.vector_iota(word, true),
+        disassemble: dump_format_v_index,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x50082057,
+        name: "VIOTA.M",
+        operation: |cpu, word, _address| cpu.vector_iota(word, false),
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x02805007,
+        name: "VL1RE16.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x02806007,
+        name: "VL1RE32.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x02807007,
+        name: "VL1RE64.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x02800007,
+        name: "VL1RE8.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x22805007,
+        name: "VL2RE16.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x22806007,
+        name: "VL2RE32.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x22807007,
+        name: "VL2RE64.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x22800007,
+        name: "VL2RE8.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x62805007,
+        name: "VL4RE16.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x62806007,
+        name: "VL4RE32.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x62807007,
+        name: "VL4RE64.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x62800007,
+        name: "VL4RE8.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0xe2805007,
+        name: "VL8RE16.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0xe2806007,
+        name: "VL8RE32.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0xe2807007,
+        name: "VL8RE64.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0xe2800007,
+        name: "VL8RE8.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1df0707f,
+        data: 0x00005007,
+        name: "VLE16.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1df0707f,
+        data: 0x01005007,
+        name: "VLE16FF.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, true),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1df0707f,
+        data: 0x00006007,
+        name: "VLE32.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1df0707f,
+        data: 0x01006007,
+        name: "VLE32FF.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, true),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1df0707f,
+        data: 0x00007007,
+        name: "VLE64.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1df0707f,
+        data: 0x01007007,
+        name: "VLE64FF.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, true),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1df0707f,
+        data: 0x00000007,
+        name: "VLE8.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1df0707f,
+        data: 0x01000007,
+        name: "VLE8FF.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, true),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x02b00007,
+        name: "VLM.V",
+        operation: |cpu, word, _address| cpu.vector_mask_memory(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x0c005007,
+        name: "VLOXEI16.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x0c006007,
+        name: "VLOXEI32.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x0c007007,
+        name: "VLOXEI64.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x0c000007,
+        name: "VLOXEI8.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x08005007,
+        name: "VLSE16.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x08006007,
+        name: "VLSE32.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x08007007,
+        name: "VLSE64.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x08000007,
+        name: "VLSE8.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x04005007,
+        name: "VLUXEI16.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x04006007,
+        name: "VLUXEI32.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x04007007,
+        name: "VLUXEI64.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x04000007,
+        name: "VLUXEI8.V",
+        operation: |cpu, word, _address| cpu.vector_load(word, false),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb4002057,
+        name: "VMACC.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::multiply_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb4006057,
+        name: "VMACC.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::multiply_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x46003057,
+        name: "VMADC.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry_out(word, VectorOperand::Immediate, vector::carry_out)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x44003057,
+        name: "VMADC.VIM",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry_out(word, VectorOperand::Immediate, vector::carry_out)
+        },
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x46000057,
+        name: "VMADC.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry_out(word, VectorOperand::Vector, vector::carry_out)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x44000057,
+        name: "VMADC.VVM",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry_out(word, VectorOperand::Vector, vector::carry_out)
+        },
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x46004057,
+        name: "VMADC.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry_out(word, VectorOperand::Integer, vector::carry_out)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x44004057,
+        name: "VMADC.VXM",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry_out(word, VectorOperand::Integer, vector::carry_out)
+        },
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa4002057,
+        name: "VMADD.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::multiply_add,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa4006057,
+        name: "VMADD.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::multiply_add,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x66002057,
+        name: "VMAND.MM",
+        operation: |cpu, word, _address| cpu.vector_mask_logical(word, |a, b| a & b),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x62002057,
+        name: "VMANDN.MM",
+        operation: |cpu, word, _address| cpu.vector_mask_logical(word, |a, b| a & !b),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x1c000057,
+        name: "VMAX.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::max,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x1c004057,
+        name: "VMAX.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::max,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x18000057,
+        name: "VMAXU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::max_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x18004057,
+        name: "VMAXU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::max_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x5c003057,
+        name: "VMERGE.VIM",
+        operation: |cpu, word, _address| cpu.vector_merge(word, VectorOperand::Immediate),
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x5c000057,
+        name: "VMERGE.VVM",
+        operation: |cpu, word, _address| cpu.vector_merge(word, VectorOperand::Vector),
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x5c004057,
+        name: "VMERGE.VXM",
+        operation: |cpu, word, _address| cpu.vector_merge(word, VectorOperand::Integer),
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x60005057,
+        name: "VMFEQ.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Float, vector::float_equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x60001057,
+        name: "VMFEQ.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Vector, vector::float_equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x7c005057,
+        name: "VMFGE.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Float, vector::float_greater_equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x74005057,
+        name: "VMFGT.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Float, vector::float_greater_than)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x64005057,
+        name: "VMFLE.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Float, vector::float_less_equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x64001057,
+        name: "VMFLE.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Vector, vector::float_less_equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x6c005057,
+        name: "VMFLT.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Float, vector::float_less_than)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x6c001057,
+        name: "VMFLT.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Vector, vector::float_less_than)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x70005057,
+        name: "VMFNE.VF",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Float, vector::float_not_equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x70001057,
+        name: "VMFNE.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Vector, vector::float_not_equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x14000057,
+        name: "VMIN.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::min,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x14004057,
+        name: "VMIN.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::min,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x10000057,
+        name: "VMINU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::min_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x10004057,
+        name: "VMINU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::min_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x76002057,
+        name: "VMNAND.MM",
+        operation: |cpu, word, _address| cpu.vector_mask_logical(word, |a, b| !(a & b)),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x7a002057,
+        name: "VMNOR.MM",
+        operation: |cpu, word, _address| cpu.vector_mask_logical(word, |a, b| !(a | b)),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x6a002057,
+        name: "VMOR.MM",
+        operation: |cpu, word, _address| cpu.vector_mask_logical(word, |a, b| a | b),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x72002057,
+        name: "VMORN.MM",
+        operation: |cpu, word, _address| cpu.vector_mask_logical(word, |a, b| a | !b),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x4e000057,
+        name: "VMSBC.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry_out(word, VectorOperand::Vector, vector::borrow_out)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x4c000057,
+        name: "VMSBC.VVM",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry_out(word, VectorOperand::Vector, vector::borrow_out)
+        },
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x4e004057,
+        name: "VMSBC.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry_out(word, VectorOperand::Integer, vector::borrow_out)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x4c004057,
+        name: "VMSBC.VXM",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry_out(word, VectorOperand::Integer, vector::borrow_out)
+        },
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x5000a057,
+        name: "VMSBF.M",
+        operation: |cpu, word, _address| cpu.vector_set_first(word, true, false),
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x60003057,
+        name: "VMSEQ.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Immediate, vector::equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x60000057,
+        name: "VMSEQ.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Vector, vector::equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x60004057,
+        name: "VMSEQ.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Integer, vector::equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x7c003057,
+        name: "VMSGT.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Immediate, vector::greater_than)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x7c004057,
+        name: "VMSGT.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Integer, vector::greater_than)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x78003057,
+        name: "VMSGTU.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(
+                word,
+                VectorOperand::Immediate,
+                vector::greater_than_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x78004057,
+        name: "VMSGTU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Integer, vector::greater_than_unsigned)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x5001a057,
+        name: "VMSIF.M",
+        operation: |cpu, word, _address| cpu.vector_set_first(word, true, true),
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x74003057,
+        name: "VMSLE.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Immediate, vector::less_equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x74000057,
+        name: "VMSLE.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Vector, vector::less_equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x74004057,
+        name: "VMSLE.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Integer, vector::less_equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x70003057,
+        name: "VMSLEU.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Immediate, vector::less_equal_unsigned)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x70000057,
+        name: "VMSLEU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Vector, vector::less_equal_unsigned)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x70004057,
+        name: "VMSLEU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Integer, vector::less_equal_unsigned)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x6c000057,
+        name: "VMSLT.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Vector, vector::less_than)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x6c004057,
+        name: "VMSLT.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Integer, vector::less_than)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x68000057,
+        name: "VMSLTU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Vector, vector::less_than_unsigned)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x68004057,
+        name: "VMSLTU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Integer, vector::less_than_unsigned)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x64003057,
+        name: "VMSNE.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Immediate, vector::not_equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x64000057,
+        name: "VMSNE.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Vector, vector::not_equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x64004057,
+        name: "VMSNE.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_compare(word, VectorOperand::Integer, vector::not_equal)
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x50012057,
+        name: "VMSOF.M",
+        operation: |cpu, word, _address| cpu.vector_set_first(word, false, true),
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x94002057,
+        name: "VMUL.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::multiply,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x94006057,
+        name: "VMUL.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::multiply,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x9c002057,
+        name: "VMULH.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::multiply_high,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x9c006057,
+        name: "VMULH.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::multiply_high,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x98002057,
+        name: "VMULHSU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::multiply_high_signed_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x98006057,
+        name: "VMULHSU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::multiply_high_signed_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x90002057,
+        name: "VMULHU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::multiply_high_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x90006057,
+        name: "VMULHU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::multiply_high_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x42006057,
+        name: "VMV.S.X",
+        operation: |cpu, word, _address| cpu.vector_move_from_scalar(word, VectorOperand::Integer),
+        disassemble: dump_format_v_move,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x5e003057,
+        name: "VMV.V.I",
+        operation: |cpu, word, _address| cpu.vector_merge(word, VectorOperand::Immediate),
+        disassemble: dump_format_v_move,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x5e000057,
+        name: "VMV.V.V",
+        operation: |cpu, word, _address| cpu.vector_merge(word, VectorOperand::Vector),
+        disassemble: dump_format_v_move,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x5e004057,
+        name: "VMV.V.X",
+        operation: |cpu, word, _address| cpu.vector_merge(word, VectorOperand::Integer),
+        disassemble: dump_format_v_move,
+    },
+    Instruction {
+        mask: 0xfe0ff07f,
+        data: 0x42002057,
+        name: "VMV.X.S",
+        operation: |cpu, word, _address| cpu.vector_move_to_scalar(word, false),
+        disassemble: dump_format_v_to_scalar,
+    },
+    Instruction {
+        mask: 0xfe0ff07f,
+        data: 0x9e003057,
+        name: "VMV1R.V",
+        operation: |cpu, word, _address| cpu.vector_move_whole_register(word),
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfe0ff07f,
+        data: 0x9e00b057,
+        name: "VMV2R.V",
+        operation: |cpu, word, _address| cpu.vector_move_whole_register(word),
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfe0ff07f,
+        data: 0x9e01b057,
+        name: "VMV4R.V",
+        operation: |cpu, word, _address| cpu.vector_move_whole_register(word),
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfe0ff07f,
+        data: 0x9e03b057,
+        name: "VMV8R.V",
+        operation: |cpu, word, _address| cpu.vector_move_whole_register(word),
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x7e002057,
+        name: "VMXNOR.MM",
+        operation: |cpu, word, _address| cpu.vector_mask_logical(word, |a, b| !(a ^ b)),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x6e002057,
+        name: "VMXOR.MM",
+        operation: |cpu, word, _address| cpu.vector_mask_logical(word, |a, b| a ^ b),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xbc003057,
+        name: "VNCLIP.WI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::UnsignedImmediate,
+                vector::NARROWING,
+                vector::narrowing_clip,
+            )
+        },
+        disassemble: dump_format_v_uimm,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xbc000057,
+        name: "VNCLIP.WV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::NARROWING,
+                vector::narrowing_clip,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xbc004057,
+        name: "VNCLIP.WX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::NARROWING,
+                vector::narrowing_clip,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb8003057,
+        name: "VNCLIPU.WI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::UnsignedImmediate,
+                vector::NARROWING,
+                vector::narrowing_clip_unsigned,
+            )
+        },
+        disassemble: dump_format_v_uimm,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb8000057,
+        name: "VNCLIPU.WV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::NARROWING,
+                vector::narrowing_clip_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb8004057,
+        name: "VNCLIPU.WX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::NARROWING,
+                vector::narrowing_clip_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xbc002057,
+        name: "VNMSAC.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::negative_multiply_subtract_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xbc006057,
+        name: "VNMSAC.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::negative_multiply_subtract_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xac002057,
+        name: "VNMSUB.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::negative_multiply_subtract,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xac006057,
+        name: "VNMSUB.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::negative_multiply_subtract,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb4003057,
+        name: "VNSRA.WI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::UnsignedImmediate,
+                vector::NARROWING,
+                vector::narrowing_shift_right_arithmetic,
+            )
+        },
+        disassemble: dump_format_v_uimm,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb4000057,
+        name: "VNSRA.WV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::NARROWING,
+                vector::narrowing_shift_right_arithmetic,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb4004057,
+        name: "VNSRA.WX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::NARROWING,
+                vector::narrowing_shift_right_arithmetic,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb0003057,
+        name: "VNSRL.WI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::UnsignedImmediate,
+                vector::NARROWING,
+                vector::narrowing_shift_right_logical,
+            )
+        },
+        disassemble: dump_format_v_uimm,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb0000057,
+        name: "VNSRL.WV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::NARROWING,
+                vector::narrowing_shift_right_logical,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xb0004057,
+        name: "VNSRL.WX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::NARROWING,
+                vector::narrowing_shift_right_logical,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x28003057,
+        name: "VOR.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Immediate,
+                vector::SINGLE_WIDTH,
+                vector::or,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x28000057,
+        name: "VOR.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::or,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x28004057,
+        name: "VOR.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::or,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x04002057,
+        name: "VREDAND.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, false, vector::and),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x1c002057,
+        name: "VREDMAX.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, false, vector::max),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x18002057,
+        name: "VREDMAXU.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, false, vector::max_unsigned),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x14002057,
+        name: "VREDMIN.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, false, vector::min),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x10002057,
+        name: "VREDMINU.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, false, vector::min_unsigned),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x08002057,
+        name: "VREDOR.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, false, vector::or),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x00002057,
+        name: "VREDSUM.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, false, vector::add),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x0c002057,
+        name: "VREDXOR.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, false, vector::xor),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x8c002057,
+        name: "VREM.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::remainder,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x8c006057,
+        name: "VREM.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::remainder,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x88002057,
+        name: "VREMU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::remainder_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x88006057,
+        name: "VREMU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::remainder_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x30003057,
+        name: "VRGATHER.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_gather(word, VectorOperand::UnsignedImmediate, false)
+        },
+        disassemble: dump_format_v_uimm,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x30000057,
+        name: "VRGATHER.VV",
+        operation: |cpu, word, _address| cpu.vector_gather(word, VectorOperand::Vector, false),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x30004057,
+        name: "VRGATHER.VX",
+        operation: |cpu, word, _address| cpu.vector_gather(word, VectorOperand::Integer, false),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x38000057,
+        name: "VRGATHEREI16.VV",
+        operation: |cpu, word, _address| cpu.vector_gather(word, VectorOperand::Vector, true),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x0c003057,
+        name: "VRSUB.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Immediate,
+                vector::SINGLE_WIDTH,
+                vector::reverse_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x0c004057,
+        name: "VRSUB.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::reverse_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x02800027,
+        name: "VS1R.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, true),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x22800027,
+        name: "VS2R.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, true),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x62800027,
+        name: "VS4R.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, true),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0xe2800027,
+        name: "VS8R.V",
+        operation: |cpu, word, _address| cpu.vector_whole_register_memory(word, true),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x84003057,
+        name: "VSADD.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Immediate,
+                vector::SINGLE_WIDTH,
+                vector::saturating_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x84000057,
+        name: "VSADD.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::saturating_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x84004057,
+        name: "VSADD.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::saturating_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x80003057,
+        name: "VSADDU.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Immediate,
+                vector::SINGLE_WIDTH,
+                vector::saturating_add_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x80000057,
+        name: "VSADDU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::saturating_add_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x80004057,
+        name: "VSADDU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::saturating_add_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x48000057,
+        name: "VSBC.VVM",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry(word, VectorOperand::Vector, vector::sub_with_borrow)
+        },
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x48004057,
+        name: "VSBC.VXM",
+        operation: |cpu, word, _address| {
+            cpu.vector_carry(word, VectorOperand::Integer, vector::sub_with_borrow)
+        },
+        disassemble: dump_format_v_merge,
+    },
+    Instruction {
+        mask: 0x1df0707f,
+        data: 0x00005027,
+        name: "VSE16.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1df0707f,
+        data: 0x00006027,
+        name: "VSE32.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1df0707f,
+        data: 0x00007027,
+        name: "VSE64.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1df0707f,
+        data: 0x00000027,
+        name: "VSE8.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xc000707f,
+        data: 0xc0007057,
+        name: "VSETIVLI",
+        operation: |cpu, word, _address| {
+            let vtype = ((word >> 20) & 0x3ff) as u64;
+            let avl = ((word >> 15) & 0x1f) as u64;
+            cpu.set_vector_configuration(word, vtype, Some(avl))
+        },
+        disassemble: dump_format_v_config,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x80007057,
+        name: "VSETVL",
+        operation: |cpu, word, _address| {
+            let vtype = cpu.unsigned_data(cpu.x[((word >> 20) & 0x1f) as usize]);
+            let avl = cpu.get_application_vector_length(word);
+            cpu.set_vector_configuration(word, vtype, avl)
+        },
+        disassemble: dump_format_v_config,
+    },
+    Instruction {
+        mask: 0x8000707f,
+        data: 0x00007057,
+        name: "VSETVLI",
+        operation: |cpu, word, _address| {
+            let vtype = ((word >> 20) & 0x7ff) as u64;
+            let avl = cpu.get_application_vector_length(word);
+            cpu.set_vector_configuration(word, vtype, avl)
+        },
+        disassemble: dump_format_v_config,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x4803a057,
+        name: "VSEXT.VF2",
+        operation: |cpu, word, _address| {
+            cpu.vector_unary(word, vector::EXTENSION_VF2, vector::sign_extend_vf2)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x4802a057,
+        name: "VSEXT.VF4",
+        operation: |cpu, word, _address| {
+            cpu.vector_unary(word, vector::EXTENSION_VF4, vector::sign_extend_vf4)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x4801a057,
+        name: "VSEXT.VF8",
+        operation: |cpu, word, _address| {
+            cpu.vector_unary(word, vector::EXTENSION_VF8, vector::sign_extend_vf8)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x3c006057,
+        name: "VSLIDE1DOWN.VX",
+        operation: |cpu, word, _address| cpu.vector_slide1(word, VectorOperand::Integer, false),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x38006057,
+        name: "VSLIDE1UP.VX",
+        operation: |cpu, word, _address| cpu.vector_slide1(word, VectorOperand::Integer, true),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x3c003057,
+        name: "VSLIDEDOWN.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_slide(word, VectorOperand::UnsignedImmediate, false)
+        },
+        disassemble: dump_format_v_uimm,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x3c004057,
+        name: "VSLIDEDOWN.VX",
+        operation: |cpu, word, _address| cpu.vector_slide(word, VectorOperand::Integer, false),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x38003057,
+        name: "VSLIDEUP.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_slide(word, VectorOperand::UnsignedImmediate, true)
+        },
+        disassemble: dump_format_v_uimm,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x38004057,
+        name: "VSLIDEUP.VX",
+        operation: |cpu, word, _address| cpu.vector_slide(word, VectorOperand::Integer, true),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x94003057,
+        name: "VSLL.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::UnsignedImmediate,
+                vector::SINGLE_WIDTH,
+                vector::shift_left,
+            )
+        },
+        disassemble: dump_format_v_uimm,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x94000057,
+        name: "VSLL.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::shift_left,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x94004057,
+        name: "VSLL.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::shift_left,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x02b00027,
+        name: "VSM.V",
+        operation: |cpu, word, _address| cpu.vector_mask_memory(word, true),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x9c000057,
+        name: "VSMUL.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::fractional_multiply,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x9c004057,
+        name: "VSMUL.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::fractional_multiply,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x0c005027,
+        name: "VSOXEI16.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x0c006027,
+        name: "VSOXEI32.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x0c007027,
+        name: "VSOXEI64.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x0c000027,
+        name: "VSOXEI8.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa4003057,
+        name: "VSRA.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::UnsignedImmediate,
+                vector::SINGLE_WIDTH,
+                vector::shift_right_arithmetic,
+            )
+        },
+        disassemble: dump_format_v_uimm,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa4000057,
+        name: "VSRA.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::shift_right_arithmetic,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa4004057,
+        name: "VSRA.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::shift_right_arithmetic,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa0003057,
+        name: "VSRL.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::UnsignedImmediate,
+                vector::SINGLE_WIDTH,
+                vector::shift_right_logical,
+            )
+        },
+        disassemble: dump_format_v_uimm,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa0000057,
+        name: "VSRL.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::shift_right_logical,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa0004057,
+        name: "VSRL.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::shift_right_logical,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x08005027,
+        name: "VSSE16.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x08006027,
+        name: "VSSE32.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x08007027,
+        name: "VSSE64.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x08000027,
+        name: "VSSE8.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xac003057,
+        name: "VSSRA.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::UnsignedImmediate,
+                vector::SINGLE_WIDTH,
+                vector::scaling_shift_right_arithmetic,
+            )
+        },
+        disassemble: dump_format_v_uimm,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xac000057,
+        name: "VSSRA.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::scaling_shift_right_arithmetic,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xac004057,
+        name: "VSSRA.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::scaling_shift_right_arithmetic,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa8003057,
+        name: "VSSRL.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::UnsignedImmediate,
+                vector::SINGLE_WIDTH,
+                vector::scaling_shift_right_logical,
+            )
+        },
+        disassemble: dump_format_v_uimm,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa8000057,
+        name: "VSSRL.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::scaling_shift_right_logical,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xa8004057,
+        name: "VSSRL.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::scaling_shift_right_logical,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x8c000057,
+        name: "VSSUB.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::saturating_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x8c004057,
+        name: "VSSUB.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::saturating_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x88000057,
+        name: "VSSUBU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::saturating_sub_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x88004057,
+        name: "VSSUBU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::saturating_sub_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x08000057,
+        name: "VSUB.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x08004057,
+        name: "VSUB.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x04005027,
+        name: "VSUXEI16.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x04006027,
+        name: "VSUXEI32.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x04007027,
+        name: "VSUXEI64.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0x1c00707f,
+        data: 0x04000027,
+        name: "VSUXEI8.V",
+        operation: |cpu, word, _address| cpu.vector_store(word),
+        disassemble: dump_format_v_memory,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xc4002057,
+        name: "VWADD.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::widening_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xc4006057,
+        name: "VWADD.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING,
+                vector::widening_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xd4002057,
+        name: "VWADD.WV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING_WIDE,
+                vector::wide_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xd4006057,
+        name: "VWADD.WX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING_WIDE,
+                vector::wide_add,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xc0002057,
+        name: "VWADDU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::widening_add_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xc0006057,
+        name: "VWADDU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING,
+                vector::widening_add_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xd0002057,
+        name: "VWADDU.WV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING_WIDE,
+                vector::wide_add_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xd0006057,
+        name: "VWADDU.WX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING_WIDE,
+                vector::wide_add_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xf4002057,
+        name: "VWMACC.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::widening_multiply_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xf4006057,
+        name: "VWMACC.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING,
+                vector::widening_multiply_accumulate,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xfc002057,
+        name: "VWMACCSU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::widening_multiply_accumulate_signed_unsigned,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xfc006057,
+        name: "VWMACCSU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING,
+                vector::widening_multiply_accumulate_signed_unsigned,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xf0002057,
+        name: "VWMACCU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::widening_multiply_accumulate_unsigned,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xf0006057,
+        name: "VWMACCU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING,
+                vector::widening_multiply_accumulate_unsigned,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xf8006057,
+        name: "VWMACCUS.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_ternary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING,
+                vector::widening_multiply_accumulate_unsigned_signed,
+            )
+        },
+        disassemble: dump_format_v_multiply_add,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xec002057,
+        name: "VWMUL.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::widening_multiply,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xec006057,
+        name: "VWMUL.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING,
+                vector::widening_multiply,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xe8002057,
+        name: "VWMULSU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::widening_multiply_signed_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xe8006057,
+        name: "VWMULSU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING,
+                vector::widening_multiply_signed_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xe0002057,
+        name: "VWMULU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::widening_multiply_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xe0006057,
+        name: "VWMULU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING,
+                vector::widening_multiply_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xc4000057,
+        name: "VWREDSUM.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, true, vector::wide_add),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xc0000057,
+        name: "VWREDSUMU.VS",
+        operation: |cpu, word, _address| cpu.vector_reduce(word, true, vector::wide_add_unsigned),
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xcc002057,
+        name: "VWSUB.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::widening_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xcc006057,
+        name: "VWSUB.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING,
+                vector::widening_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xdc002057,
+        name: "VWSUB.WV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING_WIDE,
+                vector::wide_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xdc006057,
+        name: "VWSUB.WX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING_WIDE,
+                vector::wide_sub,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xc8002057,
+        name: "VWSUBU.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING,
+                vector::widening_sub_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xc8006057,
+        name: "VWSUBU.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING,
+                vector::widening_sub_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xd8002057,
+        name: "VWSUBU.WV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::WIDENING_WIDE,
+                vector::wide_sub_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0xd8006057,
+        name: "VWSUBU.WX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::WIDENING_WIDE,
+                vector::wide_sub_unsigned,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x2c003057,
+        name: "VXOR.VI",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Immediate,
+                vector::SINGLE_WIDTH,
+                vector::xor,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x2c000057,
+        name: "VXOR.VV",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Vector,
+                vector::SINGLE_WIDTH,
+                vector::xor,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc00707f,
+        data: 0x2c004057,
+        name: "VXOR.VX",
+        operation: |cpu, word, _address| {
+            cpu.vector_binary(
+                word,
+                VectorOperand::Integer,
+                vector::SINGLE_WIDTH,
+                vector::xor,
+            )
+        },
+        disassemble: dump_format_v,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48032057,
+        name: "VZEXT.VF2",
+        operation: |cpu, word, _address| {
+            cpu.vector_unary(word, vector::EXTENSION_VF2, vector::zero_extend)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48022057,
+        name: "VZEXT.VF4",
+        operation: |cpu, word, _address| {
+            cpu.vector_unary(word, vector::EXTENSION_VF4, vector::zero_extend)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xfc0ff07f,
+        data: 0x48012057,
+        name: "VZEXT.VF8",
+        operation: |cpu, word, _address| {
+            cpu.vector_unary(word, vector::EXTENSION_VF8, vector::zero_extend)
+        },
+        disassemble: dump_format_v_unary,
+    },
+    Instruction {
+        mask: 0xffffffff,
+        data: 0x10500073,
+        name: "WFI",
+        operation: |cpu, _word, _address| {
+            cpu.wfi = true;
+            Ok(())
+        },
+        disassemble: dump_empty,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x40004033,
+        name: "XNOR",
+        operation: |cpu, word, _address| {
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = !(cpu.x[f.rs1] ^ cpu.x[f.rs2]);
+            Ok(())
+        },
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0xfe00707f,
+        data: 0x00004033,
+        name: "XOR",
+        operation: |cpu, word, address| xor(cpu, &Operands::format_r(word), address),
+        disassemble: dump_format_r,
+    },
+    Instruction {
+        mask: 0x0000707f,
+        data: 0x00004013,
+        name: "XORI",
+        operation: |cpu, word, address| xori(cpu, &Operands::format_i(word), address),
+        disassemble: dump_format_i,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x08004033,
+        name: "ZEXT.H",
+        operation: |cpu, word, _address| {
+            cpu.check_xlen(32)?;
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.x[f.rs1] as u16 as i64;
+            Ok(())
+        },
+        disassemble: dump_format_r_unary,
+    },
+    Instruction {
+        mask: 0xfff0707f,
+        data: 0x0800403b,
+        name: "ZEXT.H",
+        operation: |cpu, word, _address| {
+            cpu.check_xlen(64)?;
+            let f = parse_format_r(word);
+            cpu.x[f.rd] = cpu.x[f.rs1] as u16 as i64;
+            Ok(())
+        },
+        disassemble: dump_format_r_unary,
+    },
+];
+
+/// The number of results [`DecodeCache`](struct.DecodeCache.html) holds.
+/// You need to carefully choose the number. Too small number causes
+/// bad cache hit ratio. Too large number causes memory consumption
+/// and host hardware CPU cache memory miss.
+const DECODE_CACHE_ENTRY_NUM: usize = 0x1000;
+
+const INVALID_CACHE_ENTRY: usize = INSTRUCTION_NUM;
+const NULL_ENTRY: usize = DECODE_CACHE_ENTRY_NUM;
+
+/// `DecodeCache` provides a cache system for instruction decoding.
+/// It holds the recent [`DECODE_CACHE_ENTRY_NUM`](constant.DECODE_CACHE_ENTRY_NUM.html)
+/// instruction decode results. If it has a cache (called "hit") for passed
+/// word data, it returns decoding result very quickly. Decoding is one of the
+/// slowest parts in CPU. This cache system improves the CPU processing speed
+/// by skipping decoding. Especially it should work well for loop. It is said
+/// that some loops in a program consume the majority of time then this cache
+/// system is expected to reduce the decoding time very well.
+///
+/// This cache system is based on LRU algorithm, and consists of a hash map and
+/// a linked list. Linked list is for LRU, front means recently used and back
+/// means least recently used. A content in hash map points to an entry in the
+/// linked list. This is the key to achieve computing in O(1).
+///
+// @TODO: Write performance benchmark test to confirm this cache actually
+//        improves the speed.
+struct DecodeCache {
+    /// Holds mappings from word instruction data to an index of `entries`
+    /// pointing to the entry having the decoding result. Containing the word
+    /// means cache hit.
+    hash_map: FnvHashMap<u32, usize>,
+
+    /// Holds the entries [`DecodeCacheEntry`](struct.DecodeCacheEntry.html)
+    /// forming linked list.
+    entries: Vec<DecodeCacheEntry>,
+
+    /// An index of `entries` pointing to the head entry in the linked list
+    front_index: usize,
+
+    /// An index of `entries` pointing to the tail entry in the linked list
+    back_index: usize,
+
+    /// Cache hit count for debugging purpose
+    hit_count: u64,
+
+    /// Cache miss count for debugging purpose
+    miss_count: u64,
+}
+
+impl DecodeCache {
+    /// Creates a new `DecodeCache`.
+    fn new() -> Self {
+        // Initialize linked list
+        let mut entries = Vec::new();
+        for i in 0..DECODE_CACHE_ENTRY_NUM {
+            let next_index = match i == DECODE_CACHE_ENTRY_NUM - 1 {
+                true => NULL_ENTRY,
+                false => i + 1,
+            };
             let prev_index = match i == 0 {
                 true => NULL_ENTRY,
                 false => i - 1,
             };
-            entries.push(DecodeCacheEntry::new(next_index, prev_index));
+            entries.push(DecodeCacheEntry::new(next_index, prev_index));
+        }
+
+        DecodeCache {
+            hash_map: FnvHashMap::default(),
+            entries,
+            front_index: 0,
+            back_index: DECODE_CACHE_ENTRY_NUM - 1,
+            hit_count: 0,
+            miss_count: 0,
+        }
+    }
+
+    /// Gets the cached decoding result. If hits this method moves the
+    /// cache entry to front of the linked list and returns an index of
+    /// [`INSTRUCTIONS`](static.INSTRUCTIONS.html).
+    /// Otherwise returns `None`. This operation should compute in O(1) time.
+    ///
+    /// # Arguments
+    /// * `word` word instruction data
+    fn get(&mut self, word: u32) -> Option<usize> {
+        let result = match self.hash_map.get(&word) {
+            Some(index) => {
+                self.hit_count += 1;
+                // Move the entry to front of the list unless it is at front.
+                if self.front_index != *index {
+                    let next_index = self.entries[*index].next_index;
+                    let prev_index = self.entries[*index].prev_index;
+
+                    // Remove the entry from the list
+                    if self.back_index == *index {
+                        self.back_index = prev_index;
+                    } else {
+                        self.entries[next_index].prev_index = prev_index;
+                    }
+                    self.entries[prev_index].next_index = next_index;
+
+                    // Push the entry to front
+                    self.entries[*index].prev_index = NULL_ENTRY;
+                    self.entries[*index].next_index = self.front_index;
+                    self.entries[self.front_index].prev_index = *index;
+                    self.front_index = *index;
+                }
+                Some(self.entries[*index].instruction_index)
+            }
+            None => {
+                self.miss_count += 1;
+                None
+            }
+        };
+        //println!("Hit:{:X}, Miss:{:X}, Ratio:{}", self.hit_count, self.miss_count,
+        //	(self.hit_count as f64) / (self.hit_count + self.miss_count) as f64);
+        result
+    }
+
+    /// Inserts a new decode result to front of the linked list while removing
+    /// the least recently used result from the list. This operation should
+    /// compute in O(1) time.
+    ///
+    /// # Arguments
+    /// * `word`
+    /// * `instruction_index`
+    fn insert(&mut self, word: u32, instruction_index: usize) {
+        let index = self.back_index;
+
+        // Remove the least recently used entry. The entry resource
+        // is reused as new entry.
+        if self.entries[index].instruction_index != INVALID_CACHE_ENTRY {
+            self.hash_map.remove(&self.entries[index].word);
+        }
+        self.back_index = self.entries[index].prev_index;
+        self.entries[self.back_index].next_index = NULL_ENTRY;
+
+        // Push the new entry to front of the linked list
+        self.hash_map.insert(word, index);
+        self.entries[index].prev_index = NULL_ENTRY;
+        self.entries[index].next_index = self.front_index;
+        self.entries[index].word = word;
+        self.entries[index].instruction_index = instruction_index;
+        self.entries[self.front_index].prev_index = index;
+        self.front_index = index;
+    }
+}
+
+/// An entry of linked list managed by [`DecodeCache`](struct.DecodeCache.html).
+/// An entry consists of a mapping from word instruction data to an index of
+/// [`INSTRUCTIONS`](static.INSTRUCTIONS.html) and next/previous entry index
+/// in the linked list.
+struct DecodeCacheEntry {
+    /// Instruction word data
+    word: u32,
+
+    /// The result of decoding `word`. An index of [`INSTRUCTIONS`](static.INSTRUCTIONS.html).
+    instruction_index: usize,
+
+    /// Next entry index in the linked list. [`NULL_ENTRY`](constant.NULL_ENTRY.html)
+    /// represents no next entry, meaning the entry is at tail.
+    next_index: usize,
+
+    /// Previous entry index in the linked list. [`NULL_ENTRY`](constant.NULL_ENTRY.html)
+    /// represents no previous entry, meaning the entry is at head.
+    prev_index: usize,
+}
+
+impl DecodeCacheEntry {
+    /// Creates a new entry. Initial `instruction_index` is
+    /// `INVALID_CACHE_ENTRY` meaning the entry is invalid.
+    ///
+    /// # Arguments
+    /// * `next_index`
+    /// * `prev_index`
+    fn new(next_index: usize, prev_index: usize) -> Self {
+        Self {
+            word: 0,
+            instruction_index: INVALID_CACHE_ENTRY,
+            next_index,
+            prev_index,
+        }
+    }
+}
+
+/// Operand fields of an instruction, decoded once by
+/// [`BlockCache`](struct.BlockCache.html) so that the cached instruction
+/// doesn't parse its word again each time it's executed. The fields the
+/// format doesn't have are zero.
+#[derive(Clone, Copy, Default)]
+struct Operands {
+    rd: usize,
+    rs1: usize,
+    rs2: usize,
+    imm: i64,
+}
+
+impl Operands {
+    fn format_b(word: u32) -> Self {
+        let f = parse_format_b(word);
+        Operands {
+            rs1: f.rs1,
+            rs2: f.rs2,
+            imm: f.imm as i64,
+            ..Default::default()
+        }
+    }
+
+    fn format_i(word: u32) -> Self {
+        let f = parse_format_i(word);
+        Operands {
+            rd: f.rd,
+            rs1: f.rs1,
+            imm: f.imm,
+            ..Default::default()
+        }
+    }
+
+    fn format_j(word: u32) -> Self {
+        let f = parse_format_j(word);
+        Operands {
+            rd: f.rd,
+            imm: f.imm as i64,
+            ..Default::default()
+        }
+    }
+
+    fn format_r(word: u32) -> Self {
+        let f = parse_format_r(word);
+        Operands {
+            rd: f.rd,
+            rs1: f.rs1,
+            rs2: f.rs2,
+            imm: 0,
+        }
+    }
+
+    fn format_s(word: u32) -> Self {
+        let f = parse_format_s(word);
+        Operands {
+            rs1: f.rs1,
+            rs2: f.rs2,
+            imm: f.imm,
+            ..Default::default()
+        }
+    }
+
+    fn format_u(word: u32) -> Self {
+        let f = parse_format_u(word);
+        Operands {
+            rd: f.rd,
+            imm: f.imm as i64,
+            ..Default::default()
+        }
+    }
+
+    /// Shift by immediate, `imm` is the shift amount `[25:20]` which the
+    /// operation masks for XLEN.
+    fn format_shift(word: u32) -> Self {
+        Operands {
+            imm: ((word >> 20) & 0x3f) as i64,
+            ..Operands::format_r(word)
+        }
+    }
+}
+
+type DecodedOperation = fn(cpu: &mut Cpu, operands: &Operands, address: u64) -> Result<(), Trap>;
+
+/// Returns the operation taking decoded operands and the operands of an
+/// RV32I/RV64I base instruction, classified by the opcode and the funct
+/// fields. `None` for the other instructions, which are operated with the
+/// word. The word must have been decoded to an
+/// [`Instruction`](struct.Instruction.html) whose operation is the same.
+///
+/// # Arguments
+/// * `word` Uncompressed instruction word
+fn decode_operands(word: u32) -> Option<(DecodedOperation, Operands)> {
+    let funct3 = (word >> 12) & 0x7;
+    let funct6 = word >> 26;
+    let funct7 = word >> 25;
+    let (operation, format): (DecodedOperation, fn(u32) -> Operands) = match word & 0x7f {
+        0x37 => (lui, Operands::format_u),
+        0x17 => (auipc, Operands::format_u),
+        0x6f => (jal, Operands::format_j),
+        0x67 => (jalr, Operands::format_i),
+        0x63 => match funct3 {
+            0 => (beq, Operands::format_b),
+            1 => (bne, Operands::format_b),
+            4 => (blt, Operands::format_b),
+            5 => (bge, Operands::format_b),
+            6 => (bltu, Operands::format_b),
+            7 => (bgeu, Operands::format_b),
+            _ => return None,
+        },
+        0x03 => match funct3 {
+            0 => (lb, Operands::format_i),
+            1 => (lh, Operands::format_i),
+            2 => (lw, Operands::format_i),
+            3 => (ld, Operands::format_i),
+            4 => (lbu, Operands::format_i),
+            5 => (lhu, Operands::format_i),
+            6 => (lwu, Operands::format_i),
+            _ => return None,
+        },
+        0x23 => match funct3 {
+            0 => (sb, Operands::format_s),
+            1 => (sh, Operands::format_s),
+            2 => (sw, Operands::format_s),
+            3 => (sd, Operands::format_s),
+            _ => return None,
+        },
+        0x13 => match (funct3, funct6) {
+            (0, _) => (addi, Operands::format_i),
+            (2, _) => (slti, Operands::format_i),
+            (3, _) => (sltiu, Operands::format_i),
+            (4, _) => (xori, Operands::format_i),
+            (6, _) => (ori, Operands::format_i),
+            (7, _) => (andi, Operands::format_i),
+            (1, 0x00) => (slli, Operands::format_shift),
+            (5, 0x00) => (srli, Operands::format_shift),
+            (5, 0x10) => (srai, Operands::format_shift),
+            _ => return None,
+        },
+        0x33 => match (funct3, funct7) {
+            (0, 0x00) => (add, Operands::format_r),
+            (0, 0x20) => (sub, Operands::format_r),
+            (1, 0x00) => (sll, Operands::format_r),
+            (2, 0x00) => (slt, Operands::format_r),
+            (3, 0x00) => (sltu, Operands::format_r),
+            (4, 0x00) => (xor, Operands::format_r),
+            (5, 0x00) => (srl, Operands::format_r),
+            (5, 0x20) => (sra, Operands::format_r),
+            (6, 0x00) => (or, Operands::format_r),
+            (7, 0x00) => (and, Operands::format_r),
+            _ => return None,
+        },
+        0x1b => match (funct3, funct6, funct7) {
+            (0, _, _) => (addiw, Operands::format_i),
+            (1, _, 0x00) => (slliw, Operands::format_r),
+            (5, 0x00, _) => (srliw, Operands::format_shift),
+            (5, 0x10, _) => (sraiw, Operands::format_shift),
+            _ => return None,
+        },
+        0x3b => match (funct3, funct7) {
+            (0, 0x00) => (addw, Operands::format_r),
+            (0, 0x20) => (subw, Operands::format_r),
+            (1, 0x00) => (sllw, Operands::format_r),
+            (5, 0x00) => (srlw, Operands::format_r),
+            (5, 0x20) => (sraw, Operands::format_r),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some((operation, format(word)))
+}
+
+fn lui(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = f.imm;
+    Ok(())
+}
+
+fn auipc(cpu: &mut Cpu, f: &Operands, address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(address.wrapping_add(f.imm as u64) as i64);
+    Ok(())
+}
+
+fn jal(cpu: &mut Cpu, f: &Operands, address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(cpu.pc as i64);
+    cpu.pc = address.wrapping_add(f.imm as u64);
+    Ok(())
+}
+
+fn jalr(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    let tmp = cpu.sign_extend(cpu.pc as i64);
+    cpu.pc = (cpu.x[f.rs1] as u64).wrapping_add(f.imm as u64);
+    cpu.x[f.rd] = tmp;
+    Ok(())
+}
+
+fn beq(cpu: &mut Cpu, f: &Operands, address: u64) -> Result<(), Trap> {
+    if cpu.sign_extend(cpu.x[f.rs1]) == cpu.sign_extend(cpu.x[f.rs2]) {
+        cpu.pc = address.wrapping_add(f.imm as u64);
+    }
+    Ok(())
+}
+
+fn bne(cpu: &mut Cpu, f: &Operands, address: u64) -> Result<(), Trap> {
+    if cpu.sign_extend(cpu.x[f.rs1]) != cpu.sign_extend(cpu.x[f.rs2]) {
+        cpu.pc = address.wrapping_add(f.imm as u64);
+    }
+    Ok(())
+}
+
+fn blt(cpu: &mut Cpu, f: &Operands, address: u64) -> Result<(), Trap> {
+    if cpu.sign_extend(cpu.x[f.rs1]) < cpu.sign_extend(cpu.x[f.rs2]) {
+        cpu.pc = address.wrapping_add(f.imm as u64);
+    }
+    Ok(())
+}
+
+fn bge(cpu: &mut Cpu, f: &Operands, address: u64) -> Result<(), Trap> {
+    if cpu.sign_extend(cpu.x[f.rs1]) >= cpu.sign_extend(cpu.x[f.rs2]) {
+        cpu.pc = address.wrapping_add(f.imm as u64);
+    }
+    Ok(())
+}
+
+fn bltu(cpu: &mut Cpu, f: &Operands, address: u64) -> Result<(), Trap> {
+    if cpu.unsigned_data(cpu.x[f.rs1]) < cpu.unsigned_data(cpu.x[f.rs2]) {
+        cpu.pc = address.wrapping_add(f.imm as u64);
+    }
+    Ok(())
+}
+
+fn bgeu(cpu: &mut Cpu, f: &Operands, address: u64) -> Result<(), Trap> {
+    if cpu.unsigned_data(cpu.x[f.rs1]) >= cpu.unsigned_data(cpu.x[f.rs2]) {
+        cpu.pc = address.wrapping_add(f.imm as u64);
+    }
+    Ok(())
+}
+
+fn lb(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = match cpu.mmu.load(cpu.x[f.rs1].wrapping_add(f.imm) as u64) {
+        Ok(data) => data as i8 as i64,
+        Err(e) => return Err(e),
+    };
+    Ok(())
+}
+
+fn lh(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = match cpu
+        .mmu
+        .load_halfword(cpu.x[f.rs1].wrapping_add(f.imm) as u64)
+    {
+        Ok(data) => data as i16 as i64,
+        Err(e) => return Err(e),
+    };
+    Ok(())
+}
+
+fn lw(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = match cpu.mmu.load_word(cpu.x[f.rs1].wrapping_add(f.imm) as u64) {
+        Ok(data) => data as i32 as i64,
+        Err(e) => return Err(e),
+    };
+    Ok(())
+}
+
+fn ld(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = match cpu
+        .mmu
+        .load_doubleword(cpu.x[f.rs1].wrapping_add(f.imm) as u64)
+    {
+        Ok(data) => data as i64,
+        Err(e) => return Err(e),
+    };
+    Ok(())
+}
+
+fn lbu(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = match cpu.mmu.load(cpu.x[f.rs1].wrapping_add(f.imm) as u64) {
+        Ok(data) => data as i64,
+        Err(e) => return Err(e),
+    };
+    Ok(())
+}
+
+fn lhu(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = match cpu
+        .mmu
+        .load_halfword(cpu.x[f.rs1].wrapping_add(f.imm) as u64)
+    {
+        Ok(data) => data as i64,
+        Err(e) => return Err(e),
+    };
+    Ok(())
+}
+
+fn lwu(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = match cpu.mmu.load_word(cpu.x[f.rs1].wrapping_add(f.imm) as u64) {
+        Ok(data) => data as i64,
+        Err(e) => return Err(e),
+    };
+    Ok(())
+}
+
+fn sb(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.mmu
+        .store(cpu.x[f.rs1].wrapping_add(f.imm) as u64, cpu.x[f.rs2] as u8)
+}
+
+fn sh(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.mmu
+        .store_halfword(cpu.x[f.rs1].wrapping_add(f.imm) as u64, cpu.x[f.rs2] as u16)
+}
+
+fn sw(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.mmu
+        .store_word(cpu.x[f.rs1].wrapping_add(f.imm) as u64, cpu.x[f.rs2] as u32)
+}
+
+fn sd(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.mmu
+        .store_doubleword(cpu.x[f.rs1].wrapping_add(f.imm) as u64, cpu.x[f.rs2] as u64)
+}
+
+fn addi(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_add(f.imm));
+    Ok(())
+}
+
+fn slti(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = match cpu.x[f.rs1] < f.imm {
+        true => 1,
+        false => 0,
+    };
+    Ok(())
+}
+
+fn sltiu(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = match cpu.unsigned_data(cpu.x[f.rs1]) < cpu.unsigned_data(f.imm) {
+        true => 1,
+        false => 0,
+    };
+    Ok(())
+}
+
+fn xori(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] ^ f.imm);
+    Ok(())
+}
+
+fn ori(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] | f.imm);
+    Ok(())
+}
+
+fn andi(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] & f.imm);
+    Ok(())
+}
+
+fn slli(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    let mask = match cpu.xlen {
+        Xlen::Bit32 => 0x1f,
+        Xlen::Bit64 => 0x3f,
+    };
+    let shamt = f.imm as u32 & mask;
+    cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] << shamt);
+    Ok(())
+}
+
+fn srli(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    let mask = match cpu.xlen {
+        Xlen::Bit32 => 0x1f,
+        Xlen::Bit64 => 0x3f,
+    };
+    let shamt = f.imm as u32 & mask;
+    cpu.x[f.rd] = cpu.sign_extend((cpu.unsigned_data(cpu.x[f.rs1]) >> shamt) as i64);
+    Ok(())
+}
+
+fn srai(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    let mask = match cpu.xlen {
+        Xlen::Bit32 => 0x1f,
+        Xlen::Bit64 => 0x3f,
+    };
+    let shamt = f.imm as u32 & mask;
+    cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] >> shamt);
+    Ok(())
+}
+
+fn add(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_add(cpu.x[f.rs2]));
+    Ok(())
+}
+
+fn sub(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_sub(cpu.x[f.rs2]));
+    Ok(())
+}
+
+fn sll(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_shl(cpu.x[f.rs2] as u32));
+    Ok(())
+}
+
+fn slt(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = match cpu.x[f.rs1] < cpu.x[f.rs2] {
+        true => 1,
+        false => 0,
+    };
+    Ok(())
+}
+
+fn sltu(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = match cpu.unsigned_data(cpu.x[f.rs1]) < cpu.unsigned_data(cpu.x[f.rs2]) {
+        true => 1,
+        false => 0,
+    };
+    Ok(())
+}
+
+fn xor(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] ^ cpu.x[f.rs2]);
+    Ok(())
+}
+
+fn srl(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(
+        cpu.unsigned_data(cpu.x[f.rs1])
+            .wrapping_shr(cpu.x[f.rs2] as u32) as i64,
+    );
+    Ok(())
+}
+
+fn sra(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1].wrapping_shr(cpu.x[f.rs2] as u32));
+    Ok(())
+}
+
+fn or(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] | cpu.x[f.rs2]);
+    Ok(())
+}
+
+fn and(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.sign_extend(cpu.x[f.rs1] & cpu.x[f.rs2]);
+    Ok(())
+}
+
+fn addiw(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.x[f.rs1].wrapping_add(f.imm) as i32 as i64;
+    Ok(())
+}
+
+fn slliw(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    let shamt = f.rs2 as u32;
+    cpu.x[f.rd] = (cpu.x[f.rs1] << shamt) as i32 as i64;
+    Ok(())
+}
+
+fn srliw(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    let shamt = f.imm as u32 & 0x1f;
+    cpu.x[f.rd] = ((cpu.x[f.rs1] as u32) >> shamt) as i32 as i64;
+    Ok(())
+}
+
+fn sraiw(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    let shamt = f.imm as u32 & 0x1f;
+    cpu.x[f.rd] = ((cpu.x[f.rs1] as i32) >> shamt) as i64;
+    Ok(())
+}
+
+fn addw(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.x[f.rs1].wrapping_add(cpu.x[f.rs2]) as i32 as i64;
+    Ok(())
+}
+
+fn subw(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = cpu.x[f.rs1].wrapping_sub(cpu.x[f.rs2]) as i32 as i64;
+    Ok(())
+}
+
+fn sllw(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = (cpu.x[f.rs1] as u32).wrapping_shl(cpu.x[f.rs2] as u32) as i32 as i64;
+    Ok(())
+}
+
+fn srlw(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = (cpu.x[f.rs1] as u32).wrapping_shr(cpu.x[f.rs2] as u32) as i32 as i64;
+    Ok(())
+}
+
+fn sraw(cpu: &mut Cpu, f: &Operands, _address: u64) -> Result<(), Trap> {
+    cpu.x[f.rd] = (cpu.x[f.rs1] as i32).wrapping_shr(cpu.x[f.rs2] as u32) as i64;
+    Ok(())
+}
+
+/// The maximum number of instructions in a block [`BlockCache`](struct.BlockCache.html)
+/// holds. A longer run of instructions is split into blocks.
+const BLOCK_INSTRUCTION_NUM: usize = 64;
+
+/// An instruction decoded in a block. The operation is called directly
+/// with the decoded operands of a base instruction, or with the
+/// uncompressed word of the others.
+#[derive(Clone, Copy)]
+struct BlockInstruction {
+    operation: BlockOperation,
+
+    /// Instruction bits fetched, reported in xtval of illegal instruction
+    instruction_bits: u32,
+
+    /// Instruction size in bytes, 2 for compressed instruction or 4
+    size: u64,
+}
+
+/// Operation of [`BlockInstruction`](struct.BlockInstruction.html)
+#[derive(Clone, Copy)]
+enum BlockOperation {
+    /// Base instruction whose operands are decoded in advance
+    Decoded(DecodedOperation, Operands),
+
+    /// The other instructions, taking the uncompressed word
+    Word(
+        fn(cpu: &mut Cpu, word: u32, address: u64) -> Result<(), Trap>,
+        u32,
+    ),
+}
+
+/// `BlockCache` holds decoded basic blocks, runs of instructions up to a
+/// control transfer or a system instruction, keyed by the physical address
+/// of the first instruction. While a hart executes a block the following
+/// instructions are taken from it without fetching, translating, and
+/// decoding them, so the address translation is done once a block.
+///
+/// A block doesn't cross a page so that the instructions share the
+/// translation. The blocks of a page are invalidated when the hart stores
+/// to it, and all the blocks are invalidated with `FENCE.I` and
+/// `SFENCE.VMA`. Stores of the other harts and Virtio DMA aren't observed,
+/// so the hart may run the former instructions until it executes
+/// `FENCE.I`, which the ISA requires to fetch modified instructions anyway.
+struct BlockCache {
+    /// Holds mappings from the physical address of the first instruction
+    /// to a block
+    blocks: FnvHashMap<u64, Rc<[BlockInstruction]>>,
+
+    /// Holds mappings from physical page number to the addresses of
+    /// the blocks in the page
+    page_blocks: FnvHashMap<u64, Vec<u64>>,
+
+    /// The block being executed, the index of the next instruction in it,
+    /// and the virtual address of the next instruction
+    current: Option<(Rc<[BlockInstruction]>, usize, u64)>,
+}
+
+impl BlockCache {
+    /// Creates a new empty `BlockCache`.
+    fn new() -> Self {
+        BlockCache {
+            blocks: FnvHashMap::default(),
+            page_blocks: FnvHashMap::default(),
+            current: None,
+        }
+    }
+
+    /// Returns the next instruction of the block being executed if `pc`
+    /// points it. Otherwise leaves the block and returns `None`.
+    ///
+    /// # Arguments
+    /// * `pc` Virtual address of the instruction executed next
+    fn next(&mut self, pc: u64) -> Option<BlockInstruction> {
+        if let Some((block, index, address)) = &mut self.current {
+            if *address == pc && *index < block.len() {
+                let instruction = block[*index];
+                *index += 1;
+                *address = address.wrapping_add(instruction.size);
+                return Some(instruction);
+            }
+        }
+        self.current = None;
+        None
+    }
+
+    /// Starts executing the cached block and returns its first instruction.
+    /// Returns `None` if no block is cached at the address.
+    ///
+    /// # Arguments
+    /// * `p_address` Physical address of the block
+    /// * `pc` Virtual address of the block
+    fn enter(&mut self, p_address: u64, pc: u64) -> Option<BlockInstruction> {
+        let block = self.blocks.get(&p_address)?.clone();
+        self.current = Some((block, 0, pc));
+        self.next(pc)
+    }
+
+    /// Returns the size in bytes of the cached block, or `None` if no
+    /// block is cached at the address.
+    ///
+    /// # Arguments
+    /// * `p_address` Physical address of the block
+    fn get_block_size(&self, p_address: u64) -> Option<u64> {
+        self.blocks
+            .get(&p_address)
+            .map(|block| block.iter().map(|instruction| instruction.size).sum())
+    }
+
+    /// Caches a block.
+    ///
+    /// # Arguments
+    /// * `p_address` Physical address of the first instruction
+    /// * `block`
+    fn insert(&mut self, p_address: u64, block: Vec<BlockInstruction>) {
+        self.blocks.insert(p_address, block.into());
+        self.page_blocks
+            .entry(p_address >> 12)
+            .or_default()
+            .push(p_address);
+    }
+
+    /// Invalidates the blocks in a page.
+    ///
+    /// # Arguments
+    /// * `page` Physical page number
+    fn invalidate_page(&mut self, page: u64) {
+        if let Some(addresses) = self.page_blocks.remove(&page) {
+            for address in addresses {
+                self.blocks.remove(&address);
+            }
+        }
+        self.current = None;
+    }
+
+    /// Invalidates all the blocks.
+    fn clear(&mut self) {
+        self.blocks.clear();
+        self.page_blocks.clear();
+        self.current = None;
+    }
+
+    /// Leaves the block being executed, when the execution is redirected
+    /// not by the instructions of the block like traps.
+    fn leave(&mut self) {
+        self.current = None;
+    }
+}
+#[cfg(test)]
+mod test_cpu {
+    use super::*;
+    use crate::mmu::DRAM_BASE;
+    use crate::terminal::DummyTerminal;
+
+    fn create_cpu() -> Cpu {
+        Cpu::new(Box::new(DummyTerminal::new()))
+    }
+
+    #[test]
+    fn initialize() {
+        let _cpu = create_cpu();
+    }
+
+    #[test]
+    fn update_pc() {
+        let mut cpu = create_cpu();
+        assert_eq!(0, cpu.read_pc());
+        cpu.update_pc(1);
+        assert_eq!(1, cpu.read_pc());
+        cpu.update_pc(0xffffffffffffffff);
+        assert_eq!(0xffffffffffffffff, cpu.read_pc());
+    }
+
+    #[test]
+    fn update_xlen() {
+        let mut cpu = create_cpu();
+        assert!(matches!(cpu.xlen, Xlen::Bit64));
+        cpu.update_xlen(Xlen::Bit32);
+        assert!(matches!(cpu.xlen, Xlen::Bit32));
+        cpu.update_xlen(Xlen::Bit64);
+        assert!(matches!(cpu.xlen, Xlen::Bit64));
+        // Note: cpu.update_xlen() updates cpu.mmu.xlen, too.
+        // TODO: The test for mmu.xlen should be in Mmu?
+    }
+
+    #[test]
+    fn read_register() {
+        let mut cpu = create_cpu();
+        // Initial register values are 0. The boot ROM passes the arguments.
+        for i in 0..31 {
+            assert_eq!(0, cpu.read_register(i));
+        }
+
+        for i in 0..31 {
+            cpu.x[i] = i as i64 + 1;
+        }
+
+        for i in 0..31 {
+            match i {
+                // 0th register is hardwired zero
+                0 => assert_eq!(0, cpu.read_register(i)),
+                _ => assert_eq!(i as i64 + 1, cpu.read_register(i)),
+            }
+        }
+
+        for i in 0..31 {
+            cpu.x[i] = (0xffffffffffffffff - i) as i64;
+        }
+
+        for i in 0..31 {
+            match i {
+                // 0th register is hardwired zero
+                0 => assert_eq!(0, cpu.read_register(i)),
+                _ => assert_eq!(-(i as i64 + 1), cpu.read_register(i)),
+            }
+        }
+
+        // @TODO: Should I test the case where the argument equals to or is
+        // greater than 32?
+    }
+
+    #[test]
+    fn tick() {
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(4);
+        cpu.update_pc(DRAM_BASE);
+
+        // Write non-compressed "addi x1, x1, 1" instruction
+        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00108093) {
+            Ok(()) => {}
+            Err(_e) => panic!("Failed to store"),
+        };
+        // Write compressed "addi x8, x0, 8" instruction
+        match cpu.get_mut_mmu().store_word(DRAM_BASE + 4, 0x20) {
+            Ok(()) => {}
+            Err(_e) => panic!("Failed to store"),
+        };
+
+        cpu.tick();
+
+        assert_eq!(DRAM_BASE + 4, cpu.read_pc());
+        assert_eq!(1, cpu.read_register(1));
+
+        cpu.tick();
+
+        assert_eq!(DRAM_BASE + 6, cpu.read_pc());
+        assert_eq!(8, cpu.read_register(8));
+    }
+
+    #[test]
+    fn tick_operate() {
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(4);
+        cpu.update_pc(DRAM_BASE);
+        // write non-compressed "addi a0, a0, 12" instruction
+        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0xc50513) {
+            Ok(()) => {}
+            Err(_e) => panic!("Failed to store"),
+        };
+        assert_eq!(DRAM_BASE, cpu.read_pc());
+        assert_eq!(0, cpu.read_register(10));
+        match cpu.tick_operate() {
+            Ok(()) => {}
+            Err(_e) => panic!("tick_operate() unexpectedly did panic"),
+        };
+        // .tick_operate() increments the program counter by 4 for
+        // non-compressed instruction.
+        assert_eq!(DRAM_BASE + 4, cpu.read_pc());
+        // "addi a0, a0, a12" instruction writes 12 to a0 register.
+        assert_eq!(12, cpu.read_register(10));
+        // @TODO: Test compressed instruction operation
+    }
+
+    #[test]
+    fn fetch() {
+        // .fetch() reads four bytes from the memory
+        // at the address the program counter points to.
+        // .fetch() doesn't increment the program counter.
+        // .tick_operate() does.
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(4);
+        cpu.update_pc(DRAM_BASE);
+        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0xaaaaaaaa) {
+            Ok(()) => {}
+            Err(_e) => panic!("Failed to store"),
+        };
+        match cpu.fetch() {
+            Ok(data) => assert_eq!(0xaaaaaaaa, data),
+            Err(_e) => panic!("Failed to fetch"),
+        };
+        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0x55555555) {
+            Ok(()) => {}
+            Err(_e) => panic!("Failed to store"),
+        };
+        match cpu.fetch() {
+            Ok(data) => assert_eq!(0x55555555, data),
+            Err(_e) => panic!("Failed to fetch"),
+        };
+        // @TODO: Write test cases where Trap happens
+    }
+
+    #[test]
+    fn decode() {
+        let mut cpu = create_cpu();
+        // 0x13 is addi instruction
+        match cpu.decode(0x13) {
+            Ok(inst) => assert_eq!(inst.name, "ADDI"),
+            Err(_e) => panic!("Failed to decode"),
+        };
+        // .decode() returns error for invalid word data.
+        if cpu.decode(0x0).is_ok() {
+            panic!("Unexpectedly succeeded in decoding");
+        }
+        // @TODO: Should I test all instructions?
+    }
+
+    #[test]
+    fn uncompress() {
+        let mut cpu = create_cpu();
+        // .uncompress() doesn't directly return an instruction but
+        // it returns uncompressed word. Then you need to call .decode().
+        match cpu.decode(cpu.uncompress(0x20)) {
+            Ok(inst) => assert_eq!(inst.name, "ADDI"),
+            Err(_e) => panic!("Failed to decode"),
+        };
+        // @TODO: Should I test all compressed instructions?
+    }
+
+    #[test]
+    fn isa_string_extensions() {
+        let create_cpu_with_isa = |isa: &str| {
+            let config = MachineConfig {
+                isa: isa.to_string(),
+                ..MachineConfig::default()
+            };
+            Cpu::new_with_config(Box::new(DummyTerminal::new()), &config)
+        };
+        // Compressed instructions are uncompressed before decoded
+        let is_legal = |cpu: &Cpu, bits: u32| {
+            let word = match bits & 0x3 {
+                0x3 => bits,
+                _ => cpu.uncompress(bits),
+            };
+            cpu.decode_raw(word).is_ok()
+        };
+        let mul = 0x02b50533; // mul a0, a0, a1
+        let amoadd = 0x00b6252f; // amoadd.w a0, a1, (a2)
+        let flw = 0x0005a507; // flw fa0, 0(a1)
+        let fld = 0x0005b507; // fld fa0, 0(a1)
+        let c_nop = 0x0001; // c.nop
+        let andn = 0x40c5f533; // andn a0, a1, a2
+        let sh1add = 0x20c5a533; // sh1add a0, a1, a2
+        let clmul = 0x0ac59533; // clmul a0, a1, a2
+        let vadd = 0x022180d7; // vadd.vv v1, v2, v3
+
+        // Only the base ISA
+        let cpu = create_cpu_with_isa("rv64isu");
+        assert!(is_legal(&cpu, 0x00150513)); // addi a0, a0, 1
+        for word in [mul, amoadd, flw, fld, c_nop, andn, sh1add, clmul, vadd] {
+            assert!(!is_legal(&cpu, word), "{:08x}", word);
+        }
+
+        // F without D
+        let cpu = create_cpu_with_isa("rv64imafcsu");
+        for word in [mul, amoadd, flw, c_nop] {
+            assert!(is_legal(&cpu, word), "{:08x}", word);
+        }
+        assert!(!is_legal(&cpu, fld));
+        assert!(!is_legal(&cpu, vadd));
+
+        // Multi-letter extensions
+        let cpu = create_cpu_with_isa("rv64imafcsu_zbb");
+        assert!(is_legal(&cpu, andn));
+        assert!(!is_legal(&cpu, sh1add));
+        assert!(!is_legal(&cpu, clmul));
+
+        // B implies Zba, Zbb and Zbs but not Zbc
+        let cpu = create_cpu_with_isa("rv64gcb");
+        assert!(is_legal(&cpu, sh1add));
+        assert!(!is_legal(&cpu, clmul));
+    }
+
+    #[test]
+    fn instruction_extensions() {
+        for inst in INSTRUCTIONS.iter() {
+            assert!(get_extension(inst).is_some(), "{}", inst.name);
+        }
+    }
+
+    #[test]
+    fn wfi() {
+        let wfi_instruction = 0x10500073;
+        let mut cpu = create_cpu();
+        // Just in case
+        match cpu.decode(wfi_instruction) {
+            Ok(inst) => assert_eq!(inst.name, "WFI"),
+            Err(_e) => panic!("Failed to decode"),
+        };
+        cpu.get_mut_mmu().init_memory(4);
+        cpu.update_pc(DRAM_BASE);
+        // write WFI instruction
+        match cpu.get_mut_mmu().store_word(DRAM_BASE, wfi_instruction) {
+            Ok(()) => {}
+            Err(_e) => panic!("Failed to store"),
+        };
+        cpu.tick();
+        assert_eq!(DRAM_BASE + 4, cpu.read_pc());
+        for _i in 0..10 {
+            // Until interrupt happens, .tick() does nothing
+            // @TODO: Check accurately that the state is unchanged
+            cpu.tick();
+            assert_eq!(DRAM_BASE + 4, cpu.read_pc());
+        }
+        // Machine timer interrupt
+        cpu.write_csr_raw(CSR_MIE_ADDRESS, MIP_MTIP);
+        cpu.write_csr_raw(CSR_MIP_ADDRESS, MIP_MTIP);
+        cpu.write_csr_raw(CSR_MSTATUS_ADDRESS, 0x8);
+        cpu.write_csr_raw(CSR_MTVEC_ADDRESS, 0x0);
+        cpu.tick();
+        // Interrupt happened and moved to handler
+        assert_eq!(0, cpu.read_pc());
+    }
+
+    #[test]
+    fn interrupt() {
+        let handler_vector = 0x10000000;
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(4);
+        // Write non-compressed "addi x0, x0, 1" instruction
+        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00100013) {
+            Ok(()) => {}
+            Err(_e) => panic!("Failed to store"),
+        };
+        cpu.update_pc(DRAM_BASE);
+
+        // Machine timer interrupt but mie in mstatus is not enabled yet
+        cpu.write_csr_raw(CSR_MIE_ADDRESS, MIP_MTIP);
+        cpu.write_csr_raw(CSR_MIP_ADDRESS, MIP_MTIP);
+        cpu.write_csr_raw(CSR_MTVEC_ADDRESS, handler_vector);
+
+        cpu.tick();
+
+        // Interrupt isn't caught because mie is disabled
+        assert_eq!(DRAM_BASE + 4, cpu.read_pc());
+
+        cpu.update_pc(DRAM_BASE);
+        // Enable mie in mstatus
+        cpu.write_csr_raw(CSR_MSTATUS_ADDRESS, 0x8);
+
+        cpu.tick();
+
+        // Interrupt happened and moved to handler
+        assert_eq!(handler_vector, cpu.read_pc());
+
+        // CSR Cause register holds the reason what caused the interrupt
+        assert_eq!(0x8000000000000007, cpu.read_csr_raw(CSR_MCAUSE_ADDRESS));
+
+        // @TODO: Test post CSR status register
+        // @TODO: Test xIE bit in CSR status register
+        // @TODO: Test privilege levels
+        // @TODO: Test delegation
+        // @TODO: Test vector type handlers
+    }
+
+    #[test]
+    fn exception() {
+        let handler_vector = 0x10000000;
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(4);
+        // Write ECALL instruction
+        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00000073) {
+            Ok(()) => {}
+            Err(_e) => panic!("Failed to store"),
+        };
+        cpu.write_csr_raw(CSR_MTVEC_ADDRESS, handler_vector);
+        cpu.update_pc(DRAM_BASE);
+
+        cpu.tick();
+
+        // Interrupt happened and moved to handler
+        assert_eq!(handler_vector, cpu.read_pc());
+
+        // CSR Cause register holds the reason what caused the trap
+        assert_eq!(0xb, cpu.read_csr_raw(CSR_MCAUSE_ADDRESS));
+
+        // @TODO: Test post CSR status register
+        // @TODO: Test privilege levels
+        // @TODO: Test delegation
+        // @TODO: Test vector type handlers
+    }
+
+    #[test]
+    fn hardocded_zero() {
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(8);
+        cpu.update_pc(DRAM_BASE);
+
+        // Write non-compressed "addi x0, x0, 1" instruction
+        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00100013) {
+            Ok(()) => {}
+            Err(_e) => panic!("Failed to store"),
+        };
+        // Write non-compressed "addi x1, x1, 1" instruction
+        match cpu.get_mut_mmu().store_word(DRAM_BASE + 4, 0x00108093) {
+            Ok(()) => {}
+            Err(_e) => panic!("Failed to store"),
+        };
+
+        // Test x0
+        assert_eq!(0, cpu.read_register(0));
+        cpu.tick(); // Execute  "addi x0, x0, 1"
+                    // x0 is still zero because it's hardcoded zero
+        assert_eq!(0, cpu.read_register(0));
+
+        // Test x1
+        assert_eq!(0, cpu.read_register(1));
+        cpu.tick(); // Execute  "addi x1, x1, 1"
+                    // x1 is not hardcoded zero
+        assert_eq!(1, cpu.read_register(1));
+    }
+
+    #[test]
+    fn disassemble_next_instruction() {
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(4);
+        cpu.update_pc(DRAM_BASE);
+
+        // Write non-compressed "addi x0, x0, 1" instruction
+        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00100013) {
+            Ok(()) => {}
+            Err(_e) => panic!("Failed to store"),
+        };
+
+        assert_eq!(
+            "PC:0000000080000000 00100013 ADDI zero:0,zero:0,1",
+            cpu.disassemble_next_instruction()
+        );
+
+        // No effect to PC
+        assert_eq!(DRAM_BASE, cpu.read_pc());
+    }
+
+    #[test]
+    fn illegal_instruction() {
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(0x1000);
+        cpu.write_csr_raw(CSR_MTVEC_ADDRESS, DRAM_BASE + 0x100);
+
+        // Unknown instruction traps with its bits in mtval
+        cpu.update_pc(DRAM_BASE);
+        cpu.get_mut_mmu().store_word(DRAM_BASE, 0x1234507f).unwrap();
+        cpu.tick();
+        assert_eq!(DRAM_BASE + 0x100, cpu.read_pc());
+        assert_eq!(2, cpu.read_csr_raw(CSR_MCAUSE_ADDRESS));
+        assert_eq!(DRAM_BASE, cpu.read_csr_raw(CSR_MEPC_ADDRESS));
+        assert_eq!(0x1234507f, cpu.read_csr_raw(CSR_MTVAL_ADDRESS));
+
+        // Reserved compressed encoding, C.ADDI4SPN with zero immediate
+        cpu.update_pc(DRAM_BASE);
+        cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00010004).unwrap();
+        cpu.tick();
+        assert_eq!(2, cpu.read_csr_raw(CSR_MCAUSE_ADDRESS));
+        assert_eq!(0x4, cpu.read_csr_raw(CSR_MTVAL_ADDRESS));
+
+        // Reserved rounding mode reports the instruction bits, too
+        cpu.update_pc(DRAM_BASE);
+        cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00c5d553).unwrap();
+        cpu.tick();
+        assert_eq!(2, cpu.read_csr_raw(CSR_MCAUSE_ADDRESS));
+        assert_eq!(0x00c5d553, cpu.read_csr_raw(CSR_MTVAL_ADDRESS));
+
+        // URET of the unsupported N extension
+        cpu.update_pc(DRAM_BASE);
+        cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00200073).unwrap();
+        cpu.tick();
+        assert_eq!(DRAM_BASE + 0x100, cpu.read_pc());
+        assert_eq!(2, cpu.read_csr_raw(CSR_MCAUSE_ADDRESS));
+        assert_eq!(0x00200073, cpu.read_csr_raw(CSR_MTVAL_ADDRESS));
+    }
+
+    #[test]
+    fn unsupported_csr_values() {
+        let mut cpu = create_cpu();
+        // Writing unsupported satp mode has no effect
+        cpu.write_csr(CSR_SATP_ADDRESS, (8 << 60) | 0x80000)
+            .unwrap();
+        cpu.write_csr(CSR_SATP_ADDRESS, (15 << 60) | 0x12345)
+            .unwrap();
+        assert_eq!((8 << 60) | 0x80000, cpu.read_csr_raw(CSR_SATP_ADDRESS));
+
+        // The reserved MPP encoding keeps the former value
+        cpu.write_csr_raw(CSR_MSTATUS_ADDRESS, 1 << 11);
+        cpu.write_csr_raw(CSR_MSTATUS_ADDRESS, 2 << 11);
+        assert_eq!(1 << 11, cpu.read_csr_raw(CSR_MSTATUS_ADDRESS));
+    }
+
+    /// Builds a page table at the second page of the memory mapping
+    /// `v_address` to `p_address` with a leaf at `leaf_level`, and
+    /// enables the addressing mode in Supervisor mode. PMP permits
+    /// any access. Returns the address of the leaf entry.
+    fn map_page(
+        cpu: &mut Cpu,
+        mode: u64,
+        v_address: u64,
+        p_address: u64,
+        leaf_level: u64,
+        flags: u64,
+    ) -> u64 {
+        let levels = mode - 5;
+        let mut table = DRAM_BASE + 0x1000;
+        for level in (leaf_level + 1..levels).rev() {
+            let vpn = (v_address >> (12 + 9 * level)) & 0x1ff;
+            let next = table + 0x1000;
+            cpu.get_mut_mmu()
+                .store_doubleword_raw(table + vpn * 8, ((next >> 12) << 10) | 1);
+            table = next;
         }
+        let vpn = (v_address >> (12 + 9 * leaf_level)) & 0x1ff;
+        cpu.get_mut_mmu()
+            .store_doubleword_raw(table + vpn * 8, ((p_address >> 12) << 10) | flags);
+        cpu.write_csr(
+            CSR_SATP_ADDRESS,
+            (mode << 60) | ((DRAM_BASE + 0x1000) >> 12),
+        )
+        .unwrap();
+        cpu.write_csr(CSR_PMPADDR0_ADDRESS, u64::MAX).unwrap();
+        cpu.write_csr(CSR_PMPCFG0_ADDRESS, 0x1f).unwrap(); // NAPOT, RWX
+        cpu.privilege_mode = PrivilegeMode::Supervisor;
+        cpu.mmu.update_privilege_mode(PrivilegeMode::Supervisor);
+        table + vpn * 8
+    }
 
-        DecodeCache {
-            hash_map: FnvHashMap::default(),
-            entries,
-            front_index: 0,
-            back_index: DECODE_CACHE_ENTRY_NUM - 1,
-            hit_count: 0,
-            miss_count: 0,
+    #[test]
+    fn page_table_translation() {
+        let config = MachineConfig {
+            memory_size: 0x800000,
+            mmu_type: Some(AddressingMode::SV57),
+            ..MachineConfig::default()
+        };
+        let data = DRAM_BASE + 0x200000;
+        for (mode, v_address) in [
+            (8, 0x3f_1234_5000),
+            (9, 0xffff_fdef_1234_5000),
+            (10, 0x00ab_cdef_1234_5000),
+        ] {
+            let mut cpu = Cpu::new_with_config(Box::new(DummyTerminal::new()), &config);
+            cpu.get_mut_mmu()
+                .store_doubleword_raw(data + 8, 0x1122334455667788);
+            // Valid, readable, writable, executable, accessed and dirty
+            map_page(&mut cpu, mode, v_address, data, 0, 0xcf);
+            assert_eq!(mode, cpu.read_csr_raw(CSR_SATP_ADDRESS) >> 60);
+            assert_eq!(
+                0x1122334455667788,
+                cpu.get_mut_mmu().load_doubleword(v_address + 8).unwrap()
+            );
+            // Upper bits must be copies of the top bit of the virtual address
+            let v_address = v_address ^ (1 << 62);
+            assert!(matches!(
+                cpu.get_mut_mmu().load_doubleword(v_address + 8),
+                Err(Trap {
+                    trap_type: TrapType::LoadPageFault,
+                    ..
+                })
+            ));
         }
+
+        // Superpage keeps the lower virtual page numbers as the offset
+        let mut cpu = Cpu::new_with_config(Box::new(DummyTerminal::new()), &config);
+        cpu.get_mut_mmu().store_doubleword_raw(data + 0x1008, 0x55);
+        map_page(&mut cpu, 9, 0x4000_0000, data, 1, 0xcf);
+        assert_eq!(
+            0x55,
+            cpu.get_mut_mmu().load_doubleword(0x4000_1008).unwrap()
+        );
+
+        // Misaligned superpage
+        let mut cpu = Cpu::new_with_config(Box::new(DummyTerminal::new()), &config);
+        map_page(&mut cpu, 9, 0x4000_0000, data + 0x1000, 1, 0xcf);
+        assert!(matches!(
+            cpu.get_mut_mmu().load_doubleword(0x4000_1008),
+            Err(Trap {
+                trap_type: TrapType::LoadPageFault,
+                ..
+            })
+        ));
     }
 
-    /// Gets the cached decoding result. If hits this method moves the
-    /// cache entry to front of the linked list and returns an index of
-    /// [`INSTRUCTIONS`](constant.INSTRUCTIONS.html).
-    /// Otherwise returns `None`. This operation should compute in O(1) time.
-    ///
-    /// # Arguments
-    /// * `word` word instruction data
-    fn get(&mut self, word: u32) -> Option<usize> {
-        let result = match self.hash_map.get(&word) {
-            Some(index) => {
-                self.hit_count += 1;
-                // Move the entry to front of the list unless it is at front.
-                if self.front_index != *index {
-                    let next_index = self.entries[*index].next_index;
-                    let prev_index = self.entries[*index].prev_index;
+    #[test]
+    fn page_table_permissions() {
+        let v_address = 0x4000_0000;
+        let data = DRAM_BASE + 0x10000;
+        let trap_type = |result: Result<u64, Trap>| result.err().map(|trap| trap.trap_type);
+        let set_privilege_mode = |cpu: &mut Cpu, mode: PrivilegeMode| {
+            cpu.privilege_mode = mode.clone();
+            cpu.mmu.update_privilege_mode(mode);
+        };
 
-                    // Remove the entry from the list
-                    if self.back_index == *index {
-                        self.back_index = prev_index;
-                    } else {
-                        self.entries[next_index].prev_index = prev_index;
-                    }
-                    self.entries[prev_index].next_index = next_index;
+        // User page needs mstatus.SUM in Supervisor mode, and can't be
+        // executed even with it
+        let mut cpu = create_cpu();
+        map_page(&mut cpu, 8, v_address, data, 0, 0xdf);
+        assert_eq!(
+            Some(TrapType::LoadPageFault),
+            trap_type(cpu.get_mut_mmu().load_doubleword(v_address))
+        );
+        cpu.write_csr(CSR_SSTATUS_ADDRESS, MSTATUS_SUM).unwrap();
+        assert!(cpu.get_mut_mmu().load_doubleword(v_address).is_ok());
+        assert_eq!(
+            Some(TrapType::InstructionPageFault),
+            trap_type(cpu.get_mut_mmu().fetch_word(v_address).map(u64::from))
+        );
+        set_privilege_mode(&mut cpu, PrivilegeMode::User);
+        assert!(cpu.get_mut_mmu().fetch_word(v_address).is_ok());
+
+        // User mode can't access Supervisor page
+        let mut cpu = create_cpu();
+        map_page(&mut cpu, 8, v_address, data, 0, 0xcf);
+        set_privilege_mode(&mut cpu, PrivilegeMode::User);
+        assert_eq!(
+            Some(TrapType::StorePageFault),
+            trap_type(cpu.get_mut_mmu().store_doubleword(v_address, 0).map(|_| 0))
+        );
+
+        // Execute-only page is readable with mstatus.MXR
+        let mut cpu = create_cpu();
+        map_page(&mut cpu, 8, v_address, data, 0, 0xc9);
+        assert_eq!(
+            Some(TrapType::LoadPageFault),
+            trap_type(cpu.get_mut_mmu().load_doubleword(v_address))
+        );
+        cpu.write_csr(CSR_SSTATUS_ADDRESS, MSTATUS_MXR).unwrap();
+        assert!(cpu.get_mut_mmu().load_doubleword(v_address).is_ok());
+
+        // mstatus.MPRV translates Machine mode loads and stores as
+        // mstatus.MPP but not instruction fetches
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().store_doubleword_raw(data, 0x1234);
+        map_page(&mut cpu, 8, v_address, data, 0, 0xcf);
+        set_privilege_mode(&mut cpu, PrivilegeMode::Machine);
+        cpu.write_csr(CSR_MSTATUS_ADDRESS, MSTATUS_MPRV | (1 << 11))
+            .unwrap();
+        assert_eq!(
+            0x1234,
+            cpu.get_mut_mmu().load_doubleword(v_address).unwrap()
+        );
+        assert_eq!(
+            Some(TrapType::InstructionAccessFault),
+            trap_type(cpu.get_mut_mmu().fetch_word(v_address).map(u64::from))
+        );
+        cpu.write_csr(CSR_MSTATUS_ADDRESS, 1 << 11).unwrap();
+        assert!(cpu.get_mut_mmu().load_doubleword(v_address).is_err());
+
+        // Hardware sets A on access and D on write with menvcfg.ADUE
+        let mut cpu = create_cpu();
+        assert_eq!(MENVCFG_ADUE, cpu.read_csr_raw(CSR_MENVCFG_ADDRESS));
+        let pte_address = map_page(&mut cpu, 8, v_address, data, 0, 0x0f);
+        assert!(cpu.get_mut_mmu().load_doubleword(v_address).is_ok());
+        assert_eq!(0x4f, cpu.get_mut_mmu().load_word_raw(pte_address) & 0xff);
+        assert!(cpu.get_mut_mmu().store_doubleword(v_address, 0).is_ok());
+        assert_eq!(0xcf, cpu.get_mut_mmu().load_word_raw(pte_address) & 0xff);
+
+        // Page fault lets software update them without it
+        let mut cpu = create_cpu();
+        cpu.write_csr(CSR_MENVCFG_ADDRESS, 0).unwrap();
+        let pte_address = map_page(&mut cpu, 8, v_address, data, 0, 0x4f);
+        assert!(cpu.get_mut_mmu().load_doubleword(v_address).is_ok());
+        assert_eq!(
+            Some(TrapType::StorePageFault),
+            trap_type(cpu.get_mut_mmu().store_doubleword(v_address, 0).map(|_| 0))
+        );
+        assert_eq!(0x4f, cpu.get_mut_mmu().load_word_raw(pte_address) & 0xff);
+
+        // menvcfg.ADUE is read-only zero without Svadu
+        let config = MachineConfig {
+            isa: "rv64imafdcsu".to_string(),
+            ..MachineConfig::default()
+        };
+        let mut cpu = Cpu::new_with_config(Box::new(DummyTerminal::new()), &config);
+        cpu.write_csr(CSR_MENVCFG_ADDRESS, MENVCFG_ADUE).unwrap();
+        assert_eq!(0, cpu.read_csr_raw(CSR_MENVCFG_ADDRESS));
+
+        // Reserved bits and A, D or U in a non-leaf entry
+        for (flags, leaf_level) in [((1 << 54) | 0xcf, 0), (0x41, 1)] {
+            let mut cpu = create_cpu();
+            map_page(&mut cpu, 8, v_address, data, leaf_level, flags);
+            assert_eq!(
+                Some(TrapType::LoadPageFault),
+                trap_type(cpu.get_mut_mmu().load_doubleword(v_address))
+            );
+        }
+    }
+
+    #[test]
+    fn physical_memory_protection() {
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(0x10000);
+        // Supervisor mode can't access anything without PMP entries
+        cpu.get_mut_mmu()
+            .update_privilege_mode(PrivilegeMode::Supervisor);
+        assert!(matches!(
+            cpu.get_mut_mmu().load_word(DRAM_BASE),
+            Err(Trap {
+                trap_type: TrapType::LoadAccessFault,
+                value: DRAM_BASE,
+            })
+        ));
+        cpu.get_mut_mmu()
+            .update_privilege_mode(PrivilegeMode::Machine);
+
+        // Entry 0: 0x80000000-0x80001000 top of range, read and execute
+        // Entry 1: 0x80001000-0x80001004 four bytes, read only and locked
+        // Entry 2: 0x80000000-0x80010000 naturally aligned, read and write
+        cpu.write_csr(CSR_PMPADDR0_ADDRESS, (DRAM_BASE + 0x1000) >> 2)
+            .unwrap();
+        cpu.write_csr(CSR_PMPADDR0_ADDRESS + 1, (DRAM_BASE + 0x1000) >> 2)
+            .unwrap();
+        cpu.write_csr(CSR_PMPADDR0_ADDRESS + 2, (DRAM_BASE >> 2) | 0x1fff)
+            .unwrap();
+        cpu.write_csr(CSR_PMPCFG0_ADDRESS, 0x1b910d).unwrap();
+        assert_eq!(0x1b910d, cpu.read_csr_raw(CSR_PMPCFG0_ADDRESS));
+
+        // Locked entry applies to Machine mode, too
+        assert!(cpu.get_mut_mmu().load_word(DRAM_BASE + 0x1000).is_ok());
+        assert!(matches!(
+            cpu.get_mut_mmu().store_word(DRAM_BASE + 0x1000, 0),
+            Err(Trap {
+                trap_type: TrapType::StoreAccessFault,
+                ..
+            })
+        ));
+        // Unlocked entry doesn't, storing nop
+        assert!(cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00000013).is_ok());
+        // Locked entry can't be updated
+        cpu.write_csr(CSR_PMPCFG0_ADDRESS, 0x1b000d).unwrap();
+        assert_eq!(0x1b910d, cpu.read_csr_raw(CSR_PMPCFG0_ADDRESS));
+        cpu.write_csr(CSR_PMPADDR0_ADDRESS + 1, 0).unwrap();
+        assert_eq!(
+            (DRAM_BASE + 0x1000) >> 2,
+            cpu.read_csr_raw(CSR_PMPADDR0_ADDRESS + 1)
+        );
+
+        cpu.privilege_mode = PrivilegeMode::Supervisor;
+        cpu.get_mut_mmu()
+            .update_privilege_mode(PrivilegeMode::Supervisor);
+        // The lowest-numbered matching entry decides
+        cpu.update_pc(DRAM_BASE);
+        cpu.tick_operate().unwrap();
+        assert!(matches!(
+            cpu.get_mut_mmu().store_word(DRAM_BASE + 0x100, 0),
+            Err(Trap {
+                trap_type: TrapType::StoreAccessFault,
+                ..
+            })
+        ));
+        assert!(cpu.get_mut_mmu().store_word(DRAM_BASE + 0x2000, 0).is_ok());
+        assert!(matches!(
+            cpu.get_mut_mmu().fetch_word(DRAM_BASE + 0x2000),
+            Err(Trap {
+                trap_type: TrapType::InstructionAccessFault,
+                ..
+            })
+        ));
+        // An access must be in an entry entirely
+        assert!(matches!(
+            cpu.get_mut_mmu().load_doubleword(DRAM_BASE + 0x1000),
+            Err(Trap {
+                trap_type: TrapType::LoadAccessFault,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn unsupported_addressing_mode() {
+        // Sv39 is the widest by default
+        let mut cpu = create_cpu();
+        cpu.write_csr(CSR_SATP_ADDRESS, 9 << 60).unwrap();
+        assert_eq!(0, cpu.read_csr_raw(CSR_SATP_ADDRESS));
+
+        let config = MachineConfig {
+            mmu_type: Some(AddressingMode::SV48),
+            ..MachineConfig::default()
+        };
+        let mut cpu = Cpu::new_with_config(Box::new(DummyTerminal::new()), &config);
+        cpu.write_csr(CSR_SATP_ADDRESS, 8 << 60).unwrap();
+        assert_eq!(8 << 60, cpu.read_csr_raw(CSR_SATP_ADDRESS));
+        cpu.write_csr(CSR_SATP_ADDRESS, 10 << 60).unwrap();
+        assert_eq!(8 << 60, cpu.read_csr_raw(CSR_SATP_ADDRESS));
+        cpu.write_csr(CSR_SATP_ADDRESS, 9 << 60).unwrap();
+        assert_eq!(9 << 60, cpu.read_csr_raw(CSR_SATP_ADDRESS));
+    }
+
+    #[test]
+    fn access_fault() {
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(0x1000);
+        cpu.x[11] = 0x10;
+        match execute(&mut cpu, 0x00c5a223) {
+            // sw a2, 4(a1)
+            Err(Trap {
+                trap_type: TrapType::StoreAccessFault,
+                value: 0x14,
+            }) => {}
+            _ => panic!("Storing to unmapped address should fault"),
+        };
+        // Boot ROM is read only
+        cpu.x[11] = crate::config::RESET_VECTOR as i64;
+        match execute(&mut cpu, 0x00c5a223) {
+            // sw a2, 4(a1)
+            Err(Trap {
+                trap_type: TrapType::StoreAccessFault,
+                ..
+            }) => {}
+            _ => panic!("Storing to boot ROM should fault"),
+        };
+        match execute(&mut cpu, 0x0045a503) {
+            // lw a0, 4(a1)
+            Ok(()) => {}
+            _ => panic!("Loading from boot ROM should succeed"),
+        };
+    }
+
+    #[test]
+    fn csr_access() {
+        let mut cpu = create_fp_cpu();
+        cpu.x[10] = 1;
+        cpu.x[11] = -1;
+
+        // Read-only CSR can be read but not written
+        assert!(execute(&mut cpu, 0xf1402573).is_ok()); // csrr a0, mhartid
+        assert_eq!(0, cpu.x[10]);
+        assert!(is_illegal(execute(&mut cpu, 0xf1459073))); // csrw mhartid, a1
+        assert!(execute(&mut cpu, 0x14407073).is_ok()); // csrci sip, 0
+
+        // Nonexistent CSR, ustatus without N extension
+        cpu.x[10] = 1;
+        assert!(is_illegal(execute(&mut cpu, 0x00002573))); // csrr a0, ustatus
+        assert_eq!(1, cpu.x[10]);
+
+        // WARL fields keep legal values
+        assert!(execute(&mut cpu, 0x30059073).is_ok()); // csrw mstatus, a1
+        assert!(execute(&mut cpu, 0x30002573).is_ok()); // csrr a0, mstatus
+        assert_eq!(0x8000000a007e7faa_u64 as i64, cpu.x[10]);
+        assert!(execute(&mut cpu, 0x30259073).is_ok()); // csrw medeleg, a1
+        assert!(execute(&mut cpu, 0x30202573).is_ok()); // csrr a0, medeleg
+        assert_eq!(0xb3ff, cpu.x[10]);
+        assert_eq!(0x2, cpu.read_csr_raw(CSR_MISA_ADDRESS) >> 62);
+
+        // Machine mode CSRs can't be accessed from lower privilege modes
+        cpu.privilege_mode = PrivilegeMode::Supervisor;
+        assert!(is_illegal(execute(&mut cpu, 0x30002573))); // csrr a0, mstatus
+        assert!(execute(&mut cpu, 0x1005a073).is_ok()); // csrs sstatus, a1
+        cpu.privilege_mode = PrivilegeMode::User;
+        assert!(is_illegal(execute(&mut cpu, 0x1005a073))); // csrs sstatus, a1
+    }
+
+    #[test]
+    fn csr_access_32bit() {
+        let mut cpu = create_fp_cpu();
+        cpu.update_xlen(Xlen::Bit32);
+        assert_eq!(0x1, cpu.read_csr_raw(CSR_MISA_ADDRESS) >> 30);
+
+        // Upper half CSRs exist only in 32-bit mode
+        cpu.write_csr_raw(CSR_MCYCLE_ADDRESS, 0x1234567800000000);
+        assert!(execute(&mut cpu, 0xc8002573).is_ok()); // rdcycleh a0
+        assert_eq!(0x12345678, cpu.x[10]);
+        assert!(execute(&mut cpu, 0x31002573).is_ok()); // csrr a0, mstatush
+        assert_eq!(0, cpu.x[10]);
+
+        // SD is the most significant bit
+        cpu.x[11] = 0x6000;
+        assert!(execute(&mut cpu, 0x30059073).is_ok()); // csrw mstatus, a1
+        assert!(execute(&mut cpu, 0x30002573).is_ok()); // csrr a0, mstatus
+        assert_eq!(0x80006000_u32 as i32 as i64, cpu.x[10]);
+
+        cpu.update_xlen(Xlen::Bit64);
+        match execute(&mut cpu, 0x31002573) {
+            // csrr a0, mstatush
+            Err(Trap {
+                trap_type: TrapType::IllegalInstruction,
+                value: 0x31002573,
+            }) => {}
+            _ => panic!("mstatush should not exist in 64-bit mode"),
+        };
+    }
+
+    #[test]
+    fn supervisor_timer_compare() {
+        let mut cpu = create_fp_cpu();
+        cpu.x[11] = 5;
+        cpu.write_csr(CSR_MIE_ADDRESS, MIP_STIP).unwrap();
+
+        // Supervisor mode needs menvcfg.STCE and mcounteren.TM
+        cpu.privilege_mode = PrivilegeMode::Supervisor;
+        assert!(is_illegal(execute(&mut cpu, 0x14d59073))); // csrw stimecmp, a1
+        cpu.privilege_mode = PrivilegeMode::Machine;
+        cpu.write_csr(CSR_MENVCFG_ADDRESS, MENVCFG_STCE).unwrap();
+        cpu.write_csr(CSR_MCOUNTEREN_ADDRESS, COUNTEREN_TM).unwrap();
+        cpu.privilege_mode = PrivilegeMode::Supervisor;
+        assert!(execute(&mut cpu, 0x14d59073).is_ok()); // csrw stimecmp, a1
+        assert!(execute(&mut cpu, 0x14d02573).is_ok()); // csrr a0, stimecmp
+        assert_eq!(5, cpu.x[10]);
+        cpu.privilege_mode = PrivilegeMode::Machine;
+
+        // STIP is pending while time reaches stimecmp and software
+        // can't write it
+        cpu.update_supervisor_timer_interrupt();
+        assert_eq!(0, cpu.read_csr_raw(CSR_MIP_ADDRESS) & MIP_STIP);
+        cpu.write_csr(CSR_MIP_ADDRESS, MIP_STIP).unwrap();
+        assert_eq!(0, cpu.read_csr_raw(CSR_MIP_ADDRESS) & MIP_STIP);
+        cpu.get_mut_mmu()
+            .store_doubleword(0x0200bff8, 5) // mtime
+            .unwrap();
+        cpu.update_supervisor_timer_interrupt();
+        assert_eq!(MIP_STIP, cpu.read_csr_raw(CSR_MIP_ADDRESS) & MIP_STIP);
+        cpu.write_csr(CSR_STIMECMP_ADDRESS, 6).unwrap();
+        cpu.update_supervisor_timer_interrupt();
+        assert_eq!(0, cpu.read_csr_raw(CSR_MIP_ADDRESS) & MIP_STIP);
+
+        // stimecmp doesn't exist without Sstc
+        let config = MachineConfig {
+            isa: "rv64gc_svadu".to_string(),
+            ..MachineConfig::default()
+        };
+        let mut cpu = Cpu::new_with_config(Box::new(DummyTerminal::new()), &config);
+        cpu.get_mut_mmu().init_memory(4);
+        assert!(is_illegal(execute(&mut cpu, 0x14d02573))); // csrr a0, stimecmp
+        cpu.write_csr(CSR_MENVCFG_ADDRESS, MENVCFG_STCE | MENVCFG_ADUE)
+            .unwrap();
+        assert_eq!(MENVCFG_ADUE, cpu.read_csr_raw(CSR_MENVCFG_ADDRESS));
+    }
+
+    #[test]
+    fn performance_counters() {
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(0x1000);
+        for (i, word) in [
+            0x0005b503, // ld a0, 0(a1)
+            0x00a5b423, // sd a0, 8(a1)
+            0x00000073, // ecall
+            0xb0061073, // csrw mcycle, a2
+        ]
+        .iter()
+        .enumerate()
+        {
+            cpu.get_mut_mmu()
+                .store_word(DRAM_BASE + i as u64 * 4, *word)
+                .unwrap();
+        }
+        cpu.x[11] = (DRAM_BASE + 0x800) as i64;
+        cpu.x[12] = 100;
+        cpu.write_csr(CSR_MTVEC_ADDRESS, DRAM_BASE + 0xc).unwrap();
+        // Loads, stores (inhibited), traps and an unsupported event
+        for (i, event) in [4, 5, 1, 100].iter().enumerate() {
+            cpu.write_csr(CSR_MHPMEVENT3_ADDRESS + i as u16, *event)
+                .unwrap();
+        }
+        assert_eq!(0, cpu.read_csr_raw(CSR_MHPMEVENT3_ADDRESS + 3));
+        cpu.write_csr(CSR_MCOUNTINHIBIT_ADDRESS, u64::MAX).unwrap();
+        assert_eq!(0xfffffffd, cpu.read_csr_raw(CSR_MCOUNTINHIBIT_ADDRESS));
+        cpu.write_csr(CSR_MCOUNTINHIBIT_ADDRESS, 1 << 4).unwrap();
+
+        cpu.update_pc(DRAM_BASE);
+        for _ in 0..3 {
+            cpu.tick();
+        }
+        // ecall doesn't retire
+        assert_eq!(3, cpu.read_csr_raw(CSR_CYCLE_ADDRESS));
+        assert_eq!(2, cpu.read_csr_raw(CSR_MINSTRET_ADDRESS));
+        assert_eq!(1, cpu.read_csr_raw(CSR_MHPMCOUNTER3_ADDRESS));
+        assert_eq!(0, cpu.read_csr_raw(CSR_MHPMCOUNTER3_ADDRESS + 1));
+        assert_eq!(1, cpu.read_csr_raw(CSR_MHPMCOUNTER3_ADDRESS + 2));
+        // Written value isn't incremented in the same cycle
+        cpu.tick();
+        assert_eq!(100, cpu.read_csr_raw(CSR_MCYCLE_ADDRESS));
+        assert_eq!(3, cpu.read_csr_raw(CSR_MINSTRET_ADDRESS));
+
+        // Supervisor mode needs mcounteren and User mode scounteren, too
+        cpu.privilege_mode = PrivilegeMode::Supervisor;
+        assert!(is_illegal(execute(&mut cpu, 0xc0002573))); // rdcycle a0
+        cpu.csr[CSR_MCOUNTEREN_ADDRESS as usize] = 0x1;
+        assert!(execute(&mut cpu, 0xc0002573).is_ok()); // rdcycle a0
+        cpu.privilege_mode = PrivilegeMode::User;
+        assert!(is_illegal(execute(&mut cpu, 0xc0002573))); // rdcycle a0
+        cpu.csr[CSR_SCOUNTEREN_ADDRESS as usize] = 0x1;
+        assert!(execute(&mut cpu, 0xc0002573).is_ok()); // rdcycle a0
+        assert!(is_illegal(execute(&mut cpu, 0xc0102573))); // rdtime a0
+    }
+
+    #[test]
+    fn block_cache() {
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(0x2000);
+        for (i, word) in [
+            0x00150513, // addi a0, a0, 1
+            0xfff58593, // addi a1, a1, -1
+            0xfe059ce3, // bnez a1, -8
+            0x0000100f, // fence.i
+        ]
+        .iter()
+        .enumerate()
+        {
+            cpu.get_mut_mmu()
+                .store_word(DRAM_BASE + i as u64 * 4, *word)
+                .unwrap();
+        }
+        cpu.x[11] = 3;
+        cpu.update_pc(DRAM_BASE);
+        for _ in 0..9 {
+            cpu.tick();
+        }
+        assert_eq!(3, cpu.x[10]);
+        assert_eq!(DRAM_BASE + 0xc, cpu.read_pc());
+        // The loop is a block ending with the branch
+        assert_eq!(1, cpu.block_cache.blocks.len());
+        assert_eq!(Some(0xc), cpu.block_cache.get_block_size(DRAM_BASE));
+
+        // Stores to the page invalidate the blocks in it
+        cpu.get_mut_mmu()
+            .store_word(DRAM_BASE, 0x00250513) // addi a0, a0, 2
+            .unwrap();
+        cpu.x[11] = 1;
+        cpu.update_pc(DRAM_BASE);
+        for _ in 0..3 {
+            cpu.tick();
+        }
+        assert_eq!(5, cpu.x[10]);
+
+        // FENCE.I invalidates all the blocks
+        assert!(!cpu.block_cache.blocks.is_empty());
+        cpu.tick();
+        assert_eq!(DRAM_BASE + 0x10, cpu.read_pc());
+        assert!(cpu.block_cache.blocks.is_empty());
+
+        // A misaligned store invalidates the page of its last byte, too
+        cpu.get_mut_mmu()
+            .store_word(DRAM_BASE + 0x1000, 0x00150513) // addi a0, a0, 1
+            .unwrap();
+        cpu.update_pc(DRAM_BASE + 0x1000);
+        cpu.tick();
+        assert_eq!(6, cpu.x[10]);
+        cpu.get_mut_mmu()
+            .store_word_raw(DRAM_BASE + 0xffe, 0x05930000); // addi a1, a0, 1
+        cpu.update_pc(DRAM_BASE + 0x1000);
+        cpu.tick();
+        assert_eq!(6, cpu.x[10]);
+        assert_eq!(7, cpu.x[11]);
+    }
+
+    #[test]
+    fn block_cache_cross_hart_store() {
+        let config = MachineConfig {
+            hart_count: 2,
+            ..MachineConfig::default()
+        };
+        let mut cpu = Cpu::new_with_config(Box::new(DummyTerminal::new()), &config);
+        let mut hart = cpu.create_secondary_hart(1);
+        cpu.get_mut_mmu()
+            .store_word(DRAM_BASE, 0x00150513) // addi a0, a0, 1
+            .unwrap();
+        cpu.get_mut_mmu()
+            .store_word(DRAM_BASE + 4, 0x0000100f) // fence.i
+            .unwrap();
+        hart.update_pc(DRAM_BASE);
+        hart.tick();
+        assert_eq!(1, hart.x[10]);
+
+        // The store of another hart isn't observed until FENCE.I
+        cpu.get_mut_mmu()
+            .store_word(DRAM_BASE, 0x00250513) // addi a0, a0, 2
+            .unwrap();
+        hart.update_pc(DRAM_BASE);
+        hart.tick();
+        assert_eq!(2, hart.x[10]);
+        hart.tick();
+        assert_eq!(DRAM_BASE + 8, hart.read_pc());
+        hart.update_pc(DRAM_BASE);
+        hart.tick();
+        assert_eq!(4, hart.x[10]);
+    }
+
+    #[test]
+    fn block_operands() {
+        let create_base_cpu = || {
+            let mut cpu = create_cpu();
+            cpu.get_mut_mmu().init_memory(0x2000);
+            for i in 1..32 {
+                cpu.x[i] = (DRAM_BASE + 0x800 + i as u64 * 0x11) as i64;
+            }
+            cpu.update_pc(DRAM_BASE + 4);
+            cpu
+        };
+        // The operation with decoded operands acts as the one with the word
+        let mut count = 0;
+        for inst in INSTRUCTIONS.iter() {
+            if decode_operands(inst.data).is_none() {
+                continue;
+            }
+            count += 1;
+            for fields in [0, 0xa5a5a5a5, 0x5a5a5a5a, 0xffffffff] {
+                let word = inst.data | (!inst.mask & fields);
+                let (operation, operands) = decode_operands(word).unwrap();
+                let mut decoded_cpu = create_base_cpu();
+                let mut word_cpu = create_base_cpu();
+                let decoded = operation(&mut decoded_cpu, &operands, DRAM_BASE);
+                let result = (inst.operation)(&mut word_cpu, word, DRAM_BASE);
+                assert_eq!(
+                    result.map_err(|trap| trap.trap_type),
+                    decoded.map_err(|trap| trap.trap_type),
+                    "{} {:08x}",
+                    inst.name,
+                    word
+                );
+                assert_eq!(word_cpu.x, decoded_cpu.x, "{} {:08x}", inst.name, word);
+                assert_eq!(word_cpu.pc, decoded_cpu.pc, "{} {:08x}", inst.name, word);
+                for address in (DRAM_BASE..DRAM_BASE + 0x2000).step_by(4) {
+                    assert_eq!(
+                        word_cpu.get_mut_mmu().load_word_raw(address),
+                        decoded_cpu.get_mut_mmu().load_word_raw(address),
+                        "{} {:08x}",
+                        inst.name,
+                        word
+                    );
+                }
+            }
+        }
+        // RV64I base instructions but FENCE, ECALL and EBREAK
+        assert_eq!(49, count);
+    }
+
+    #[test]
+    fn table_decoder() {
+        let linear_scan = |word: u32| {
+            INSTRUCTIONS
+                .iter()
+                .position(|inst| (word & inst.mask) == inst.data)
+        };
+        // Every instruction, with its operand fields clear and set
+        for inst in INSTRUCTIONS.iter() {
+            for word in [inst.data, inst.data | !inst.mask] {
+                assert_eq!(linear_scan(word), decoder().decode(word));
+            }
+        }
+        // Arbitrary words, mostly invalid
+        let mut word = 0x12345678_u32;
+        for _ in 0..0x10000 {
+            word = word.wrapping_mul(1664525).wrapping_add(1013904223);
+            assert_eq!(linear_scan(word), decoder().decode(word));
+        }
+        assert_eq!(None, decoder().decode(0));
+        assert_eq!(None, decoder().decode(0xffffffff));
+    }
+
+    /// Executes an instruction word at the beginning of the memory.
+    fn execute(cpu: &mut Cpu, word: u32) -> Result<(), Trap> {
+        cpu.update_pc(DRAM_BASE);
+        match cpu.get_mut_mmu().store_word(DRAM_BASE, word) {
+            Ok(()) => {}
+            Err(_e) => panic!("Failed to store"),
+        };
+        cpu.tick_operate()
+    }
+
+    fn is_illegal(result: Result<(), Trap>) -> bool {
+        matches!(
+            result,
+            Err(Trap {
+                trap_type: TrapType::IllegalInstruction,
+                ..
+            })
+        )
+    }
+
+    fn create_fp_cpu() -> Cpu {
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(4);
+        cpu
+    }
+
+    #[test]
+    fn single_precision_arithmetic() {
+        let mut cpu = create_fp_cpu();
+        cpu.write_f32(11, 1.5);
+        cpu.write_f32(12, 2.25);
+        cpu.write_f32(13, 0.5);
+        execute(&mut cpu, 0x00c5f553).unwrap(); // fadd.s fa0, fa1, fa2
+        assert_eq!(0xffffffff40700000, cpu.f[10]);
+        assert_eq!(0, cpu.read_csr_raw(CSR_FFLAGS_ADDRESS));
+        // The floating-point state becomes dirty
+        assert_eq!(
+            MSTATUS_FS_DIRTY,
+            cpu.read_csr_raw(CSR_MSTATUS_ADDRESS) & MSTATUS_FS_DIRTY
+        );
+
+        execute(&mut cpu, 0x68c5f543).unwrap(); // fmadd.s fa0, fa1, fa2, fa3
+        assert_eq!(1.5 * 2.25 + 0.5, cpu.read_f32(10));
+
+        cpu.write_f32(11, 1.0);
+        cpu.write_f32(12, 3.0);
+        execute(&mut cpu, 0x18c5f553).unwrap(); // fdiv.s fa0, fa1, fa2
+        assert_eq!(1.0 / 3.0, cpu.read_f32(10));
+        assert_eq!(fpu::FLAG_NX as u64, cpu.read_csr_raw(CSR_FFLAGS_ADDRESS));
+
+        cpu.write_f32(12, 0.0);
+        execute(&mut cpu, 0x18c5f553).unwrap(); // fdiv.s fa0, fa1, fa2
+        assert_eq!(f32::INFINITY, cpu.read_f32(10));
+        assert_eq!(
+            (fpu::FLAG_NX | fpu::FLAG_DZ) as u64,
+            cpu.read_csr_raw(CSR_FFLAGS_ADDRESS)
+        );
+
+        cpu.write_f32(11, -1.0);
+        execute(&mut cpu, 0x5805f553).unwrap(); // fsqrt.s fa0, fa1
+        assert_eq!(0xffffffff7fc00000, cpu.f[10]);
+        assert_eq!(
+            fpu::FLAG_NV as u64,
+            cpu.read_csr_raw(CSR_FFLAGS_ADDRESS) & fpu::FLAG_NV as u64
+        );
 
-                    // Push the entry to front
-                    self.entries[*index].prev_index = NULL_ENTRY;
-                    self.entries[*index].next_index = self.front_index;
-                    self.entries[self.front_index].prev_index = *index;
-                    self.front_index = *index;
-                }
-                Some(self.entries[*index].instruction_index)
-            }
-            None => {
-                self.miss_count += 1;
-                None
-            }
-        };
-        //println!("Hit:{:X}, Miss:{:X}, Ratio:{}", self.hit_count, self.miss_count,
-        //	(self.hit_count as f64) / (self.hit_count + self.miss_count) as f64);
-        result
+        cpu.write_f32(12, 2.0);
+        execute(&mut cpu, 0x20c59553).unwrap(); // fsgnjn.s fa0, fa1, fa2
+        assert_eq!(-1.0, cpu.read_f32(10));
+
+        // fmin.s returns the other operand if one of them is NaN
+        cpu.write_f32_bits(11, 0x7f800001);
+        execute(&mut cpu, 0x28c58553).unwrap(); // fmin.s fa0, fa1, fa2
+        assert_eq!(2.0, cpu.read_f32(10));
     }
 
-    /// Inserts a new decode result to front of the linked list while removing
-    /// the least recently used result from the list. This operation should
-    /// compute in O(1) time.
-    ///
-    /// # Arguments
-    /// * `word`
-    /// * `instruction_index`
-    fn insert(&mut self, word: u32, instruction_index: usize) {
-        let index = self.back_index;
+    #[test]
+    fn single_precision_underflow() {
+        let mut cpu = create_fp_cpu();
+        // (1 - 2^-24) * 2^-126 rounds up to the smallest normal value but
+        // it's tiny as it has 24 bits with unbounded exponent range
+        cpu.write_f32(11, 1.0 - 2.0f32.powi(-24));
+        cpu.write_f32(12, f32::MIN_POSITIVE);
+        execute(&mut cpu, 0x10c5f553).unwrap(); // fmul.s fa0, fa1, fa2
+        assert_eq!(f32::MIN_POSITIVE, cpu.read_f32(10));
+        assert_eq!(
+            (fpu::FLAG_UF | fpu::FLAG_NX) as u64,
+            cpu.read_csr_raw(CSR_FFLAGS_ADDRESS)
+        );
 
-        // Remove the least recently used entry. The entry resource
-        // is reused as new entry.
-        if self.entries[index].instruction_index != INVALID_CACHE_ENTRY {
-            self.hash_map.remove(&self.entries[index].word);
-        }
-        self.back_index = self.entries[index].prev_index;
-        self.entries[self.back_index].next_index = NULL_ENTRY;
+        cpu.write_csr_raw(CSR_FFLAGS_ADDRESS, 0);
+        execute(&mut cpu, 0x10c59553).unwrap(); // fmul.s fa0, fa1, fa2, rtz
+        assert_eq!(f32::MIN_POSITIVE.next_down(), cpu.read_f32(10));
+        assert_eq!(
+            (fpu::FLAG_UF | fpu::FLAG_NX) as u64,
+            cpu.read_csr_raw(CSR_FFLAGS_ADDRESS)
+        );
 
-        // Push the new entry to front of the linked list
-        self.hash_map.insert(word, index);
-        self.entries[index].prev_index = NULL_ENTRY;
-        self.entries[index].next_index = self.front_index;
-        self.entries[index].word = word;
-        self.entries[index].instruction_index = instruction_index;
-        self.entries[self.front_index].prev_index = index;
-        self.front_index = index;
+        // 2^-126 - 2^-152 rounds to 2^-126 even in 24 bits, so isn't tiny
+        cpu.write_csr_raw(CSR_FFLAGS_ADDRESS, 0);
+        cpu.write_f32(11, -(2.0f32.powi(-76)));
+        cpu.write_f32(12, 2.0f32.powi(-76));
+        cpu.write_f32(13, f32::MIN_POSITIVE);
+        execute(&mut cpu, 0x68c5f543).unwrap(); // fmadd.s fa0, fa1, fa2, fa3
+        assert_eq!(f32::MIN_POSITIVE, cpu.read_f32(10));
+        assert_eq!(fpu::FLAG_NX as u64, cpu.read_csr_raw(CSR_FFLAGS_ADDRESS));
     }
-}
 
-/// An entry of linked list managed by [`DecodeCache`](struct.DecodeCache.html).
-/// An entry consists of a mapping from word instruction data to an index of
-/// [`INSTRUCTIONS`](constant.INSTRUCTIONS.html) and next/previous entry index
-/// in the linked list.
-struct DecodeCacheEntry {
-    /// Instruction word data
-    word: u32,
+    #[test]
+    fn single_precision_rounding_mode() {
+        let mut cpu = create_fp_cpu();
+        cpu.write_f32(11, 1.0);
+        cpu.write_f32(12, 2.0f32.powi(-30));
+        execute(&mut cpu, 0x00c5f553).unwrap(); // fadd.s fa0, fa1, fa2
+        assert_eq!(1.0, cpu.read_f32(10));
+
+        // Dynamic rounding mode refers to frm
+        cpu.write_csr_raw(CSR_FRM_ADDRESS, 3); // Round up
+        execute(&mut cpu, 0x00c5f553).unwrap(); // fadd.s fa0, fa1, fa2
+        assert_eq!(1.0f32.next_up(), cpu.read_f32(10));
+
+        execute(&mut cpu, 0x00c5a553).unwrap(); // fadd.s fa0, fa1, fa2, rdn
+        assert_eq!(1.0, cpu.read_f32(10));
+
+        // Reserved rounding mode
+        match execute(&mut cpu, 0x00c5d553) {
+            Err(Trap {
+                trap_type: TrapType::IllegalInstruction,
+                value: 0x00c5d553,
+            }) => {}
+            _ => panic!("Reserved rounding mode should be illegal"),
+        };
+        cpu.write_csr_raw(CSR_FRM_ADDRESS, 5);
+        assert!(execute(&mut cpu, 0x00c5f553).is_err());
+    }
 
-    /// The result of decoding `word`. An index of [`INSTRUCTIONS`](constant.INSTRUCTIONS.html).
-    instruction_index: usize,
+    #[test]
+    fn single_precision_nan_boxing() {
+        let mut cpu = create_fp_cpu();
+        // Not NaN-boxed value is regarded as the canonical NaN
+        cpu.f[11] = 0x3f800000;
+        cpu.write_f32(12, 1.0);
+        execute(&mut cpu, 0x00c5f553).unwrap(); // fadd.s fa0, fa1, fa2
+        assert_eq!(0xffffffff7fc00000, cpu.f[10]);
+
+        // fmv.x.w sign-extends the lower 32 bits
+        cpu.write_f32(11, -1.0);
+        execute(&mut cpu, 0xe0058553).unwrap(); // fmv.x.w a0, fa1
+        assert_eq!(0xffffffffbf800000, cpu.read_register(10) as u64);
+
+        cpu.write_f32(11, f32::NEG_INFINITY);
+        execute(&mut cpu, 0xe0059553).unwrap(); // fclass.s a0, fa1
+        assert_eq!(1, cpu.read_register(10));
+        cpu.f[11] = 0x3f800000;
+        execute(&mut cpu, 0xe0059553).unwrap(); // fclass.s a0, fa1
+        assert_eq!(0x200, cpu.read_register(10));
+    }
 
-    /// Next entry index in the linked list. [`NULL_ENTRY`](constant.NULL_ENTRY.html)
-    /// represents no next entry, meaning the entry is at tail.
-    next_index: usize,
+    #[test]
+    fn single_precision_conversion() {
+        let mut cpu = create_fp_cpu();
+        cpu.write_f32(11, -1.5);
+        execute(&mut cpu, 0xc0059553).unwrap(); // fcvt.w.s a0, fa1, rtz
+        assert_eq!(-1, cpu.read_register(10));
+        assert_eq!(fpu::FLAG_NX as u64, cpu.read_csr_raw(CSR_FFLAGS_ADDRESS));
+
+        execute(&mut cpu, 0xc0159553).unwrap(); // fcvt.wu.s a0, fa1, rtz
+        assert_eq!(0, cpu.read_register(10));
+        assert_eq!(
+            (fpu::FLAG_NX | fpu::FLAG_NV) as u64,
+            cpu.read_csr_raw(CSR_FFLAGS_ADDRESS)
+        );
 
-    /// Previous entry index in the linked list. [`NULL_ENTRY`](constant.NULL_ENTRY.html)
-    /// represents no previous entry, meaning the entry is at head.
-    prev_index: usize,
-}
+        cpu.write_f32_bits(11, fpu::CANONICAL_NAN_F32);
+        execute(&mut cpu, 0xc0059553).unwrap(); // fcvt.w.s a0, fa1, rtz
+        assert_eq!(i32::MAX as i64, cpu.read_register(10));
 
-impl DecodeCacheEntry {
-    /// Creates a new entry. Initial `instruction_index` is
-    /// `INVALID_CACHE_ENTRY` meaning the entry is invalid.
-    ///
-    /// # Arguments
-    /// * `next_index`
-    /// * `prev_index`
-    fn new(next_index: usize, prev_index: usize) -> Self {
-        Self {
-            word: 0,
-            instruction_index: INVALID_CACHE_ENTRY,
-            next_index,
-            prev_index,
-        }
+        cpu.x[11] = 0x1000001;
+        execute(&mut cpu, 0xd025f553).unwrap(); // fcvt.s.l fa0, a1
+        assert_eq!(16777216.0, cpu.read_f32(10));
+
+        cpu.write_f32(11, 1.0);
+        cpu.write_f32(12, 1.0);
+        execute(&mut cpu, 0xa0c5a553).unwrap(); // feq.s a0, fa1, fa2
+        assert_eq!(1, cpu.read_register(10));
     }
-}
 
-#[cfg(test)]
-mod test_cpu {
-    use super::*;
-    use crate::mmu::DRAM_BASE;
-    use crate::terminal::DummyTerminal;
+    #[test]
+    fn atomic_memory_operation() {
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(0x1000);
+        let address = DRAM_BASE + 0x100;
+        cpu.x[11] = address as i64;
+
+        cpu.get_mut_mmu()
+            .store_doubleword(address, 0x1_8000_0000)
+            .unwrap();
+        cpu.x[12] = 0x7fff_ffff;
+        execute(&mut cpu, 0x80c5a52f).unwrap(); // amomin.w a0, a2, (a1)
+                                                // Word operands are sign-extended and the upper word is untouched
+        assert_eq!(-0x8000_0000, cpu.read_register(10));
+        assert_eq!(
+            0x1_8000_0000,
+            cpu.get_mut_mmu().load_doubleword(address).unwrap()
+        );
 
-    fn create_cpu() -> Cpu {
-        Cpu::new(Box::new(DummyTerminal::new()))
+        execute(&mut cpu, 0xc0c5a52f).unwrap(); // amominu.w a0, a2, (a1)
+        assert_eq!(
+            0x1_7fff_ffff,
+            cpu.get_mut_mmu().load_doubleword(address).unwrap()
+        );
+
+        cpu.x[12] = -1;
+        execute(&mut cpu, 0x20c5a52f).unwrap(); // amoxor.w a0, a2, (a1)
+        assert_eq!(0x7fff_ffff, cpu.read_register(10));
+        assert_eq!(
+            0x1_8000_0000,
+            cpu.get_mut_mmu().load_doubleword(address).unwrap()
+        );
+
+        execute(&mut cpu, 0xa0c5b52f).unwrap(); // amomax.d a0, a2, (a1)
+        assert_eq!(0x1_8000_0000, cpu.read_register(10));
+        assert_eq!(
+            0x1_8000_0000,
+            cpu.get_mut_mmu().load_doubleword(address).unwrap()
+        );
+
+        cpu.x[11] = address as i64 + 4;
+        match execute(&mut cpu, 0xa0c5b52f) {
+            Err(Trap {
+                trap_type: TrapType::StoreAddressMisaligned,
+                value,
+            }) => assert_eq!(address + 4, value),
+            _ => panic!("Misaligned AMO should trap"),
+        };
     }
 
     #[test]
-    fn initialize() {
-        let _cpu = create_cpu();
+    fn load_reserved_store_conditional() {
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(0x1000);
+        let address = DRAM_BASE + 0x100;
+        cpu.x[11] = address as i64;
+        cpu.x[12] = 0x1234;
+        cpu.get_mut_mmu().store_doubleword(address, 5).unwrap();
+
+        execute(&mut cpu, 0x1005b52f).unwrap(); // lr.d a0, (a1)
+        assert_eq!(5, cpu.read_register(10));
+        execute(&mut cpu, 0x18c5b6af).unwrap(); // sc.d a3, a2, (a1)
+        assert_eq!(0, cpu.read_register(13));
+        assert_eq!(0x1234, cpu.get_mut_mmu().load_doubleword(address).unwrap());
+
+        // The reservation is consumed by the former SC
+        execute(&mut cpu, 0x18c5b6af).unwrap(); // sc.d a3, a2, (a1)
+        assert_eq!(1, cpu.read_register(13));
+
+        // A store to the reservation set invalidates the reservation
+        execute(&mut cpu, 0x1405a52f).unwrap(); // lr.w.aq a0, (a1)
+        execute(&mut cpu, 0x00c5a223).unwrap(); // sw a2, 4(a1)
+        execute(&mut cpu, 0x1ac5a6af).unwrap(); // sc.w.rl a3, a2, (a1)
+        assert_eq!(1, cpu.read_register(13));
+
+        cpu.x[11] = address as i64 + 2;
+        match execute(&mut cpu, 0x1405a52f) {
+            Err(Trap {
+                trap_type: TrapType::LoadAddressMisaligned,
+                ..
+            }) => {}
+            _ => panic!("Misaligned LR should trap"),
+        };
+    }
+
+    /// Runs instructions writing `a0` from `a1` = `rs1` and `a2` = `rs2`
+    /// and checks `a0` is the expected value.
+    ///
+    /// # Arguments
+    /// * `cpu`
+    /// * `rs1`
+    /// * `rs2`
+    /// * `cases` Instruction word and the expected value
+    fn check_bit_manipulation(cpu: &mut Cpu, rs1: u64, rs2: u64, cases: &[(u32, u64)]) {
+        for (word, expected) in cases {
+            cpu.x[11] = rs1 as i64;
+            cpu.x[12] = rs2 as i64;
+            execute(cpu, *word).unwrap();
+            assert_eq!(*expected as i64, cpu.read_register(10), "{:08x}", word);
+        }
     }
 
     #[test]
-    fn update_pc() {
+    fn bit_manipulation() {
         let mut cpu = create_cpu();
-        assert_eq!(0, cpu.read_pc());
-        cpu.update_pc(1);
-        assert_eq!(1, cpu.read_pc());
-        cpu.update_pc(0xffffffffffffffff);
-        assert_eq!(0xffffffffffffffff, cpu.read_pc());
+        cpu.get_mut_mmu().init_memory(4);
+        // Zba, Zbb and Zbs are advertised as B
+        assert_eq!(0x2, cpu.read_csr_raw(CSR_MISA_ADDRESS) & 0x2);
+        check_bit_manipulation(
+            &mut cpu,
+            0x8123456789ab00ef,
+            0xa7,
+            &[
+                (0x20c5a533, 0x2468acf13560285),  // sh1add a0, a1, a2
+                (0x20c5c533, 0x48d159e26ac0463),  // sh2add a0, a1, a2
+                (0x20c5e533, 0x91a2b3c4d58081f),  // sh3add a0, a1, a2
+                (0x08c5853b, 0x89ab0196),         // add.uw a0, a1, a2
+                (0x20c5a53b, 0x113560285),        // sh1add.uw a0, a1, a2
+                (0x20c5c53b, 0x226ac0463),        // sh2add.uw a0, a1, a2
+                (0x20c5e53b, 0x44d58081f),        // sh3add.uw a0, a1, a2
+                (0x0855951b, 0x1135601de0),       // slli.uw a0, a1, 5
+                (0x40c5f533, 0x8123456789ab0048), // andn a0, a1, a2
+                (0x40c5e533, 0xffffffffffffffff), // orn a0, a1, a2
+                (0x40c5c533, 0x7edcba987654ffb7), // xnor a0, a1, a2
+                (0x60059513, 0x0),                // clz a0, a1
+                (0x60159513, 0x0),                // ctz a0, a1
+                (0x60259513, 0x1c),               // cpop a0, a1
+                (0x6005951b, 0x0),                // clzw a0, a1
+                (0x6015951b, 0x0),                // ctzw a0, a1
+                (0x6025951b, 0xf),                // cpopw a0, a1
+                (0x0ac5e533, 0xa7),               // max a0, a1, a2
+                (0x0ac5f533, 0x8123456789ab00ef), // maxu a0, a1, a2
+                (0x0ac5c533, 0x8123456789ab00ef), // min a0, a1, a2
+                (0x0ac5d533, 0xa7),               // minu a0, a1, a2
+                (0x60459513, 0xffffffffffffffef), // sext.b a0, a1
+                (0x60559513, 0xef),               // sext.h a0, a1
+                (0x0805c53b, 0xef),               // zext.h a0, a1
+                (0x60c59533, 0xd58077c091a2b3c4), // rol a0, a1, a2
+                (0x60c5d533, 0xcf135601df02468a), // ror a0, a1, a2
+                (0x60c5953b, 0xffffffffd58077c4), // rolw a0, a1, a2
+                (0x60c5d53b, 0xffffffffdf135601), // rorw a0, a1, a2
+                (0x6055d513, 0x7c091a2b3c4d5807), // rori a0, a1, 5
+                (0x6055d51b, 0x7c4d5807),         // roriw a0, a1, 5
+                (0x2875d513, 0xffffffffffff00ff), // orc.b a0, a1
+                (0x6b85d513, 0xef00ab8967452381), // rev8 a0, a1
+                (0x0ac59533, 0x3222c5035cb168ed), // clmul a0, a1, a2
+                (0x0ac5b533, 0x53),               // clmulh a0, a1, a2
+                (0x0ac5a533, 0xa6),               // clmulr a0, a1, a2
+                (0x48c59533, 0x8123456789ab00ef), // bclr a0, a1, a2
+                (0x48559513, 0x8123456789ab00cf), // bclri a0, a1, 5
+                (0x48c5d533, 0x0),                // bext a0, a1, a2
+                (0x4855d513, 0x1),                // bexti a0, a1, 5
+                (0x68c59533, 0x812345e789ab00ef), // binv a0, a1, a2
+                (0x68559513, 0x8123456789ab00cf), // binvi a0, a1, 5
+                (0x28c59533, 0x812345e789ab00ef), // bset a0, a1, a2
+                (0x28559513, 0x8123456789ab00ef), // bseti a0, a1, 5
+            ],
+        );
+        check_bit_manipulation(
+            &mut cpu,
+            0x18000,
+            0,
+            &[
+                (0x60059513, 47), // clz a0, a1
+                (0x60159513, 15), // ctz a0, a1
+                (0x60259513, 2),  // cpop a0, a1
+                (0x6005951b, 15), // clzw a0, a1
+                (0x6015951b, 15), // ctzw a0, a1
+            ],
+        );
+        check_bit_manipulation(
+            &mut cpu,
+            0,
+            0,
+            &[
+                (0x60059513, 64), // clz a0, a1
+                (0x60159513, 64), // ctz a0, a1
+                (0x6005951b, 32), // clzw a0, a1
+                (0x6015951b, 32), // ctzw a0, a1
+            ],
+        );
+        // Encodings for 32-bit mode are illegal
+        assert!(is_illegal(execute(&mut cpu, 0x6985d513))); // rev8 a0, a1
+        assert!(is_illegal(execute(&mut cpu, 0x0805c533))); // zext.h a0, a1
     }
 
     #[test]
-    fn update_xlen() {
+    fn bit_manipulation_32bit() {
         let mut cpu = create_cpu();
-        assert!(matches!(cpu.xlen, Xlen::Bit64));
+        cpu.get_mut_mmu().init_memory(4);
         cpu.update_xlen(Xlen::Bit32);
-        assert!(matches!(cpu.xlen, Xlen::Bit32));
-        cpu.update_xlen(Xlen::Bit64);
-        assert!(matches!(cpu.xlen, Xlen::Bit64));
-        // Note: cpu.update_xlen() updates cpu.mmu.xlen, too.
-        // TODO: The test for mmu.xlen should be in Mmu?
+        check_bit_manipulation(
+            &mut cpu,
+            0xffffffff8900cdef,
+            0x13,
+            &[
+                (0x20c5a533, 0x12019bf1),         // sh1add a0, a1, a2
+                (0x20c5c533, 0x240337cf),         // sh2add a0, a1, a2
+                (0x20c5e533, 0x48066f8b),         // sh3add a0, a1, a2
+                (0x40c5f533, 0xffffffff8900cdec), // andn a0, a1, a2
+                (0x40c5e533, 0xffffffffffffffef), // orn a0, a1, a2
+                (0x40c5c533, 0x76ff3203),         // xnor a0, a1, a2
+                (0x60059513, 0x0),                // clz a0, a1
+                (0x60159513, 0x0),                // ctz a0, a1
+                (0x60259513, 0xf),                // cpop a0, a1
+                (0x0ac5e533, 0x13),               // max a0, a1, a2
+                (0x0ac5f533, 0xffffffff8900cdef), // maxu a0, a1, a2
+                (0x0ac5c533, 0xffffffff8900cdef), // min a0, a1, a2
+                (0x0ac5d533, 0x13),               // minu a0, a1, a2
+                (0x60459513, 0xffffffffffffffef), // sext.b a0, a1
+                (0x60559513, 0xffffffffffffcdef), // sext.h a0, a1
+                (0x0805c533, 0xcdef),             // zext.h a0, a1
+                (0x60c59533, 0x6f7c4806),         // rol a0, a1, a2
+                (0x60c5d533, 0x19bdf120),         // ror a0, a1, a2
+                (0x6055d513, 0x7c48066f),         // rori a0, a1, 5
+                (0x2875d513, 0xffffffffff00ffff), // orc.b a0, a1
+                (0x6985d513, 0xffffffffefcd0089), // rev8 a0, a1
+                (0x0ac59533, 0xb0d88c1),          // clmul a0, a1, a2
+                (0x0ac5b533, 0x9),                // clmulh a0, a1, a2
+                (0x0ac5a533, 0x12),               // clmulr a0, a1, a2
+                (0x48c59533, 0xffffffff8900cdef), // bclr a0, a1, a2
+                (0x48559513, 0xffffffff8900cdcf), // bclri a0, a1, 5
+                (0x48c5d533, 0x0),                // bext a0, a1, a2
+                (0x4855d513, 0x1),                // bexti a0, a1, 5
+                (0x68c59533, 0xffffffff8908cdef), // binv a0, a1, a2
+                (0x68559513, 0xffffffff8900cdcf), // binvi a0, a1, 5
+                (0x28c59533, 0xffffffff8908cdef), // bset a0, a1, a2
+                (0x28559513, 0xffffffff8900cdef), // bseti a0, a1, 5
+            ],
+        );
+        check_bit_manipulation(
+            &mut cpu,
+            0x18000,
+            0,
+            &[
+                (0x60059513, 15), // clz a0, a1
+                (0x60159513, 15), // ctz a0, a1
+                (0x60259513, 2),  // cpop a0, a1
+            ],
+        );
+        check_bit_manipulation(
+            &mut cpu,
+            0,
+            0,
+            &[
+                (0x60059513, 32), // clz a0, a1
+                (0x60159513, 32), // ctz a0, a1
+            ],
+        );
+        // 64-bit only instructions are illegal
+        for word in [
+            0x08c5853b, // add.uw a0, a1, a2
+            0x0855951b, // slli.uw a0, a1, 5
+            0x6005951b, // clzw a0, a1
+            0x60c5953b, // rolw a0, a1, a2
+            0x6055d51b, // roriw a0, a1, 5
+            0x6b85d513, // rev8 a0, a1
+            0x0805c53b, // zext.h a0, a1
+        ] {
+            assert!(is_illegal(execute(&mut cpu, word)));
+        }
     }
 
     #[test]
-    fn read_register() {
+    fn bit_manipulation_disassemble() {
         let mut cpu = create_cpu();
-        // Initial register values are 0 other than 0xb th register.
-        // Initial value of 0xb th register is temporal for Linux boot and
-        // I'm not sure if the value is correct. Then skipping so far.
-        for i in 0..31 {
-            if i != 0xb {
-                assert_eq!(0, cpu.read_register(i));
-            }
+        cpu.get_mut_mmu().init_memory(4);
+        cpu.update_pc(DRAM_BASE);
+        for (word, expected) in [
+            (0x20c5a533, "SH1ADD a0:0,a1:0,a2:0"),
+            (0x60059513, "CLZ a0:0,a1:0"),
+            (0x6055d513, "RORI a0:0,a1:0,5"),
+            (0x0855951b, "SLLI.UW a0:0,a1:0,5"),
+        ] {
+            cpu.get_mut_mmu().store_word(DRAM_BASE, word).unwrap();
+            assert_eq!(
+                format!("PC:0000000080000000 {:08x} {}", word, expected),
+                cpu.disassemble_next_instruction()
+            );
         }
+    }
 
-        for i in 0..31 {
-            cpu.x[i] = i as i64 + 1;
-        }
+    #[test]
+    fn double_precision_arithmetic() {
+        let mut cpu = create_fp_cpu();
+        cpu.write_f64(11, 1.0);
+        cpu.write_f64(12, 2.0f64.powi(-60));
+        execute(&mut cpu, 0x02c5f553).unwrap(); // fadd.d fa0, fa1, fa2
+        assert_eq!(1.0, cpu.read_f64(10));
+        assert_eq!(fpu::FLAG_NX as u64, cpu.read_csr_raw(CSR_FFLAGS_ADDRESS));
+        execute(&mut cpu, 0x02c5b553).unwrap(); // fadd.d fa0, fa1, fa2, rup
+        assert_eq!(1.0f64.next_up(), cpu.read_f64(10));
+
+        // -(1 + 2^-52) * (1 + 2^-52) = -(1 + 2^-51 + 2^-104)
+        cpu.write_f64(11, -(1.0f64.next_up()));
+        cpu.write_f64(12, 1.0f64.next_up());
+        execute(&mut cpu, 0x12c5a553).unwrap(); // fmul.d fa0, fa1, fa2, rdn
+        assert_eq!((-(1.0 + 2.0f64.powi(-51))).next_down(), cpu.read_f64(10));
+
+        // The fused operation doesn't round the product
+        cpu.write_f64(11, 1.0f64.next_up());
+        cpu.write_f64(13, 1.0 + 2.0f64.powi(-51));
+        execute(&mut cpu, 0x6ac5f547).unwrap(); // fmsub.d fa0, fa1, fa2, fa3
+        assert_eq!(2.0f64.powi(-104), cpu.read_f64(10));
+
+        cpu.write_f64(11, 2.0);
+        cpu.write_f64(12, 3.0);
+        cpu.write_f64(13, 1.0);
+        execute(&mut cpu, 0x6ac5f54f).unwrap(); // fnmadd.d fa0, fa1, fa2, fa3
+        assert_eq!(-7.0, cpu.read_f64(10));
+
+        execute(&mut cpu, 0x5a05f553).unwrap(); // fsqrt.d fa0, fa1
+        assert_eq!(2.0f64.sqrt(), cpu.read_f64(10));
+
+        cpu.write_csr_raw(CSR_FFLAGS_ADDRESS, 0);
+        cpu.write_f64(11, -1.0);
+        cpu.write_f64(12, 0.0);
+        execute(&mut cpu, 0x1ac5f553).unwrap(); // fdiv.d fa0, fa1, fa2
+        assert_eq!(f64::NEG_INFINITY, cpu.read_f64(10));
+        assert_eq!(fpu::FLAG_DZ as u64, cpu.read_csr_raw(CSR_FFLAGS_ADDRESS));
+
+        cpu.write_f64(11, f64::MAX);
+        cpu.write_f64(12, 0.5);
+        execute(&mut cpu, 0x1ac5f553).unwrap(); // fdiv.d fa0, fa1, fa2
+        assert_eq!(f64::INFINITY, cpu.read_f64(10));
+        assert_eq!(
+            (fpu::FLAG_DZ | fpu::FLAG_OF | fpu::FLAG_NX) as u64,
+            cpu.read_csr_raw(CSR_FFLAGS_ADDRESS)
+        );
 
-        for i in 0..31 {
-            match i {
-                // 0th register is hardwired zero
-                0 => assert_eq!(0, cpu.read_register(i)),
-                _ => assert_eq!(i as i64 + 1, cpu.read_register(i)),
+        cpu.write_f64(11, -0.0);
+        cpu.write_f64(12, 0.0);
+        execute(&mut cpu, 0x2ac59553).unwrap(); // fmax.d fa0, fa1, fa2
+        assert_eq!(0, cpu.f[10]);
+        execute(&mut cpu, 0x22c59553).unwrap(); // fsgnjn.d fa0, fa1, fa2
+        assert_eq!(0x8000000000000000, cpu.f[10]);
+
+        cpu.write_f64(11, f64::MIN_POSITIVE / 2.0);
+        execute(&mut cpu, 0xe2059553).unwrap(); // fclass.d a0, fa1
+        assert_eq!(0x20, cpu.read_register(10));
+    }
+
+    #[test]
+    fn double_precision_conversion() {
+        let mut cpu = create_fp_cpu();
+        cpu.write_f64(11, -1.5);
+        execute(&mut cpu, 0xc2359553).unwrap(); // fcvt.lu.d a0, fa1, rtz
+        assert_eq!(0, cpu.read_register(10));
+        assert_eq!(fpu::FLAG_NV as u64, cpu.read_csr_raw(CSR_FFLAGS_ADDRESS));
+
+        cpu.write_f64(11, 2.0f64.powi(63));
+        execute(&mut cpu, 0xc225f553).unwrap(); // fcvt.l.d a0, fa1
+        assert_eq!(i64::MAX, cpu.read_register(10));
+
+        cpu.write_csr_raw(CSR_FFLAGS_ADDRESS, 0);
+        cpu.x[11] = -1;
+        execute(&mut cpu, 0xd235f553).unwrap(); // fcvt.d.lu fa0, a1
+        assert_eq!(2.0f64.powi(64), cpu.read_f64(10));
+        assert_eq!(fpu::FLAG_NX as u64, cpu.read_csr_raw(CSR_FFLAGS_ADDRESS));
+
+        // The single precision result is NaN-boxed
+        cpu.write_f64(11, 1.0 + 2.0f64.powi(-30));
+        execute(&mut cpu, 0x4015f553).unwrap(); // fcvt.s.d fa0, fa1
+        assert_eq!(0xffffffff3f800000, cpu.f[10]);
+
+        cpu.write_csr_raw(CSR_FFLAGS_ADDRESS, 0);
+        cpu.write_f64(11, 2.0f64.powi(128));
+        execute(&mut cpu, 0x40159553).unwrap(); // fcvt.s.d fa0, fa1, rtz
+        assert_eq!(f32::MAX, cpu.read_f32(10));
+        assert_eq!(
+            (fpu::FLAG_OF | fpu::FLAG_NX) as u64,
+            cpu.read_csr_raw(CSR_FFLAGS_ADDRESS)
+        );
+
+        // Signaling NaN is converted to the canonical NaN
+        cpu.write_csr_raw(CSR_FFLAGS_ADDRESS, 0);
+        cpu.write_f32_bits(11, 0x7f800001);
+        execute(&mut cpu, 0x42058553).unwrap(); // fcvt.d.s fa0, fa1
+        assert_eq!(fpu::CANONICAL_NAN_F64, cpu.f[10]);
+        assert_eq!(fpu::FLAG_NV as u64, cpu.read_csr_raw(CSR_FFLAGS_ADDRESS));
+    }
+
+    /// Runs double precision instructions reading `fa1`, `fa2` and `fa3`
+    /// and checks the result and the accrued exception flags, like
+    /// the rv64ud cases of riscv-tests. Comparisons, classification and
+    /// conversions to integer write `a0`, the others write `fa0`.
+    ///
+    /// # Arguments
+    /// * `cpu`
+    /// * `cases` Instruction word, operands, the expected result bits and
+    ///   the expected flags
+    fn check_double_precision(cpu: &mut Cpu, cases: &[(u32, [f64; 3], u64, u8)]) {
+        for (word, operands, expected, flags) in cases {
+            for (index, operand) in operands.iter().enumerate() {
+                cpu.write_f64(11 + index, *operand);
             }
+            cpu.write_csr_raw(CSR_FFLAGS_ADDRESS, 0);
+            execute(cpu, *word).unwrap();
+            let result = match word >> 25 {
+                0x51 | 0x61 | 0x71 => cpu.read_register(10) as u64,
+                _ => cpu.f[10],
+            };
+            assert_eq!(*expected, result, "{:08x} {:?}", word, operands);
+            assert_eq!(
+                *flags as u64,
+                cpu.read_csr_raw(CSR_FFLAGS_ADDRESS),
+                "{:08x} {:?}",
+                word,
+                operands
+            );
         }
+    }
 
-        for i in 0..31 {
-            cpu.x[i] = (0xffffffffffffffff - i) as i64;
-        }
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn double_precision_fadd() {
+        let mut cpu = create_fp_cpu();
+        let bits = f64::to_bits;
+        let (nx, nv) = (fpu::FLAG_NX, fpu::FLAG_NV);
+        let inf = f64::INFINITY;
+        check_double_precision(
+            &mut cpu,
+            &[
+                (0x02c5f553, [2.5, 1.0, 0.0], bits(3.5), 0), // fadd.d fa0, fa1, fa2
+                (0x02c5f553, [-1235.1, 1.1, 0.0], bits(-1234.0), nx),
+                (
+                    0x02c5f553,
+                    [3.14159265, 0.00000001, 0.0],
+                    bits(3.14159266),
+                    nx,
+                ),
+                (0x0ac5f553, [2.5, 1.0, 0.0], bits(1.5), 0), // fsub.d fa0, fa1, fa2
+                (0x0ac5f553, [-1235.1, -1.1, 0.0], bits(-1234.0), nx),
+                (
+                    0x0ac5f553,
+                    [3.14159265, 0.00000001, 0.0],
+                    bits(3.1415926400000003),
+                    nx,
+                ),
+                (0x12c5f553, [2.5, 1.0, 0.0], bits(2.5), 0), // fmul.d fa0, fa1, fa2
+                (0x12c5f553, [-1235.1, -1.1, 0.0], bits(1358.61), nx),
+                (
+                    0x12c5f553,
+                    [3.14159265, 0.00000001, 0.0],
+                    bits(3.14159265e-8),
+                    nx,
+                ),
+                (0x0ac5f553, [inf, inf, 0.0], fpu::CANONICAL_NAN_F64, nv),
+                (0x02c5f553, [inf, -inf, 0.0], fpu::CANONICAL_NAN_F64, nv),
+            ],
+        );
+    }
 
-        for i in 0..31 {
-            match i {
-                // 0th register is hardwired zero
-                0 => assert_eq!(0, cpu.read_register(i)),
-                _ => assert_eq!(-(i as i64 + 1), cpu.read_register(i)),
-            }
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn double_precision_fdiv() {
+        let mut cpu = create_fp_cpu();
+        let bits = f64::to_bits;
+        let (nx, nv) = (fpu::FLAG_NX, fpu::FLAG_NV);
+        check_double_precision(
+            &mut cpu,
+            &[
+                // fdiv.d fa0, fa1, fa2
+                (
+                    0x1ac5f553,
+                    [3.14159265, 2.71828182, 0.0],
+                    bits(1.1557273520668288),
+                    nx,
+                ),
+                (
+                    0x1ac5f553,
+                    [-1234.0, 1235.1, 0.0],
+                    bits(-0.9991093838555584),
+                    nx,
+                ),
+                (0x1ac5f553, [3.14159265, 1.0, 0.0], bits(3.14159265), 0),
+                // fsqrt.d fa0, fa1
+                (
+                    0x5a05f553,
+                    [3.14159265, 0.0, 0.0],
+                    bits(1.7724538498928541),
+                    nx,
+                ),
+                (0x5a05f553, [10000.0, 0.0, 0.0], bits(100.0), 0),
+                (0x5a05f553, [-1.0, 0.0, 0.0], fpu::CANONICAL_NAN_F64, nv),
+                (0x5a05f553, [171.0, 0.0, 0.0], bits(13.076696830622021), nx),
+                (
+                    0x5a05f553,
+                    [1.60795e-7, 0.0, 0.0],
+                    bits(4.0099251863345283e-4),
+                    nx,
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn double_precision_fcmp() {
+        let mut cpu = create_fp_cpu();
+        let nv = fpu::FLAG_NV;
+        let (qnan, snan) = (f64::NAN, f64::from_bits(0x7ff0000000000001));
+        check_double_precision(
+            &mut cpu,
+            &[
+                (0xa2c5a553, [-1.36, -1.36, 0.0], 1, 0), // feq.d a0, fa1, fa2
+                (0xa2c58553, [-1.36, -1.36, 0.0], 1, 0), // fle.d a0, fa1, fa2
+                (0xa2c59553, [-1.36, -1.36, 0.0], 0, 0), // flt.d a0, fa1, fa2
+                (0xa2c5a553, [-1.37, -1.36, 0.0], 0, 0),
+                (0xa2c58553, [-1.37, -1.36, 0.0], 1, 0),
+                (0xa2c59553, [-1.37, -1.36, 0.0], 1, 0),
+                (0xa2c5a553, [-0.0, 0.0, 0.0], 1, 0),
+                // feq.d is a quiet comparison, flt.d and fle.d are signaling
+                (0xa2c5a553, [qnan, 0.0, 0.0], 0, 0),
+                (0xa2c5a553, [qnan, qnan, 0.0], 0, 0),
+                (0xa2c5a553, [snan, 0.0, 0.0], 0, nv),
+                (0xa2c59553, [qnan, 0.0, 0.0], 0, nv),
+                (0xa2c59553, [qnan, qnan, 0.0], 0, nv),
+                (0xa2c59553, [snan, 0.0, 0.0], 0, nv),
+                (0xa2c58553, [qnan, 0.0, 0.0], 0, nv),
+                (0xa2c58553, [qnan, qnan, 0.0], 0, nv),
+                (0xa2c58553, [snan, 0.0, 0.0], 0, nv),
+            ],
+        );
+    }
+
+    #[test]
+    fn double_precision_fcvt() {
+        let mut cpu = create_fp_cpu();
+        for (word, operand, expected, flags) in [
+            // fcvt.d.w fa0, a1 and fcvt.d.wu fa0, a1
+            (0xd2058553, 2u64, 2.0, 0),
+            (0xd2058553, 0xfffffffffffffffe, -2.0, 0),
+            (0xd2158553, 2, 2.0, 0),
+            (0xd2158553, 0xfffffffffffffffe, 4294967294.0, 0),
+            // fcvt.d.l fa0, a1 and fcvt.d.lu fa0, a1
+            (0xd225f553, 2, 2.0, 0),
+            (0xd225f553, 0xfffffffffffffffe, -2.0, 0),
+            (0xd235f553, 2, 2.0, 0),
+            (
+                0xd235f553,
+                0xfffffffffffffffe,
+                1.8446744073709552e19,
+                fpu::FLAG_NX,
+            ),
+        ] {
+            cpu.x[11] = operand as i64;
+            cpu.write_csr_raw(CSR_FFLAGS_ADDRESS, 0);
+            execute(&mut cpu, word).unwrap();
+            assert_eq!(expected, cpu.read_f64(10), "{:08x}", word);
+            assert_eq!(flags as u64, cpu.read_csr_raw(CSR_FFLAGS_ADDRESS));
         }
 
-        // @TODO: Should I test the case where the argument equals to or is
-        // greater than 32?
+        check_double_precision(
+            &mut cpu,
+            &[
+                // fcvt.s.d fa0, fa1
+                (0x4015f553, [-1.5, 0.0, 0.0], 0xffffffffbfc00000, 0),
+                (
+                    0x4015f553,
+                    [1.1, 0.0, 0.0],
+                    0xffffffff3f8ccccd,
+                    fpu::FLAG_NX,
+                ),
+            ],
+        );
+
+        // fcvt.d.s reads a NaN-boxed single precision value
+        cpu.write_f32(11, -1.5);
+        execute(&mut cpu, 0x42058553).unwrap(); // fcvt.d.s fa0, fa1
+        assert_eq!(-1.5, cpu.read_f64(10));
+        cpu.f[11] = 0xbfc00000;
+        execute(&mut cpu, 0x42058553).unwrap(); // fcvt.d.s fa0, fa1
+        assert_eq!(fpu::CANONICAL_NAN_F64, cpu.f[10]);
     }
 
     #[test]
-    fn tick() {
-        let mut cpu = create_cpu();
-        cpu.get_mut_mmu().init_memory(4);
-        cpu.update_pc(DRAM_BASE);
+    fn double_precision_fcvt_w() {
+        let mut cpu = create_fp_cpu();
+        let (nx, nv) = (fpu::FLAG_NX, fpu::FLAG_NV);
+        let (nan, inf) = (f64::NAN, f64::INFINITY);
+        check_double_precision(
+            &mut cpu,
+            &[
+                (0xc2059553, [-1.1, 0.0, 0.0], 0xffffffffffffffff, nx), // fcvt.w.d a0, fa1, rtz
+                (0xc2059553, [-1.0, 0.0, 0.0], 0xffffffffffffffff, 0),
+                (0xc2059553, [-0.9, 0.0, 0.0], 0, nx),
+                (0xc2059553, [0.9, 0.0, 0.0], 0, nx),
+                (0xc2059553, [1.0, 0.0, 0.0], 1, 0),
+                (0xc2059553, [1.1, 0.0, 0.0], 1, nx),
+                (0xc2059553, [-3e9, 0.0, 0.0], 0xffffffff80000000, nv),
+                (0xc2059553, [3e9, 0.0, 0.0], 0x7fffffff, nv),
+                (0xc2059553, [nan, 0.0, 0.0], 0x7fffffff, nv),
+                (0xc2059553, [-inf, 0.0, 0.0], 0xffffffff80000000, nv),
+                (0xc2159553, [-3.0, 0.0, 0.0], 0, nv), // fcvt.wu.d a0, fa1, rtz
+                (0xc2159553, [-1.0, 0.0, 0.0], 0, nv),
+                (0xc2159553, [-0.9, 0.0, 0.0], 0, nx),
+                (0xc2159553, [0.9, 0.0, 0.0], 0, nx),
+                (0xc2159553, [1.0, 0.0, 0.0], 1, 0),
+                (0xc2159553, [1.1, 0.0, 0.0], 1, nx),
+                (0xc2159553, [-3e9, 0.0, 0.0], 0, nv),
+                // The 32-bit result is sign-extended
+                (0xc2159553, [3e9, 0.0, 0.0], 0xffffffffb2d05e00, 0),
+                (0xc2159553, [nan, 0.0, 0.0], 0xffffffffffffffff, nv),
+                (0xc2159553, [inf, 0.0, 0.0], 0xffffffffffffffff, nv),
+                (0xc2259553, [-3e9, 0.0, 0.0], 0xffffffff4d2fa200, 0), // fcvt.l.d a0, fa1, rtz
+                (0xc2259553, [3e19, 0.0, 0.0], 0x7fffffffffffffff, nv),
+                (0xc2259553, [-3e19, 0.0, 0.0], 0x8000000000000000, nv),
+                (0xc2259553, [nan, 0.0, 0.0], 0x7fffffffffffffff, nv),
+                (0xc2359553, [-3.0, 0.0, 0.0], 0, nv), // fcvt.lu.d a0, fa1, rtz
+                (0xc2359553, [-0.9, 0.0, 0.0], 0, nx),
+                (0xc2359553, [3e9, 0.0, 0.0], 3000000000, 0),
+                (0xc2359553, [1.8e19, 0.0, 0.0], 1.8e19 as u64, 0),
+                (0xc2359553, [-3e9, 0.0, 0.0], 0, nv),
+                (0xc2359553, [3e19, 0.0, 0.0], 0xffffffffffffffff, nv),
+                (0xc2359553, [nan, 0.0, 0.0], 0xffffffffffffffff, nv),
+            ],
+        );
+    }
 
-        // Write non-compressed "addi x1, x1, 1" instruction
-        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00108093) {
-            Ok(()) => {}
-            Err(_e) => panic!("Failed to store"),
-        };
-        // Write compressed "addi x8, x0, 8" instruction
-        match cpu.get_mut_mmu().store_word(DRAM_BASE + 4, 0x20) {
-            Ok(()) => {}
-            Err(_e) => panic!("Failed to store"),
-        };
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn double_precision_fmin() {
+        let mut cpu = create_fp_cpu();
+        let bits = f64::to_bits;
+        let (qnan, snan) = (f64::NAN, f64::from_bits(0x7ff0000000000001));
+        check_double_precision(
+            &mut cpu,
+            &[
+                (0x2ac58553, [2.5, 1.0, 0.0], bits(1.0), 0), // fmin.d fa0, fa1, fa2
+                (0x2ac58553, [-1235.1, 1.1, 0.0], bits(-1235.1), 0),
+                (0x2ac58553, [1.1, -1235.1, 0.0], bits(-1235.1), 0),
+                (0x2ac58553, [qnan, -1235.1, 0.0], bits(-1235.1), 0),
+                (
+                    0x2ac58553,
+                    [3.14159265, 0.00000001, 0.0],
+                    bits(0.00000001),
+                    0,
+                ),
+                (0x2ac58553, [-1.0, -2.0, 0.0], bits(-2.0), 0),
+                (0x2ac59553, [2.5, 1.0, 0.0], bits(2.5), 0), // fmax.d fa0, fa1, fa2
+                (0x2ac59553, [-1235.1, 1.1, 0.0], bits(1.1), 0),
+                (0x2ac59553, [1.1, -1235.1, 0.0], bits(1.1), 0),
+                (0x2ac59553, [qnan, -1235.1, 0.0], bits(-1235.1), 0),
+                (
+                    0x2ac59553,
+                    [3.14159265, 0.00000001, 0.0],
+                    bits(3.14159265),
+                    0,
+                ),
+                (0x2ac59553, [-1.0, -2.0, 0.0], bits(-1.0), 0),
+                // Signaling NaN raises invalid operation, two NaNs give the canonical NaN
+                (0x2ac59553, [snan, 1.0, 0.0], bits(1.0), fpu::FLAG_NV),
+                (0x2ac59553, [qnan, qnan, 0.0], fpu::CANONICAL_NAN_F64, 0),
+                (
+                    0x2ac58553,
+                    [snan, snan, 0.0],
+                    fpu::CANONICAL_NAN_F64,
+                    fpu::FLAG_NV,
+                ),
+                // -0.0 is less than 0.0
+                (0x2ac58553, [-0.0, 0.0, 0.0], bits(-0.0), 0),
+                (0x2ac58553, [0.0, -0.0, 0.0], bits(-0.0), 0),
+                (0x2ac59553, [-0.0, 0.0, 0.0], bits(0.0), 0),
+                (0x2ac59553, [0.0, -0.0, 0.0], bits(0.0), 0),
+            ],
+        );
+    }
 
-        cpu.tick();
+    #[test]
+    fn double_precision_fmadd() {
+        let mut cpu = create_fp_cpu();
+        let bits = f64::to_bits;
+        let nx = fpu::FLAG_NX;
+        check_double_precision(
+            &mut cpu,
+            &[
+                (0x6ac5f543, [1.0, 2.5, 1.0], bits(3.5), 0), // fmadd.d fa0, fa1, fa2, fa3
+                (
+                    0x6ac5f543,
+                    [-1.0, -1235.1, 1.1],
+                    bits(1236.1999999999998),
+                    nx,
+                ),
+                (0x6ac5f543, [2.0, -5.0, -2.0], bits(-12.0), 0),
+                (0x6ac5f54f, [1.0, 2.5, 1.0], bits(-3.5), 0), // fnmadd.d fa0, fa1, fa2, fa3
+                (
+                    0x6ac5f54f,
+                    [-1.0, -1235.1, 1.1],
+                    bits(-1236.1999999999998),
+                    nx,
+                ),
+                (0x6ac5f54f, [2.0, -5.0, -2.0], bits(12.0), 0),
+                (0x6ac5f547, [1.0, 2.5, 1.0], bits(1.5), 0), // fmsub.d fa0, fa1, fa2, fa3
+                (0x6ac5f547, [-1.0, -1235.1, 1.1], bits(1234.0), nx),
+                (0x6ac5f547, [2.0, -5.0, -2.0], bits(-8.0), 0),
+                (0x6ac5f54b, [1.0, 2.5, 1.0], bits(-1.5), 0), // fnmsub.d fa0, fa1, fa2, fa3
+                (0x6ac5f54b, [-1.0, -1235.1, 1.1], bits(-1234.0), nx),
+                (0x6ac5f54b, [2.0, -5.0, -2.0], bits(8.0), 0),
+                // Infinity times zero is invalid even if the addend is quiet NaN
+                (
+                    0x6ac5f543,
+                    [f64::INFINITY, 0.0, f64::NAN],
+                    fpu::CANONICAL_NAN_F64,
+                    fpu::FLAG_NV,
+                ),
+            ],
+        );
+    }
 
-        assert_eq!(DRAM_BASE + 4, cpu.read_pc());
-        assert_eq!(1, cpu.read_register(1));
+    #[test]
+    fn double_precision_fsgnj() {
+        let mut cpu = create_fp_cpu();
+        let bits = f64::to_bits;
+        let snan = f64::from_bits(0x7ff0000000000001);
+        check_double_precision(
+            &mut cpu,
+            &[
+                (0x22c58553, [1.0, -2.0, 0.0], bits(-1.0), 0), // fsgnj.d fa0, fa1, fa2
+                (0x22c58553, [-1.0, 2.0, 0.0], bits(1.0), 0),
+                (0x22c59553, [1.0, -2.0, 0.0], bits(1.0), 0), // fsgnjn.d fa0, fa1, fa2
+                (0x22c59553, [-1.0, 2.0, 0.0], bits(-1.0), 0),
+                (0x22c5a553, [1.0, -2.0, 0.0], bits(-1.0), 0), // fsgnjx.d fa0, fa1, fa2
+                (0x22c5a553, [-1.0, -2.0, 0.0], bits(1.0), 0),
+                (0x22c5a553, [-1.0, 2.0, 0.0], bits(-1.0), 0),
+                // Sign injection keeps NaN payloads without raising flags
+                (0x22c58553, [snan, -1.0, 0.0], 0xfff0000000000001, 0),
+                (0x22c5a553, [-snan, -1.0, 0.0], 0x7ff0000000000001, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn double_precision_fclass() {
+        let mut cpu = create_fp_cpu();
+        check_double_precision(
+            &mut cpu,
+            &[
+                (0xe2059553, [f64::NEG_INFINITY, 0.0, 0.0], 0x1, 0), // fclass.d a0, fa1
+                (0xe2059553, [-1.0, 0.0, 0.0], 0x2, 0),
+                (0xe2059553, [-f64::MIN_POSITIVE / 2.0, 0.0, 0.0], 0x4, 0),
+                (0xe2059553, [-0.0, 0.0, 0.0], 0x8, 0),
+                (0xe2059553, [0.0, 0.0, 0.0], 0x10, 0),
+                (0xe2059553, [f64::MIN_POSITIVE / 2.0, 0.0, 0.0], 0x20, 0),
+                (0xe2059553, [1.0, 0.0, 0.0], 0x40, 0),
+                (0xe2059553, [f64::INFINITY, 0.0, 0.0], 0x80, 0),
+                (
+                    0xe2059553,
+                    [f64::from_bits(0x7ff0000000000001), 0.0, 0.0],
+                    0x100,
+                    0,
+                ),
+                (0xe2059553, [f64::NAN, 0.0, 0.0], 0x200, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn double_precision_rounding_mode_flags() {
+        let mut cpu = create_fp_cpu();
+        let bits = f64::to_bits;
+        let (nx, of, uf) = (fpu::FLAG_NX, fpu::FLAG_OF, fpu::FLAG_UF);
+        let one_up = 1.0f64.next_up().to_bits();
+        let tie = 2.0f64.powi(-53);
+        let (max, inf) = (f64::MAX, f64::INFINITY);
+        // (1 - 2^-53) * 2^-1022 is exact in 53 bits, so tiny in any mode
+        let (below_one, min) = (1.0f64.next_down(), f64::MIN_POSITIVE);
+        let subnormal = min.next_down().to_bits();
+        check_double_precision(
+            &mut cpu,
+            &[
+                // fadd.d fa0, fa1, fa2 with rne, rtz, rdn, rup and rmm
+                (0x02c58553, [1.0, tie, 0.0], bits(1.0), nx),
+                (0x02c59553, [1.0, tie, 0.0], bits(1.0), nx),
+                (0x02c5a553, [1.0, tie, 0.0], bits(1.0), nx),
+                (0x02c5b553, [1.0, tie, 0.0], one_up, nx),
+                (0x02c5c553, [1.0, tie, 0.0], one_up, nx),
+                (0x02c58553, [-1.0, -tie, 0.0], bits(-1.0), nx),
+                (0x02c59553, [-1.0, -tie, 0.0], bits(-1.0), nx),
+                (0x02c5a553, [-1.0, -tie, 0.0], one_up | 1 << 63, nx),
+                (0x02c5b553, [-1.0, -tie, 0.0], bits(-1.0), nx),
+                (0x02c5c553, [-1.0, -tie, 0.0], one_up | 1 << 63, nx),
+                // fmul.d fa0, fa1, fa2 with rne, rtz, rdn, rup and rmm
+                (0x12c58553, [max, 2.0, 0.0], inf.to_bits(), of | nx),
+                (0x12c59553, [max, 2.0, 0.0], max.to_bits(), of | nx),
+                (0x12c5a553, [max, 2.0, 0.0], max.to_bits(), of | nx),
+                (0x12c5b553, [max, 2.0, 0.0], inf.to_bits(), of | nx),
+                (0x12c5c553, [max, 2.0, 0.0], inf.to_bits(), of | nx),
+                (0x12c58553, [-max, 2.0, 0.0], (-inf).to_bits(), of | nx),
+                (0x12c59553, [-max, 2.0, 0.0], (-max).to_bits(), of | nx),
+                (0x12c5a553, [-max, 2.0, 0.0], (-inf).to_bits(), of | nx),
+                (0x12c5b553, [-max, 2.0, 0.0], (-max).to_bits(), of | nx),
+                (0x12c5c553, [-max, 2.0, 0.0], (-inf).to_bits(), of | nx),
+                (0x12c58553, [below_one, min, 0.0], bits(min), uf | nx),
+                (0x12c59553, [below_one, min, 0.0], subnormal, uf | nx),
+                (0x12c5a553, [below_one, min, 0.0], subnormal, uf | nx),
+                (0x12c5b553, [below_one, min, 0.0], bits(min), uf | nx),
+                (0x12c5c553, [below_one, min, 0.0], bits(min), uf | nx),
+                // fcvt.w.d a0, fa1 with rne, rtz, rdn, rup and rmm
+                (0xc2058553, [2.5, 0.0, 0.0], 2, nx),
+                (0xc2059553, [2.5, 0.0, 0.0], 2, nx),
+                (0xc205a553, [2.5, 0.0, 0.0], 2, nx),
+                (0xc205b553, [2.5, 0.0, 0.0], 3, nx),
+                (0xc205c553, [2.5, 0.0, 0.0], 3, nx),
+                (0xc2058553, [-2.5, 0.0, 0.0], 0xfffffffffffffffe, nx),
+                (0xc2059553, [-2.5, 0.0, 0.0], 0xfffffffffffffffe, nx),
+                (0xc205a553, [-2.5, 0.0, 0.0], 0xfffffffffffffffd, nx),
+                (0xc205b553, [-2.5, 0.0, 0.0], 0xfffffffffffffffe, nx),
+                (0xc205c553, [-2.5, 0.0, 0.0], 0xfffffffffffffffd, nx),
+            ],
+        );
+
+        // Dynamic rounding mode refers to frm
+        cpu.write_csr_raw(CSR_FRM_ADDRESS, 3); // Round up
+        check_double_precision(
+            &mut cpu,
+            &[(0x02c5f553, [1.0, tie, 0.0], one_up, nx)], // fadd.d fa0, fa1, fa2
+        );
+    }
+
+    fn create_vector_cpu() -> Cpu {
+        let mut cpu = create_cpu();
+        cpu.get_mut_mmu().init_memory(0x1000);
+        let status = cpu.read_csr_raw(CSR_MSTATUS_ADDRESS);
+        cpu.write_csr_raw(CSR_MSTATUS_ADDRESS, status | MSTATUS_VS_DIRTY);
+        cpu
+    }
 
-        cpu.tick();
+    /// Writes 32-bit elements to a vector register.
+    fn write_vector_words(cpu: &mut Cpu, reg: usize, values: &[u32]) {
+        for (index, value) in values.iter().enumerate() {
+            cpu.vector.write_element(reg, index, 32, *value as u64);
+        }
+    }
 
-        assert_eq!(DRAM_BASE + 6, cpu.read_pc());
-        assert_eq!(8, cpu.read_register(8));
+    fn read_vector_words(cpu: &Cpu, reg: usize, count: usize) -> Vec<u32> {
+        (0..count)
+            .map(|index| cpu.vector.read_element(reg, index, 32) as u32)
+            .collect()
     }
 
     #[test]
-    fn tick_operate() {
+    fn vector_configuration() {
         let mut cpu = create_cpu();
         cpu.get_mut_mmu().init_memory(4);
-        cpu.update_pc(DRAM_BASE);
-        // write non-compressed "addi a0, a0, 12" instruction
-        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0xc50513) {
-            Ok(()) => {}
-            Err(_e) => panic!("Failed to store"),
-        };
-        assert_eq!(DRAM_BASE, cpu.read_pc());
+        cpu.x[11] = 10;
+        // The vector unit is off at reset
+        assert!(is_illegal(execute(&mut cpu, 0x0d05f557))); // vsetvli a0, a1, e32, m1, ta, ma
+
+        let mut cpu = create_vector_cpu();
+        assert_eq!(16, cpu.read_csr_raw(CSR_VLENB_ADDRESS));
+        cpu.x[11] = 10;
+        execute(&mut cpu, 0x0d05f557).unwrap(); // vsetvli a0, a1, e32, m1, ta, ma
+        assert_eq!(4, cpu.read_register(10));
+        assert_eq!(4, cpu.read_csr_raw(CSR_VL_ADDRESS));
+        assert_eq!(0xd0, cpu.read_csr_raw(CSR_VTYPE_ADDRESS));
+
+        cpu.x[11] = 1000;
+        execute(&mut cpu, 0x0c35f557).unwrap(); // vsetvli a0, a1, e8, m8, ta, ma
+        assert_eq!(128, cpu.read_register(10));
+
+        execute(&mut cpu, 0xc191f557).unwrap(); // vsetivli a0, 3, e64, m2, tu, mu
+        assert_eq!(3, cpu.read_register(10));
+        assert_eq!(0x19, cpu.read_csr_raw(CSR_VTYPE_ADDRESS));
+
+        // SEW=64 can't be held with LMUL=1/8
+        execute(&mut cpu, 0x01d5f557).unwrap(); // vsetvli a0, a1, e64, mf8
         assert_eq!(0, cpu.read_register(10));
-        match cpu.tick_operate() {
-            Ok(()) => {}
-            Err(_e) => panic!("tick_operate() unexpectedly did panic"),
-        };
-        // .tick_operate() increments the program counter by 4 for
-        // non-compressed instruction.
-        assert_eq!(DRAM_BASE + 4, cpu.read_pc());
-        // "addi a0, a0, a12" instruction writes 12 to a0 register.
-        assert_eq!(12, cpu.read_register(10));
-        // @TODO: Test compressed instruction operation
+        assert_eq!(1 << 63, cpu.read_csr_raw(CSR_VTYPE_ADDRESS));
+        assert!(is_illegal(execute(&mut cpu, 0x022180d7))); // vadd.vv v1, v2, v3
     }
 
     #[test]
-    fn fetch() {
-        // .fetch() reads four bytes from the memory
-        // at the address the program counter points to.
-        // .fetch() doesn't increment the program counter.
-        // .tick_operate() does.
-        let mut cpu = create_cpu();
-        cpu.get_mut_mmu().init_memory(4);
-        cpu.update_pc(DRAM_BASE);
-        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0xaaaaaaaa) {
-            Ok(()) => {}
-            Err(_e) => panic!("Failed to store"),
-        };
-        match cpu.fetch() {
-            Ok(data) => assert_eq!(0xaaaaaaaa, data),
-            Err(_e) => panic!("Failed to fetch"),
-        };
-        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0x55555555) {
-            Ok(()) => {}
-            Err(_e) => panic!("Failed to store"),
-        };
-        match cpu.fetch() {
-            Ok(data) => assert_eq!(0x55555555, data),
-            Err(_e) => panic!("Failed to fetch"),
-        };
-        // @TODO: Write test cases where Trap happens
+    fn vector_integer_arithmetic() {
+        let mut cpu = create_vector_cpu();
+        cpu.x[11] = 4;
+        execute(&mut cpu, 0x0d05f557).unwrap(); // vsetvli a0, a1, e32, m1, ta, ma
+        write_vector_words(&mut cpu, 2, &[1, 2, 3, 0xffffffff]);
+        write_vector_words(&mut cpu, 3, &[10, 20, 30, 1]);
+
+        execute(&mut cpu, 0x022180d7).unwrap(); // vadd.vv v1, v2, v3
+        assert_eq!(vec![11, 22, 33, 0], read_vector_words(&cpu, 1, 4));
+
+        // Masked-off elements are left undisturbed
+        write_vector_words(&mut cpu, 0, &[0b0101]);
+        write_vector_words(&mut cpu, 1, &[0x55; 4]);
+        execute(&mut cpu, 0x002180d7).unwrap(); // vadd.vv v1, v2, v3, v0.t
+        assert_eq!(vec![11, 0x55, 33, 0x55], read_vector_words(&cpu, 1, 4));
+
+        execute(&mut cpu, 0xc221a257).unwrap(); // vwaddu.vv v4, v2, v3
+        assert_eq!(0x100000000, cpu.vector.read_element(4, 3, 64));
+
+        execute(&mut cpu, 0x0221a0d7).unwrap(); // vredsum.vs v1, v2, v3
+        execute(&mut cpu, 0x42102557).unwrap(); // vmv.x.s a0, v1
+        assert_eq!(0xf, cpu.read_register(10));
+
+        // Saturating add sets vxsat
+        cpu.x[11] = 0xfffffff0;
+        execute(&mut cpu, 0x8225c0d7).unwrap(); // vsaddu.vx v1, v2, a1
+        assert_eq!(
+            vec![0xfffffff1, 0xfffffff2, 0xfffffff3, 0xffffffff],
+            read_vector_words(&cpu, 1, 4)
+        );
+        assert_eq!(1, cpu.read_csr_raw(CSR_VXSAT_ADDRESS));
+        assert_eq!(
+            MSTATUS_VS_DIRTY,
+            cpu.read_csr_raw(CSR_MSTATUS_ADDRESS) & MSTATUS_VS_DIRTY
+        );
+
+        cpu.x[11] = 3;
+        execute(&mut cpu, 0x6e25c057).unwrap(); // vmslt.vx v0, v2, a1
+        assert_eq!(0b1011, cpu.vector.read_element(0, 0, 8) & 0xf);
+        execute(&mut cpu, 0x42082557).unwrap(); // vcpop.m a0, v0
+        assert_eq!(3, cpu.read_register(10));
     }
 
     #[test]
-    fn decode() {
-        let mut cpu = create_cpu();
-        // 0x13 is addi instruction
-        match cpu.decode(0x13) {
-            Ok(inst) => assert_eq!(inst.name, "ADDI"),
-            Err(_e) => panic!("Failed to decode"),
-        };
-        // .decode() returns error for invalid word data.
-        match cpu.decode(0x0) {
-            Ok(_inst) => panic!("Unexpectedly succeeded in decoding"),
-            Err(()) => assert!(true),
-        };
-        // @TODO: Should I test all instructions?
+    fn vector_float_arithmetic() {
+        let mut cpu = create_vector_cpu();
+        cpu.x[11] = 4;
+        execute(&mut cpu, 0x0d05f557).unwrap(); // vsetvli a0, a1, e32, m1, ta, ma
+        write_vector_words(&mut cpu, 2, &[1.5f32, 1.0, f32::MAX, 0.0].map(f32::to_bits));
+        write_vector_words(
+            &mut cpu,
+            3,
+            &[2.25f32, 2.0f32.powi(-30), f32::MAX, -0.0].map(f32::to_bits),
+        );
+        execute(&mut cpu, 0x022190d7).unwrap(); // vfadd.vv v1, v2, v3
+        assert_eq!(
+            [3.75f32, 1.0, f32::INFINITY, 0.0]
+                .map(f32::to_bits)
+                .to_vec(),
+            read_vector_words(&cpu, 1, 4)
+        );
+        assert_eq!(
+            (fpu::FLAG_OF | fpu::FLAG_NX) as u64,
+            cpu.read_csr_raw(CSR_FFLAGS_ADDRESS)
+        );
+
+        // Single-width floating-point operations need SEW=32 or 64
+        cpu.x[11] = 4;
+        execute(&mut cpu, 0x0c05f557).unwrap(); // vsetvli a0, a1, e8, m1, ta, ma
+        assert!(is_illegal(execute(&mut cpu, 0x022190d7))); // vfadd.vv v1, v2, v3
     }
 
     #[test]
-    fn uncompress() {
-        let mut cpu = create_cpu();
-        // .uncompress() doesn't directly return an instruction but
-        // it returns uncompressed word. Then you need to call .decode().
-        match cpu.decode(cpu.uncompress(0x20)) {
-            Ok(inst) => assert_eq!(inst.name, "ADDI"),
-            Err(_e) => panic!("Failed to decode"),
-        };
-        // @TODO: Should I test all compressed instructions?
+    fn vector_permutation() {
+        let mut cpu = create_vector_cpu();
+        cpu.x[11] = 4;
+        execute(&mut cpu, 0x0d05f557).unwrap(); // vsetvli a0, a1, e32, m1, ta, ma
+        write_vector_words(&mut cpu, 1, &[0x55; 4]);
+        write_vector_words(&mut cpu, 2, &[1, 2, 3, 4]);
+        write_vector_words(&mut cpu, 3, &[3, 0, 7, 1]);
+
+        execute(&mut cpu, 0x3a20b0d7).unwrap(); // vslideup.vi v1, v2, 1
+        assert_eq!(vec![0x55, 1, 2, 3], read_vector_words(&cpu, 1, 4));
+
+        execute(&mut cpu, 0x322180d7).unwrap(); // vrgather.vv v1, v2, v3
+        assert_eq!(vec![4, 1, 0, 2], read_vector_words(&cpu, 1, 4));
+
+        // The destination can't overlap the source of the slide up
+        assert!(is_illegal(execute(&mut cpu, 0x3a20b157))); // vslideup.vi v2, v2, 1
     }
 
     #[test]
-    fn wfi() {
-        let wfi_instruction = 0x10500073;
-        let mut cpu = create_cpu();
-        // Just in case
-        match cpu.decode(wfi_instruction) {
-            Ok(inst) => assert_eq!(inst.name, "WFI"),
-            Err(_e) => panic!("Failed to decode"),
-        };
-        cpu.get_mut_mmu().init_memory(4);
-        cpu.update_pc(DRAM_BASE);
-        // write WFI instruction
-        match cpu.get_mut_mmu().store_word(DRAM_BASE, wfi_instruction) {
-            Ok(()) => {}
-            Err(_e) => panic!("Failed to store"),
-        };
-        cpu.tick();
-        assert_eq!(DRAM_BASE + 4, cpu.read_pc());
-        for _i in 0..10 {
-            // Until interrupt happens, .tick() does nothing
-            // @TODO: Check accurately that the state is unchanged
-            cpu.tick();
-            assert_eq!(DRAM_BASE + 4, cpu.read_pc());
+    fn vector_memory() {
+        let mut cpu = create_vector_cpu();
+        cpu.x[11] = 4;
+        execute(&mut cpu, 0x0d05f557).unwrap(); // vsetvli a0, a1, e32, m1, ta, ma
+        let base = DRAM_BASE + 0x100;
+        for i in 0..8 {
+            cpu.get_mut_mmu()
+                .store_word(base + i * 4, i as u32 + 1)
+                .unwrap();
         }
-        // Machine timer interrupt
-        cpu.write_csr_raw(CSR_MIE_ADDRESS, MIP_MTIP);
-        cpu.write_csr_raw(CSR_MIP_ADDRESS, MIP_MTIP);
-        cpu.write_csr_raw(CSR_MSTATUS_ADDRESS, 0x8);
-        cpu.write_csr_raw(CSR_MTVEC_ADDRESS, 0x0);
-        cpu.tick();
-        // Interrupt happened and moved to handler
-        assert_eq!(0, cpu.read_pc());
+
+        cpu.x[11] = base as i64;
+        execute(&mut cpu, 0x0205e087).unwrap(); // vle32.v v1, (a1)
+        assert_eq!(vec![1, 2, 3, 4], read_vector_words(&cpu, 1, 4));
+
+        cpu.x[12] = 8;
+        execute(&mut cpu, 0x0ac5e087).unwrap(); // vlse32.v v1, (a1), a2
+        assert_eq!(vec![1, 3, 5, 7], read_vector_words(&cpu, 1, 4));
+
+        write_vector_words(&mut cpu, 3, &[28, 0, 4, 12]);
+        execute(&mut cpu, 0x0635e087).unwrap(); // vluxei32.v v1, (a1), v3
+        assert_eq!(vec![8, 1, 2, 4], read_vector_words(&cpu, 1, 4));
+
+        execute(&mut cpu, 0x2205e207).unwrap(); // vlseg2e32.v v4, (a1)
+        assert_eq!(vec![1, 3, 5, 7], read_vector_words(&cpu, 4, 4));
+        assert_eq!(vec![2, 4, 6, 8], read_vector_words(&cpu, 5, 4));
+
+        cpu.x[11] = (base + 0x40) as i64;
+        execute(&mut cpu, 0x0205e0a7).unwrap(); // vse32.v v1, (a1)
+        assert_eq!(8, cpu.get_mut_mmu().load_word(base + 0x40).unwrap());
+        assert_eq!(4, cpu.get_mut_mmu().load_word(base + 0x4c).unwrap());
+
+        // Fault-only-first load trims vl at the faulting element
+        cpu.x[11] = (DRAM_BASE + 0x1000 - 8) as i64;
+        execute(&mut cpu, 0x0305e087).unwrap(); // vle32ff.v v1, (a1)
+        assert_eq!(2, cpu.read_csr_raw(CSR_VL_ADDRESS));
+
+        // Other loads trap and record the element in vstart
+        cpu.x[11] = 4;
+        execute(&mut cpu, 0x0d05f557).unwrap(); // vsetvli a0, a1, e32, m1, ta, ma
+        cpu.x[11] = (DRAM_BASE + 0x1000 - 8) as i64;
+        assert!(execute(&mut cpu, 0x0205e087).is_err()); // vle32.v v1, (a1)
+        assert_eq!(2, cpu.read_csr_raw(CSR_VSTART_ADDRESS));
     }
 
     #[test]
-    fn interrupt() {
-        let handler_vector = 0x10000000;
-        let mut cpu = create_cpu();
-        cpu.get_mut_mmu().init_memory(4);
-        // Write non-compressed "addi x0, x0, 1" instruction
-        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00100013) {
-            Ok(()) => {}
-            Err(_e) => panic!("Failed to store"),
-        };
-        cpu.update_pc(DRAM_BASE);
-
-        // Machine timer interrupt but mie in mstatus is not enabled yet
-        cpu.write_csr_raw(CSR_MIE_ADDRESS, MIP_MTIP);
-        cpu.write_csr_raw(CSR_MIP_ADDRESS, MIP_MTIP);
-        cpu.write_csr_raw(CSR_MTVEC_ADDRESS, handler_vector);
-
-        cpu.tick();
-
-        // Interrupt isn't caught because mie is disabled
-        assert_eq!(DRAM_BASE + 4, cpu.read_pc());
-
-        cpu.update_pc(DRAM_BASE);
-        // Enable mie in mstatus
-        cpu.write_csr_raw(CSR_MSTATUS_ADDRESS, 0x8);
+    fn vector_fixed_point() {
+        let mut cpu = create_vector_cpu();
+        cpu.x[11] = 4;
+        execute(&mut cpu, 0x0d05f557).unwrap(); // vsetvli a0, a1, e32, m1, ta, ma
+
+        // vxrm is round-to-nearest-up, -even, round-down or round-to-odd
+        write_vector_words(&mut cpu, 2, &[5, 6, 7, 10]);
+        for (vxrm, expected) in [
+            (0, [1, 2, 2, 3]),
+            (1, [1, 2, 2, 2]),
+            (2, [1, 1, 1, 2]),
+            (3, [1, 1, 1, 3]),
+        ] {
+            cpu.write_csr_raw(CSR_VXRM_ADDRESS, vxrm);
+            execute(&mut cpu, 0xaa2130d7).unwrap(); // vssrl.vi v1, v2, 2
+            assert_eq!(expected.to_vec(), read_vector_words(&cpu, 1, 4));
+        }
 
-        cpu.tick();
+        write_vector_words(&mut cpu, 2, &[-5i32, -6, -10, i32::MIN].map(|v| v as u32));
+        cpu.write_csr_raw(CSR_VXRM_ADDRESS, 0);
+        execute(&mut cpu, 0xae2130d7).unwrap(); // vssra.vi v1, v2, 2
+        assert_eq!(
+            [-1i32, -1, -2, -0x20000000].map(|v| v as u32).to_vec(),
+            read_vector_words(&cpu, 1, 4)
+        );
+        cpu.write_csr_raw(CSR_VXRM_ADDRESS, 2);
+        execute(&mut cpu, 0xae2130d7).unwrap(); // vssra.vi v1, v2, 2
+        assert_eq!(
+            [-2i32, -2, -3, -0x20000000].map(|v| v as u32).to_vec(),
+            read_vector_words(&cpu, 1, 4)
+        );
 
-        // Interrupt happened and moved to handler
-        assert_eq!(handler_vector, cpu.read_pc());
+        // vsmul only saturates -1 * -1 and rounds the product with vxrm
+        write_vector_words(&mut cpu, 2, &[0x80000000, 0x40000000, 3, 3]);
+        write_vector_words(
+            &mut cpu,
+            3,
+            &[0x80000000, 0x40000000, 0x40000000, 0x40000000],
+        );
+        cpu.write_csr_raw(CSR_VXRM_ADDRESS, 0);
+        execute(&mut cpu, 0x9e2180d7).unwrap(); // vsmul.vv v1, v2, v3
+        assert_eq!(
+            vec![0x7fffffff, 0x20000000, 2, 2],
+            read_vector_words(&cpu, 1, 4)
+        );
+        assert_eq!(1, cpu.read_csr_raw(CSR_VXSAT_ADDRESS));
+        cpu.write_csr_raw(CSR_VXSAT_ADDRESS, 0);
+        write_vector_words(&mut cpu, 2, &[0x7fffffff, 0x40000000, 3, 3]);
+        cpu.write_csr_raw(CSR_VXRM_ADDRESS, 2);
+        execute(&mut cpu, 0x9e2180d7).unwrap(); // vsmul.vv v1, v2, v3
+        assert_eq!(
+            vec![0x80000001, 0x20000000, 1, 1],
+            read_vector_words(&cpu, 1, 4)
+        );
+        assert_eq!(0, cpu.read_csr_raw(CSR_VXSAT_ADDRESS));
 
-        // CSR Cause register holds the reason what caused the interrupt
-        assert_eq!(0x8000000000000007, cpu.read_csr_raw(CSR_MCAUSE_ADDRESS));
+        // Narrowing clips read 2 * SEW wide elements of v2 and v3
+        for (index, value) in [0x3_0000_0000u64, 0xffff_ffff, 5, 7].iter().enumerate() {
+            cpu.vector.write_element(2, index, 64, *value);
+        }
+        cpu.write_csr_raw(CSR_VXRM_ADDRESS, 0);
+        execute(&mut cpu, 0xba20b0d7).unwrap(); // vnclipu.wi v1, v2, 1
+        assert_eq!(
+            vec![0xffffffff, 0x80000000, 3, 4],
+            read_vector_words(&cpu, 1, 4)
+        );
+        assert_eq!(1, cpu.read_csr_raw(CSR_VXSAT_ADDRESS));
 
-        // @TODO: Test post CSR status register
-        // @TODO: Test xIE bit in CSR status register
-        // @TODO: Test privilege levels
-        // @TODO: Test delegation
-        // @TODO: Test vector type handlers
+        cpu.write_csr_raw(CSR_VXSAT_ADDRESS, 0);
+        for (index, value) in [-0x2_0000_0000i64, 0x1_0000_0000, -5, 7].iter().enumerate() {
+            cpu.vector.write_element(2, index, 64, *value as u64);
+        }
+        execute(&mut cpu, 0xbe20b0d7).unwrap(); // vnclip.wi v1, v2, 1
+        assert_eq!(
+            vec![0x80000000, 0x7fffffff, (-2i32) as u32, 4],
+            read_vector_words(&cpu, 1, 4)
+        );
+        assert_eq!(1, cpu.read_csr_raw(CSR_VXSAT_ADDRESS));
     }
 
     #[test]
-    fn exception() {
-        let handler_vector = 0x10000000;
-        let mut cpu = create_cpu();
-        cpu.get_mut_mmu().init_memory(4);
-        // Write ECALL instruction
-        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00000073) {
-            Ok(()) => {}
-            Err(_e) => panic!("Failed to store"),
+    fn vector_mask() {
+        let mut cpu = create_vector_cpu();
+        cpu.x[11] = 16;
+        execute(&mut cpu, 0x0c05f557).unwrap(); // vsetvli a0, a1, e8, m1, ta, ma
+        let read_mask = |cpu: &Cpu| cpu.vector.read_element(1, 0, 16);
+
+        cpu.vector.write_element(2, 0, 16, 0b1100_1010_0101_0011);
+        cpu.vector.write_element(3, 0, 16, 0b1010_0110_1111_0000);
+        execute(&mut cpu, 0x6621a0d7).unwrap(); // vmand.mm v1, v2, v3
+        assert_eq!(0b1000_0010_0101_0000, read_mask(&cpu));
+        execute(&mut cpu, 0x6a21a0d7).unwrap(); // vmor.mm v1, v2, v3
+        assert_eq!(0b1110_1110_1111_0011, read_mask(&cpu));
+        execute(&mut cpu, 0x7621a0d7).unwrap(); // vmnand.mm v1, v2, v3
+        assert_eq!(0b0111_1101_1010_1111, read_mask(&cpu));
+
+        cpu.vector.write_element(2, 0, 16, 0b0101_0000);
+        execute(&mut cpu, 0x5220a0d7).unwrap(); // vmsbf.m v1, v2
+        assert_eq!(0b0000_1111, read_mask(&cpu));
+        execute(&mut cpu, 0x5221a0d7).unwrap(); // vmsif.m v1, v2
+        assert_eq!(0b0001_1111, read_mask(&cpu));
+        execute(&mut cpu, 0x522120d7).unwrap(); // vmsof.m v1, v2
+        assert_eq!(0b0001_0000, read_mask(&cpu));
+
+        // Without active set bits all the elements are before the first one
+        cpu.vector.write_element(2, 0, 16, 0);
+        execute(&mut cpu, 0x5220a0d7).unwrap(); // vmsbf.m v1, v2
+        assert_eq!(0xffff, read_mask(&cpu));
+        execute(&mut cpu, 0x5221a0d7).unwrap(); // vmsif.m v1, v2
+        assert_eq!(0xffff, read_mask(&cpu));
+        execute(&mut cpu, 0x522120d7).unwrap(); // vmsof.m v1, v2
+        assert_eq!(0, read_mask(&cpu));
+
+        cpu.x[11] = 8;
+        execute(&mut cpu, 0x0005f557).unwrap(); // vsetvli a0, a1, e8, m1, tu, mu
+        let read_bytes = |cpu: &Cpu| {
+            (0..8)
+                .map(|index| cpu.vector.read_element(1, index, 8))
+                .collect::<Vec<_>>()
         };
-        cpu.write_csr_raw(CSR_MTVEC_ADDRESS, handler_vector);
-        cpu.update_pc(DRAM_BASE);
+        cpu.vector.write_element(2, 0, 8, 0b1001_0110);
+        execute(&mut cpu, 0x522820d7).unwrap(); // viota.m v1, v2
+        assert_eq!(vec![0, 0, 1, 2, 2, 3, 3, 3], read_bytes(&cpu));
+
+        execute(&mut cpu, 0x5208a0d7).unwrap(); // vid.v v1
+        assert_eq!(vec![0, 1, 2, 3, 4, 5, 6, 7], read_bytes(&cpu));
+
+        // Masked-off elements are left undisturbed
+        cpu.vector.write_element(0, 0, 8, 0b0101_0101);
+        cpu.vector.write_element(1, 0, 64, u64::MAX);
+        execute(&mut cpu, 0x5008a0d7).unwrap(); // vid.v v1, v0.t
+        assert_eq!(vec![0, 0xff, 2, 0xff, 4, 0xff, 6, 0xff], read_bytes(&cpu));
+    }
 
-        cpu.tick();
+    #[test]
+    fn vector_float_exception_flags() {
+        let mut cpu = create_vector_cpu();
+        cpu.x[11] = 4;
+        execute(&mut cpu, 0x0105f557).unwrap(); // vsetvli a0, a1, e32, m1, tu, mu
+        let tiny = 2.0f32.powi(-30);
+        write_vector_words(
+            &mut cpu,
+            2,
+            &[1.0f32, 1.0, f32::INFINITY, 1.0].map(f32::to_bits),
+        );
+        write_vector_words(
+            &mut cpu,
+            3,
+            &[1.0f32, tiny, f32::NEG_INFINITY, 0.0].map(f32::to_bits),
+        );
 
-        // Interrupt happened and moved to handler
-        assert_eq!(handler_vector, cpu.read_pc());
+        // Masked-off elements raise no exception
+        write_vector_words(&mut cpu, 0, &[0b0001]);
+        execute(&mut cpu, 0x002190d7).unwrap(); // vfadd.vv v1, v2, v3, v0.t
+        assert_eq!(2.0f32.to_bits(), read_vector_words(&cpu, 1, 1)[0]);
+        assert_eq!(0, cpu.read_csr_raw(CSR_FFLAGS_ADDRESS));
 
-        // CSR Cause register holds the reason what caused the trap
-        assert_eq!(0xb, cpu.read_csr_raw(CSR_MCAUSE_ADDRESS));
+        // The flags of the elements are accrued
+        execute(&mut cpu, 0x022190d7).unwrap(); // vfadd.vv v1, v2, v3
+        assert_eq!(
+            [2.0f32, 1.0, f32::NAN, 1.0].map(f32::to_bits).to_vec(),
+            read_vector_words(&cpu, 1, 4)
+        );
+        assert_eq!(
+            (fpu::FLAG_NV | fpu::FLAG_NX) as u64,
+            cpu.read_csr_raw(CSR_FFLAGS_ADDRESS)
+        );
 
-        // @TODO: Test post CSR status register
-        // @TODO: Test privilege levels
-        // @TODO: Test delegation
-        // @TODO: Test vector type handlers
-    }
+        // The rounding mode is frm
+        cpu.write_csr_raw(CSR_FFLAGS_ADDRESS, 0);
+        cpu.write_csr_raw(CSR_FRM_ADDRESS, 3); // Round up
+        execute(&mut cpu, 0x022190d7).unwrap(); // vfadd.vv v1, v2, v3
+        assert_eq!(1.0f32.next_up().to_bits(), read_vector_words(&cpu, 1, 2)[1]);
 
-    #[test]
-    fn hardocded_zero() {
-        let mut cpu = create_cpu();
-        cpu.get_mut_mmu().init_memory(8);
-        cpu.update_pc(DRAM_BASE);
+        cpu.write_csr_raw(CSR_FFLAGS_ADDRESS, 0);
+        execute(&mut cpu, 0x822190d7).unwrap(); // vfdiv.vv v1, v2, v3
+        assert_eq!(f32::INFINITY.to_bits(), read_vector_words(&cpu, 1, 4)[3]);
+        assert_eq!(
+            (fpu::FLAG_NV | fpu::FLAG_DZ) as u64,
+            cpu.read_csr_raw(CSR_FFLAGS_ADDRESS)
+        );
 
-        // Write non-compressed "addi x0, x0, 1" instruction
-        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00100013) {
-            Ok(()) => {}
-            Err(_e) => panic!("Failed to store"),
-        };
-        // Write non-compressed "addi x1, x1, 1" instruction
-        match cpu.get_mut_mmu().store_word(DRAM_BASE + 4, 0x00108093) {
-            Ok(()) => {}
-            Err(_e) => panic!("Failed to store"),
-        };
+        cpu.write_csr_raw(CSR_FFLAGS_ADDRESS, 0);
+        write_vector_words(&mut cpu, 2, &[2.5f32, -2.5, -1.0, 3e9].map(f32::to_bits));
+        cpu.write_csr_raw(CSR_FRM_ADDRESS, 2); // Round down
+        execute(&mut cpu, 0x4a2090d7).unwrap(); // vfcvt.x.f.v v1, v2
+        assert_eq!(
+            [2i32, -3, -1, i32::MAX].map(|v| v as u32).to_vec(),
+            read_vector_words(&cpu, 1, 4)
+        );
+        assert_eq!(
+            (fpu::FLAG_NV | fpu::FLAG_NX) as u64,
+            cpu.read_csr_raw(CSR_FFLAGS_ADDRESS)
+        );
+        execute(&mut cpu, 0x4a2390d7).unwrap(); // vfcvt.rtz.x.f.v v1, v2
+        assert_eq!(
+            [2i32, -2, -1, i32::MAX].map(|v| v as u32).to_vec(),
+            read_vector_words(&cpu, 1, 4)
+        );
 
-        // Test x0
-        assert_eq!(0, cpu.read_register(0));
-        cpu.tick(); // Execute  "addi x0, x0, 1"
-                    // x0 is still zero because it's hardcoded zero
-        assert_eq!(0, cpu.read_register(0));
+        cpu.write_csr_raw(CSR_FFLAGS_ADDRESS, 0);
+        execute(&mut cpu, 0x4e2010d7).unwrap(); // vfsqrt.v v1, v2
+        assert_eq!(fpu::CANONICAL_NAN_F32, read_vector_words(&cpu, 1, 2)[1]);
+        assert_eq!(
+            (fpu::FLAG_NV | fpu::FLAG_NX) as u64,
+            cpu.read_csr_raw(CSR_FFLAGS_ADDRESS)
+        );
 
-        // Test x1
-        assert_eq!(0, cpu.read_register(1));
-        cpu.tick(); // Execute  "addi x1, x1, 1"
-                    // x1 is not hardcoded zero
-        assert_eq!(1, cpu.read_register(1));
+        // Only the ordered comparison is invalid for quiet NaN
+        cpu.write_csr_raw(CSR_FFLAGS_ADDRESS, 0);
+        write_vector_words(&mut cpu, 3, &[f32::NAN; 4].map(f32::to_bits));
+        execute(&mut cpu, 0x622190d7).unwrap(); // vmfeq.vv v1, v2, v3
+        assert_eq!(0, cpu.read_csr_raw(CSR_FFLAGS_ADDRESS));
+        execute(&mut cpu, 0x6e2190d7).unwrap(); // vmflt.vv v1, v2, v3
+        assert_eq!(fpu::FLAG_NV as u64, cpu.read_csr_raw(CSR_FFLAGS_ADDRESS));
+
+        // Reserved rounding mode in frm is illegal
+        cpu.write_csr_raw(CSR_FRM_ADDRESS, 5);
+        assert!(is_illegal(execute(&mut cpu, 0x022190d7))); // vfadd.vv v1, v2, v3
     }
 
     #[test]
-    fn disassemble_next_instruction() {
-        let mut cpu = create_cpu();
-        cpu.get_mut_mmu().init_memory(4);
-        cpu.update_pc(DRAM_BASE);
-
-        // Write non-compressed "addi x0, x0, 1" instruction
-        match cpu.get_mut_mmu().store_word(DRAM_BASE, 0x00100013) {
-            Ok(()) => {}
-            Err(_e) => panic!("Failed to store"),
+    fn vector_store() {
+        let mut cpu = create_vector_cpu();
+        cpu.x[11] = 4;
+        execute(&mut cpu, 0x0d05f557).unwrap(); // vsetvli a0, a1, e32, m1, ta, ma
+        let load_words = |cpu: &mut Cpu, address: u64, count: u64| {
+            (0..count)
+                .map(|i| cpu.get_mut_mmu().load_word(address + i * 4).unwrap())
+                .collect::<Vec<_>>()
         };
+        write_vector_words(&mut cpu, 1, &[0x11, 0x22, 0x33, 0x44]);
 
+        let base = DRAM_BASE + 0x100;
+        cpu.x[11] = base as i64;
+        cpu.x[12] = 8;
+        execute(&mut cpu, 0x0ac5e0a7).unwrap(); // vsse32.v v1, (a1), a2
         assert_eq!(
-            "PC:0000000080000000 00100013 ADDI zero:0,zero:0,1",
-            cpu.disassemble_next_instruction()
+            vec![0x11, 0, 0x22, 0, 0x33, 0, 0x44],
+            load_words(&mut cpu, base, 7)
         );
 
-        // No effect to PC
-        assert_eq!(DRAM_BASE, cpu.read_pc());
+        let base = DRAM_BASE + 0x200;
+        cpu.x[11] = base as i64;
+        write_vector_words(&mut cpu, 3, &[12, 0, 8, 4]);
+        execute(&mut cpu, 0x0635e0a7).unwrap(); // vsuxei32.v v1, (a1), v3
+        assert_eq!(vec![0x22, 0x44, 0x33, 0x11], load_words(&mut cpu, base, 4));
+
+        let base = DRAM_BASE + 0x300;
+        cpu.x[11] = base as i64;
+        write_vector_words(&mut cpu, 4, &[1, 2, 3, 4]);
+        write_vector_words(&mut cpu, 5, &[5, 6, 7, 8]);
+        execute(&mut cpu, 0x2205e227).unwrap(); // vsseg2e32.v v4, (a1)
+        assert_eq!(vec![1, 5, 2, 6, 3, 7, 4, 8], load_words(&mut cpu, base, 8));
+
+        // A faulting store keeps the former elements and records the element
+        // in vstart. The store resumes from vstart.
+        let end = DRAM_BASE + 0x1000;
+        cpu.x[11] = (end - 8) as i64;
+        assert!(execute(&mut cpu, 0x0205e0a7).is_err()); // vse32.v v1, (a1)
+        assert_eq!(2, cpu.read_csr_raw(CSR_VSTART_ADDRESS));
+        assert_eq!(vec![0x11, 0x22], load_words(&mut cpu, end - 8, 2));
+
+        let base = DRAM_BASE + 0x400;
+        cpu.x[11] = base as i64;
+        execute(&mut cpu, 0x0205e0a7).unwrap(); // vse32.v v1, (a1)
+        assert_eq!(vec![0, 0, 0x33, 0x44], load_words(&mut cpu, base, 4));
+        assert_eq!(0, cpu.read_csr_raw(CSR_VSTART_ADDRESS));
+
+        // Loads resume from vstart too
+        write_vector_words(&mut cpu, 2, &[0x55; 4]);
+        cpu.write_csr_raw(CSR_VSTART_ADDRESS, 1);
+        cpu.x[11] = (DRAM_BASE + 0x300) as i64;
+        execute(&mut cpu, 0x0205e107).unwrap(); // vle32.v v2, (a1)
+        assert_eq!(vec![0x55, 5, 2, 6], read_vector_words(&cpu, 2, 4));
+        assert_eq!(0, cpu.read_csr_raw(CSR_VSTART_ADDRESS));
+    }
+
+    #[test]
+    fn vector_disassemble() {
+        let mut cpu = create_vector_cpu();
+        cpu.update_pc(DRAM_BASE);
+        for (word, expected) in [
+            (0x0d05f557, "VSETVLI a0:0,a1:0,d0"),
+            (0x002180d7, "VADD.VV v1,v2,v3,v0.t"),
+            (0x8225c0d7, "VSADDU.VX v1,v2,a1:0"),
+            (0x0ac5e087, "VLSE32.V v1,(a1:0),a2:0"),
+        ] {
+            cpu.get_mut_mmu().store_word(DRAM_BASE, word).unwrap();
+            assert_eq!(
+                format!("PC:0000000080000000 {:08x} {}", word, expected),
+                cpu.disassemble_next_instruction()
+            );
+        }
     }
 }
 
 #[cfg(test)]
-
 mod test_decode_cache {
     use super::*;
 
@@ -4059,10 +13633,9 @@ mod test_decode_cache {
         };
 
         // Cache miss test
-        match cache.get(2) {
-            Some(_index) => panic!("Unexpected cache hit"),
-            None => {}
-        };
+        if cache.get(2).is_some() {
+            panic!("Unexpected cache hit");
+        }
     }
 
     #[test]
@@ -4080,17 +13653,15 @@ mod test_decode_cache {
         }
 
         // The oldest entry should have been removed because of the overflow
-        match cache.get(0) {
-            Some(_index) => panic!("Unexpected cache hit"),
-            None => {}
-        };
+        if cache.get(0).is_some() {
+            panic!("Unexpected cache hit");
+        }
 
         // With this .get(), the entry with the word "1" moves to the tail of the list
         // and the entry with the word "2" becomes the oldest entry.
-        match cache.get(1) {
-            Some(index) => assert_eq!(2, index),
-            None => {}
-        };
+        if let Some(index) = cache.get(1) {
+            assert_eq!(2, index);
+        }
 
         // The oldest entry with the word "2" will be removed due to the overflow
         cache.insert(
@@ -4098,9 +13669,8 @@ mod test_decode_cache {
             DECODE_CACHE_ENTRY_NUM + 2,
         );
 
-        match cache.get(2) {
-            Some(_index) => panic!("Unexpected cache hit"),
-            None => {}
-        };
+        if cache.get(2).is_some() {
+            panic!("Unexpected cache hit");
+        }
     }
 }
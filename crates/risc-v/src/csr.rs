@@ -0,0 +1,223 @@
+//! Control and status registers. Addresses, fields, and the write masks
+//! modeling WARL (write any values, read legal values) behavior of each
+//! implemented CSR. CSR values are held in `Cpu`.
+
+use crate::cpu::Xlen;
+
+pub(crate) const CSR_USTATUS_ADDRESS: u16 = 0x000;
+pub(crate) const CSR_FFLAGS_ADDRESS: u16 = 0x001;
+pub(crate) const CSR_FRM_ADDRESS: u16 = 0x002;
+pub(crate) const CSR_FCSR_ADDRESS: u16 = 0x003;
+pub(crate) const CSR_UIE_ADDRESS: u16 = 0x004;
+pub(crate) const CSR_UTVEC_ADDRESS: u16 = 0x005;
+pub(crate) const CSR_VSTART_ADDRESS: u16 = 0x008;
+pub(crate) const CSR_VXSAT_ADDRESS: u16 = 0x009;
+pub(crate) const CSR_VXRM_ADDRESS: u16 = 0x00a;
+pub(crate) const CSR_VCSR_ADDRESS: u16 = 0x00f;
+pub(crate) const _CSR_USCRATCH_ADDRESS: u16 = 0x040;
+pub(crate) const CSR_UEPC_ADDRESS: u16 = 0x041;
+pub(crate) const CSR_UCAUSE_ADDRESS: u16 = 0x042;
+pub(crate) const CSR_UTVAL_ADDRESS: u16 = 0x043;
+pub(crate) const _CSR_UIP_ADDRESS: u16 = 0x044;
+pub(crate) const CSR_SSTATUS_ADDRESS: u16 = 0x100;
+pub(crate) const _CSR_SEDELEG_ADDRESS: u16 = 0x102;
+pub(crate) const _CSR_SIDELEG_ADDRESS: u16 = 0x103;
+pub(crate) const CSR_SIE_ADDRESS: u16 = 0x104;
+pub(crate) const CSR_STVEC_ADDRESS: u16 = 0x105;
+pub(crate) const CSR_SCOUNTEREN_ADDRESS: u16 = 0x106;
+pub(crate) const CSR_SENVCFG_ADDRESS: u16 = 0x10a;
+pub(crate) const CSR_SSCRATCH_ADDRESS: u16 = 0x140;
+pub(crate) const CSR_SEPC_ADDRESS: u16 = 0x141;
+pub(crate) const CSR_SCAUSE_ADDRESS: u16 = 0x142;
+pub(crate) const CSR_STVAL_ADDRESS: u16 = 0x143;
+pub(crate) const CSR_SIP_ADDRESS: u16 = 0x144;
+pub(crate) const CSR_STIMECMP_ADDRESS: u16 = 0x14d;
+pub(crate) const CSR_STIMECMPH_ADDRESS: u16 = 0x15d;
+pub(crate) const CSR_SATP_ADDRESS: u16 = 0x180;
+pub(crate) const CSR_MSTATUS_ADDRESS: u16 = 0x300;
+pub(crate) const CSR_MISA_ADDRESS: u16 = 0x301;
+pub(crate) const CSR_MEDELEG_ADDRESS: u16 = 0x302;
+pub(crate) const CSR_MIDELEG_ADDRESS: u16 = 0x303;
+pub(crate) const CSR_MIE_ADDRESS: u16 = 0x304;
+pub(crate) const CSR_MTVEC_ADDRESS: u16 = 0x305;
+pub(crate) const CSR_MCOUNTEREN_ADDRESS: u16 = 0x306;
+pub(crate) const CSR_MENVCFG_ADDRESS: u16 = 0x30a;
+pub(crate) const CSR_MSTATUSH_ADDRESS: u16 = 0x310;
+pub(crate) const CSR_MENVCFGH_ADDRESS: u16 = 0x31a;
+pub(crate) const CSR_MCOUNTINHIBIT_ADDRESS: u16 = 0x320;
+pub(crate) const CSR_MHPMEVENT3_ADDRESS: u16 = 0x323;
+pub(crate) const CSR_MHPMEVENT31_ADDRESS: u16 = 0x33f;
+pub(crate) const CSR_MSCRATCH_ADDRESS: u16 = 0x340;
+pub(crate) const CSR_MEPC_ADDRESS: u16 = 0x341;
+pub(crate) const CSR_MCAUSE_ADDRESS: u16 = 0x342;
+pub(crate) const CSR_MTVAL_ADDRESS: u16 = 0x343;
+pub(crate) const CSR_MIP_ADDRESS: u16 = 0x344;
+pub(crate) const CSR_PMPCFG0_ADDRESS: u16 = 0x3a0;
+pub(crate) const CSR_PMPCFG15_ADDRESS: u16 = 0x3af;
+pub(crate) const CSR_PMPADDR0_ADDRESS: u16 = 0x3b0;
+pub(crate) const CSR_PMPADDR63_ADDRESS: u16 = 0x3ef;
+pub(crate) const CSR_MCYCLE_ADDRESS: u16 = 0xb00;
+pub(crate) const CSR_MINSTRET_ADDRESS: u16 = 0xb02;
+pub(crate) const CSR_MHPMCOUNTER3_ADDRESS: u16 = 0xb03;
+pub(crate) const CSR_MHPMCOUNTER31_ADDRESS: u16 = 0xb1f;
+pub(crate) const CSR_MCYCLEH_ADDRESS: u16 = 0xb80;
+pub(crate) const CSR_MHPMCOUNTER31H_ADDRESS: u16 = 0xb9f;
+pub(crate) const CSR_CYCLE_ADDRESS: u16 = 0xc00;
+pub(crate) const CSR_TIME_ADDRESS: u16 = 0xc01;
+pub(crate) const _CSR_INSTRET_ADDRESS: u16 = 0xc02;
+pub(crate) const CSR_HPMCOUNTER31_ADDRESS: u16 = 0xc1f;
+pub(crate) const CSR_VL_ADDRESS: u16 = 0xc20;
+pub(crate) const CSR_VTYPE_ADDRESS: u16 = 0xc21;
+pub(crate) const CSR_VLENB_ADDRESS: u16 = 0xc22;
+pub(crate) const CSR_CYCLEH_ADDRESS: u16 = 0xc80;
+pub(crate) const CSR_HPMCOUNTER31H_ADDRESS: u16 = 0xc9f;
+pub(crate) const CSR_MVENDORID_ADDRESS: u16 = 0xf11;
+pub(crate) const CSR_MHARTID_ADDRESS: u16 = 0xf14;
+pub(crate) const CSR_MCONFIGPTR_ADDRESS: u16 = 0xf15;
+
+/// Offset from the lower half CSRs, like `cycle`, to the upper half CSRs,
+/// like `cycleh`, holding the upper 32 bits in 32-bit mode
+pub(crate) const CSR_UPPER_HALF_OFFSET: u16 = 0x80;
+
+/// `mstatus.FS` value indicating the floating-point state is modified
+pub(crate) const MSTATUS_FS_DIRTY: u64 = 0x6000;
+/// `mstatus.VS`, the vector state. Off (zero) disables vector instructions.
+pub(crate) const MSTATUS_VS: u64 = 0x600;
+/// `mstatus.VS` value indicating the vector state is modified
+pub(crate) const MSTATUS_VS_DIRTY: u64 = 0x600;
+/// `mstatus.MPP`, the privilege mode before the trap into Machine mode
+pub(crate) const MSTATUS_MPP: u64 = 0x1800;
+/// `mstatus.MPRV`, making loads and stores in Machine mode performed
+/// in the privilege mode of `MPP`
+pub(crate) const MSTATUS_MPRV: u64 = 0x20000;
+/// `mstatus.SUM`, permitting Supervisor mode to access user pages
+pub(crate) const MSTATUS_SUM: u64 = 0x40000;
+/// `mstatus.MXR`, making executable pages readable
+pub(crate) const MSTATUS_MXR: u64 = 0x80000;
+/// `mstatus.TVM`, trapping `satp` accesses in Supervisor mode
+pub(crate) const MSTATUS_TVM: u64 = 0x100000;
+/// `mstatus.UXL` and `mstatus.SXL` fixed to 64-bit in 64-bit mode
+pub(crate) const MSTATUS_UXL_SXL_64: u64 = 0xa00000000;
+/// `mstatus` bits software can write. MIE, SIE, MPIE, SPIE, SPP, VS, MPP,
+/// FS, MPRV, SUM, MXR, TVM, TW, and TSR.
+const MSTATUS_WRITE_MASK: u64 = 0x7e7faa;
+/// `mstatus` bits visible in `sstatus`
+pub(crate) const SSTATUS_MASK: u64 = 0x80000003000de762;
+/// `sstatus` bits software can write. SIE, SPIE, SPP, VS, FS, SUM, and MXR.
+const SSTATUS_WRITE_MASK: u64 = 0xc6722;
+
+/// Supervisor interrupt bits (SSIP, STIP, and SEIP) of `mip` and `mie`
+pub(crate) const SUPERVISOR_INTERRUPTS: u64 = 0x222;
+/// Interrupt bits of `mie` software can enable
+const MIE_WRITE_MASK: u64 = 0xaaa;
+/// Supervisor software interrupt, the only `sip` bit software can write
+const SIP_WRITE_MASK: u64 = 0x2;
+/// Exceptions Machine mode can delegate. All but environment call from
+/// Machine mode and the reserved causes.
+const MEDELEG_WRITE_MASK: u64 = 0xb3ff;
+/// `xenvcfg.FIOM`. The other fields are for unimplemented extensions
+/// or depend on the extensions in the ISA string.
+const ENVCFG_WRITE_MASK: u64 = 0x1;
+/// `menvcfg.ADUE` of the Svadu extension, enabling hardware updates of
+/// the A and D bits of page table entries
+pub(crate) const MENVCFG_ADUE: u64 = 1 << 61;
+/// `menvcfg.STCE` of the Sstc extension, enabling `stimecmp`
+pub(crate) const MENVCFG_STCE: u64 = 1 << 63;
+/// `mcounteren.TM`, permitting lower privilege modes to read `time`
+pub(crate) const COUNTEREN_TM: u64 = 0x2;
+
+/// Indicates whether the CSR belongs to the vector extension.
+///
+/// # Arguments
+/// * `address`
+pub(crate) fn is_vector(address: u16) -> bool {
+    matches!(
+        address,
+        CSR_VSTART_ADDRESS..=CSR_VXRM_ADDRESS
+            | CSR_VCSR_ADDRESS
+            | CSR_VL_ADDRESS..=CSR_VLENB_ADDRESS
+    )
+}
+
+/// Indicates whether the CSR is read-only, encoded in the address.
+///
+/// # Arguments
+/// * `address`
+pub(crate) fn is_read_only(address: u16) -> bool {
+    ((address >> 10) & 0x3) == 0x3
+}
+
+/// Returns the bits software can write to the CSR with the CSR instructions.
+/// The other bits keep their values, so zero means writes are ignored.
+/// Returns `None` if the CSR isn't implemented for the XLEN.
+///
+/// # Arguments
+/// * `address`
+/// * `xlen`
+pub(crate) fn get_write_mask(address: u16, xlen: &Xlen) -> Option<u64> {
+    let is_32bit = matches!(xlen, Xlen::Bit32);
+    let mask = match address {
+        CSR_FFLAGS_ADDRESS => 0x1f,
+        CSR_FRM_ADDRESS => 0x7,
+        CSR_FCSR_ADDRESS => 0xff,
+        // Wide enough for the element indices of VLEN up to 65536 bits
+        CSR_VSTART_ADDRESS => 0xffff,
+        CSR_VXSAT_ADDRESS => 0x1,
+        CSR_VXRM_ADDRESS => 0x3,
+        CSR_VCSR_ADDRESS => 0x7,
+        CSR_SSTATUS_ADDRESS => SSTATUS_WRITE_MASK,
+        CSR_SIE_ADDRESS | CSR_MIDELEG_ADDRESS | CSR_MIP_ADDRESS => SUPERVISOR_INTERRUPTS,
+        // Vectored and direct modes. The reserved modes are WARL.
+        CSR_STVEC_ADDRESS | CSR_MTVEC_ADDRESS => !0x2,
+        CSR_SCOUNTEREN_ADDRESS | CSR_MCOUNTEREN_ADDRESS => 0xffffffff,
+        CSR_SENVCFG_ADDRESS | CSR_MENVCFG_ADDRESS => ENVCFG_WRITE_MASK,
+        CSR_SSCRATCH_ADDRESS | CSR_MSCRATCH_ADDRESS => u64::MAX,
+        CSR_SCAUSE_ADDRESS | CSR_MCAUSE_ADDRESS => u64::MAX,
+        CSR_STVAL_ADDRESS | CSR_MTVAL_ADDRESS => u64::MAX,
+        // Instructions are aligned to 16 bits with the C extension
+        CSR_SEPC_ADDRESS | CSR_MEPC_ADDRESS => !0x1,
+        CSR_SIP_ADDRESS => SIP_WRITE_MASK,
+        // stimecmp exists only with the Sstc extension
+        CSR_STIMECMP_ADDRESS => u64::MAX,
+        CSR_STIMECMPH_ADDRESS if is_32bit => u64::MAX,
+        // MODE and PPN. ASID isn't supported, hardwired to zero.
+        CSR_SATP_ADDRESS => match is_32bit {
+            true => 0x803fffff,
+            false => 0xf0000fffffffffff,
+        },
+        CSR_MSTATUS_ADDRESS => MSTATUS_WRITE_MASK,
+        // Extensions can't be turned off and MXL is fixed
+        CSR_MISA_ADDRESS => 0,
+        CSR_MEDELEG_ADDRESS => MEDELEG_WRITE_MASK,
+        CSR_MIE_ADDRESS => MIE_WRITE_MASK,
+        // Big endian memory accesses aren't supported. menvcfgh has
+        // the fields of the extensions only.
+        CSR_MSTATUSH_ADDRESS | CSR_MENVCFGH_ADDRESS if is_32bit => 0,
+        // Time isn't inhibited, mcountinhibit.TM is hardwired to zero
+        CSR_MCOUNTINHIBIT_ADDRESS => 0xfffffffd,
+        CSR_MHPMEVENT3_ADDRESS..=CSR_MHPMEVENT31_ADDRESS => u64::MAX,
+        // pmpcfg with odd number exists only in 32-bit mode. The bits 6:5
+        // of each entry are reserved.
+        CSR_PMPCFG0_ADDRESS..=CSR_PMPCFG15_ADDRESS if is_32bit || address.is_multiple_of(2) => {
+            0x9f9f9f9f9f9f9f9f
+        }
+        // Bits 55:2 of physical address, 33:2 in 32-bit mode
+        CSR_PMPADDR0_ADDRESS..=CSR_PMPADDR63_ADDRESS => 0x3fffffffffffff,
+        CSR_MCYCLE_ADDRESS | CSR_MINSTRET_ADDRESS => u64::MAX,
+        CSR_MHPMCOUNTER3_ADDRESS..=CSR_MHPMCOUNTER31_ADDRESS => u64::MAX,
+        CSR_MCYCLEH_ADDRESS..=CSR_MHPMCOUNTER31H_ADDRESS
+            if is_32bit && address != CSR_MCYCLEH_ADDRESS + 1 =>
+        {
+            u64::MAX
+        }
+        CSR_CYCLE_ADDRESS..=CSR_HPMCOUNTER31_ADDRESS => 0,
+        CSR_VL_ADDRESS..=CSR_VLENB_ADDRESS => 0,
+        CSR_CYCLEH_ADDRESS..=CSR_HPMCOUNTER31H_ADDRESS if is_32bit => 0,
+        CSR_MVENDORID_ADDRESS..=CSR_MCONFIGPTR_ADDRESS => 0,
+        _ => return None,
+    };
+    Some(match is_32bit {
+        true => mask & 0xffffffff,
+        false => mask,
+    })
+}
@@ -0,0 +1,122 @@
+//! Table-driven instruction decoder. Instructions are described by a mask
+//! and the data the masked word must match. The decoder buckets them by
+//! `opcode` and `funct3`, and the crowded buckets again by `funct7`, so a
+//! decode only tests the few instructions sharing those fields.
+
+/// Bits 6:0 `opcode` and 14:12 `funct3` indexing the primary table
+const PRIMARY_MASK: u32 = 0x0000707f;
+const PRIMARY_ENTRY_NUM: usize = 1 << 10;
+
+/// Bits 31:25 `funct7` indexing a secondary table
+const SECONDARY_MASK: u32 = 0xfe000000;
+const SECONDARY_ENTRY_NUM: usize = 1 << 7;
+
+/// Buckets with more candidates than this are split by `funct7`
+const SPLIT_THRESHOLD: usize = 8;
+
+/// An entry of the primary table
+#[derive(Clone, Copy)]
+enum DecoderEntry {
+    /// Range of `candidates`, start and end exclusive
+    Candidates(u32, u32),
+    /// Start of the secondary table in `secondary`
+    Table(u32),
+}
+
+/// Decoder generated from instruction patterns. Candidates keep the order
+/// of the patterns so the first matching pattern wins, as in a linear scan.
+pub(crate) struct Decoder {
+    primary: Vec<DecoderEntry>,
+    /// Ranges of `candidates`, start and end exclusive
+    secondary: Vec<(u32, u32)>,
+    /// Pattern indices of all the buckets
+    candidates: Vec<u16>,
+    /// Masks and data of the patterns
+    patterns: Vec<(u32, u32)>,
+}
+
+impl Decoder {
+    /// Creates a new `Decoder`.
+    ///
+    /// # Arguments
+    /// * `patterns` Masks and data of the instructions, in priority order
+    pub(crate) fn new(patterns: impl Iterator<Item = (u32, u32)>) -> Self {
+        let mut decoder = Decoder {
+            primary: Vec::with_capacity(PRIMARY_ENTRY_NUM),
+            secondary: vec![],
+            candidates: vec![],
+            patterns: patterns.collect(),
+        };
+        for key in 0..PRIMARY_ENTRY_NUM as u32 {
+            let word = (key & 0x7f) | ((key >> 7) << 12);
+            let bucket = decoder.bucket(word, PRIMARY_MASK, 0..decoder.patterns.len());
+            let is_split = bucket.len() > SPLIT_THRESHOLD
+                && bucket
+                    .iter()
+                    .any(|&index| decoder.patterns[index as usize].0 & SECONDARY_MASK != 0);
+            let entry = match is_split {
+                true => {
+                    let start = decoder.secondary.len() as u32;
+                    for funct7 in 0..SECONDARY_ENTRY_NUM as u32 {
+                        let sub_bucket = decoder.bucket(
+                            word | (funct7 << 25),
+                            PRIMARY_MASK | SECONDARY_MASK,
+                            bucket.iter().map(|&index| index as usize),
+                        );
+                        let range = decoder.push_candidates(&sub_bucket);
+                        decoder.secondary.push(range);
+                    }
+                    DecoderEntry::Table(start)
+                }
+                false => {
+                    let (start, end) = decoder.push_candidates(&bucket);
+                    DecoderEntry::Candidates(start, end)
+                }
+            };
+            decoder.primary.push(entry);
+        }
+        decoder
+    }
+
+    /// Returns the indices of the patterns which can match words having
+    /// the bits of `word` selected by `key_mask`.
+    ///
+    /// # Arguments
+    /// * `word`
+    /// * `key_mask` Bits of `word` the bucket is keyed by
+    /// * `indices` Pattern indices to choose from
+    fn bucket(&self, word: u32, key_mask: u32, indices: impl Iterator<Item = usize>) -> Vec<u16> {
+        indices
+            .filter(|&index| {
+                let (mask, data) = self.patterns[index];
+                (word ^ data) & mask & key_mask == 0
+            })
+            .map(|index| index as u16)
+            .collect()
+    }
+
+    fn push_candidates(&mut self, bucket: &[u16]) -> (u32, u32) {
+        let start = self.candidates.len() as u32;
+        self.candidates.extend_from_slice(bucket);
+        (start, self.candidates.len() as u32)
+    }
+
+    /// Decodes a word instruction data and returns the index of the first
+    /// pattern it matches.
+    ///
+    /// # Arguments
+    /// * `word` word instruction data decoded
+    pub(crate) fn decode(&self, word: u32) -> Option<usize> {
+        let (start, end) = match self.primary[(((word >> 5) & 0x380) | (word & 0x7f)) as usize] {
+            DecoderEntry::Candidates(start, end) => (start, end),
+            DecoderEntry::Table(start) => self.secondary[(start + (word >> 25)) as usize],
+        };
+        self.candidates[start as usize..end as usize]
+            .iter()
+            .map(|&index| index as usize)
+            .find(|&index| {
+                let (mask, data) = self.patterns[index];
+                word & mask == data
+            })
+    }
+}
@@ -0,0 +1,87 @@
+use crate::cpu::Xlen;
+use crate::error::EmulatorError;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
+
+/// Offsets of the jump target and the device tree address in the ROM
+const ENTRY_OFFSET: usize = 0x18;
+const DTB_ADDRESS_OFFSET: usize = 0x20;
+const ROM_CONTENT_SIZE: usize = 0x28;
+
+/// Emulates reset vector ROM compatible with the one QEMU virt machine has.
+/// Harts start from it. It follows the standard RISC-V boot convention,
+/// sets `a0` to the hart ID and `a1` to the device tree physical address,
+/// then jumps to the entry point of the program.
+pub struct BootRom {
+    content: Vec<u8>,
+}
+
+impl Default for BootRom {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BootRom {
+    /// Creates a new empty `BootRom`. Reading it returns zero
+    /// until `init()` is called.
+    pub fn new() -> Self {
+        Self { content: vec![] }
+    }
+
+    /// Writes the boot code.
+    ///
+    /// # Arguments
+    /// * `xlen` Decides loading the addresses with `lw` or `ld`
+    /// * `entry` Address the boot code jumps to
+    /// * `dtb_address` Device tree physical address passed in `a1`
+    pub fn init(&mut self, xlen: &Xlen, entry: u64, dtb_address: u64) {
+        let (load_dtb_address, load_entry) = match xlen {
+            Xlen::Bit32 => (0x0202a583, 0x0182a283), // lw a1, 32(t0); lw t0, 24(t0)
+            Xlen::Bit64 => (0x0202b583, 0x0182b283), // ld a1, 32(t0); ld t0, 24(t0)
+        };
+        let code: [u32; 6] = [
+            0x00000297, // auipc t0, 0
+            0xf1402573, // csrr a0, mhartid
+            load_dtb_address,
+            load_entry,
+            0x00028067, // jr t0
+            0,
+        ];
+        self.content = vec![0; ROM_CONTENT_SIZE];
+        for (i, instruction) in code.iter().enumerate() {
+            self.content[i * 4..i * 4 + 4].copy_from_slice(&instruction.to_le_bytes());
+        }
+        self.content[ENTRY_OFFSET..ENTRY_OFFSET + 8].copy_from_slice(&entry.to_le_bytes());
+        self.content[DTB_ADDRESS_OFFSET..DTB_ADDRESS_OFFSET + 8]
+            .copy_from_slice(&dtb_address.to_le_bytes());
+    }
+
+    /// Loads ROM content
+    ///
+    /// # Arguments
+    /// * `address` Offset from the device base address
+    pub fn load(&self, address: u64) -> u8 {
+        match self.content.get(address as usize) {
+            Some(value) => *value,
+            None => 0,
+        }
+    }
+
+    /// Saves the ROM content to a snapshot.
+    pub(crate) fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.write_bytes(&self.content);
+    }
+
+    /// Restores the ROM content saved with `save_state()`.
+    pub(crate) fn restore_state(
+        &mut self,
+        reader: &mut SnapshotReader,
+    ) -> Result<(), EmulatorError> {
+        let content = reader.read_bytes()?;
+        if !content.is_empty() && content.len() != ROM_CONTENT_SIZE {
+            return Err(EmulatorError::InvalidSnapshot);
+        }
+        self.content = content.to_vec();
+        Ok(())
+    }
+}
@@ -1,45 +1,60 @@
 use crate::cpu::{MIP_MSIP, MIP_MTIP};
+use crate::error::EmulatorError;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
 
 /// Emulates CLINT known as Timer. Refer to the [specification](https://sifive.cdn.prismic.io/sifive%2Fc89f6e5a-cf9e-44c3-a3db-04420702dcc1_sifive+e31+manual+v19.08.pdf)
-/// for the detail.
+/// for the detail. Each hart has its own `msip` and `mtimecmp` registers
+/// and all the harts share `mtime`.
 pub struct Clint {
     clock: u64,
-    msip: u32,
-    mtimecmp: u64,
+    msip: Vec<u32>,
+    mtimecmp: Vec<u64>,
     mtime: u64,
 }
 
+/// Offsets of the register arrays, indexed by hart ID, and `mtime`
+const MSIP_BASE: u64 = 0x0000;
+const MTIMECMP_BASE: u64 = 0x4000;
+const MTIME_BASE: u64 = 0xbff8;
+
 impl Default for Clint {
     fn default() -> Self {
-        Self::new()
+        Self::new(1)
     }
 }
 
 impl Clint {
     /// Creates a new `Clint`
-    pub fn new() -> Self {
+    ///
+    /// # Arguments
+    /// * `hart_count`
+    pub fn new(hart_count: usize) -> Self {
         Self {
             clock: 0,
-            msip: 0,
-            mtimecmp: 0,
+            msip: vec![0; hart_count],
+            mtimecmp: vec![0; hart_count],
             mtime: 0, // @TODO: Should be bound to csr time register
         }
     }
 
-    /// Runs one cycle. `Clint` can raise interrupt. If it does it rises a certain bit
-    /// depending on interrupt type of CPU `mip` register.
-    ///
-    /// # Arguments
-    /// * `mip` CPU `mip` register. It can be updated if interrupt occurs.
-    pub fn tick(&mut self, mip: &mut u64) {
+    /// Runs one cycle. Called once for all the harts.
+    pub fn tick(&mut self) {
         self.clock = self.clock.wrapping_add(1);
         self.mtime = self.mtime.wrapping_add(1);
+    }
 
-        if (self.msip & 1) != 0 {
+    /// Raises the interrupts of a hart. If `Clint` raises an interrupt
+    /// a certain bit depending on interrupt type of CPU `mip` register rises.
+    ///
+    /// # Arguments
+    /// * `hart_id`
+    /// * `mip` CPU `mip` register of the hart. It can be updated if interrupt occurs.
+    pub fn raise_interrupts(&self, hart_id: usize, mip: &mut u64) {
+        if (self.msip[hart_id] & 1) != 0 {
             *mip |= MIP_MSIP;
         }
 
-        if self.mtimecmp > 0 && self.mtime >= self.mtimecmp {
+        if self.mtimecmp[hart_id] > 0 && self.mtime >= self.mtimecmp[hart_id] {
             *mip |= MIP_MTIP;
         }
     }
@@ -47,32 +62,28 @@ impl Clint {
     /// Loads register content.
     ///
     /// # Arguments
-    /// * `address`
+    /// * `address` Offset from the device base address
     pub fn load(&self, address: u64) -> u8 {
         //println!("CLINT Load AD:{:X}", address);
         match address {
-            // MSIP register 4 bytes
-            0x02000000 => (self.msip & 0xff) as u8,
-            0x02000001 => ((self.msip >> 8) & 0xff) as u8,
-            0x02000002 => ((self.msip >> 16) & 0xff) as u8,
-            0x02000003 => ((self.msip >> 24) & 0xff) as u8,
+            // MSIP registers 4 bytes
+            MSIP_BASE..MTIMECMP_BASE => {
+                let hart_id = ((address - MSIP_BASE) / 4) as usize;
+                match self.msip.get(hart_id) {
+                    Some(msip) => (msip >> ((address % 4) * 8)) as u8,
+                    None => 0,
+                }
+            }
             // MTIMECMP Registers 8 bytes
-            0x02004000 => self.mtimecmp as u8,
-            0x02004001 => (self.mtimecmp >> 8) as u8,
-            0x02004002 => (self.mtimecmp >> 16) as u8,
-            0x02004003 => (self.mtimecmp >> 24) as u8,
-            0x02004004 => (self.mtimecmp >> 32) as u8,
-            0x02004005 => (self.mtimecmp >> 40) as u8,
-            0x02004006 => (self.mtimecmp >> 48) as u8,
-            0x02004007 => (self.mtimecmp >> 56) as u8,
-            0x0200bff8 => self.mtime as u8,
-            0x0200bff9 => (self.mtime >> 8) as u8,
-            0x0200bffa => (self.mtime >> 16) as u8,
-            0x0200bffb => (self.mtime >> 24) as u8,
-            0x0200bffc => (self.mtime >> 32) as u8,
-            0x0200bffd => (self.mtime >> 40) as u8,
-            0x0200bffe => (self.mtime >> 48) as u8,
-            0x0200bfff => (self.mtime >> 56) as u8,
+            MTIMECMP_BASE..MTIME_BASE => {
+                let hart_id = ((address - MTIMECMP_BASE) / 8) as usize;
+                match self.mtimecmp.get(hart_id) {
+                    Some(mtimecmp) => (mtimecmp >> ((address % 8) * 8)) as u8,
+                    None => 0,
+                }
+            }
+            // MTIME registers 8 bytes
+            MTIME_BASE..=0xbfff => (self.mtime >> ((address - MTIME_BASE) * 8)) as u8,
             _ => 0,
         }
     }
@@ -80,64 +91,28 @@ impl Clint {
     /// Stores register content.
     ///
     /// # Arguments
-    /// * `address`
+    /// * `address` Offset from the device base address
     /// * `value`
     pub fn store(&mut self, address: u64, value: u8) {
         //println!("CLINT Store AD:{:X} VAL:{:X}", address, value);
         match address {
-            // MSIP register 4 bytes. Upper 31 bits are hardwired to zero.
-            0x02000000 => {
-                self.msip = (self.msip & !0x1) | ((value & 1) as u32);
+            // MSIP registers 4 bytes. Upper 31 bits are hardwired to zero.
+            MSIP_BASE..MTIMECMP_BASE if address.is_multiple_of(4) => {
+                let hart_id = ((address - MSIP_BASE) / 4) as usize;
+                if let Some(msip) = self.msip.get_mut(hart_id) {
+                    *msip = (*msip & !0x1) | ((value & 1) as u32);
+                }
             }
             // MTIMECMP Registers 8 bytes
-            0x02004000 => {
-                self.mtimecmp = (self.mtimecmp & !0xff) | (value as u64);
-            }
-            0x02004001 => {
-                self.mtimecmp = (self.mtimecmp & !(0xff << 8)) | ((value as u64) << 8);
-            }
-            0x02004002 => {
-                self.mtimecmp = (self.mtimecmp & !(0xff << 16)) | ((value as u64) << 16);
-            }
-            0x02004003 => {
-                self.mtimecmp = (self.mtimecmp & !(0xff << 24)) | ((value as u64) << 24);
-            }
-            0x02004004 => {
-                self.mtimecmp = (self.mtimecmp & !(0xff << 32)) | ((value as u64) << 32);
-            }
-            0x02004005 => {
-                self.mtimecmp = (self.mtimecmp & !(0xff << 40)) | ((value as u64) << 40);
-            }
-            0x02004006 => {
-                self.mtimecmp = (self.mtimecmp & !(0xff << 48)) | ((value as u64) << 48);
-            }
-            0x02004007 => {
-                self.mtimecmp = (self.mtimecmp & !(0xff << 56)) | ((value as u64) << 56);
+            MTIMECMP_BASE..MTIME_BASE => {
+                let hart_id = ((address - MTIMECMP_BASE) / 8) as usize;
+                if let Some(mtimecmp) = self.mtimecmp.get_mut(hart_id) {
+                    *mtimecmp = replace_byte(*mtimecmp, address % 8, value);
+                }
             }
             // MTIME registers 8 bytes
-            0x0200bff8 => {
-                self.mtime = (self.mtime & !0xff) | (value as u64);
-            }
-            0x0200bff9 => {
-                self.mtime = (self.mtime & !(0xff << 8)) | ((value as u64) << 8);
-            }
-            0x0200bffa => {
-                self.mtime = (self.mtime & !(0xff << 16)) | ((value as u64) << 16);
-            }
-            0x0200bffb => {
-                self.mtime = (self.mtime & !(0xff << 24)) | ((value as u64) << 24);
-            }
-            0x0200bffc => {
-                self.mtime = (self.mtime & !(0xff << 32)) | ((value as u64) << 32);
-            }
-            0x0200bffd => {
-                self.mtime = (self.mtime & !(0xff << 40)) | ((value as u64) << 40);
-            }
-            0x0200bffe => {
-                self.mtime = (self.mtime & !(0xff << 48)) | ((value as u64) << 48);
-            }
-            0x0200bfff => {
-                self.mtime = (self.mtime & !(0xff << 56)) | ((value as u64) << 56);
+            MTIME_BASE..=0xbfff => {
+                self.mtime = replace_byte(self.mtime, address - MTIME_BASE, value);
             }
             _ => {}
         };
@@ -148,8 +123,42 @@ impl Clint {
         self.mtime
     }
 
-    /// Writes to `mtime` register content
-    pub fn write_mtime(&mut self, value: u64) {
-        self.mtime = value;
+    /// Saves the register state to a snapshot.
+    pub(crate) fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.write_u64(self.clock);
+        for msip in &self.msip {
+            writer.write_u32(*msip);
+        }
+        for mtimecmp in &self.mtimecmp {
+            writer.write_u64(*mtimecmp);
+        }
+        writer.write_u64(self.mtime);
+    }
+
+    /// Restores the register state saved with `save_state()`.
+    pub(crate) fn restore_state(
+        &mut self,
+        reader: &mut SnapshotReader,
+    ) -> Result<(), EmulatorError> {
+        self.clock = reader.read_u64()?;
+        for msip in self.msip.iter_mut() {
+            *msip = reader.read_u32()?;
+        }
+        for mtimecmp in self.mtimecmp.iter_mut() {
+            *mtimecmp = reader.read_u64()?;
+        }
+        self.mtime = reader.read_u64()?;
+        Ok(())
     }
 }
+
+/// Returns the value with the byte at `index` replaced.
+///
+/// # Arguments
+/// * `value`
+/// * `index` Byte position from the least significant one
+/// * `byte`
+fn replace_byte(value: u64, index: u64, byte: u8) -> u64 {
+    let shift = index * 8;
+    (value & !(0xff << shift)) | ((byte as u64) << shift)
+}
@@ -1,4 +1,6 @@
+pub mod boot_rom;
 pub mod clint;
 pub mod plic;
+pub mod test_finisher;
 pub mod uart;
 pub mod virtio_block_disk;
@@ -1,57 +1,77 @@
 //! Based on SiFive Interrupt Cookbook
 //! https://sifive.cdn.prismic.io/sifive/0d163928-2128-42be-a75a-464df65e04e0_sifive-interrupt-cookbook.pdf
 
-use crate::cpu::MIP_SEIP;
+use crate::cpu::{MIP_MEIP, MIP_SEIP};
+use crate::error::EmulatorError;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
 
 /// Emulates PLIC known as Interrupt Controller.
 /// Refer to the [specification](https://sifive.cdn.prismic.io/sifive%2Fc89f6e5a-cf9e-44c3-a3db-04420702dcc1_sifive+e31+manual+v19.08.pdf)
-/// for the detail.
+/// for the detail. Each hart has two contexts, Machine mode context
+/// `2 * hart ID` raising machine external interrupt and Supervisor mode
+/// context `2 * hart ID + 1` raising supervisor external interrupt.
 pub struct Plic {
     clock: u64,
-    irq: u32,
-    enabled: u64,
-    threshold: u32,
     ips: [u8; 1024],
     priorities: [u32; 1024],
     needs_update_irq: bool,
     virtio_ip_cache: bool,
+    contexts: Vec<PlicContext>,
+}
+
+/// Per context registers
+#[derive(Clone, Default)]
+struct PlicContext {
+    irq: u32,
+    enabled: u64,
+    threshold: u32,
+    /// Whether the interrupt needs raising at the next `raise_interrupts()`
+    interrupting: bool,
 }
 
 // @TODO: IRQ numbers should be configurable with device tree
-const VIRTIO_IRQ: u32 = 1;
-const UART_IRQ: u32 = 10;
+pub(crate) const VIRTIO_IRQ: u32 = 1;
+pub(crate) const UART_IRQ: u32 = 10;
+
+/// The number of interrupt sources advertised in the device tree
+pub(crate) const PLIC_SOURCE_COUNT: u32 = 53;
+
+/// Offsets of the per context register arrays
+const ENABLE_BASE: u64 = 0x002000;
+const ENABLE_STRIDE: u64 = 0x80;
+const CONTEXT_BASE: u64 = 0x200000;
+const CONTEXT_STRIDE: u64 = 0x1000;
 
 impl Default for Plic {
     fn default() -> Self {
-        Self::new()
+        Self::new(1)
     }
 }
 
 impl Plic {
     /// Creates a new `Plic`.
-    pub fn new() -> Self {
+    ///
+    /// # Arguments
+    /// * `hart_count`
+    pub fn new(hart_count: usize) -> Self {
         Plic {
             clock: 0,
-            irq: 0,
-            enabled: 0,
-            threshold: 0,
             priorities: [0; 1024],
             ips: [0; 1024],
             needs_update_irq: false,
             virtio_ip_cache: false,
+            contexts: vec![PlicContext::default(); hart_count * 2],
         }
     }
 
     /// Runs one cycle. Takes interrupting signals from devices and
-    /// raises an interrupt to CPU depending on configuration.
-    /// If interrupt occurs a certain bit of `mip` regiser is risen
-    /// depending on interrupt type.
+    /// decides the interrupts raised to each context depending on
+    /// configuration. Called once for all the harts.
     ///
     /// # Arguments
     /// * `virtio_ip`
     /// * `uart_ip`
-    /// * `mip`
-    pub fn tick(&mut self, virtio_ip: bool, uart_ip: bool, mip: &mut u64) {
+    pub fn tick(&mut self, virtio_ip: bool, uart_ip: bool) {
         self.clock = self.clock.wrapping_add(1);
 
         // Handling interrupts as "Edge-triggered" interrupt so far
@@ -73,12 +93,29 @@ impl Plic {
         }
 
         if self.needs_update_irq {
-            self.update_irq(mip);
+            self.update_irq();
             self.needs_update_irq = false;
         }
     }
 
-    fn update_irq(&mut self, mip: &mut u64) {
+    /// Raises the interrupts of a hart decided at `tick()`. If an interrupt
+    /// is raised a certain bit of `mip` register is risen depending on
+    /// interrupt type.
+    ///
+    /// # Arguments
+    /// * `hart_id`
+    /// * `mip` CPU `mip` register of the hart
+    pub fn raise_interrupts(&mut self, hart_id: usize, mip: &mut u64) {
+        for (mode, bit) in [MIP_MEIP, MIP_SEIP].iter().enumerate() {
+            let context = &mut self.contexts[hart_id * 2 + mode];
+            if context.interrupting {
+                *mip |= bit;
+                context.interrupting = false;
+            }
+        }
+    }
+
+    fn update_irq(&mut self) {
         // Hardcoded VirtIO and UART
         // @TODO: Should be configurable with device tree
 
@@ -90,75 +127,94 @@ impl Plic {
         let virtio_priority = self.priorities[VIRTIO_IRQ as usize];
         let uart_priority = self.priorities[UART_IRQ as usize];
 
-        let virtio_enabled = ((self.enabled >> VIRTIO_IRQ) & 1) == 1;
-        let uart_enabled = ((self.enabled >> UART_IRQ) & 1) == 1;
-
         let ips = [virtio_ip, uart_ip];
-        let enables = [virtio_enabled, uart_enabled];
         let priorities = [virtio_priority, uart_priority];
         let irqs = [VIRTIO_IRQ, UART_IRQ];
 
-        let mut irq = 0;
-        let mut priority = 0;
-        for i in 0..2 {
-            if ips[i] && enables[i] && priorities[i] > self.threshold && priorities[i] > priority {
-                irq = irqs[i];
-                priority = priorities[i];
+        for context in self.contexts.iter_mut() {
+            let mut irq = 0;
+            let mut priority = 0;
+            for i in 0..2 {
+                let enabled = ((context.enabled >> irqs[i]) & 1) == 1;
+                if ips[i]
+                    && enabled
+                    && priorities[i] > context.threshold
+                    && priorities[i] > priority
+                {
+                    irq = irqs[i];
+                    priority = priorities[i];
+                }
             }
-        }
 
-        self.irq = irq;
-        if self.irq != 0 {
-            //println!("IRQ: {:X}", self.irq);
-            *mip |= MIP_SEIP;
+            context.irq = irq;
+            if context.irq != 0 {
+                //println!("IRQ: {:X}", context.irq);
+                context.interrupting = true;
+            }
         }
     }
 
     fn set_ip(&mut self, irq: u32) {
         let index = (irq >> 3) as usize;
-        self.ips[index] |= 1 << irq;
+        self.ips[index] |= 1 << (irq & 7);
         self.needs_update_irq = true;
     }
 
     fn clear_ip(&mut self, irq: u32) {
         let index = (irq >> 3) as usize;
-        self.ips[index] &= !(1 << irq);
+        self.ips[index] &= !(1 << (irq & 7));
         self.needs_update_irq = true;
     }
 
+    /// Returns the context and the offset in its registers if the address
+    /// points a register of an existing context.
+    ///
+    /// # Arguments
+    /// * `address` Offset from the device base address
+    /// * `base` Base address of the register array
+    /// * `stride` Size of the registers of each context
+    fn get_context(&self, address: u64, base: u64, stride: u64) -> Option<(usize, u64)> {
+        let index = ((address - base) / stride) as usize;
+        match index < self.contexts.len() {
+            true => Some((index, (address - base) % stride)),
+            false => None,
+        }
+    }
+
     /// Loads register content
     ///
     /// # Arguments
-    /// * `address`
+    /// * `address` Offset from the device base address
     pub fn load(&self, address: u64) -> u8 {
         //println!("PLIC Load AD:{:X}", address);
         match address {
-            0x0c000000..=0x0c000fff => {
+            0x000000..=0x000fff => {
                 let offset = address % 4;
-                let index = ((address - 0xc000000) >> 2) as usize;
+                let index = ((address) >> 2) as usize;
                 let pos = offset << 3;
                 (self.priorities[index] >> pos) as u8
             }
-            0x0c001000..=0x0c00107f => {
-                let index = (address - 0xc001000) as usize;
+            0x001000..=0x00107f => {
+                let index = (address - 0x001000) as usize;
                 self.ips[index]
             }
-            0x0c002080 => self.enabled as u8,
-            0x0c002081 => (self.enabled >> 8) as u8,
-            0x0c002082 => (self.enabled >> 16) as u8,
-            0x0c002083 => (self.enabled >> 24) as u8,
-            0x0c002084 => (self.enabled >> 32) as u8,
-            0x0c002085 => (self.enabled >> 40) as u8,
-            0x0c002086 => (self.enabled >> 48) as u8,
-            0x0c002087 => (self.enabled >> 56) as u8,
-            0x0c201000 => self.threshold as u8,
-            0x0c201001 => (self.threshold >> 8) as u8,
-            0x0c201002 => (self.threshold >> 16) as u8,
-            0x0c201003 => (self.threshold >> 24) as u8,
-            0x0c201004 => self.irq as u8,
-            0x0c201005 => (self.irq >> 8) as u8,
-            0x0c201006 => (self.irq >> 16) as u8,
-            0x0c201007 => (self.irq >> 24) as u8,
+            ENABLE_BASE..CONTEXT_BASE => {
+                match self.get_context(address, ENABLE_BASE, ENABLE_STRIDE) {
+                    // Only first 64 interrupt sources support so far.
+                    Some((index, offset)) if offset < 8 => {
+                        (self.contexts[index].enabled >> (offset * 8)) as u8
+                    }
+                    _ => 0,
+                }
+            }
+            CONTEXT_BASE.. => match self.get_context(address, CONTEXT_BASE, CONTEXT_STRIDE) {
+                Some((index, offset)) => match offset {
+                    0..=3 => (self.contexts[index].threshold >> (offset * 8)) as u8,
+                    4..=7 => (self.contexts[index].irq >> ((offset - 4) * 8)) as u8,
+                    _ => 0,
+                },
+                None => 0,
+            },
             _ => 0,
         }
     }
@@ -166,14 +222,14 @@ impl Plic {
     /// Stores register content
     ///
     /// # Arguments
-    /// * `address`
+    /// * `address` Offset from the device base address
     /// * `value`
     pub fn store(&mut self, address: u64, value: u8) {
         //println!("PLIC Store AD:{:X} VAL:{:X}", address, value);
         match address {
-            0x0c000000..=0x0c000fff => {
+            0x000000..=0x000fff => {
                 let offset = address % 4;
-                let index = ((address - 0xc000000) >> 2) as usize;
+                let index = ((address) >> 2) as usize;
                 let pos = offset << 3;
                 self.priorities[index] =
                     (self.priorities[index] & !(0xff << pos)) | ((value as u32) << pos);
@@ -181,51 +237,83 @@ impl Plic {
             }
             // Enable. Only first 64 interrupt sources support so far.
             // @TODO: Implement all 1024 interrupt source enables.
-            0x0c002080 => {
-                self.enabled = (self.enabled & !0xff) | (value as u64);
-                self.needs_update_irq = true;
-            }
-            0x0c002081 => {
-                self.enabled = (self.enabled & !(0xff << 8)) | ((value as u64) << 8);
-            }
-            0x0c002082 => {
-                self.enabled = (self.enabled & !(0xff << 16)) | ((value as u64) << 16);
-            }
-            0x0c002083 => {
-                self.enabled = (self.enabled & !(0xff << 24)) | ((value as u64) << 24);
-            }
-            0x0c002084 => {
-                self.enabled = (self.enabled & !(0xff << 32)) | ((value as u64) << 32);
-            }
-            0x0c002085 => {
-                self.enabled = (self.enabled & !(0xff << 40)) | ((value as u64) << 40);
-            }
-            0x0c002086 => {
-                self.enabled = (self.enabled & !(0xff << 48)) | ((value as u64) << 48);
-            }
-            0x0c002087 => {
-                self.enabled = (self.enabled & !(0xff << 56)) | ((value as u64) << 56);
-            }
-            0x0c201000 => {
-                self.threshold = (self.threshold & !0xff) | (value as u32);
-                self.needs_update_irq = true;
-            }
-            0x0c201001 => {
-                self.threshold = (self.threshold & !(0xff << 8)) | ((value as u32) << 8);
+            ENABLE_BASE..CONTEXT_BASE => {
+                if let Some((index, offset)) = self.get_context(address, ENABLE_BASE, ENABLE_STRIDE)
+                {
+                    if offset < 8 {
+                        let context = &mut self.contexts[index];
+                        let shift = offset * 8;
+                        context.enabled =
+                            (context.enabled & !(0xff << shift)) | ((value as u64) << shift);
+                        self.needs_update_irq = true;
+                    }
+                }
             }
-            0x0c201002 => {
-                self.threshold = (self.threshold & !(0xff << 16)) | ((value as u32) << 16);
-            }
-            0x0c201003 => {
-                self.threshold = (self.threshold & !(0xff << 24)) | ((value as u32) << 24);
-            }
-            // Claim
-            0x0c201004 => {
-                // Assuming written data is a byte so far
-                // @TODO: Should be four bytes.
-                self.clear_ip(value as u32);
+            CONTEXT_BASE.. => {
+                if let Some((index, offset)) =
+                    self.get_context(address, CONTEXT_BASE, CONTEXT_STRIDE)
+                {
+                    match offset {
+                        0..=3 => {
+                            let context = &mut self.contexts[index];
+                            let shift = offset * 8;
+                            context.threshold =
+                                (context.threshold & !(0xff << shift)) | ((value as u32) << shift);
+                            self.needs_update_irq = true;
+                        }
+                        // Claim
+                        4 => {
+                            // Assuming written data is a byte so far
+                            // @TODO: Should be four bytes.
+                            self.clear_ip(value as u32);
+                        }
+                        _ => {}
+                    };
+                }
             }
             _ => {}
         };
     }
+
+    /// Saves the register state to a snapshot.
+    pub(crate) fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.write_u64(self.clock);
+        for context in &self.contexts {
+            writer.write_u32(context.irq);
+            writer.write_u64(context.enabled);
+            writer.write_u32(context.threshold);
+            writer.write_bool(context.interrupting);
+        }
+        for ip in &self.ips {
+            writer.write_u8(*ip);
+        }
+        for priority in &self.priorities {
+            writer.write_u32(*priority);
+        }
+        writer.write_bool(self.needs_update_irq);
+        writer.write_bool(self.virtio_ip_cache);
+    }
+
+    /// Restores the register state saved with `save_state()`.
+    pub(crate) fn restore_state(
+        &mut self,
+        reader: &mut SnapshotReader,
+    ) -> Result<(), EmulatorError> {
+        self.clock = reader.read_u64()?;
+        for context in self.contexts.iter_mut() {
+            context.irq = reader.read_u32()?;
+            context.enabled = reader.read_u64()?;
+            context.threshold = reader.read_u32()?;
+            context.interrupting = reader.read_bool()?;
+        }
+        for ip in self.ips.iter_mut() {
+            *ip = reader.read_u8()?;
+        }
+        for priority in self.priorities.iter_mut() {
+            *priority = reader.read_u32()?;
+        }
+        self.needs_update_irq = reader.read_bool()?;
+        self.virtio_ip_cache = reader.read_bool()?;
+        Ok(())
+    }
 }
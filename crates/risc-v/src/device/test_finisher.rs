@@ -0,0 +1,90 @@
+use crate::error::EmulatorError;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
+
+// Values written to the register, compatible with QEMU `sifive,test0`
+const FINISHER_FAIL: u32 = 0x3333;
+const FINISHER_PASS: u32 = 0x5555;
+
+/// Emulates test finisher (`sifive,test0` compatible) which QEMU virt machine
+/// has. Guest powers off the machine by writing to its register. Writing
+/// `0x5555` powers off with exit code zero, and writing `(code << 16) | 0x3333`
+/// powers off with exit code `code`.
+pub struct TestFinisher {
+    register: u32,
+    exit_code: Option<u32>,
+}
+
+impl Default for TestFinisher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestFinisher {
+    /// Creates a new `TestFinisher`.
+    pub fn new() -> Self {
+        Self {
+            register: 0,
+            exit_code: None,
+        }
+    }
+
+    /// Returns the exit code if the guest has requested power-off.
+    pub fn get_exit_code(&self) -> Option<u32> {
+        self.exit_code
+    }
+
+    /// Loads register content
+    ///
+    /// # Arguments
+    /// * `address` Offset from the device base address
+    pub fn load(&self, address: u64) -> u8 {
+        match address {
+            0x0..=0x3 => (self.register >> (address * 8)) as u8,
+            _ => 0,
+        }
+    }
+
+    /// Stores register content. The request is handled when the most
+    /// significant byte of the register is written because word stores
+    /// to devices are split into bytes in ascending address order.
+    ///
+    /// # Arguments
+    /// * `address` Offset from the device base address
+    /// * `value`
+    pub fn store(&mut self, address: u64, value: u8) {
+        if address > 0x3 {
+            return;
+        }
+        let pos = address * 8;
+        self.register = (self.register & !(0xff << pos)) | ((value as u32) << pos);
+        if address == 0x3 {
+            match self.register & 0xffff {
+                FINISHER_PASS => self.exit_code = Some(0),
+                FINISHER_FAIL => self.exit_code = Some(self.register >> 16),
+                // @TODO: Support reset request (0x7777)
+                _ => {}
+            };
+        }
+    }
+    /// Saves the register state to a snapshot.
+    pub(crate) fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.write_u32(self.register);
+        writer.write_option_u64(self.exit_code.map(u64::from));
+    }
+
+    /// Restores the register state saved with `save_state()`.
+    pub(crate) fn restore_state(
+        &mut self,
+        reader: &mut SnapshotReader,
+    ) -> Result<(), EmulatorError> {
+        self.register = reader.read_u32()?;
+        self.exit_code = match reader.read_option_u64()? {
+            Some(exit_code) => {
+                Some(u32::try_from(exit_code).map_err(|_| EmulatorError::InvalidSnapshot)?)
+            }
+            None => None,
+        };
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 use std::num::NonZeroU8;
 
+use crate::error::EmulatorError;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
 use crate::terminal::Terminal;
 
 const IER_RXINT_BIT: u8 = 0x1;
@@ -58,14 +60,17 @@ impl Uart {
 
     /// Runs one cycle. `Uart` gets/puts input/output data via `Terminal`
     /// at certain timing.
-    pub fn tick(&mut self) {
+    ///
+    /// # Arguments
+    /// * `cpu_clock` Passed to `Terminal` on input polling
+    pub fn tick(&mut self, cpu_clock: u64) {
         self.clock = self.clock.wrapping_add(1);
         let mut rx_ip = false;
 
         // Reads input.
         // 0x38400 is just an arbitrary number @TODO: Fix me
-        if (self.clock % 0x38400) == 0 && self.rbr == 0 {
-            let value = self.terminal.get_input();
+        if self.clock.is_multiple_of(0x38400) && self.rbr == 0 {
+            let value = self.terminal.get_input_at(cpu_clock);
             if let Some(value) = value {
                 self.rbr = value.get();
                 self.lsr |= LSR_DATA_AVAILABLE;
@@ -78,7 +83,7 @@ impl Uart {
 
         // Writes output.
         // 0x10 is just an arbitrary number @TODO: Fix me
-        if (self.clock % 0x10) == 0 {
+        if self.clock.is_multiple_of(0x10) {
             if let Some(thr) = NonZeroU8::new(self.thr) {
                 self.terminal.put_byte(thr);
                 self.thr = 0;
@@ -128,11 +133,11 @@ impl Uart {
     /// Loads register content
     ///
     /// # Arguments
-    /// * `address`
+    /// * `address` Offset from the device base address
     pub fn load(&mut self, address: u64) -> u8 {
         //println!("UART Load AD:{:X}", address);
         match address {
-            0x10000000 => match (self.lcr >> 7) == 0 {
+            0x00 => match (self.lcr >> 7) == 0 {
                 true => {
                     let rbr = self.rbr;
                     self.rbr = 0;
@@ -142,15 +147,15 @@ impl Uart {
                 }
                 false => 0, // @TODO: Implement properly
             },
-            0x10000001 => match (self.lcr >> 7) == 0 {
+            0x01 => match (self.lcr >> 7) == 0 {
                 true => self.ier,
                 false => 0, // @TODO: Implement properly
             },
-            0x10000002 => self.iir,
-            0x10000003 => self.lcr,
-            0x10000004 => self.mcr,
-            0x10000005 => self.lsr,
-            0x10000007 => self.scr,
+            0x02 => self.iir,
+            0x03 => self.lcr,
+            0x04 => self.mcr,
+            0x05 => self.lsr,
+            0x07 => self.scr,
             _ => 0,
         }
     }
@@ -160,39 +165,64 @@ impl Uart {
         //println!("UART Store AD:{:X} VAL:{:X}", address, value);
         match address {
             // Transfer Holding Register
-            0x10000000 => match (self.lcr >> 7) == 0 {
-                true => {
-                    self.thr = value;
-                    self.lsr &= !LSR_THR_EMPTY;
-                    self.update_iir();
-                }
-                false => {} // @TODO: Implement properly
-            },
-            0x10000001 => match (self.lcr >> 7) == 0 {
-                true => {
-                    // This bahavior isn't written in the data sheet
-                    // but some drivers seem to rely on it.
-                    if (self.ier & IER_THREINT_BIT) == 0
-                        && (value & IER_THREINT_BIT) != 0
-                        && self.thr == 0
-                    {
-                        self.thre_ip = true;
-                    }
-                    self.ier = value;
-                    self.update_iir();
+            // @TODO: Implement divisor latch access (DLAB = 1) properly
+            0x00 if (self.lcr >> 7) == 0 => {
+                self.thr = value;
+                self.lsr &= !LSR_THR_EMPTY;
+                self.update_iir();
+            }
+            0x01 if (self.lcr >> 7) == 0 => {
+                // This bahavior isn't written in the data sheet
+                // but some drivers seem to rely on it.
+                if (self.ier & IER_THREINT_BIT) == 0
+                    && (value & IER_THREINT_BIT) != 0
+                    && self.thr == 0
+                {
+                    self.thre_ip = true;
                 }
-                false => {} // @TODO: Implement properly
-            },
-            0x10000003 => {
+                self.ier = value;
+                self.update_iir();
+            }
+            0x03 => {
                 self.lcr = value;
             }
-            0x10000004 => {
+            0x04 => {
                 self.mcr = value;
             }
-            0x10000007 => {
+            0x07 => {
                 self.scr = value;
             }
             _ => {}
         };
     }
+    /// Saves the register state to a snapshot. `Terminal` isn't saved.
+    pub(crate) fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.write_u64(self.clock);
+        for register in [
+            self.rbr, self.thr, self.ier, self.iir, self.lcr, self.mcr, self.lsr, self.scr,
+        ] {
+            writer.write_u8(register);
+        }
+        writer.write_bool(self.thre_ip);
+        writer.write_bool(self.interrupting);
+    }
+
+    /// Restores the register state saved with `save_state()`.
+    pub(crate) fn restore_state(
+        &mut self,
+        reader: &mut SnapshotReader,
+    ) -> Result<(), EmulatorError> {
+        self.clock = reader.read_u64()?;
+        self.rbr = reader.read_u8()?;
+        self.thr = reader.read_u8()?;
+        self.ier = reader.read_u8()?;
+        self.iir = reader.read_u8()?;
+        self.lcr = reader.read_u8()?;
+        self.mcr = reader.read_u8()?;
+        self.lsr = reader.read_u8()?;
+        self.scr = reader.read_u8()?;
+        self.thre_ip = reader.read_bool()?;
+        self.interrupting = reader.read_bool()?;
+        Ok(())
+    }
 }
@@ -1,4 +1,6 @@
-use crate::mmu::MemoryWrapper;
+use crate::bus::MemoryWrapper;
+use crate::error::EmulatorError;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
 
 // Based on Virtual I/O Device (VIRTIO) Version 1.1
 // https://docs.oasis-open.org/virtio/virtio/v1.1/csprd01/virtio-v1.1-csprd01.html
@@ -18,6 +20,9 @@ const VIRTQ_DESC_F_WRITE: u16 = 2;
 
 const SECTOR_SIZE: u64 = 512;
 
+// Disk capacity in sectors advertised in the device configuration space.
+const CAPACITY_SECTORS: u64 = 0x32000;
+
 /// Emulates Virtio Block device. Refer to the [specification](https://docs.oasis-open.org/virtio/virtio/v1.1/csprd01/virtio-v1.1-csprd01.html)
 /// for the detail. It follows legacy API.
 pub struct VirtioBlockDisk {
@@ -73,6 +78,11 @@ impl VirtioBlockDisk {
         (self.interrupt_status & 0x1) == 1
     }
 
+    /// Returns the disk capacity in bytes.
+    pub fn capacity(&self) -> u64 {
+        CAPACITY_SECTORS * SECTOR_SIZE
+    }
+
     /// Initializes filesystem content. The method is expected to be called
     /// only up to once.
     ///
@@ -80,10 +90,7 @@ impl VirtioBlockDisk {
     /// * `contents` filesystem content binary
     pub fn init(&mut self, contents: Vec<u8>) {
         // @TODO: Optimize
-        self.contents.resize(
-            (contents.len() + 7) / 8,
-            0,
-        );
+        self.contents.resize(contents.len().div_ceil(8), 0);
         for (i, content) in contents.iter().enumerate() {
             let index = i >> 3;
             let pos = (i % 8) * 8;
@@ -114,64 +121,58 @@ impl VirtioBlockDisk {
     /// Loads register content
     ///
     /// # Arguments
-    /// * `address`
+    /// * `address` Offset from the device base address
     pub fn load(&mut self, address: u64) -> u8 {
         //println!("Disk Load AD:{:X}", address);
         match address {
             // Magic number: 0x74726976
-            0x10001000 => 0x76,
-            0x10001001 => 0x69,
-            0x10001002 => 0x72,
-            0x10001003 => 0x74,
+            0x000 => 0x76,
+            0x001 => 0x69,
+            0x002 => 0x72,
+            0x003 => 0x74,
             // Device version: 1 (Legacy device)
-            0x10001004 => 1,
+            0x004 => 1,
             // Virtio Subsystem Device id: 2 (Block device)
-            0x10001008 => 2,
+            0x008 => 2,
             // Virtio Subsystem Vendor id: 0x554d4551
-            0x1000100c => 0x51,
-            0x1000100d => 0x45,
-            0x1000100e => 0x4d,
-            0x1000100f => 0x55,
+            0x00c => 0x51,
+            0x00d => 0x45,
+            0x00e => 0x4d,
+            0x00f => 0x55,
             // Flags representing features the device supports
-            0x10001010 => ((self.device_features >> (self.device_features_sel * 32)) & 0xff) as u8,
-            0x10001011 => {
+            0x010 => ((self.device_features >> (self.device_features_sel * 32)) & 0xff) as u8,
+            0x011 => {
                 (((self.device_features >> (self.device_features_sel * 32)) >> 8) & 0xff) as u8
             }
-            0x10001012 => {
+            0x012 => {
                 (((self.device_features >> (self.device_features_sel * 32)) >> 16) & 0xff) as u8
             }
-            0x10001013 => {
+            0x013 => {
                 (((self.device_features >> (self.device_features_sel * 32)) >> 24) & 0xff) as u8
             }
             // Maximum virtual queue size
-            0x10001034 => MAX_QUEUE_SIZE as u8,
-            0x10001035 => (MAX_QUEUE_SIZE >> 8) as u8,
-            0x10001036 => (MAX_QUEUE_SIZE >> 16) as u8,
-            0x10001037 => (MAX_QUEUE_SIZE >> 24) as u8,
+            0x034 => MAX_QUEUE_SIZE as u8,
+            0x035 => (MAX_QUEUE_SIZE >> 8) as u8,
+            0x036 => (MAX_QUEUE_SIZE >> 16) as u8,
+            0x037 => (MAX_QUEUE_SIZE >> 24) as u8,
             // Guest physical page number of the virtual queue
-            0x10001040 => self.queue_pfn as u8,
-            0x10001041 => (self.queue_pfn >> 8) as u8,
-            0x10001042 => (self.queue_pfn >> 16) as u8,
-            0x10001043 => (self.queue_pfn >> 24) as u8,
+            0x040 => self.queue_pfn as u8,
+            0x041 => (self.queue_pfn >> 8) as u8,
+            0x042 => (self.queue_pfn >> 16) as u8,
+            0x043 => (self.queue_pfn >> 24) as u8,
             // Interrupt status
-            0x10001060 => self.interrupt_status as u8,
-            0x10001061 => (self.interrupt_status >> 8) as u8,
-            0x10001062 => (self.interrupt_status >> 16) as u8,
-            0x10001063 => (self.interrupt_status >> 24) as u8,
+            0x060 => self.interrupt_status as u8,
+            0x061 => (self.interrupt_status >> 8) as u8,
+            0x062 => (self.interrupt_status >> 16) as u8,
+            0x063 => (self.interrupt_status >> 24) as u8,
             // Device status
-            0x10001070 => self.status as u8,
-            0x10001071 => (self.status >> 8) as u8,
-            0x10001072 => (self.status >> 16) as u8,
-            0x10001073 => (self.status >> 24) as u8,
+            0x070 => self.status as u8,
+            0x071 => (self.status >> 8) as u8,
+            0x072 => (self.status >> 16) as u8,
+            0x073 => (self.status >> 24) as u8,
             // Configurations @TODO: Implement properly
-            0x10001100 => 0x00,
-            0x10001101 => 0x20,
-            0x10001102 => 0x03,
-            0x10001103 => 0,
-            0x10001104 => 0,
-            0x10001105 => 0,
-            0x10001106 => 0,
-            0x10001107 => 0,
+            // Capacity in sectors
+            0x100..=0x107 => (CAPACITY_SECTORS >> ((address - 0x100) * 8)) as u8,
             _ => 0,
         }
     }
@@ -179,145 +180,191 @@ impl VirtioBlockDisk {
     /// Stores register content
     ///
     /// # Arguments
-    /// * `address`
+    /// * `address` Offset from the device base address
     /// * `value`
     pub fn store(&mut self, address: u64, value: u8) {
         //println!("Disk Store AD:{:X} VAL:{:X}", address, value);
         match address {
-            0x10001014 => {
+            0x014 => {
                 self.device_features_sel = (self.device_features_sel & !0xff) | (value as u32);
             }
-            0x10001015 => {
+            0x015 => {
                 self.device_features_sel =
                     (self.device_features_sel & !(0xff << 8)) | ((value as u32) << 8);
             }
-            0x10001016 => {
+            0x016 => {
                 self.device_features_sel =
                     (self.device_features_sel & !(0xff << 16)) | ((value as u32) << 16);
             }
-            0x10001017 => {
+            0x017 => {
                 self.device_features_sel =
                     (self.device_features_sel & !(0xff << 24)) | ((value as u32) << 24);
             }
-            0x10001020 => {
+            0x020 => {
                 self.driver_features = (self.driver_features & !0xff) | (value as u32);
             }
-            0x10001021 => {
+            0x021 => {
                 self.driver_features =
                     (self.driver_features & !(0xff << 8)) | ((value as u32) << 8);
             }
-            0x10001022 => {
+            0x022 => {
                 self.driver_features =
                     (self.driver_features & !(0xff << 16)) | ((value as u32) << 16);
             }
-            0x10001023 => {
+            0x023 => {
                 self.driver_features =
                     (self.driver_features & !(0xff << 24)) | ((value as u32) << 24);
             }
-            0x10001028 => {
+            0x028 => {
                 self.guest_page_size = (self.guest_page_size & !0xff) | (value as u32);
             }
-            0x10001029 => {
+            0x029 => {
                 self.guest_page_size =
                     (self.guest_page_size & !(0xff << 8)) | ((value as u32) << 8);
             }
-            0x1000102a => {
+            0x02a => {
                 self.guest_page_size =
                     (self.guest_page_size & !(0xff << 16)) | ((value as u32) << 16);
             }
-            0x1000102b => {
+            0x02b => {
                 self.guest_page_size =
                     (self.guest_page_size & !(0xff << 24)) | ((value as u32) << 24);
             }
-            0x10001030 => {
+            0x030 => {
                 self.queue_select = (self.queue_select & !0xff) | (value as u32);
             }
-            0x10001031 => {
+            0x031 => {
                 self.queue_select = (self.queue_select & !(0xff << 8)) | ((value as u32) << 8);
             }
-            0x10001032 => {
+            0x032 => {
                 self.queue_select = (self.queue_select & !(0xff << 16)) | ((value as u32) << 16);
             }
-            0x10001033 => {
+            0x033 => {
+                // @TODO: Multi queue support. Only the queue 0 works so far.
                 self.queue_select = (self.queue_select & !(0xff << 24)) | ((value as u32) << 24);
-                if self.queue_select != 0 {
-                    panic!("Virtio: No multi queue support yet.");
-                }
             }
-            0x10001038 => {
+            0x038 => {
                 self.queue_size = (self.queue_size & !0xff) | (value as u32);
             }
-            0x10001039 => {
+            0x039 => {
                 self.queue_size = (self.queue_size & !(0xff << 8)) | ((value as u32) << 8);
             }
-            0x1000103a => {
+            0x03a => {
                 self.queue_size = (self.queue_size & !(0xff << 16)) | ((value as u32) << 16);
             }
-            0x1000103b => {
+            0x03b => {
                 self.queue_size = (self.queue_size & !(0xff << 24)) | ((value as u32) << 24);
             }
-            0x1000103c => {
+            0x03c => {
                 self.queue_align = (self.queue_align & !0xff) | (value as u32);
             }
-            0x1000103d => {
+            0x03d => {
                 self.queue_align = (self.queue_align & !(0xff << 8)) | ((value as u32) << 8);
             }
-            0x1000103e => {
+            0x03e => {
                 self.queue_align = (self.queue_align & !(0xff << 16)) | ((value as u32) << 16);
             }
-            0x1000103f => {
+            0x03f => {
                 self.queue_align = (self.queue_align & !(0xff << 24)) | ((value as u32) << 24);
             }
-            0x10001040 => {
+            0x040 => {
                 self.queue_pfn = (self.queue_pfn & !0xff) | (value as u32);
             }
-            0x10001041 => {
+            0x041 => {
                 self.queue_pfn = (self.queue_pfn & !(0xff << 8)) | ((value as u32) << 8);
             }
-            0x10001042 => {
+            0x042 => {
                 self.queue_pfn = (self.queue_pfn & !(0xff << 16)) | ((value as u32) << 16);
             }
-            0x10001043 => {
+            0x043 => {
                 self.queue_pfn = (self.queue_pfn & !(0xff << 24)) | ((value as u32) << 24);
             }
             // @TODO: Queue request support
-            0x10001050 => {
+            0x050 => {
                 self.queue_notify = (self.queue_notify & !0xff) | (value as u32);
             }
-            0x10001051 => {
+            0x051 => {
                 self.queue_notify = (self.queue_notify & !(0xff << 8)) | ((value as u32) << 8);
             }
-            0x10001052 => {
+            0x052 => {
                 self.queue_notify = (self.queue_notify & !(0xff << 16)) | ((value as u32) << 16);
             }
-            0x10001053 => {
+            0x053 => {
                 self.queue_notify = (self.queue_notify & !(0xff << 24)) | ((value as u32) << 24);
                 self.notify_clocks.push(self.clock);
             }
-            0x10001064 => {
+            0x064 => {
                 // interrupt ack
-                if (value & 0x1) == 1 {
-                    self.interrupt_status &= !0x1;
-                } else {
-                    panic!("Unknown ack {:X}", value);
-                }
+                self.interrupt_status &= !(value as u32);
             }
-            0x10001070 => {
+            0x070 => {
                 self.status = (self.status & !0xff) | (value as u32);
             }
-            0x10001071 => {
+            0x071 => {
                 self.status = (self.status & !(0xff << 8)) | ((value as u32) << 8);
             }
-            0x10001072 => {
+            0x072 => {
                 self.status = (self.status & !(0xff << 16)) | ((value as u32) << 16);
             }
-            0x10001073 => {
+            0x073 => {
                 self.status = (self.status & !(0xff << 24)) | ((value as u32) << 24);
             }
             _ => {}
         };
     }
 
+    /// Saves the register state, the pending requests and the disk content
+    /// to a snapshot.
+    pub(crate) fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.write_u16(self.used_ring_index);
+        writer.write_u64(self.clock);
+        writer.write_u64(self.device_features);
+        writer.write_u32(self.device_features_sel);
+        writer.write_u32(self.driver_features);
+        writer.write_u32(self._driver_features_sel);
+        writer.write_u32(self.guest_page_size);
+        writer.write_u32(self.queue_select);
+        writer.write_u32(self.queue_size);
+        writer.write_u32(self.queue_align);
+        writer.write_u32(self.queue_pfn);
+        writer.write_u32(self.queue_notify);
+        writer.write_u32(self.interrupt_status);
+        writer.write_u32(self.status);
+        writer.write_u64(self.notify_clocks.len() as u64);
+        for clock in &self.notify_clocks {
+            writer.write_u64(*clock);
+        }
+        writer.write_sparse_words(&self.contents);
+    }
+
+    /// Restores the state saved with `save_state()`.
+    pub(crate) fn restore_state(
+        &mut self,
+        reader: &mut SnapshotReader,
+    ) -> Result<(), EmulatorError> {
+        self.used_ring_index = reader.read_u16()?;
+        self.clock = reader.read_u64()?;
+        self.device_features = reader.read_u64()?;
+        self.device_features_sel = reader.read_u32()?;
+        self.driver_features = reader.read_u32()?;
+        self._driver_features_sel = reader.read_u32()?;
+        self.guest_page_size = reader.read_u32()?;
+        self.queue_select = reader.read_u32()?;
+        self.queue_size = reader.read_u32()?;
+        self.queue_align = reader.read_u32()?;
+        self.queue_pfn = reader.read_u32()?;
+        self.queue_notify = reader.read_u32()?;
+        self.interrupt_status = reader.read_u32()?;
+        self.status = reader.read_u32()?;
+        let notify_count = reader.read_u64()?;
+        self.notify_clocks.clear();
+        for _ in 0..notify_count {
+            self.notify_clocks.push(reader.read_u64()?);
+        }
+        let max_words = (self.capacity() / 8) as usize;
+        reader.read_sparse_words(&mut self.contents, max_words)
+    }
+
     /// Fast path of transferring the data from disk to memory.
     ///
     /// # Arguments
@@ -333,17 +380,17 @@ impl VirtioBlockDisk {
         length: u64,
     ) {
         debug_assert!(
-            (mem_address % 8) == 0,
+            mem_address.is_multiple_of(8),
             "Memory address should be eight-byte aligned. {:X}",
             mem_address
         );
         debug_assert!(
-            (disk_address % 8) == 0,
+            disk_address.is_multiple_of(8),
             "Disk address should be eight-byte aligned. {:X}",
             disk_address
         );
         debug_assert!(
-            (length % 8) == 0,
+            length.is_multiple_of(8),
             "Length should be eight-byte aligned. {:X}",
             length
         );
@@ -368,17 +415,17 @@ impl VirtioBlockDisk {
         length: u64,
     ) {
         debug_assert!(
-            (mem_address % 8) == 0,
+            mem_address.is_multiple_of(8),
             "Memory address should be eight-byte aligned. {:X}",
             mem_address
         );
         debug_assert!(
-            (disk_address % 8) == 0,
+            disk_address.is_multiple_of(8),
             "Disk address should be eight-byte aligned. {:X}",
             disk_address
         );
         debug_assert!(
-            (length % 8) == 0,
+            length.is_multiple_of(8),
             "Length should be eight-byte aligned. {:X}",
             length
         );
@@ -457,7 +504,7 @@ impl VirtioBlockDisk {
     fn get_base_used_address(&self) -> u64 {
         let align = self.queue_align as u64;
         let queue_size = self.queue_size as u64;
-        ((self.get_base_avail_address() + 4 + queue_size * 2 + align - 1) / align) * align
+        (self.get_base_avail_address() + 4 + queue_size * 2).div_ceil(align) * align
     }
 
     // @TODO: Follow the virtio block specification more propertly.
@@ -530,9 +577,9 @@ impl VirtioBlockDisk {
                     match (desc_flags & VIRTQ_DESC_F_WRITE) == 0 {
                         true => {
                             // write to disk
-                            if (desc_addr % 8) == 0
-                                && ((blk_sector * SECTOR_SIZE) % 8) == 0
-                                && (desc_len % 8) == 0
+                            if desc_addr.is_multiple_of(8)
+                                && (blk_sector * SECTOR_SIZE).is_multiple_of(8)
+                                && desc_len.is_multiple_of(8)
                             {
                                 // Enter fast path if possible
                                 self.transfer_to_disk(
@@ -550,9 +597,9 @@ impl VirtioBlockDisk {
                         }
                         false => {
                             // read from disk
-                            if (desc_addr % 8) == 0
-                                && ((blk_sector * SECTOR_SIZE) % 8) == 0
-                                && (desc_len % 8) == 0
+                            if desc_addr.is_multiple_of(8)
+                                && (blk_sector * SECTOR_SIZE).is_multiple_of(8)
+                                && desc_len.is_multiple_of(8)
                             {
                                 // Enter fast path if possible
                                 self.transfer_from_disk(
@@ -570,14 +617,9 @@ impl VirtioBlockDisk {
                         }
                     };
                 }
-                2 => {
-                    // Third descriptor: Result status
-                    if (desc_flags & VIRTQ_DESC_F_WRITE) == 0 {
-                        panic!("Third descriptor should be write.");
-                    }
-                    if desc_len != 1 {
-                        panic!("Third descriptor length should be one.");
-                    }
+                // Third descriptor: Result status. Malformed one is
+                // ignored because the driver can't see the result anyway.
+                2 if (desc_flags & VIRTQ_DESC_F_WRITE) != 0 && desc_len == 1 => {
                     memory.write_byte(desc_addr, 0); // 0 means succeeded
                 }
                 _ => {}
@@ -590,9 +632,8 @@ impl VirtioBlockDisk {
             }
         }
 
-        if desc_num != 3 {
-            panic!("Descript chain length should be three.");
-        }
+        // @TODO: Descriptor chains other than three descriptors aren't
+        // supported. They're just returned to the used ring.
 
         memory.write_word(
             base_used_address
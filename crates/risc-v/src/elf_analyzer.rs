@@ -0,0 +1,183 @@
+use elf::abi::{EM_RISCV, ET_DYN, PT_LOAD, R_RISCV_NONE, R_RISCV_RELATIVE, SHT_RELA};
+use elf::endian::AnyEndian;
+use elf::file::Class;
+use elf::ElfBytes;
+
+use crate::cpu::Xlen;
+use crate::error::EmulatorError;
+
+/// A loadable (`PT_LOAD`) segment placed at its load address.
+pub struct Segment<'a> {
+    /// Physical address the segment is loaded to
+    pub address: u64,
+    /// Content read from the file (`p_filesz` bytes)
+    pub data: &'a [u8],
+    /// Size the segment occupies in memory (`p_memsz` bytes).
+    /// The bytes following `data` up to this size are zero-filled (BSS).
+    pub memory_size: u64,
+}
+
+/// A relocation to be applied after the segments are loaded.
+pub struct Relocation {
+    /// Physical address to be patched
+    pub address: u64,
+    /// XLEN-wide value to be stored at `address`
+    pub value: u64,
+}
+
+/// Analyzes a RISC-V ELF file and tells where and how its contents are loaded.
+/// Loading is based on program headers so stripped binaries without
+/// section headers are supported.
+pub struct ElfAnalyzer<'a> {
+    elf: ElfBytes<'a, AnyEndian>,
+}
+
+impl<'a> ElfAnalyzer<'a> {
+    /// Creates a new `ElfAnalyzer`. Returns `Err` if the passed content
+    /// doesn't seem RISC-V ELF file.
+    ///
+    /// # Arguments
+    /// * `content` ELF file content
+    pub fn new(content: &'a [u8]) -> Result<Self, EmulatorError> {
+        let elf = ElfBytes::<AnyEndian>::minimal_parse(content)?;
+        if elf.ehdr.e_machine != EM_RISCV {
+            return Err(EmulatorError::UnsupportedMachine(elf.ehdr.e_machine));
+        }
+        Ok(ElfAnalyzer { elf })
+    }
+
+    /// Returns XLEN the program is built for.
+    pub fn get_xlen(&self) -> Xlen {
+        match self.elf.ehdr.class {
+            Class::ELF32 => Xlen::Bit32,
+            Class::ELF64 => Xlen::Bit64,
+        }
+    }
+
+    /// Indicates whether the program is position independent (`ET_DYN`)
+    /// and then can be loaded at an arbitrary base address.
+    pub fn is_position_independent(&self) -> bool {
+        self.elf.ehdr.e_type == ET_DYN
+    }
+
+    /// Returns the offset to be added to the addresses in the file
+    /// to place the lowest segment at `base`. Only position independent
+    /// programs can be moved, the offset is always zero for the others.
+    ///
+    /// # Arguments
+    /// * `base` Address the lowest segment is loaded to
+    pub fn get_load_bias(&self, base: u64) -> Result<u64, EmulatorError> {
+        if !self.is_position_independent() {
+            return Ok(0);
+        }
+        let lowest_address = self
+            .get_program_headers()?
+            .iter()
+            .map(|phdr| phdr.p_vaddr)
+            .min()
+            .unwrap_or(0);
+        Ok(base.wrapping_sub(lowest_address))
+    }
+
+    /// Returns the loadable segments sorted by address. Returns `Err`
+    /// if segments overlap each other.
+    ///
+    /// # Arguments
+    /// * `bias` Offset returned by `get_load_bias()`
+    pub fn get_segments(&self, bias: u64) -> Result<Vec<Segment<'a>>, EmulatorError> {
+        let mut segments = vec![];
+        for phdr in self.get_program_headers()? {
+            // Position independent programs are loaded by virtual address
+            // while the others are loaded by physical address like QEMU does.
+            let address = match self.is_position_independent() {
+                true => phdr.p_vaddr.wrapping_add(bias),
+                false => phdr.p_paddr,
+            };
+            let data = self.elf.segment_data(&phdr)?;
+            let memory_size = phdr.p_memsz.max(phdr.p_filesz);
+            if memory_size == 0 {
+                continue;
+            }
+            if address.checked_add(memory_size).is_none() {
+                return Err(EmulatorError::SegmentOutOfMemory {
+                    address,
+                    size: memory_size,
+                });
+            }
+            segments.push(Segment {
+                address,
+                data,
+                memory_size,
+            });
+        }
+        segments.sort_by_key(|segment| segment.address);
+        for pair in segments.windows(2) {
+            if pair[0].address + pair[0].memory_size > pair[1].address {
+                return Err(EmulatorError::OverlappingSegments {
+                    address: pair[1].address,
+                });
+            }
+        }
+        Ok(segments)
+    }
+
+    /// Returns the physical address where the program starts.
+    /// If the entry point is in a segment whose virtual and physical
+    /// addresses differ, the physical one is returned.
+    ///
+    /// # Arguments
+    /// * `bias` Offset returned by `get_load_bias()`
+    pub fn get_entry_point(&self, bias: u64) -> Result<u64, EmulatorError> {
+        let entry = self.elf.ehdr.e_entry;
+        if self.is_position_independent() {
+            return Ok(entry.wrapping_add(bias));
+        }
+        for phdr in self.get_program_headers()? {
+            if entry >= phdr.p_vaddr && entry - phdr.p_vaddr < phdr.p_memsz {
+                return Ok(phdr.p_paddr + (entry - phdr.p_vaddr));
+            }
+        }
+        Ok(entry)
+    }
+
+    /// Returns the relocations to be applied to a position independent program.
+    /// Only `R_RISCV_RELATIVE` is supported because programs needing symbol
+    /// resolution require a dynamic linker. Returns `Err` for other types.
+    ///
+    /// # Arguments
+    /// * `bias` Offset returned by `get_load_bias()`
+    pub fn get_relocations(&self, bias: u64) -> Result<Vec<Relocation>, EmulatorError> {
+        let mut relocations = vec![];
+        if !self.is_position_independent() {
+            return Ok(relocations);
+        }
+        let section_headers = match self.elf.section_headers() {
+            Some(section_headers) => section_headers,
+            None => return Ok(relocations),
+        };
+        for section_header in section_headers.iter().filter(|s| s.sh_type == SHT_RELA) {
+            for rela in self.elf.section_data_as_relas(&section_header)? {
+                match rela.r_type {
+                    R_RISCV_NONE => {}
+                    R_RISCV_RELATIVE => relocations.push(Relocation {
+                        address: rela.r_offset.wrapping_add(bias),
+                        value: bias.wrapping_add(rela.r_addend as u64),
+                    }),
+                    r_type => return Err(EmulatorError::UnsupportedRelocation(r_type)),
+                };
+            }
+        }
+        Ok(relocations)
+    }
+
+    fn get_program_headers(&self) -> Result<Vec<elf::segment::ProgramHeader>, EmulatorError> {
+        let program_headers: Vec<_> = match self.elf.segments() {
+            Some(segments) => segments.iter().filter(|p| p.p_type == PT_LOAD).collect(),
+            None => vec![],
+        };
+        match program_headers.is_empty() {
+            true => Err(EmulatorError::NoLoadableSegment),
+            false => Ok(program_headers),
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Builds small ELF files for the tests loading programs and symbols.
+
+use elf::abi::{EM_RISCV, ET_DYN, ET_EXEC};
+
+/// Builds a 64-bit little endian RISC-V ELF file. The file has a section
+/// header for each segment, a relocation section if there are relocations,
+/// and symbol and string table sections if there are symbols.
+pub(crate) struct ElfBuilder {
+    machine: u16,
+    e_type: u16,
+    entry: u64,
+    /// Loadable segments: address, file content and memory size
+    segments: Vec<(u64, Vec<u8>, u64)>,
+    /// `R_RISCV_RELATIVE` relocations: offset and addend
+    relocations: Vec<(u64, i64)>,
+    /// Symbols defined in the first segment: name, value, size and `st_info`
+    symbols: Vec<(String, u64, u64, u8)>,
+    has_section_headers: bool,
+}
+
+impl ElfBuilder {
+    /// Creates a new `ElfBuilder` of an executable without segments.
+    ///
+    /// # Arguments
+    /// * `entry` `e_entry`
+    pub(crate) fn new(entry: u64) -> Self {
+        ElfBuilder {
+            machine: EM_RISCV,
+            e_type: ET_EXEC,
+            entry,
+            segments: vec![],
+            relocations: vec![],
+            symbols: vec![],
+            has_section_headers: true,
+        }
+    }
+
+    /// Creates a new `ElfBuilder` of an executable which has a single
+    /// loadable segment `data` placed at `address`, its entry point.
+    ///
+    /// # Arguments
+    /// * `address`
+    /// * `data`
+    pub(crate) fn program(address: u64, data: &[u8]) -> Self {
+        Self::new(address).segment(address, data, data.len() as u64)
+    }
+
+    /// Sets `e_machine`.
+    pub(crate) fn machine(mut self, machine: u16) -> Self {
+        self.machine = machine;
+        self
+    }
+
+    /// Makes the file a position independent executable, `ET_DYN`.
+    pub(crate) fn position_independent(mut self) -> Self {
+        self.e_type = ET_DYN;
+        self
+    }
+
+    /// Adds a loadable segment. Memory beyond the data is zero-filled.
+    ///
+    /// # Arguments
+    /// * `address`
+    /// * `data` File content
+    /// * `memory_size`
+    pub(crate) fn segment(mut self, address: u64, data: &[u8], memory_size: u64) -> Self {
+        self.segments.push((address, data.to_vec(), memory_size));
+        self
+    }
+
+    /// Adds an `R_RISCV_RELATIVE` relocation.
+    ///
+    /// # Arguments
+    /// * `offset`
+    /// * `addend`
+    pub(crate) fn relocation(mut self, offset: u64, addend: i64) -> Self {
+        self.relocations.push((offset, addend));
+        self
+    }
+
+    /// Adds a symbol defined in the first segment.
+    ///
+    /// # Arguments
+    /// * `name`
+    /// * `value`
+    /// * `size`
+    /// * `info` `st_info`, type and binding
+    pub(crate) fn symbol(mut self, name: &str, value: u64, size: u64, info: u8) -> Self {
+        self.symbols.push((name.to_string(), value, size, info));
+        self
+    }
+
+    /// Leaves section headers out. Symbols aren't written either.
+    pub(crate) fn without_section_headers(mut self) -> Self {
+        self.has_section_headers = false;
+        self
+    }
+
+    /// Returns the file content.
+    pub(crate) fn build(&self) -> Vec<u8> {
+        let align = |value: usize| (value + 7) & !7;
+        let mut data_offsets = vec![];
+        let mut offset = 64 + 56 * self.segments.len();
+        for (_, data, _) in self.segments.iter() {
+            data_offsets.push(offset as u64);
+            offset = align(offset + data.len());
+        }
+        let rela_offset = offset as u64;
+        offset += self.relocations.len() * 24;
+        // The first entry is the null symbol
+        let has_symbols = !self.symbols.is_empty();
+        let symtab_offset = align(offset) as u64;
+        let symtab_size = match has_symbols {
+            true => (self.symbols.len() + 1) * 24,
+            false => 0,
+        };
+        let mut strtab = vec![0];
+        for (name, _, _, _) in self.symbols.iter() {
+            strtab.extend_from_slice(name.as_bytes());
+            strtab.push(0);
+        }
+        let strtab_offset = symtab_offset + symtab_size as u64;
+        offset = strtab_offset as usize + has_symbols as usize * strtab.len();
+        let (sh_offset, sh_num) = match self.has_section_headers {
+            true => (
+                align(offset) as u64,
+                1 + self.segments.len()
+                    + (!self.relocations.is_empty()) as usize
+                    + 2 * has_symbols as usize,
+            ),
+            false => (0, 0),
+        };
+
+        let mut elf = vec![];
+        // File header
+        elf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+        elf.extend_from_slice(&[0; 8]);
+        elf.extend_from_slice(&self.e_type.to_le_bytes());
+        elf.extend_from_slice(&self.machine.to_le_bytes());
+        elf.extend_from_slice(&1u32.to_le_bytes()); // e_version
+        elf.extend_from_slice(&self.entry.to_le_bytes());
+        elf.extend_from_slice(&64u64.to_le_bytes()); // e_phoff
+        elf.extend_from_slice(&sh_offset.to_le_bytes());
+        elf.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+        elf.extend_from_slice(&64u16.to_le_bytes()); // e_ehsize
+        elf.extend_from_slice(&56u16.to_le_bytes()); // e_phentsize
+        elf.extend_from_slice(&(self.segments.len() as u16).to_le_bytes()); // e_phnum
+        elf.extend_from_slice(&64u16.to_le_bytes()); // e_shentsize
+        elf.extend_from_slice(&(sh_num as u16).to_le_bytes()); // e_shnum
+        elf.extend_from_slice(&0u16.to_le_bytes()); // e_shstrndx
+
+        // Program headers
+        for (i, (address, data, memory_size)) in self.segments.iter().enumerate() {
+            elf.extend_from_slice(&1u32.to_le_bytes()); // p_type: PT_LOAD
+            elf.extend_from_slice(&7u32.to_le_bytes()); // p_flags: RWX
+            elf.extend_from_slice(&data_offsets[i].to_le_bytes()); // p_offset
+            elf.extend_from_slice(&address.to_le_bytes()); // p_vaddr
+            elf.extend_from_slice(&address.to_le_bytes()); // p_paddr
+            elf.extend_from_slice(&(data.len() as u64).to_le_bytes()); // p_filesz
+            elf.extend_from_slice(&memory_size.to_le_bytes()); // p_memsz
+            elf.extend_from_slice(&0x1000u64.to_le_bytes()); // p_align
+        }
+        // Contents
+        for (i, (_, data, _)) in self.segments.iter().enumerate() {
+            elf.resize(data_offsets[i] as usize, 0);
+            elf.extend_from_slice(data);
+        }
+        elf.resize(rela_offset as usize, 0);
+        for (r_offset, r_addend) in self.relocations.iter() {
+            elf.extend_from_slice(&r_offset.to_le_bytes());
+            elf.extend_from_slice(&3u64.to_le_bytes()); // r_info: R_RISCV_RELATIVE
+            elf.extend_from_slice(&r_addend.to_le_bytes());
+        }
+        if !self.has_section_headers {
+            return elf;
+        }
+        if has_symbols {
+            elf.resize(symtab_offset as usize + 24, 0);
+            let mut name_offset = 1u32;
+            for (name, value, size, info) in self.symbols.iter() {
+                elf.extend_from_slice(&name_offset.to_le_bytes()); // st_name
+                elf.push(*info); // st_info
+                elf.push(0); // st_other
+                elf.extend_from_slice(&1u16.to_le_bytes()); // st_shndx
+                elf.extend_from_slice(&value.to_le_bytes()); // st_value
+                elf.extend_from_slice(&size.to_le_bytes()); // st_size
+                name_offset += name.len() as u32 + 1;
+            }
+            elf.extend_from_slice(&strtab);
+        }
+        // Section headers. The first one is the null section.
+        elf.resize(sh_offset as usize, 0);
+        elf.extend_from_slice(&[0; 64]);
+        let mut push_section_header =
+            |sh_type: u32, address: u64, offset: u64, size: u64, link: u32, entsize: u64| {
+                elf.extend_from_slice(&0u32.to_le_bytes()); // sh_name
+                elf.extend_from_slice(&sh_type.to_le_bytes());
+                elf.extend_from_slice(&2u64.to_le_bytes()); // sh_flags: ALLOC
+                elf.extend_from_slice(&address.to_le_bytes());
+                elf.extend_from_slice(&offset.to_le_bytes());
+                elf.extend_from_slice(&size.to_le_bytes());
+                elf.extend_from_slice(&link.to_le_bytes()); // sh_link
+                elf.extend_from_slice(&0u32.to_le_bytes()); // sh_info
+                elf.extend_from_slice(&8u64.to_le_bytes()); // sh_addralign
+                elf.extend_from_slice(&entsize.to_le_bytes());
+            };
+        for (i, (address, data, _)) in self.segments.iter().enumerate() {
+            // SHT_PROGBITS
+            push_section_header(1, *address, data_offsets[i], data.len() as u64, 0, 0);
+        }
+        if !self.relocations.is_empty() {
+            // SHT_RELA
+            let size = self.relocations.len() as u64 * 24;
+            push_section_header(4, 0, rela_offset, size, 0, 24);
+        }
+        if has_symbols {
+            // SHT_SYMTAB linked to the following SHT_STRTAB
+            let strtab_index = sh_num as u32 - 1;
+            push_section_header(2, 0, symtab_offset, symtab_size as u64, strtab_index, 24);
+            push_section_header(3, 0, strtab_offset, strtab.len() as u64, 0, 0);
+        }
+        elf
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+
+use elf::ParseError;
+
+use crate::mmu::AddressingMode;
+
+/// Error reported while setting up [`Emulator`](crate::Emulator) with
+/// a program, a filesystem or a device tree.
+#[derive(Debug)]
+pub enum EmulatorError {
+    /// The passed content isn't a well-formed ELF file.
+    InvalidElf(ParseError),
+    /// The ELF file targets a machine other than RISC-V. Holds `e_machine`.
+    UnsupportedMachine(u16),
+    /// The ELF file has no loadable (`PT_LOAD`) segment.
+    NoLoadableSegment,
+    /// A loadable segment is placed where no memory is mapped.
+    SegmentOutOfMemory { address: u64, size: u64 },
+    /// A loadable segment overlaps another one. Holds the higher segment address.
+    OverlappingSegments { address: u64 },
+    /// A load base address is specified for a program which isn't
+    /// position independent.
+    NotPositionIndependent,
+    /// The program requires a relocation type the loader doesn't support.
+    UnsupportedRelocation(u32),
+    /// A loadable part of the program is larger than the main memory.
+    ImageTooLarge { size: u64, capacity: u64 },
+    /// The device tree binary doesn't fit into the device tree area.
+    DtbTooLarge { size: u64, capacity: u64 },
+    /// The filesystem image is larger than the disk capacity.
+    DiskImageTooLarge { size: u64, capacity: u64 },
+    /// The ISA string in the machine configuration is malformed or
+    /// has unsupported extensions.
+    InvalidIsa(String),
+    /// The number of harts in the machine configuration isn't supported.
+    UnsupportedHartCount(usize),
+    /// The vector register length in the machine configuration isn't
+    /// a power of two in the supported range.
+    UnsupportedVlen(usize),
+    /// The MMU type in the machine configuration isn't defined for XLEN
+    /// of the ISA string.
+    UnsupportedMmuType(AddressingMode),
+    /// The main memory in the machine configuration is empty or exceeds
+    /// the address space.
+    InvalidMemoryRange { base: u64, size: u64 },
+    /// The main memory and devices in the machine configuration overlap.
+    /// Holds the higher address of the overlapping regions.
+    OverlappingMemoryMap { address: u64 },
+    /// The snapshot is truncated or malformed.
+    InvalidSnapshot,
+    /// The snapshot was saved in a format version this emulator can't read.
+    UnsupportedSnapshotVersion(u32),
+    /// The snapshot was saved from a machine configured differently,
+    /// e.g. with other memory size or device addresses.
+    SnapshotMismatch,
+    /// The input log to replay is malformed. Holds the line number.
+    InvalidInputLog { line: usize },
+    /// The device tree binary is malformed.
+    InvalidDtb,
+    /// The device tree address in the machine configuration isn't
+    /// 8-byte aligned address in the main memory.
+    InvalidDtbAddress(u64),
+    /// The content doesn't have RISC-V Linux kernel `Image` header.
+    InvalidKernelImage,
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmulatorError::InvalidElf(error) => {
+                write!(f, "This file does not seem to be an ELF file: {}", error)
+            }
+            EmulatorError::UnsupportedMachine(machine) => {
+                write!(
+                    f,
+                    "Unsupported ELF machine type {} (expected RISC-V)",
+                    machine
+                )
+            }
+            EmulatorError::NoLoadableSegment => write!(f, "No loadable segment is found"),
+            EmulatorError::SegmentOutOfMemory { address, size } => write!(
+                f,
+                "Segment {:X}-{:X} is out of the main memory",
+                address,
+                address.wrapping_add(*size)
+            ),
+            EmulatorError::OverlappingSegments { address } => {
+                write!(f, "Segment at {:X} overlaps another segment", address)
+            }
+            EmulatorError::NotPositionIndependent => write!(
+                f,
+                "The program is not position independent and can't be moved"
+            ),
+            EmulatorError::UnsupportedRelocation(r_type) => {
+                write!(f, "Unsupported relocation type {}", r_type)
+            }
+            EmulatorError::ImageTooLarge { size, capacity } => write!(
+                f,
+                "Program image size {:X} exceeds the memory capacity {:X}",
+                size, capacity
+            ),
+            EmulatorError::DtbTooLarge { size, capacity } => write!(
+                f,
+                "Device tree size {:X} exceeds the device tree area size {:X}",
+                size, capacity
+            ),
+            EmulatorError::DiskImageTooLarge { size, capacity } => write!(
+                f,
+                "Filesystem image size {:X} exceeds the disk capacity {:X}",
+                size, capacity
+            ),
+            EmulatorError::InvalidIsa(isa) => write!(f, "Invalid ISA string {}", isa),
+            EmulatorError::UnsupportedHartCount(count) => {
+                write!(f, "Unsupported hart count {}", count)
+            }
+            EmulatorError::UnsupportedVlen(vlen) => write!(f, "Unsupported VLEN {}", vlen),
+            EmulatorError::UnsupportedMmuType(mode) => {
+                write!(f, "Unsupported MMU type {}", mode.get_mmu_type())
+            }
+            EmulatorError::InvalidMemoryRange { base, size } => write!(
+                f,
+                "Invalid main memory range base {:X} size {:X}",
+                base, size
+            ),
+            EmulatorError::OverlappingMemoryMap { address } => {
+                write!(
+                    f,
+                    "Memory map region at {:X} overlaps another region",
+                    address
+                )
+            }
+            EmulatorError::InvalidSnapshot => write!(f, "The snapshot is broken"),
+            EmulatorError::UnsupportedSnapshotVersion(version) => {
+                write!(f, "Unsupported snapshot version {}", version)
+            }
+            EmulatorError::SnapshotMismatch => write!(
+                f,
+                "The snapshot was saved from a differently configured machine"
+            ),
+            EmulatorError::InvalidInputLog { line } => {
+                write!(f, "Invalid input log at line {}", line)
+            }
+            EmulatorError::InvalidDtb => write!(f, "The device tree binary is broken"),
+            EmulatorError::InvalidDtbAddress(address) => {
+                write!(f, "Invalid device tree address {:X}", address)
+            }
+            EmulatorError::InvalidKernelImage => {
+                write!(
+                    f,
+                    "This file does not seem to be a RISC-V Linux kernel Image"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for EmulatorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EmulatorError::InvalidElf(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for EmulatorError {
+    fn from(error: ParseError) -> Self {
+        EmulatorError::InvalidElf(error)
+    }
+}
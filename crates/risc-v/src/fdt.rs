@@ -0,0 +1,327 @@
+//! Flattened device tree (DTB) parser and serializer.
+//! Based on Devicetree Specification v0.4
+//! https://github.com/devicetree-org/devicetree-specification/releases/tag/v0.4
+
+use crate::error::EmulatorError;
+
+const FDT_MAGIC: u32 = 0xd00dfeed;
+const FDT_VERSION: u32 = 17;
+const FDT_LAST_COMPATIBLE_VERSION: u32 = 16;
+const FDT_HEADER_SIZE: usize = 40;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+/// A property of a device tree node. The value is raw big endian bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Property {
+    pub name: String,
+    pub value: Vec<u8>,
+}
+
+/// A device tree node
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    /// Node name with unit address, e.g. `uart@10000000`. Empty for the root.
+    pub name: String,
+    pub properties: Vec<Property>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    /// Creates a new `Node` without properties and children.
+    ///
+    /// # Arguments
+    /// * `name`
+    pub fn new(name: &str) -> Self {
+        Node {
+            name: name.to_string(),
+            properties: vec![],
+            children: vec![],
+        }
+    }
+
+    /// Returns the property value.
+    ///
+    /// # Arguments
+    /// * `name`
+    pub fn get_property(&self, name: &str) -> Option<&[u8]> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+            .map(|property| property.value.as_slice())
+    }
+
+    /// Sets the property value. The property is added if it doesn't exist.
+    ///
+    /// # Arguments
+    /// * `name`
+    /// * `value` Raw value
+    pub fn set_property(&mut self, name: &str, value: Vec<u8>) {
+        match self.properties.iter_mut().find(|p| p.name == name) {
+            Some(property) => property.value = value,
+            None => self.properties.push(Property {
+                name: name.to_string(),
+                value,
+            }),
+        };
+    }
+
+    /// Sets a property having 32-bit cells.
+    pub fn set_property_cells(&mut self, name: &str, cells: &[u32]) {
+        let value = cells.iter().flat_map(|cell| cell.to_be_bytes()).collect();
+        self.set_property(name, value);
+    }
+
+    /// Sets a property having a 64-bit value, two cells.
+    pub fn set_property_u64(&mut self, name: &str, value: u64) {
+        self.set_property(name, value.to_be_bytes().to_vec());
+    }
+
+    /// Sets a null-terminated string property.
+    pub fn set_property_string(&mut self, name: &str, value: &str) {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        self.set_property(name, bytes);
+    }
+
+    /// Removes the property if it exists.
+    pub fn remove_property(&mut self, name: &str) {
+        self.properties.retain(|property| property.name != name);
+    }
+
+    /// Returns the child node.
+    ///
+    /// # Arguments
+    /// * `name` Node name with unit address
+    pub fn get_child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Returns the child node, adding an empty one if it doesn't exist.
+    ///
+    /// # Arguments
+    /// * `name` Node name with unit address
+    pub fn get_or_add_child(&mut self, name: &str) -> &mut Node {
+        let index = match self.children.iter().position(|child| child.name == name) {
+            Some(index) => index,
+            None => {
+                self.children.push(Node::new(name));
+                self.children.len() - 1
+            }
+        };
+        &mut self.children[index]
+    }
+}
+
+/// Device tree, converted from/to flattened device tree binary (DTB).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceTree {
+    pub root: Node,
+    pub boot_cpuid_phys: u32,
+    /// Memory reservation block entries: address and size
+    pub memory_reservations: Vec<(u64, u64)>,
+}
+
+impl Default for DeviceTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeviceTree {
+    /// Creates a new `DeviceTree` having only the empty root node.
+    pub fn new() -> Self {
+        DeviceTree {
+            root: Node::new(""),
+            boot_cpuid_phys: 0,
+            memory_reservations: vec![],
+        }
+    }
+
+    /// Parses DTB. Bytes following `totalsize` in the header are ignored.
+    /// Returns `Err` if the content is malformed.
+    ///
+    /// # Arguments
+    /// * `data` DTB content
+    pub fn parse(data: &[u8]) -> Result<Self, EmulatorError> {
+        if read_u32(data, 0)? != FDT_MAGIC || read_u32(data, 24)? > FDT_VERSION {
+            return Err(EmulatorError::InvalidDtb);
+        }
+        let total_size = read_u32(data, 4)? as usize;
+        let data = data.get(..total_size).ok_or(EmulatorError::InvalidDtb)?;
+        let read_u32 = |offset: usize| read_u32(data, offset);
+        let read_u64 = |offset: usize| -> Result<u64, EmulatorError> {
+            Ok(((read_u32(offset)? as u64) << 32) | read_u32(offset + 4)? as u64)
+        };
+        let struct_offset = read_u32(8)? as usize;
+        let strings_offset = read_u32(12)? as usize;
+        let reservations_offset = read_u32(16)? as usize;
+        let strings = data
+            .get(strings_offset..strings_offset + read_u32(32)? as usize)
+            .ok_or(EmulatorError::InvalidDtb)?;
+
+        let mut memory_reservations = vec![];
+        let mut offset = reservations_offset;
+        loop {
+            let address = read_u64(offset)?;
+            let size = read_u64(offset + 8)?;
+            if address == 0 && size == 0 {
+                break;
+            }
+            memory_reservations.push((address, size));
+            offset += 16;
+        }
+
+        // Nodes being parsed. The last one is the innermost.
+        let mut stack: Vec<Node> = vec![];
+        let mut root = None;
+        let mut offset = struct_offset;
+        loop {
+            let token = read_u32(offset)?;
+            offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    if root.is_some() {
+                        return Err(EmulatorError::InvalidDtb);
+                    }
+                    let name = read_string(data, offset)?;
+                    offset = align4(offset + name.len() + 1);
+                    stack.push(Node::new(name));
+                }
+                FDT_END_NODE => {
+                    let node = stack.pop().ok_or(EmulatorError::InvalidDtb)?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(node),
+                        None => root = Some(node),
+                    };
+                }
+                FDT_PROP => {
+                    let length = read_u32(offset)? as usize;
+                    let name = read_string(strings, read_u32(offset + 4)? as usize)?;
+                    let value = data
+                        .get(offset + 8..offset + 8 + length)
+                        .ok_or(EmulatorError::InvalidDtb)?;
+                    offset = align4(offset + 8 + length);
+                    let node = stack.last_mut().ok_or(EmulatorError::InvalidDtb)?;
+                    node.properties.push(Property {
+                        name: name.to_string(),
+                        value: value.to_vec(),
+                    });
+                }
+                FDT_NOP => {}
+                FDT_END => break,
+                _ => return Err(EmulatorError::InvalidDtb),
+            };
+        }
+        match (root, stack.is_empty()) {
+            (Some(root), true) => Ok(DeviceTree {
+                root,
+                boot_cpuid_phys: read_u32(28)?,
+                memory_reservations,
+            }),
+            _ => Err(EmulatorError::InvalidDtb),
+        }
+    }
+
+    /// Serializes to DTB.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut structure = vec![];
+        let mut strings = vec![];
+        write_node(&self.root, &mut structure, &mut strings);
+        push_u32(&mut structure, FDT_END);
+
+        let reservations_offset = FDT_HEADER_SIZE;
+        let struct_offset = reservations_offset + (self.memory_reservations.len() + 1) * 16;
+        let strings_offset = struct_offset + structure.len();
+        let total_size = strings_offset + strings.len();
+
+        let mut data = vec![];
+        for value in [
+            FDT_MAGIC,
+            total_size as u32,
+            struct_offset as u32,
+            strings_offset as u32,
+            reservations_offset as u32,
+            FDT_VERSION,
+            FDT_LAST_COMPATIBLE_VERSION,
+            self.boot_cpuid_phys,
+            strings.len() as u32,
+            structure.len() as u32,
+        ] {
+            push_u32(&mut data, value);
+        }
+        for (address, size) in self.memory_reservations.iter().chain([(0, 0)].iter()) {
+            data.extend_from_slice(&address.to_be_bytes());
+            data.extend_from_slice(&size.to_be_bytes());
+        }
+        data.extend_from_slice(&structure);
+        data.extend_from_slice(&strings);
+        data
+    }
+}
+
+fn align4(value: usize) -> usize {
+    (value + 3) & !3
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, EmulatorError> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or(EmulatorError::InvalidDtb)
+}
+
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Reads a null-terminated string.
+fn read_string(data: &[u8], offset: usize) -> Result<&str, EmulatorError> {
+    let bytes = data.get(offset..).ok_or(EmulatorError::InvalidDtb)?;
+    let length = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .ok_or(EmulatorError::InvalidDtb)?;
+    std::str::from_utf8(&bytes[..length]).map_err(|_| EmulatorError::InvalidDtb)
+}
+
+/// Writes a node and its descendants to the structure block. Property names
+/// are added to the strings block unless they're already in it.
+fn write_node(node: &Node, structure: &mut Vec<u8>, strings: &mut Vec<u8>) {
+    push_u32(structure, FDT_BEGIN_NODE);
+    structure.extend_from_slice(node.name.as_bytes());
+    structure.push(0);
+    structure.resize(align4(structure.len()), 0);
+    for property in &node.properties {
+        let name_offset = find_string(strings, &property.name).unwrap_or_else(|| {
+            let offset = strings.len();
+            strings.extend_from_slice(property.name.as_bytes());
+            strings.push(0);
+            offset
+        });
+        push_u32(structure, FDT_PROP);
+        push_u32(structure, property.value.len() as u32);
+        push_u32(structure, name_offset as u32);
+        structure.extend_from_slice(&property.value);
+        structure.resize(align4(structure.len()), 0);
+    }
+    for child in &node.children {
+        write_node(child, structure, strings);
+    }
+    push_u32(structure, FDT_END_NODE);
+}
+
+/// Returns the offset of the null-terminated string in the strings block.
+fn find_string(strings: &[u8], name: &str) -> Option<usize> {
+    let mut offset = 0;
+    for string in strings.split(|byte| *byte == 0) {
+        if string == name.as_bytes() {
+            return Some(offset);
+        }
+        offset += string.len() + 1;
+    }
+    None
+}
@@ -0,0 +1,823 @@
+//! IEEE 754 floating-point arithmetic for the F and D extensions.
+//! Single precision operations use host double precision operations, which
+//! round to nearest, ties to even. The other rounding modes and the exception
+//! flags are derived from the exact error of each operation, computed with
+//! error-free transformations. Double precision operations compute the exact
+//! results with integer arithmetic and round them in software.
+
+use std::cmp::Ordering;
+
+/// Exception flags accrued in `fflags`
+pub const FLAG_NX: u8 = 0x1;
+pub const FLAG_UF: u8 = 0x2;
+pub const FLAG_OF: u8 = 0x4;
+pub const FLAG_DZ: u8 = 0x8;
+pub const FLAG_NV: u8 = 0x10;
+
+/// Quiet NaN every operation returns instead of NaN operands
+pub const CANONICAL_NAN_F32: u32 = 0x7fc00000;
+pub const CANONICAL_NAN_F64: u64 = 0x7ff8000000000000;
+
+/// Rounding modes, the `rm` field of instructions and `frm`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundingMode {
+    NearestEven,
+    TowardZero,
+    Down,
+    Up,
+    NearestMaxMagnitude,
+}
+
+impl RoundingMode {
+    /// Returns the rounding mode of the encoding, `None` if it's reserved
+    /// or dynamic (7).
+    ///
+    /// # Arguments
+    /// * `rm`
+    pub fn from_encoding(rm: u64) -> Option<Self> {
+        match rm {
+            0 => Some(RoundingMode::NearestEven),
+            1 => Some(RoundingMode::TowardZero),
+            2 => Some(RoundingMode::Down),
+            3 => Some(RoundingMode::Up),
+            4 => Some(RoundingMode::NearestMaxMagnitude),
+            _ => None,
+        }
+    }
+}
+
+/// Indicates whether the value is signaling NaN.
+pub fn is_signaling_f32(value: f32) -> bool {
+    value.is_nan() && (value.to_bits() & 0x00400000) == 0
+}
+
+/// Indicates whether the value is signaling NaN.
+pub fn is_signaling_f64(value: f64) -> bool {
+    value.is_nan() && (value.to_bits() & 0x0008000000000000) == 0
+}
+
+/// Returns `a + b` rounded to nearest even and its exact error.
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    let b_virtual = sum - a;
+    let error = (a - (sum - b_virtual)) + (b - b_virtual);
+    (sum, error)
+}
+
+/// Adjusts `value`, the exact result rounded to nearest even, to
+/// the rounding mode and accrues the exception flags. Tininess is
+/// detected after rounding.
+///
+/// # Arguments
+/// * `value`
+/// * `odd` The exact result rounded to odd in double precision. It rounds
+///   to 24 bits the same as the exact result.
+/// * `error` Sign of the exact result minus `value`
+/// * `tie` The other nearest value if the exact result is halfway between
+/// * `rm`
+/// * `flags`
+fn round_f32(
+    value: f32,
+    odd: f64,
+    error: Ordering,
+    tie: Option<f32>,
+    rm: RoundingMode,
+    flags: &mut u8,
+) -> f32 {
+    if error == Ordering::Equal {
+        return value;
+    }
+    let result = match rm {
+        RoundingMode::NearestEven => value,
+        RoundingMode::NearestMaxMagnitude => match tie {
+            Some(other) if other.abs() > value.abs() => other,
+            _ => value,
+        },
+        RoundingMode::TowardZero => match error {
+            Ordering::Less if value > 0.0 => value.next_down(),
+            Ordering::Greater if value < 0.0 => value.next_up(),
+            _ => value,
+        },
+        RoundingMode::Down => match error {
+            Ordering::Less => value.next_down(),
+            _ => value,
+        },
+        RoundingMode::Up => match error {
+            Ordering::Greater => value.next_up(),
+            _ => value,
+        },
+    };
+    *flags |= FLAG_NX;
+    if value.is_infinite() || result.is_infinite() {
+        *flags |= FLAG_OF;
+    }
+    if odd.abs() < f32::MIN_POSITIVE as f64 && is_tiny_f32(odd, rm) {
+        *flags |= FLAG_UF;
+    }
+    result
+}
+
+/// Returns true if the magnitude is less than the smallest normal value
+/// after rounding to 24 bits as if the exponent range were unbounded.
+///
+/// # Arguments
+/// * `odd` Nonzero exact result rounded to odd in double precision,
+///   smaller than the smallest normal single precision value
+/// * `rm`
+fn is_tiny_f32(odd: f64, rm: RoundingMode) -> bool {
+    let bits = odd.abs().to_bits();
+    let significand = (bits & ((1 << 52) - 1)) | (1 << 52);
+    let kept = significand >> 29;
+    let rest = significand & ((1 << 29) - 1);
+    let half = 1 << 28;
+    let round_up = match rm {
+        RoundingMode::NearestEven => rest > half || (rest == half && (kept & 1) == 1),
+        RoundingMode::NearestMaxMagnitude => rest >= half,
+        RoundingMode::TowardZero => false,
+        RoundingMode::Down => rest != 0 && odd < 0.0,
+        RoundingMode::Up => rest != 0 && odd > 0.0,
+    };
+    // Only the values just below 2^-126 can round up to it
+    let exponent = (bits >> 52) as i64 - 1023;
+    !(round_up && kept == (1 << 24) - 1 && exponent == -127)
+}
+
+/// Rounds the exact value `hi + lo` to single precision.
+///
+/// # Arguments
+/// * `hi`
+/// * `lo` Smaller than the half ulp of `hi`. Only its sign matters unless
+///   the exact value can be halfway between two single precision values.
+/// * `rm`
+/// * `flags`
+fn round_to_f32(hi: f64, lo: f64, rm: RoundingMode, flags: &mut u8) -> f32 {
+    if !hi.is_finite() {
+        return hi as f32;
+    }
+    // Rounding to odd keeps `lo` as sticky bit so that rounding to single
+    // precision below doesn't suffer from double rounding.
+    let odd = match lo != 0.0 && (hi.to_bits() & 1) == 0 {
+        true if lo > 0.0 => hi.next_up(),
+        true => hi.next_down(),
+        false => hi,
+    };
+    let value = odd as f32;
+    let difference = hi - value as f64;
+    let error = match difference != 0.0 {
+        true => difference.partial_cmp(&0.0),
+        false => lo.partial_cmp(&0.0),
+    }
+    .unwrap_or(Ordering::Equal);
+    let tie = match lo == 0.0 && difference != 0.0 && value.is_finite() {
+        true => {
+            let other = match difference > 0.0 {
+                true => value.next_up(),
+                false => value.next_down(),
+            };
+            match other as f64 - hi == difference {
+                true => Some(other),
+                false => None,
+            }
+        }
+        false => None,
+    };
+    round_f32(value, odd, error, tie, rm, flags)
+}
+
+/// Replaces NaN result with the canonical NaN. Raises invalid operation
+/// flag if an operand is signaling NaN or NaN is made of non-NaN operands.
+fn check_nan_f32(result: f32, operands: &[f32], flags: &mut u8) -> f32 {
+    if operands.iter().any(|operand| is_signaling_f32(*operand)) {
+        *flags |= FLAG_NV;
+    }
+    match result.is_nan() {
+        true => {
+            if !operands.iter().any(|operand| operand.is_nan()) {
+                *flags |= FLAG_NV;
+            }
+            f32::from_bits(CANONICAL_NAN_F32)
+        }
+        false => result,
+    }
+}
+
+/// Returns the sign of an exact zero sum. It's negative only if both
+/// addends are negative zero, or rounding down.
+fn zero_sum_f64(a: f64, b: f64, rm: RoundingMode) -> f64 {
+    match (
+        a == 0.0 && b == 0.0 && a.is_sign_negative() == b.is_sign_negative(),
+        rm,
+    ) {
+        (true, _) => a,
+        (false, RoundingMode::Down) => -0.0,
+        (false, _) => 0.0,
+    }
+}
+
+/// Returns `a + b`.
+pub fn add_f32(a: f32, b: f32, rm: RoundingMode, flags: &mut u8) -> f32 {
+    let result = fused_sum_f32(a as f64, b as f64, rm, flags);
+    check_nan_f32(result, &[a, b], flags)
+}
+
+/// Returns `a - b`.
+pub fn sub_f32(a: f32, b: f32, rm: RoundingMode, flags: &mut u8) -> f32 {
+    let result = fused_sum_f32(a as f64, -(b as f64), rm, flags);
+    check_nan_f32(result, &[a, b], flags)
+}
+
+/// Returns `a + b` of exact double precision values rounded to single precision.
+fn fused_sum_f32(a: f64, b: f64, rm: RoundingMode, flags: &mut u8) -> f32 {
+    let (hi, lo) = two_sum(a, b);
+    match hi == 0.0 && lo == 0.0 {
+        true => zero_sum_f64(a, b, rm) as f32,
+        false => round_to_f32(hi, lo, rm, flags),
+    }
+}
+
+/// Returns `a * b`.
+pub fn mul_f32(a: f32, b: f32, rm: RoundingMode, flags: &mut u8) -> f32 {
+    // The product of single precision values is exact in double precision
+    let result = round_to_f32(a as f64 * b as f64, 0.0, rm, flags);
+    check_nan_f32(result, &[a, b], flags)
+}
+
+/// Returns `a / b`.
+pub fn div_f32(a: f32, b: f32, rm: RoundingMode, flags: &mut u8) -> f32 {
+    if b == 0.0 && a.is_finite() && a != 0.0 {
+        *flags |= FLAG_DZ;
+    }
+    let (a64, b64) = (a as f64, b as f64);
+    let hi = a64 / b64;
+    // The remainder is exact with fused multiply-add
+    let lo = match hi.is_finite() && hi != 0.0 {
+        true => (-hi).mul_add(b64, a64) / b64,
+        false => 0.0,
+    };
+    let result = round_to_f32(hi, lo, rm, flags);
+    check_nan_f32(result, &[a, b], flags)
+}
+
+/// Returns the square root of `a`.
+pub fn sqrt_f32(a: f32, rm: RoundingMode, flags: &mut u8) -> f32 {
+    let a64 = a as f64;
+    let hi = a64.sqrt();
+    let lo = match hi.is_finite() && hi != 0.0 {
+        true => (-hi).mul_add(hi, a64) / (2.0 * hi),
+        false => 0.0,
+    };
+    let result = round_to_f32(hi, lo, rm, flags);
+    check_nan_f32(result, &[a], flags)
+}
+
+/// Returns `a * b + c` with a single rounding. `negate_product` and
+/// `negate_addend` give the variants `FMSUB`, `FNMSUB` and `FNMADD`.
+pub fn fused_mul_add_f32(
+    a: f32,
+    b: f32,
+    c: f32,
+    negate_product: bool,
+    negate_addend: bool,
+    rm: RoundingMode,
+    flags: &mut u8,
+) -> f32 {
+    // Infinity times zero is invalid even if the addend is quiet NaN
+    if (a.is_infinite() && b == 0.0) || (a == 0.0 && b.is_infinite()) {
+        *flags |= FLAG_NV;
+    }
+    let product = a as f64 * b as f64;
+    let product = if negate_product { -product } else { product };
+    let addend = if negate_addend { -c } else { c } as f64;
+    let result = fused_sum_f32(product, addend, rm, flags);
+    check_nan_f32(result, &[a, b, c], flags)
+}
+
+/// Returns the smaller value. Negative zero is smaller than positive zero.
+/// Returns the other operand if one is NaN.
+pub fn min_f32(a: f32, b: f32, flags: &mut u8) -> f32 {
+    min_max_f32(a, b, true, flags)
+}
+
+/// Returns the larger value. Positive zero is larger than negative zero.
+/// Returns the other operand if one is NaN.
+pub fn max_f32(a: f32, b: f32, flags: &mut u8) -> f32 {
+    min_max_f32(a, b, false, flags)
+}
+
+fn min_max_f32(a: f32, b: f32, is_min: bool, flags: &mut u8) -> f32 {
+    if is_signaling_f32(a) || is_signaling_f32(b) {
+        *flags |= FLAG_NV;
+    }
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => f32::from_bits(CANONICAL_NAN_F32),
+        (true, false) => b,
+        (false, true) => a,
+        // Zeros of the different signs are equal
+        _ if a == b => match a.is_sign_negative() == is_min {
+            true => a,
+            false => b,
+        },
+        _ if (a < b) == is_min => a,
+        _ => b,
+    }
+}
+
+/// Returns `a == b`. Only signaling NaN raises invalid operation flag.
+pub fn eq_f32(a: f32, b: f32, flags: &mut u8) -> bool {
+    if is_signaling_f32(a) || is_signaling_f32(b) {
+        *flags |= FLAG_NV;
+    }
+    a == b
+}
+
+/// Returns `a < b`. NaN raises invalid operation flag.
+pub fn lt_f32(a: f32, b: f32, flags: &mut u8) -> bool {
+    if a.is_nan() || b.is_nan() {
+        *flags |= FLAG_NV;
+    }
+    a < b
+}
+
+/// Returns `a <= b`. NaN raises invalid operation flag.
+pub fn le_f32(a: f32, b: f32, flags: &mut u8) -> bool {
+    if a.is_nan() || b.is_nan() {
+        *flags |= FLAG_NV;
+    }
+    a <= b
+}
+
+/// Returns the class mask of `FCLASS` instruction.
+pub fn classify_f32(a: f32) -> u64 {
+    let negative = a.is_sign_negative();
+    let bit = match a.classify() {
+        std::num::FpCategory::Infinite if negative => 0,
+        std::num::FpCategory::Normal if negative => 1,
+        std::num::FpCategory::Subnormal if negative => 2,
+        std::num::FpCategory::Zero if negative => 3,
+        std::num::FpCategory::Zero => 4,
+        std::num::FpCategory::Subnormal => 5,
+        std::num::FpCategory::Normal => 6,
+        std::num::FpCategory::Infinite => 7,
+        std::num::FpCategory::Nan if is_signaling_f32(a) => 8,
+        std::num::FpCategory::Nan => 9,
+    };
+    1 << bit
+}
+
+/// Converts a floating-point value to an integer in the range `min..=max`.
+/// Out of range values and NaN saturate with invalid operation flag,
+/// NaN to `max`.
+///
+/// # Arguments
+/// * `a` Single precision values are converted exactly
+/// * `min` Zero or the negated power of two
+/// * `max` The power of two minus one
+/// * `rm`
+/// * `flags`
+pub fn to_integer(a: f64, min: i128, max: i128, rm: RoundingMode, flags: &mut u8) -> i128 {
+    if a.is_nan() {
+        *flags |= FLAG_NV;
+        return max;
+    }
+    let rounded = match rm {
+        RoundingMode::NearestEven => a.round_ties_even(),
+        RoundingMode::TowardZero => a.trunc(),
+        RoundingMode::Down => a.floor(),
+        RoundingMode::Up => a.ceil(),
+        RoundingMode::NearestMaxMagnitude => a.round(),
+    };
+    if rounded < min as f64 {
+        *flags |= FLAG_NV;
+        return min;
+    }
+    if rounded >= (max + 1) as f64 {
+        *flags |= FLAG_NV;
+        return max;
+    }
+    if rounded != a {
+        *flags |= FLAG_NX;
+    }
+    rounded as i128
+}
+
+/// Converts an integer to single precision.
+pub fn from_integer_f32(a: i128, rm: RoundingMode, flags: &mut u8) -> f32 {
+    let hi = a as f64;
+    // The error is small enough to be exact in double precision
+    let lo = (a - hi as i128) as f64;
+    round_to_f32(hi, lo, rm, flags)
+}
+
+/// Converts an integer to double precision.
+pub fn from_integer_f64(a: i128, rm: RoundingMode, flags: &mut u8) -> f64 {
+    let value = Term {
+        negative: a < 0,
+        exponent: 0,
+        significand: a.unsigned_abs(),
+    };
+    round_pack_f64(value, rm, flags)
+}
+
+/// Converts double precision to single precision.
+pub fn to_f32(a: f64, rm: RoundingMode, flags: &mut u8) -> f32 {
+    match a.is_nan() {
+        true => {
+            if is_signaling_f64(a) {
+                *flags |= FLAG_NV;
+            }
+            f32::from_bits(CANONICAL_NAN_F32)
+        }
+        false => round_to_f32(a, 0.0, rm, flags),
+    }
+}
+
+/// Converts single precision to double precision. It's always exact.
+pub fn to_f64(a: f32, flags: &mut u8) -> f64 {
+    match a.is_nan() {
+        true => {
+            if is_signaling_f32(a) {
+                *flags |= FLAG_NV;
+            }
+            f64::from_bits(CANONICAL_NAN_F64)
+        }
+        false => a as f64,
+    }
+}
+
+/// Exact finite value `(-1)^negative * significand * 2^exponent`
+#[derive(Clone, Copy, Debug)]
+struct Term {
+    negative: bool,
+    exponent: i32,
+    significand: u128,
+}
+
+impl Term {
+    /// Returns the number of the significant bits.
+    fn width(&self) -> i32 {
+        128 - self.significand.leading_zeros() as i32
+    }
+
+    /// Returns the same value whose significand has 53 bits.
+    /// Zero stays zero.
+    fn normalize(self) -> Term {
+        let shift = match self.significand {
+            0 => 0,
+            _ => 53 - self.width(),
+        };
+        Term {
+            negative: self.negative,
+            exponent: self.exponent - shift,
+            significand: self.significand << shift,
+        }
+    }
+}
+
+/// Decomposes a finite double precision value.
+fn unpack_f64(a: f64) -> Term {
+    let bits = a.to_bits();
+    let biased_exponent = ((bits >> 52) & 0x7ff) as i32;
+    let fraction = (bits & 0xfffffffffffff) as u128;
+    match biased_exponent {
+        0 => Term {
+            negative: a.is_sign_negative(),
+            exponent: -1074,
+            significand: fraction,
+        },
+        _ => Term {
+            negative: a.is_sign_negative(),
+            exponent: biased_exponent - 1075,
+            significand: fraction | (1 << 52),
+        },
+    }
+}
+
+/// Returns the significand of `value` scaled to `2^exponent` and rounded to
+/// an integer, and whether it's inexact.
+fn round_significand(value: Term, exponent: i32, rm: RoundingMode) -> (u128, bool) {
+    let shift = exponent - value.exponent;
+    if shift <= 0 {
+        return (value.significand << -shift, false);
+    }
+    // The significands are less than 2^127, so smaller than the half
+    // if the shift is 128 or more.
+    let (kept, rest, half) = match shift {
+        1..=127 => (
+            value.significand >> shift,
+            value.significand & ((1 << shift) - 1),
+            1 << (shift - 1),
+        ),
+        _ => (0, value.significand, u128::MAX),
+    };
+    let inexact = rest != 0;
+    let increment = match rm {
+        RoundingMode::NearestEven => rest > half || (rest == half && (kept & 1) == 1),
+        RoundingMode::NearestMaxMagnitude => rest >= half,
+        RoundingMode::TowardZero => false,
+        RoundingMode::Down => value.negative && inexact,
+        RoundingMode::Up => !value.negative && inexact,
+    };
+    (kept + increment as u128, inexact)
+}
+
+/// Rounds the exact value to double precision and accrues the exception
+/// flags. Tininess is detected after rounding.
+fn round_pack_f64(value: Term, rm: RoundingMode, flags: &mut u8) -> f64 {
+    let sign = (value.negative as u64) << 63;
+    if value.significand == 0 {
+        return f64::from_bits(sign);
+    }
+    // Exponent giving 53-bit significand if the exponent range were unbounded
+    let unbounded_exponent = value.exponent + value.width() - 53;
+    let mut exponent = unbounded_exponent.max(-1074);
+    let (mut significand, inexact) = round_significand(value, exponent, rm);
+    if significand == 1 << 53 {
+        significand >>= 1;
+        exponent += 1;
+    }
+    if exponent > 971 {
+        *flags |= FLAG_OF | FLAG_NX;
+        let infinity = match rm {
+            RoundingMode::NearestEven | RoundingMode::NearestMaxMagnitude => true,
+            RoundingMode::TowardZero => false,
+            RoundingMode::Down => value.negative,
+            RoundingMode::Up => !value.negative,
+        };
+        return match infinity {
+            true => f64::from_bits(sign | f64::INFINITY.to_bits()),
+            false => f64::from_bits(sign | f64::MAX.to_bits()),
+        };
+    }
+    if inexact {
+        *flags |= FLAG_NX;
+        if unbounded_exponent < -1074 {
+            let (unbounded, _) = round_significand(value, unbounded_exponent, rm);
+            if unbounded < 1 << 53 || unbounded_exponent < -1075 {
+                *flags |= FLAG_UF;
+            }
+        }
+    }
+    let biased_exponent = match significand >> 52 {
+        0 => 0,
+        _ => (exponent + 1075) as u64,
+    };
+    f64::from_bits(sign | (biased_exponent << 52) | (significand as u64 & 0xfffffffffffff))
+}
+
+/// Returns `x + y`. The result is exact except that the lowest bit of
+/// the significand is set if the bits lower than it are lost. It's enough
+/// for rounding because the result keeps more than 120 bits then.
+/// The significands must be less than 2^107.
+fn add_terms(x: Term, y: Term) -> Term {
+    if x.significand == 0 {
+        return y;
+    }
+    if y.significand == 0 {
+        return x;
+    }
+    let (large, small) = match x.exponent + x.width() >= y.exponent + y.width() {
+        true => (x, y),
+        false => (y, x),
+    };
+    // The larger one is shifted up to bit 125, leaving room for the carry
+    // and the sign.
+    let shift = 126 - large.width();
+    let exponent = large.exponent - shift;
+    let small_shift = exponent - small.exponent;
+    let small_significand = match small_shift {
+        ..=0 => small.significand << -small_shift,
+        1..=127 => {
+            let lost = small.significand & ((1 << small_shift) - 1);
+            (small.significand >> small_shift) | (lost != 0) as u128
+        }
+        _ => 1,
+    };
+    let signed = |negative: bool, significand: u128| match negative {
+        true => -(significand as i128),
+        false => significand as i128,
+    };
+    let sum = signed(large.negative, large.significand << shift)
+        + signed(small.negative, small_significand);
+    Term {
+        negative: sum < 0,
+        exponent,
+        significand: sum.unsigned_abs(),
+    }
+}
+
+/// Replaces NaN result with the canonical NaN. Raises invalid operation
+/// flag if an operand is signaling NaN or NaN is made of non-NaN operands.
+fn check_nan_f64(result: f64, operands: &[f64], flags: &mut u8) -> f64 {
+    if operands.iter().any(|operand| is_signaling_f64(*operand)) {
+        *flags |= FLAG_NV;
+    }
+    match result.is_nan() {
+        true => {
+            if !operands.iter().any(|operand| operand.is_nan()) {
+                *flags |= FLAG_NV;
+            }
+            f64::from_bits(CANONICAL_NAN_F64)
+        }
+        false => result,
+    }
+}
+
+/// Returns `a + b`.
+pub fn add_f64(a: f64, b: f64, rm: RoundingMode, flags: &mut u8) -> f64 {
+    let result = sum_f64(a, b, rm, flags);
+    check_nan_f64(result, &[a, b], flags)
+}
+
+/// Returns `a - b`.
+pub fn sub_f64(a: f64, b: f64, rm: RoundingMode, flags: &mut u8) -> f64 {
+    let result = sum_f64(a, -b, rm, flags);
+    check_nan_f64(result, &[a, b], flags)
+}
+
+fn sum_f64(a: f64, b: f64, rm: RoundingMode, flags: &mut u8) -> f64 {
+    // Infinity and NaN results are exact
+    if !a.is_finite() || !b.is_finite() {
+        return a + b;
+    }
+    let sum = add_terms(unpack_f64(a), unpack_f64(b));
+    match sum.significand {
+        0 => zero_sum_f64(a, b, rm),
+        _ => round_pack_f64(sum, rm, flags),
+    }
+}
+
+/// Returns the exact product of finite values.
+fn multiply_terms(a: f64, b: f64) -> Term {
+    let (x, y) = (unpack_f64(a), unpack_f64(b));
+    Term {
+        negative: x.negative != y.negative,
+        exponent: x.exponent + y.exponent,
+        significand: x.significand * y.significand,
+    }
+}
+
+/// Returns `a * b`.
+pub fn mul_f64(a: f64, b: f64, rm: RoundingMode, flags: &mut u8) -> f64 {
+    let result = match a.is_finite() && b.is_finite() {
+        true => round_pack_f64(multiply_terms(a, b), rm, flags),
+        false => a * b,
+    };
+    check_nan_f64(result, &[a, b], flags)
+}
+
+/// Returns `a / b`.
+pub fn div_f64(a: f64, b: f64, rm: RoundingMode, flags: &mut u8) -> f64 {
+    if b == 0.0 && a.is_finite() && a != 0.0 {
+        *flags |= FLAG_DZ;
+    }
+    let result = match a.is_finite() && b.is_finite() && b != 0.0 {
+        true => {
+            let (x, y) = (unpack_f64(a).normalize(), unpack_f64(b).normalize());
+            // The quotient has 64 or 65 bits. The remainder goes to the sticky bit.
+            let dividend = x.significand << 64;
+            let quotient = dividend / y.significand;
+            let remainder = dividend % y.significand;
+            let value = Term {
+                negative: x.negative != y.negative,
+                exponent: x.exponent - y.exponent - 64,
+                significand: quotient | (remainder != 0) as u128,
+            };
+            round_pack_f64(value, rm, flags)
+        }
+        false => a / b,
+    };
+    check_nan_f64(result, &[a, b], flags)
+}
+
+/// Returns the square root of `a`.
+pub fn sqrt_f64(a: f64, rm: RoundingMode, flags: &mut u8) -> f64 {
+    let result = match a.is_finite() && a > 0.0 {
+        true => {
+            let mut x = unpack_f64(a).normalize();
+            if x.exponent % 2 != 0 {
+                x.significand <<= 1;
+                x.exponent -= 1;
+            }
+            // The root has 63 or 64 bits. The remainder goes to the sticky bit.
+            let radicand = x.significand << 74;
+            let root = radicand.isqrt();
+            let value = Term {
+                negative: false,
+                exponent: (x.exponent - 74) / 2,
+                significand: root | (root * root != radicand) as u128,
+            };
+            round_pack_f64(value, rm, flags)
+        }
+        false => a.sqrt(),
+    };
+    check_nan_f64(result, &[a], flags)
+}
+
+/// Returns `a * b + c` with a single rounding. `negate_product` and
+/// `negate_addend` give the variants `FMSUB`, `FNMSUB` and `FNMADD`.
+pub fn fused_mul_add_f64(
+    a: f64,
+    b: f64,
+    c: f64,
+    negate_product: bool,
+    negate_addend: bool,
+    rm: RoundingMode,
+    flags: &mut u8,
+) -> f64 {
+    // Infinity times zero is invalid even if the addend is quiet NaN
+    if (a.is_infinite() && b == 0.0) || (a == 0.0 && b.is_infinite()) {
+        *flags |= FLAG_NV;
+    }
+    let factor = if negate_product { -a } else { a };
+    let addend = if negate_addend { -c } else { c };
+    let result = match a.is_finite() && b.is_finite() && c.is_finite() {
+        true => {
+            let product = multiply_terms(factor, b);
+            let sum = add_terms(product, unpack_f64(addend));
+            match sum.significand {
+                0 => {
+                    let product = match product.negative {
+                        true => -(product.significand as f64),
+                        false => product.significand as f64,
+                    };
+                    zero_sum_f64(product, addend, rm)
+                }
+                _ => round_pack_f64(sum, rm, flags),
+            }
+        }
+        false => factor.mul_add(b, addend),
+    };
+    check_nan_f64(result, &[a, b, c], flags)
+}
+
+/// Returns the smaller value. Negative zero is smaller than positive zero.
+/// Returns the other operand if one is NaN.
+pub fn min_f64(a: f64, b: f64, flags: &mut u8) -> f64 {
+    min_max_f64(a, b, true, flags)
+}
+
+/// Returns the larger value. Positive zero is larger than negative zero.
+/// Returns the other operand if one is NaN.
+pub fn max_f64(a: f64, b: f64, flags: &mut u8) -> f64 {
+    min_max_f64(a, b, false, flags)
+}
+
+fn min_max_f64(a: f64, b: f64, is_min: bool, flags: &mut u8) -> f64 {
+    if is_signaling_f64(a) || is_signaling_f64(b) {
+        *flags |= FLAG_NV;
+    }
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => f64::from_bits(CANONICAL_NAN_F64),
+        (true, false) => b,
+        (false, true) => a,
+        // Zeros of the different signs are equal
+        _ if a == b => match a.is_sign_negative() == is_min {
+            true => a,
+            false => b,
+        },
+        _ if (a < b) == is_min => a,
+        _ => b,
+    }
+}
+
+/// Returns `a == b`. Only signaling NaN raises invalid operation flag.
+pub fn eq_f64(a: f64, b: f64, flags: &mut u8) -> bool {
+    if is_signaling_f64(a) || is_signaling_f64(b) {
+        *flags |= FLAG_NV;
+    }
+    a == b
+}
+
+/// Returns `a < b`. NaN raises invalid operation flag.
+pub fn lt_f64(a: f64, b: f64, flags: &mut u8) -> bool {
+    if a.is_nan() || b.is_nan() {
+        *flags |= FLAG_NV;
+    }
+    a < b
+}
+
+/// Returns `a <= b`. NaN raises invalid operation flag.
+pub fn le_f64(a: f64, b: f64, flags: &mut u8) -> bool {
+    if a.is_nan() || b.is_nan() {
+        *flags |= FLAG_NV;
+    }
+    a <= b
+}
+
+/// Returns the class mask of `FCLASS` instruction.
+pub fn classify_f64(a: f64) -> u64 {
+    let negative = a.is_sign_negative();
+    let bit = match a.classify() {
+        std::num::FpCategory::Infinite if negative => 0,
+        std::num::FpCategory::Normal if negative => 1,
+        std::num::FpCategory::Subnormal if negative => 2,
+        std::num::FpCategory::Zero if negative => 3,
+        std::num::FpCategory::Zero => 4,
+        std::num::FpCategory::Subnormal => 5,
+        std::num::FpCategory::Normal => 6,
+        std::num::FpCategory::Infinite => 7,
+        std::num::FpCategory::Nan if is_signaling_f64(a) => 8,
+        std::num::FpCategory::Nan => 9,
+    };
+    1 << bit
+}
@@ -1,25 +1,41 @@
-// @TODO: temporal
-const PROGRAM_MEMORY_CAPACITY: u64 = 1024 * 1024 * 128; // big enough to run Linux and xv6
+use std::cell::RefMut;
 
-use std::collections::HashMap;
-use std::hash::BuildHasherDefault;
-
-use elf::section::SectionHeader;
-use fnv::{FnvHashMap, FnvHasher};
+use fnv::FnvHashSet;
 
+pub mod bus;
+pub mod config;
+mod counter;
 pub mod cpu;
+mod csr;
+mod decoder;
 pub mod default_terminal;
 pub mod device;
+pub mod elf_analyzer;
+#[cfg(test)]
+mod elf_builder;
+pub mod error;
+pub mod fdt;
+mod fpu;
+pub mod linux_image;
 pub mod memory;
 pub mod mmu;
+mod pmp;
+pub mod replay_terminal;
+mod snapshot;
+pub mod symbol_map;
 pub mod terminal;
+mod vector;
 
-use cpu::{Cpu, Xlen};
+use config::{MachineConfig, RESET_VECTOR};
+use cpu::{Cpu, TrapType, Xlen};
+use elf_analyzer::ElfAnalyzer;
+use error::EmulatorError;
+use fdt::DeviceTree;
+use linux_image::LinuxImage;
+use snapshot::{SnapshotReader, SnapshotWriter, SNAPSHOT_MAGIC, SNAPSHOT_VERSION};
+use symbol_map::SymbolMap;
 use terminal::Terminal;
 
-use elf::endian::AnyEndian;
-use elf::ElfBytes;
-
 /// RISC-V emulator. It emulates RISC-V CPU and peripheral devices.
 ///
 /// Sample code to run the emulator.
@@ -27,151 +43,515 @@ use elf::ElfBytes;
 /// // Creates an emulator with arbitrary terminal
 /// let mut emulator = Emulator::new(Box::new(DefaultTerminal::new()));
 /// // Set up program content binary
-/// emulator.setup_program(program_content);
+/// emulator.setup_program(program_content)?;
 /// // Set up Filesystem content binary
-/// emulator.setup_filesystem(fs_content);
+/// emulator.setup_filesystem(fs_content)?;
 /// // Go!
-/// emulator.run();
+/// let stop_reason = emulator.run();
+/// ```
+///
+/// Sample code to boot Linux kernel `Image` with firmware jumping to it,
+/// e.g. OpenSBI `fw_jump.elf`.
+/// ```ignore
+/// emulator.setup_program(firmware_content)?;
+/// emulator.setup_kernel_image(image_content)?;
+/// emulator.setup_initrd(initrd_content, 0x84000000)?;
+/// emulator.setup_bootargs("console=ttyS0 rdinit=/sbin/init")?;
+/// let stop_reason = emulator.run();
 /// ```
 pub struct Emulator {
+    /// The boot hart, hart ID 0
     pub cpu: Cpu,
 
-    /// Stores mapping from symbol to virtual address
-    symbol_map: FnvHashMap<String, u64>,
+    /// The other harts sharing the bus with `cpu`, in hart ID order from 1
+    harts: Vec<Cpu>,
+
+    /// Symbols of the program and the additional symbol files
+    symbol_map: SymbolMap,
+
+    /// Addresses where `run_for()` and `run_until()` stop
+    breakpoints: FnvHashSet<u64>,
+
+    /// Physical address range (start, end) of Linux kernel image and initrd,
+    /// and kernel command line. Initrd and command line are passed to the kernel
+    /// via `/chosen` node in the device tree.
+    kernel_range: Option<(u64, u64)>,
+    initrd_range: Option<(u64, u64)>,
+    bootargs: Option<String>,
+
+    /// Whether the device tree and boot ROM have been set up and
+    /// the hart has started from the reset vector
+    booted: bool,
+}
+
+/// Reason why `Emulator::run_for()`, `Emulator::run_until()` or
+/// `Emulator::run()` returned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The specified number of cycles has been run.
+    CycleBudgetExhausted,
+    /// The condition passed to `run_until()` is satisfied.
+    ConditionMet,
+    /// The guest requested power-off through the test finisher device.
+    PowerOff { exit_code: u32 },
+    /// Program counter reached a breakpoint added with `add_breakpoint()`.
+    Breakpoint { address: u64 },
+    /// An exception was taken while no trap handler is set up.
+    FatalTrap {
+        trap_type: TrapType,
+        value: u64,
+        address: u64,
+    },
+    /// CPU waits for interrupt (WFI) while all the interrupts are disabled
+    /// then it never wakes up.
+    Halted,
 }
 
 impl Emulator {
-    /// Creates a new `Emulator`. [`Terminal`]
+    /// Creates a new `Emulator` with the default [`MachineConfig`]. [`Terminal`]
     /// is internally used for transferring input/output data to/from `Emulator`.
     pub fn new(terminal: Box<dyn Terminal>) -> Self {
         Emulator {
             cpu: Cpu::new(terminal),
+            harts: vec![],
+
+            symbol_map: SymbolMap::new(),
+            breakpoints: FnvHashSet::default(),
+            kernel_range: None,
+            initrd_range: None,
+            bootargs: None,
+            booted: false,
+        }
+    }
+
+    /// Creates a new `Emulator` with machine configuration. Returns `Err`
+    /// if the configuration is invalid. [`EmulatorBuilder`](config::EmulatorBuilder) is a handy way
+    /// to make the configuration.
+    ///
+    /// # Arguments
+    /// * `terminal`
+    /// * `config`
+    pub fn new_with_config(
+        terminal: Box<dyn Terminal>,
+        config: MachineConfig,
+    ) -> Result<Self, EmulatorError> {
+        config.validate()?;
+        let cpu = Cpu::new_with_config(terminal, &config);
+        let harts = (1..config.hart_count)
+            .map(|hart_id| cpu.create_secondary_hart(hart_id))
+            .collect();
+        Ok(Emulator {
+            cpu,
+            harts,
+
+            symbol_map: SymbolMap::new(),
+            breakpoints: FnvHashSet::default(),
+            kernel_range: None,
+            initrd_range: None,
+            bootargs: None,
+            booted: false,
+        })
+    }
+
+    /// Runs program set by `setup_program()` until the guest stops.
+    /// It may never return, use `run_for()` to run in time slices.
+    pub fn run(&mut self) -> StopReason {
+        self.run_until(|_| false)
+    }
 
-            symbol_map: FnvHashMap::default(),
+    /// Runs up to `cycles` CPU cycles. Returns earlier if the guest stops
+    /// or a breakpoint is hit. A breakpoint at the current program counter
+    /// is ignored so that the execution can be resumed from it.
+    ///
+    /// # Arguments
+    /// * `cycles`
+    pub fn run_for(&mut self, cycles: u64) -> StopReason {
+        if let Some(reason) = self.get_terminal_state() {
+            return reason;
+        }
+        for _ in 0..cycles {
+            self.tick();
+            if let Some(reason) = self.check_stop() {
+                return reason;
+            }
         }
+        StopReason::CycleBudgetExhausted
     }
 
-    /// Runs program set by `setup_program()`.
-    pub fn run(&mut self) {
+    /// Runs until `condition` returns true, checked after every cycle.
+    /// Returns earlier if the guest stops or a breakpoint is hit.
+    ///
+    /// # Arguments
+    /// * `condition`
+    pub fn run_until<F: FnMut(&mut Emulator) -> bool>(&mut self, mut condition: F) -> StopReason {
+        if let Some(reason) = self.get_terminal_state() {
+            return reason;
+        }
         loop {
             self.tick();
+            if let Some(reason) = self.check_stop() {
+                return reason;
+            }
+            if condition(self) {
+                return StopReason::ConditionMet;
+            }
         }
     }
 
-    /// Runs CPU one cycle
+    /// Adds a breakpoint. `run_for()` and `run_until()` stop when
+    /// program counter reaches the address.
+    ///
+    /// # Arguments
+    /// * `address` Virtual address
+    pub fn add_breakpoint(&mut self, address: u64) {
+        self.breakpoints.insert(address);
+    }
+
+    /// Removes a breakpoint added with `add_breakpoint()`.
+    ///
+    /// # Arguments
+    /// * `address` Virtual address
+    pub fn remove_breakpoint(&mut self, address: u64) {
+        self.breakpoints.remove(&address);
+    }
+
+    /// Returns the number of harts.
+    pub fn get_hart_count(&self) -> usize {
+        self.harts.len() + 1
+    }
+
+    /// Returns the hart of the hart ID, or `None` if it doesn't exist.
+    /// Hart 0 is also accessible as `cpu`.
+    ///
+    /// # Arguments
+    /// * `hart_id`
+    pub fn get_hart(&self, hart_id: usize) -> Option<&Cpu> {
+        match hart_id {
+            0 => Some(&self.cpu),
+            _ => self.harts.get(hart_id - 1),
+        }
+    }
+
+    /// Returns the mutable hart of the hart ID, or `None` if it doesn't exist.
+    ///
+    /// # Arguments
+    /// * `hart_id`
+    pub fn get_mut_hart(&mut self, hart_id: usize) -> Option<&mut Cpu> {
+        match hart_id {
+            0 => Some(&mut self.cpu),
+            _ => self.harts.get_mut(hart_id - 1),
+        }
+    }
+
+    /// Returns the harts in hart ID order.
+    fn iter_harts(&self) -> impl Iterator<Item = &Cpu> {
+        std::iter::once(&self.cpu).chain(self.harts.iter())
+    }
+
+    /// Returns the reason if the guest can't run any more.
+    fn get_terminal_state(&self) -> Option<StopReason> {
+        let mmu = self.cpu.get_mmu();
+        if let Some(exit_code) = mmu.get_exit_code() {
+            return Some(StopReason::PowerOff { exit_code });
+        }
+        for cpu in self.iter_harts() {
+            if let Some((trap, address)) = cpu.get_unhandled_exception() {
+                return Some(StopReason::FatalTrap {
+                    trap_type: trap.trap_type,
+                    value: trap.value,
+                    address: *address,
+                });
+            }
+        }
+        None
+    }
+
+    fn check_stop(&self) -> Option<StopReason> {
+        if let Some(reason) = self.get_terminal_state() {
+            return Some(reason);
+        }
+        if !self.breakpoints.is_empty() {
+            for cpu in self.iter_harts() {
+                if self.breakpoints.contains(&cpu.read_pc()) {
+                    return Some(StopReason::Breakpoint {
+                        address: cpu.read_pc(),
+                    });
+                }
+            }
+        }
+        if self.iter_harts().all(|cpu| cpu.is_halted()) {
+            return Some(StopReason::Halted);
+        }
+        None
+    }
+
+    /// Runs all the harts one cycle each, in hart ID order. At the first
+    /// cycle the device tree is placed in the main memory and the harts
+    /// start from the boot ROM at the reset vector, which jumps to
+    /// the program entry point with `a0` = hart ID and `a1` = device tree
+    /// address.
     pub fn tick(&mut self) {
+        if !self.booted {
+            self.boot();
+        }
         self.cpu.tick();
+        for cpu in self.harts.iter_mut() {
+            cpu.tick();
+        }
+    }
+
+    /// Sets up the boot ROM jumping to the current program counter,
+    /// the entry point of the program, and moves all the harts to
+    /// the reset vector.
+    fn boot(&mut self) {
+        let entry = self.cpu.read_pc();
+        self.cpu.get_mut_mmu().init_boot_rom(entry);
+        self.cpu.update_pc(RESET_VECTOR);
+        for cpu in self.harts.iter_mut() {
+            cpu.update_pc(RESET_VECTOR);
+        }
+        self.booted = true;
     }
 
     /// Sets up program run by the program. This method analyzes the passed content
-    /// and configure CPU properly. If the passed contend doesn't seem ELF file,
-    /// it panics. This method is expected to be called only once.
+    /// and configure CPU properly. This method is expected to be called only once.
+    /// The program is loaded by its program headers. Position independent
+    /// programs are loaded at the beginning of the main memory.
+    ///
+    /// Returns `Err` if the passed content doesn't seem RISC-V ELF file or
+    /// if the program can't be placed in the main memory.
+    ///
+    /// # Arguments
+    /// * `content` Program binary
+    pub fn setup_program(&mut self, content: Vec<u8>) -> Result<(), EmulatorError> {
+        let analyzer = ElfAnalyzer::new(&content)?;
+        let base = match analyzer.is_position_independent() {
+            true => self.cpu.get_mut_mmu().memory_base(),
+            false => 0,
+        };
+        self.load_program(&content, &analyzer, base)
+    }
+
+    /// Sets up position independent program loaded at `base`. Same as
+    /// `setup_program()` except for the load address. Returns `Err` if
+    /// the program isn't position independent.
     ///
     /// # Arguments
     /// * `content` Program binary
-    // @TODO: Make ElfAnalyzer and move the core logic there.
-    // @TODO: Returns `Err` if the passed contend doesn't seem ELF file
-    pub fn setup_program(&mut self, content: Vec<u8>) {
-        let (analyzer, (program_data_section_headers, _, _)) =
-            Self::load_program_for_symbols(&mut self.symbol_map, &content);
+    /// * `base` Address the lowest segment of the program is loaded to
+    pub fn setup_program_with_base(
+        &mut self,
+        content: Vec<u8>,
+        base: u64,
+    ) -> Result<(), EmulatorError> {
+        let analyzer = ElfAnalyzer::new(&content)?;
+        if !analyzer.is_position_independent() {
+            return Err(EmulatorError::NotPositionIndependent);
+        }
+        self.load_program(&content, &analyzer, base)
+    }
+
+    fn load_program(
+        &mut self,
+        content: &[u8],
+        analyzer: &ElfAnalyzer,
+        base: u64,
+    ) -> Result<(), EmulatorError> {
+        let bias = analyzer.get_load_bias(base)?;
+        let segments = analyzer.get_segments(bias)?;
+        let relocations = analyzer.get_relocations(bias)?;
+        let entry_point = analyzer.get_entry_point(bias)?;
+
+        let memory_base = self.cpu.get_mut_mmu().memory_base();
+        let capacity = self.cpu.get_mut_mmu().memory_capacity();
+
+        let xlen_bytes = match analyzer.get_xlen() {
+            Xlen::Bit32 => 4,
+            Xlen::Bit64 => 8,
+        };
+
+        // Checks all the segments and relocations before touching the memory
+        for relocation in relocations.iter() {
+            if relocation.address < memory_base
+                || relocation.address - memory_base > capacity.saturating_sub(xlen_bytes)
+            {
+                return Err(EmulatorError::SegmentOutOfMemory {
+                    address: relocation.address,
+                    size: xlen_bytes,
+                });
+            }
+        }
+        for segment in segments.iter() {
+            self.check_memory_range(segment.address, segment.memory_size)?;
+        }
+
+        self.symbol_map.load_elf(content, bias)?;
 
         // Detected whether the elf file is riscv-tests.
         // Setting up CPU and Memory depending on it.
-        self.cpu.update_xlen(match analyzer.ehdr.class {
-            elf::file::Class::ELF32 => Xlen::Bit32,
-            elf::file::Class::ELF64 => Xlen::Bit64,
-        });
-
-        self.cpu.get_mut_mmu().init_memory(PROGRAM_MEMORY_CAPACITY);
-
-        for section_header in program_data_section_headers {
-            let sh_addr = section_header.sh_addr;
-            let sh_offset = section_header.sh_offset as usize;
-            let sh_size = section_header.sh_size as usize;
-            if sh_addr >= 0x80000000 && sh_offset > 0 && sh_size > 0 {
-                for j in 0..sh_size {
-                    self.cpu
-                        .get_mut_mmu()
-                        .store_raw(sh_addr + j as u64, content[sh_offset + j]);
-                }
+        self.update_xlen(analyzer.get_xlen());
+
+        let mmu = self.cpu.get_mut_mmu();
+        for segment in segments.iter() {
+            for (i, byte) in segment.data.iter().enumerate() {
+                mmu.store_raw(segment.address + i as u64, *byte);
+            }
+            // Zero-fills BSS
+            for i in segment.data.len() as u64..segment.memory_size {
+                mmu.store_raw(segment.address + i, 0);
             }
         }
 
-        self.cpu.update_pc(analyzer.ehdr.e_entry);
+        for relocation in relocations {
+            match analyzer.get_xlen() {
+                Xlen::Bit32 => mmu.store_word_raw(relocation.address, relocation.value as u32),
+                Xlen::Bit64 => mmu.store_doubleword_raw(relocation.address, relocation.value),
+            };
+        }
+
+        self.cpu.update_pc(entry_point);
+        Ok(())
     }
 
-    /// Loads symbols of program and adds them to `symbol_map`.
+    /// Returns `Err` unless the range fits in the main memory.
     ///
     /// # Arguments
-    /// * `content` Program binary
-    pub fn load_program_for_symbols<'a>(
-        symbol_map: &'a mut HashMap<String, u64, BuildHasherDefault<FnvHasher>>,
-        content: &'a [u8],
-    ) -> (
-        ElfBytes<'a, AnyEndian>,
-        (Vec<SectionHeader>, Vec<SectionHeader>, Vec<SectionHeader>),
-    ) {
-        let analyzer = ElfBytes::<AnyEndian>::minimal_parse(content)
-            .expect("This file does not seem to be an ELF file");
-
-        let section_headers = analyzer
-            .section_headers()
-            .expect("This file does not have section headers");
-
-        let mut program_data_section_headers = vec![];
-        let mut symbol_table_section_headers = vec![];
-        let mut string_table_section_headers = vec![];
-
-        for section_header in section_headers {
-            match section_header.sh_type {
-                1 => program_data_section_headers.push(section_header),
-                2 => symbol_table_section_headers.push(section_header),
-                3 => string_table_section_headers.push(section_header),
-                _ => {}
-            };
+    /// * `address` Physical address
+    /// * `size` Size in bytes
+    fn check_memory_range(&self, address: u64, size: u64) -> Result<(), EmulatorError> {
+        let memory_base = self.cpu.get_mmu().memory_base();
+        let capacity = self.cpu.get_mmu().memory_capacity();
+        if size > capacity {
+            return Err(EmulatorError::ImageTooLarge { size, capacity });
         }
-
-        // Creates symbol - virtual address mapping
-        if !string_table_section_headers.is_empty() {
-            let (symbols, _) = analyzer.symbol_table().unwrap().unwrap();
-            // Assuming symbols are in the first string table section.
-            for symbol in symbols {
-                symbol_map.insert(symbol.st_name.to_string(), symbol.st_value);
-            }
+        if address < memory_base || address - memory_base > capacity - size {
+            return Err(EmulatorError::SegmentOutOfMemory { address, size });
         }
-
-        (
-            analyzer,
-            (
-                program_data_section_headers,
-                symbol_table_section_headers,
-                string_table_section_headers,
-            ),
-        )
+        Ok(())
     }
 
     /// Sets up filesystem. Use this method if program (e.g. Linux) uses
     /// filesystem. This method is expected to be called up to only once.
+    /// Returns `Err` if the content is larger than the disk capacity.
     ///
     /// # Arguments
     /// * `content` File system content binary
-    pub fn setup_filesystem(&mut self, content: Vec<u8>) {
-        self.cpu.get_mut_mmu().init_disk(content);
+    pub fn setup_filesystem(&mut self, content: Vec<u8>) -> Result<(), EmulatorError> {
+        self.cpu.get_mut_mmu().init_disk(content)
     }
 
     /// Sets up device tree. The emulator has default device tree configuration.
     /// If you want to override it, use this method. This method is expected to
     /// to be called up to only once.
+    /// Returns `Err` if the content is larger than the device tree area.
     ///
     /// # Arguments
     /// * `content` DTB content binary
-    pub fn setup_dtb(&mut self, content: Vec<u8>) {
-        self.cpu.get_mut_mmu().init_dtb(content);
+    pub fn setup_dtb(&mut self, content: Vec<u8>) -> Result<(), EmulatorError> {
+        self.cpu.get_mut_mmu().init_dtb(content)?;
+        self.update_chosen()
+    }
+
+    /// Sets up RISC-V Linux kernel `Image`. The image is placed at
+    /// `text_offset` in its header from the main memory base, and the area up
+    /// to `image_size` following it is zero-filled. The image doesn't start
+    /// by itself, load firmware jumping to it (e.g. OpenSBI `fw_jump.elf`
+    /// built with `FW_JUMP_ADDR` pointing the image) with `setup_program()`.
+    /// Returns `Err` if the content isn't `Image` or doesn't fit in the memory.
+    ///
+    /// # Arguments
+    /// * `content` `Image` file content
+    pub fn setup_kernel_image(&mut self, content: Vec<u8>) -> Result<(), EmulatorError> {
+        let image = LinuxImage::parse(&content)?;
+        let address = self
+            .cpu
+            .get_mmu()
+            .memory_base()
+            .wrapping_add(image.text_offset);
+        self.check_memory_range(address, image.image_size)?;
+        if let Some((start, end)) = self.initrd_range {
+            if address < end && start < address + image.image_size {
+                return Err(EmulatorError::OverlappingSegments {
+                    address: address.max(start),
+                });
+            }
+        }
+
+        let mmu = self.cpu.get_mut_mmu();
+        for (i, byte) in image.data.iter().enumerate() {
+            mmu.store_raw(address + i as u64, *byte);
+        }
+        // Zero-fills BSS
+        for i in image.data.len() as u64..image.image_size {
+            mmu.store_raw(address + i, 0);
+        }
+        self.kernel_range = Some((address, address + image.image_size));
+        Ok(())
+    }
+
+    /// Sets up initial ramdisk (initrd) for Linux. Its location is passed
+    /// to the kernel via `linux,initrd-start` and `linux,initrd-end`
+    /// properties of `/chosen` node in the device tree.
+    /// Returns `Err` if the content doesn't fit in the memory or overlaps
+    /// the kernel image.
+    ///
+    /// # Arguments
+    /// * `content` Initrd file content
+    /// * `address` Physical address to place the initrd
+    pub fn setup_initrd(&mut self, content: Vec<u8>, address: u64) -> Result<(), EmulatorError> {
+        let size = content.len() as u64;
+        self.check_memory_range(address, size)?;
+        if let Some((start, end)) = self.kernel_range {
+            if address < end && start < address + size {
+                return Err(EmulatorError::OverlappingSegments {
+                    address: address.max(start),
+                });
+            }
+        }
+
+        let mmu = self.cpu.get_mut_mmu();
+        for (i, byte) in content.iter().enumerate() {
+            mmu.store_raw(address + i as u64, *byte);
+        }
+        self.initrd_range = Some((address, address + size));
+        self.update_chosen()
+    }
+
+    /// Sets up kernel command line. It's passed to the kernel via `bootargs`
+    /// property of `/chosen` node in the device tree, replacing the one in it.
+    /// Returns `Err` if the updated device tree doesn't fit in the area.
+    ///
+    /// # Arguments
+    /// * `bootargs` Kernel command line, e.g. `console=ttyS0 root=/dev/vda`
+    pub fn setup_bootargs(&mut self, bootargs: &str) -> Result<(), EmulatorError> {
+        self.bootargs = Some(bootargs.to_string());
+        self.update_chosen()
+    }
+
+    /// Patches `/chosen` node of the device tree in the memory with
+    /// the initrd and kernel command line set up so far.
+    fn update_chosen(&mut self) -> Result<(), EmulatorError> {
+        if self.initrd_range.is_none() && self.bootargs.is_none() {
+            return Ok(());
+        }
+        let mut tree = DeviceTree::parse(&self.cpu.get_mmu().get_dtb())?;
+        let chosen = tree.root.get_or_add_child("chosen");
+        if let Some(bootargs) = &self.bootargs {
+            chosen.set_property_string("bootargs", bootargs);
+        }
+        if let Some((start, end)) = self.initrd_range {
+            chosen.set_property_u64("linux,initrd-start", start);
+            chosen.set_property_u64("linux,initrd-end", end);
+        }
+        self.cpu.get_mut_mmu().init_dtb(tree.to_bytes())
     }
 
-    /// Updates XLEN (the width of an integer register in bits) in CPU.
+    /// Updates XLEN (the width of an integer register in bits) in all the harts.
     pub fn update_xlen(&mut self, xlen: Xlen) {
-        self.cpu.update_xlen(xlen);
+        self.cpu.update_xlen(xlen.clone());
+        for cpu in self.harts.iter_mut() {
+            cpu.update_xlen(xlen.clone());
+        }
     }
 
     /// Enables or disables page cache optimization.
@@ -179,22 +559,126 @@ impl Emulator {
     /// See [`Mmu`] for the detail.
     pub fn enable_page_cache(&mut self, enabled: bool) {
         self.cpu.get_mut_mmu().enable_page_cache(enabled);
+        for cpu in self.harts.iter_mut() {
+            cpu.get_mut_mmu().enable_page_cache(enabled);
+        }
+    }
+
+    /// Saves the whole machine state, CPU, MMU, devices, main memory and
+    /// disk content, to a versioned binary snapshot. Restoring it with
+    /// `restore_snapshot()` resumes the execution from this point, e.g.
+    /// a snapshot taken after Linux boot gives a shell immediately.
+    /// Terminal, breakpoints and page cache setting aren't saved.
+    pub fn save_snapshot(&self) -> Vec<u8> {
+        let mut writer = SnapshotWriter::new();
+        for byte in SNAPSHOT_MAGIC {
+            writer.write_u8(byte);
+        }
+        writer.write_u32(SNAPSHOT_VERSION);
+        self.cpu
+            .get_mmu()
+            .get_bus()
+            .borrow()
+            .save_state(&mut writer);
+        for cpu in self.iter_harts() {
+            cpu.save_state(&mut writer);
+        }
+        writer.write_bool(self.booted);
+        self.symbol_map.save_state(&mut writer);
+        writer.into_bytes()
+    }
+
+    /// Restores the machine state saved with `save_snapshot()`. The emulator
+    /// must be configured the same as the one the snapshot was saved from.
+    /// Returns `Err` without updating anything if the snapshot format version
+    /// or the configuration differs. If `Err` is returned because the
+    /// snapshot is broken, the machine state is unspecified.
+    ///
+    /// # Arguments
+    /// * `snapshot` Snapshot content returned by `save_snapshot()`
+    pub fn restore_snapshot(&mut self, snapshot: &[u8]) -> Result<(), EmulatorError> {
+        let mut reader = SnapshotReader::new(snapshot);
+        for byte in SNAPSHOT_MAGIC {
+            if reader.read_u8()? != byte {
+                return Err(EmulatorError::InvalidSnapshot);
+            }
+        }
+        let version = reader.read_u32()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(EmulatorError::UnsupportedSnapshotVersion(version));
+        }
+        self.cpu
+            .get_mmu()
+            .get_bus()
+            .borrow_mut()
+            .restore_state(&mut reader)?;
+        self.cpu.restore_state(&mut reader)?;
+        for cpu in self.harts.iter_mut() {
+            cpu.restore_state(&mut reader)?;
+        }
+        self.booted = reader.read_bool()?;
+        self.symbol_map.restore_state(&mut reader)?;
+        match reader.is_end() {
+            true => Ok(()),
+            false => Err(EmulatorError::InvalidSnapshot),
+        }
     }
 
     /// Returns mutable reference to [`Terminal`].
-    pub fn get_mut_terminal(&mut self) -> &mut Box<dyn Terminal> {
+    pub fn get_mut_terminal(&mut self) -> RefMut<'_, Box<dyn Terminal>> {
         self.cpu.get_mut_terminal()
     }
 
     /// Returns a virtual address corresponding to symbol strings
-    pub fn get_addredd_of_symbol(&self, symbol_string: &String) -> Option<u64> {
-        self.symbol_map.get(symbol_string).copied()
+    #[deprecated(note = "Use get_address_of_symbol()")]
+    pub fn get_addredd_of_symbol(&self, symbol_string: &str) -> Option<u64> {
+        self.get_address_of_symbol(symbol_string)
+    }
+
+    /// Returns a virtual address corresponding to the symbol name
+    ///
+    /// # Arguments
+    /// * `name`
+    pub fn get_address_of_symbol(&self, name: &str) -> Option<u64> {
+        self.symbol_map.get_address(name)
+    }
+
+    /// Returns the symbol name containing the virtual address and the offset
+    /// from the symbol, e.g. `("schedule", 0x24)`. See [`SymbolMap::symbolize()`].
+    ///
+    /// # Arguments
+    /// * `address` Virtual address
+    pub fn symbolize(&self, address: u64) -> Option<(&str, u64)> {
+        self.symbol_map.symbolize(address)
+    }
+
+    /// Loads symbols of an additional ELF file which isn't loaded to memory,
+    /// e.g. `vmlinux` for the kernel in `fw_payload.elf`, so that
+    /// `symbolize()` resolves the addresses in it.
+    /// Returns `Err` if the passed content doesn't seem RISC-V ELF file.
+    ///
+    /// # Arguments
+    /// * `content` ELF file content
+    pub fn load_symbols(&mut self, content: &[u8]) -> Result<(), EmulatorError> {
+        self.symbol_map.load_elf(content, 0)
+    }
+
+    /// Returns symbols of the program and the additional symbol files.
+    pub fn get_symbol_map(&self) -> &SymbolMap {
+        &self.symbol_map
     }
 }
 
 #[cfg(test)]
 mod test_emulator {
     use super::*;
+    use config::{EmulatorBuilder, MachineConfig, DEFAULT_MEMORY_SIZE};
+    use default_terminal::DefaultTerminal;
+    use elf_builder::ElfBuilder;
+    use memory::Memory;
+    use mmu::{AddressingMode, DRAM_BASE};
+    use replay_terminal::{InputLog, RecordingTerminal, ReplayTerminal};
+    use std::num::NonZeroU8;
     use terminal::DummyTerminal;
 
     fn create_emu() -> Emulator {
@@ -206,9 +690,387 @@ mod test_emulator {
         let _emu = create_emu();
     }
 
+    /// Creates `Emulator` running `program` placed at `DRAM_BASE`.
+    fn create_emu_with_program(program: &[u32]) -> Emulator {
+        let mut emu = create_emu();
+        let data: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+        emu.setup_program(ElfBuilder::program(DRAM_BASE, &data).build())
+            .unwrap();
+        emu
+    }
+
     #[test]
-    #[ignore]
-    fn run() {}
+    fn memory_validates_addresses_up_to_capacity() {
+        let mut memory = Memory::new();
+        memory.init(0x100);
+        assert_eq!(0x100, memory.capacity());
+        assert!(memory.validate_address(0));
+        assert!(memory.validate_address(0xff));
+        assert!(!memory.validate_address(0x100));
+    }
+
+    #[test]
+    fn run() {
+        // lui t0, 0x100; lui t1, 0x5; addi t1, t1, 0x555; sw t1, 0(t0)
+        let mut emu = create_emu_with_program(&[0x001002b7, 0x00005337, 0x55530313, 0x0062a023]);
+        assert_eq!(StopReason::PowerOff { exit_code: 0 }, emu.run());
+    }
+
+    #[test]
+    fn run_for() {
+        // j .
+        let mut emu = create_emu_with_program(&[0x0000006f]);
+        assert_eq!(StopReason::CycleBudgetExhausted, emu.run_for(100));
+        assert_eq!(DRAM_BASE, emu.cpu.read_pc());
+    }
+
+    #[test]
+    fn boot() {
+        // j .
+        let mut emu = create_emu_with_program(&[0x0000006f]);
+        emu.setup_dtb(vec![0xd0, 0x0d, 0xfe, 0xed]).unwrap();
+        emu.tick();
+        assert_eq!(config::RESET_VECTOR + 4, emu.cpu.read_pc());
+        emu.run_for(4);
+        assert_eq!(DRAM_BASE, emu.cpu.read_pc());
+        // a0 = hart ID, a1 = device tree at the end of the memory
+        let dtb_address = DRAM_BASE + DEFAULT_MEMORY_SIZE - 0x1000;
+        assert_eq!(0, emu.cpu.read_register(10));
+        assert_eq!(dtb_address, emu.cpu.read_register(11) as u64);
+        let mmu = emu.cpu.get_mut_mmu();
+        assert_eq!(dtb_address, mmu.get_dtb_address());
+        assert_eq!(0xedfe0dd0, mmu.load_word_raw(dtb_address));
+    }
+
+    #[test]
+    fn boot_with_dtb_address() {
+        let mut emu = EmulatorBuilder::new()
+            .dtb_address(Some(DRAM_BASE + 0x100000))
+            .build(Box::new(DummyTerminal::new()))
+            .unwrap();
+        // j .
+        emu.setup_program(ElfBuilder::program(DRAM_BASE, &[0x6f, 0, 0, 0]).build())
+            .unwrap();
+        emu.run_for(5);
+        assert_eq!(DRAM_BASE, emu.cpu.read_pc());
+        assert_eq!(DRAM_BASE as i64 + 0x100000, emu.cpu.read_register(11));
+        // The generated device tree is placed
+        let mmu = emu.cpu.get_mut_mmu();
+        assert_eq!(0xedfe0dd0, mmu.load_word_raw(DRAM_BASE + 0x100000));
+
+        for address in [
+            DRAM_BASE - 8,
+            DRAM_BASE + 4,
+            DRAM_BASE + DEFAULT_MEMORY_SIZE,
+        ] {
+            let result = EmulatorBuilder::new()
+                .dtb_address(Some(address))
+                .build(Box::new(DummyTerminal::new()));
+            match result {
+                Err(EmulatorError::InvalidDtbAddress(_)) => {}
+                _ => panic!("Device tree address {:X} must be rejected", address),
+            };
+        }
+    }
+
+    #[test]
+    fn run_for_power_off() {
+        // lui t0, 0x100; lui t1, 0x2a3; addi t1, t1, 0x333; sw t1, 0(t0); j .
+        let program = [0x001002b7, 0x002a3337, 0x33330313, 0x0062a023, 0x0000006f];
+        let mut emu = create_emu_with_program(&program);
+        assert_eq!(StopReason::PowerOff { exit_code: 42 }, emu.run_for(100));
+        // Powered off machine doesn't run any more
+        let pc = emu.cpu.read_pc();
+        assert_eq!(StopReason::PowerOff { exit_code: 42 }, emu.run_for(100));
+        assert_eq!(pc, emu.cpu.read_pc());
+    }
+
+    #[test]
+    fn run_for_breakpoint() {
+        // addi ra, ra, 1 x 3; j .
+        let program = [0x00108093, 0x00108093, 0x00108093, 0x0000006f];
+        let mut emu = create_emu_with_program(&program);
+        emu.add_breakpoint(DRAM_BASE + 8);
+        let expected = StopReason::Breakpoint {
+            address: DRAM_BASE + 8,
+        };
+        assert_eq!(expected, emu.run_for(100));
+        assert_eq!(2, emu.cpu.read_register(1));
+        // Resumes from the breakpoint
+        assert_eq!(StopReason::CycleBudgetExhausted, emu.run_for(100));
+        assert_eq!(3, emu.cpu.read_register(1));
+
+        emu.remove_breakpoint(DRAM_BASE + 8);
+        emu.cpu.update_pc(DRAM_BASE);
+        assert_eq!(StopReason::CycleBudgetExhausted, emu.run_for(100));
+        assert_eq!(6, emu.cpu.read_register(1));
+    }
+
+    #[test]
+    fn run_for_fatal_trap() {
+        // addi ra, ra, 1; ecall
+        let mut emu = create_emu_with_program(&[0x00108093, 0x00000073]);
+        let expected = StopReason::FatalTrap {
+            trap_type: TrapType::EnvironmentCallFromMMode,
+            value: DRAM_BASE + 4,
+            address: DRAM_BASE + 4,
+        };
+        assert_eq!(expected, emu.run_for(100));
+        assert_eq!(expected, emu.run_for(100));
+    }
+
+    #[test]
+    fn run_for_halted() {
+        // wfi
+        let mut emu = create_emu_with_program(&[0x10500073]);
+        assert_eq!(StopReason::Halted, emu.run_for(100));
+    }
+
+    #[test]
+    fn run_until() {
+        // addi ra, ra, 1; j -4
+        let mut emu = create_emu_with_program(&[0x00108093, 0xffdff06f]);
+        let reason = emu.run_until(|emu| emu.cpu.read_register(1) == 10);
+        assert_eq!(StopReason::ConditionMet, reason);
+        assert_eq!(10, emu.cpu.read_register(1));
+    }
+
+    /// Creates `Emulator` with two harts running the program.
+    fn create_smp_emu_with_program(program: &[u32]) -> Emulator {
+        let mut emu = EmulatorBuilder::new()
+            .hart_count(2)
+            .build(Box::new(DummyTerminal::new()))
+            .unwrap();
+        let data: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+        emu.setup_program(ElfBuilder::program(DRAM_BASE, &data).build())
+            .unwrap();
+        emu
+    }
+
+    #[test]
+    fn multiple_harts() {
+        // j .
+        let mut emu = create_smp_emu_with_program(&[0x0000006f]);
+        assert_eq!(2, emu.get_hart_count());
+        assert!(emu.get_hart(2).is_none());
+        emu.run_for(100);
+        // All the harts boot from the boot ROM with a0 = hart ID
+        for hart_id in 0..2 {
+            let cpu = emu.get_hart(hart_id).unwrap();
+            assert_eq!(DRAM_BASE, cpu.read_pc());
+            assert_eq!(hart_id as i64, cpu.read_register(10));
+            assert_eq!(
+                emu.cpu.get_mmu().get_dtb_address(),
+                cpu.read_register(11) as u64
+            );
+        }
+    }
+
+    #[test]
+    fn multiple_harts_software_interrupt() {
+        // csrr t0, mhartid; bnez t0, 1f
+        // Hart 0: lui t1, 0x2000; li t2, 1; sw t2, 4(t1); j .
+        // Hart 1: 1: csrr t3, mip; beqz t3, 1b; j .
+        let mut emu = create_smp_emu_with_program(&[
+            0xf14022f3, 0x00029a63, 0x02000337, 0x00100393, 0x00732223, 0x0000006f, 0x34402e73,
+            0xfe0e0ee3, 0x0000006f,
+        ]);
+        let reason = emu.run_until(|emu| emu.get_hart(1).unwrap().read_register(28) != 0);
+        assert_eq!(StopReason::ConditionMet, reason);
+        // Only hart 1 gets machine software interrupt written to its msip
+        assert_eq!(0x8, emu.get_hart(1).unwrap().read_register(28));
+        assert_eq!(0, emu.cpu.read_register(28));
+    }
+
+    #[test]
+    fn multiple_harts_reservation() {
+        // j .
+        let mut emu = create_smp_emu_with_program(&[0x0000006f]);
+        let address = DRAM_BASE + 0x100;
+
+        // Store from another hart invalidates the reservation
+        emu.cpu.get_mut_mmu().load_reserved(address, 8).unwrap();
+        let hart = emu.get_mut_hart(1).unwrap().get_mut_mmu();
+        hart.store_doubleword(address + 4, 1).unwrap();
+        let mmu = emu.cpu.get_mut_mmu();
+        assert!(!mmu.store_conditional(address, 2, 8).unwrap());
+        assert_eq!(0x100000000, mmu.load_doubleword(address).unwrap());
+
+        // Reservations of the harts are independent
+        emu.cpu.get_mut_mmu().load_reserved(address, 8).unwrap();
+        let hart = emu.get_mut_hart(1).unwrap().get_mut_mmu();
+        hart.load_reserved(address + 0x10, 8).unwrap();
+        assert!(hart.store_conditional(address + 0x10, 3, 8).unwrap());
+        let mmu = emu.cpu.get_mut_mmu();
+        assert!(mmu.store_conditional(address, 2, 8).unwrap());
+
+        // Successful store conditional of another hart invalidates it too
+        emu.cpu.get_mut_mmu().load_reserved(address, 8).unwrap();
+        let hart = emu.get_mut_hart(1).unwrap().get_mut_mmu();
+        hart.load_reserved(address, 8).unwrap();
+        assert!(hart.store_conditional(address, 4, 8).unwrap());
+        let mmu = emu.cpu.get_mut_mmu();
+        assert!(!mmu.store_conditional(address, 2, 8).unwrap());
+        assert_eq!(4, mmu.load_doubleword(address).unwrap());
+    }
+
+    /// Creates `Emulator` with 1MiB memory running a loop which increments
+    /// `ra` and stores it to `DRAM_BASE + 64`.
+    fn create_snapshot_emu() -> Emulator {
+        create_snapshot_emu_with_harts(1)
+    }
+
+    /// Same as `create_snapshot_emu()` except that all the harts run the loop.
+    fn create_snapshot_emu_with_harts(hart_count: usize) -> Emulator {
+        let mut emu = EmulatorBuilder::new()
+            .memory_size(0x100000)
+            .hart_count(hart_count)
+            .build(Box::new(DummyTerminal::new()))
+            .unwrap();
+        // auipc t0, 0; addi ra, ra, 1; sd ra, 64(t0); j -8
+        let program: [u32; 4] = [0x00000297, 0x00108093, 0x0412b023, 0xff9ff06f];
+        let data: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+        emu.setup_program(ElfBuilder::program(DRAM_BASE, &data).build())
+            .unwrap();
+        emu.setup_filesystem(vec![0xa5; 0x1000]).unwrap();
+        emu
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut emu = create_snapshot_emu();
+        emu.run_for(100);
+        let snapshot = emu.save_snapshot();
+        emu.run_for(100);
+        let pc = emu.cpu.read_pc();
+        let ra = emu.cpu.read_register(1);
+
+        let mut restored = create_snapshot_emu();
+        restored.restore_snapshot(&snapshot).unwrap();
+        assert_eq!(snapshot, restored.save_snapshot());
+        restored.run_for(100);
+        assert_eq!(pc, restored.cpu.read_pc());
+        assert_eq!(ra, restored.cpu.read_register(1));
+        let stored = emu.cpu.get_mut_mmu().load_doubleword(DRAM_BASE + 64);
+        let mmu = restored.cpu.get_mut_mmu();
+        assert_eq!(
+            stored.unwrap(),
+            mmu.load_doubleword(DRAM_BASE + 64).unwrap()
+        );
+        assert_eq!(emu.save_snapshot(), restored.save_snapshot());
+    }
+
+    /// Creates `Emulator` running a loop which reads UART receiver buffer
+    /// and accumulates the input bytes to `ra`.
+    fn create_uart_echo_emu(terminal: Box<dyn Terminal>) -> Emulator {
+        let mut emu = EmulatorBuilder::new()
+            .memory_size(0x100000)
+            .build(terminal)
+            .unwrap();
+        // lui t0, 0x10000; lbu t1, 0(t0); add ra, ra, t1; j -8
+        let program: [u32; 4] = [0x100002b7, 0x0002c303, 0x006080b3, 0xff9ff06f];
+        let data: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+        emu.setup_program(ElfBuilder::program(DRAM_BASE, &data).build())
+            .unwrap();
+        emu
+    }
+
+    #[test]
+    fn record_and_replay_input() {
+        let mut terminal = DefaultTerminal::new();
+        // DefaultTerminal pops the input from the tail
+        terminal.input_data = "cba".bytes().map(|b| NonZeroU8::new(b).unwrap()).collect();
+        let terminal = RecordingTerminal::new(Box::new(terminal));
+        let log = terminal.get_log();
+        let mut emu = create_uart_echo_emu(Box::new(terminal));
+        emu.run_for(0x100000);
+        assert_eq!(
+            ('a' as i64) + ('b' as i64) + ('c' as i64),
+            emu.cpu.read_register(1)
+        );
+
+        let text = log.lock().unwrap().to_string();
+        let log: InputLog = text.parse().unwrap();
+        assert_eq!(3, log.get_events().len());
+        assert_eq!(NonZeroU8::new(b'a').unwrap(), log.get_events()[0].value);
+
+        let terminal = ReplayTerminal::new(log, Box::new(DummyTerminal::new()));
+        let mut replayed = create_uart_echo_emu(Box::new(terminal));
+        replayed.run_for(0x100000);
+        assert_eq!(emu.save_snapshot(), replayed.save_snapshot());
+    }
+
+    #[test]
+    fn input_log_rejects_invalid_text() {
+        match "10 97\n20\n".parse::<InputLog>() {
+            Err(EmulatorError::InvalidInputLog { line: 2 }) => {}
+            _ => panic!("Line without value must be rejected"),
+        };
+        match "10 0\n".parse::<InputLog>() {
+            Err(EmulatorError::InvalidInputLog { line: 1 }) => {}
+            _ => panic!("Zero value must be rejected"),
+        };
+        match "20 97\n\n10 98\n".parse::<InputLog>() {
+            Err(EmulatorError::InvalidInputLog { line: 3 }) => {}
+            _ => panic!("Clocks out of order must be rejected"),
+        };
+    }
+
+    #[test]
+    fn snapshot_multiple_harts() {
+        let mut emu = create_snapshot_emu_with_harts(2);
+        emu.run_for(100);
+        let snapshot = emu.save_snapshot();
+        emu.run_for(100);
+
+        let mut restored = create_snapshot_emu_with_harts(2);
+        restored.restore_snapshot(&snapshot).unwrap();
+        assert_eq!(snapshot, restored.save_snapshot());
+        restored.run_for(100);
+        for hart_id in 0..2 {
+            let cpu = emu.get_hart(hart_id).unwrap();
+            let restored_cpu = restored.get_hart(hart_id).unwrap();
+            assert_eq!(cpu.read_pc(), restored_cpu.read_pc());
+            assert_eq!(cpu.read_register(1), restored_cpu.read_register(1));
+        }
+        assert_eq!(emu.save_snapshot(), restored.save_snapshot());
+
+        match create_snapshot_emu().restore_snapshot(&snapshot) {
+            Err(EmulatorError::SnapshotMismatch) => {}
+            _ => panic!("Snapshot with a different hart count must be rejected"),
+        };
+    }
+
+    #[test]
+    fn snapshot_rejects_invalid_snapshot() {
+        let emu = create_snapshot_emu();
+        let snapshot = emu.save_snapshot();
+        let mut restored = create_snapshot_emu();
+
+        match restored.restore_snapshot(&snapshot[..snapshot.len() - 1]) {
+            Err(EmulatorError::InvalidSnapshot) => {}
+            _ => panic!("Truncated snapshot must be rejected"),
+        };
+        match restored.restore_snapshot(&[snapshot.as_slice(), &[0]].concat()) {
+            Err(EmulatorError::InvalidSnapshot) => {}
+            _ => panic!("Snapshot with trailing data must be rejected"),
+        };
+        let mut future_snapshot = snapshot.clone();
+        future_snapshot[8] = 0xff;
+        match restored.restore_snapshot(&future_snapshot) {
+            Err(EmulatorError::UnsupportedSnapshotVersion(0xff)) => {}
+            _ => panic!("Unknown snapshot version must be rejected"),
+        };
+
+        let mut different_emu = EmulatorBuilder::new()
+            .memory_size(0x200000)
+            .build(Box::new(DummyTerminal::new()))
+            .unwrap();
+        match different_emu.restore_snapshot(&snapshot) {
+            Err(EmulatorError::SnapshotMismatch) => {}
+            _ => panic!("Snapshot from a different machine must be rejected"),
+        };
+    }
 
     #[test]
     #[ignore]
@@ -223,20 +1085,486 @@ mod test_emulator {
     fn tick() {}
 
     #[test]
-    #[ignore]
-    fn setup_program() {}
+    fn setup_program() {
+        let mut emu = create_emu();
+        let elf = ElfBuilder::program(DRAM_BASE + 0x100, &[0x93, 0x80, 0x10, 0x00]).build();
+        emu.setup_program(elf).unwrap();
+        assert_eq!(DRAM_BASE + 0x100, emu.cpu.read_pc());
+        assert_eq!(
+            0x00108093,
+            emu.cpu.get_mut_mmu().load_word_raw(DRAM_BASE + 0x100)
+        );
+    }
 
     #[test]
-    #[ignore]
-    fn load_program_for_symbols() {}
+    fn setup_program_rejects_non_elf() {
+        let mut emu = create_emu();
+        match emu.setup_program(vec![0; 0x100]) {
+            Err(EmulatorError::InvalidElf(_)) => {}
+            _ => panic!("Non ELF content must be rejected"),
+        };
+        match emu.setup_program(vec![]) {
+            Err(EmulatorError::InvalidElf(_)) => {}
+            _ => panic!("Empty content must be rejected"),
+        };
+    }
 
     #[test]
-    #[ignore]
-    fn setup_filesystem() {}
+    fn setup_program_rejects_other_machine() {
+        let mut emu = create_emu();
+        // EM_X86_64
+        let elf = ElfBuilder::program(DRAM_BASE, &[0; 4]).machine(62).build();
+        match emu.setup_program(elf) {
+            Err(EmulatorError::UnsupportedMachine(62)) => {}
+            _ => panic!("Non RISC-V ELF must be rejected"),
+        };
+    }
 
     #[test]
-    #[ignore]
-    fn setup_dtb() {}
+    fn setup_program_rejects_out_of_memory_segment() {
+        let mut emu = create_emu();
+        let elf = ElfBuilder::program(0x1000, &[0; 4]).build();
+        match emu.setup_program(elf) {
+            Err(EmulatorError::SegmentOutOfMemory {
+                address: 0x1000, ..
+            }) => {}
+            _ => panic!("Segment below DRAM must be rejected"),
+        };
+        let elf = ElfBuilder::program(DRAM_BASE + DEFAULT_MEMORY_SIZE - 2, &[0; 4]).build();
+        match emu.setup_program(elf) {
+            Err(EmulatorError::SegmentOutOfMemory { .. }) => {}
+            _ => panic!("Segment crossing the end of DRAM must be rejected"),
+        };
+    }
+
+    #[test]
+    fn setup_program_rejected_keeps_state() {
+        let mut emu = create_emu();
+        emu.update_xlen(Xlen::Bit32);
+        let elf = with_test_symbols(ElfBuilder::program(0x1000, &[0; 0x48])).build();
+        match emu.setup_program(elf) {
+            Err(EmulatorError::SegmentOutOfMemory { .. }) => {}
+            _ => panic!("Segment below DRAM must be rejected"),
+        };
+        assert_eq!(None, emu.symbolize(DRAM_BASE));
+        assert_eq!(None, emu.get_address_of_symbol("main"));
+        assert!(matches!(emu.cpu.get_xlen(), Xlen::Bit32));
+    }
+
+    #[test]
+    fn setup_program_rejects_truncated_elf() {
+        let mut emu = create_emu();
+        let mut elf = ElfBuilder::program(DRAM_BASE, &[0; 4]).build();
+        // Points the segment content out of the file
+        elf[72..80].copy_from_slice(&0x10000u64.to_le_bytes());
+        match emu.setup_program(elf) {
+            Err(EmulatorError::InvalidElf(_)) => {}
+            _ => panic!("Truncated ELF must be rejected"),
+        };
+    }
+
+    #[test]
+    fn setup_program_without_section_headers() {
+        let mut emu = create_emu();
+        let data = [0x13, 0x05, 0xa0, 0x02];
+        let elf = ElfBuilder::program(DRAM_BASE, &data)
+            .without_section_headers()
+            .build();
+        emu.setup_program(elf).unwrap();
+        assert_eq!(DRAM_BASE, emu.cpu.read_pc());
+        assert_eq!(0x02a00513, emu.cpu.get_mut_mmu().load_word_raw(DRAM_BASE));
+    }
+
+    #[test]
+    fn setup_program_zero_fills_bss() {
+        let mut emu = create_emu();
+        // Fills the memory with garbage which BSS must not inherit
+        let garbage = ElfBuilder::program(DRAM_BASE, &[0xff; 0x20]).build();
+        emu.setup_program(garbage).unwrap();
+        let data = [1, 2, 3, 4];
+        let elf = ElfBuilder::new(DRAM_BASE)
+            .segment(DRAM_BASE, &data, 0x20)
+            .build();
+        emu.setup_program(elf).unwrap();
+        let mmu = emu.cpu.get_mut_mmu();
+        assert_eq!(0x04030201, mmu.load_word_raw(DRAM_BASE));
+        for i in 1..8 {
+            assert_eq!(0, mmu.load_word_raw(DRAM_BASE + i * 4));
+        }
+    }
+
+    #[test]
+    fn setup_program_rejects_overlapping_segments() {
+        let mut emu = create_emu();
+        let data = [0; 0x10];
+        let elf = ElfBuilder::new(DRAM_BASE)
+            .segment(DRAM_BASE, &data, 0x10)
+            .segment(DRAM_BASE + 0x8, &data, 0x10)
+            .build();
+        match emu.setup_program(elf) {
+            Err(EmulatorError::OverlappingSegments { address }) => {
+                assert_eq!(DRAM_BASE + 0x8, address)
+            }
+            _ => panic!("Overlapping segments must be rejected"),
+        };
+    }
+
+    #[test]
+    fn setup_program_rejects_no_segment() {
+        let mut emu = create_emu();
+        let elf = ElfBuilder::new(DRAM_BASE).build();
+        match emu.setup_program(elf) {
+            Err(EmulatorError::NoLoadableSegment) => {}
+            _ => panic!("ELF without loadable segments must be rejected"),
+        };
+    }
+
+    #[test]
+    fn setup_program_with_base() {
+        let mut emu = create_emu();
+        let base = DRAM_BASE + 0x10000;
+        let data = [0; 0x10];
+        // Position independent executable linked at 0 whose data at 0x8
+        // points to the address 0x4 of the program.
+        let elf = ElfBuilder::new(0x4)
+            .position_independent()
+            .segment(0, &data, 0x10)
+            .relocation(0x8, 0x4)
+            .build();
+        emu.setup_program_with_base(elf.clone(), base).unwrap();
+        assert_eq!(base + 0x4, emu.cpu.read_pc());
+        let mmu = emu.cpu.get_mut_mmu();
+        assert_eq!(base + 0x4, mmu.load_word_raw(base + 0x8) as u64);
+        assert_eq!(0, mmu.load_word_raw(base + 0xc));
+
+        // Loaded at the beginning of the memory by default
+        emu.setup_program(elf).unwrap();
+        assert_eq!(DRAM_BASE + 0x4, emu.cpu.read_pc());
+
+        // Non position independent executable can't be moved
+        let elf = ElfBuilder::program(DRAM_BASE, &[0; 4]).build();
+        match emu.setup_program_with_base(elf, base) {
+            Err(EmulatorError::NotPositionIndependent) => {}
+            _ => panic!("Non position independent executable must be rejected"),
+        };
+    }
+
+    #[test]
+    fn setup_filesystem() {
+        let mut emu = create_emu();
+        emu.setup_filesystem(vec![0; 0x1000]).unwrap();
+        match emu.setup_filesystem(vec![0; 0x32000 * 512 + 1]) {
+            Err(EmulatorError::DiskImageTooLarge { .. }) => {}
+            _ => panic!("Filesystem larger than the disk must be rejected"),
+        };
+    }
+
+    #[test]
+    fn setup_dtb() {
+        let mut emu = create_emu();
+        match emu.setup_dtb(vec![0; DEFAULT_MEMORY_SIZE as usize + 1]) {
+            Err(EmulatorError::DtbTooLarge { .. }) => {}
+            _ => panic!("DTB larger than the area must be rejected"),
+        };
+    }
+
+    /// Builds a Linux kernel `Image` having the header and `data` following it.
+    fn build_kernel_image(text_offset: u64, image_size: u64, data: &[u8]) -> Vec<u8> {
+        let mut image = vec![0; 64];
+        image[8..16].copy_from_slice(&text_offset.to_le_bytes());
+        image[16..24].copy_from_slice(&image_size.to_le_bytes());
+        image[48..56].copy_from_slice(b"RISCV\0\0\0");
+        image[56..60].copy_from_slice(b"RSC\x05");
+        image.extend_from_slice(data);
+        image
+    }
+
+    #[test]
+    fn device_tree_roundtrip() {
+        let emu = create_emu();
+        let dtb = emu.cpu.get_mmu().get_dtb();
+        let tree = fdt::DeviceTree::parse(&dtb).unwrap();
+        let chosen = tree.root.get_child("chosen").unwrap();
+        assert_eq!(
+            Some(&b"root=/dev/vda rw ttyS0\0"[..]),
+            chosen.get_property("bootargs")
+        );
+        assert_eq!(tree, fdt::DeviceTree::parse(&tree.to_bytes()).unwrap());
+        match fdt::DeviceTree::parse(&dtb[..dtb.len() - 4]) {
+            Err(EmulatorError::InvalidDtb) => {}
+            _ => panic!("Truncated DTB must be rejected"),
+        };
+    }
+
+    #[test]
+    fn generate_device_tree() {
+        let config = MachineConfig {
+            memory_size: 0x100000,
+            isa: "rv32imac".to_string(),
+            virtio_block_disk_base: None,
+            ..MachineConfig::new()
+        };
+        let tree = config.generate_device_tree().unwrap();
+        let memory = tree.root.get_child("memory@80000000").unwrap();
+        assert_eq!(
+            Some(&[0, 0, 0, 0, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0x10, 0, 0][..]),
+            memory.get_property("reg")
+        );
+        let cpu = tree
+            .root
+            .get_child("cpus")
+            .unwrap()
+            .get_child("cpu@0")
+            .unwrap();
+        assert_eq!(Some(&b"rv32imac\0"[..]), cpu.get_property("riscv,isa"));
+        assert_eq!(Some(&b"riscv,sv32\0"[..]), cpu.get_property("mmu-type"));
+        let soc = tree.root.get_child("soc").unwrap();
+        assert!(soc.get_child("clint@2000000").is_some());
+        assert!(soc.get_child("interrupt-controller@c000000").is_some());
+        assert!(soc.get_child("uart@10000000").is_some());
+        assert!(soc.get_child("test@100000").is_some());
+        // Disabled device isn't described
+        assert!(soc.get_child("virtio_mmio@10001000").is_none());
+
+        // Emulator maps the tree generated from its configuration
+        let emu = EmulatorBuilder::new()
+            .config(config)
+            .build(Box::new(DummyTerminal::new()))
+            .unwrap();
+        assert_eq!(
+            tree,
+            fdt::DeviceTree::parse(&emu.cpu.get_mmu().get_dtb()).unwrap()
+        );
+    }
+
+    #[test]
+    fn setup_kernel_image() {
+        let mut emu = create_emu();
+        // Garbage left by a previous program in BSS
+        emu.cpu
+            .get_mut_mmu()
+            .store_doubleword_raw(DRAM_BASE + 0x200048, u64::MAX);
+        let image = build_kernel_image(0x200000, 0x1000, &[0x13, 0, 0, 0, 0x6f, 0, 0, 0]);
+        emu.setup_kernel_image(image).unwrap();
+        let mmu = emu.cpu.get_mut_mmu();
+        assert_eq!(0x13, mmu.load_word_raw(DRAM_BASE + 0x200040));
+        assert_eq!(0x6f, mmu.load_word_raw(DRAM_BASE + 0x200044));
+        assert_eq!(0, mmu.load_word_raw(DRAM_BASE + 0x200048));
+        assert_eq!(0, mmu.load_word_raw(DRAM_BASE + 0x20004c));
+
+        let mut image = build_kernel_image(0x200000, 0, &[]);
+        image[48] = 0;
+        image[56] = 0;
+        match emu.setup_kernel_image(image) {
+            Err(EmulatorError::InvalidKernelImage) => {}
+            _ => panic!("Image without magic must be rejected"),
+        };
+        let image = build_kernel_image(DEFAULT_MEMORY_SIZE, 0, &[]);
+        match emu.setup_kernel_image(image) {
+            Err(EmulatorError::SegmentOutOfMemory { .. }) => {}
+            _ => panic!("Image outside the memory must be rejected"),
+        };
+    }
+
+    #[test]
+    fn setup_initrd_and_bootargs() {
+        let mut emu = create_emu();
+        let image = build_kernel_image(0x200000, 0x1000, &[]);
+        emu.setup_kernel_image(image).unwrap();
+        match emu.setup_initrd(vec![1; 0x1000], DRAM_BASE + 0x200800) {
+            Err(EmulatorError::OverlappingSegments { .. }) => {}
+            _ => panic!("Initrd overlapping the kernel must be rejected"),
+        };
+        emu.setup_initrd(vec![1; 0x1000], DRAM_BASE + 0x400000)
+            .unwrap();
+        emu.setup_bootargs("console=ttyS0 rdinit=/init").unwrap();
+        assert_eq!(
+            0x01010101,
+            emu.cpu.get_mut_mmu().load_word_raw(DRAM_BASE + 0x400ffc)
+        );
+
+        let tree = fdt::DeviceTree::parse(&emu.cpu.get_mmu().get_dtb()).unwrap();
+        let chosen = tree.root.get_child("chosen").unwrap();
+        assert_eq!(
+            Some(&b"console=ttyS0 rdinit=/init\0"[..]),
+            chosen.get_property("bootargs")
+        );
+        let start = (DRAM_BASE + 0x400000).to_be_bytes();
+        let end = (DRAM_BASE + 0x401000).to_be_bytes();
+        assert_eq!(Some(&start[..]), chosen.get_property("linux,initrd-start"));
+        assert_eq!(Some(&end[..]), chosen.get_property("linux,initrd-end"));
+        // The other nodes are kept
+        assert!(tree.root.get_child("cpus").is_some());
+
+        // The properties survive replacing the device tree
+        let dtb = MachineConfig::new().generate_device_tree().unwrap();
+        emu.setup_dtb(dtb.to_bytes()).unwrap();
+        let tree = fdt::DeviceTree::parse(&emu.cpu.get_mmu().get_dtb()).unwrap();
+        let chosen = tree.root.get_child("chosen").unwrap();
+        assert_eq!(Some(&end[..]), chosen.get_property("linux,initrd-end"));
+    }
+
+    #[test]
+    fn new_with_config() {
+        let emu = EmulatorBuilder::new()
+            .memory_base(0x20000000)
+            .memory_size(0x100000)
+            .virtio_block_disk_base(None)
+            .build(Box::new(DummyTerminal::new()));
+        let mut emu = match emu {
+            Ok(emu) => emu,
+            Err(e) => panic!("Failed to build: {}", e),
+        };
+        assert_eq!(0x20000000, emu.cpu.get_mut_mmu().memory_base());
+        assert_eq!(0x100000, emu.cpu.get_mut_mmu().memory_capacity());
+
+        // Programs are loaded to the configured memory
+        let elf = ElfBuilder::program(0x200ffffc, &[1, 2, 3, 4]).build();
+        emu.setup_program(elf).unwrap();
+        assert_eq!(0x04030201, emu.cpu.get_mut_mmu().load_word_raw(0x200ffffc));
+        let elf = ElfBuilder::program(DRAM_BASE, &[0; 4]).build();
+        match emu.setup_program(elf) {
+            Err(EmulatorError::SegmentOutOfMemory { .. }) => {}
+            _ => panic!("Segment out of the configured memory must be rejected"),
+        };
+
+        // Disabled device isn't mapped
+        let mmu = emu.cpu.get_mut_mmu();
+        assert_eq!(Ok(true), mmu.validate_address(0x10000000));
+        assert_eq!(Ok(false), mmu.validate_address(0x10001000));
+    }
+
+    #[test]
+    fn new_with_config_moves_devices() {
+        let mut emu = EmulatorBuilder::new()
+            .uart_base(Some(0x20000000))
+            .build(Box::new(DummyTerminal::new()))
+            .unwrap();
+        let mmu = emu.cpu.get_mut_mmu();
+        assert_eq!(Ok(false), mmu.validate_address(0x10000000));
+        assert_eq!(Ok(true), mmu.validate_address(0x20000000));
+        // Line status register: transmitter empty
+        assert_eq!(0x20, mmu.load_word_raw(0x20000004) >> 8 & 0x20);
+    }
+
+    #[test]
+    fn new_with_config_rejects_invalid_config() {
+        let build = |builder: EmulatorBuilder| builder.build(Box::new(DummyTerminal::new()));
+        match build(EmulatorBuilder::new().hart_count(0)) {
+            Err(EmulatorError::UnsupportedHartCount(0)) => {}
+            _ => panic!("No hart must be rejected"),
+        };
+        match build(EmulatorBuilder::new().hart_count(config::MAX_HART_COUNT + 1)) {
+            Err(EmulatorError::UnsupportedHartCount(_)) => {}
+            _ => panic!("Too many harts must be rejected"),
+        };
+        match build(EmulatorBuilder::new().memory_size(0)) {
+            Err(EmulatorError::InvalidMemoryRange { .. }) => {}
+            _ => panic!("Empty memory must be rejected"),
+        };
+        match build(EmulatorBuilder::new().memory_base(u64::MAX)) {
+            Err(EmulatorError::InvalidMemoryRange { .. }) => {}
+            _ => panic!("Memory exceeding the address space must be rejected"),
+        };
+        match build(EmulatorBuilder::new().uart_base(Some(0x0c001000))) {
+            Err(EmulatorError::OverlappingMemoryMap { address }) => {
+                assert_eq!(0x0c001000, address)
+            }
+            _ => panic!("Overlapping devices must be rejected"),
+        };
+        match build(EmulatorBuilder::new().memory_base(0x10000000)) {
+            Err(EmulatorError::OverlappingMemoryMap { .. }) => {}
+            _ => panic!("Memory overlapping devices must be rejected"),
+        };
+        match build(EmulatorBuilder::new().mmu_type(Some(AddressingMode::SV32))) {
+            Err(EmulatorError::UnsupportedMmuType(AddressingMode::SV32)) => {}
+            _ => panic!("Sv32 must be rejected for RV64"),
+        };
+        match build(
+            EmulatorBuilder::new()
+                .isa("rv32imac")
+                .mmu_type(Some(AddressingMode::SV48)),
+        ) {
+            Err(EmulatorError::UnsupportedMmuType(AddressingMode::SV48)) => {}
+            _ => panic!("Sv48 must be rejected for RV32"),
+        };
+        for vlen in [64, 192, config::MAX_VLEN * 2] {
+            match build(EmulatorBuilder::new().vlen(vlen)) {
+                Err(EmulatorError::UnsupportedVlen(_)) => {}
+                _ => panic!("VLEN {} must be rejected", vlen),
+            };
+        }
+        for isa in ["rv128i", "rv64", "rv64mi", "rv64ix", "x86"] {
+            match build(EmulatorBuilder::new().isa(isa)) {
+                Err(EmulatorError::InvalidIsa(_)) => {}
+                _ => panic!("Invalid ISA string {} must be rejected", isa),
+            };
+        }
+    }
+
+    #[test]
+    fn new_with_config_mmu_type() {
+        for (mode, expected) in [
+            (None, &b"riscv,sv39\0"[..]),
+            (Some(AddressingMode::SV57), &b"riscv,sv57\0"[..]),
+            (Some(AddressingMode::None), &b"riscv,none\0"[..]),
+        ] {
+            let config = MachineConfig {
+                mmu_type: mode,
+                ..MachineConfig::default()
+            };
+            config.validate().unwrap();
+            let tree = config.generate_device_tree().unwrap();
+            let cpu = tree
+                .root
+                .get_child("cpus")
+                .unwrap()
+                .get_child("cpu@0")
+                .unwrap();
+            assert_eq!(Some(expected), cpu.get_property("mmu-type"));
+        }
+    }
+
+    #[test]
+    fn new_with_config_isa() {
+        let emu = EmulatorBuilder::new()
+            .isa("rv32gc_zicsr")
+            .build(Box::new(DummyTerminal::new()))
+            .unwrap();
+        assert!(matches!(emu.cpu.get_xlen(), Xlen::Bit32));
+    }
+
+    #[test]
+    fn new_with_config_bit_manipulation_isa() {
+        let tree = MachineConfig::new().generate_device_tree().unwrap();
+        let cpu = tree
+            .root
+            .get_child("cpus")
+            .unwrap()
+            .get_child("cpu@0")
+            .unwrap();
+        assert_eq!(
+            Some(&b"rv64imafdcvsu_zba_zbb_zbc_zbs_sstc_svadu\0"[..]),
+            cpu.get_property("riscv,isa")
+        );
+        for isa in ["rv64gcb", "rv32imac_zba_zbb_zbs"] {
+            assert_eq!(
+                0x2,
+                MachineConfig {
+                    isa: isa.to_string(),
+                    ..MachineConfig::new()
+                }
+                .get_isa()
+                .unwrap()
+                .1 & 0x2
+            );
+        }
+        let config = MachineConfig {
+            isa: "rv64gc_zba_zbb".to_string(),
+            ..MachineConfig::new()
+        };
+        assert_eq!(0, config.get_isa().unwrap().1 & 0x2);
+    }
 
     #[test]
     #[ignore]
@@ -246,7 +1574,59 @@ mod test_emulator {
     #[ignore]
     fn enable_page_cache() {}
 
+    /// Adds the symbols for the symbol tests. `_start` label, `main`
+    /// function, and `counter` local object following them.
+    fn with_test_symbols(builder: ElfBuilder) -> ElfBuilder {
+        builder
+            .symbol("_start", DRAM_BASE, 0, 0x10)
+            .symbol("main", DRAM_BASE + 0x10, 0x20, 0x12)
+            .symbol("counter", DRAM_BASE + 0x40, 8, 0x01)
+    }
+
     #[test]
-    #[ignore]
-    fn get_addredd_of_symbol() {}
+    fn get_address_of_symbol() {
+        let mut emu = create_emu();
+        let elf = with_test_symbols(ElfBuilder::program(DRAM_BASE, &[0; 0x48])).build();
+        emu.setup_program(elf).unwrap();
+        assert_eq!(Some(DRAM_BASE), emu.get_address_of_symbol("_start"));
+        assert_eq!(Some(DRAM_BASE + 0x10), emu.get_address_of_symbol("main"));
+        assert_eq!(None, emu.get_address_of_symbol("missing"));
+
+        let symbol = emu.get_symbol_map().get_symbol("main").unwrap();
+        assert_eq!(0x20, symbol.size);
+        assert_eq!(elf::abi::STT_FUNC, symbol.symbol_type);
+        assert_eq!(elf::abi::STB_GLOBAL, symbol.binding);
+    }
+
+    #[test]
+    fn get_address_of_symbol_position_independent() {
+        let mut emu = create_emu();
+        let elf = ElfBuilder::new(0x10)
+            .position_independent()
+            .segment(0, &[0; 0x48], 0x48)
+            .symbol("main", 0x10, 0x20, 0x12)
+            .build();
+        emu.setup_program_with_base(elf, DRAM_BASE + 0x1000)
+            .unwrap();
+        assert_eq!(Some(DRAM_BASE + 0x1010), emu.get_address_of_symbol("main"));
+    }
+
+    #[test]
+    fn symbolize() {
+        let mut emu = create_emu();
+        let elf = with_test_symbols(ElfBuilder::program(DRAM_BASE, &[0; 0x48])).build();
+        emu.load_symbols(&elf).unwrap();
+        assert_eq!(None, emu.symbolize(DRAM_BASE - 1));
+        assert_eq!(Some(("_start", 0)), emu.symbolize(DRAM_BASE));
+        // Labels without size cover up to the next symbol
+        assert_eq!(Some(("_start", 0xc)), emu.symbolize(DRAM_BASE + 0xc));
+        assert_eq!(Some(("main", 0)), emu.symbolize(DRAM_BASE + 0x10));
+        assert_eq!(Some(("main", 0x1f)), emu.symbolize(DRAM_BASE + 0x2f));
+        // Symbols with size cover only their range
+        assert_eq!(None, emu.symbolize(DRAM_BASE + 0x30));
+        assert_eq!(Some(("counter", 4)), emu.symbolize(DRAM_BASE + 0x44));
+        assert_eq!(None, emu.symbolize(DRAM_BASE + 0x48));
+        // The program isn't loaded
+        assert_eq!(0, emu.cpu.read_pc());
+    }
 }
@@ -0,0 +1,49 @@
+//! RISC-V Linux kernel `Image` header parser. Refer to
+//! [the document](https://www.kernel.org/doc/html/latest/arch/riscv/boot-image-header.html)
+//! for the detail.
+
+use crate::error::EmulatorError;
+
+const HEADER_SIZE: usize = 64;
+
+/// Deprecated magic number at offset 48, still set by the kernels
+const MAGIC: &[u8; 8] = b"RISCV\0\0\0";
+
+/// Magic number at offset 56 since header version 0.2
+const MAGIC2: &[u8; 4] = b"RSC\x05";
+
+/// Linux kernel `Image` placed in the main memory
+pub struct LinuxImage<'a> {
+    /// Offset from the main memory base the image is loaded to
+    pub text_offset: u64,
+    /// Size the image occupies in memory including BSS
+    pub image_size: u64,
+    /// Image content
+    pub data: &'a [u8],
+}
+
+impl<'a> LinuxImage<'a> {
+    /// Parses the header of `Image`. Returns `Err` if the content doesn't
+    /// have RISC-V Linux image header.
+    ///
+    /// # Arguments
+    /// * `content` `Image` file content
+    pub fn parse(content: &'a [u8]) -> Result<Self, EmulatorError> {
+        if content.len() < HEADER_SIZE || (&content[48..56] != MAGIC && &content[56..60] != MAGIC2)
+        {
+            return Err(EmulatorError::InvalidKernelImage);
+        }
+        let read_u64 = |offset: usize| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&content[offset..offset + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        // Old kernels leave image_size zero
+        let image_size = read_u64(16).max(content.len() as u64);
+        Ok(LinuxImage {
+            text_offset: read_u64(8),
+            image_size,
+            data: content,
+        })
+    }
+}
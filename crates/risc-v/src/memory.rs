@@ -1,3 +1,6 @@
+use crate::error::EmulatorError;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
+
 /// Emulates main memory.
 pub struct Memory(Vec<u64>);
 
@@ -19,12 +22,14 @@ impl Memory {
     /// # Arguments
     /// * `capacity`
     pub fn init(&mut self, capacity: u64) {
-        self.0.resize(
-            ((capacity + 7) / 8)
+        // vec! macro lets the allocator provide zeroed pages lazily
+        self.0 = vec![
+            0;
+            capacity
+                .div_ceil(8)
                 .try_into()
-                .expect("unable to allocate, usize cannot handle the required capacity"),
-            0,
-        );
+                .expect("unable to allocate, usize cannot handle the required capacity")
+        ];
     }
 
     /// Reads a byte from memory.
@@ -42,7 +47,7 @@ impl Memory {
     /// # Arguments
     /// * `address`
     pub fn read_halfword(&self, address: u64) -> u16 {
-        if (address % 2) == 0 {
+        if address.is_multiple_of(2) {
             let index = (address >> 3) as usize;
             let pos = (address % 8) * 8;
             (self.0[index] >> pos) as u16
@@ -56,7 +61,7 @@ impl Memory {
     /// # Arguments
     /// * `address`
     pub fn read_word(&self, address: u64) -> u32 {
-        if (address % 4) == 0 {
+        if address.is_multiple_of(4) {
             let index = (address >> 3) as usize;
             let pos = (address % 8) * 8;
             (self.0[index] >> pos) as u32
@@ -70,12 +75,12 @@ impl Memory {
     /// # Arguments
     /// * `address`
     pub fn read_doubleword(&self, address: u64) -> u64 {
-        if (address % 8) == 0 {
+        if address.is_multiple_of(8) {
             let index = (address >> 3) as usize;
             self.0[index]
-        } else if (address % 4) == 0 {
+        } else if address.is_multiple_of(4) {
             (self.read_word(address) as u64)
-                | ((self.read_word(address.wrapping_add(4)) as u64) << 4)
+                | ((self.read_word(address.wrapping_add(4)) as u64) << 32)
         } else {
             self.read_bytes(address, 8)
         }
@@ -111,7 +116,7 @@ impl Memory {
     /// * `address`
     /// * `value`
     pub fn write_halfword(&mut self, address: u64, value: u16) {
-        if (address % 2) == 0 {
+        if address.is_multiple_of(2) {
             let index = (address >> 3) as usize;
             let pos = (address % 8) * 8;
             self.0[index] = (self.0[index] & !(0xffff << pos)) | ((value as u64) << pos);
@@ -126,7 +131,7 @@ impl Memory {
     /// * `address`
     /// * `value`
     pub fn write_word(&mut self, address: u64, value: u32) {
-        if (address % 4) == 0 {
+        if address.is_multiple_of(4) {
             let index = (address >> 3) as usize;
             let pos = (address % 8) * 8;
             self.0[index] = (self.0[index] & !(0xffffffff << pos)) | ((value as u64) << pos);
@@ -141,10 +146,10 @@ impl Memory {
     /// * `address`
     /// * `value`
     pub fn write_doubleword(&mut self, address: u64, value: u64) {
-        if (address % 8) == 0 {
+        if address.is_multiple_of(8) {
             let index = (address >> 3) as usize;
             self.0[index] = value;
-        } else if (address % 4) == 0 {
+        } else if address.is_multiple_of(4) {
             self.write_word(address, (value & 0xffffffff) as u32);
             self.write_word(address.wrapping_add(4), (value >> 32) as u32);
         } else {
@@ -169,6 +174,29 @@ impl Memory {
     /// # Arguments
     /// * `address`
     pub fn validate_address(&self, address: u64) -> bool {
-        (address as usize) < self.0.len()
+        address < self.capacity()
+    }
+
+    /// Returns memory capacity in bytes
+    pub fn capacity(&self) -> u64 {
+        self.0.len() as u64 * 8
+    }
+    /// Saves memory content to a snapshot.
+    pub(crate) fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.write_sparse_words(&self.0);
+    }
+
+    /// Restores memory content saved with `save_state()`.
+    /// The capacity must be the same as the saved one.
+    pub(crate) fn restore_state(
+        &mut self,
+        reader: &mut SnapshotReader,
+    ) -> Result<(), EmulatorError> {
+        let length = self.0.len();
+        reader.read_sparse_words(&mut self.0, length)?;
+        match self.0.len() == length {
+            true => Ok(()),
+            false => Err(EmulatorError::SnapshotMismatch),
+        }
     }
 }
@@ -1,41 +1,59 @@
-/// DRAM base address. Offset from this base address
+/// Default DRAM base address. Offset from the base address
 /// is the address in main memory.
 pub const DRAM_BASE: u64 = 0x80000000;
 
-const DTB_SIZE: usize = 0xfe0;
-
-use fnv::FnvHashMap;
-
-use crate::cpu::{get_privilege_mode, PrivilegeMode, Trap, TrapType, Xlen};
-use crate::device::clint::Clint;
-use crate::device::plic::Plic;
-use crate::device::uart::Uart;
-use crate::device::virtio_block_disk::VirtioBlockDisk;
-use crate::memory::Memory;
+use std::cell::{RefCell, RefMut};
+use std::rc::Rc;
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+use crate::bus::Bus;
+use crate::config::MachineConfig;
+use crate::counter::{HpmEvent, HpmEventCounts};
+use crate::cpu::{
+    get_privilege_encoding, get_privilege_mode, read_privilege_mode, read_xlen, write_xlen,
+    PrivilegeMode, Trap, TrapType, Xlen,
+};
+use crate::csr::{MENVCFG_ADUE, MSTATUS_MPP, MSTATUS_MPRV, MSTATUS_MXR, MSTATUS_SUM};
+use crate::error::EmulatorError;
+use crate::pmp::{Pmp, PMP_R, PMP_W, PMP_X};
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
 use crate::terminal::Terminal;
 
-/// Emulates Memory Management Unit. It holds the Main memory and peripheral
-/// devices, maps address to them, and accesses them depending on address.
-/// It also manages virtual-physical address translation and memory protection.
-/// It may also be said Bus.
-/// TODO: Memory protection is not implemented yet - all accesses are permitted.
+/// Emulates Memory Management Unit of a hart. It manages virtual-physical
+/// address translation and memory protection, and accesses the [`Bus`]
+/// shared with the other harts with physical address. Physical memory
+/// protection checks the physical addresses with [`Pmp`].
 pub struct Mmu {
-    clock: u64,
+    hart_id: usize,
     xlen: Xlen,
     ppn: u64,
     addressing_mode: AddressingMode,
+    /// The widest addressing mode supported, `None` for the default
+    /// of the current XLEN
+    mmu_type: Option<AddressingMode>,
     privilege_mode: PrivilegeMode,
-    memory: MemoryWrapper,
-    dtb: Vec<u8>,
-    disk: VirtioBlockDisk,
-    plic: Plic,
-    clint: Clint,
-    uart: Uart,
+    bus: Rc<RefCell<Bus>>,
 
     /// Address translation can be affected `mstatus` (MPRV, MPP in machine mode)
     /// then `Mmu` has copy of it.
     mstatus: u64,
 
+    /// Hardware A/D bit updates are enabled with `menvcfg.ADUE` then
+    /// `Mmu` has copy of it, too.
+    menvcfg: u64,
+
+    pmp: Pmp,
+
+    /// Memory access events for the hardware performance counters
+    hpm_events: HpmEventCounts,
+
+    /// Physical page numbers holding instructions `Cpu` caches decoded.
+    /// Stores to them are recorded in `written_code_pages` for `Cpu`
+    /// to invalidate the decoded instructions.
+    code_pages: FnvHashSet<u64>,
+    written_code_pages: Vec<u64>,
+
     /// Address translation page cache. Experimental feature.
     /// The cache is cleared when translation mapping can be changed;
     /// xlen, ppn, privilege_mode, or addressing_mode is updated.
@@ -53,11 +71,70 @@ pub struct Mmu {
     store_page_cache: FnvHashMap<u64, u64>,
 }
 
+/// Virtual address translation scheme `satp` selects
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AddressingMode {
     None,
     SV32,
     SV39,
-    SV48, // TODO: Implement
+    SV48,
+    SV57,
+}
+
+impl AddressingMode {
+    /// Returns the number of page table levels
+    fn get_levels(&self) -> u8 {
+        match self {
+            AddressingMode::None => 0,
+            AddressingMode::SV32 => 2,
+            AddressingMode::SV39 => 3,
+            AddressingMode::SV48 => 4,
+            AddressingMode::SV57 => 5,
+        }
+    }
+
+    /// Returns the width of each virtual page number field in bits
+    fn get_vpn_bits(&self) -> u64 {
+        match self {
+            AddressingMode::SV32 => 10,
+            _ => 9,
+        }
+    }
+
+    /// Returns the `mmu-type` device tree property value
+    pub fn get_mmu_type(&self) -> &'static str {
+        match self {
+            AddressingMode::None => "riscv,none",
+            AddressingMode::SV32 => "riscv,sv32",
+            AddressingMode::SV39 => "riscv,sv39",
+            AddressingMode::SV48 => "riscv,sv48",
+            AddressingMode::SV57 => "riscv,sv57",
+        }
+    }
+
+    /// Returns the default, the widest addressing mode the emulator has
+    /// always supported for XLEN
+    ///
+    /// # Arguments
+    /// * `xlen`
+    pub fn get_default(xlen: &Xlen) -> Self {
+        match xlen {
+            Xlen::Bit32 => AddressingMode::SV32,
+            Xlen::Bit64 => AddressingMode::SV39,
+        }
+    }
+
+    /// Returns true if the addressing mode is defined for XLEN
+    ///
+    /// # Arguments
+    /// * `xlen`
+    pub fn is_valid_for(&self, xlen: &Xlen) -> bool {
+        match self {
+            AddressingMode::None => true,
+            AddressingMode::SV32 => matches!(xlen, Xlen::Bit32),
+            _ => matches!(xlen, Xlen::Bit64),
+        }
+    }
 }
 
 enum MemoryAccessType {
@@ -67,37 +144,79 @@ enum MemoryAccessType {
     DontCare,
 }
 
+/// Returns the page fault exception of the access type.
+///
+/// # Arguments
+/// * `access_type`
+/// * `v_address` Virtual address reported in the exception
+fn get_page_fault(access_type: &MemoryAccessType, v_address: u64) -> Trap {
+    Trap {
+        trap_type: match access_type {
+            MemoryAccessType::Execute => TrapType::InstructionPageFault,
+            MemoryAccessType::Read | MemoryAccessType::DontCare => TrapType::LoadPageFault,
+            MemoryAccessType::Write => TrapType::StorePageFault,
+        },
+        value: v_address,
+    }
+}
+
+/// Returns the access fault exception of the access type.
+///
+/// # Arguments
+/// * `access_type`
+/// * `v_address` Virtual address reported in the exception
+fn get_access_fault(access_type: &MemoryAccessType, v_address: u64) -> Trap {
+    Trap {
+        trap_type: match access_type {
+            MemoryAccessType::Execute => TrapType::InstructionAccessFault,
+            MemoryAccessType::Read | MemoryAccessType::DontCare => TrapType::LoadAccessFault,
+            MemoryAccessType::Write => TrapType::StoreAccessFault,
+        },
+        value: v_address,
+    }
+}
+
 fn _get_addressing_mode_name(mode: &AddressingMode) -> &'static str {
     match mode {
         AddressingMode::None => "None",
         AddressingMode::SV32 => "SV32",
         AddressingMode::SV39 => "SV39",
         AddressingMode::SV48 => "SV48",
+        AddressingMode::SV57 => "SV57",
     }
 }
 
 impl Mmu {
-    /// Creates a new `Mmu`.
-    pub fn new(xlen: Xlen, terminal: Box<dyn Terminal>) -> Self {
-        let mut dtb = vec![0; DTB_SIZE];
-
-        // Load default device tree binary content
-        let content = include_bytes!("./device/dtb.dtb");
-        dtb[..content.len()].copy_from_slice(&content[..]);
+    /// Creates a new `Mmu` of the boot hart along with the [`Bus`]. Main
+    /// memory and devices are mapped as `config` describes. `config` is
+    /// expected to be validated beforehand.
+    ///
+    /// # Arguments
+    /// * `xlen`
+    /// * `terminal`
+    /// * `config`
+    pub fn new(xlen: Xlen, terminal: Box<dyn Terminal>, config: &MachineConfig) -> Self {
+        let mut mmu =
+            Self::new_with_bus(0, xlen, Rc::new(RefCell::new(Bus::new(terminal, config))));
+        mmu.mmu_type = config.mmu_type;
+        mmu
+    }
 
+    fn new_with_bus(hart_id: usize, xlen: Xlen, bus: Rc<RefCell<Bus>>) -> Self {
         Self {
-            clock: 0,
+            hart_id,
             xlen,
             ppn: 0,
             addressing_mode: AddressingMode::None,
+            mmu_type: None,
             privilege_mode: PrivilegeMode::Machine,
-            memory: MemoryWrapper::new(),
-            dtb,
-            disk: VirtioBlockDisk::new(),
-            plic: Plic::new(),
-            clint: Clint::new(),
-            uart: Uart::new(terminal),
+            bus,
             mstatus: 0,
+            menvcfg: 0,
+            pmp: Pmp::new(),
+            hpm_events: HpmEventCounts::default(),
+            code_pages: FnvHashSet::default(),
+            written_code_pages: vec![],
             page_cache_enabled: false,
             fetch_page_cache: FnvHashMap::default(),
             load_page_cache: FnvHashMap::default(),
@@ -105,6 +224,22 @@ impl Mmu {
         }
     }
 
+    /// Creates a new `Mmu` of another hart sharing the [`Bus`].
+    ///
+    /// # Arguments
+    /// * `hart_id`
+    pub fn create_secondary_hart(&self, hart_id: usize) -> Self {
+        let mut mmu = Self::new_with_bus(hart_id, self.xlen.clone(), self.bus.clone());
+        mmu.page_cache_enabled = self.page_cache_enabled;
+        mmu.mmu_type = self.mmu_type;
+        mmu
+    }
+
+    /// Returns the [`Bus`] shared by all the harts.
+    pub fn get_bus(&self) -> &Rc<RefCell<Bus>> {
+        &self.bus
+    }
+
     /// Updates XLEN, 32-bit or 64-bit
     pub fn update_xlen(&mut self, xlen: Xlen) {
         self.xlen = xlen;
@@ -113,26 +248,57 @@ impl Mmu {
 
     /// Initializes Main memory. This method is expected to be called only once.
     pub fn init_memory(&mut self, capacity: u64) {
-        self.memory.init(capacity);
+        self.bus.borrow_mut().init_memory(capacity);
     }
 
     /// Initializes Virtio block disk. This method is expected to be called only once.
+    /// Returns `Err` if the content is larger than the disk capacity.
     ///
     /// # Arguments
     /// * `data` Filesystem binary content
-    pub fn init_disk(&mut self, data: Vec<u8>) {
-        self.disk.init(data);
+    pub fn init_disk(&mut self, data: Vec<u8>) -> Result<(), EmulatorError> {
+        self.bus.borrow_mut().init_disk(data)
     }
 
-    /// Overrides default Device tree configuration.
+    /// Overrides the device tree generated from the machine configuration.
+    /// Returns `Err` if the content doesn't fit into the main memory
+    /// from the device tree address.
     ///
     /// # Arguments
     /// * `data` DTB binary content
-    pub fn init_dtb(&mut self, data: Vec<u8>) {
-        self.dtb[..data.len()].copy_from_slice(&data[..]);
-        for i in data.len()..self.dtb.len() {
-            self.dtb[i] = 0;
-        }
+    pub fn init_dtb(&mut self, data: Vec<u8>) -> Result<(), EmulatorError> {
+        self.bus.borrow_mut().init_dtb(data)
+    }
+
+    /// Returns the device tree binary placed in the main memory at boot.
+    pub fn get_dtb(&self) -> Vec<u8> {
+        self.bus.borrow().get_dtb().to_vec()
+    }
+
+    /// Returns the physical address where the device tree is placed at boot.
+    /// By default it's the end of the main memory aligned down to the page.
+    pub fn get_dtb_address(&self) -> u64 {
+        self.bus.borrow().get_dtb_address()
+    }
+
+    /// Places the device tree in the main memory and sets up the boot ROM
+    /// jumping to `entry` with the device tree address. Called once before
+    /// harts start from the reset vector.
+    ///
+    /// # Arguments
+    /// * `entry` Program entry point
+    pub fn init_boot_rom(&mut self, entry: u64) {
+        self.bus.borrow_mut().init_boot_rom(&self.xlen, entry);
+    }
+
+    /// Returns the main memory capacity in bytes.
+    pub fn memory_capacity(&self) -> u64 {
+        self.bus.borrow().memory_capacity()
+    }
+
+    /// Returns the main memory base address.
+    pub fn memory_base(&self) -> u64 {
+        self.bus.borrow().memory_base()
     }
 
     /// Enables or disables page cache optimization.
@@ -142,23 +308,39 @@ impl Mmu {
     }
 
     /// Clears page cache entries
-    fn clear_page_cache(&mut self) {
+    /// Clears the address translation page cache. `SFENCE.VMA` calls
+    /// this as page table entries may be updated.
+    pub(crate) fn clear_page_cache(&mut self) {
         self.fetch_page_cache.clear();
         self.load_page_cache.clear();
         self.store_page_cache.clear();
     }
 
-    /// Runs one cycle of MMU and peripheral devices.
+    /// Runs one cycle of MMU. The boot hart also runs one cycle of
+    /// peripheral devices on the bus so that they run once for all the harts.
+    ///
+    /// # Arguments
+    /// * `mip` CPU `mip` register of the hart
     pub fn tick(&mut self, mip: &mut u64) {
-        self.clint.tick(mip);
-        self.disk.tick(&mut self.memory);
-        self.uart.tick();
-        self.plic.tick(
-            self.disk.is_interrupting(),
-            self.uart.is_interrupting(),
-            mip,
-        );
-        self.clock = self.clock.wrapping_add(1);
+        let mut bus = self.bus.borrow_mut();
+        if self.hart_id == 0 {
+            bus.tick();
+        }
+        bus.raise_interrupts(self.hart_id, mip);
+    }
+
+    /// Returns true if `satp` can select the addressing mode. A hart
+    /// supporting an addressing mode supports the narrower ones too.
+    ///
+    /// # Arguments
+    /// * `mode`
+    pub fn is_addressing_mode_supported(&self, mode: &AddressingMode) -> bool {
+        let widest = self
+            .mmu_type
+            .unwrap_or(AddressingMode::get_default(&self.xlen));
+        mode.is_valid_for(&self.xlen)
+            && widest.is_valid_for(&self.xlen)
+            && mode.get_levels() <= widest.get_levels()
     }
 
     /// Updates addressing mode
@@ -176,9 +358,56 @@ impl Mmu {
     /// Updates mstatus copy. `CPU` needs to call this method whenever
     /// `mstatus` is updated.
     pub fn update_mstatus(&mut self, mstatus: u64) {
+        // Cached translations depend on the effective privilege mode
+        // and the page permissions the status allows
+        let changed = self.mstatus ^ mstatus;
+        if changed & (MSTATUS_MPRV | MSTATUS_SUM | MSTATUS_MXR) != 0
+            || (mstatus & MSTATUS_MPRV != 0 && changed & MSTATUS_MPP != 0)
+        {
+            self.clear_page_cache();
+        }
         self.mstatus = mstatus;
     }
 
+    /// Returns the memory access events occurring since the last call
+    /// and resets them. `Cpu` takes them once a cycle.
+    pub(crate) fn take_hpm_events(&mut self) -> HpmEventCounts {
+        self.hpm_events.take()
+    }
+
+    /// Updates menvcfg copy. `CPU` needs to call this method whenever
+    /// `menvcfg` is updated.
+    pub fn update_menvcfg(&mut self, menvcfg: u64) {
+        self.menvcfg = menvcfg;
+    }
+
+    /// Returns the physical memory protection entries
+    pub(crate) fn get_pmp(&self) -> &Pmp {
+        &self.pmp
+    }
+
+    /// Writes the configuration of a physical memory protection entry.
+    /// See [`Pmp::write_config()`].
+    ///
+    /// # Arguments
+    /// * `index` Entry index
+    /// * `config`
+    pub(crate) fn write_pmp_config(&mut self, index: usize, config: u8) {
+        self.pmp.write_config(index, config);
+        self.clear_page_cache();
+    }
+
+    /// Writes the address of a physical memory protection entry.
+    /// See [`Pmp::write_address()`].
+    ///
+    /// # Arguments
+    /// * `index` Entry index
+    /// * `address`
+    pub(crate) fn write_pmp_address(&mut self, index: usize, address: u64) {
+        self.pmp.write_address(index, address);
+        self.clear_page_cache();
+    }
+
     /// Updates PPN used for address translation
     pub fn update_ppn(&mut self, ppn: u64) {
         self.ppn = ppn;
@@ -192,16 +421,96 @@ impl Mmu {
         }
     }
 
+    /// Translates the virtual address of an instruction for `Cpu` executing
+    /// the cached decoded instructions. `is_fetchable()` checks the access.
+    ///
+    /// # Arguments
+    /// * `v_address` Virtual address
+    pub(crate) fn translate_fetch_address(&mut self, v_address: u64) -> Result<u64, Trap> {
+        let effective_address = self.get_effective_address(v_address);
+        self.translate_address(effective_address, &MemoryAccessType::Execute)
+    }
+
+    /// Indicates whether the instructions of `width` bytes from physical
+    /// address can be fetched.
+    ///
+    /// # Arguments
+    /// * `p_address` Physical address
+    /// * `width`
+    pub(crate) fn is_fetchable(&mut self, p_address: u64, width: u64) -> bool {
+        self.check_access(p_address, width, p_address, &MemoryAccessType::Execute)
+            .is_ok()
+    }
+
+    /// Reads an instruction in the main memory for `Cpu` caching it decoded,
+    /// and records the page to observe stores to it. Returns the lower 16
+    /// bits for compressed instruction, or `None` if the instruction isn't
+    /// entirely in the main memory or the page.
+    ///
+    /// # Arguments
+    /// * `p_address` Physical address
+    pub(crate) fn read_code(&mut self, p_address: u64) -> Option<u32> {
+        let address = self.get_effective_address(p_address);
+        let room = 0x1000 - (address & 0xfff);
+        let is_in_memory =
+            |mmu: &Self, width| room >= width && mmu.bus.borrow().is_main_memory(address, width);
+        if !is_in_memory(self, 2) {
+            return None;
+        }
+        let halfword = self.load_halfword_raw(address) as u32;
+        let bits = match halfword & 0x3 {
+            0x3 if !is_in_memory(self, 4) => return None,
+            0x3 => self.load_word_raw(address),
+            _ => halfword,
+        };
+        self.code_pages.insert(address >> 12);
+        Some(bits)
+    }
+
+    /// Indicates whether stores have written to the pages of cached
+    /// instructions since `take_written_code_pages()` was called last.
+    pub(crate) fn has_written_code_pages(&self) -> bool {
+        !self.written_code_pages.is_empty()
+    }
+
+    /// Returns the physical page numbers of cached instructions stores
+    /// have written to, and stops observing them.
+    pub(crate) fn take_written_code_pages(&mut self) -> Vec<u64> {
+        std::mem::take(&mut self.written_code_pages)
+    }
+
+    /// Stops observing stores to any page, when `Cpu` drops all the
+    /// cached instructions.
+    pub(crate) fn clear_code_pages(&mut self) {
+        self.code_pages.clear();
+        self.written_code_pages.clear();
+    }
+
+    /// Records the store to the pages of cached instructions. A misaligned
+    /// store can write to the page following the one of its first byte.
+    ///
+    /// # Arguments
+    /// * `effective_address` Effective physical address
+    /// * `width` Store size in bytes
+    fn observe_store(&mut self, effective_address: u64, width: u64) {
+        if self.code_pages.is_empty() {
+            return;
+        }
+        let first_page = effective_address >> 12;
+        let last_page = effective_address.wrapping_add(width - 1) >> 12;
+        for page in [first_page, last_page] {
+            if self.code_pages.remove(&page) {
+                self.written_code_pages.push(page);
+            }
+        }
+    }
+
     /// Fetches an instruction byte. This method takes virtual address
     /// and translates into physical address inside.
     fn fetch(&mut self, virtual_address: u64) -> Result<u8, Trap> {
-        match self.translate_address(virtual_address, &MemoryAccessType::Execute) {
-            Ok(p_address) => Ok(self.load_raw(p_address)),
-            Err(()) => Err(Trap {
-                trap_type: TrapType::InstructionPageFault,
-                value: virtual_address,
-            }),
-        }
+        let p_address = self.translate_address(virtual_address, &MemoryAccessType::Execute)?;
+        self.check_access(p_address, 1, virtual_address, &MemoryAccessType::Execute)?;
+        Ok(self.load_raw(p_address))
     }
 
     /// Fetches instruction four bytes. This method takes virtual address
@@ -213,13 +522,15 @@ impl Mmu {
                 // Fast path. All bytes fetched are in the same page so
                 // translating an address only once.
                 let effective_address = self.get_effective_address(virtual_address);
-                match self.translate_address(effective_address, &MemoryAccessType::Execute) {
-                    Ok(p_address) => Ok(self.load_word_raw(p_address)),
-                    Err(()) => Err(Trap {
-                        trap_type: TrapType::InstructionPageFault,
-                        value: effective_address,
-                    }),
-                }
+                let p_address =
+                    self.translate_address(effective_address, &MemoryAccessType::Execute)?;
+                self.check_access(
+                    p_address,
+                    width,
+                    effective_address,
+                    &MemoryAccessType::Execute,
+                )?;
+                Ok(self.load_word_raw(p_address))
             }
             false => {
                 let mut data = 0_u32;
@@ -237,14 +548,18 @@ impl Mmu {
     /// Loads an byte. This method takes virtual address and translates
     /// into physical address inside.
     pub fn load(&mut self, virtual_address: u64) -> Result<u8, Trap> {
+        let data = self.load_byte(virtual_address)?;
+        self.hpm_events.count(HpmEvent::Load);
+        Ok(data)
+    }
+
+    /// Loads a byte without counting it as a load event, for a load
+    /// split into bytes.
+    fn load_byte(&mut self, virtual_address: u64) -> Result<u8, Trap> {
         let effective_address = self.get_effective_address(virtual_address);
-        match self.translate_address(effective_address, &MemoryAccessType::Read) {
-            Ok(p_address) => Ok(self.load_raw(p_address)),
-            Err(()) => Err(Trap {
-                trap_type: TrapType::LoadPageFault,
-                value: virtual_address,
-            }),
-        }
+        let p_address = self.translate_address(effective_address, &MemoryAccessType::Read)?;
+        self.check_access(p_address, 1, virtual_address, &MemoryAccessType::Read)?;
+        Ok(self.load_raw(p_address))
     }
 
     /// Loads multiple bytes. This method takes virtual address and translates
@@ -259,35 +574,33 @@ impl Mmu {
             "Width must be 1, 2, 4, or 8. {:X}",
             width
         );
-        match (v_address & 0xfff) <= (0x1000 - width) {
-            true => match self.translate_address(v_address, &MemoryAccessType::Read) {
-                Ok(p_address) => {
-                    // Fast path. All bytes fetched are in the same page so
-                    // translating an address only once.
-                    match width {
-                        1 => Ok(self.load_raw(p_address) as u64),
-                        2 => Ok(self.load_halfword_raw(p_address) as u64),
-                        4 => Ok(self.load_word_raw(p_address) as u64),
-                        8 => Ok(self.load_doubleword_raw(p_address)),
-                        _ => panic!("Width must be 1, 2, 4, or 8. {:X}", width),
-                    }
+        let data = match (v_address & 0xfff) <= (0x1000 - width) {
+            true => {
+                let p_address = self.translate_address(v_address, &MemoryAccessType::Read)?;
+                self.check_access(p_address, width, v_address, &MemoryAccessType::Read)?;
+                // Fast path. All bytes fetched are in the same page so
+                // translating an address only once.
+                match width {
+                    1 => self.load_raw(p_address) as u64,
+                    2 => self.load_halfword_raw(p_address) as u64,
+                    4 => self.load_word_raw(p_address) as u64,
+                    8 => self.load_doubleword_raw(p_address),
+                    _ => panic!("Width must be 1, 2, 4, or 8. {:X}", width),
                 }
-                Err(()) => Err(Trap {
-                    trap_type: TrapType::LoadPageFault,
-                    value: v_address,
-                }),
-            },
+            }
             false => {
                 let mut data = 0_u64;
                 for i in 0..width {
-                    match self.load(v_address.wrapping_add(i)) {
+                    match self.load_byte(v_address.wrapping_add(i)) {
                         Ok(byte) => data |= (byte as u64) << (i * 8),
                         Err(e) => return Err(e),
                     };
                 }
-                Ok(data)
+                data
             }
-        }
+        };
+        self.hpm_events.count(HpmEvent::Load);
+        Ok(data)
     }
 
     /// Loads two bytes. This method takes virtual address and translates
@@ -320,16 +633,18 @@ impl Mmu {
     /// Store an byte. This method takes virtual address and translates
     /// into physical address inside.
     pub fn store(&mut self, virtual_address: u64, value: u8) -> Result<(), Trap> {
-        match self.translate_address(virtual_address, &MemoryAccessType::Write) {
-            Ok(p_address) => {
-                self.store_raw(p_address, value);
-                Ok(())
-            }
-            Err(()) => Err(Trap {
-                trap_type: TrapType::StorePageFault,
-                value: virtual_address,
-            }),
-        }
+        self.store_byte(virtual_address, value)?;
+        self.hpm_events.count(HpmEvent::Store);
+        Ok(())
+    }
+
+    /// Stores a byte without counting it as a store event, for a store
+    /// split into bytes.
+    fn store_byte(&mut self, virtual_address: u64, value: u8) -> Result<(), Trap> {
+        let p_address = self.translate_address(virtual_address, &MemoryAccessType::Write)?;
+        self.check_access(p_address, 1, virtual_address, &MemoryAccessType::Write)?;
+        self.store_raw(p_address, value);
+        Ok(())
     }
 
     /// Stores multiple bytes. This method takes virtual address and translates
@@ -346,27 +661,23 @@ impl Mmu {
             width
         );
         match (virtual_address & 0xfff) <= (0x1000 - width) {
-            true => match self.translate_address(virtual_address, &MemoryAccessType::Write) {
-                Ok(p_address) => {
-                    // Fast path. All bytes fetched are in the same page so
-                    // translating an address only once.
-                    match width {
-                        1 => self.store_raw(p_address, value as u8),
-                        2 => self.store_halfword_raw(p_address, value as u16),
-                        4 => self.store_word_raw(p_address, value as u32),
-                        8 => self.store_doubleword_raw(p_address, value),
-                        _ => panic!("Width must be 1, 2, 4, or 8. {:X}", width),
-                    }
-                    Ok(())
+            true => {
+                let p_address =
+                    self.translate_address(virtual_address, &MemoryAccessType::Write)?;
+                self.check_access(p_address, width, virtual_address, &MemoryAccessType::Write)?;
+                // Fast path. All bytes fetched are in the same page so
+                // translating an address only once.
+                match width {
+                    1 => self.store_raw(p_address, value as u8),
+                    2 => self.store_halfword_raw(p_address, value as u16),
+                    4 => self.store_word_raw(p_address, value as u32),
+                    8 => self.store_doubleword_raw(p_address, value),
+                    _ => panic!("Width must be 1, 2, 4, or 8. {:X}", width),
                 }
-                Err(()) => Err(Trap {
-                    trap_type: TrapType::StorePageFault,
-                    value: virtual_address,
-                }),
-            },
+            }
             false => {
                 for i in 0..width {
-                    match self.store(
+                    match self.store_byte(
                         virtual_address.wrapping_add(i),
                         ((value >> (i * 8)) & 0xff) as u8,
                     ) {
@@ -374,9 +685,10 @@ impl Mmu {
                         Err(e) => return Err(e),
                     }
                 }
-                Ok(())
             }
-        }
+        };
+        self.hpm_events.count(HpmEvent::Store);
+        Ok(())
     }
 
     /// Stores two bytes. This method takes virtual address and translates
@@ -409,6 +721,59 @@ impl Mmu {
         self.store_bytes(v_address, value, 8)
     }
 
+    /// Loads four or eight bytes and registers a reservation set on
+    /// the address for `LR` instruction. The address must be aligned.
+    ///
+    /// # Arguments
+    /// * `v_address` Virtual address
+    /// * `width` Must be 4 or 8
+    pub fn load_reserved(&mut self, v_address: u64, width: u64) -> Result<u64, Trap> {
+        let p_address = self.translate_address(v_address, &MemoryAccessType::Read)?;
+        self.check_access(p_address, width, v_address, &MemoryAccessType::Read)?;
+        let data = match width {
+            4 => self.load_word_raw(p_address) as u64,
+            _ => self.load_doubleword_raw(p_address),
+        };
+        let effective_address = self.get_effective_address(p_address);
+        self.bus
+            .borrow_mut()
+            .reserve(self.hart_id, effective_address);
+        self.hpm_events.count(HpmEvent::Load);
+        Ok(data)
+    }
+
+    /// Stores four or eight bytes for `SC` instruction only if the address
+    /// is still reserved. The reservation is cleared either way.
+    /// Returns whether the store is done. The address must be aligned.
+    ///
+    /// # Arguments
+    /// * `v_address` Virtual address
+    /// * `value` data written
+    /// * `width` Must be 4 or 8
+    pub fn store_conditional(
+        &mut self,
+        v_address: u64,
+        value: u64,
+        width: u64,
+    ) -> Result<bool, Trap> {
+        let p_address = self.translate_address(v_address, &MemoryAccessType::Write)?;
+        self.check_access(p_address, width, v_address, &MemoryAccessType::Write)?;
+        let effective_address = self.get_effective_address(p_address);
+        if !self
+            .bus
+            .borrow_mut()
+            .take_reservation(self.hart_id, effective_address)
+        {
+            return Ok(false);
+        }
+        match width {
+            4 => self.store_word_raw(p_address, value as u32),
+            _ => self.store_doubleword_raw(p_address, value),
+        };
+        self.hpm_events.count(HpmEvent::Store);
+        Ok(true)
+    }
+
     /// Loads a byte from main memory or peripheral devices depending on
     /// physical address.
     ///
@@ -416,21 +781,7 @@ impl Mmu {
     /// * `p_address` Physical address
     fn load_raw(&mut self, p_address: u64) -> u8 {
         let effective_address = self.get_effective_address(p_address);
-        // @TODO: Mapping should be configurable with dtb
-        match effective_address >= DRAM_BASE {
-            true => self.memory.read_byte(effective_address),
-            false => match effective_address {
-                // I don't know why but dtb data seems to be stored from 0x1020 on Linux.
-                // It might be from self.x[0xb] initialization?
-                // And DTB size is arbitrary.
-                0x00001020..=0x00001fff => self.dtb[effective_address as usize - 0x1020],
-                0x02000000..=0x0200ffff => self.clint.load(effective_address),
-                0x0C000000..=0x0fffffff => self.plic.load(effective_address),
-                0x10000000..=0x100000ff => self.uart.load(effective_address),
-                0x10001000..=0x10001FFF => self.disk.load(effective_address),
-                _ => panic!("Unknown memory mapping {:X}.", effective_address),
-            },
-        }
+        self.bus.borrow_mut().load(effective_address)
     }
 
     /// Loads two bytes from main memory or peripheral devices depending on
@@ -440,19 +791,7 @@ impl Mmu {
     /// * `p_address` Physical address
     fn load_halfword_raw(&mut self, p_address: u64) -> u16 {
         let effective_address = self.get_effective_address(p_address);
-        match effective_address >= DRAM_BASE
-            && effective_address.wrapping_add(1) > effective_address
-        {
-            // Fast path. Directly load main memory at a time.
-            true => self.memory.read_halfword(effective_address),
-            false => {
-                let mut data = 0_u16;
-                for i in 0..2 {
-                    data |= (self.load_raw(effective_address.wrapping_add(i)) as u16) << (i * 8)
-                }
-                data
-            }
-        }
+        self.bus.borrow_mut().load_halfword(effective_address)
     }
 
     /// Loads four bytes from main memory or peripheral devices depending on
@@ -462,19 +801,7 @@ impl Mmu {
     /// * `p_address` Physical address
     pub fn load_word_raw(&mut self, p_address: u64) -> u32 {
         let effective_address = self.get_effective_address(p_address);
-        match effective_address >= DRAM_BASE
-            && effective_address.wrapping_add(3) > effective_address
-        {
-            // Fast path. Directly load main memory at a time.
-            true => self.memory.read_word(effective_address),
-            false => {
-                let mut data = 0_u32;
-                for i in 0..4 {
-                    data |= (self.load_raw(effective_address.wrapping_add(i)) as u32) << (i * 8)
-                }
-                data
-            }
-        }
+        self.bus.borrow_mut().load_word(effective_address)
     }
 
     /// Loads eight bytes from main memory or peripheral devices depending on
@@ -484,19 +811,7 @@ impl Mmu {
     /// * `p_address` Physical address
     fn load_doubleword_raw(&mut self, p_address: u64) -> u64 {
         let effective_address = self.get_effective_address(p_address);
-        match effective_address >= DRAM_BASE
-            && effective_address.wrapping_add(7) > effective_address
-        {
-            // Fast path. Directly load main memory at a time.
-            true => self.memory.read_doubleword(effective_address),
-            false => {
-                let mut data = 0_u64;
-                for i in 0..8 {
-                    data |= (self.load_raw(effective_address.wrapping_add(i)) as u64) << (i * 8)
-                }
-                data
-            }
-        }
+        self.bus.borrow_mut().load_doubleword(effective_address)
     }
 
     /// Stores a byte to main memory or peripheral devices depending on
@@ -507,17 +822,61 @@ impl Mmu {
     /// * `value` data written
     pub fn store_raw(&mut self, p_address: u64, value: u8) {
         let effective_address = self.get_effective_address(p_address);
-        // @TODO: Mapping should be configurable with dtb
-        match effective_address >= DRAM_BASE {
-            true => self.memory.write_byte(effective_address, value),
-            false => match effective_address {
-                0x02000000..=0x0200ffff => self.clint.store(effective_address, value),
-                0x0c000000..=0x0fffffff => self.plic.store(effective_address, value),
-                0x10000000..=0x100000ff => self.uart.store(effective_address, value),
-                0x10001000..=0x10001FFF => self.disk.store(effective_address, value),
-                _ => panic!("Unknown memory mapping {:X}.", effective_address),
-            },
+        self.observe_store(effective_address, 1);
+        self.bus.borrow_mut().store(effective_address, value);
+    }
+
+    /// Returns access fault exception if nothing is mapped at the physical
+    /// address or the device doesn't accept the access type.
+    ///
+    /// # Arguments
+    /// * `p_address` Physical address
+    /// * `width`
+    /// * `v_address` Virtual address reported in the exception
+    /// * `access_type`
+    fn check_access(
+        &self,
+        p_address: u64,
+        width: u64,
+        v_address: u64,
+        access_type: &MemoryAccessType,
+    ) -> Result<(), Trap> {
+        let effective_address = self.get_effective_address(p_address);
+        let (is_write, permission) = match access_type {
+            MemoryAccessType::Execute => (false, PMP_X),
+            MemoryAccessType::Read | MemoryAccessType::DontCare => (false, PMP_R),
+            MemoryAccessType::Write => (true, PMP_W),
         };
+        let is_machine = matches!(
+            self.get_effective_privilege_mode(access_type),
+            PrivilegeMode::Machine
+        );
+        if self
+            .pmp
+            .is_permitted(effective_address, width, is_machine, permission)
+            && self
+                .bus
+                .borrow()
+                .is_accessible(effective_address, width, is_write)
+        {
+            return Ok(());
+        }
+        Err(get_access_fault(access_type, v_address))
+    }
+
+    /// Returns the privilege mode loads and stores are performed in.
+    /// `mstatus.MPRV` in Machine mode makes them in the mode of `MPP`.
+    ///
+    /// # Arguments
+    /// * `access_type`
+    fn get_effective_privilege_mode(&self, access_type: &MemoryAccessType) -> PrivilegeMode {
+        match (&self.privilege_mode, access_type) {
+            (PrivilegeMode::Machine, MemoryAccessType::Execute) => PrivilegeMode::Machine,
+            (PrivilegeMode::Machine, _) if self.mstatus & MSTATUS_MPRV != 0 => {
+                get_privilege_mode((self.mstatus & MSTATUS_MPP) >> 11)
+            }
+            _ => self.privilege_mode.clone(),
+        }
     }
 
     /// Stores two bytes to main memory or peripheral devices depending on
@@ -528,20 +887,10 @@ impl Mmu {
     /// * `value` data written
     fn store_halfword_raw(&mut self, p_address: u64, value: u16) {
         let effective_address = self.get_effective_address(p_address);
-        match effective_address >= DRAM_BASE
-            && effective_address.wrapping_add(1) > effective_address
-        {
-            // Fast path. Directly store to main memory at a time.
-            true => self.memory.write_halfword(effective_address, value),
-            false => {
-                for i in 0..2 {
-                    self.store_raw(
-                        effective_address.wrapping_add(i),
-                        ((value >> (i * 8)) & 0xff) as u8,
-                    );
-                }
-            }
-        }
+        self.observe_store(effective_address, 2);
+        self.bus
+            .borrow_mut()
+            .store_halfword(effective_address, value);
     }
 
     /// Stores four bytes to main memory or peripheral devices depending on
@@ -550,22 +899,10 @@ impl Mmu {
     /// # Arguments
     /// * `p_address` Physical address
     /// * `value` data written
-    fn store_word_raw(&mut self, p_address: u64, value: u32) {
+    pub fn store_word_raw(&mut self, p_address: u64, value: u32) {
         let effective_address = self.get_effective_address(p_address);
-        match effective_address >= DRAM_BASE
-            && effective_address.wrapping_add(3) > effective_address
-        {
-            // Fast path. Directly store to main memory at a time.
-            true => self.memory.write_word(effective_address, value),
-            false => {
-                for i in 0..4 {
-                    self.store_raw(
-                        effective_address.wrapping_add(i),
-                        ((value >> (i * 8)) & 0xff) as u8,
-                    );
-                }
-            }
-        }
+        self.observe_store(effective_address, 4);
+        self.bus.borrow_mut().store_word(effective_address, value);
     }
 
     /// Stores eight bytes to main memory or peripheral devices depending on
@@ -574,22 +911,12 @@ impl Mmu {
     /// # Arguments
     /// * `p_address` Physical address
     /// * `value` data written
-    fn store_doubleword_raw(&mut self, p_address: u64, value: u64) {
+    pub fn store_doubleword_raw(&mut self, p_address: u64, value: u64) {
         let effective_address = self.get_effective_address(p_address);
-        match effective_address >= DRAM_BASE
-            && effective_address.wrapping_add(7) > effective_address
-        {
-            // Fast path. Directly store to main memory at a time.
-            true => self.memory.write_doubleword(effective_address, value),
-            false => {
-                for i in 0..8 {
-                    self.store_raw(
-                        effective_address.wrapping_add(i),
-                        ((value >> (i * 8)) & 0xff) as u8,
-                    );
-                }
-            }
-        }
+        self.observe_store(effective_address, 8);
+        self.bus
+            .borrow_mut()
+            .store_doubleword(effective_address, value);
     }
 
     /// Checks if passed virtual address is valid (pointing a certain device) or not.
@@ -597,27 +924,29 @@ impl Mmu {
     ///
     /// # Arguments
     /// * `v_address` Virtual address
+    #[allow(clippy::result_unit_err)]
     pub fn validate_address(&mut self, v_address: u64) -> Result<bool, ()> {
         // @TODO: Support other access types?
         let p_address = match self.translate_address(v_address, &MemoryAccessType::DontCare) {
             Ok(address) => address,
-            Err(()) => return Err(()),
+            Err(_) => return Err(()),
         };
         let effective_address = self.get_effective_address(p_address);
-        let valid = match effective_address >= DRAM_BASE {
-            true => self.memory.validate_address(effective_address),
-            false => {
-                matches!(effective_address, 0x00001020..=0x00001fff | 0x02000000..=0x0200ffff | 0x0C000000..=0x0fffffff | 0x10000000..=0x100000ff | 0x10001000..=0x10001FFF)
-            }
-        };
-        Ok(valid)
+        Ok(self.bus.borrow().is_mapped(effective_address))
     }
 
+    /// Translates a virtual address into physical address. Returns page
+    /// fault, or access fault if a page table entry access isn't
+    /// permitted, reporting the virtual address.
+    ///
+    /// # Arguments
+    /// * `v_address` Virtual address
+    /// * `access_type`
     fn translate_address(
         &mut self,
         v_address: u64,
         access_type: &MemoryAccessType,
-    ) -> Result<u64, ()> {
+    ) -> Result<u64, Trap> {
         let address = self.get_effective_address(v_address);
         let v_page = address & !0xfff;
         let cache = match self.page_cache_enabled {
@@ -634,76 +963,12 @@ impl Mmu {
             None => {
                 let p_address = match self.addressing_mode {
                     AddressingMode::None => Ok(address),
-                    AddressingMode::SV32 => match self.privilege_mode {
-                        // @TODO: Optimize
-                        PrivilegeMode::Machine => match access_type {
-                            MemoryAccessType::Execute => Ok(address),
-                            // @TODO: Remove magic number
-                            _ => match (self.mstatus >> 17) & 1 {
-                                0 => Ok(address),
-                                _ => {
-                                    let privilege_mode =
-                                        get_privilege_mode((self.mstatus >> 9) & 3);
-                                    match privilege_mode {
-                                        PrivilegeMode::Machine => Ok(address),
-                                        _ => {
-                                            let current_privilege_mode =
-                                                self.privilege_mode.clone();
-                                            self.update_privilege_mode(privilege_mode);
-                                            let result =
-                                                self.translate_address(v_address, access_type);
-                                            self.update_privilege_mode(current_privilege_mode);
-                                            result
-                                        }
-                                    }
-                                }
-                            },
-                        },
+                    _ => match self.get_effective_privilege_mode(access_type) {
                         PrivilegeMode::User | PrivilegeMode::Supervisor => {
-                            let vpns = [(address >> 12) & 0x3ff, (address >> 22) & 0x3ff];
-                            self.traverse_page(address, 2 - 1, self.ppn, &vpns, access_type)
+                            self.walk_page_table(address, access_type)
                         }
                         _ => Ok(address),
                     },
-                    AddressingMode::SV39 => match self.privilege_mode {
-                        // @TODO: Optimize
-                        // @TODO: Remove duplicated code with SV32
-                        PrivilegeMode::Machine => match access_type {
-                            MemoryAccessType::Execute => Ok(address),
-                            // @TODO: Remove magic number
-                            _ => match (self.mstatus >> 17) & 1 {
-                                0 => Ok(address),
-                                _ => {
-                                    let privilege_mode =
-                                        get_privilege_mode((self.mstatus >> 9) & 3);
-                                    match privilege_mode {
-                                        PrivilegeMode::Machine => Ok(address),
-                                        _ => {
-                                            let current_privilege_mode =
-                                                self.privilege_mode.clone();
-                                            self.update_privilege_mode(privilege_mode);
-                                            let result =
-                                                self.translate_address(v_address, access_type);
-                                            self.update_privilege_mode(current_privilege_mode);
-                                            result
-                                        }
-                                    }
-                                }
-                            },
-                        },
-                        PrivilegeMode::User | PrivilegeMode::Supervisor => {
-                            let vpns = [
-                                (address >> 12) & 0x1ff,
-                                (address >> 21) & 0x1ff,
-                                (address >> 30) & 0x1ff,
-                            ];
-                            self.traverse_page(address, 3 - 1, self.ppn, &vpns, access_type)
-                        }
-                        _ => Ok(address),
-                    },
-                    AddressingMode::SV48 => {
-                        panic!("AddressingMode SV48 is not supported yet.");
-                    }
                 };
                 match self.page_cache_enabled {
                     true => match p_address {
@@ -723,7 +988,7 @@ impl Mmu {
                             };
                             Ok(p_address)
                         }
-                        Err(()) => Err(()),
+                        Err(trap) => Err(trap),
                     },
                     false => p_address,
                 }
@@ -731,6 +996,34 @@ impl Mmu {
         }
     }
 
+    /// Translates a virtual address with the page table of the current
+    /// addressing mode. 64-bit modes require the bits above the virtual
+    /// address width to be copies of its top bit.
+    ///
+    /// # Arguments
+    /// * `address` Effective virtual address
+    /// * `access_type`
+    fn walk_page_table(
+        &mut self,
+        address: u64,
+        access_type: &MemoryAccessType,
+    ) -> Result<u64, Trap> {
+        let levels = self.addressing_mode.get_levels();
+        let vpn_bits = self.addressing_mode.get_vpn_bits();
+        if let Xlen::Bit64 = self.xlen {
+            let unused_bits = 64 - 12 - levels as u64 * vpn_bits;
+            if (((address << unused_bits) as i64) >> unused_bits) as u64 != address {
+                return Err(get_page_fault(access_type, address));
+            }
+        }
+        self.hpm_events.count(HpmEvent::PageWalk);
+        let mut vpns = [0; 5];
+        for (i, vpn) in vpns.iter_mut().enumerate().take(levels as usize) {
+            *vpn = (address >> (12 + i as u64 * vpn_bits)) & ((1 << vpn_bits) - 1);
+        }
+        self.traverse_page(address, levels - 1, self.ppn, &vpns, access_type)
+    }
+
     fn traverse_page(
         &mut self,
         v_address: u64,
@@ -738,13 +1031,17 @@ impl Mmu {
         parent_ppn: u64,
         vpns: &[u64],
         access_type: &MemoryAccessType,
-    ) -> Result<u64, ()> {
+    ) -> Result<u64, Trap> {
         let pagesize = 4096;
         let ptesize = match self.addressing_mode {
             AddressingMode::SV32 => 4,
             _ => 8,
         };
         let pte_address = parent_ppn * pagesize + vpns[level as usize] * ptesize;
+        // Page table accesses are checked as Supervisor mode reads
+        if !self.pmp.is_permitted(pte_address, ptesize, false, PMP_R) {
+            return Err(get_access_fault(access_type, v_address));
+        }
         let pte = match self.addressing_mode {
             AddressingMode::SV32 => self.load_word_raw(pte_address) as u64,
             _ => self.load_doubleword_raw(pte_address),
@@ -753,214 +1050,148 @@ impl Mmu {
             AddressingMode::SV32 => (pte >> 10) & 0x3fffff,
             _ => (pte >> 10) & 0xfffffffffff,
         };
-        let ppns = match self.addressing_mode {
-            AddressingMode::SV32 => [(pte >> 10) & 0x3ff, (pte >> 20) & 0xfff, 0 /*dummy*/],
-            AddressingMode::SV39 => [
-                (pte >> 10) & 0x1ff,
-                (pte >> 19) & 0x1ff,
-                (pte >> 28) & 0x3ffffff,
-            ],
-            _ => panic!(), // Shouldn't happen
-        };
         let _rsw = (pte >> 8) & 0x3;
         let d = (pte >> 7) & 1;
         let a = (pte >> 6) & 1;
         let _g = (pte >> 5) & 1;
-        let _u = (pte >> 4) & 1;
+        let u = (pte >> 4) & 1;
         let x = (pte >> 3) & 1;
         let w = (pte >> 2) & 1;
         let r = (pte >> 1) & 1;
         let v = pte & 1;
+        let page_fault = || get_page_fault(access_type, v_address);
 
-        // println!("VA:{:X} Level:{:X} PTE_AD:{:X} PTE:{:X} PPPN:{:X} PPN:{:X} PPN1:{:X} PPN0:{:X}", v_address, level, pte_address, pte, parent_ppn, ppn, ppns[1], ppns[0]);
+        // println!("VA:{:X} Level:{:X} PTE_AD:{:X} PTE:{:X} PPPN:{:X} PPN:{:X}", v_address, level, pte_address, pte, parent_ppn, ppn);
 
-        if v == 0 || (r == 0 && w == 1) {
-            return Err(());
+        // Bits 63:54 are reserved for Svnapot and Svpbmt not implemented
+        if v == 0 || (r == 0 && w == 1) || (pte >> 54) != 0 {
+            return Err(page_fault());
         }
 
         if r == 0 && x == 0 {
+            // D, A and U are reserved in non-leaf entries
+            if d != 0 || a != 0 || u != 0 {
+                return Err(page_fault());
+            }
             return match level {
-                0 => Err(()),
+                0 => Err(page_fault()),
                 _ => self.traverse_page(v_address, level - 1, ppn, vpns, access_type),
             };
         }
 
         // Leaf page found
 
-        if a == 0
-            || (match access_type {
-                MemoryAccessType::Write => d == 0,
-                _ => false,
-            })
-        {
-            let new_pte = pte
-                | (1 << 6)
-                | (match access_type {
-                    MemoryAccessType::Write => 1 << 7,
-                    _ => 0,
-                });
+        // User mode can access only user pages. Supervisor mode can't
+        // execute them and accesses them only if mstatus.SUM is set.
+        match self.get_effective_privilege_mode(access_type) {
+            PrivilegeMode::User if u == 0 => return Err(page_fault()),
+            PrivilegeMode::Supervisor if u == 1 => match access_type {
+                MemoryAccessType::Execute => return Err(page_fault()),
+                _ if self.mstatus & MSTATUS_SUM == 0 => return Err(page_fault()),
+                _ => {}
+            },
+            _ => {}
+        };
+
+        // mstatus.MXR makes executable pages readable
+        let readable = r == 1 || (x == 1 && self.mstatus & MSTATUS_MXR != 0);
+        match access_type {
+            MemoryAccessType::Execute if x == 0 => return Err(page_fault()),
+            MemoryAccessType::Read if !readable => return Err(page_fault()),
+            MemoryAccessType::Write if w == 0 => return Err(page_fault()),
+            _ => {}
+        };
+
+        // Superpage must be aligned to its size, the PPN fields
+        // below the level must be zero
+        let superpage_mask = (1 << (level as u64 * self.addressing_mode.get_vpn_bits())) - 1;
+        if ppn & superpage_mask != 0 {
+            return Err(page_fault());
+        }
+
+        // The A bit is set on any access and the D bit on write. Hardware
+        // updates them only if menvcfg.ADUE is enabled with Svadu,
+        // otherwise page fault lets software update them.
+        let is_write = matches!(access_type, MemoryAccessType::Write);
+        if !matches!(access_type, MemoryAccessType::DontCare) && (a == 0 || (is_write && d == 0)) {
+            if self.menvcfg & MENVCFG_ADUE == 0 {
+                return Err(page_fault());
+            }
+            if !self.pmp.is_permitted(pte_address, ptesize, false, PMP_W) {
+                return Err(get_access_fault(access_type, v_address));
+            }
+            let new_pte = pte | (1 << 6) | ((is_write as u64) << 7);
             match self.addressing_mode {
                 AddressingMode::SV32 => self.store_word_raw(pte_address, new_pte as u32),
                 _ => self.store_doubleword_raw(pte_address, new_pte),
             };
         }
 
-        match access_type {
-            MemoryAccessType::Execute => {
-                if x == 0 {
-                    return Err(());
-                }
-            }
-            MemoryAccessType::Read => {
-                if r == 0 {
-                    return Err(());
-                }
-            }
-            MemoryAccessType::Write => {
-                if w == 0 {
-                    return Err(());
-                }
-            }
-            _ => {}
-        };
-
+        // The virtual page number fields below the level are
+        // the offset in the superpage
         let offset = v_address & 0xfff; // [11:0]
-                                        // @TODO: Optimize
-        let p_address = match self.addressing_mode {
-            AddressingMode::SV32 => match level {
-                1 => {
-                    if ppns[0] != 0 {
-                        return Err(());
-                    }
-                    (ppns[1] << 22) | (vpns[0] << 12) | offset
-                }
-                0 => (ppn << 12) | offset,
-                _ => panic!(), // Shouldn't happen
-            },
-            _ => match level {
-                2 => {
-                    if ppns[1] != 0 || ppns[0] != 0 {
-                        return Err(());
-                    }
-                    (ppns[2] << 30) | (vpns[1] << 21) | (vpns[0] << 12) | offset
-                }
-                1 => {
-                    if ppns[0] != 0 {
-                        return Err(());
-                    }
-                    (ppns[2] << 30) | (ppns[1] << 21) | (vpns[0] << 12) | offset
-                }
-                0 => (ppn << 12) | offset,
-                _ => panic!(), // Shouldn't happen
-            },
-        };
+        let p_address = ((ppn | ((v_address >> 12) & superpage_mask)) << 12) | offset;
 
         // println!("PA:{:X}", p_address);
         Ok(p_address)
     }
 
-    /// Returns immutable reference to `Clint`.
-    pub fn get_clint(&self) -> &Clint {
-        &self.clint
+    /// Reads `mtime` register content of CLINT on the bus
+    pub fn read_mtime(&self) -> u64 {
+        self.bus.borrow().read_mtime()
     }
 
-    /// Returns mutable reference to `Clint`.
-    pub fn get_mut_clint(&mut self) -> &mut Clint {
-        &mut self.clint
+    /// Returns the exit code if the guest has requested power-off
+    /// through the test finisher on the bus.
+    pub fn get_exit_code(&self) -> Option<u32> {
+        self.bus.borrow().get_exit_code()
     }
 
-    /// Returns mutable reference to `Uart`.
-    pub fn get_mut_uart(&mut self) -> &mut Uart {
-        &mut self.uart
+    /// Returns mutable reference to `Terminal` of the UART on the bus.
+    pub fn get_mut_terminal(&mut self) -> RefMut<'_, Box<dyn Terminal>> {
+        RefMut::map(self.bus.borrow_mut(), |bus| {
+            &mut bus.get_mut_uart().terminal
+        })
     }
-}
 
-/// [`Memory`] wrapper. Converts physical address to the one in memory
-/// using [`DRAM_BASE`] and accesses [`Memory`].
-pub struct MemoryWrapper(Memory);
-
-impl MemoryWrapper {
-    fn new() -> Self {
-        MemoryWrapper(Memory::new())
-    }
-
-    fn init(&mut self, capacity: u64) {
-        self.0.init(capacity);
-    }
-
-    pub fn read_byte(&mut self, p_address: u64) -> u8 {
-        debug_assert!(
-            p_address >= DRAM_BASE,
-            "Memory address must equals to or bigger than DRAM_BASE. {:X}",
-            p_address
-        );
-        self.0.read_byte(p_address - DRAM_BASE)
-    }
-
-    pub fn read_halfword(&mut self, p_address: u64) -> u16 {
-        debug_assert!(
-            p_address >= DRAM_BASE && p_address.wrapping_add(1) >= DRAM_BASE,
-            "Memory address must equals to or bigger than DRAM_BASE. {:X}",
-            p_address
-        );
-        self.0.read_halfword(p_address - DRAM_BASE)
-    }
-
-    pub fn read_word(&mut self, p_address: u64) -> u32 {
-        debug_assert!(
-            p_address >= DRAM_BASE && p_address.wrapping_add(3) >= DRAM_BASE,
-            "Memory address must equals to or bigger than DRAM_BASE. {:X}",
-            p_address
-        );
-        self.0.read_word(p_address - DRAM_BASE)
-    }
-
-    pub fn read_doubleword(&mut self, p_address: u64) -> u64 {
-        debug_assert!(
-            p_address >= DRAM_BASE && p_address.wrapping_add(7) >= DRAM_BASE,
-            "Memory address must equals to or bigger than DRAM_BASE. {:X}",
-            p_address
-        );
-        self.0.read_doubleword(p_address - DRAM_BASE)
+    /// Saves the address translation state to a snapshot. The shared
+    /// [`Bus`] is saved separately.
+    pub(crate) fn save_state(&self, writer: &mut SnapshotWriter) {
+        write_xlen(writer, &self.xlen);
+        writer.write_u64(self.ppn);
+        writer.write_u8(match self.addressing_mode {
+            AddressingMode::None => 0,
+            AddressingMode::SV32 => 1,
+            AddressingMode::SV39 => 2,
+            AddressingMode::SV48 => 3,
+            AddressingMode::SV57 => 4,
+        });
+        writer.write_u8(get_privilege_encoding(&self.privilege_mode));
+        writer.write_u64(self.mstatus);
+        writer.write_u64(self.menvcfg);
+        self.pmp.save_state(writer);
     }
 
-    pub fn write_byte(&mut self, p_address: u64, value: u8) {
-        debug_assert!(
-            p_address >= DRAM_BASE,
-            "Memory address must equals to or bigger than DRAM_BASE. {:X}",
-            p_address
-        );
-        self.0.write_byte(p_address - DRAM_BASE, value)
-    }
-
-    pub fn write_halfword(&mut self, p_address: u64, value: u16) {
-        debug_assert!(
-            p_address >= DRAM_BASE && p_address.wrapping_add(1) >= DRAM_BASE,
-            "Memory address must equals to or bigger than DRAM_BASE. {:X}",
-            p_address
-        );
-        self.0.write_halfword(p_address - DRAM_BASE, value)
-    }
-
-    pub fn write_word(&mut self, p_address: u64, value: u32) {
-        debug_assert!(
-            p_address >= DRAM_BASE && p_address.wrapping_add(3) >= DRAM_BASE,
-            "Memory address must equals to or bigger than DRAM_BASE. {:X}",
-            p_address
-        );
-        self.0.write_word(p_address - DRAM_BASE, value)
-    }
-
-    pub fn write_doubleword(&mut self, p_address: u64, value: u64) {
-        debug_assert!(
-            p_address >= DRAM_BASE && p_address.wrapping_add(7) >= DRAM_BASE,
-            "Memory address must equals to or bigger than DRAM_BASE. {:X}",
-            p_address
-        );
-        self.0.write_doubleword(p_address - DRAM_BASE, value)
-    }
-
-    pub fn validate_address(&self, address: u64) -> bool {
-        self.0.validate_address(address - DRAM_BASE)
+    /// Restores the state saved with `save_state()`.
+    pub(crate) fn restore_state(
+        &mut self,
+        reader: &mut SnapshotReader,
+    ) -> Result<(), EmulatorError> {
+        self.xlen = read_xlen(reader)?;
+        self.ppn = reader.read_u64()?;
+        self.addressing_mode = match reader.read_u8()? {
+            0 => AddressingMode::None,
+            1 => AddressingMode::SV32,
+            2 => AddressingMode::SV39,
+            3 => AddressingMode::SV48,
+            4 => AddressingMode::SV57,
+            _ => return Err(EmulatorError::InvalidSnapshot),
+        };
+        self.privilege_mode = read_privilege_mode(reader)?;
+        self.mstatus = reader.read_u64()?;
+        self.menvcfg = reader.read_u64()?;
+        self.pmp.restore_state(reader)?;
+        self.clear_page_cache();
+        Ok(())
     }
 }
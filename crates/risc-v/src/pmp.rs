@@ -0,0 +1,180 @@
+use crate::error::EmulatorError;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
+
+/// The number of PMP entries, all the ones `pmpcfg0`-`pmpcfg15` describe
+pub(crate) const PMP_ENTRY_NUM: usize = 64;
+
+/// `pmpcfg` entry fields
+pub(crate) const PMP_R: u8 = 0x01;
+pub(crate) const PMP_W: u8 = 0x02;
+pub(crate) const PMP_X: u8 = 0x04;
+const PMP_A: u8 = 0x18;
+const PMP_L: u8 = 0x80;
+
+/// Address-matching modes in the `A` field
+const PMP_A_OFF: u8 = 0x00;
+const PMP_A_TOR: u8 = 0x08;
+const PMP_A_NA4: u8 = 0x10;
+const PMP_A_NAPOT: u8 = 0x18;
+
+/// Emulates Physical Memory Protection unit of a hart. Each entry has an
+/// 8-bit configuration in `pmpcfg` and an address in `pmpaddr` holding
+/// bits 55:2 of physical address. The grain is four bytes so every
+/// address-matching mode is available.
+pub struct Pmp {
+    configs: [u8; PMP_ENTRY_NUM],
+    addresses: [u64; PMP_ENTRY_NUM],
+    /// Physical address range, start and end exclusive, each entry matches.
+    /// Calculated from the configurations and addresses when they're updated.
+    ranges: [(u64, u64); PMP_ENTRY_NUM],
+    /// The number of entries up to the last active one
+    active_entry_num: usize,
+}
+
+impl Default for Pmp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pmp {
+    /// Creates a new `Pmp`. All the entries are off at reset.
+    pub fn new() -> Self {
+        Pmp {
+            configs: [0; PMP_ENTRY_NUM],
+            addresses: [0; PMP_ENTRY_NUM],
+            ranges: [(0, 0); PMP_ENTRY_NUM],
+            active_entry_num: 0,
+        }
+    }
+
+    /// Reads the configuration of an entry.
+    ///
+    /// # Arguments
+    /// * `index` Entry index
+    pub fn read_config(&self, index: usize) -> u8 {
+        self.configs[index]
+    }
+
+    /// Reads the address of an entry.
+    ///
+    /// # Arguments
+    /// * `index` Entry index
+    pub fn read_address(&self, index: usize) -> u64 {
+        self.addresses[index]
+    }
+
+    /// Writes the configuration of an entry. Writes to a locked entry are
+    /// ignored. The reserved permission, writable but not readable, is
+    /// WARL and drops the write permission.
+    ///
+    /// # Arguments
+    /// * `index` Entry index
+    /// * `config`
+    pub fn write_config(&mut self, index: usize, config: u8) {
+        if self.configs[index] & PMP_L != 0 {
+            return;
+        }
+        self.configs[index] = match config & (PMP_R | PMP_W) {
+            PMP_W => config & !PMP_W,
+            _ => config,
+        };
+        self.update_ranges();
+    }
+
+    /// Writes the address of an entry. Writes are ignored if the entry is
+    /// locked, or if the next entry is a locked top of range entry using
+    /// the address as its bottom.
+    ///
+    /// # Arguments
+    /// * `index` Entry index
+    /// * `address` Bits 55:2 of physical address
+    pub fn write_address(&mut self, index: usize, address: u64) {
+        let is_locked = |config: u8| config & PMP_L != 0;
+        if is_locked(self.configs[index])
+            || (index + 1 < PMP_ENTRY_NUM
+                && is_locked(self.configs[index + 1])
+                && self.configs[index + 1] & PMP_A == PMP_A_TOR)
+        {
+            return;
+        }
+        self.addresses[index] = address;
+        self.update_ranges();
+    }
+
+    fn update_ranges(&mut self) {
+        self.active_entry_num = 0;
+        for index in 0..PMP_ENTRY_NUM {
+            let address = self.addresses[index];
+            self.ranges[index] = match self.configs[index] & PMP_A {
+                // Empty if the bottom isn't below the top
+                PMP_A_TOR => {
+                    let start = match index {
+                        0 => 0,
+                        _ => self.addresses[index - 1] << 2,
+                    };
+                    match start < address << 2 {
+                        true => (start, address << 2),
+                        false => (0, 0),
+                    }
+                }
+                PMP_A_NA4 => (address << 2, (address << 2) + 4),
+                // The trailing ones encode the size, from eight bytes
+                PMP_A_NAPOT => {
+                    let trailing_ones = address.trailing_ones();
+                    let base = (address & !((1 << trailing_ones) - 1)) << 2;
+                    (base, base + (8 << trailing_ones))
+                }
+                _ => continue,
+            };
+            self.active_entry_num = index + 1;
+        }
+    }
+
+    /// Checks if an access is permitted. The lowest-numbered entry matching
+    /// any byte of the access decides it, and the entry must match all the
+    /// bytes. Machine mode accesses are permitted by unlocked entries and
+    /// by no entry, the others need the permission of an entry.
+    ///
+    /// # Arguments
+    /// * `address` Physical address
+    /// * `width` Access size in bytes
+    /// * `is_machine` Whether the effective privilege mode is Machine mode
+    /// * `permission` [`PMP_R`], [`PMP_W`] or [`PMP_X`]
+    pub fn is_permitted(&self, address: u64, width: u64, is_machine: bool, permission: u8) -> bool {
+        let end = address.wrapping_add(width);
+        for index in 0..self.active_entry_num {
+            let config = self.configs[index];
+            let (start, range_end) = self.ranges[index];
+            if config & PMP_A == PMP_A_OFF || address >= range_end || end <= start {
+                continue;
+            }
+            if address < start || end > range_end {
+                return false;
+            }
+            return (is_machine && config & PMP_L == 0) || config & permission != 0;
+        }
+        is_machine
+    }
+
+    /// Saves the entries to a snapshot.
+    pub(crate) fn save_state(&self, writer: &mut SnapshotWriter) {
+        for (config, address) in self.configs.iter().zip(self.addresses.iter()) {
+            writer.write_u8(*config);
+            writer.write_u64(*address);
+        }
+    }
+
+    /// Restores the entries saved with `save_state()`.
+    pub(crate) fn restore_state(
+        &mut self,
+        reader: &mut SnapshotReader,
+    ) -> Result<(), EmulatorError> {
+        for index in 0..PMP_ENTRY_NUM {
+            self.configs[index] = reader.read_u8()?;
+            self.addresses[index] = reader.read_u64()?;
+        }
+        self.update_ranges();
+        Ok(())
+    }
+}
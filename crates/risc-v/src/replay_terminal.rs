@@ -0,0 +1,185 @@
+use std::fmt;
+use std::num::NonZeroU8;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use crate::error::EmulatorError;
+use crate::terminal::Terminal;
+
+/// An input byte delivered to the guest and the CPU clock it was consumed at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputEvent {
+    pub clock: u64,
+    pub value: NonZeroU8,
+}
+
+/// Input events of a session in the order they were consumed.
+/// It's converted to/from text, one `<clock> <value>` pair in decimal per line,
+/// so that it can be attached to a bug report.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputLog {
+    events: Vec<InputEvent>,
+}
+
+impl InputLog {
+    /// Creates a new empty `InputLog`.
+    pub fn new() -> Self {
+        InputLog { events: vec![] }
+    }
+
+    /// Appends an event.
+    ///
+    /// # Arguments
+    /// * `event`
+    pub fn push(&mut self, event: InputEvent) {
+        self.events.push(event);
+    }
+
+    /// Returns the events in the order they were consumed.
+    pub fn get_events(&self) -> &[InputEvent] {
+        &self.events
+    }
+}
+
+impl fmt::Display for InputLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in &self.events {
+            writeln!(f, "{} {}", event.clock, event.value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for InputLog {
+    type Err = EmulatorError;
+
+    /// Parses the text `InputLog` is formatted to. Empty lines are ignored.
+    /// Returns `Err` if a line is malformed or clocks aren't in order.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut log = InputLog::new();
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = || EmulatorError::InvalidInputLog { line: index + 1 };
+            let mut fields = line.split_whitespace();
+            let (clock, value) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(clock), Some(value), None) => (clock, value),
+                _ => return Err(invalid()),
+            };
+            let clock = clock.parse().map_err(|_| invalid())?;
+            let value = value.parse().map_err(|_| invalid())?;
+            if let Some(last) = log.events.last() {
+                if last.clock > clock {
+                    return Err(invalid());
+                }
+            }
+            log.push(InputEvent { clock, value });
+        }
+        Ok(log)
+    }
+}
+
+/// `Terminal` wrapper recording each input byte delivered to the guest with
+/// the CPU clock it was consumed at. Pass the recorded log to
+/// [`ReplayTerminal`] to reproduce the session.
+///
+/// ```ignore
+/// let terminal = RecordingTerminal::new(Box::new(DefaultTerminal::new()));
+/// let log = terminal.get_log();
+/// let mut emulator = Emulator::new(Box::new(terminal));
+/// // ...
+/// fs::write("input.log", log.lock().unwrap().to_string())?;
+/// ```
+pub struct RecordingTerminal {
+    terminal: Box<dyn Terminal>,
+    log: Arc<Mutex<InputLog>>,
+}
+
+impl RecordingTerminal {
+    /// Creates a new `RecordingTerminal`.
+    ///
+    /// # Arguments
+    /// * `terminal` Wrapped terminal. Input and output are passed through.
+    pub fn new(terminal: Box<dyn Terminal>) -> Self {
+        RecordingTerminal {
+            terminal,
+            log: Arc::new(Mutex::new(InputLog::new())),
+        }
+    }
+
+    /// Returns the log shared with this terminal. It's still accessible
+    /// after the terminal is moved into `Emulator`.
+    pub fn get_log(&self) -> Arc<Mutex<InputLog>> {
+        self.log.clone()
+    }
+}
+
+impl Terminal for RecordingTerminal {
+    fn put_byte(&mut self, value: NonZeroU8) {
+        self.terminal.put_byte(value);
+    }
+
+    /// Input without clock can't be recorded. `Emulator` always calls
+    /// `get_input_at()` then this is only for the other users.
+    fn get_input(&mut self) -> Option<NonZeroU8> {
+        self.terminal.get_input()
+    }
+
+    fn get_input_at(&mut self, clock: u64) -> Option<NonZeroU8> {
+        let value = self.terminal.get_input_at(clock)?;
+        self.log.lock().unwrap().push(InputEvent { clock, value });
+        Some(value)
+    }
+}
+
+/// `Terminal` feeding input bytes recorded by [`RecordingTerminal`] at
+/// the same CPU clocks. Running the same program on the same machine
+/// configuration reproduces the recorded session exactly.
+pub struct ReplayTerminal {
+    terminal: Box<dyn Terminal>,
+    events: Vec<InputEvent>,
+    next_index: usize,
+}
+
+impl ReplayTerminal {
+    /// Creates a new `ReplayTerminal`.
+    ///
+    /// # Arguments
+    /// * `log` Recorded input
+    /// * `terminal` Output is passed to it. Its input is ignored.
+    pub fn new(log: InputLog, terminal: Box<dyn Terminal>) -> Self {
+        ReplayTerminal {
+            terminal,
+            events: log.events,
+            next_index: 0,
+        }
+    }
+
+    /// Indicates whether all the recorded input has been fed.
+    pub fn is_finished(&self) -> bool {
+        self.next_index == self.events.len()
+    }
+}
+
+impl Terminal for ReplayTerminal {
+    fn put_byte(&mut self, value: NonZeroU8) {
+        self.terminal.put_byte(value);
+    }
+
+    /// Recorded input is fed only through `get_input_at()`.
+    fn get_input(&mut self) -> Option<NonZeroU8> {
+        None
+    }
+
+    fn get_input_at(&mut self, clock: u64) -> Option<NonZeroU8> {
+        // An event whose clock has passed means the replay diverged from
+        // the recording. Feeds it at the next poll rather than dropping it.
+        let event = self.events.get(self.next_index)?;
+        if event.clock > clock {
+            return None;
+        }
+        self.next_index += 1;
+        Some(event.value)
+    }
+}
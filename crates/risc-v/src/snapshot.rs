@@ -0,0 +1,200 @@
+use crate::error::EmulatorError;
+
+/// Magic number at the head of a snapshot
+pub(crate) const SNAPSHOT_MAGIC: [u8; 8] = *b"WSLSNAP\0";
+
+/// Snapshot format version. Increment it whenever the layout changes.
+pub(crate) const SNAPSHOT_VERSION: u32 = 9;
+
+/// Number of 64-bit words in a chunk of sparsely stored memory.
+/// Chunks filled with zero are omitted from a snapshot.
+const CHUNK_WORDS: usize = 512;
+
+/// Terminates the chunk list of sparsely stored memory
+const END_OF_CHUNKS: u64 = u64::MAX;
+
+/// Serializes emulator state into the snapshot binary format.
+/// All the values are stored in little endian.
+pub(crate) struct SnapshotWriter {
+    data: Vec<u8>,
+}
+
+impl SnapshotWriter {
+    /// Creates a new `SnapshotWriter` with empty content.
+    pub fn new() -> Self {
+        SnapshotWriter { data: vec![] }
+    }
+
+    /// Returns the serialized content.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_option_u64(&mut self, value: Option<u64>) {
+        self.write_bool(value.is_some());
+        self.write_u64(value.unwrap_or(0));
+    }
+
+    /// Writes length-prefixed bytes.
+    ///
+    /// # Arguments
+    /// * `bytes`
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u64(bytes.len() as u64);
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Writes length-prefixed 64-bit words. Only the chunks having non-zero
+    /// words are stored to keep snapshots of mostly empty memory small.
+    ///
+    /// # Arguments
+    /// * `words`
+    pub fn write_sparse_words(&mut self, words: &[u64]) {
+        self.write_u64(words.len() as u64);
+        for (index, chunk) in words.chunks(CHUNK_WORDS).enumerate() {
+            if chunk.iter().all(|word| *word == 0) {
+                continue;
+            }
+            self.write_u64(index as u64);
+            for word in chunk {
+                self.write_u64(*word);
+            }
+        }
+        self.write_u64(END_OF_CHUNKS);
+    }
+}
+
+/// Deserializes emulator state from the snapshot binary format.
+/// Every method returns `Err` if the content is truncated or malformed.
+pub(crate) struct SnapshotReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> SnapshotReader<'a> {
+    /// Creates a new `SnapshotReader`.
+    ///
+    /// # Arguments
+    /// * `data` Snapshot content
+    pub fn new(data: &'a [u8]) -> Self {
+        SnapshotReader { data, position: 0 }
+    }
+
+    /// Indicates whether the whole content has been read.
+    pub fn is_end(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    fn read_slice(&mut self, length: usize) -> Result<&'a [u8], EmulatorError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(EmulatorError::InvalidSnapshot)?;
+        let slice = &self.data[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], EmulatorError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_slice(N)?);
+        Ok(array)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, EmulatorError> {
+        Ok(self.read_slice(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, EmulatorError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(EmulatorError::InvalidSnapshot),
+        }
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, EmulatorError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, EmulatorError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, EmulatorError> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_option_u64(&mut self) -> Result<Option<u64>, EmulatorError> {
+        let is_some = self.read_bool()?;
+        let value = self.read_u64()?;
+        Ok(match is_some {
+            true => Some(value),
+            false => None,
+        })
+    }
+
+    /// Reads bytes written by `SnapshotWriter::write_bytes()`.
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], EmulatorError> {
+        let length = self.read_u64()?;
+        let length = usize::try_from(length).map_err(|_| EmulatorError::InvalidSnapshot)?;
+        self.read_slice(length)
+    }
+
+    /// Reads words written by `SnapshotWriter::write_sparse_words()`.
+    /// `words` is resized to the stored length if `max_length` allows,
+    /// otherwise `Err` is returned.
+    ///
+    /// # Arguments
+    /// * `words`
+    /// * `max_length` The maximum number of words `words` can hold
+    pub fn read_sparse_words(
+        &mut self,
+        words: &mut Vec<u64>,
+        max_length: usize,
+    ) -> Result<(), EmulatorError> {
+        let length = self.read_u64()?;
+        let length = match usize::try_from(length) {
+            Ok(length) if length <= max_length => length,
+            _ => return Err(EmulatorError::InvalidSnapshot),
+        };
+        words.clear();
+        words.resize(length, 0);
+        loop {
+            let index = self.read_u64()?;
+            if index == END_OF_CHUNKS {
+                return Ok(());
+            }
+            let start = usize::try_from(index)
+                .ok()
+                .and_then(|index| index.checked_mul(CHUNK_WORDS))
+                .filter(|start| *start < length)
+                .ok_or(EmulatorError::InvalidSnapshot)?;
+            let end = length.min(start + CHUNK_WORDS);
+            for word in &mut words[start..end] {
+                *word = self.read_u64()?;
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use elf::abi::{EM_RISCV, SHN_UNDEF, STB_LOCAL, STT_FILE, STT_FUNC, STT_OBJECT, STT_SECTION};
+use elf::endian::AnyEndian;
+use elf::ElfBytes;
+use fnv::FnvHashMap;
+
+use crate::error::EmulatorError;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
+
+/// A symbol of a program
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    /// Virtual address
+    pub address: u64,
+    /// Size in bytes. Zero if unknown, e.g. labels in assembly.
+    pub size: u64,
+    /// Symbol type, `STT_*` in ELF
+    pub symbol_type: u8,
+    /// Symbol binding, `STB_*` in ELF
+    pub binding: u8,
+}
+
+impl Symbol {
+    /// Returns the priority to be chosen among the symbols at the same
+    /// address in `SymbolMap::symbolize()`. Bigger is preferred.
+    fn get_priority(&self) -> u8 {
+        match self.symbol_type {
+            STT_FUNC => 2,
+            STT_OBJECT => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Symbols of the programs and the symbol files loaded to `Emulator`.
+/// Looks up a symbol by name, and an address by symbol (`symbolize()`)
+/// for traces and crash reports.
+pub struct SymbolMap {
+    symbols: Vec<Symbol>,
+    /// Symbol name to index in `symbols`
+    name_index: FnvHashMap<String, usize>,
+    /// Indices in `symbols` sorted by address and priority
+    address_index: Vec<usize>,
+}
+
+impl Default for SymbolMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SymbolMap {
+    /// Creates a new empty `SymbolMap`.
+    pub fn new() -> Self {
+        SymbolMap {
+            symbols: vec![],
+            name_index: FnvHashMap::default(),
+            address_index: vec![],
+        }
+    }
+
+    /// Adds symbols in the symbol table of an ELF file. The names are
+    /// resolved through the string table associated with the symbol table.
+    /// Returns `Err` if the content doesn't seem RISC-V ELF file.
+    /// Nothing is added if the file has no symbol table (stripped).
+    ///
+    /// # Arguments
+    /// * `content` ELF file content
+    /// * `bias` Offset added to the symbol addresses, for position
+    ///   independent programs loaded at an address other than the linked one
+    pub fn load_elf(&mut self, content: &[u8], bias: u64) -> Result<(), EmulatorError> {
+        let elf = ElfBytes::<AnyEndian>::minimal_parse(content)?;
+        if elf.ehdr.e_machine != EM_RISCV {
+            return Err(EmulatorError::UnsupportedMachine(elf.ehdr.e_machine));
+        }
+        let (symbols, strings) = match elf.symbol_table()? {
+            Some(table) => table,
+            None => return Ok(()),
+        };
+        // Reads all the symbols first not to add a part of them on error
+        let mut new_symbols = vec![];
+        for symbol in symbols {
+            let symbol_type = symbol.st_symtype();
+            if symbol.st_shndx == SHN_UNDEF || symbol_type == STT_SECTION || symbol_type == STT_FILE
+            {
+                continue;
+            }
+            let name = strings.get(symbol.st_name as usize)?;
+            if name.is_empty() {
+                continue;
+            }
+            new_symbols.push(Symbol {
+                name: name.to_string(),
+                address: symbol.st_value.wrapping_add(bias),
+                size: symbol.st_size,
+                symbol_type,
+                binding: symbol.st_bind(),
+            });
+        }
+        for symbol in new_symbols {
+            self.insert(symbol);
+        }
+        self.sort_address_index();
+        Ok(())
+    }
+
+    /// Adds a symbol. If a symbol having the same name already exists,
+    /// name lookup keeps returning it unless it's local and the new one isn't.
+    fn insert(&mut self, symbol: Symbol) {
+        let index = self.symbols.len();
+        match self.name_index.get(&symbol.name) {
+            Some(current) if self.symbols[*current].binding != STB_LOCAL => {}
+            Some(_) if symbol.binding == STB_LOCAL => {}
+            _ => {
+                self.name_index.insert(symbol.name.clone(), index);
+            }
+        };
+        self.address_index.push(index);
+        self.symbols.push(symbol);
+    }
+
+    fn sort_address_index(&mut self) {
+        let symbols = &self.symbols;
+        self.address_index.sort_by_key(|index| {
+            let symbol = &symbols[*index];
+            (symbol.address, symbol.get_priority())
+        });
+    }
+
+    /// Returns the symbol of the name.
+    ///
+    /// # Arguments
+    /// * `name`
+    pub fn get_symbol(&self, name: &str) -> Option<&Symbol> {
+        self.name_index.get(name).map(|index| &self.symbols[*index])
+    }
+
+    /// Returns the virtual address of the symbol.
+    ///
+    /// # Arguments
+    /// * `name`
+    pub fn get_address(&self, name: &str) -> Option<u64> {
+        self.get_symbol(name).map(|symbol| symbol.address)
+    }
+
+    /// Returns the name of the symbol containing the address and the offset
+    /// from it. The closest symbol at or below the address is chosen,
+    /// symbols with size are chosen only if the address is in the range.
+    ///
+    /// # Arguments
+    /// * `address` Virtual address
+    pub fn symbolize(&self, address: u64) -> Option<(&str, u64)> {
+        let position = self
+            .address_index
+            .partition_point(|index| self.symbols[*index].address <= address);
+        let symbol = &self.symbols[self.address_index[position.checked_sub(1)?]];
+        let offset = address - symbol.address;
+        match symbol.size == 0 || offset < symbol.size {
+            true => Some((&symbol.name, offset)),
+            false => None,
+        }
+    }
+
+    /// Returns the number of the symbols.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Indicates whether no symbol is loaded.
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Removes all the symbols.
+    pub fn clear(&mut self) {
+        self.symbols.clear();
+        self.name_index.clear();
+        self.address_index.clear();
+    }
+
+    /// Saves the symbols to a snapshot.
+    pub(crate) fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.write_u64(self.symbols.len() as u64);
+        for symbol in &self.symbols {
+            writer.write_bytes(symbol.name.as_bytes());
+            writer.write_u64(symbol.address);
+            writer.write_u64(symbol.size);
+            writer.write_u8(symbol.symbol_type);
+            writer.write_u8(symbol.binding);
+        }
+    }
+
+    /// Replaces the symbols with the ones saved with `save_state()`.
+    pub(crate) fn restore_state(
+        &mut self,
+        reader: &mut SnapshotReader,
+    ) -> Result<(), EmulatorError> {
+        self.clear();
+        let count = reader.read_u64()?;
+        for _ in 0..count {
+            let name = String::from_utf8(reader.read_bytes()?.to_vec())
+                .map_err(|_| EmulatorError::InvalidSnapshot)?;
+            self.insert(Symbol {
+                name,
+                address: reader.read_u64()?,
+                size: reader.read_u64()?,
+                symbol_type: reader.read_u8()?,
+                binding: reader.read_u8()?,
+            });
+        }
+        self.sort_address_index();
+        Ok(())
+    }
+}
@@ -18,6 +18,15 @@ pub trait Terminal {
     /// Gets an input ascii byte data from input buffer.
     /// Used by `Emulator`.
     fn get_input(&mut self) -> Option<NonZeroU8>;
+    /// Gets an input ascii byte data from input buffer at CPU clock `clock`.
+    /// Used by `Emulator`. Terminals which need the input timing, like
+    /// recording or replaying ones, override it. It calls `get_input()` by default.
+    ///
+    /// # Arguments
+    /// * `clock` CPU clock the input is polled at
+    fn get_input_at(&mut self, _clock: u64) -> Option<NonZeroU8> {
+        self.get_input()
+    }
 }
 
 /// For the test.
@@ -0,0 +1,2506 @@
+//! Vector extension (RVV 1.0). The vector register file and the element
+//! loops of the vector instructions. `Cpu` decodes the instructions, checks
+//! `mstatus.VS`, takes the vector state from the CSRs and the scalar operands
+//! from the registers, and passes them here with the element operations.
+//! Masked-off and tail elements are always left undisturbed, which both
+//! the undisturbed and the agnostic policies allow.
+
+use crate::cpu::{Trap, TrapType};
+use crate::error::EmulatorError;
+use crate::fpu::{self, RoundingMode};
+use crate::mmu::Mmu;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
+
+/// The maximum element width in bits, ELEN
+pub(crate) const ELEN: usize = 64;
+
+/// Element operation taking an element of `vs2` and an element of `vs1` or
+/// the scalar operand
+pub(crate) type BinaryOperation = fn(&mut VectorContext, u64, u64) -> u64;
+/// Element operation taking an element of `vs2`, an element of `vs1` or
+/// the scalar operand, and the element of `vd`
+pub(crate) type TernaryOperation = fn(&mut VectorContext, u64, u64, u64) -> u64;
+/// Element operation taking an element of `vs2`
+pub(crate) type UnaryOperation = fn(&mut VectorContext, u64) -> u64;
+/// Element operation producing a mask bit
+pub(crate) type CompareOperation = fn(&mut VectorContext, u64, u64) -> bool;
+/// Element operation taking the carry or borrow in `v0` too
+pub(crate) type CarryOperation = fn(&mut VectorContext, u64, u64, bool) -> u64;
+/// Element operation producing the carry or borrow out
+pub(crate) type CarryOutOperation = fn(&mut VectorContext, u64, u64, bool) -> bool;
+
+/// Element widths of the destination and the source operands, base 2
+/// logarithms relative to SEW
+#[derive(Clone, Copy)]
+pub(crate) struct Widths {
+    pub vd: i32,
+    pub vs2: i32,
+    pub vs1: i32,
+}
+
+/// SEW = SEW op SEW
+pub(crate) const SINGLE_WIDTH: Widths = Widths {
+    vd: 0,
+    vs2: 0,
+    vs1: 0,
+};
+/// 2*SEW = SEW op SEW
+pub(crate) const WIDENING: Widths = Widths {
+    vd: 1,
+    vs2: 0,
+    vs1: 0,
+};
+/// 2*SEW = 2*SEW op SEW, the `.w*` forms
+pub(crate) const WIDENING_WIDE: Widths = Widths {
+    vd: 1,
+    vs2: 1,
+    vs1: 0,
+};
+/// SEW = 2*SEW op SEW
+pub(crate) const NARROWING: Widths = Widths {
+    vd: 0,
+    vs2: 1,
+    vs1: 0,
+};
+/// SEW = SEW/2, SEW/4 and SEW/8 of the integer extension instructions
+pub(crate) const EXTENSION_VF2: Widths = Widths {
+    vd: 0,
+    vs2: -1,
+    vs1: 0,
+};
+pub(crate) const EXTENSION_VF4: Widths = Widths {
+    vd: 0,
+    vs2: -2,
+    vs1: 0,
+};
+pub(crate) const EXTENSION_VF8: Widths = Widths {
+    vd: 0,
+    vs2: -3,
+    vs1: 0,
+};
+
+/// Decoded `vtype`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct VectorType {
+    /// Selected element width in bits
+    pub sew: usize,
+    /// Base 2 logarithm of the register group multiplier, -3 to 3
+    pub lmul: i32,
+}
+
+impl VectorType {
+    /// Decodes `vtype` value. Returns `None` if the setting is reserved or
+    /// unsupported, in which case `vtype.vill` is set. The reserved bits,
+    /// `vill` included, must be zero.
+    ///
+    /// # Arguments
+    /// * `value`
+    pub fn decode(value: u64) -> Option<Self> {
+        if (value >> 8) != 0 {
+            return None;
+        }
+        let lmul = match value & 0x7 {
+            4 => return None,
+            vlmul => ((vlmul as i32) << 29) >> 29,
+        };
+        let vsew = (value >> 3) & 0x7;
+        if vsew > 3 {
+            return None;
+        }
+        let sew = 8 << vsew;
+        // Fractional LMUL must hold at least one ELEN wide element
+        if lmul < 0 && sew > ELEN >> -lmul {
+            return None;
+        }
+        Some(VectorType { sew, lmul })
+    }
+
+    /// Returns VLMAX, the maximum number of elements
+    ///
+    /// # Arguments
+    /// * `vlen` VLEN in bits
+    pub fn get_vlmax(&self, vlen: usize) -> usize {
+        scale(vlen / self.sew, self.lmul)
+    }
+}
+
+/// The second source operand, a register group or a scalar
+#[derive(Clone, Copy)]
+pub(crate) enum Operand {
+    Vector(usize),
+    Scalar(u64),
+}
+
+/// How the elements of a vector load or store are addressed
+#[derive(Clone, Copy)]
+pub(crate) enum Addressing {
+    /// Consecutive elements of the width
+    UnitStride { eew: usize },
+    /// Elements of the width separated by the byte stride
+    Strided { eew: usize, stride: u64 },
+    /// Elements of SEW at the byte offsets in `vs2`, elements of the width
+    Indexed { vs2: usize, eew: usize },
+}
+
+/// Vector state an instruction runs with, taken from the vector CSRs.
+/// The element operations accrue the fixed-point saturation and
+/// the floating-point exception flags to it.
+pub(crate) struct VectorContext {
+    /// Selected element width in bits
+    pub sew: usize,
+    /// Base 2 logarithm of LMUL
+    pub lmul: i32,
+    pub vl: usize,
+    pub vstart: usize,
+    /// Fixed-point rounding mode, `vxrm`
+    pub vxrm: u64,
+    /// Floating-point rounding mode, `None` if `frm` is reserved
+    pub rm: Option<RoundingMode>,
+    /// Whether a fixed-point operation saturated, accrued to `vxsat`
+    pub saturated: bool,
+    /// `fpu::FLAG_*` bits accrued to `fflags`
+    pub flags: u8,
+}
+
+impl VectorContext {
+    /// Returns EEW in bits and base 2 logarithm of EMUL of an operand.
+    /// Raises an illegal instruction exception if they aren't supported.
+    ///
+    /// # Arguments
+    /// * `width` Base 2 logarithm of EEW relative to SEW
+    fn get_operand(&self, width: i32) -> Result<(usize, i32), Trap> {
+        let eew = scale(self.sew, width);
+        let emul = self.lmul + width;
+        match (8..=ELEN).contains(&eew) && (-3..=3).contains(&emul) {
+            true => Ok((eew, emul)),
+            false => Err(illegal_instruction()),
+        }
+    }
+
+    /// Raises an illegal instruction exception unless the floating-point
+    /// operands are single or double precision and `frm` is valid.
+    ///
+    /// # Arguments
+    /// * `width` Base 2 logarithm of the floating-point element width
+    ///   relative to SEW
+    pub fn check_float(&self, width: i32) -> Result<(), Trap> {
+        match matches!(scale(self.sew, width), 32 | 64) && self.rm.is_some() {
+            true => Ok(()),
+            false => Err(illegal_instruction()),
+        }
+    }
+
+    fn rounding_mode(&self) -> RoundingMode {
+        self.rm.unwrap_or(RoundingMode::NearestEven)
+    }
+
+    /// Raises an illegal instruction exception unless `vstart` is zero,
+    /// required by the instructions which can't resume.
+    fn check_vstart(&self) -> Result<(), Trap> {
+        match self.vstart {
+            0 => Ok(()),
+            _ => Err(illegal_instruction()),
+        }
+    }
+}
+
+/// Vector register file of 32 registers of VLEN bits
+pub(crate) struct VectorUnit {
+    /// VLEN in bytes
+    vlenb: usize,
+    /// The registers in order, so register groups are contiguous
+    registers: Vec<u8>,
+}
+
+impl VectorUnit {
+    /// Creates a new `VectorUnit`.
+    ///
+    /// # Arguments
+    /// * `vlen` VLEN in bits, validated with `MachineConfig::validate()`
+    pub fn new(vlen: usize) -> Self {
+        let vlenb = vlen / 8;
+        VectorUnit {
+            vlenb,
+            registers: vec![0; vlenb * 32],
+        }
+    }
+
+    /// Returns VLEN in bits
+    pub fn get_vlen(&self) -> usize {
+        self.vlenb * 8
+    }
+
+    /// Returns VLEN in bytes, `vlenb` CSR content
+    pub fn get_vlenb(&self) -> usize {
+        self.vlenb
+    }
+
+    fn get_vlmax(&self, context: &VectorContext) -> usize {
+        scale(self.get_vlen() / context.sew, context.lmul)
+    }
+
+    /// Reads an element of a register group.
+    ///
+    /// # Arguments
+    /// * `reg` The first register of the group
+    /// * `index` Element index in the group
+    /// * `eew` Element width in bits
+    pub fn read_element(&self, reg: usize, index: usize, eew: usize) -> u64 {
+        let bytes = eew / 8;
+        let offset = reg * self.vlenb + index * bytes;
+        let mut data = [0; 8];
+        data[..bytes].copy_from_slice(&self.registers[offset..offset + bytes]);
+        u64::from_le_bytes(data)
+    }
+
+    /// Writes an element of a register group. The value is truncated to
+    /// the element width.
+    ///
+    /// # Arguments
+    /// * `reg` The first register of the group
+    /// * `index` Element index in the group
+    /// * `eew` Element width in bits
+    /// * `value`
+    pub fn write_element(&mut self, reg: usize, index: usize, eew: usize, value: u64) {
+        let bytes = eew / 8;
+        let offset = reg * self.vlenb + index * bytes;
+        self.registers[offset..offset + bytes].copy_from_slice(&value.to_le_bytes()[..bytes]);
+    }
+
+    /// Reads a bit of a mask register.
+    ///
+    /// # Arguments
+    /// * `reg`
+    /// * `index` Element index
+    pub fn read_mask(&self, reg: usize, index: usize) -> bool {
+        (self.registers[reg * self.vlenb + index / 8] >> (index % 8)) & 1 == 1
+    }
+
+    /// Writes a bit of a mask register.
+    ///
+    /// # Arguments
+    /// * `reg`
+    /// * `index` Element index
+    /// * `value`
+    pub fn write_mask(&mut self, reg: usize, index: usize, value: bool) {
+        let byte = &mut self.registers[reg * self.vlenb + index / 8];
+        *byte = (*byte & !(1 << (index % 8))) | ((value as u8) << (index % 8));
+    }
+
+    /// Indicates whether the element is active, unmasked or enabled in `v0`.
+    ///
+    /// # Arguments
+    /// * `vm` Unmasked if true
+    /// * `index` Element index
+    fn is_active(&self, vm: bool, index: usize) -> bool {
+        vm || self.read_mask(0, index)
+    }
+
+    fn read_operand(&self, operand: Operand, index: usize, eew: usize) -> u64 {
+        match operand {
+            Operand::Vector(reg) => self.read_element(reg, index, eew),
+            Operand::Scalar(value) => truncate(value, eew),
+        }
+    }
+
+    /// Checks the register groups of `vd`, `vs2` and the second source
+    /// operand, and returns their EEWs. The destination group may overlap
+    /// a source group only as the spec allows.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `rhs`
+    /// * `vm`
+    /// * `widths`
+    fn check_operands(
+        context: &VectorContext,
+        vd: usize,
+        vs2: usize,
+        rhs: Operand,
+        vm: bool,
+        widths: Widths,
+    ) -> Result<(usize, usize, usize), Trap> {
+        let (vd_eew, vd_emul) = context.get_operand(widths.vd)?;
+        let (vs2_eew, vs2_emul) = context.get_operand(widths.vs2)?;
+        let (vs1_eew, vs1_emul) = context.get_operand(widths.vs1)?;
+        check_group(vd, vd_emul)?;
+        check_group(vs2, vs2_emul)?;
+        check_overlap((vd, vd_eew, vd_emul), (vs2, vs2_eew, vs2_emul))?;
+        if let Operand::Vector(vs1) = rhs {
+            check_group(vs1, vs1_emul)?;
+            check_overlap((vd, vd_eew, vd_emul), (vs1, vs1_eew, vs1_emul))?;
+        }
+        check_mask_overlap(vd, vm)?;
+        Ok((vd_eew, vs2_eew, vs1_eew))
+    }
+
+    /// Performs an element-wise operation, `vd[i] = op(vs2[i], rhs[i])`.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `rhs` `vs1` or the scalar operand
+    /// * `vm` Unmasked if true
+    /// * `widths`
+    /// * `operation`
+    #[allow(clippy::too_many_arguments)]
+    pub fn binary(
+        &mut self,
+        context: &mut VectorContext,
+        vd: usize,
+        vs2: usize,
+        rhs: Operand,
+        vm: bool,
+        widths: Widths,
+        operation: BinaryOperation,
+    ) -> Result<(), Trap> {
+        let (vd_eew, vs2_eew, vs1_eew) = Self::check_operands(context, vd, vs2, rhs, vm, widths)?;
+        for i in context.vstart..context.vl {
+            if self.is_active(vm, i) {
+                let a = self.read_element(vs2, i, vs2_eew);
+                let b = self.read_operand(rhs, i, vs1_eew);
+                let result = operation(context, a, b);
+                self.write_element(vd, i, vd_eew, result);
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs an element-wise operation also taking the destination,
+    /// `vd[i] = op(vs2[i], rhs[i], vd[i])`, like multiply-add.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `rhs` `vs1` or the scalar operand
+    /// * `vm` Unmasked if true
+    /// * `widths`
+    /// * `operation`
+    #[allow(clippy::too_many_arguments)]
+    pub fn ternary(
+        &mut self,
+        context: &mut VectorContext,
+        vd: usize,
+        vs2: usize,
+        rhs: Operand,
+        vm: bool,
+        widths: Widths,
+        operation: TernaryOperation,
+    ) -> Result<(), Trap> {
+        let (vd_eew, vs2_eew, vs1_eew) = Self::check_operands(context, vd, vs2, rhs, vm, widths)?;
+        for i in context.vstart..context.vl {
+            if self.is_active(vm, i) {
+                let a = self.read_element(vs2, i, vs2_eew);
+                let b = self.read_operand(rhs, i, vs1_eew);
+                let d = self.read_element(vd, i, vd_eew);
+                let result = operation(context, a, b, d);
+                self.write_element(vd, i, vd_eew, result);
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs an element-wise unary operation, `vd[i] = op(vs2[i])`.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `vm` Unmasked if true
+    /// * `widths`
+    /// * `operation`
+    pub fn unary(
+        &mut self,
+        context: &mut VectorContext,
+        vd: usize,
+        vs2: usize,
+        vm: bool,
+        widths: Widths,
+        operation: UnaryOperation,
+    ) -> Result<(), Trap> {
+        let (vd_eew, vs2_eew, _) =
+            Self::check_operands(context, vd, vs2, Operand::Scalar(0), vm, widths)?;
+        // Integer extension reads narrower elements, it can't overlap
+        if widths.vs2 < 0 && overlaps(vd, context.lmul, vs2, context.lmul + widths.vs2) {
+            return Err(illegal_instruction());
+        }
+        for i in context.vstart..context.vl {
+            if self.is_active(vm, i) {
+                let a = self.read_element(vs2, i, vs2_eew);
+                let result = operation(context, a);
+                self.write_element(vd, i, vd_eew, result);
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs an element-wise comparison writing the mask register `vd`.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `rhs` `vs1` or the scalar operand
+    /// * `vm` Unmasked if true
+    /// * `operation`
+    pub fn compare(
+        &mut self,
+        context: &mut VectorContext,
+        vd: usize,
+        vs2: usize,
+        rhs: Operand,
+        vm: bool,
+        operation: CompareOperation,
+    ) -> Result<(), Trap> {
+        self.check_mask_destination(context, vd, vs2, rhs)?;
+        for i in context.vstart..context.vl {
+            if self.is_active(vm, i) {
+                let a = self.read_element(vs2, i, context.sew);
+                let b = self.read_operand(rhs, i, context.sew);
+                let result = operation(context, a, b);
+                self.write_mask(vd, i, result);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the operands of an instruction writing the mask register `vd`
+    /// from SEW wide sources. `vd` may overlap only the first register of
+    /// a source group.
+    fn check_mask_destination(
+        &self,
+        context: &VectorContext,
+        vd: usize,
+        vs2: usize,
+        rhs: Operand,
+    ) -> Result<(), Trap> {
+        let (_, emul) = context.get_operand(0)?;
+        check_group(vd, 0)?;
+        check_group(vs2, emul)?;
+        let mut sources = vec![vs2];
+        if let Operand::Vector(vs1) = rhs {
+            check_group(vs1, emul)?;
+            sources.push(vs1);
+        }
+        for source in sources {
+            if vd != source && overlaps(vd, 0, source, emul) {
+                return Err(illegal_instruction());
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs `VADC` and `VSBC`, adding or subtracting with the carry
+    /// or borrow in `v0`. All the body elements are written.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `rhs` `vs1` or the scalar operand
+    /// * `operation`
+    pub fn carry(
+        &mut self,
+        context: &mut VectorContext,
+        vd: usize,
+        vs2: usize,
+        rhs: Operand,
+        operation: CarryOperation,
+    ) -> Result<(), Trap> {
+        Self::check_operands(context, vd, vs2, rhs, false, SINGLE_WIDTH)?;
+        for i in context.vstart..context.vl {
+            let a = self.read_element(vs2, i, context.sew);
+            let b = self.read_operand(rhs, i, context.sew);
+            let carry = self.read_mask(0, i);
+            let result = operation(context, a, b, carry);
+            self.write_element(vd, i, context.sew, result);
+        }
+        Ok(())
+    }
+
+    /// Performs `VMADC` and `VMSBC`, writing the carry or borrow out to
+    /// the mask register `vd`. All the body elements are written.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `rhs` `vs1` or the scalar operand
+    /// * `vm` Takes the carry or borrow in `v0` if false
+    /// * `operation`
+    pub fn carry_out(
+        &mut self,
+        context: &mut VectorContext,
+        vd: usize,
+        vs2: usize,
+        rhs: Operand,
+        vm: bool,
+        operation: CarryOutOperation,
+    ) -> Result<(), Trap> {
+        self.check_mask_destination(context, vd, vs2, rhs)?;
+        for i in context.vstart..context.vl {
+            let a = self.read_element(vs2, i, context.sew);
+            let b = self.read_operand(rhs, i, context.sew);
+            let carry = !vm && self.read_mask(0, i);
+            let result = operation(context, a, b, carry);
+            self.write_mask(vd, i, result);
+        }
+        Ok(())
+    }
+
+    /// Performs `VMERGE` and `VMV.V`. Takes `rhs` for the elements enabled
+    /// in `v0` and `vs2` for the others, or `rhs` for all the elements
+    /// if unmasked.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `rhs` `vs1` or the scalar operand
+    /// * `vm` `VMV.V` if true
+    pub fn merge(
+        &mut self,
+        context: &mut VectorContext,
+        vd: usize,
+        vs2: usize,
+        rhs: Operand,
+        vm: bool,
+    ) -> Result<(), Trap> {
+        if vm && vs2 != 0 {
+            return Err(illegal_instruction());
+        }
+        Self::check_operands(context, vd, vs2, rhs, vm, SINGLE_WIDTH)?;
+        for i in context.vstart..context.vl {
+            let value = match self.is_active(vm, i) {
+                true => self.read_operand(rhs, i, context.sew),
+                false => self.read_element(vs2, i, context.sew),
+            };
+            self.write_element(vd, i, context.sew, value);
+        }
+        Ok(())
+    }
+
+    /// Performs a reduction, `vd[0] = op(... op(op(vs1[0], vs2[0]), vs2[1]) ...)`
+    /// over the active elements, in element order.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `vs1`
+    /// * `vm` Unmasked if true
+    /// * `widening` The accumulator and the result are 2*SEW wide if true
+    /// * `operation` Takes the accumulator and an element of `vs2`
+    #[allow(clippy::too_many_arguments)]
+    pub fn reduce(
+        &mut self,
+        context: &mut VectorContext,
+        vd: usize,
+        vs2: usize,
+        vs1: usize,
+        vm: bool,
+        widening: bool,
+        operation: BinaryOperation,
+    ) -> Result<(), Trap> {
+        context.check_vstart()?;
+        let (_, emul) = context.get_operand(0)?;
+        let (accumulator_eew, _) = context.get_operand(widening as i32)?;
+        check_group(vs2, emul)?;
+        if context.vl == 0 {
+            return Ok(());
+        }
+        let mut accumulator = self.read_element(vs1, 0, accumulator_eew);
+        for i in 0..context.vl {
+            if self.is_active(vm, i) {
+                let element = self.read_element(vs2, i, context.sew);
+                accumulator = operation(context, accumulator, element);
+            }
+        }
+        self.write_element(vd, 0, accumulator_eew, accumulator);
+        Ok(())
+    }
+
+    /// Performs a mask-register logical operation, `VMAND.MM` and the like.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `vs1`
+    /// * `operation`
+    pub fn mask_logical(
+        &mut self,
+        context: &mut VectorContext,
+        vd: usize,
+        vs2: usize,
+        vs1: usize,
+        operation: fn(bool, bool) -> bool,
+    ) -> Result<(), Trap> {
+        for i in context.vstart..context.vl {
+            let result = operation(self.read_mask(vs2, i), self.read_mask(vs1, i));
+            self.write_mask(vd, i, result);
+        }
+        Ok(())
+    }
+
+    /// Performs `VCPOP.M`, returns the number of the active elements set
+    /// in the mask register `vs2`.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vs2`
+    /// * `vm` Unmasked if true
+    pub fn count_population(
+        &self,
+        context: &VectorContext,
+        vs2: usize,
+        vm: bool,
+    ) -> Result<u64, Trap> {
+        context.check_vstart()?;
+        Ok((0..context.vl)
+            .filter(|i| self.is_active(vm, *i) && self.read_mask(vs2, *i))
+            .count() as u64)
+    }
+
+    /// Performs `VFIRST.M`, returns the index of the first active element
+    /// set in the mask register `vs2`, or -1.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vs2`
+    /// * `vm` Unmasked if true
+    pub fn find_first(&self, context: &VectorContext, vs2: usize, vm: bool) -> Result<i64, Trap> {
+        context.check_vstart()?;
+        Ok((0..context.vl)
+            .find(|i| self.is_active(vm, *i) && self.read_mask(vs2, *i))
+            .map_or(-1, |i| i as i64))
+    }
+
+    /// Performs `VMSBF.M`, `VMSIF.M` and `VMSOF.M`. Sets the active mask
+    /// bits before the first active element set in `vs2`, and the one
+    /// at the element if `including`. `VMSOF.M` sets only the one.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `vm` Unmasked if true
+    /// * `before` Sets the bits before the first set element if true
+    /// * `including` Sets the bit of the first set element if true
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_first(
+        &mut self,
+        context: &VectorContext,
+        vd: usize,
+        vs2: usize,
+        vm: bool,
+        before: bool,
+        including: bool,
+    ) -> Result<(), Trap> {
+        context.check_vstart()?;
+        if vd == vs2 || (!vm && vd == 0) {
+            return Err(illegal_instruction());
+        }
+        let mut found = false;
+        for i in 0..context.vl {
+            if self.is_active(vm, i) {
+                let value = match (found, self.read_mask(vs2, i)) {
+                    (true, _) => false,
+                    (false, true) => {
+                        found = true;
+                        including
+                    }
+                    (false, false) => before,
+                };
+                self.write_mask(vd, i, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs `VIOTA.M`, writes the number of the active elements set in
+    /// the mask register `vs2` preceding each element.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `vm` Unmasked if true
+    pub fn iota(
+        &mut self,
+        context: &VectorContext,
+        vd: usize,
+        vs2: usize,
+        vm: bool,
+    ) -> Result<(), Trap> {
+        context.check_vstart()?;
+        let (_, emul) = context.get_operand(0)?;
+        check_group(vd, emul)?;
+        check_mask_overlap(vd, vm)?;
+        if overlaps(vd, emul, vs2, 0) {
+            return Err(illegal_instruction());
+        }
+        let mut count = 0;
+        for i in 0..context.vl {
+            if self.is_active(vm, i) {
+                self.write_element(vd, i, context.sew, count);
+                if self.read_mask(vs2, i) {
+                    count += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs `VID.V`, writes the index of each element.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vm` Unmasked if true
+    pub fn element_index(
+        &mut self,
+        context: &VectorContext,
+        vd: usize,
+        vm: bool,
+    ) -> Result<(), Trap> {
+        let (_, emul) = context.get_operand(0)?;
+        check_group(vd, emul)?;
+        check_mask_overlap(vd, vm)?;
+        for i in context.vstart..context.vl {
+            if self.is_active(vm, i) {
+                self.write_element(vd, i, context.sew, i as u64);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the scalar operand to the first element of `vd`,
+    /// `VMV.S.X` and `VFMV.S.F`. Nothing is written if `vstart` >= `vl`.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `value`
+    pub fn move_from_scalar(&mut self, context: &VectorContext, vd: usize, value: u64) {
+        if context.vstart < context.vl {
+            self.write_element(vd, 0, context.sew, value);
+        }
+    }
+
+    /// Performs `VSLIDEUP`, `vd[i + offset] = vs2[i]`. The elements
+    /// below the offset are unchanged.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `offset`
+    /// * `vm` Unmasked if true
+    pub fn slide_up(
+        &mut self,
+        context: &VectorContext,
+        vd: usize,
+        vs2: usize,
+        offset: u64,
+        vm: bool,
+    ) -> Result<(), Trap> {
+        self.check_permutation(context, vd, vs2, vm)?;
+        let start = context.vstart.max(offset.min(context.vl as u64) as usize);
+        for i in start..context.vl {
+            if self.is_active(vm, i) {
+                let value = self.read_element(vs2, i - offset as usize, context.sew);
+                self.write_element(vd, i, context.sew, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs `VSLIDEDOWN`, `vd[i] = vs2[i + offset]`. The elements past
+    /// VLMAX read as zero.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `offset`
+    /// * `vm` Unmasked if true
+    pub fn slide_down(
+        &mut self,
+        context: &VectorContext,
+        vd: usize,
+        vs2: usize,
+        offset: u64,
+        vm: bool,
+    ) -> Result<(), Trap> {
+        let (_, emul) = context.get_operand(0)?;
+        check_group(vd, emul)?;
+        check_group(vs2, emul)?;
+        check_mask_overlap(vd, vm)?;
+        let vlmax = self.get_vlmax(context) as u64;
+        for i in context.vstart..context.vl {
+            if self.is_active(vm, i) {
+                let value = match (i as u64).checked_add(offset) {
+                    Some(index) if index < vlmax => {
+                        self.read_element(vs2, index as usize, context.sew)
+                    }
+                    _ => 0,
+                };
+                self.write_element(vd, i, context.sew, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs `VSLIDE1UP` and `VFSLIDE1UP`, slides up by one element and
+    /// writes the scalar operand to the first element.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `value`
+    /// * `vm` Unmasked if true
+    pub fn slide1_up(
+        &mut self,
+        context: &VectorContext,
+        vd: usize,
+        vs2: usize,
+        value: u64,
+        vm: bool,
+    ) -> Result<(), Trap> {
+        self.check_permutation(context, vd, vs2, vm)?;
+        for i in context.vstart..context.vl {
+            if self.is_active(vm, i) {
+                let value = match i {
+                    0 => value,
+                    _ => self.read_element(vs2, i - 1, context.sew),
+                };
+                self.write_element(vd, i, context.sew, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs `VSLIDE1DOWN` and `VFSLIDE1DOWN`, slides down by one element
+    /// and writes the scalar operand to the last body element.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `value`
+    /// * `vm` Unmasked if true
+    pub fn slide1_down(
+        &mut self,
+        context: &VectorContext,
+        vd: usize,
+        vs2: usize,
+        value: u64,
+        vm: bool,
+    ) -> Result<(), Trap> {
+        let (_, emul) = context.get_operand(0)?;
+        check_group(vd, emul)?;
+        check_group(vs2, emul)?;
+        check_mask_overlap(vd, vm)?;
+        for i in context.vstart..context.vl {
+            if self.is_active(vm, i) {
+                let value = match i + 1 == context.vl {
+                    true => value,
+                    false => self.read_element(vs2, i + 1, context.sew),
+                };
+                self.write_element(vd, i, context.sew, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the operands of the permutation instructions whose
+    /// destination can't overlap the source.
+    fn check_permutation(
+        &self,
+        context: &VectorContext,
+        vd: usize,
+        vs2: usize,
+        vm: bool,
+    ) -> Result<(), Trap> {
+        let (_, emul) = context.get_operand(0)?;
+        check_group(vd, emul)?;
+        check_group(vs2, emul)?;
+        check_mask_overlap(vd, vm)?;
+        match overlaps(vd, emul, vs2, emul) {
+            true => Err(illegal_instruction()),
+            false => Ok(()),
+        }
+    }
+
+    /// Performs `VRGATHER` and `VRGATHEREI16`, `vd[i] = vs2[index[i]]`.
+    /// The indices past VLMAX read as zero.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `index` `vs1` or the scalar index
+    /// * `index_eew` 16 for `VRGATHEREI16`, otherwise SEW
+    /// * `vm` Unmasked if true
+    pub fn gather(
+        &mut self,
+        context: &VectorContext,
+        vd: usize,
+        vs2: usize,
+        index: Operand,
+        index_eew: usize,
+        vm: bool,
+    ) -> Result<(), Trap> {
+        self.check_permutation(context, vd, vs2, vm)?;
+        if let Operand::Vector(vs1) = index {
+            let index_emul = context.lmul + log2(index_eew) - log2(context.sew);
+            if !(-3..=3).contains(&index_emul) {
+                return Err(illegal_instruction());
+            }
+            check_group(vs1, index_emul)?;
+            if overlaps(vd, context.lmul, vs1, index_emul) {
+                return Err(illegal_instruction());
+            }
+        }
+        let vlmax = self.get_vlmax(context) as u64;
+        for i in context.vstart..context.vl {
+            if self.is_active(vm, i) {
+                // The scalar index isn't truncated to SEW
+                let index = match index {
+                    Operand::Vector(vs1) => self.read_element(vs1, i, index_eew),
+                    Operand::Scalar(index) => index,
+                };
+                let value = match index < vlmax {
+                    true => self.read_element(vs2, index as usize, context.sew),
+                    false => 0,
+                };
+                self.write_element(vd, i, context.sew, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs `VCOMPRESS.VM`, packs the elements of `vs2` enabled in
+    /// the mask register `vs1` into the beginning of `vd`.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd`
+    /// * `vs2`
+    /// * `vs1`
+    pub fn compress(
+        &mut self,
+        context: &VectorContext,
+        vd: usize,
+        vs2: usize,
+        vs1: usize,
+    ) -> Result<(), Trap> {
+        context.check_vstart()?;
+        self.check_permutation(context, vd, vs2, true)?;
+        if overlaps(vd, context.lmul, vs1, 0) {
+            return Err(illegal_instruction());
+        }
+        let mut count = 0;
+        for i in 0..context.vl {
+            if self.read_mask(vs1, i) {
+                let value = self.read_element(vs2, i, context.sew);
+                self.write_element(vd, count, context.sew, value);
+                count += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs `VMV<nr>R.V`, copies whole registers.
+    ///
+    /// # Arguments
+    /// * `context` SEW is the element width `vstart` counts
+    /// * `vd`
+    /// * `vs2`
+    /// * `count` The number of registers, 1, 2, 4 or 8
+    pub fn move_whole_register(
+        &mut self,
+        context: &VectorContext,
+        vd: usize,
+        vs2: usize,
+        count: usize,
+    ) -> Result<(), Trap> {
+        if !matches!(count, 1 | 2 | 4 | 8)
+            || !vd.is_multiple_of(count)
+            || !vs2.is_multiple_of(count)
+        {
+            return Err(illegal_instruction());
+        }
+        let length = count * self.vlenb / (context.sew / 8);
+        for i in context.vstart..length {
+            let value = self.read_element(vs2, i, context.sew);
+            self.write_element(vd, i, context.sew, value);
+        }
+        Ok(())
+    }
+
+    /// Returns EEW and base 2 logarithm of EMUL of the data elements of
+    /// a load or store, after checking the register groups.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `vd` `vd` of loads, `vs3` of stores
+    /// * `addressing`
+    /// * `nf` The number of fields in a segment, 1 to 8
+    fn check_memory_operands(
+        context: &VectorContext,
+        vd: usize,
+        addressing: Addressing,
+        nf: usize,
+    ) -> Result<(usize, i32), Trap> {
+        let (eew, emul) = match addressing {
+            Addressing::UnitStride { eew } | Addressing::Strided { eew, .. } => {
+                (eew, context.lmul + log2(eew) - log2(context.sew))
+            }
+            Addressing::Indexed { vs2, eew } => {
+                let index_emul = context.lmul + log2(eew) - log2(context.sew);
+                if !(-3..=3).contains(&index_emul) {
+                    return Err(illegal_instruction());
+                }
+                check_group(vs2, index_emul)?;
+                (context.sew, context.lmul)
+            }
+        };
+        let group_size = get_group_size(emul);
+        if !(-3..=3).contains(&emul) || group_size * nf > 8 || vd + group_size * nf > 32 {
+            return Err(illegal_instruction());
+        }
+        check_group(vd, emul)?;
+        Ok((eew, emul))
+    }
+
+    /// Returns the address of a field of a segment.
+    ///
+    /// # Arguments
+    /// * `base` Base address in `rs1`
+    /// * `addressing`
+    /// * `eew` Data element width
+    /// * `nf` The number of fields in a segment
+    /// * `index` Segment index
+    /// * `field`
+    fn get_element_address(
+        &self,
+        base: u64,
+        addressing: Addressing,
+        eew: usize,
+        nf: usize,
+        index: usize,
+        field: usize,
+    ) -> u64 {
+        let bytes = (eew / 8) as u64;
+        let offset = match addressing {
+            Addressing::UnitStride { .. } => (index * nf) as u64 * bytes,
+            Addressing::Strided { stride, .. } => (index as u64).wrapping_mul(stride),
+            Addressing::Indexed { vs2, eew } => self.read_element(vs2, index, eew),
+        };
+        base.wrapping_add(offset).wrapping_add(field as u64 * bytes)
+    }
+
+    /// Performs a vector load, segment loads included. If an element raises
+    /// an exception, `vstart` of the context is set to its index. Fault-only-
+    /// first loads trim `vl` instead unless the first element raises.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `mmu`
+    /// * `vd`
+    /// * `base` Base address in `rs1`
+    /// * `addressing`
+    /// * `nf` The number of fields in a segment, 1 to 8
+    /// * `vm` Unmasked if true
+    /// * `fault_only_first`
+    #[allow(clippy::too_many_arguments)]
+    pub fn load(
+        &mut self,
+        context: &mut VectorContext,
+        mmu: &mut Mmu,
+        vd: usize,
+        base: u64,
+        addressing: Addressing,
+        nf: usize,
+        vm: bool,
+        fault_only_first: bool,
+    ) -> Result<(), Trap> {
+        let (eew, emul) = Self::check_memory_operands(context, vd, addressing, nf)?;
+        check_mask_overlap(vd, vm)?;
+        if let Addressing::Indexed { vs2, eew } = addressing {
+            let index_emul = context.lmul + log2(eew) - log2(context.sew);
+            let source = (vs2, eew, index_emul);
+            for field in 0..nf {
+                let destination = (vd + field * get_group_size(emul), context.sew, emul);
+                check_overlap(destination, source)?;
+            }
+        }
+        for i in context.vstart..context.vl {
+            if !self.is_active(vm, i) {
+                continue;
+            }
+            for field in 0..nf {
+                let address = self.get_element_address(base, addressing, eew, nf, i, field);
+                match load_element(mmu, address, eew) {
+                    Ok(value) => {
+                        self.write_element(vd + field * get_group_size(emul), i, eew, value)
+                    }
+                    Err(_trap) if fault_only_first && i > 0 => {
+                        context.vl = i;
+                        return Ok(());
+                    }
+                    Err(trap) => {
+                        context.vstart = i;
+                        return Err(trap);
+                    }
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs a vector store, segment stores included. If an element
+    /// raises an exception, `vstart` of the context is set to its index.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `mmu`
+    /// * `vs3`
+    /// * `base` Base address in `rs1`
+    /// * `addressing`
+    /// * `nf` The number of fields in a segment, 1 to 8
+    /// * `vm` Unmasked if true
+    #[allow(clippy::too_many_arguments)]
+    pub fn store(
+        &self,
+        context: &mut VectorContext,
+        mmu: &mut Mmu,
+        vs3: usize,
+        base: u64,
+        addressing: Addressing,
+        nf: usize,
+        vm: bool,
+    ) -> Result<(), Trap> {
+        let (eew, emul) = Self::check_memory_operands(context, vs3, addressing, nf)?;
+        for i in context.vstart..context.vl {
+            if !self.is_active(vm, i) {
+                continue;
+            }
+            for field in 0..nf {
+                let address = self.get_element_address(base, addressing, eew, nf, i, field);
+                let value = self.read_element(vs3 + field * get_group_size(emul), i, eew);
+                if let Err(trap) = store_element(mmu, address, eew, value) {
+                    context.vstart = i;
+                    return Err(trap);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs `VLM.V`, loads `ceil(vl / 8)` bytes to the mask register.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `mmu`
+    /// * `vd`
+    /// * `base` Base address in `rs1`
+    pub fn load_mask(
+        &mut self,
+        context: &mut VectorContext,
+        mmu: &mut Mmu,
+        vd: usize,
+        base: u64,
+    ) -> Result<(), Trap> {
+        for i in context.vstart..context.vl.div_ceil(8) {
+            match mmu.load(base.wrapping_add(i as u64)) {
+                Ok(value) => self.write_element(vd, i, 8, value as u64),
+                Err(trap) => {
+                    context.vstart = i;
+                    return Err(trap);
+                }
+            };
+        }
+        Ok(())
+    }
+
+    /// Performs `VSM.V`, stores `ceil(vl / 8)` bytes of the mask register.
+    ///
+    /// # Arguments
+    /// * `context`
+    /// * `mmu`
+    /// * `vs3`
+    /// * `base` Base address in `rs1`
+    pub fn store_mask(
+        &self,
+        context: &mut VectorContext,
+        mmu: &mut Mmu,
+        vs3: usize,
+        base: u64,
+    ) -> Result<(), Trap> {
+        for i in context.vstart..context.vl.div_ceil(8) {
+            let value = self.read_element(vs3, i, 8) as u8;
+            if let Err(trap) = mmu.store(base.wrapping_add(i as u64), value) {
+                context.vstart = i;
+                return Err(trap);
+            }
+        }
+        Ok(())
+    }
+
+    /// Performs the whole register loads, `VL<nr>RE<eew>.V`, independent
+    /// of `vtype` and `vl`.
+    ///
+    /// # Arguments
+    /// * `context` Only `vstart` is used
+    /// * `mmu`
+    /// * `vd`
+    /// * `base` Base address in `rs1`
+    /// * `eew`
+    /// * `count` The number of registers, 1, 2, 4 or 8
+    pub fn load_whole_register(
+        &mut self,
+        context: &mut VectorContext,
+        mmu: &mut Mmu,
+        vd: usize,
+        base: u64,
+        eew: usize,
+        count: usize,
+    ) -> Result<(), Trap> {
+        if !matches!(count, 1 | 2 | 4 | 8) || !vd.is_multiple_of(count) {
+            return Err(illegal_instruction());
+        }
+        let bytes = eew / 8;
+        for i in context.vstart..count * self.vlenb / bytes {
+            match load_element(mmu, base.wrapping_add((i * bytes) as u64), eew) {
+                Ok(value) => self.write_element(vd, i, eew, value),
+                Err(trap) => {
+                    context.vstart = i;
+                    return Err(trap);
+                }
+            };
+        }
+        Ok(())
+    }
+
+    /// Performs the whole register stores, `VS<nr>R.V`, independent
+    /// of `vtype` and `vl`.
+    ///
+    /// # Arguments
+    /// * `context` Only `vstart` is used
+    /// * `mmu`
+    /// * `vs3`
+    /// * `base` Base address in `rs1`
+    /// * `count` The number of registers, 1, 2, 4 or 8
+    pub fn store_whole_register(
+        &self,
+        context: &mut VectorContext,
+        mmu: &mut Mmu,
+        vs3: usize,
+        base: u64,
+        count: usize,
+    ) -> Result<(), Trap> {
+        if !matches!(count, 1 | 2 | 4 | 8) || !vs3.is_multiple_of(count) {
+            return Err(illegal_instruction());
+        }
+        for i in context.vstart..count * self.vlenb {
+            let value = self.registers[vs3 * self.vlenb + i];
+            if let Err(trap) = mmu.store(base.wrapping_add(i as u64), value) {
+                context.vstart = i;
+                return Err(trap);
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves the register file to a snapshot.
+    pub(crate) fn save_state(&self, writer: &mut SnapshotWriter) {
+        writer.write_u64(self.vlenb as u64);
+        writer.write_bytes(&self.registers);
+    }
+
+    /// Restores the register file saved with `save_state()`. VLEN must be
+    /// the same.
+    pub(crate) fn restore_state(
+        &mut self,
+        reader: &mut SnapshotReader,
+    ) -> Result<(), EmulatorError> {
+        if reader.read_u64()? != self.vlenb as u64 {
+            return Err(EmulatorError::SnapshotMismatch);
+        }
+        let registers = reader.read_bytes()?;
+        if registers.len() != self.registers.len() {
+            return Err(EmulatorError::InvalidSnapshot);
+        }
+        self.registers.copy_from_slice(registers);
+        Ok(())
+    }
+}
+
+fn illegal_instruction() -> Trap {
+    Trap {
+        trap_type: TrapType::IllegalInstruction,
+        value: 0,
+    }
+}
+
+/// Returns `value * 2^log`
+fn scale(value: usize, log: i32) -> usize {
+    match log >= 0 {
+        true => value << log,
+        false => value >> -log,
+    }
+}
+
+fn log2(value: usize) -> i32 {
+    value.trailing_zeros() as i32
+}
+
+/// Returns the number of registers in a group, EMUL rounded up to one.
+fn get_group_size(emul: i32) -> usize {
+    1 << emul.max(0)
+}
+
+/// Raises an illegal instruction exception unless the register number
+/// is aligned to the group size.
+fn check_group(reg: usize, emul: i32) -> Result<(), Trap> {
+    match reg % get_group_size(emul) {
+        0 => Ok(()),
+        _ => Err(illegal_instruction()),
+    }
+}
+
+/// Indicates whether the two register groups share a register.
+fn overlaps(reg1: usize, emul1: i32, reg2: usize, emul2: i32) -> bool {
+    reg1 < reg2 + get_group_size(emul2) && reg2 < reg1 + get_group_size(emul1)
+}
+
+/// Raises an illegal instruction exception if the destination group
+/// overlaps the source group while the spec doesn't allow. The groups are
+/// register number, EEW and base 2 logarithm of EMUL. Wider destination may
+/// overlap only with its highest-numbered part and narrower destination
+/// only with the lowest-numbered part of the source.
+fn check_overlap(
+    destination: (usize, usize, i32),
+    source: (usize, usize, i32),
+) -> Result<(), Trap> {
+    let (vd, vd_eew, vd_emul) = destination;
+    let (vs, vs_eew, vs_emul) = source;
+    if !overlaps(vd, vd_emul, vs, vs_emul) || vd_eew == vs_eew {
+        return Ok(());
+    }
+    let allowed = match vd_eew > vs_eew {
+        true => vs_emul >= 0 && vs + get_group_size(vs_emul) == vd + get_group_size(vd_emul),
+        false => vd == vs,
+    };
+    match allowed {
+        true => Ok(()),
+        false => Err(illegal_instruction()),
+    }
+}
+
+/// Raises an illegal instruction exception if a masked instruction
+/// overwrites the mask in `v0`.
+fn check_mask_overlap(vd: usize, vm: bool) -> Result<(), Trap> {
+    match !vm && vd == 0 {
+        true => Err(illegal_instruction()),
+        false => Ok(()),
+    }
+}
+
+fn load_element(mmu: &mut Mmu, address: u64, eew: usize) -> Result<u64, Trap> {
+    Ok(match eew {
+        8 => mmu.load(address)? as u64,
+        16 => mmu.load_halfword(address)? as u64,
+        32 => mmu.load_word(address)? as u64,
+        _ => mmu.load_doubleword(address)?,
+    })
+}
+
+fn store_element(mmu: &mut Mmu, address: u64, eew: usize, value: u64) -> Result<(), Trap> {
+    match eew {
+        8 => mmu.store(address, value as u8),
+        16 => mmu.store_halfword(address, value as u16),
+        32 => mmu.store_word(address, value as u32),
+        _ => mmu.store_doubleword(address, value),
+    }
+}
+
+/// Returns the lower bits of the value.
+///
+/// # Arguments
+/// * `value`
+/// * `bits` 1 to 64
+pub(crate) fn truncate(value: u64, bits: usize) -> u64 {
+    match bits {
+        64 => value,
+        _ => value & ((1 << bits) - 1),
+    }
+}
+
+/// Sign-extends the lower bits of the value.
+///
+/// # Arguments
+/// * `value`
+/// * `bits` 1 to 64
+pub(crate) fn sign_extend(value: u64, bits: usize) -> i64 {
+    ((value << (64 - bits)) as i64) >> (64 - bits)
+}
+
+/// Returns `value >> shift` rounded with the fixed-point rounding mode.
+///
+/// # Arguments
+/// * `value` Two's complement value
+/// * `shift`
+/// * `vxrm` Round-to-nearest-up, round-to-nearest-even, round-down or
+///   round-to-odd
+fn rounding_shift(value: i128, shift: usize, vxrm: u64) -> i128 {
+    if shift == 0 {
+        return value;
+    }
+    let bit = |n: usize| (value >> n) & 1;
+    let lower = value & ((1 << shift) - 1);
+    let increment = match vxrm {
+        0 => bit(shift - 1),
+        1 => {
+            (bit(shift - 1) == 1 && (lower & ((1 << (shift - 1)) - 1) != 0 || bit(shift) == 1))
+                as i128
+        }
+        2 => 0,
+        _ => (bit(shift) == 0 && lower != 0) as i128,
+    };
+    (value >> shift) + increment
+}
+
+/// Clamps the value to the unsigned range of the bits, flagging saturation.
+fn saturate_unsigned(context: &mut VectorContext, value: i128, bits: usize) -> u64 {
+    let max = (1i128 << bits) - 1;
+    match value {
+        _ if value > max => {
+            context.saturated = true;
+            max as u64
+        }
+        _ if value < 0 => {
+            context.saturated = true;
+            0
+        }
+        _ => value as u64,
+    }
+}
+
+/// Clamps the value to the signed range of the bits, flagging saturation.
+fn saturate_signed(context: &mut VectorContext, value: i128, bits: usize) -> u64 {
+    let max = (1i128 << (bits - 1)) - 1;
+    let min = -(1i128 << (bits - 1));
+    match value {
+        _ if value > max => {
+            context.saturated = true;
+            max as u64
+        }
+        _ if value < min => {
+            context.saturated = true;
+            min as u64
+        }
+        _ => value as u64,
+    }
+}
+
+fn signed(context: &VectorContext, value: u64) -> i64 {
+    sign_extend(value, context.sew)
+}
+
+fn signed_wide(context: &VectorContext, value: u64) -> i64 {
+    sign_extend(value, context.sew * 2)
+}
+
+// Integer operations. Operands are `vs2` and `vs1` or the scalar operand
+// in this order, zero-extended from their element widths.
+
+pub(crate) fn add(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a.wrapping_add(b)
+}
+
+pub(crate) fn sub(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a.wrapping_sub(b)
+}
+
+pub(crate) fn reverse_sub(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    b.wrapping_sub(a)
+}
+
+pub(crate) fn min_unsigned(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a.min(b)
+}
+
+pub(crate) fn min(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    signed(context, a).min(signed(context, b)) as u64
+}
+
+pub(crate) fn max_unsigned(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a.max(b)
+}
+
+pub(crate) fn max(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    signed(context, a).max(signed(context, b)) as u64
+}
+
+pub(crate) fn and(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a & b
+}
+
+pub(crate) fn or(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a | b
+}
+
+pub(crate) fn xor(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a ^ b
+}
+
+pub(crate) fn shift_left(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a << (b as usize & (context.sew - 1))
+}
+
+pub(crate) fn shift_right_logical(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a >> (b as usize & (context.sew - 1))
+}
+
+pub(crate) fn shift_right_arithmetic(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    (signed(context, a) >> (b as usize & (context.sew - 1))) as u64
+}
+
+pub(crate) fn narrowing_shift_right_logical(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a >> (b as usize & (context.sew * 2 - 1))
+}
+
+pub(crate) fn narrowing_shift_right_arithmetic(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    (signed_wide(context, a) >> (b as usize & (context.sew * 2 - 1))) as u64
+}
+
+pub(crate) fn multiply(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a.wrapping_mul(b)
+}
+
+pub(crate) fn multiply_high(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let product = signed(context, a) as i128 * signed(context, b) as i128;
+    (product >> context.sew) as u64
+}
+
+pub(crate) fn multiply_high_unsigned(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    ((a as u128 * b as u128) >> context.sew) as u64
+}
+
+pub(crate) fn multiply_high_signed_unsigned(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let product = signed(context, a) as i128 * b as i128;
+    (product >> context.sew) as u64
+}
+
+pub(crate) fn divide_unsigned(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    match b {
+        0 => u64::MAX,
+        _ => a / b,
+    }
+}
+
+pub(crate) fn divide(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    match signed(context, b) {
+        0 => u64::MAX,
+        // The overflow wraps to the most negative value of SEW
+        divisor => signed(context, a).wrapping_div(divisor) as u64,
+    }
+}
+
+pub(crate) fn remainder_unsigned(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    match b {
+        0 => a,
+        _ => a % b,
+    }
+}
+
+pub(crate) fn remainder(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    match signed(context, b) {
+        0 => a,
+        divisor => signed(context, a).wrapping_rem(divisor) as u64,
+    }
+}
+
+pub(crate) fn widening_add_unsigned(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a + b
+}
+
+pub(crate) fn widening_add(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    (signed(context, a) + signed(context, b)) as u64
+}
+
+pub(crate) fn widening_sub_unsigned(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a.wrapping_sub(b)
+}
+
+pub(crate) fn widening_sub(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    (signed(context, a) - signed(context, b)) as u64
+}
+
+pub(crate) fn wide_add_unsigned(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a.wrapping_add(b)
+}
+
+pub(crate) fn wide_add(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    signed_wide(context, a).wrapping_add(signed(context, b)) as u64
+}
+
+pub(crate) fn wide_sub_unsigned(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a.wrapping_sub(b)
+}
+
+pub(crate) fn wide_sub(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    signed_wide(context, a).wrapping_sub(signed(context, b)) as u64
+}
+
+pub(crate) fn widening_multiply_unsigned(_context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    a * b
+}
+
+pub(crate) fn widening_multiply(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    (signed(context, a) * signed(context, b)) as u64
+}
+
+pub(crate) fn widening_multiply_signed_unsigned(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+) -> u64 {
+    (signed(context, a) * b as i64) as u64
+}
+
+pub(crate) fn add_with_carry(_context: &mut VectorContext, a: u64, b: u64, carry: bool) -> u64 {
+    a.wrapping_add(b).wrapping_add(carry as u64)
+}
+
+pub(crate) fn sub_with_borrow(_context: &mut VectorContext, a: u64, b: u64, borrow: bool) -> u64 {
+    a.wrapping_sub(b).wrapping_sub(borrow as u64)
+}
+
+pub(crate) fn carry_out(context: &mut VectorContext, a: u64, b: u64, carry: bool) -> bool {
+    (a as u128 + b as u128 + carry as u128) >> context.sew != 0
+}
+
+pub(crate) fn borrow_out(_context: &mut VectorContext, a: u64, b: u64, borrow: bool) -> bool {
+    (a as u128) < b as u128 + borrow as u128
+}
+
+// Multiply-add operations. Operands are `vs2`, `vs1` or the scalar operand,
+// and `vd` in this order.
+
+pub(crate) fn multiply_accumulate(_context: &mut VectorContext, a: u64, b: u64, d: u64) -> u64 {
+    b.wrapping_mul(a).wrapping_add(d)
+}
+
+pub(crate) fn negative_multiply_subtract_accumulate(
+    _context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    d.wrapping_sub(b.wrapping_mul(a))
+}
+
+pub(crate) fn multiply_add(_context: &mut VectorContext, a: u64, b: u64, d: u64) -> u64 {
+    b.wrapping_mul(d).wrapping_add(a)
+}
+
+pub(crate) fn negative_multiply_subtract(
+    _context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    a.wrapping_sub(b.wrapping_mul(d))
+}
+
+pub(crate) fn widening_multiply_accumulate_unsigned(
+    _context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    (b * a).wrapping_add(d)
+}
+
+pub(crate) fn widening_multiply_accumulate(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    (signed(context, b) * signed(context, a)).wrapping_add(d as i64) as u64
+}
+
+pub(crate) fn widening_multiply_accumulate_signed_unsigned(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    (signed(context, b) * a as i64).wrapping_add(d as i64) as u64
+}
+
+pub(crate) fn widening_multiply_accumulate_unsigned_signed(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    (b as i64 * signed(context, a)).wrapping_add(d as i64) as u64
+}
+
+// Integer comparisons
+
+pub(crate) fn equal(_context: &mut VectorContext, a: u64, b: u64) -> bool {
+    a == b
+}
+
+pub(crate) fn not_equal(_context: &mut VectorContext, a: u64, b: u64) -> bool {
+    a != b
+}
+
+pub(crate) fn less_than_unsigned(_context: &mut VectorContext, a: u64, b: u64) -> bool {
+    a < b
+}
+
+pub(crate) fn less_than(context: &mut VectorContext, a: u64, b: u64) -> bool {
+    signed(context, a) < signed(context, b)
+}
+
+pub(crate) fn less_equal_unsigned(_context: &mut VectorContext, a: u64, b: u64) -> bool {
+    a <= b
+}
+
+pub(crate) fn less_equal(context: &mut VectorContext, a: u64, b: u64) -> bool {
+    signed(context, a) <= signed(context, b)
+}
+
+pub(crate) fn greater_than_unsigned(_context: &mut VectorContext, a: u64, b: u64) -> bool {
+    a > b
+}
+
+pub(crate) fn greater_than(context: &mut VectorContext, a: u64, b: u64) -> bool {
+    signed(context, a) > signed(context, b)
+}
+
+// Integer extension
+
+pub(crate) fn zero_extend(_context: &mut VectorContext, a: u64) -> u64 {
+    a
+}
+
+pub(crate) fn sign_extend_vf2(context: &mut VectorContext, a: u64) -> u64 {
+    sign_extend(a, context.sew / 2) as u64
+}
+
+pub(crate) fn sign_extend_vf4(context: &mut VectorContext, a: u64) -> u64 {
+    sign_extend(a, context.sew / 4) as u64
+}
+
+pub(crate) fn sign_extend_vf8(context: &mut VectorContext, a: u64) -> u64 {
+    sign_extend(a, context.sew / 8) as u64
+}
+
+// Fixed-point operations, rounding with `vxrm` and flagging saturation
+
+pub(crate) fn saturating_add_unsigned(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    saturate_unsigned(context, a as i128 + b as i128, context.sew)
+}
+
+pub(crate) fn saturating_add(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let sum = signed(context, a) as i128 + signed(context, b) as i128;
+    saturate_signed(context, sum, context.sew)
+}
+
+pub(crate) fn saturating_sub_unsigned(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    saturate_unsigned(context, a as i128 - b as i128, context.sew)
+}
+
+pub(crate) fn saturating_sub(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let difference = signed(context, a) as i128 - signed(context, b) as i128;
+    saturate_signed(context, difference, context.sew)
+}
+
+pub(crate) fn averaging_add_unsigned(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    rounding_shift(a as i128 + b as i128, 1, context.vxrm) as u64
+}
+
+pub(crate) fn averaging_add(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let sum = signed(context, a) as i128 + signed(context, b) as i128;
+    rounding_shift(sum, 1, context.vxrm) as u64
+}
+
+pub(crate) fn averaging_sub_unsigned(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    rounding_shift(a as i128 - b as i128, 1, context.vxrm) as u64
+}
+
+pub(crate) fn averaging_sub(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let difference = signed(context, a) as i128 - signed(context, b) as i128;
+    rounding_shift(difference, 1, context.vxrm) as u64
+}
+
+pub(crate) fn fractional_multiply(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let product = signed(context, a) as i128 * signed(context, b) as i128;
+    let result = rounding_shift(product, context.sew - 1, context.vxrm);
+    saturate_signed(context, result, context.sew)
+}
+
+pub(crate) fn scaling_shift_right_logical(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    rounding_shift(a as i128, b as usize & (context.sew - 1), context.vxrm) as u64
+}
+
+pub(crate) fn scaling_shift_right_arithmetic(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let shift = b as usize & (context.sew - 1);
+    rounding_shift(signed(context, a) as i128, shift, context.vxrm) as u64
+}
+
+pub(crate) fn narrowing_clip_unsigned(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let shift = b as usize & (context.sew * 2 - 1);
+    let result = rounding_shift(a as i128, shift, context.vxrm);
+    saturate_unsigned(context, result, context.sew)
+}
+
+pub(crate) fn narrowing_clip(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let shift = b as usize & (context.sew * 2 - 1);
+    let result = rounding_shift(signed_wide(context, a) as i128, shift, context.vxrm);
+    saturate_signed(context, result, context.sew)
+}
+
+// Floating-point operations on the element bits of SEW, single or double
+// precision. Widening operations take single precision and produce double.
+
+fn to_f32(value: u64) -> f32 {
+    f32::from_bits(value as u32)
+}
+
+fn to_f64(value: u64) -> f64 {
+    f64::from_bits(value)
+}
+
+/// Converts a single precision element to double precision.
+fn widen(context: &mut VectorContext, value: u64) -> f64 {
+    fpu::to_f64(to_f32(value), &mut context.flags)
+}
+
+fn float_arithmetic(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    single: fn(f32, f32, RoundingMode, &mut u8) -> f32,
+    double: fn(f64, f64, RoundingMode, &mut u8) -> f64,
+) -> u64 {
+    let rm = context.rounding_mode();
+    match context.sew {
+        32 => single(to_f32(a), to_f32(b), rm, &mut context.flags).to_bits() as u64,
+        _ => double(to_f64(a), to_f64(b), rm, &mut context.flags).to_bits(),
+    }
+}
+
+pub(crate) fn float_add(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    float_arithmetic(context, a, b, fpu::add_f32, fpu::add_f64)
+}
+
+pub(crate) fn float_sub(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    float_arithmetic(context, a, b, fpu::sub_f32, fpu::sub_f64)
+}
+
+pub(crate) fn float_reverse_sub(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    float_arithmetic(context, b, a, fpu::sub_f32, fpu::sub_f64)
+}
+
+pub(crate) fn float_multiply(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    float_arithmetic(context, a, b, fpu::mul_f32, fpu::mul_f64)
+}
+
+pub(crate) fn float_divide(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    float_arithmetic(context, a, b, fpu::div_f32, fpu::div_f64)
+}
+
+pub(crate) fn float_reverse_divide(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    float_arithmetic(context, b, a, fpu::div_f32, fpu::div_f64)
+}
+
+pub(crate) fn float_min(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    match context.sew {
+        32 => fpu::min_f32(to_f32(a), to_f32(b), &mut context.flags).to_bits() as u64,
+        _ => fpu::min_f64(to_f64(a), to_f64(b), &mut context.flags).to_bits(),
+    }
+}
+
+pub(crate) fn float_max(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    match context.sew {
+        32 => fpu::max_f32(to_f32(a), to_f32(b), &mut context.flags).to_bits() as u64,
+        _ => fpu::max_f64(to_f64(a), to_f64(b), &mut context.flags).to_bits(),
+    }
+}
+
+pub(crate) fn sign_inject(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let sign = 1 << (context.sew - 1);
+    (a & !sign) | (b & sign)
+}
+
+pub(crate) fn sign_inject_negate(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let sign = 1 << (context.sew - 1);
+    (a & !sign) | (!b & sign)
+}
+
+pub(crate) fn sign_inject_xor(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let sign = 1 << (context.sew - 1);
+    a ^ (b & sign)
+}
+
+pub(crate) fn float_widening_add(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let (a, b) = (widen(context, a), widen(context, b));
+    fpu::add_f64(a, b, context.rounding_mode(), &mut context.flags).to_bits()
+}
+
+pub(crate) fn float_widening_sub(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let (a, b) = (widen(context, a), widen(context, b));
+    fpu::sub_f64(a, b, context.rounding_mode(), &mut context.flags).to_bits()
+}
+
+pub(crate) fn float_wide_add(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let b = widen(context, b);
+    fpu::add_f64(to_f64(a), b, context.rounding_mode(), &mut context.flags).to_bits()
+}
+
+pub(crate) fn float_wide_sub(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let b = widen(context, b);
+    fpu::sub_f64(to_f64(a), b, context.rounding_mode(), &mut context.flags).to_bits()
+}
+
+pub(crate) fn float_widening_multiply(context: &mut VectorContext, a: u64, b: u64) -> u64 {
+    let (a, b) = (widen(context, a), widen(context, b));
+    fpu::mul_f64(a, b, context.rounding_mode(), &mut context.flags).to_bits()
+}
+
+/// Returns `x * y + z` with a single rounding, negated as requested.
+fn float_fused(
+    context: &mut VectorContext,
+    x: u64,
+    y: u64,
+    z: u64,
+    negate_product: bool,
+    negate_addend: bool,
+) -> u64 {
+    let rm = context.rounding_mode();
+    let flags = &mut context.flags;
+    match context.sew {
+        32 => fpu::fused_mul_add_f32(
+            to_f32(x),
+            to_f32(y),
+            to_f32(z),
+            negate_product,
+            negate_addend,
+            rm,
+            flags,
+        )
+        .to_bits() as u64,
+        _ => fpu::fused_mul_add_f64(
+            to_f64(x),
+            to_f64(y),
+            to_f64(z),
+            negate_product,
+            negate_addend,
+            rm,
+            flags,
+        )
+        .to_bits(),
+    }
+}
+
+/// Returns `x * y + z` of single precision `x` and `y` and double
+/// precision `z` with a single rounding, negated as requested.
+fn float_widening_fused(
+    context: &mut VectorContext,
+    x: u64,
+    y: u64,
+    z: u64,
+    negate_product: bool,
+    negate_addend: bool,
+) -> u64 {
+    let (x, y) = (widen(context, x), widen(context, y));
+    let rm = context.rounding_mode();
+    fpu::fused_mul_add_f64(
+        x,
+        y,
+        to_f64(z),
+        negate_product,
+        negate_addend,
+        rm,
+        &mut context.flags,
+    )
+    .to_bits()
+}
+
+pub(crate) fn float_multiply_accumulate(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    float_fused(context, b, a, d, false, false)
+}
+
+pub(crate) fn float_negative_multiply_accumulate(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    float_fused(context, b, a, d, true, true)
+}
+
+pub(crate) fn float_multiply_subtract_accumulate(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    float_fused(context, b, a, d, false, true)
+}
+
+pub(crate) fn float_negative_multiply_subtract_accumulate(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    float_fused(context, b, a, d, true, false)
+}
+
+pub(crate) fn float_multiply_add(context: &mut VectorContext, a: u64, b: u64, d: u64) -> u64 {
+    float_fused(context, b, d, a, false, false)
+}
+
+pub(crate) fn float_negative_multiply_add(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    float_fused(context, b, d, a, true, true)
+}
+
+pub(crate) fn float_multiply_subtract(context: &mut VectorContext, a: u64, b: u64, d: u64) -> u64 {
+    float_fused(context, b, d, a, false, true)
+}
+
+pub(crate) fn float_negative_multiply_subtract(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    float_fused(context, b, d, a, true, false)
+}
+
+pub(crate) fn float_widening_multiply_accumulate(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    float_widening_fused(context, b, a, d, false, false)
+}
+
+pub(crate) fn float_widening_negative_multiply_accumulate(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    float_widening_fused(context, b, a, d, true, true)
+}
+
+pub(crate) fn float_widening_multiply_subtract_accumulate(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    float_widening_fused(context, b, a, d, false, true)
+}
+
+pub(crate) fn float_widening_negative_multiply_subtract_accumulate(
+    context: &mut VectorContext,
+    a: u64,
+    b: u64,
+    d: u64,
+) -> u64 {
+    float_widening_fused(context, b, a, d, true, false)
+}
+
+pub(crate) fn float_equal(context: &mut VectorContext, a: u64, b: u64) -> bool {
+    match context.sew {
+        32 => fpu::eq_f32(to_f32(a), to_f32(b), &mut context.flags),
+        _ => fpu::eq_f64(to_f64(a), to_f64(b), &mut context.flags),
+    }
+}
+
+pub(crate) fn float_not_equal(context: &mut VectorContext, a: u64, b: u64) -> bool {
+    !float_equal(context, a, b)
+}
+
+pub(crate) fn float_less_than(context: &mut VectorContext, a: u64, b: u64) -> bool {
+    match context.sew {
+        32 => fpu::lt_f32(to_f32(a), to_f32(b), &mut context.flags),
+        _ => fpu::lt_f64(to_f64(a), to_f64(b), &mut context.flags),
+    }
+}
+
+pub(crate) fn float_less_equal(context: &mut VectorContext, a: u64, b: u64) -> bool {
+    match context.sew {
+        32 => fpu::le_f32(to_f32(a), to_f32(b), &mut context.flags),
+        _ => fpu::le_f64(to_f64(a), to_f64(b), &mut context.flags),
+    }
+}
+
+pub(crate) fn float_greater_than(context: &mut VectorContext, a: u64, b: u64) -> bool {
+    float_less_than(context, b, a)
+}
+
+pub(crate) fn float_greater_equal(context: &mut VectorContext, a: u64, b: u64) -> bool {
+    float_less_equal(context, b, a)
+}
+
+pub(crate) fn float_sqrt(context: &mut VectorContext, a: u64) -> u64 {
+    let rm = context.rounding_mode();
+    match context.sew {
+        32 => fpu::sqrt_f32(to_f32(a), rm, &mut context.flags).to_bits() as u64,
+        _ => fpu::sqrt_f64(to_f64(a), rm, &mut context.flags).to_bits(),
+    }
+}
+
+pub(crate) fn float_classify(context: &mut VectorContext, a: u64) -> u64 {
+    match context.sew {
+        32 => fpu::classify_f32(to_f32(a)),
+        _ => fpu::classify_f64(to_f64(a)),
+    }
+}
+
+/// 7-bit reciprocal square root estimates indexed by the exponent LSB and
+/// the upper 6 bits of the significand
+const RECIPROCAL_SQRT_TABLE: [u8; 128] = [
+    52, 51, 50, 48, 47, 46, 44, 43, 42, 41, 40, 39, 38, 36, 35, 34, 33, 32, 31, 30, 30, 29, 28, 27,
+    26, 25, 24, 23, 23, 22, 21, 20, 19, 19, 18, 17, 16, 16, 15, 14, 14, 13, 12, 12, 11, 10, 10, 9,
+    9, 8, 7, 7, 6, 6, 5, 4, 4, 3, 3, 2, 2, 1, 1, 0, 127, 125, 123, 121, 119, 118, 116, 114, 113,
+    111, 109, 108, 106, 105, 103, 102, 100, 99, 97, 96, 95, 93, 92, 91, 90, 88, 87, 86, 85, 84, 83,
+    82, 80, 79, 78, 77, 76, 75, 74, 73, 72, 71, 70, 70, 69, 68, 67, 66, 65, 64, 63, 63, 62, 61, 60,
+    59, 59, 58, 57, 56, 56, 55, 54, 53,
+];
+
+/// 7-bit reciprocal estimates indexed by the upper 7 bits of the significand
+const RECIPROCAL_TABLE: [u8; 128] = [
+    127, 125, 123, 121, 119, 117, 116, 114, 112, 110, 109, 107, 105, 104, 102, 100, 99, 97, 96, 94,
+    93, 91, 90, 88, 87, 85, 84, 83, 81, 80, 79, 77, 76, 75, 74, 72, 71, 70, 69, 68, 66, 65, 64, 63,
+    62, 61, 60, 59, 58, 57, 56, 55, 54, 53, 52, 51, 50, 49, 48, 47, 46, 45, 44, 43, 42, 41, 40, 40,
+    39, 38, 37, 36, 35, 35, 34, 33, 32, 31, 31, 30, 29, 28, 28, 27, 26, 25, 25, 24, 23, 23, 22, 21,
+    21, 20, 19, 19, 18, 17, 17, 16, 15, 15, 14, 14, 13, 12, 12, 11, 11, 10, 9, 9, 8, 8, 7, 7, 6, 5,
+    5, 4, 4, 3, 3, 2, 2, 1, 1, 0,
+];
+
+/// Splits the element bits of SEW into sign, exponent and significand.
+/// Subnormal significands are normalized, leaving the exponent zero
+/// or negative. Returns the exponent and the significand widths too.
+fn unpack_float(context: &VectorContext, value: u64) -> (u64, i64, u64, u32, u32) {
+    let (exponent_bits, significand_bits) = match context.sew {
+        32 => (8, 23),
+        _ => (11, 52),
+    };
+    let sign = value >> (context.sew - 1);
+    let mut exponent = ((value >> significand_bits) & ((1 << exponent_bits) - 1)) as i64;
+    let mut significand = value & ((1 << significand_bits) - 1);
+    if exponent == 0 {
+        while significand & (1 << (significand_bits - 1)) == 0 {
+            exponent -= 1;
+            significand <<= 1;
+        }
+        significand = (significand << 1) & ((1 << significand_bits) - 1);
+    }
+    (sign, exponent, significand, exponent_bits, significand_bits)
+}
+
+fn canonical_nan(context: &VectorContext) -> u64 {
+    match context.sew {
+        32 => fpu::CANONICAL_NAN_F32 as u64,
+        _ => fpu::CANONICAL_NAN_F64,
+    }
+}
+
+/// Returns the element bits of infinity with the sign.
+fn infinity(context: &VectorContext, sign: u64) -> u64 {
+    match context.sew {
+        32 => (sign << 31) | 0x7f800000,
+        _ => (sign << 63) | 0x7ff0000000000000,
+    }
+}
+
+fn is_signaling_nan(context: &VectorContext, value: u64) -> bool {
+    match context.sew {
+        32 => fpu::is_signaling_f32(to_f32(value)),
+        _ => fpu::is_signaling_f64(to_f64(value)),
+    }
+}
+
+pub(crate) fn float_reciprocal_sqrt_estimate(context: &mut VectorContext, a: u64) -> u64 {
+    let class = float_classify(context, a);
+    match class {
+        // Negative values
+        0x01..=0x04 => {
+            context.flags |= fpu::FLAG_NV;
+            return canonical_nan(context);
+        }
+        // Zeros
+        0x08 | 0x10 => {
+            context.flags |= fpu::FLAG_DZ;
+            return infinity(context, (class == 0x08) as u64);
+        }
+        // Positive infinity
+        0x80 => return 0,
+        // NaNs
+        0x100 | 0x200 => {
+            if is_signaling_nan(context, a) {
+                context.flags |= fpu::FLAG_NV;
+            }
+            return canonical_nan(context);
+        }
+        _ => {}
+    };
+    let (sign, exponent, significand, exponent_bits, significand_bits) = unpack_float(context, a);
+    let bias = (1 << (exponent_bits - 1)) - 1;
+    let index = (((exponent & 1) as u64) << 6) | (significand >> (significand_bits - 6));
+    let output_significand =
+        (RECIPROCAL_SQRT_TABLE[index as usize] as u64) << (significand_bits - 7);
+    let output_exponent = ((3 * bias - 1 - exponent) / 2) as u64;
+    (sign << (context.sew - 1)) | (output_exponent << significand_bits) | output_significand
+}
+
+pub(crate) fn float_reciprocal_estimate(context: &mut VectorContext, a: u64) -> u64 {
+    let class = float_classify(context, a);
+    match class {
+        // Infinities
+        0x01 | 0x80 => return (class == 0x01) as u64 * (1 << (context.sew - 1)),
+        // Zeros
+        0x08 | 0x10 => {
+            context.flags |= fpu::FLAG_DZ;
+            return infinity(context, (class == 0x08) as u64);
+        }
+        // NaNs
+        0x100 | 0x200 => {
+            if is_signaling_nan(context, a) {
+                context.flags |= fpu::FLAG_NV;
+            }
+            return canonical_nan(context);
+        }
+        _ => {}
+    };
+    let (sign, exponent, significand, exponent_bits, significand_bits) = unpack_float(context, a);
+    let bias = (1 << (exponent_bits - 1)) - 1;
+    // Subnormal inputs too small overflow to infinity or the largest
+    // finite value depending on the rounding direction
+    if exponent < -1 {
+        context.flags |= fpu::FLAG_OF | fpu::FLAG_NX;
+        let toward_zero = match context.rounding_mode() {
+            RoundingMode::TowardZero => true,
+            RoundingMode::Down => sign == 0,
+            RoundingMode::Up => sign == 1,
+            _ => false,
+        };
+        return infinity(context, sign) - toward_zero as u64;
+    }
+    let index = significand >> (significand_bits - 7);
+    let mut output_significand =
+        (RECIPROCAL_TABLE[index as usize] as u64) << (significand_bits - 7);
+    let mut output_exponent = 2 * bias - 1 - exponent;
+    // Subnormal outputs
+    if output_exponent <= 0 {
+        output_significand = (output_significand >> 1) | (1 << (significand_bits - 1));
+        if output_exponent == -1 {
+            output_significand >>= 1;
+        }
+        output_exponent = 0;
+    }
+    (sign << (context.sew - 1))
+        | ((output_exponent as u64) << significand_bits)
+        | output_significand
+}
+
+// Conversions between floating-point and integer elements. The names tell
+// the destination and the source types, SEW wide unless widening or
+// narrowing.
+
+/// Reads single or double precision element as double precision.
+/// Single precision values are converted exactly.
+fn read_float(value: u64, eew: usize) -> f64 {
+    match eew {
+        32 => to_f32(value) as f64,
+        _ => to_f64(value),
+    }
+}
+
+/// Converts a float to an integer, saturating and raising the invalid
+/// flag if it's out of range. NaN converts to the maximum value.
+fn float_to_integer(
+    context: &mut VectorContext,
+    value: u64,
+    float_eew: usize,
+    integer_eew: usize,
+    is_signed: bool,
+    rm: RoundingMode,
+) -> u64 {
+    let value = read_float(value, float_eew);
+    let (min, max) = match is_signed {
+        true => (
+            -(1i128 << (integer_eew - 1)),
+            (1i128 << (integer_eew - 1)) - 1,
+        ),
+        false => (0, (1i128 << integer_eew) - 1),
+    };
+    fpu::to_integer(value, min, max, rm, &mut context.flags) as u64
+}
+
+fn integer_to_float(
+    context: &mut VectorContext,
+    value: u64,
+    integer_eew: usize,
+    is_signed: bool,
+    float_eew: usize,
+) -> u64 {
+    let value = match is_signed {
+        true => sign_extend(value, integer_eew) as i128,
+        false => truncate(value, integer_eew) as i128,
+    };
+    let rm = context.rounding_mode();
+    match float_eew {
+        32 => fpu::from_integer_f32(value, rm, &mut context.flags).to_bits() as u64,
+        _ => fpu::from_integer_f64(value, rm, &mut context.flags).to_bits(),
+    }
+}
+
+pub(crate) fn unsigned_from_float(context: &mut VectorContext, a: u64) -> u64 {
+    let rm = context.rounding_mode();
+    float_to_integer(context, a, context.sew, context.sew, false, rm)
+}
+
+pub(crate) fn signed_from_float(context: &mut VectorContext, a: u64) -> u64 {
+    let rm = context.rounding_mode();
+    float_to_integer(context, a, context.sew, context.sew, true, rm)
+}
+
+pub(crate) fn unsigned_from_float_rtz(context: &mut VectorContext, a: u64) -> u64 {
+    float_to_integer(
+        context,
+        a,
+        context.sew,
+        context.sew,
+        false,
+        RoundingMode::TowardZero,
+    )
+}
+
+pub(crate) fn signed_from_float_rtz(context: &mut VectorContext, a: u64) -> u64 {
+    float_to_integer(
+        context,
+        a,
+        context.sew,
+        context.sew,
+        true,
+        RoundingMode::TowardZero,
+    )
+}
+
+pub(crate) fn float_from_unsigned(context: &mut VectorContext, a: u64) -> u64 {
+    integer_to_float(context, a, context.sew, false, context.sew)
+}
+
+pub(crate) fn float_from_signed(context: &mut VectorContext, a: u64) -> u64 {
+    integer_to_float(context, a, context.sew, true, context.sew)
+}
+
+pub(crate) fn widening_unsigned_from_float(context: &mut VectorContext, a: u64) -> u64 {
+    let rm = context.rounding_mode();
+    float_to_integer(context, a, context.sew, context.sew * 2, false, rm)
+}
+
+pub(crate) fn widening_signed_from_float(context: &mut VectorContext, a: u64) -> u64 {
+    let rm = context.rounding_mode();
+    float_to_integer(context, a, context.sew, context.sew * 2, true, rm)
+}
+
+pub(crate) fn widening_unsigned_from_float_rtz(context: &mut VectorContext, a: u64) -> u64 {
+    let rm = RoundingMode::TowardZero;
+    float_to_integer(context, a, context.sew, context.sew * 2, false, rm)
+}
+
+pub(crate) fn widening_signed_from_float_rtz(context: &mut VectorContext, a: u64) -> u64 {
+    let rm = RoundingMode::TowardZero;
+    float_to_integer(context, a, context.sew, context.sew * 2, true, rm)
+}
+
+pub(crate) fn widening_float_from_unsigned(context: &mut VectorContext, a: u64) -> u64 {
+    integer_to_float(context, a, context.sew, false, context.sew * 2)
+}
+
+pub(crate) fn widening_float_from_signed(context: &mut VectorContext, a: u64) -> u64 {
+    integer_to_float(context, a, context.sew, true, context.sew * 2)
+}
+
+pub(crate) fn widening_float_from_float(context: &mut VectorContext, a: u64) -> u64 {
+    widen(context, a).to_bits()
+}
+
+pub(crate) fn narrowing_unsigned_from_float(context: &mut VectorContext, a: u64) -> u64 {
+    let rm = context.rounding_mode();
+    float_to_integer(context, a, context.sew * 2, context.sew, false, rm)
+}
+
+pub(crate) fn narrowing_signed_from_float(context: &mut VectorContext, a: u64) -> u64 {
+    let rm = context.rounding_mode();
+    float_to_integer(context, a, context.sew * 2, context.sew, true, rm)
+}
+
+pub(crate) fn narrowing_unsigned_from_float_rtz(context: &mut VectorContext, a: u64) -> u64 {
+    let rm = RoundingMode::TowardZero;
+    float_to_integer(context, a, context.sew * 2, context.sew, false, rm)
+}
+
+pub(crate) fn narrowing_signed_from_float_rtz(context: &mut VectorContext, a: u64) -> u64 {
+    let rm = RoundingMode::TowardZero;
+    float_to_integer(context, a, context.sew * 2, context.sew, true, rm)
+}
+
+pub(crate) fn narrowing_float_from_unsigned(context: &mut VectorContext, a: u64) -> u64 {
+    integer_to_float(context, a, context.sew * 2, false, context.sew)
+}
+
+pub(crate) fn narrowing_float_from_signed(context: &mut VectorContext, a: u64) -> u64 {
+    integer_to_float(context, a, context.sew * 2, true, context.sew)
+}
+
+pub(crate) fn narrowing_float_from_float(context: &mut VectorContext, a: u64) -> u64 {
+    let rm = context.rounding_mode();
+    fpu::to_f32(to_f64(a), rm, &mut context.flags).to_bits() as u64
+}
+
+/// `VFNCVT.ROD.F.F.W`, rounds toward odd. Truncates and sets the least
+/// significant bit if the result is inexact.
+pub(crate) fn narrowing_float_from_float_rod(context: &mut VectorContext, a: u64) -> u64 {
+    let mut flags = 0;
+    let result = fpu::to_f32(to_f64(a), RoundingMode::TowardZero, &mut flags);
+    context.flags |= flags;
+    match (flags & fpu::FLAG_NX) != 0 && result.is_finite() {
+        true => (result.to_bits() | 1) as u64,
+        false => result.to_bits() as u64,
+    }
+}
@@ -1,7 +1,8 @@
 use risc_v::terminal::Terminal;
-use risc_v::Emulator;
+use risc_v::{Emulator, StopReason};
 
 use std::num::NonZeroU8;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::{fs, thread};
@@ -70,6 +71,8 @@ impl Terminal for MultiThreadedTerminal {
 const CTRL_C: &str = "\u{3}";
 const ENTER: &str = "\u{D}";
 
+const RUN_SLICE_CYCLES: u64 = 0x100000;
+
 #[test]
 fn main() {
     let elf_contents = fs::read("resources/fw_payload.elf").unwrap();
@@ -78,18 +81,26 @@ fn main() {
     let input_buffer = Arc::new(Mutex::new(Vec::new()));
     let output_buffer = Arc::new(Mutex::new(Vec::new()));
 
+    let stop = Arc::new(AtomicBool::new(false));
+
     let local_input_buffer = input_buffer.clone();
     let local_output_buffer = output_buffer.clone();
-    std::thread::spawn(move || {
+    let local_stop = stop.clone();
+    let emulator_thread = std::thread::spawn(move || {
         let mut emulator = Emulator::new(Box::new(MultiThreadedTerminal {
             input_buffer: local_input_buffer,
             output_buffer: local_output_buffer,
         }));
-        emulator.setup_program(elf_contents);
+        emulator.setup_program(elf_contents).unwrap();
 
-        emulator.setup_filesystem(fs_contents);
+        emulator.setup_filesystem(fs_contents).unwrap();
 
-        emulator.run();
+        while !local_stop.load(Ordering::Relaxed) {
+            match emulator.run_for(RUN_SLICE_CYCLES) {
+                StopReason::CycleBudgetExhausted => {}
+                reason => panic!("Emulator stopped: {reason:?}"),
+            }
+        }
     });
 
     wait_until(
@@ -158,4 +169,7 @@ fn main() {
             50,
         );
     }
+
+    stop.store(true, Ordering::Relaxed);
+    emulator_thread.join().unwrap();
 }